@@ -20,6 +20,9 @@
 
 static __thread tokio_bridge_ctx_t *tls_ctx = NULL;
 
+/* Set once graceful shutdown starts; read from every worker thread. */
+static int g_draining = 0;
+
 /* ============================================================================
  * Context lifecycle
  * ============================================================================ */
@@ -137,6 +140,22 @@ int tokio_bridge_get_finished_response_code(void)
     return tls_ctx->response_code;
 }
 
+void tokio_bridge_set_response_code(int code)
+{
+    if (tls_ctx == NULL) {
+        return;
+    }
+    tls_ctx->status_code = code;
+}
+
+int tokio_bridge_get_response_code(void)
+{
+    if (tls_ctx == NULL) {
+        return 0;
+    }
+    return tls_ctx->status_code;
+}
+
 /* ============================================================================
  * Finish Request Callback API (streaming early response)
  * ============================================================================ */
@@ -539,3 +558,33 @@ int tokio_bridge_are_headers_sent(void)
     }
     return tls_ctx->headers_sent;
 }
+
+void tokio_bridge_mark_stream_aborted(void)
+{
+    if (tls_ctx == NULL) {
+        return;
+    }
+    tls_ctx->stream_aborted = 1;
+}
+
+int tokio_bridge_is_stream_aborted(void)
+{
+    if (tls_ctx == NULL) {
+        return 0;
+    }
+    return tls_ctx->stream_aborted;
+}
+
+/* ============================================================================
+ * Drain API
+ * ============================================================================ */
+
+void tokio_bridge_set_draining(int draining)
+{
+    __atomic_store_n(&g_draining, draining ? 1 : 0, __ATOMIC_RELEASE);
+}
+
+int tokio_bridge_is_draining(void)
+{
+    return __atomic_load_n(&g_draining, __ATOMIC_ACQUIRE);
+}
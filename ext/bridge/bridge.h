@@ -123,6 +123,9 @@ typedef struct tokio_bridge_ctx {
     int finished_header_count;  /* Header count at finish time */
     int response_code;
 
+    /* Status set by tokio_http_response_code() or header() (0 = not set) */
+    int status_code;
+
     /* Captured headers storage (shared between Rust SAPI and PHP) */
     tokio_bridge_header_t headers[TOKIO_BRIDGE_MAX_HEADERS];
     int header_count;
@@ -149,6 +152,7 @@ typedef struct tokio_bridge_ctx {
     /* Chunked transfer encoding mode (set when flush() called before output) */
     int chunked_mode;                               /* 1 = chunked mode enabled */
     int headers_sent;                               /* 1 = headers already sent to client */
+    int stream_aborted;                             /* 1 = client stopped reading (STREAM_WRITE_TIMEOUT) */
 
 } tokio_bridge_ctx_t;
 
@@ -233,6 +237,22 @@ int tokio_bridge_get_finished_header_count(void);
  */
 int tokio_bridge_get_finished_response_code(void);
 
+/**
+ * Set the response status for the current request.
+ * Called from PHP's tokio_http_response_code() and from the SAPI header
+ * handler, so the latest code wins whichever way it was set.
+ *
+ * @param code HTTP status code (100-599)
+ */
+void tokio_bridge_set_response_code(int code);
+
+/**
+ * Get the response status set for the current request.
+ *
+ * @return Status code, or 0 if none was set
+ */
+int tokio_bridge_get_response_code(void);
+
 /**
  * Set the finish request callback.
  * Called from Rust before PHP execution to enable streaming early response.
@@ -474,6 +494,39 @@ void tokio_bridge_mark_headers_sent(void);
  */
 int tokio_bridge_are_headers_sent(void);
 
+/**
+ * Mark the stream as aborted because the client stopped reading.
+ * Called from Rust when a chunk could not be sent within STREAM_WRITE_TIMEOUT.
+ */
+void tokio_bridge_mark_stream_aborted(void);
+
+/**
+ * Check if the stream was aborted.
+ *
+ * @return 1 if aborted, 0 otherwise
+ */
+int tokio_bridge_is_stream_aborted(void);
+
+/* ============================================================================
+ * Drain API (process-wide, not per request)
+ * ============================================================================ */
+
+/**
+ * Set whether the server is draining for shutdown.
+ * Called from Rust when graceful shutdown starts.
+ *
+ * @param draining 1 if draining, 0 otherwise
+ */
+void tokio_bridge_set_draining(int draining);
+
+/**
+ * Check if the server is draining.
+ * Called from PHP's tokio_is_draining() function.
+ *
+ * @return 1 if draining, 0 otherwise
+ */
+int tokio_bridge_is_draining(void);
+
 #ifdef __cplusplus
 }
 #endif
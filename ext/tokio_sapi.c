@@ -972,6 +972,19 @@ PHP_FUNCTION(tokio_request_heartbeat)
     RETURN_BOOL(result != 0);
 }
 
+/* tokio_is_draining(): bool - whether the server is shutting down
+ *
+ * Becomes true once graceful shutdown starts (SIGTERM/SIGINT) and stays true.
+ * Long-running scripts, e.g. background work after tokio_finish_request(),
+ * can poll it to checkpoint and return before DRAIN_TIMEOUT_SECS runs out.
+ */
+PHP_FUNCTION(tokio_is_draining)
+{
+    ZEND_PARSE_PARAMETERS_NONE();
+
+    RETURN_BOOL(tokio_bridge_is_draining() != 0);
+}
+
 /* ============================================================================
  * Helper functions for streaming early response
  * ============================================================================ */
@@ -1167,6 +1180,14 @@ void tokio_sapi_flush(void *server_context)
         return;
     }
 
+    /* The client stopped reading (STREAM_WRITE_TIMEOUT). Treat it like a
+     * closed connection: connection_aborted() becomes true and, unless
+     * ignore_user_abort is set, the script ends here and frees the worker. */
+    if (tokio_bridge_is_stream_aborted()) {
+        php_handle_aborted_connection();
+        return;
+    }
+
     flush_in_progress = 1;
 
     /* Enable chunked transfer encoding mode for any Content-Type.
@@ -1288,6 +1309,129 @@ PHP_FUNCTION(tokio_is_streaming)
     RETURN_BOOL(tokio_bridge_is_streaming());
 }
 
+/* Whether a header was set for this request (case-insensitive name) */
+static int bridge_has_header(const char *name)
+{
+    int count = tokio_bridge_get_header_count();
+    for (int i = 0; i < count; i++) {
+        const char *hname = NULL;
+        if (tokio_bridge_get_header(i, &hname, NULL) && hname && strcasecmp(hname, name) == 0) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+/* Set a header through PHP's SAPI, so the header handler sees it */
+static void sse_set_header(const char *line, int replace)
+{
+    sapi_header_line ctr = {0};
+    ctr.line = line;
+    ctr.line_len = strlen(line);
+    sapi_header_op(replace ? SAPI_HEADER_REPLACE : SAPI_HEADER_ADD, &ctr);
+}
+
+/* Append an SSE field, one line per line of value (CR, LF or CRLF separated) */
+static void sse_append_lines(smart_str *buf, const char *field, const char *value, size_t len)
+{
+    size_t start = 0;
+    for (size_t i = 0; i <= len; i++) {
+        if (i < len && value[i] != '\r' && value[i] != '\n') {
+            continue;
+        }
+        smart_str_appends(buf, field);
+        smart_str_appendl(buf, ": ", 2);
+        smart_str_appendl(buf, value + start, i - start);
+        smart_str_appendc(buf, '\n');
+        if (i + 1 < len && value[i] == '\r' && value[i + 1] == '\n') {
+            i++;
+        }
+        start = i + 1;
+    }
+}
+
+/* tokio_sse_event(string $data, ?string $event = null, ?string $id = null, ?int $retry = null): bool
+ *
+ * Writes one Server-Sent Events message and flushes it to the client.
+ * Each line of $data becomes its own "data:" line, so multi-line payloads
+ * are framed correctly. On the first call, before headers are sent, sets
+ * Content-Type: text/event-stream (which switches the request to streaming
+ * through the header handler) and Cache-Control: no-cache unless the script
+ * set its own.
+ *
+ * Returns false if $event or $id contain a line break or $retry is
+ * negative (with a warning), after tokio_finish_request(), or if the
+ * request could not switch to streaming; the event is then only buffered.
+ *
+ * Usage:
+ *   foreach ($updates as $i => $update) {
+ *       tokio_sse_event(json_encode($update), 'update', (string)$i);
+ *   }
+ */
+PHP_FUNCTION(tokio_sse_event)
+{
+    zend_string *data;
+    zend_string *event = NULL;
+    zend_string *id = NULL;
+    zend_long retry = 0;
+    bool retry_is_null = 1;
+
+    ZEND_PARSE_PARAMETERS_START(1, 4)
+        Z_PARAM_STR(data)
+        Z_PARAM_OPTIONAL
+        Z_PARAM_STR_OR_NULL(event)
+        Z_PARAM_STR_OR_NULL(id)
+        Z_PARAM_LONG_OR_NULL(retry, retry_is_null)
+    ZEND_PARSE_PARAMETERS_END();
+
+    if (event && strpbrk(ZSTR_VAL(event), "\r\n") != NULL) {
+        php_error_docref(NULL, E_WARNING, "Event name must not contain line breaks");
+        RETURN_FALSE;
+    }
+    if (id && (strpbrk(ZSTR_VAL(id), "\r\n") != NULL || memchr(ZSTR_VAL(id), '\0', ZSTR_LEN(id)))) {
+        php_error_docref(NULL, E_WARNING, "Event ID must not contain line breaks or NUL");
+        RETURN_FALSE;
+    }
+    if (!retry_is_null && retry < 0) {
+        php_error_docref(NULL, E_WARNING, "Retry must be non-negative, got " ZEND_LONG_FMT, retry);
+        RETURN_FALSE;
+    }
+
+    if (tokio_bridge_is_finished()) {
+        RETURN_FALSE;
+    }
+
+    if (!tokio_bridge_are_headers_sent() && !SG(headers_sent)) {
+        sse_set_header("Content-Type: text/event-stream", 1);
+        if (!bridge_has_header("Cache-Control")) {
+            sse_set_header("Cache-Control: no-cache", 1);
+        }
+    }
+
+    smart_str buf = {0};
+    if (event) {
+        sse_append_lines(&buf, "event", ZSTR_VAL(event), ZSTR_LEN(event));
+    }
+    if (id) {
+        sse_append_lines(&buf, "id", ZSTR_VAL(id), ZSTR_LEN(id));
+    }
+    if (!retry_is_null) {
+        smart_str_appends(&buf, "retry: ");
+        smart_str_append_long(&buf, retry);
+        smart_str_appendc(&buf, '\n');
+    }
+    sse_append_lines(&buf, "data", ZSTR_VAL(data), ZSTR_LEN(data));
+    smart_str_appendc(&buf, '\n');
+    smart_str_0(&buf);
+
+    /* Goes through ub_write, which sends headers first and then the chunk */
+    php_output_write(ZSTR_VAL(buf.s), ZSTR_LEN(buf.s));
+    smart_str_free(&buf);
+    sapi_flush();
+
+    RETURN_BOOL(tokio_bridge_is_streaming());
+}
+
 /* tokio_finish_request(): bool - send response to client, continue script execution
  *
  * Analog of fastcgi_finish_request(). After calling:
@@ -1386,6 +1530,7 @@ PHP_FUNCTION(tokio_send_headers)
 
     /* Set HTTP status code via PHP's SAPI */
     SG(sapi_headers).http_response_code = (int)status;
+    tokio_bridge_set_response_code((int)status);
 
     /* Disable all output buffering */
     while (php_output_get_level() > 0) {
@@ -1406,6 +1551,54 @@ PHP_FUNCTION(tokio_send_headers)
     RETURN_TRUE;
 }
 
+/* ============================================================================
+ * tokio_http_response_code() - Set the response status
+ * ============================================================================ */
+
+/**
+ * tokio_http_response_code(int $code): int
+ *
+ * Sets the HTTP status of the response and returns the previous one.
+ * PHP's http_response_code() only updates sapi_headers, which the server
+ * reads when a header() call or the end of the script passes through the
+ * SAPI; in streaming mode the headers can go out on the first output before
+ * that, with the old status. This also records the code in the bridge,
+ * which every path that sends headers consults.
+ *
+ * Codes outside 100-599 and calls after headers were sent emit a warning
+ * and leave the status unchanged.
+ */
+PHP_FUNCTION(tokio_http_response_code)
+{
+    zend_long code;
+
+    ZEND_PARSE_PARAMETERS_START(1, 1)
+        Z_PARAM_LONG(code)
+    ZEND_PARSE_PARAMETERS_END();
+
+    int previous = tokio_bridge_get_response_code();
+    if (previous == 0) {
+        previous = SG(sapi_headers).http_response_code > 0
+            ? SG(sapi_headers).http_response_code
+            : 200;
+    }
+
+    if (code < 100 || code > 599) {
+        php_error_docref(NULL, E_WARNING, "Invalid HTTP status code: " ZEND_LONG_FMT, code);
+        RETURN_LONG(previous);
+    }
+
+    if (tokio_bridge_are_headers_sent()) {
+        php_error_docref(NULL, E_WARNING, "Cannot change response code - headers already sent");
+        RETURN_LONG(previous);
+    }
+
+    SG(sapi_headers).http_response_code = (int)code;
+    tokio_bridge_set_response_code((int)code);
+
+    RETURN_LONG(previous);
+}
+
 /* ============================================================================
  * Finish Request C API (called from Rust)
  * Now delegates to tokio_bridge shared library.
@@ -1457,6 +1650,9 @@ ZEND_BEGIN_ARG_WITH_RETURN_TYPE_INFO_EX(arginfo_tokio_request_heartbeat, 0, 0, _
     ZEND_ARG_TYPE_INFO_WITH_DEFAULT_VALUE(0, time, IS_LONG, 0, "10")
 ZEND_END_ARG_INFO()
 
+ZEND_BEGIN_ARG_WITH_RETURN_TYPE_INFO_EX(arginfo_tokio_is_draining, 0, 0, _IS_BOOL, 0)
+ZEND_END_ARG_INFO()
+
 ZEND_BEGIN_ARG_WITH_RETURN_TYPE_INFO_EX(arginfo_tokio_finish_request, 0, 0, _IS_BOOL, 0)
 ZEND_END_ARG_INFO()
 
@@ -1470,6 +1666,17 @@ ZEND_BEGIN_ARG_WITH_RETURN_TYPE_INFO_EX(arginfo_tokio_send_headers, 0, 0, _IS_BO
     ZEND_ARG_TYPE_INFO_WITH_DEFAULT_VALUE(0, status, IS_LONG, 0, "200")
 ZEND_END_ARG_INFO()
 
+ZEND_BEGIN_ARG_WITH_RETURN_TYPE_INFO_EX(arginfo_tokio_http_response_code, 0, 1, IS_LONG, 0)
+    ZEND_ARG_TYPE_INFO(0, code, IS_LONG, 0)
+ZEND_END_ARG_INFO()
+
+ZEND_BEGIN_ARG_WITH_RETURN_TYPE_INFO_EX(arginfo_tokio_sse_event, 0, 1, _IS_BOOL, 0)
+    ZEND_ARG_TYPE_INFO(0, data, IS_STRING, 0)
+    ZEND_ARG_TYPE_INFO_WITH_DEFAULT_VALUE(0, event, IS_STRING, 1, "null")
+    ZEND_ARG_TYPE_INFO_WITH_DEFAULT_VALUE(0, id, IS_STRING, 1, "null")
+    ZEND_ARG_TYPE_INFO_WITH_DEFAULT_VALUE(0, retry, IS_LONG, 1, "null")
+ZEND_END_ARG_INFO()
+
 /* ============================================================================
  * PHP Extension registration
  * ============================================================================ */
@@ -1480,10 +1687,13 @@ static const zend_function_entry tokio_sapi_functions[] = {
     PHP_FE(tokio_server_info, arginfo_tokio_server_info)
     PHP_FE(tokio_async_call, arginfo_tokio_async_call)
     PHP_FE(tokio_request_heartbeat, arginfo_tokio_request_heartbeat)
+    PHP_FE(tokio_is_draining, arginfo_tokio_is_draining)
     PHP_FE(tokio_finish_request, arginfo_tokio_finish_request)
     PHP_FE(tokio_stream_flush, arginfo_tokio_stream_flush)
     PHP_FE(tokio_is_streaming, arginfo_tokio_is_streaming)
     PHP_FE(tokio_send_headers, arginfo_tokio_send_headers)
+    PHP_FE(tokio_http_response_code, arginfo_tokio_http_response_code)
+    PHP_FE(tokio_sse_event, arginfo_tokio_sse_event)
     PHP_FE_END
 };
 
@@ -94,6 +94,10 @@ extern "C" {
     fn tokio_bridge_get_finished_header_count() -> c_int;
     fn tokio_bridge_get_finished_response_code() -> c_int;
 
+    // Response status
+    fn tokio_bridge_set_response_code(code: c_int);
+    fn tokio_bridge_get_response_code() -> c_int;
+
     // Heartbeat
     fn tokio_bridge_set_heartbeat(ctx: *mut c_void, max_secs: u64, callback: HeartbeatCallback);
 
@@ -113,6 +117,10 @@ extern "C" {
     // Stream finish (new streaming architecture)
     fn tokio_bridge_set_stream_finish_callback(ctx: *mut c_void, callback: StreamFinishCallback);
     fn tokio_bridge_trigger_stream_finish() -> c_int;
+
+    // Drain state (process-wide)
+    fn tokio_bridge_set_draining(draining: c_int);
+    fn tokio_bridge_is_draining() -> c_int;
 }
 
 // =============================================================================
@@ -193,6 +201,19 @@ pub fn get_finished_response_code() -> u16 {
     unsafe { tokio_bridge_get_finished_response_code() as u16 }
 }
 
+/// Record the response status for the current request.
+#[inline]
+pub fn set_response_code(code: u16) {
+    unsafe { tokio_bridge_set_response_code(c_int::from(code)) }
+}
+
+/// Status set via `tokio_http_response_code()` or `header()`, if any.
+#[inline]
+pub fn get_response_code() -> Option<u16> {
+    let code = unsafe { tokio_bridge_get_response_code() };
+    (code > 0).then_some(code as u16)
+}
+
 /// Set the heartbeat callback.
 ///
 /// The callback will be invoked when PHP calls `tokio_request_heartbeat()`.
@@ -302,6 +323,24 @@ pub fn trigger_stream_finish() -> bool {
     unsafe { tokio_bridge_trigger_stream_finish() != 0 }
 }
 
+// =============================================================================
+// Drain API
+// =============================================================================
+
+/// Mark the server as draining, for PHP's `tokio_is_draining()`.
+///
+/// Unlike the rest of the bridge this is process-wide, not per thread.
+#[inline]
+pub fn set_draining(draining: bool) {
+    unsafe { tokio_bridge_set_draining(c_int::from(draining)) }
+}
+
+/// Whether `set_draining(true)` was called.
+#[inline]
+pub fn is_draining() -> bool {
+    unsafe { tokio_bridge_is_draining() != 0 }
+}
+
 // =============================================================================
 // Finish Channel (Streaming Early Response)
 // =============================================================================
@@ -1,8 +1,13 @@
 //! Executor configuration.
 
-use super::parse::env_or;
+use std::time::Duration;
+
+use super::parse::{env_bool, env_opt, env_or, parse_duration};
+use super::sizing::{CgroupLimits, Sizing};
 use super::ConfigError;
-use std::num::NonZeroUsize;
+use crate::executor::{
+    DEFAULT_PRIORITY_CAPACITY, DEFAULT_STREAM_BUFFER_SIZE, DEFAULT_STREAM_WRITE_TIMEOUT,
+};
 
 /// Executor type selection.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
@@ -14,6 +19,20 @@ pub enum ExecutorType {
     /// Ext executor using php_execute_script with FFI superglobals (default, recommended).
     #[default]
     Ext,
+    /// FastCGI executor forwarding to an external PHP-FPM pool.
+    FastCgi,
+}
+
+impl ExecutorType {
+    /// Name reported by the matching executor's `ScriptExecutor::name()`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ExecutorType::Stub => "stub",
+            ExecutorType::Php => "php",
+            ExecutorType::Ext => "ext",
+            ExecutorType::FastCgi => "fastcgi",
+        }
+    }
 }
 
 /// Executor configuration loaded from environment.
@@ -23,102 +42,234 @@ pub enum ExecutorType {
 pub struct ExecutorConfig {
     /// Executor type to use.
     pub executor_type: ExecutorType,
-    /// Resolved worker count (never zero).
-    worker_count: NonZeroUsize,
-    /// Resolved queue capacity (never zero).
-    queue_capacity: NonZeroUsize,
+    /// Resolved worker count and queue capacity.
+    sizing: Sizing,
+    /// Log the sizing decision and export it on /metrics (SIZING_REPORT).
+    pub sizing_report: bool,
+    /// Queue slots reserved for high-priority requests (PRIORITY_QUEUE_CAPACITY).
+    pub priority_queue_capacity: usize,
+    /// Response chunks buffered ahead of a streaming client (STREAM_BUFFER_SIZE).
+    pub stream_buffer_size: usize,
+    /// How long a stream may wait for its client to read (STREAM_WRITE_TIMEOUT, None = forever).
+    pub stream_write_timeout: Option<Duration>,
+    /// PHP ini overrides applied at SAPI startup (take precedence over php.ini).
+    pub php_ini: Vec<(String, String)>,
+    /// PHP-FPM address for the FastCGI executor (FASTCGI_ADDR).
+    pub fastcgi_addr: Option<String>,
 }
 
 impl ExecutorConfig {
     /// Load configuration from environment variables.
     pub fn from_env() -> Result<Self, ConfigError> {
         let executor_type = Self::parse_executor_type();
-        let worker_count = Self::parse_worker_count()?;
-        let queue_capacity = Self::parse_queue_capacity(worker_count)?;
+        let sizing = Self::parse_sizing()?;
 
         Ok(Self {
             executor_type,
-            worker_count,
-            queue_capacity,
+            sizing,
+            sizing_report: env_bool("SIZING_REPORT", false),
+            priority_queue_capacity: match env_opt("PRIORITY_QUEUE_CAPACITY") {
+                Some(_) => Self::parse_count("PRIORITY_QUEUE_CAPACITY")?,
+                None => DEFAULT_PRIORITY_CAPACITY,
+            },
+            stream_buffer_size: Self::parse_stream_buffer_size(env_opt("STREAM_BUFFER_SIZE"))?,
+            stream_write_timeout: Self::parse_stream_write_timeout(env_opt(
+                "STREAM_WRITE_TIMEOUT",
+            ))?,
+            php_ini: Self::parse_php_ini()?,
+            fastcgi_addr: Self::parse_fastcgi_addr(executor_type)?,
         })
     }
 
     /// Get worker count (pre-computed, zero-cost).
     #[inline]
     pub fn worker_count(&self) -> usize {
-        self.worker_count.get()
+        self.sizing.workers.get()
     }
 
     /// Get queue capacity (pre-computed, zero-cost).
     #[inline]
     pub fn queue_capacity(&self) -> usize {
-        self.queue_capacity.get()
+        self.sizing.queue_capacity.get()
+    }
+
+    /// How the worker count and queue capacity were chosen.
+    pub fn sizing(&self) -> &Sizing {
+        &self.sizing
     }
 
     fn parse_executor_type() -> ExecutorType {
         match env_or("EXECUTOR", "ext").to_lowercase().as_str() {
             "stub" => ExecutorType::Stub,
             "php" => ExecutorType::Php,
+            "fastcgi" => ExecutorType::FastCgi,
             _ => ExecutorType::Ext, // "ext" or any other value defaults to Ext
         }
     }
 
-    fn parse_worker_count() -> Result<NonZeroUsize, ConfigError> {
+    fn parse_sizing() -> Result<Sizing, ConfigError> {
+        let queue_capacity = Self::parse_count("QUEUE_CAPACITY")?;
+        let queue_multiplier = Self::parse_count("QUEUE_MULTIPLIER")?;
+        let limits = CgroupLimits::detect();
+
         // Debug profile: force single worker for accurate profiling
-        #[cfg(feature = "debug-profile")]
-        {
-            Ok(NonZeroUsize::new(1).unwrap())
+        if cfg!(feature = "debug-profile") {
+            return Ok(Sizing::debug_profile(queue_capacity, limits)
+                .with_queue_multiplier(queue_multiplier));
         }
 
-        #[cfg(not(feature = "debug-profile"))]
-        {
-            let raw = env_or("PHP_WORKERS", "0");
-            let workers: usize = raw.parse().map_err(|e| ConfigError::Parse {
-                key: "PHP_WORKERS".into(),
-                value: raw,
-                error: format!("{e}"),
+        // 0 = auto: cgroup CPU quota or CPU count, capped by memory limit
+        // when PHP_MEMORY_PER_WORKER_MB is set; queue = workers * QUEUE_MULTIPLIER
+        let workers = Self::parse_count("PHP_WORKERS")?;
+        let memory_per_worker_mb = Self::parse_count("PHP_MEMORY_PER_WORKER_MB")? as u64;
+        let memory_per_worker = memory_per_worker_mb
+            .checked_mul(1024 * 1024)
+            .ok_or_else(|| ConfigError::Invalid {
+                key: "PHP_MEMORY_PER_WORKER_MB".into(),
+                message: format!("{memory_per_worker_mb} MB is out of range"),
             })?;
+        Ok(Sizing::resolve(
+            workers,
+            queue_capacity,
+            limits,
+            num_cpus::get(),
+            memory_per_worker,
+        )
+        .with_queue_multiplier(queue_multiplier))
+    }
 
-            // Resolve 0 to CPU count
-            let count = if workers == 0 {
-                num_cpus::get()
-            } else {
-                workers
+    /// Collect PHP error ini settings: PHP_DISPLAY_ERRORS, PHP_LOG_ERRORS,
+    /// PHP_ERROR_REPORTING. Unset variables leave php.ini in charge.
+    fn parse_php_ini() -> Result<Vec<(String, String)>, ConfigError> {
+        let settings: [(&str, &str, IniValidator); 3] = [
+            ("PHP_DISPLAY_ERRORS", "display_errors", is_display_errors),
+            ("PHP_LOG_ERRORS", "log_errors", is_ini_bool),
+            ("PHP_ERROR_REPORTING", "error_reporting", is_error_reporting),
+        ];
+        let mut entries = Vec::new();
+        for (key, directive, valid) in settings {
+            let Some(raw) = env_opt(key) else {
+                continue;
             };
+            let value = raw.trim();
+            if !valid(value) {
+                return Err(ConfigError::Invalid {
+                    key: key.into(),
+                    message: format!("invalid value for {directive}: '{raw}'"),
+                });
+            }
+            entries.push((directive.to_string(), value.to_string()));
+        }
+        Ok(entries)
+    }
 
-            NonZeroUsize::new(count).ok_or_else(|| ConfigError::Invalid {
-                key: "PHP_WORKERS".into(),
-                message: "worker count cannot be zero".into(),
-            })
+    /// FASTCGI_ADDR: `host:port` or `unix:/path`, required for EXECUTOR=fastcgi.
+    fn parse_fastcgi_addr(executor_type: ExecutorType) -> Result<Option<String>, ConfigError> {
+        match env_opt("FASTCGI_ADDR").map(|s| s.trim().to_string()) {
+            Some(addr) if is_fastcgi_addr(&addr) => Ok(Some(addr)),
+            Some(addr) => Err(ConfigError::Invalid {
+                key: "FASTCGI_ADDR".into(),
+                message: format!("expected host:port or unix:/path, got '{addr}'"),
+            }),
+            None if executor_type == ExecutorType::FastCgi => Err(ConfigError::Missing {
+                key: "FASTCGI_ADDR".into(),
+            }),
+            None => Ok(None),
         }
     }
 
-    fn parse_queue_capacity(workers: NonZeroUsize) -> Result<NonZeroUsize, ConfigError> {
-        let raw = env_or("QUEUE_CAPACITY", "0");
-        let capacity: usize = raw.parse().map_err(|e| ConfigError::Parse {
-            key: "QUEUE_CAPACITY".into(),
-            value: raw,
-            error: format!("{e}"),
-        })?;
+    /// STREAM_BUFFER_SIZE value (None = unset).
+    fn parse_stream_buffer_size(raw: Option<String>) -> Result<usize, ConfigError> {
+        let Some(raw) = raw else {
+            return Ok(DEFAULT_STREAM_BUFFER_SIZE);
+        };
+        match raw.trim().parse() {
+            Ok(0) => Err(ConfigError::Invalid {
+                key: "STREAM_BUFFER_SIZE".into(),
+                message: "must be at least 1".into(),
+            }),
+            Ok(size) => Ok(size),
+            Err(e) => Err(ConfigError::Parse {
+                key: "STREAM_BUFFER_SIZE".into(),
+                value: raw,
+                error: format!("{e}"),
+            }),
+        }
+    }
 
-        // Resolve 0 to workers * 100
-        let count = if capacity == 0 {
-            workers.get() * 100
-        } else {
-            capacity
+    /// STREAM_WRITE_TIMEOUT value (None = unset).
+    fn parse_stream_write_timeout(raw: Option<String>) -> Result<Option<Duration>, ConfigError> {
+        let Some(raw) = raw else {
+            return Ok(Some(DEFAULT_STREAM_WRITE_TIMEOUT));
         };
+        parse_duration(&raw).map_err(|error| ConfigError::Parse {
+            key: "STREAM_WRITE_TIMEOUT".into(),
+            value: raw,
+            error,
+        })
+    }
 
-        NonZeroUsize::new(count).ok_or_else(|| ConfigError::Invalid {
-            key: "QUEUE_CAPACITY".into(),
-            message: "queue capacity cannot be zero".into(),
+    fn parse_count(key: &str) -> Result<usize, ConfigError> {
+        let raw = env_or(key, "0");
+        raw.parse().map_err(|e| ConfigError::Parse {
+            key: key.into(),
+            value: raw,
+            error: format!("{e}"),
         })
     }
 }
 
+/// Checks that an env value is acceptable for an ini directive.
+type IniValidator = fn(&str) -> bool;
+
+fn is_ini_bool(value: &str) -> bool {
+    matches!(
+        value.to_lowercase().as_str(),
+        "0" | "1" | "on" | "off" | "true" | "false" | "yes" | "no"
+    )
+}
+
+/// display_errors also accepts a stream name.
+fn is_display_errors(value: &str) -> bool {
+    is_ini_bool(value) || matches!(value.to_lowercase().as_str(), "stderr" | "stdout")
+}
+
+/// FastCGI listen address: `host:port` or `unix:/path`.
+pub fn is_fastcgi_addr(value: &str) -> bool {
+    match value.strip_prefix("unix:") {
+        Some(path) => !path.is_empty(),
+        None => value
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok()),
+    }
+}
+
+/// A number or an expression of E_* constants, e.g. "E_ALL & ~E_DEPRECATED".
+/// Anything else could smuggle extra directives into the ini string.
+fn is_error_reporting(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || " _&|~^()-".contains(c))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn config(sizing: Sizing) -> ExecutorConfig {
+        ExecutorConfig {
+            executor_type: ExecutorType::Ext,
+            sizing,
+            sizing_report: false,
+            priority_queue_capacity: DEFAULT_PRIORITY_CAPACITY,
+            stream_buffer_size: DEFAULT_STREAM_BUFFER_SIZE,
+            stream_write_timeout: Some(DEFAULT_STREAM_WRITE_TIMEOUT),
+            php_ini: Vec::new(),
+            fastcgi_addr: None,
+        }
+    }
+
     #[test]
     fn test_executor_type_default_is_ext() {
         assert_eq!(ExecutorType::default(), ExecutorType::Ext);
@@ -126,31 +277,90 @@ mod tests {
 
     #[test]
     fn test_worker_count_explicit() {
-        let config = ExecutorConfig {
-            executor_type: ExecutorType::Ext,
-            worker_count: NonZeroUsize::new(4).unwrap(),
-            queue_capacity: NonZeroUsize::new(400).unwrap(),
-        };
+        let config = config(Sizing::resolve(4, 0, CgroupLimits::default(), 16, 0));
         assert_eq!(config.worker_count(), 4);
     }
 
     #[test]
     fn test_queue_capacity_explicit() {
-        let config = ExecutorConfig {
-            executor_type: ExecutorType::Ext,
-            worker_count: NonZeroUsize::new(4).unwrap(),
-            queue_capacity: NonZeroUsize::new(500).unwrap(),
-        };
+        let config = config(Sizing::resolve(4, 500, CgroupLimits::default(), 16, 0));
         assert_eq!(config.queue_capacity(), 500);
     }
 
     #[test]
     fn test_queue_capacity_derived() {
-        let config = ExecutorConfig {
-            executor_type: ExecutorType::Ext,
-            worker_count: NonZeroUsize::new(4).unwrap(),
-            queue_capacity: NonZeroUsize::new(400).unwrap(), // 4 * 100
-        };
-        assert_eq!(config.queue_capacity(), 400);
+        let config = config(Sizing::resolve(4, 0, CgroupLimits::default(), 16, 0));
+        assert_eq!(config.queue_capacity(), 400); // 4 * 100
+    }
+
+    #[test]
+    fn test_queue_capacity_multiplier() {
+        let sizing = Sizing::resolve(4, 0, CgroupLimits::default(), 16, 0);
+        let config = config(sizing.with_queue_multiplier(10));
+        assert_eq!(config.queue_capacity(), 40);
+    }
+
+    #[cfg(not(feature = "debug-profile"))]
+    #[test]
+    fn test_memory_per_worker_overflow_is_invalid() {
+        let file = super::super::parse::parse_env_file("PHP_MEMORY_PER_WORKER_MB=99999999999999\n");
+        let err = super::super::parse::with_overrides(Some(file), ExecutorConfig::parse_sizing)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("PHP_MEMORY_PER_WORKER_MB"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_fastcgi_addr_validation() {
+        assert!(is_fastcgi_addr("php-fpm:9000"));
+        assert!(is_fastcgi_addr("[::1]:9000"));
+        assert!(is_fastcgi_addr("unix:/run/php/php-fpm.sock"));
+        assert!(!is_fastcgi_addr("php-fpm"));
+        assert!(!is_fastcgi_addr("php-fpm:http"));
+        assert!(!is_fastcgi_addr("unix:"));
+    }
+
+    #[test]
+    fn test_stream_buffer_size() {
+        let parse =
+            |raw: Option<&str>| ExecutorConfig::parse_stream_buffer_size(raw.map(Into::into));
+        assert_eq!(parse(None).unwrap(), DEFAULT_STREAM_BUFFER_SIZE);
+        assert_eq!(parse(Some("16")).unwrap(), 16);
+        assert!(matches!(parse(Some("0")), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse(Some("-1")), Err(ConfigError::Parse { .. })));
+        assert!(matches!(
+            parse(Some("lots")),
+            Err(ConfigError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_stream_write_timeout() {
+        let parse =
+            |raw: Option<&str>| ExecutorConfig::parse_stream_write_timeout(raw.map(Into::into));
+        assert_eq!(parse(None).unwrap(), Some(DEFAULT_STREAM_WRITE_TIMEOUT));
+        assert_eq!(parse(Some("5s")).unwrap(), Some(Duration::from_secs(5)));
+        assert_eq!(parse(Some("2m")).unwrap(), Some(Duration::from_secs(120)));
+        assert_eq!(parse(Some("off")).unwrap(), None);
+        assert!(matches!(
+            parse(Some("soon")),
+            Err(ConfigError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_php_ini_value_validation() {
+        assert!(is_ini_bool("On"));
+        assert!(is_ini_bool("0"));
+        assert!(!is_ini_bool("maybe"));
+        assert!(is_display_errors("stderr"));
+        assert!(!is_display_errors("/dev/null"));
+        assert!(is_error_reporting("E_ALL & ~E_DEPRECATED & ~E_STRICT"));
+        assert!(is_error_reporting("32767"));
+        assert!(!is_error_reporting(""));
+        assert!(!is_error_reporting("E_ALL\nauto_prepend_file=/tmp/x"));
+        assert!(!is_error_reporting("E_ALL ; comment"));
     }
 }
@@ -1,7 +1,136 @@
 //! Logging configuration.
 
-use super::parse::env_or;
+use std::path::PathBuf;
+
+use super::parse::{env_opt, env_or, env_var, parse_list};
 use super::ConfigError;
+use crate::logging::DEFAULT_REDACT_KEYS;
+
+const DEFAULT_ACCESS_LOG_MAX_FILES: usize = 7;
+/// Threshold used by `ACCESS_LOG_ERRORS_ONLY=1`.
+const DEFAULT_ACCESS_LOG_ERROR_STATUS: u16 = 400;
+
+/// Access log line format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum AccessLogFormat {
+    /// Unified JSON format (same envelope as application logs).
+    #[default]
+    Json,
+    /// Apache Common Log Format.
+    Common,
+    /// Apache Combined Log Format (common + referer + user agent).
+    Combined,
+}
+
+/// Access log file rotation policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LogRotation {
+    /// Never rotate (external tools may still delete/move the file).
+    #[default]
+    Never,
+    /// Rotate at UTC midnight.
+    Daily,
+    /// Rotate when the file reaches the given size in bytes.
+    Size(u64),
+}
+
+/// Access log sink configuration.
+#[derive(Clone, Debug, Default)]
+pub struct AccessLogConfig {
+    /// Log file path (None = stdout).
+    pub file: Option<PathBuf>,
+    /// Line format.
+    pub format: AccessLogFormat,
+    /// Rotation policy (file sink only).
+    pub rotation: LogRotation,
+    /// Rotated files to keep (oldest are deleted).
+    pub max_files: usize,
+    /// Only log responses with status >= this (0 = log everything).
+    pub min_status: u16,
+}
+
+impl AccessLogConfig {
+    /// Load from ACCESS_LOG_FILE, ACCESS_LOG_FORMAT, ACCESS_LOG_ROTATE, ACCESS_LOG_MAX_FILES,
+    /// ACCESS_LOG_ERRORS_ONLY.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Ok(Self {
+            file: env_opt("ACCESS_LOG_FILE").map(PathBuf::from),
+            format: Self::parse_format()?,
+            rotation: Self::parse_rotation()?,
+            max_files: Self::parse_max_files()?,
+            min_status: Self::parse_min_status()?,
+        })
+    }
+
+    fn parse_format() -> Result<AccessLogFormat, ConfigError> {
+        let raw = env_or("ACCESS_LOG_FORMAT", "json");
+        match raw.to_lowercase().as_str() {
+            "json" => Ok(AccessLogFormat::Json),
+            "common" => Ok(AccessLogFormat::Common),
+            "combined" => Ok(AccessLogFormat::Combined),
+            _ => Err(ConfigError::Invalid {
+                key: "ACCESS_LOG_FORMAT".into(),
+                message: format!("expected json, common or combined, got '{raw}'"),
+            }),
+        }
+    }
+
+    fn parse_rotation() -> Result<LogRotation, ConfigError> {
+        let raw = env_or("ACCESS_LOG_ROTATE", "off");
+        parse_rotation(&raw).ok_or_else(|| ConfigError::Invalid {
+            key: "ACCESS_LOG_ROTATE".into(),
+            message: format!("expected off, daily or a size like 100M, got '{raw}'"),
+        })
+    }
+
+    fn parse_max_files() -> Result<usize, ConfigError> {
+        let raw = env_or(
+            "ACCESS_LOG_MAX_FILES",
+            &DEFAULT_ACCESS_LOG_MAX_FILES.to_string(),
+        );
+        raw.parse().map_err(|e| ConfigError::Parse {
+            key: "ACCESS_LOG_MAX_FILES".into(),
+            value: raw,
+            error: format!("{e}"),
+        })
+    }
+
+    fn parse_min_status() -> Result<u16, ConfigError> {
+        let raw = env_or("ACCESS_LOG_ERRORS_ONLY", "0");
+        parse_errors_only(&raw).ok_or_else(|| ConfigError::Invalid {
+            key: "ACCESS_LOG_ERRORS_ONLY".into(),
+            message: format!("expected 0, 1 or a status threshold (100-599), got '{raw}'"),
+        })
+    }
+}
+
+/// Parse errors-only mode: "0"/"false" (log all), "1"/"true" (>= 400), or a threshold like "500".
+fn parse_errors_only(raw: &str) -> Option<u16> {
+    match raw.trim().to_lowercase().as_str() {
+        "" | "0" | "false" | "off" => Some(0),
+        "1" | "true" | "on" => Some(DEFAULT_ACCESS_LOG_ERROR_STATUS),
+        s => s.parse().ok().filter(|code| (100..=599).contains(code)),
+    }
+}
+
+/// Parse rotation policy: "off", "daily", or a size ("500K", "100M", "1G", bytes).
+fn parse_rotation(raw: &str) -> Option<LogRotation> {
+    let s = raw.trim().to_lowercase();
+    match s.as_str() {
+        "" | "off" | "never" | "0" => return Some(LogRotation::Never),
+        "daily" => return Some(LogRotation::Daily),
+        _ => {}
+    }
+    let (num, multiplier) = match s.as_bytes()[s.len() - 1] {
+        b'k' => (&s[..s.len() - 1], 1024),
+        b'm' => (&s[..s.len() - 1], 1024 * 1024),
+        b'g' => (&s[..s.len() - 1], 1024 * 1024 * 1024),
+        _ => (&s[..], 1),
+    };
+    let size: u64 = num.parse().ok()?;
+    let bytes = size.checked_mul(multiplier)?;
+    (bytes > 0).then_some(LogRotation::Size(bytes))
+}
 
 /// Logging configuration loaded from environment.
 #[derive(Clone, Debug)]
@@ -10,6 +139,10 @@ pub struct LoggingConfig {
     pub filter: String,
     /// Service name for structured logging.
     pub service_name: String,
+    /// Access log sink (file, format, rotation).
+    pub access_log: AccessLogConfig,
+    /// Header names and query/form keys whose values are logged as "[REDACTED]".
+    pub redact_keys: Vec<String>,
 }
 
 impl LoggingConfig {
@@ -24,15 +157,32 @@ impl LoggingConfig {
         Ok(Self {
             filter,
             service_name: env_or("SERVICE_NAME", "tokio_php"),
+            access_log: AccessLogConfig::from_env()?,
+            redact_keys: Self::resolve_redact_keys(),
         })
     }
 
+    /// Resolve redaction keys.
+    ///
+    /// LOG_REDACT replaces the defaults (empty value disables redaction),
+    /// LOG_REDACT_EXTRA adds to them.
+    fn resolve_redact_keys() -> Vec<String> {
+        let mut keys = match env_var("LOG_REDACT") {
+            Some(raw) => parse_list(&raw),
+            None => DEFAULT_REDACT_KEYS.iter().map(|k| k.to_string()).collect(),
+        };
+        if let Some(extra) = env_opt("LOG_REDACT_EXTRA") {
+            keys.extend(parse_list(&extra));
+        }
+        keys
+    }
+
     /// Resolve log filter from environment.
     ///
     /// Priority: LOG_LEVEL > RUST_LOG > default (info)
     fn resolve_log_filter() -> String {
         // 1. Check LOG_LEVEL first (simple: debug, info, warn, error)
-        if let Ok(level) = std::env::var("LOG_LEVEL") {
+        if let Some(level) = env_var("LOG_LEVEL") {
             let level = level.to_lowercase();
             match level.as_str() {
                 "trace" | "debug" | "info" | "warn" | "error" => {
@@ -49,7 +199,7 @@ impl LoggingConfig {
         }
 
         // 2. Check RUST_LOG (full tracing filter syntax)
-        if let Ok(filter) = std::env::var("RUST_LOG") {
+        if let Some(filter) = env_var("RUST_LOG") {
             return filter;
         }
 
@@ -87,4 +237,33 @@ mod tests {
         env::remove_var("LOG_LEVEL");
         env::remove_var("RUST_LOG");
     }
+
+    #[test]
+    fn test_parse_rotation() {
+        assert_eq!(parse_rotation("off"), Some(LogRotation::Never));
+        assert_eq!(parse_rotation(""), Some(LogRotation::Never));
+        assert_eq!(parse_rotation("Daily"), Some(LogRotation::Daily));
+        assert_eq!(
+            parse_rotation("100M"),
+            Some(LogRotation::Size(100 * 1024 * 1024))
+        );
+        assert_eq!(parse_rotation("512k"), Some(LogRotation::Size(512 * 1024)));
+        assert_eq!(parse_rotation("4096"), Some(LogRotation::Size(4096)));
+        assert_eq!(parse_rotation("weekly"), None);
+        assert_eq!(parse_rotation("M"), None);
+        assert_eq!(parse_rotation("99999999999G"), None);
+    }
+
+    #[test]
+    fn test_parse_errors_only() {
+        assert_eq!(parse_errors_only("0"), Some(0));
+        assert_eq!(parse_errors_only(""), Some(0));
+        assert_eq!(parse_errors_only("false"), Some(0));
+        assert_eq!(parse_errors_only("1"), Some(400));
+        assert_eq!(parse_errors_only("TRUE"), Some(400));
+        assert_eq!(parse_errors_only("500"), Some(500));
+        assert_eq!(parse_errors_only("99"), None);
+        assert_eq!(parse_errors_only("600"), None);
+        assert_eq!(parse_errors_only("errors"), None);
+    }
 }
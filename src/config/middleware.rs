@@ -4,6 +4,36 @@ use super::parse::{env_bool, env_or};
 use super::ConfigError;
 use std::num::NonZeroU64;
 
+/// Rate limiting algorithm (RATE_LIMIT_ALGORITHM).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RateLimitAlgorithm {
+    /// One counter per IP, reset when its window ends (default). Cheapest,
+    /// but up to twice the limit can pass around a window boundary.
+    #[default]
+    FixedWindow,
+    /// Recent request timestamps per IP, counted over the trailing window.
+    /// Exact at any instant; memory grows with the limit.
+    SlidingLog,
+}
+
+impl RateLimitAlgorithm {
+    /// Parse a RATE_LIMIT_ALGORITHM value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "fixed" | "fixed_window" => Some(Self::FixedWindow),
+            "sliding_log" => Some(Self::SlidingLog),
+            _ => None,
+        }
+    }
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::FixedWindow => "fixed",
+            Self::SlidingLog => "sliding_log",
+        }
+    }
+}
+
 /// Rate limiting configuration.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct RateLimitConfig {
@@ -11,6 +41,8 @@ pub struct RateLimitConfig {
     limit: NonZeroU64,
     /// Window size in seconds.
     window_secs: u64,
+    /// How requests are counted within the window.
+    algorithm: RateLimitAlgorithm,
 }
 
 impl RateLimitConfig {
@@ -25,6 +57,12 @@ impl RateLimitConfig {
     pub const fn window_secs(&self) -> u64 {
         self.window_secs
     }
+
+    /// Get the counting algorithm.
+    #[inline]
+    pub const fn algorithm(&self) -> RateLimitAlgorithm {
+        self.algorithm
+    }
 }
 
 /// Middleware configuration loaded from environment.
@@ -36,6 +74,8 @@ pub struct MiddlewareConfig {
     rate_limit: Option<RateLimitConfig>,
     /// Access logging enabled.
     access_log: bool,
+    /// Aggregate profiles into per-phase percentiles (PROFILE_STATS).
+    profile_stats: bool,
 }
 
 impl MiddlewareConfig {
@@ -44,6 +84,7 @@ impl MiddlewareConfig {
         Ok(Self {
             rate_limit: Self::parse_rate_limit()?,
             access_log: env_bool("ACCESS_LOG", false),
+            profile_stats: env_bool("PROFILE_STATS", false),
         })
     }
 
@@ -74,6 +115,14 @@ impl MiddlewareConfig {
         cfg!(feature = "debug-profile")
     }
 
+    /// Check if profile percentiles are collected.
+    ///
+    /// Requires the `debug-profile` feature; PROFILE_STATS alone does nothing.
+    #[inline]
+    pub const fn is_profile_stats_enabled(&self) -> bool {
+        cfg!(feature = "debug-profile") && self.profile_stats
+    }
+
     fn parse_rate_limit() -> Result<Option<RateLimitConfig>, ConfigError> {
         let raw_limit = env_or("RATE_LIMIT", "0");
         let limit: u64 = raw_limit.parse().map_err(|e| ConfigError::Parse {
@@ -94,7 +143,18 @@ impl MiddlewareConfig {
             error: format!("{e}"),
         })?;
 
-        Ok(Some(RateLimitConfig { limit, window_secs }))
+        let raw_algorithm = env_or("RATE_LIMIT_ALGORITHM", "fixed");
+        let algorithm =
+            RateLimitAlgorithm::parse(&raw_algorithm).ok_or_else(|| ConfigError::Invalid {
+                key: "RATE_LIMIT_ALGORITHM".into(),
+                message: format!("expected 'fixed' or 'sliding_log', got '{raw_algorithm}'"),
+            })?;
+
+        Ok(Some(RateLimitConfig {
+            limit,
+            window_secs,
+            algorithm,
+        }))
     }
 }
 
@@ -107,6 +167,7 @@ mod tests {
         let config = MiddlewareConfig {
             rate_limit: None,
             access_log: false,
+            profile_stats: false,
         };
         assert!(!config.is_rate_limiting_enabled());
         assert!(config.rate_limit().is_none());
@@ -118,8 +179,10 @@ mod tests {
             rate_limit: Some(RateLimitConfig {
                 limit: NonZeroU64::new(100).unwrap(),
                 window_secs: 60,
+                algorithm: RateLimitAlgorithm::FixedWindow,
             }),
             access_log: false,
+            profile_stats: false,
         };
         assert!(config.is_rate_limiting_enabled());
         let rl = config.rate_limit().unwrap();
@@ -132,9 +195,28 @@ mod tests {
         let rl = RateLimitConfig {
             limit: NonZeroU64::new(500).unwrap(),
             window_secs: 120,
+            algorithm: RateLimitAlgorithm::SlidingLog,
         };
         assert_eq!(rl.limit(), 500);
         assert_eq!(rl.window_secs(), 120);
+        assert_eq!(rl.algorithm(), RateLimitAlgorithm::SlidingLog);
+    }
+
+    #[test]
+    fn test_rate_limit_algorithm_parse() {
+        assert_eq!(
+            RateLimitAlgorithm::default(),
+            RateLimitAlgorithm::FixedWindow
+        );
+        assert_eq!(
+            RateLimitAlgorithm::parse("fixed"),
+            Some(RateLimitAlgorithm::FixedWindow)
+        );
+        assert_eq!(
+            RateLimitAlgorithm::parse("Sliding_Log"),
+            Some(RateLimitAlgorithm::SlidingLog)
+        );
+        assert_eq!(RateLimitAlgorithm::parse("token_bucket"), None);
     }
 
     #[test]
@@ -142,6 +224,7 @@ mod tests {
         let config = MiddlewareConfig {
             rate_limit: None,
             access_log: true,
+            profile_stats: false,
         };
         assert!(config.is_access_log_enabled());
     }
@@ -151,16 +234,31 @@ mod tests {
         let config = MiddlewareConfig {
             rate_limit: None,
             access_log: false,
+            profile_stats: false,
         };
         // With debug-profile feature: true, without: false
         assert_eq!(config.is_profile_enabled(), cfg!(feature = "debug-profile"));
     }
 
+    #[test]
+    fn test_profile_stats_requires_feature() {
+        let config = MiddlewareConfig {
+            rate_limit: None,
+            access_log: false,
+            profile_stats: true,
+        };
+        assert_eq!(
+            config.is_profile_stats_enabled(),
+            cfg!(feature = "debug-profile")
+        );
+    }
+
     #[test]
     fn test_middleware_config_is_copy() {
         let config = MiddlewareConfig {
             rate_limit: None,
             access_log: true,
+            profile_stats: false,
         };
         let copy = config; // Copy
         assert!(copy.is_access_log_enabled());
@@ -18,12 +18,27 @@ mod logging;
 mod middleware;
 mod parse;
 mod server;
+mod sizing;
 
 pub use error::ConfigError;
-pub use executor::{ExecutorConfig, ExecutorType};
-pub use logging::LoggingConfig;
-pub use middleware::{MiddlewareConfig, RateLimitConfig};
-pub use server::{OptionalDuration, RequestTimeout, ServerConfig, SseTimeout, StaticCacheTtl};
+pub use executor::{is_fastcgi_addr, ExecutorConfig, ExecutorType};
+pub use logging::{AccessLogConfig, AccessLogFormat, LogRotation, LoggingConfig};
+pub use middleware::{MiddlewareConfig, RateLimitAlgorithm, RateLimitConfig};
+pub use parse::env_var;
+pub use server::{
+    BrotliSettings, DefaultContentType, Http2Settings, MemoryPressureSettings, OptionalDuration,
+    RequestTimeout, RuntimeThreads, ServerConfig, SseTimeout, StaticCacheTtl, TimingAllowOrigin,
+    TimingAllowScope,
+};
+pub use sizing::{
+    CgroupLimits, QueueSource, Sizing, WorkerSource, DEFAULT_QUEUE_MULTIPLIER,
+    MEMORY_HEADROOM_PERCENT,
+};
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::types::OutputLimits;
 
 /// Complete application configuration.
 #[derive(Clone, Debug)]
@@ -40,7 +55,26 @@ pub struct Config {
 
 impl Config {
     /// Load configuration from environment variables.
+    ///
+    /// If CONFIG_FILE is set, its `KEY=VALUE` lines are (re-)read first and
+    /// take precedence over the process environment. Calling this again on
+    /// SIGHUP picks up edits to that file.
     pub fn from_env() -> Result<Self, ConfigError> {
+        // A file that fails validation must not leak into later lookups
+        let overrides = Self::read_config_file()?;
+        let config = parse::with_overrides(overrides.clone(), Self::load)?;
+        parse::set_overrides(overrides);
+        Ok(config)
+    }
+
+    /// Configuration with every setting at its default, ignoring the
+    /// environment and CONFIG_FILE.
+    #[cfg(test)]
+    pub(crate) fn defaults() -> Self {
+        parse::without_env(Self::load).expect("defaults are valid")
+    }
+
+    fn load() -> Result<Self, ConfigError> {
         Ok(Self {
             server: ServerConfig::from_env()?,
             executor: ExecutorConfig::from_env()?,
@@ -49,16 +83,97 @@ impl Config {
         })
     }
 
+    fn read_config_file() -> Result<Option<HashMap<String, String>>, ConfigError> {
+        let Some(path) = std::env::var("CONFIG_FILE").ok().filter(|p| !p.is_empty()) else {
+            return Ok(None);
+        };
+        let contents = std::fs::read_to_string(&path).map_err(|error| ConfigError::Io {
+            path: path.clone(),
+            error,
+        })?;
+        Ok(Some(parse::parse_env_file(&contents)))
+    }
+
+    /// Streaming and background-time limits for PHP requests (reloadable).
+    pub fn output_limits(&self) -> OutputLimits {
+        OutputLimits {
+            stream_buffer_size: self.executor.stream_buffer_size.max(1),
+            stream_write_timeout: self.executor.stream_write_timeout,
+            // PHP's execution timer counts whole seconds
+            max_background_time: self
+                .server
+                .max_background_time
+                .as_duration()
+                .map(|max| Duration::from_secs(max.as_secs().max(1))),
+        }
+    }
+
     /// Print configuration summary to log.
     pub fn log_summary(&self) {
-        use tracing::info;
+        use tracing::{info, warn};
 
         info!("Configuration loaded:");
         info!("Listen: {}", self.server.listen_addr);
         info!("Document root: {:?}", self.server.document_root);
-        info!("Workers: {}", self.executor.worker_count());
-        info!("Queue capacity: {}", self.executor.queue_capacity());
+        let sizing = self.executor.sizing();
+        info!(
+            "Workers: {} (from {})",
+            sizing.workers,
+            sizing.worker_source.as_str()
+        );
+        if sizing.worker_source == WorkerSource::CgroupMemoryLimit {
+            warn!(
+                memory_limit_bytes = sizing.limits.memory_limit,
+                memory_per_worker_bytes = sizing.memory_per_worker,
+                headroom_percent = MEMORY_HEADROOM_PERCENT,
+                "Worker count limited by memory, not CPU"
+            );
+        }
+        match sizing.queue_source {
+            QueueSource::Explicit => info!("Queue capacity: {}", sizing.queue_capacity),
+            QueueSource::PerWorker => info!(
+                "Queue capacity: {} ({} workers x {})",
+                sizing.queue_capacity, sizing.workers, sizing.queue_multiplier
+            ),
+        }
+        info!(
+            "Priority lane: {} slots",
+            self.executor.priority_queue_capacity
+        );
+        if !self.server.priority_paths.is_empty() {
+            info!("Priority paths: {:?}", self.server.priority_paths);
+        }
+        if let Some(wait) = self.server.max_queue_wait.as_duration() {
+            info!("Max queue wait: {}s", wait.as_secs());
+        }
+        match self.executor.stream_write_timeout {
+            Some(timeout) => info!(
+                "Stream buffer: {} chunks, write timeout {}s",
+                self.executor.stream_buffer_size,
+                timeout.as_secs()
+            ),
+            None => info!(
+                "Stream buffer: {} chunks, no write timeout",
+                self.executor.stream_buffer_size
+            ),
+        }
         info!("Executor: {:?}", self.executor.executor_type);
+        if let Some(addr) = &self.executor.fastcgi_addr {
+            info!("FastCGI upstream: {}", addr);
+        }
+
+        if self.executor.sizing_report {
+            info!(
+                cpu_quota = sizing.limits.cpu_quota,
+                memory_limit_bytes = sizing.limits.memory_limit,
+                workers = sizing.workers.get(),
+                workers_source = sizing.worker_source.as_str(),
+                queue_capacity = sizing.queue_capacity.get(),
+                queue_capacity_source = sizing.queue_source.as_str(),
+                queue_multiplier = sizing.queue_multiplier.get(),
+                "Sizing decision"
+            );
+        }
 
         if let Some(ref index) = self.server.index_file {
             info!("Index file: {}", index);
@@ -68,10 +183,21 @@ impl Config {
             info!("Internal server: {}", internal);
         }
 
-        if self.server.tls.is_enabled() {
+        if self.server.tls.auto {
+            info!("TLS: enabled (self-signed, TLS_MODE=auto)");
+        } else if self.server.tls.is_enabled() {
             info!("TLS: enabled");
         }
 
+        if self.server.expose_errors {
+            warn!("EXPOSE_ERRORS is on: script error details are sent to clients");
+        }
+
+        info!(
+            "Methods: {}",
+            self.server.allowed_methods.names().join(", ")
+        );
+
         if self.server.static_cache_ttl.is_enabled() {
             info!(
                 "Static cache TTL: {}s",
@@ -96,17 +222,41 @@ impl Config {
             info!("SSE timeout: disabled");
         }
 
+        let h2 = &self.server.http2;
+        info!(
+            "HTTP/2: max_concurrent_streams={}, stream_window={}, connection_window={}, max_frame_size={}",
+            h2.max_concurrent_streams,
+            h2.initial_stream_window_size,
+            h2.initial_connection_window_size,
+            h2.max_frame_size
+        );
+
+        let br = &self.server.brotli;
+        info!(
+            "Brotli: quality={}, static_quality={}, window={}, min_size={}, static_min_size={}",
+            br.quality, br.static_quality, br.window, br.min_size, br.static_min_size
+        );
+        info!(
+            "Default Content-Type: {}",
+            self.server.default_content_type.header_value()
+        );
+
         if let Some(rl) = self.middleware.rate_limit() {
             info!(
-                "Rate limit: {} req/{}s per IP",
+                "Rate limit: {} req/{}s per IP ({})",
                 rl.limit(),
-                rl.window_secs()
+                rl.window_secs(),
+                rl.algorithm().as_str()
             );
         }
 
         if self.middleware.is_access_log_enabled() {
             info!("Access log: enabled");
         }
+
+        if self.middleware.is_profile_stats_enabled() {
+            info!("Profile stats: enabled (/profile/stats)");
+        }
     }
 }
 
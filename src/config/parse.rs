@@ -1,25 +1,143 @@
 //! Environment variable parsing utilities.
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::RwLock;
 use std::time::Duration;
 
+/// Values loaded from CONFIG_FILE. Take precedence over the process environment.
+static OVERRIDES: RwLock<Option<HashMap<String, String>>> = RwLock::new(None);
+
+thread_local! {
+    /// Lookup used by a configuration load running on this thread, so a
+    /// CONFIG_FILE is validated before it replaces [`OVERRIDES`].
+    static LOADING: RefCell<Option<Lookup>> = const { RefCell::new(None) };
+}
+
+/// Where variables are looked up during a scoped load.
+struct Lookup {
+    /// CONFIG_FILE values, checked first.
+    overrides: Option<HashMap<String, String>>,
+    /// Fall back to the process environment.
+    process_env: bool,
+}
+
+/// Replace the CONFIG_FILE overrides (None clears them).
+pub fn set_overrides(values: Option<HashMap<String, String>>) {
+    *OVERRIDES.write().unwrap() = values;
+}
+
+/// Run `f` with lookups on this thread using `overrides` instead of the
+/// installed ones.
+pub fn with_overrides<T>(overrides: Option<HashMap<String, String>>, f: impl FnOnce() -> T) -> T {
+    with_lookup(
+        Lookup {
+            overrides,
+            process_env: true,
+        },
+        f,
+    )
+}
+
+/// Run `f` with every lookup on this thread missing, so config loads its
+/// defaults regardless of the process environment and CONFIG_FILE.
+#[cfg(test)]
+pub fn without_env<T>(f: impl FnOnce() -> T) -> T {
+    with_lookup(
+        Lookup {
+            overrides: None,
+            process_env: false,
+        },
+        f,
+    )
+}
+
+fn with_lookup<T>(lookup: Lookup, f: impl FnOnce() -> T) -> T {
+    /// Restores the previous lookup, also if `f` panics.
+    struct Restore(Option<Lookup>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LOADING.with(|loading| *loading.borrow_mut() = self.0.take());
+        }
+    }
+    let _restore = Restore(LOADING.with(|loading| loading.borrow_mut().replace(lookup)));
+    f()
+}
+
+/// Look up a variable in CONFIG_FILE overrides, then the process environment.
+pub fn env_var(key: &str) -> Option<String> {
+    let scoped = LOADING.with(|loading| {
+        loading.borrow().as_ref().map(|lookup| {
+            lookup
+                .overrides
+                .as_ref()
+                .and_then(|map| map.get(key).cloned())
+                .or_else(|| {
+                    lookup
+                        .process_env
+                        .then(|| std::env::var(key).ok())
+                        .flatten()
+                })
+        })
+    });
+    if let Some(value) = scoped {
+        return value;
+    }
+    if let Some(ref map) = *OVERRIDES.read().unwrap() {
+        if let Some(value) = map.get(key) {
+            return Some(value.clone());
+        }
+    }
+    std::env::var(key).ok()
+}
+
 /// Get environment variable with default value.
 pub fn env_or(key: &str, default: &str) -> String {
-    std::env::var(key).unwrap_or_else(|_| default.to_string())
+    env_var(key).unwrap_or_else(|| default.to_string())
 }
 
 /// Get optional environment variable (None if empty or missing).
 pub fn env_opt(key: &str) -> Option<String> {
-    std::env::var(key).ok().filter(|s| !s.is_empty())
+    env_var(key).filter(|s| !s.is_empty())
 }
 
 /// Parse environment variable as boolean.
 /// Treats "1", "true" (case-insensitive) as true.
 pub fn env_bool(key: &str, default: bool) -> bool {
-    std::env::var(key)
+    env_var(key)
         .map(|v| v == "1" || v.to_lowercase() == "true")
         .unwrap_or(default)
 }
 
+/// Parse a comma-separated list, dropping empty entries.
+pub fn parse_list(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Parse an env-style file: `KEY=VALUE` per line, `#` comments, optional quotes.
+pub fn parse_env_file(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
 /// Parse duration string (e.g., "30s", "2m", "1h", "1d", "1w").
 /// Returns None for "off" or "0".
 pub fn parse_duration(s: &str) -> Result<Option<Duration>, String> {
@@ -65,6 +183,37 @@ pub fn parse_duration(s: &str) -> Result<Option<Duration>, String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list("/api/reports, /admin ,,"),
+            vec!["/api/reports".to_string(), "/admin".to_string()]
+        );
+        assert!(parse_list("").is_empty());
+    }
+
+    #[test]
+    fn test_overrides_apply_only_inside_the_load() {
+        let file = parse_env_file("TOKIO_PHP_TEST_SCOPED=1\n");
+        let inside = with_overrides(Some(file), || env_var("TOKIO_PHP_TEST_SCOPED"));
+        assert_eq!(inside.as_deref(), Some("1"));
+        // Not installed, so a rejected file leaves no trace
+        assert_eq!(env_var("TOKIO_PHP_TEST_SCOPED"), None);
+        assert_eq!(without_env(|| env_var("PATH")), None);
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let map = parse_env_file(
+            "# comment\n\nRATE_LIMIT=100\nexport ACCESS_LOG = 1\nSERVICE_NAME=\"my app\"\nX='a=b'\ninvalid\n",
+        );
+        assert_eq!(map.len(), 4);
+        assert_eq!(map["RATE_LIMIT"], "100");
+        assert_eq!(map["ACCESS_LOG"], "1");
+        assert_eq!(map["SERVICE_NAME"], "my app");
+        assert_eq!(map["X"], "a=b");
+    }
+
     #[test]
     fn test_parse_duration() {
         assert_eq!(parse_duration("off").unwrap(), None);
@@ -4,16 +4,38 @@ use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::time::Duration;
 
-use super::parse::{env_opt, env_or, parse_duration};
+use super::parse::{env_bool, env_opt, env_or, env_var, parse_duration, parse_list};
 use super::ConfigError;
+use crate::server::request::UploadTypes;
+use crate::server::response::{
+    CompressibleTypes, ImmutableAssets, MimeTypes, UaVariants, DEFAULT_COMPRESSED_CACHE_SIZE,
+    DEFAULT_SERVER_HEADER, MIN_COMPRESSION_SIZE,
+};
+use crate::server::{
+    AllowedMethods, CanonicalLinks, CspNonce, DirectoryRequest, ProxyRoutes, RouteLabels,
+    TrustedProxies,
+};
 
 // Default values as constants
 const DEFAULT_STATIC_CACHE_TTL_SECS: u64 = 86400; // 1 day
 const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 120; // 2 minutes
 const DEFAULT_SSE_TIMEOUT_SECS: u64 = 1800; // 30 minutes (SSE connections are long-lived)
 const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_GOAWAY_WINDOW_SECS: u64 = 30;
+const DEFAULT_MAX_CONNECTIONS: u64 = 0; // Unlimited
+const DEFAULT_MAX_CONNECTIONS_PER_WORKER: u64 = 0; // Unlimited
+const DEFAULT_MAX_CONNECTIONS_PER_IP: u64 = 0; // Unlimited
 const DEFAULT_HEADER_TIMEOUT_SECS: u64 = 5; // 5 seconds (Slowloris protection)
-const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 60; // 60 seconds (keep-alive idle timeout)
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 60; // 60 seconds (wait for first request)
+const DEFAULT_KEEPALIVE_TIMEOUT_SECS: u64 = 75; // Nothing in flight on a connection
+const DEFAULT_TLS_HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_SLOW_REQUEST_THRESHOLD_MS: u64 = 5000;
+const DEFAULT_READY_QUEUE_THRESHOLD: u64 = 90; // Percent of QUEUE_CAPACITY
+const DEFAULT_READY_SATURATION_WINDOW_SECS: u64 = 10;
+const DEFAULT_DISABLED_PATH_STATUS: u16 = 503;
+const DEFAULT_MAX_INPUT_VARS: u64 = 1000; // Same as PHP's max_input_vars default
+const DEFAULT_BODY_PREALLOC_MAX: u64 = 8 * 1024 * 1024; // 8 MiB
+const DEFAULT_PROXY_POOL_SIZE: u64 = 8; // Idle connections per upstream
 
 /// Duration-based configuration that can be disabled.
 ///
@@ -89,6 +111,8 @@ pub struct TlsConfig {
     pub cert_path: Option<PathBuf>,
     /// Path to TLS private key (PEM format).
     pub key_path: Option<PathBuf>,
+    /// Generate a self-signed certificate at startup (TLS_MODE=auto).
+    pub auto: bool,
     /// Pre-computed enabled flag (zero-cost check).
     enabled: bool,
 }
@@ -101,18 +125,486 @@ impl TlsConfig {
     }
 
     /// Load from environment variables.
-    pub fn from_env() -> Self {
+    pub fn from_env() -> Result<Self, ConfigError> {
         let cert_path = env_opt("TLS_CERT").map(PathBuf::from);
         let key_path = env_opt("TLS_KEY").map(PathBuf::from);
-        let enabled = cert_path.is_some() && key_path.is_some();
-        Self {
+        let auto = match env_or("TLS_MODE", "files").to_lowercase().as_str() {
+            "files" => false,
+            "auto" if cert_path.is_none() && key_path.is_none() => true,
+            "auto" => {
+                return Err(ConfigError::Invalid {
+                    key: "TLS_MODE".into(),
+                    message: "auto can't be combined with TLS_CERT/TLS_KEY".into(),
+                })
+            }
+            other => {
+                return Err(ConfigError::Invalid {
+                    key: "TLS_MODE".into(),
+                    message: format!("expected files or auto, got {other:?}"),
+                })
+            }
+        };
+        let enabled = auto || (cert_path.is_some() && key_path.is_some());
+        Ok(Self {
             cert_path,
             key_path,
+            auto,
             enabled,
+        })
+    }
+}
+
+/// Responses that get a `Timing-Allow-Origin` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TimingAllowScope {
+    /// Static files only.
+    Static,
+    /// PHP responses only.
+    Php,
+    /// Static files and PHP responses.
+    #[default]
+    All,
+}
+
+/// `Timing-Allow-Origin` header for cross-origin Resource Timing.
+#[derive(Clone, Debug)]
+pub struct TimingAllowOrigin {
+    /// Header value: `*` or a comma-separated origin list.
+    pub value: String,
+    /// Which responses carry the header.
+    pub scope: TimingAllowScope,
+}
+
+impl TimingAllowOrigin {
+    /// Load from TIMING_ALLOW_ORIGIN and TIMING_ALLOW_ORIGIN_SCOPE (None if unset).
+    pub fn from_env() -> Result<Option<Self>, ConfigError> {
+        let Some(raw) = env_opt("TIMING_ALLOW_ORIGIN") else {
+            return Ok(None);
+        };
+        let value = Self::parse_origins(&raw).map_err(|message| ConfigError::Invalid {
+            key: "TIMING_ALLOW_ORIGIN".into(),
+            message,
+        })?;
+        let raw_scope = env_or("TIMING_ALLOW_ORIGIN_SCOPE", "all");
+        let scope = Self::parse_scope(&raw_scope).ok_or_else(|| ConfigError::Invalid {
+            key: "TIMING_ALLOW_ORIGIN_SCOPE".into(),
+            message: format!("expected static, php or all, got '{raw_scope}'"),
+        })?;
+        Ok(Some(Self { value, scope }))
+    }
+
+    /// Whether a response of this kind gets the header.
+    #[inline]
+    pub fn applies_to(&self, is_php: bool) -> bool {
+        match self.scope {
+            TimingAllowScope::Static => !is_php,
+            TimingAllowScope::Php => is_php,
+            TimingAllowScope::All => true,
+        }
+    }
+
+    /// Validate `*` or a list of `scheme://host[:port]` origins.
+    fn parse_origins(raw: &str) -> Result<String, String> {
+        let origins = parse_list(raw);
+        if origins.is_empty() {
+            return Err("expected * or a list of origins".into());
+        }
+        if origins.iter().any(|o| o == "*") {
+            if origins.len() > 1 {
+                return Err("* cannot be combined with other origins".into());
+            }
+            return Ok("*".into());
+        }
+        for origin in &origins {
+            let host = origin
+                .strip_prefix("https://")
+                .or_else(|| origin.strip_prefix("http://"));
+            match host {
+                Some(host)
+                    if !host.is_empty()
+                        && host
+                            .bytes()
+                            .all(|b| b.is_ascii_alphanumeric() || b".-:[]".contains(&b)) => {}
+                _ => return Err(format!("invalid origin '{origin}'")),
+            }
+        }
+        Ok(origins.join(", "))
+    }
+
+    fn parse_scope(raw: &str) -> Option<TimingAllowScope> {
+        match raw.to_lowercase().as_str() {
+            "static" => Some(TimingAllowScope::Static),
+            "php" => Some(TimingAllowScope::Php),
+            "all" => Some(TimingAllowScope::All),
+            _ => None,
+        }
+    }
+}
+
+/// Tokio runtime flavor for the HTTP side (`RUNTIME_THREADS`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RuntimeThreads {
+    /// A single thread runs every connection; PHP workers do the blocking work.
+    #[default]
+    CurrentThread,
+    /// Multi-threaded runtime with this many worker threads.
+    MultiThread(usize),
+}
+
+impl RuntimeThreads {
+    /// `0`/`current` (single thread), `auto` (one per CPU) or a thread count.
+    fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_lowercase().as_str() {
+            "" | "0" | "current" => Ok(Self::CurrentThread),
+            "auto" => Ok(Self::MultiThread(num_cpus::get())),
+            n => n
+                .parse()
+                .map(Self::MultiThread)
+                .map_err(|_| format!("expected current, auto or a thread count, got '{raw}'")),
+        }
+    }
+}
+
+/// Memory pressure thresholds and the paths shed first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryPressureSettings {
+    /// Memory usage percent at which `shed_paths` get 503 (0 = off).
+    pub high_percent: u8,
+    /// Memory usage percent at which every new request gets 503 (0 = off).
+    pub critical_percent: u8,
+    /// Low-priority path prefixes shed under high pressure.
+    pub shed_paths: Vec<String>,
+}
+
+impl MemoryPressureSettings {
+    /// Load from MEMORY_PRESSURE_HIGH, MEMORY_PRESSURE_CRITICAL and MEMORY_SHED_PATHS.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self {
+            high_percent: ServerConfig::parse_percent("MEMORY_PRESSURE_HIGH", 0)?,
+            critical_percent: ServerConfig::parse_percent("MEMORY_PRESSURE_CRITICAL", 0)?,
+            shed_paths: env_opt("MEMORY_SHED_PATHS")
+                .map(|raw| parse_list(&raw))
+                .unwrap_or_default(),
+        }
+        .validate()
+    }
+
+    /// Critical has to be above high when both are set.
+    pub fn validate(self) -> Result<Self, ConfigError> {
+        if self.high_percent > 0
+            && self.critical_percent > 0
+            && self.critical_percent <= self.high_percent
+        {
+            return Err(ConfigError::Invalid {
+                key: "MEMORY_PRESSURE_CRITICAL".into(),
+                message: format!(
+                    "must be above MEMORY_PRESSURE_HIGH ({}%), got {}%",
+                    self.high_percent, self.critical_percent
+                ),
+            });
+        }
+        Ok(self)
+    }
+}
+
+/// HTTP/2 connection tuning.
+///
+/// Defaults match what the server used before these were configurable:
+/// 250 streams and hyper's 1 MiB windows with 16 KiB frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Http2Settings {
+    /// SETTINGS_MAX_CONCURRENT_STREAMS advertised to clients.
+    pub max_concurrent_streams: u32,
+    /// Per-stream flow-control window (SETTINGS_INITIAL_WINDOW_SIZE).
+    pub initial_stream_window_size: u32,
+    /// Connection-level flow-control window.
+    pub initial_connection_window_size: u32,
+    /// Largest frame payload we accept (SETTINGS_MAX_FRAME_SIZE).
+    pub max_frame_size: u32,
+}
+
+impl Default for Http2Settings {
+    fn default() -> Self {
+        Self {
+            max_concurrent_streams: 250,
+            initial_stream_window_size: 1024 * 1024,
+            initial_connection_window_size: 1024 * 1024,
+            max_frame_size: 16 * 1024,
+        }
+    }
+}
+
+impl Http2Settings {
+    /// Largest flow-control window HTTP/2 allows (2^31 - 1).
+    const MAX_WINDOW_SIZE: u32 = (1 << 31) - 1;
+    /// Initial connection window every peer starts with; it can only grow.
+    const MIN_CONNECTION_WINDOW_SIZE: u32 = 65_535;
+    /// SETTINGS_MAX_FRAME_SIZE bounds from RFC 9113.
+    const FRAME_SIZE_RANGE: std::ops::RangeInclusive<u32> = 16_384..=16_777_215;
+
+    /// Load from the HTTP2_* variables, falling back to the defaults.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        let read = |key: &str, default: u32| -> Result<u32, ConfigError> {
+            let value = ServerConfig::parse_u64(key, default.into())?;
+            u32::try_from(value).map_err(|_| ConfigError::Invalid {
+                key: key.into(),
+                message: format!("{value} is out of range"),
+            })
+        };
+        Self {
+            max_concurrent_streams: read(
+                "HTTP2_MAX_CONCURRENT_STREAMS",
+                defaults.max_concurrent_streams,
+            )?,
+            initial_stream_window_size: read(
+                "HTTP2_INITIAL_STREAM_WINDOW_SIZE",
+                defaults.initial_stream_window_size,
+            )?,
+            initial_connection_window_size: read(
+                "HTTP2_INITIAL_CONNECTION_WINDOW_SIZE",
+                defaults.initial_connection_window_size,
+            )?,
+            max_frame_size: read("HTTP2_MAX_FRAME_SIZE", defaults.max_frame_size)?,
+        }
+        .validate()
+    }
+
+    /// Check every value against the limits of the HTTP/2 protocol.
+    pub fn validate(self) -> Result<Self, ConfigError> {
+        let invalid = |key: &str, message: String| ConfigError::Invalid {
+            key: key.into(),
+            message,
+        };
+        if self.max_concurrent_streams == 0 {
+            return Err(invalid(
+                "HTTP2_MAX_CONCURRENT_STREAMS",
+                "must be at least 1".into(),
+            ));
+        }
+        if !(1..=Self::MAX_WINDOW_SIZE).contains(&self.initial_stream_window_size) {
+            return Err(invalid(
+                "HTTP2_INITIAL_STREAM_WINDOW_SIZE",
+                format!("must be between 1 and {}", Self::MAX_WINDOW_SIZE),
+            ));
+        }
+        if !(Self::MIN_CONNECTION_WINDOW_SIZE..=Self::MAX_WINDOW_SIZE)
+            .contains(&self.initial_connection_window_size)
+        {
+            return Err(invalid(
+                "HTTP2_INITIAL_CONNECTION_WINDOW_SIZE",
+                format!(
+                    "must be between {} and {}",
+                    Self::MIN_CONNECTION_WINDOW_SIZE,
+                    Self::MAX_WINDOW_SIZE
+                ),
+            ));
+        }
+        if !Self::FRAME_SIZE_RANGE.contains(&self.max_frame_size) {
+            return Err(invalid(
+                "HTTP2_MAX_FRAME_SIZE",
+                format!(
+                    "must be between {} and {}",
+                    Self::FRAME_SIZE_RANGE.start(),
+                    Self::FRAME_SIZE_RANGE.end()
+                ),
+            ));
+        }
+        Ok(self)
+    }
+}
+
+/// Brotli encoder settings and size thresholds.
+///
+/// Defaults match the former built-in quality 4, 20-bit window and 256-byte
+/// minimum. Static files have their own quality and minimum since they can
+/// often afford slower, denser compression than PHP output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BrotliSettings {
+    /// Quality for PHP responses (BROTLI_QUALITY, 0-11).
+    pub quality: u32,
+    /// Quality for static files (BROTLI_STATIC_QUALITY, 0-11).
+    pub static_quality: u32,
+    /// Window size as log2 of bytes (BROTLI_WINDOW, 10-24).
+    pub window: u32,
+    /// Smallest PHP response compressed, in bytes (BROTLI_MIN_SIZE).
+    pub min_size: usize,
+    /// Smallest static file compressed, in bytes (BROTLI_STATIC_MIN_SIZE).
+    pub static_min_size: usize,
+}
+
+impl BrotliSettings {
+    pub const DEFAULT: Self = Self {
+        quality: 4,
+        static_quality: 4,
+        window: 20,
+        min_size: MIN_COMPRESSION_SIZE,
+        static_min_size: MIN_COMPRESSION_SIZE,
+    };
+    const QUALITY_RANGE: std::ops::RangeInclusive<u32> = 0..=11;
+    const WINDOW_RANGE: std::ops::RangeInclusive<u32> = 10..=24;
+
+    /// Load from the BROTLI_* variables, falling back to the defaults.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let read = |key: &str, default: u32| -> Result<u32, ConfigError> {
+            let value = ServerConfig::parse_u64(key, default.into())?;
+            u32::try_from(value).map_err(|_| ConfigError::Invalid {
+                key: key.into(),
+                message: format!("{value} is out of range"),
+            })
+        };
+        let quality = read("BROTLI_QUALITY", Self::DEFAULT.quality)?;
+        let min_size = ServerConfig::parse_u64("BROTLI_MIN_SIZE", Self::DEFAULT.min_size as u64)?;
+        Self {
+            quality,
+            // Static settings follow the PHP ones unless set separately
+            static_quality: read("BROTLI_STATIC_QUALITY", quality)?,
+            window: read("BROTLI_WINDOW", Self::DEFAULT.window)?,
+            min_size: min_size as usize,
+            static_min_size: ServerConfig::parse_u64("BROTLI_STATIC_MIN_SIZE", min_size)? as usize,
+        }
+        .validate()
+    }
+
+    /// Check quality and window against what the encoder supports.
+    pub fn validate(self) -> Result<Self, ConfigError> {
+        let check = |key: &str, value: u32, range: std::ops::RangeInclusive<u32>| {
+            if range.contains(&value) {
+                Ok(())
+            } else {
+                Err(ConfigError::Invalid {
+                    key: key.into(),
+                    message: format!(
+                        "must be between {} and {}, got {value}",
+                        range.start(),
+                        range.end()
+                    ),
+                })
+            }
+        };
+        check("BROTLI_QUALITY", self.quality, Self::QUALITY_RANGE)?;
+        check(
+            "BROTLI_STATIC_QUALITY",
+            self.static_quality,
+            Self::QUALITY_RANGE,
+        )?;
+        check("BROTLI_WINDOW", self.window, Self::WINDOW_RANGE)?;
+        Ok(self)
+    }
+}
+
+impl Default for BrotliSettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Content type for PHP responses that don't send one
+/// (`DEFAULT_CONTENT_TYPE`, `DEFAULT_CHARSET`).
+///
+/// When set explicitly, also given to PHP as `default_mimetype` /
+/// `default_charset`, so `header('Content-Type: text/html')` gets the same
+/// charset appended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DefaultContentType {
+    pub mime: String,
+    /// None = no charset parameter (`DEFAULT_CHARSET=off`).
+    pub charset: Option<String>,
+    /// DEFAULT_CONTENT_TYPE was set, so it overrides php.ini's `default_mimetype`.
+    pub mime_set: bool,
+    /// DEFAULT_CHARSET was set, so it overrides php.ini's `default_charset`.
+    pub charset_set: bool,
+}
+
+impl DefaultContentType {
+    /// Load from DEFAULT_CONTENT_TYPE and DEFAULT_CHARSET.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Ok(Self {
+            mime_set: env_var("DEFAULT_CONTENT_TYPE").is_some(),
+            charset_set: env_var("DEFAULT_CHARSET").is_some(),
+            ..Self::parse(
+                &env_or("DEFAULT_CONTENT_TYPE", "text/html"),
+                &env_or("DEFAULT_CHARSET", "utf-8"),
+            )?
+        })
+    }
+
+    /// Parse a `type/subtype` and a charset name (`off` or empty = none).
+    pub fn parse(mime: &str, charset: &str) -> Result<Self, ConfigError> {
+        let mime = mime.trim();
+        let valid_mime = mime
+            .split_once('/')
+            .is_some_and(|(t, s)| is_token(t) && is_token(s));
+        if !valid_mime {
+            return Err(ConfigError::Invalid {
+                key: "DEFAULT_CONTENT_TYPE".into(),
+                message: format!("expected type/subtype without parameters, got '{mime}'"),
+            });
+        }
+
+        let charset = charset.trim();
+        let charset = match charset.to_lowercase().as_str() {
+            "" | "off" => None,
+            _ if is_token(charset) => Some(charset.to_string()),
+            _ => {
+                return Err(ConfigError::Invalid {
+                    key: "DEFAULT_CHARSET".into(),
+                    message: format!("invalid charset '{charset}'"),
+                })
+            }
+        };
+        Ok(Self {
+            mime: mime.to_string(),
+            charset,
+            mime_set: true,
+            charset_set: true,
+        })
+    }
+
+    /// Header value, e.g. `text/html; charset=utf-8`.
+    pub fn header_value(&self) -> String {
+        match &self.charset {
+            Some(charset) => format!("{}; charset={}", self.mime, charset),
+            None => self.mime.clone(),
+        }
+    }
+
+    /// The matching PHP ini settings, for the values set explicitly.
+    ///
+    /// Unset ones are left out so php.ini keeps deciding them.
+    pub fn php_ini(&self) -> Vec<(String, String)> {
+        let mut ini = Vec::with_capacity(2);
+        if self.mime_set {
+            ini.push(("default_mimetype".into(), format!("\"{}\"", self.mime)));
+        }
+        if self.charset_set {
+            ini.push((
+                "default_charset".into(),
+                format!("\"{}\"", self.charset.as_deref().unwrap_or("")),
+            ));
         }
+        ini
     }
 }
 
+impl Default for DefaultContentType {
+    fn default() -> Self {
+        Self {
+            mime: "text/html".into(),
+            charset: Some("utf-8".into()),
+            mime_set: false,
+            charset_set: false,
+        }
+    }
+}
+
+/// RFC 9110 token (used for media types and charset names).
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
 /// Server configuration loaded from environment.
 #[derive(Clone, Debug)]
 pub struct ServerConfig {
@@ -124,22 +616,115 @@ pub struct ServerConfig {
     pub index_file: Option<String>,
     /// Internal server address for /health and /metrics.
     pub internal_addr: Option<SocketAddr>,
+    /// Bearer token required for non-probe internal endpoints (None = off).
+    pub internal_token: Option<String>,
+    /// Networks allowed to call non-probe internal endpoints (empty = any).
+    pub internal_allow: TrustedProxies,
     /// Directory with custom error pages.
     pub error_pages_dir: Option<PathBuf>,
     /// Graceful shutdown drain timeout.
     pub drain_timeout: Duration,
+    /// Time in-flight requests get after GOAWAY before a connection is closed.
+    pub goaway_window: Duration,
     /// Static file cache TTL.
     pub static_cache_ttl: StaticCacheTtl,
     /// Request timeout.
     pub request_timeout: RequestTimeout,
+    /// Reject requests whose estimated queue wait exceeds this (off by
+    /// default; never above the request timeout).
+    pub max_queue_wait: OptionalDuration,
+    /// How far heartbeats may extend a request past its timeout (off = no cap).
+    pub max_request_extension: OptionalDuration,
+    /// How long a script may run after tokio_finish_request() (off = no limit).
+    pub max_background_time: OptionalDuration,
     /// SSE (Server-Sent Events) timeout.
     pub sse_timeout: SseTimeout,
     /// Header read timeout (Slowloris protection).
     pub header_timeout: Duration,
-    /// Keep-alive idle timeout.
+    /// Wait for the first request on a new plain connection.
     pub idle_timeout: Duration,
+    /// Close connections with no request in flight for this long (zero = off).
+    pub keepalive_timeout: Duration,
+    /// TLS handshake timeout.
+    pub tls_handshake_timeout: Duration,
+    /// Requests slower than this are logged as warnings (zero = off).
+    pub slow_request_threshold: Duration,
+    /// Queue fill (percent) that makes /ready fail once sustained (0 = off).
+    pub ready_queue_threshold: u8,
+    /// How long the queue must stay above the threshold.
+    pub ready_saturation_window: Duration,
+    /// HTTP/2 stream limit, flow-control windows and frame size.
+    pub http2: Http2Settings,
+    /// Brotli quality and window.
+    pub brotli: BrotliSettings,
+    /// Content-Type for PHP responses without one.
+    pub default_content_type: DefaultContentType,
     /// TLS configuration.
     pub tls: TlsConfig,
+    /// Path prefixes disabled at startup (kill-switch).
+    pub disabled_paths: Vec<String>,
+    /// Status returned for disabled paths (default: 503).
+    pub disabled_path_status: u16,
+    /// Request shedding under memory pressure (off by default).
+    pub memory_pressure: MemoryPressureSettings,
+    /// Max query/form/cookie variables parsed per request.
+    pub max_input_vars: usize,
+    /// Max bytes reserved for a request body from its Content-Length (0 = off).
+    pub body_prealloc_max: usize,
+    /// Max body size passed to PHP as php://input (0 = unlimited).
+    pub raw_body_max: usize,
+    /// Max request body size; larger bodies get 413 (0 = unlimited).
+    pub max_body_size: usize,
+    /// Allowed multipart file types (empty = any).
+    pub upload_types: UploadTypes,
+    /// Expose a B3 header value to PHP for downstream calls.
+    pub trace_b3_egress: bool,
+    /// Answer 421 for hosts the TLS certificate doesn't cover.
+    pub tls_check_authority: bool,
+    /// Include script error details in 500 responses (development only).
+    pub expose_errors: bool,
+    /// `Server` response header value (None = not sent).
+    pub server_header: Option<String>,
+    /// Proxies allowed to set X-Forwarded-* headers.
+    pub trusted_proxies: TrustedProxies,
+    /// Max concurrent connections across all workers (0 = unlimited).
+    pub max_connections: usize,
+    /// Max concurrent connections per accept worker (0 = unlimited).
+    pub max_connections_per_worker: usize,
+    /// Max concurrent connections per client IP (0 = unlimited).
+    pub max_connections_per_ip: usize,
+    /// Timing-Allow-Origin header (None = not sent).
+    pub timing_allow_origin: Option<TimingAllowOrigin>,
+    /// URI prefixes with directory listings enabled (empty = off).
+    pub autoindex: Vec<String>,
+    /// URI prefixes queued in the reserved high-priority lane (empty = off).
+    pub priority_paths: Vec<String>,
+    /// Handling of directory URIs without a trailing slash (default: 404).
+    pub directory_request: DirectoryRequest,
+    /// Per-request CSP nonce (None = disabled).
+    pub csp_nonce: Option<CspNonce>,
+    /// Static file Content-Type overrides (defaults + MIME_TYPES).
+    pub mime_types: MimeTypes,
+    /// MIME types compressed with Brotli (defaults + COMPRESS_TYPES).
+    pub compress_types: CompressibleTypes,
+    /// Memory for Brotli-compressed static files, in bytes (0 = off).
+    pub static_compress_cache_size: usize,
+    /// Fingerprinted asset patterns served as immutable (empty = off).
+    pub immutable_assets: ImmutableAssets,
+    /// Device classes with `name.<class>.ext` static variants (empty = off).
+    pub ua_variants: UaVariants,
+    /// Canonical URLs sent as `Link` headers, by exact path (empty = off).
+    pub canonical_links: CanonicalLinks,
+    /// Route templates for the per-route request metric (empty = off).
+    pub metrics_routes: RouteLabels,
+    /// URI prefixes forwarded to HTTP upstreams (empty = off).
+    pub proxy_routes: ProxyRoutes,
+    /// Idle upstream connections kept per proxy upstream.
+    pub proxy_pool_size: usize,
+    /// Methods passed to PHP; the rest answer 405.
+    pub allowed_methods: AllowedMethods,
+    /// Tokio runtime flavor (default: current-thread).
+    pub runtime_threads: RuntimeThreads,
 }
 
 impl ServerConfig {
@@ -150,11 +735,17 @@ impl ServerConfig {
             document_root: PathBuf::from(env_or("DOCUMENT_ROOT", "/var/www/html")),
             index_file: env_opt("INDEX_FILE"),
             internal_addr: Self::parse_addr_opt("INTERNAL_ADDR")?,
+            internal_token: env_opt("INTERNAL_TOKEN"),
+            internal_allow: Self::parse_list_with("INTERNAL_ALLOW", TrustedProxies::parse)?,
             error_pages_dir: env_opt("ERROR_PAGES_DIR").map(PathBuf::from),
             drain_timeout: Duration::from_secs(Self::parse_u64(
                 "DRAIN_TIMEOUT_SECS",
                 DEFAULT_DRAIN_TIMEOUT_SECS,
             )?),
+            goaway_window: Duration::from_secs(Self::parse_u64(
+                "GOAWAY_WINDOW_SECS",
+                DEFAULT_GOAWAY_WINDOW_SECS,
+            )?),
             static_cache_ttl: OptionalDuration::parse(
                 &env_or("STATIC_CACHE_TTL", "1d"),
                 DEFAULT_STATIC_CACHE_TTL_SECS,
@@ -163,6 +754,12 @@ impl ServerConfig {
                 &env_or("REQUEST_TIMEOUT", "2m"),
                 DEFAULT_REQUEST_TIMEOUT_SECS,
             ),
+            max_queue_wait: OptionalDuration::parse(&env_or("MAX_QUEUE_WAIT", "off"), 0),
+            max_request_extension: OptionalDuration::parse(
+                &env_or("MAX_REQUEST_EXTENSION", "off"),
+                0,
+            ),
+            max_background_time: OptionalDuration::parse(&env_or("MAX_BACKGROUND_TIME", "off"), 0),
             sse_timeout: OptionalDuration::parse(
                 &env_or("SSE_TIMEOUT", "30m"),
                 DEFAULT_SSE_TIMEOUT_SECS,
@@ -175,10 +772,176 @@ impl ServerConfig {
                 "IDLE_TIMEOUT_SECS",
                 DEFAULT_IDLE_TIMEOUT_SECS,
             )?),
-            tls: TlsConfig::from_env(),
+            keepalive_timeout: Duration::from_secs(Self::parse_u64(
+                "KEEPALIVE_TIMEOUT_SECS",
+                DEFAULT_KEEPALIVE_TIMEOUT_SECS,
+            )?),
+            tls_handshake_timeout: Duration::from_secs(Self::parse_u64(
+                "TLS_HANDSHAKE_TIMEOUT_SECS",
+                DEFAULT_TLS_HANDSHAKE_TIMEOUT_SECS,
+            )?),
+            slow_request_threshold: Duration::from_millis(Self::parse_u64(
+                "SLOW_REQUEST_THRESHOLD_MS",
+                DEFAULT_SLOW_REQUEST_THRESHOLD_MS,
+            )?),
+            ready_queue_threshold: Self::parse_percent(
+                "READY_QUEUE_THRESHOLD",
+                DEFAULT_READY_QUEUE_THRESHOLD,
+            )?,
+            ready_saturation_window: Duration::from_secs(Self::parse_u64(
+                "READY_SATURATION_WINDOW_SECS",
+                DEFAULT_READY_SATURATION_WINDOW_SECS,
+            )?),
+            http2: Http2Settings::from_env()?,
+            brotli: BrotliSettings::from_env()?,
+            default_content_type: DefaultContentType::from_env()?,
+            tls: TlsConfig::from_env()?,
+            disabled_paths: env_opt("DISABLED_PATHS")
+                .map(|raw| parse_list(&raw))
+                .unwrap_or_default(),
+            disabled_path_status: Self::parse_error_status(
+                "DISABLED_PATH_STATUS",
+                DEFAULT_DISABLED_PATH_STATUS,
+            )?,
+            memory_pressure: MemoryPressureSettings::from_env()?,
+            max_input_vars: Self::parse_u64("MAX_INPUT_VARS", DEFAULT_MAX_INPUT_VARS)? as usize,
+            body_prealloc_max: Self::parse_u64("BODY_PREALLOC_MAX", DEFAULT_BODY_PREALLOC_MAX)?
+                as usize,
+            raw_body_max: Self::parse_u64("RAW_BODY_MAX", 0)? as usize,
+            max_body_size: Self::parse_u64("MAX_BODY_SIZE", 0)? as usize,
+            upload_types: Self::parse_list_with("UPLOAD_ALLOWED_TYPES", |types| {
+                UploadTypes::parse(types, env_bool("UPLOAD_SNIFF", true))
+            })?,
+            trace_b3_egress: env_bool("TRACE_B3_EGRESS", false),
+            tls_check_authority: env_bool("TLS_CHECK_AUTHORITY", false),
+            expose_errors: env_bool("EXPOSE_ERRORS", false),
+            server_header: Self::parse_server_header("SERVER_HEADER")?,
+            trusted_proxies: Self::parse_list_with("TRUSTED_PROXIES", TrustedProxies::parse)?,
+            max_connections: Self::parse_u64("MAX_CONNECTIONS", DEFAULT_MAX_CONNECTIONS)? as usize,
+            max_connections_per_worker: Self::parse_u64(
+                "MAX_CONNECTIONS_PER_WORKER",
+                DEFAULT_MAX_CONNECTIONS_PER_WORKER,
+            )? as usize,
+            max_connections_per_ip: Self::parse_u64(
+                "MAX_CONNECTIONS_PER_IP",
+                DEFAULT_MAX_CONNECTIONS_PER_IP,
+            )? as usize,
+            timing_allow_origin: TimingAllowOrigin::from_env()?,
+            autoindex: env_opt("AUTOINDEX")
+                .map(|raw| parse_list(&raw))
+                .unwrap_or_default(),
+            priority_paths: env_opt("PRIORITY_PATHS")
+                .map(|raw| parse_list(&raw))
+                .unwrap_or_default(),
+            directory_request: Self::parse_directory_request("DIRECTORY_REQUEST")?,
+            csp_nonce: Self::parse_csp_nonce()?,
+            mime_types: Self::parse_list_with("MIME_TYPES", MimeTypes::parse)?,
+            compress_types: Self::parse_list_with("COMPRESS_TYPES", CompressibleTypes::parse)?,
+            static_compress_cache_size: Self::parse_u64(
+                "STATIC_COMPRESS_CACHE_SIZE",
+                DEFAULT_COMPRESSED_CACHE_SIZE as u64,
+            )? as usize,
+            immutable_assets: Self::parse_list_with("STATIC_IMMUTABLE", ImmutableAssets::parse)?,
+            ua_variants: Self::parse_list_with("STATIC_UA_VARIANTS", UaVariants::parse)?,
+            canonical_links: Self::parse_list_with("CANONICAL_LINKS", CanonicalLinks::parse)?,
+            metrics_routes: Self::parse_list_with("METRICS_ROUTES", RouteLabels::parse)?,
+            proxy_routes: Self::parse_list_with("PROXY_ROUTES", ProxyRoutes::parse)?,
+            proxy_pool_size: Self::parse_u64("PROXY_POOL_SIZE", DEFAULT_PROXY_POOL_SIZE)? as usize,
+            allowed_methods: Self::parse_list_with("ALLOWED_METHODS", |methods| {
+                // Unset keeps the default set
+                if methods.is_empty() {
+                    Ok(AllowedMethods::default())
+                } else {
+                    AllowedMethods::parse(methods)
+                }
+            })?,
+            runtime_threads: Self::parse_runtime_threads("RUNTIME_THREADS")?,
+        })
+    }
+
+    fn parse_runtime_threads(key: &str) -> Result<RuntimeThreads, ConfigError> {
+        RuntimeThreads::parse(&env_or(key, "current")).map_err(|message| ConfigError::Invalid {
+            key: key.into(),
+            message,
+        })
+    }
+
+    /// Parse a comma-separated list (empty when unset) with `parse`; its
+    /// error message is reported against `key`.
+    fn parse_list_with<T>(
+        key: &str,
+        parse: impl FnOnce(&[String]) -> Result<T, String>,
+    ) -> Result<T, ConfigError> {
+        let entries = env_opt(key).map(|raw| parse_list(&raw)).unwrap_or_default();
+        parse(&entries).map_err(|message| ConfigError::Invalid {
+            key: key.into(),
+            message,
+        })
+    }
+
+    /// CSP_NONCE=1 exposes the nonce to PHP; CSP_POLICY also sends the header.
+    fn parse_csp_nonce() -> Result<Option<CspNonce>, ConfigError> {
+        let policy = env_opt("CSP_POLICY");
+        if policy.is_none() && !env_bool("CSP_NONCE", false) {
+            return Ok(None);
+        }
+        CspNonce::new(policy)
+            .map(Some)
+            .map_err(|message| ConfigError::Invalid {
+                key: "CSP_POLICY".into(),
+                message,
+            })
+    }
+
+    fn parse_server_header(key: &str) -> Result<Option<String>, ConfigError> {
+        Self::server_header_value(env_var(key).as_deref()).map_err(|message| ConfigError::Invalid {
+            key: key.into(),
+            message,
         })
     }
 
+    /// Unset keeps the default; empty or "off" suppresses the header.
+    fn server_header_value(raw: Option<&str>) -> Result<Option<String>, String> {
+        let value = match raw.map(str::trim) {
+            None => return Ok(Some(DEFAULT_SERVER_HEADER.to_string())),
+            Some("") => return Ok(None),
+            Some(v) if v.eq_ignore_ascii_case("off") => return Ok(None),
+            Some(v) => v,
+        };
+        http::HeaderValue::from_str(value)
+            .map(|_| Some(value.to_string()))
+            .map_err(|_| format!("invalid header value {value:?}"))
+    }
+
+    fn parse_directory_request(key: &str) -> Result<DirectoryRequest, ConfigError> {
+        let raw = env_or(key, "not_found");
+        Self::directory_request_mode(&raw).ok_or_else(|| ConfigError::Invalid {
+            key: key.into(),
+            message: format!("expected not_found, redirect, index or forbidden, got '{raw}'"),
+        })
+    }
+
+    fn directory_request_mode(raw: &str) -> Option<DirectoryRequest> {
+        match raw.trim().to_lowercase().as_str() {
+            "not_found" | "404" => Some(DirectoryRequest::NotFound),
+            "redirect" => Some(DirectoryRequest::Redirect),
+            "index" => Some(DirectoryRequest::Index),
+            "forbidden" | "403" => Some(DirectoryRequest::Forbidden),
+            _ => None,
+        }
+    }
+
+    fn parse_error_status(key: &str, default: u16) -> Result<u16, ConfigError> {
+        let status = Self::parse_u64(key, default as u64)?;
+        if !(400..600).contains(&status) {
+            return Err(ConfigError::Invalid {
+                key: key.into(),
+                message: format!("expected a 4xx or 5xx status code, got {status}"),
+            });
+        }
+        Ok(status as u16)
+    }
+
     fn parse_addr(key: &str, default: &str) -> Result<SocketAddr, ConfigError> {
         let raw = env_or(key, default);
         raw.parse().map_err(|e| ConfigError::Parse {
@@ -208,12 +971,170 @@ impl ServerConfig {
             error: format!("{e}"),
         })
     }
+
+    fn parse_percent(key: &str, default: u64) -> Result<u8, ConfigError> {
+        match Self::parse_u64(key, default)? {
+            percent @ 0..=100 => Ok(percent as u8),
+            percent => Err(ConfigError::Invalid {
+                key: key.into(),
+                message: format!("must be a percentage (0-100), got {percent}"),
+            }),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_http2_settings_validation() {
+        let defaults = Http2Settings::default();
+        assert_eq!(defaults.validate().unwrap(), defaults);
+
+        let tuned = Http2Settings {
+            max_concurrent_streams: 1000,
+            initial_stream_window_size: 4 * 1024 * 1024,
+            initial_connection_window_size: 16 * 1024 * 1024,
+            max_frame_size: 16_777_215,
+        };
+        assert!(tuned.validate().is_ok());
+
+        let invalid = [
+            Http2Settings {
+                max_concurrent_streams: 0,
+                ..defaults
+            },
+            Http2Settings {
+                initial_stream_window_size: 1 << 31,
+                ..defaults
+            },
+            Http2Settings {
+                initial_connection_window_size: 65_534,
+                ..defaults
+            },
+            Http2Settings {
+                max_frame_size: 16_383,
+                ..defaults
+            },
+            Http2Settings {
+                max_frame_size: 1 << 24,
+                ..defaults
+            },
+        ];
+        for settings in invalid {
+            assert!(
+                matches!(settings.validate(), Err(ConfigError::Invalid { .. })),
+                "{settings:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_content_type() {
+        let defaults = DefaultContentType::default();
+        assert_eq!(defaults.header_value(), "text/html; charset=utf-8");
+        assert_eq!(
+            DefaultContentType::parse("text/html", "utf-8")
+                .unwrap()
+                .header_value(),
+            defaults.header_value()
+        );
+        // Unset values leave php.ini's default_mimetype/default_charset alone
+        assert!(defaults.php_ini().is_empty());
+        let charset_only = DefaultContentType {
+            charset: Some("ISO-8859-1".into()),
+            charset_set: true,
+            ..DefaultContentType::default()
+        };
+        assert_eq!(
+            charset_only.php_ini(),
+            [("default_charset".to_string(), "\"ISO-8859-1\"".to_string())]
+        );
+
+        let latin1 = DefaultContentType::parse(" text/plain ", "ISO-8859-1").unwrap();
+        assert_eq!(latin1.header_value(), "text/plain; charset=ISO-8859-1");
+        assert_eq!(
+            latin1.php_ini()[1],
+            ("default_charset".to_string(), "\"ISO-8859-1\"".to_string())
+        );
+
+        let bare = DefaultContentType::parse("application/json", "off").unwrap();
+        assert_eq!(bare.header_value(), "application/json");
+        assert_eq!(bare.php_ini()[1].1, "\"\"");
+
+        for (mime, charset) in [
+            ("text", "utf-8"),
+            ("text/html; charset=utf-8", "utf-8"),
+            ("text/html", "utf 8"),
+            ("text/html", "utf-8\""),
+        ] {
+            assert!(
+                matches!(
+                    DefaultContentType::parse(mime, charset),
+                    Err(ConfigError::Invalid { .. })
+                ),
+                "{mime} {charset}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_brotli_settings_validation() {
+        let defaults = BrotliSettings::default();
+        assert_eq!(defaults.validate().unwrap(), defaults);
+
+        let tuned = BrotliSettings {
+            quality: 0,
+            static_quality: 11,
+            window: 24,
+            min_size: 1024,
+            static_min_size: 0,
+        };
+        assert!(tuned.validate().is_ok());
+
+        let invalid = [
+            BrotliSettings {
+                quality: 12,
+                ..defaults
+            },
+            BrotliSettings {
+                static_quality: 12,
+                ..defaults
+            },
+            BrotliSettings {
+                window: 9,
+                ..defaults
+            },
+            BrotliSettings {
+                window: 25,
+                ..defaults
+            },
+        ];
+        for settings in invalid {
+            assert!(
+                matches!(settings.validate(), Err(ConfigError::Invalid { .. })),
+                "{settings:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_memory_pressure_validation() {
+        let settings = |high_percent, critical_percent| MemoryPressureSettings {
+            high_percent,
+            critical_percent,
+            shed_paths: Vec::new(),
+        };
+        assert!(settings(85, 95).validate().is_ok());
+        assert!(settings(0, 95).validate().is_ok());
+        assert!(settings(85, 0).validate().is_ok());
+        assert!(matches!(
+            settings(90, 90).validate(),
+            Err(ConfigError::Invalid { .. })
+        ));
+    }
+
     // OptionalDuration tests
     #[test]
     fn test_optional_duration_disabled() {
@@ -308,6 +1229,7 @@ mod tests {
         let tls = TlsConfig {
             cert_path: Some(PathBuf::from("/path/to/cert.pem")),
             key_path: Some(PathBuf::from("/path/to/key.pem")),
+            auto: false,
             enabled: true,
         };
         assert!(tls.is_enabled());
@@ -318,6 +1240,7 @@ mod tests {
         let tls = TlsConfig {
             cert_path: Some(PathBuf::from("/path/to/cert.pem")),
             key_path: None,
+            auto: false,
             enabled: false,
         };
         assert!(!tls.is_enabled());
@@ -328,8 +1251,104 @@ mod tests {
         let tls = TlsConfig {
             cert_path: None,
             key_path: Some(PathBuf::from("/path/to/key.pem")),
+            auto: false,
             enabled: false,
         };
         assert!(!tls.is_enabled());
     }
+
+    #[test]
+    fn test_timing_allow_origin_list() {
+        assert_eq!(
+            TimingAllowOrigin::parse_origins("https://app.example.com, http://localhost:3000")
+                .unwrap(),
+            "https://app.example.com, http://localhost:3000"
+        );
+        assert_eq!(TimingAllowOrigin::parse_origins(" * ").unwrap(), "*");
+    }
+
+    #[test]
+    fn test_timing_allow_origin_invalid() {
+        assert!(TimingAllowOrigin::parse_origins("").is_err());
+        assert!(TimingAllowOrigin::parse_origins("*, https://a.com").is_err());
+        assert!(TimingAllowOrigin::parse_origins("app.example.com").is_err());
+        assert!(TimingAllowOrigin::parse_origins("https://a.com/path").is_err());
+    }
+
+    #[test]
+    fn test_timing_allow_origin_scope() {
+        let tao = |scope| TimingAllowOrigin {
+            value: "*".into(),
+            scope,
+        };
+        assert!(tao(TimingAllowScope::Static).applies_to(false));
+        assert!(!tao(TimingAllowScope::Static).applies_to(true));
+        assert!(tao(TimingAllowScope::Php).applies_to(true));
+        assert!(!tao(TimingAllowScope::Php).applies_to(false));
+        assert!(tao(TimingAllowScope::All).applies_to(true));
+        assert_eq!(
+            TimingAllowOrigin::parse_scope("PHP"),
+            Some(TimingAllowScope::Php)
+        );
+        assert_eq!(TimingAllowOrigin::parse_scope("images"), None);
+    }
+
+    #[test]
+    fn test_directory_request_mode() {
+        assert_eq!(
+            ServerConfig::directory_request_mode("Redirect"),
+            Some(DirectoryRequest::Redirect)
+        );
+        assert_eq!(
+            ServerConfig::directory_request_mode("index"),
+            Some(DirectoryRequest::Index)
+        );
+        assert_eq!(
+            ServerConfig::directory_request_mode("403"),
+            Some(DirectoryRequest::Forbidden)
+        );
+        assert_eq!(
+            ServerConfig::directory_request_mode("not_found"),
+            Some(DirectoryRequest::NotFound)
+        );
+        assert_eq!(ServerConfig::directory_request_mode("list"), None);
+    }
+
+    #[test]
+    fn test_server_header_value() {
+        assert_eq!(
+            ServerConfig::server_header_value(None),
+            Ok(Some(DEFAULT_SERVER_HEADER.to_string()))
+        );
+        assert_eq!(
+            ServerConfig::server_header_value(Some(" acme ")),
+            Ok(Some("acme".to_string()))
+        );
+        assert_eq!(ServerConfig::server_header_value(Some("")), Ok(None));
+        assert_eq!(ServerConfig::server_header_value(Some("OFF")), Ok(None));
+        assert!(ServerConfig::server_header_value(Some("a\nb")).is_err());
+    }
+
+    #[test]
+    fn test_runtime_threads() {
+        assert_eq!(RuntimeThreads::parse(""), Ok(RuntimeThreads::CurrentThread));
+        assert_eq!(
+            RuntimeThreads::parse("0"),
+            Ok(RuntimeThreads::CurrentThread)
+        );
+        assert_eq!(
+            RuntimeThreads::parse("Current"),
+            Ok(RuntimeThreads::CurrentThread)
+        );
+        assert_eq!(
+            RuntimeThreads::parse(" 4 "),
+            Ok(RuntimeThreads::MultiThread(4))
+        );
+        assert_eq!(
+            RuntimeThreads::parse("auto"),
+            Ok(RuntimeThreads::MultiThread(num_cpus::get()))
+        );
+        assert!(RuntimeThreads::parse("many").is_err());
+        assert!(RuntimeThreads::parse("-1").is_err());
+    }
 }
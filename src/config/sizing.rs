@@ -0,0 +1,360 @@
+//! Worker and queue auto-sizing.
+//!
+//! `PHP_WORKERS=0` and `QUEUE_CAPACITY=0` leave the numbers to heuristics;
+//! the automatic queue holds `QUEUE_MULTIPLIER` requests per worker.
+//! [`Sizing`] records what they picked and why, along with the cgroup limits
+//! seen at startup, so the decision can be logged and exported
+//! (`SIZING_REPORT`).
+//!
+//! With `PHP_MEMORY_PER_WORKER_MB` set, the automatic worker count is also
+//! capped so the workers fit in the cgroup memory limit, keeping
+//! [`MEMORY_HEADROOM_PERCENT`] of it free.
+
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+/// cgroup v1 reports "no limit" as a page-rounded `i64::MAX`.
+const UNLIMITED_MEMORY: u64 = 1 << 60;
+
+/// Share of the memory limit not budgeted for PHP workers (server, OPcache
+/// shared memory, request buffers).
+pub const MEMORY_HEADROOM_PERCENT: u64 = 20;
+
+/// Queue slots per worker when neither `QUEUE_CAPACITY` nor
+/// `QUEUE_MULTIPLIER` is set.
+pub const DEFAULT_QUEUE_MULTIPLIER: usize = 100;
+
+/// Resource limits of the container the server runs in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CgroupLimits {
+    /// CPU quota in cores (`None` = unlimited).
+    pub cpu_quota: Option<f64>,
+    /// Memory limit in bytes (`None` = unlimited).
+    pub memory_limit: Option<u64>,
+}
+
+impl CgroupLimits {
+    /// Read limits from `/sys/fs/cgroup`.
+    pub fn detect() -> Self {
+        Self::read_from(Path::new("/sys/fs/cgroup"))
+    }
+
+    /// Read limits from a cgroup v2 or v1 hierarchy mounted at `root`.
+    pub fn read_from(root: &Path) -> Self {
+        let read = |file: &str| fs::read_to_string(root.join(file)).ok();
+
+        // v2: "max 100000" or "150000 100000"
+        let cpu_quota = match read("cpu.max") {
+            Some(max) => {
+                let mut parts = max.split_whitespace();
+                quota_cores(parts.next(), parts.next())
+            }
+            None => ["cpu", "cpu,cpuacct"].iter().find_map(|dir| {
+                let quota = read(&format!("{dir}/cpu.cfs_quota_us"))?;
+                let period = read(&format!("{dir}/cpu.cfs_period_us"))?;
+                quota_cores(Some(quota.trim()), Some(period.trim()))
+            }),
+        };
+
+        let memory_limit = read("memory.max")
+            .or_else(|| read("memory/memory.limit_in_bytes"))
+            .and_then(|limit| limit.trim().parse::<u64>().ok())
+            .filter(|&bytes| bytes < UNLIMITED_MEMORY);
+
+        Self {
+            cpu_quota,
+            memory_limit,
+        }
+    }
+
+    /// Current memory usage in bytes from a v2 or v1 hierarchy at `root`.
+    pub fn memory_usage(root: &Path) -> Option<u64> {
+        fs::read_to_string(root.join("memory.current"))
+            .or_else(|_| fs::read_to_string(root.join("memory/memory.usage_in_bytes")))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+}
+
+/// Quota/period in cores; "max" or a negative quota means unlimited.
+fn quota_cores(quota: Option<&str>, period: Option<&str>) -> Option<f64> {
+    let quota: i64 = quota?.parse().ok()?;
+    let period: i64 = period?.parse().ok()?;
+    (quota > 0 && period > 0).then(|| quota as f64 / period as f64)
+}
+
+/// Workers that fit the memory limit minus headroom (at least one).
+fn memory_workers(limits: CgroupLimits, memory_per_worker: u64) -> Option<usize> {
+    let limit = limits.memory_limit?;
+    if memory_per_worker == 0 {
+        return None;
+    }
+    let budget = limit / 100 * (100 - MEMORY_HEADROOM_PERCENT);
+    Some(((budget / memory_per_worker) as usize).max(1))
+}
+
+/// What decided the worker count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkerSource {
+    /// Set with `PHP_WORKERS`.
+    Explicit,
+    /// Rounded-up cgroup CPU quota.
+    CgroupCpuQuota,
+    /// Number of CPUs visible to the process.
+    CpuCount,
+    /// Capped so `PHP_MEMORY_PER_WORKER_MB` per worker fits the memory limit.
+    CgroupMemoryLimit,
+    /// Forced to one by the `debug-profile` feature.
+    DebugProfile,
+}
+
+impl WorkerSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Explicit => "PHP_WORKERS",
+            Self::CgroupCpuQuota => "cgroup_cpu_quota",
+            Self::CpuCount => "cpu_count",
+            Self::CgroupMemoryLimit => "cgroup_memory_limit",
+            Self::DebugProfile => "debug_profile",
+        }
+    }
+}
+
+/// What decided the queue capacity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueueSource {
+    /// Set with `QUEUE_CAPACITY`.
+    Explicit,
+    /// `QUEUE_MULTIPLIER` slots per worker.
+    PerWorker,
+}
+
+impl QueueSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Explicit => "QUEUE_CAPACITY",
+            Self::PerWorker => "workers_x_multiplier",
+        }
+    }
+}
+
+/// Effective worker and queue sizing, with the reasons behind it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sizing {
+    pub limits: CgroupLimits,
+    pub workers: NonZeroUsize,
+    pub worker_source: WorkerSource,
+    pub queue_capacity: NonZeroUsize,
+    pub queue_source: QueueSource,
+    /// Queue slots per worker when the capacity is automatic.
+    pub queue_multiplier: NonZeroUsize,
+    /// Estimated PHP memory per worker in bytes (0 = not set).
+    pub memory_per_worker: u64,
+}
+
+impl Sizing {
+    /// Resolve `PHP_WORKERS` / `QUEUE_CAPACITY` values (0 = auto).
+    ///
+    /// `memory_per_worker` (bytes, 0 = unknown) caps an automatic worker
+    /// count by the memory limit; an explicit `PHP_WORKERS` is left alone.
+    pub fn resolve(
+        workers: usize,
+        queue_capacity: usize,
+        limits: CgroupLimits,
+        cpus: usize,
+        memory_per_worker: u64,
+    ) -> Self {
+        let (workers, worker_source) = match (workers, limits.cpu_quota) {
+            (n, _) if n > 0 => (n, WorkerSource::Explicit),
+            // num_cpus may already account for the quota; attribute it either way
+            (_, Some(quota)) if (quota.ceil() as usize) <= cpus => {
+                (quota.ceil() as usize, WorkerSource::CgroupCpuQuota)
+            }
+            _ => (cpus, WorkerSource::CpuCount),
+        };
+        let (workers, worker_source) = match memory_workers(limits, memory_per_worker) {
+            Some(max) if worker_source != WorkerSource::Explicit && max < workers => {
+                (max, WorkerSource::CgroupMemoryLimit)
+            }
+            _ => (workers, worker_source),
+        };
+        Self {
+            memory_per_worker,
+            ..Self::with_workers(workers, worker_source, queue_capacity, limits)
+        }
+    }
+
+    /// Single worker for the `debug-profile` build.
+    pub fn debug_profile(queue_capacity: usize, limits: CgroupLimits) -> Self {
+        Self::with_workers(1, WorkerSource::DebugProfile, queue_capacity, limits)
+    }
+
+    /// Use `multiplier` slots per worker for an automatic queue capacity
+    /// (0 keeps the default of 100). An explicit `QUEUE_CAPACITY` wins.
+    pub fn with_queue_multiplier(mut self, multiplier: usize) -> Self {
+        let Some(multiplier) = NonZeroUsize::new(multiplier) else {
+            return self;
+        };
+        self.queue_multiplier = multiplier;
+        if self.queue_source == QueueSource::PerWorker {
+            self.queue_capacity = self.workers.saturating_mul(multiplier);
+        }
+        self
+    }
+
+    fn with_workers(
+        workers: usize,
+        worker_source: WorkerSource,
+        queue_capacity: usize,
+        limits: CgroupLimits,
+    ) -> Self {
+        let workers = NonZeroUsize::new(workers).unwrap_or(NonZeroUsize::MIN);
+        let queue_multiplier = NonZeroUsize::new(DEFAULT_QUEUE_MULTIPLIER).unwrap();
+        let (queue_capacity, queue_source) = match NonZeroUsize::new(queue_capacity) {
+            Some(n) => (n, QueueSource::Explicit),
+            None => (
+                workers.saturating_mul(queue_multiplier),
+                QueueSource::PerWorker,
+            ),
+        };
+        Self {
+            limits,
+            workers,
+            worker_source,
+            queue_capacity,
+            queue_source,
+            queue_multiplier,
+            memory_per_worker: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cgroup(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, contents) in files {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_cgroup_v2_limits() {
+        let dir = cgroup(&[
+            ("cpu.max", "150000 100000\n"),
+            ("memory.max", "536870912\n"),
+        ]);
+        let limits = CgroupLimits::read_from(dir.path());
+        assert_eq!(limits.cpu_quota, Some(1.5));
+        assert_eq!(limits.memory_limit, Some(512 * 1024 * 1024));
+
+        let dir = cgroup(&[("cpu.max", "max 100000\n"), ("memory.max", "max\n")]);
+        assert_eq!(CgroupLimits::read_from(dir.path()), CgroupLimits::default());
+    }
+
+    #[test]
+    fn test_cgroup_v1_limits() {
+        let dir = cgroup(&[
+            ("cpu,cpuacct/cpu.cfs_quota_us", "200000\n"),
+            ("cpu,cpuacct/cpu.cfs_period_us", "100000\n"),
+            ("memory/memory.limit_in_bytes", "9223372036854771712\n"),
+        ]);
+        let limits = CgroupLimits::read_from(dir.path());
+        assert_eq!(limits.cpu_quota, Some(2.0));
+        assert_eq!(limits.memory_limit, None);
+
+        let dir = cgroup(&[
+            ("cpu/cpu.cfs_quota_us", "-1\n"),
+            ("cpu/cpu.cfs_period_us", "100000\n"),
+        ]);
+        assert_eq!(CgroupLimits::read_from(dir.path()).cpu_quota, None);
+    }
+
+    #[test]
+    fn test_resolve_sizing() {
+        let dir = cgroup(&[
+            ("cpu.max", "150000 100000\n"),
+            ("memory.max", "1073741824\n"),
+        ]);
+        let limits = CgroupLimits::read_from(dir.path());
+
+        let sizing = Sizing::resolve(0, 0, limits, 16, 0);
+        assert_eq!(sizing.workers.get(), 2);
+        assert_eq!(sizing.worker_source, WorkerSource::CgroupCpuQuota);
+        assert_eq!(sizing.queue_capacity.get(), 200);
+        assert_eq!(sizing.queue_source, QueueSource::PerWorker);
+
+        let sizing = Sizing::resolve(0, 50, CgroupLimits::default(), 8, 0);
+        assert_eq!(sizing.workers.get(), 8);
+        assert_eq!(sizing.worker_source.as_str(), "cpu_count");
+        assert_eq!(sizing.queue_capacity.get(), 50);
+        assert_eq!(sizing.queue_source.as_str(), "QUEUE_CAPACITY");
+
+        let sizing = Sizing::resolve(3, 0, limits, 16, 0);
+        assert_eq!(sizing.workers.get(), 3);
+        assert_eq!(sizing.worker_source.as_str(), "PHP_WORKERS");
+        assert_eq!(sizing.queue_capacity.get(), 300);
+    }
+
+    #[test]
+    fn test_memory_caps_auto_workers() {
+        const MIB: u64 = 1024 * 1024;
+        // 8 CPUs, 1 GiB limit: 80% budget / 128 MiB = 6 workers
+        let limits = CgroupLimits {
+            cpu_quota: None,
+            memory_limit: Some(1024 * MIB),
+        };
+        let sizing = Sizing::resolve(0, 0, limits, 8, 128 * MIB);
+        assert_eq!(sizing.workers.get(), 6);
+        assert_eq!(sizing.worker_source.as_str(), "cgroup_memory_limit");
+        assert_eq!(sizing.queue_capacity.get(), 600);
+
+        // CPU is the tighter limit
+        let limits = CgroupLimits {
+            cpu_quota: Some(2.0),
+            memory_limit: Some(1024 * MIB),
+        };
+        let sizing = Sizing::resolve(0, 0, limits, 8, 128 * MIB);
+        assert_eq!(sizing.workers.get(), 2);
+        assert_eq!(sizing.worker_source, WorkerSource::CgroupCpuQuota);
+
+        // Tiny limit still gets one worker; explicit counts are not capped
+        let limits = CgroupLimits {
+            cpu_quota: None,
+            memory_limit: Some(64 * MIB),
+        };
+        assert_eq!(Sizing::resolve(0, 0, limits, 8, 128 * MIB).workers.get(), 1);
+        assert_eq!(Sizing::resolve(8, 0, limits, 8, 128 * MIB).workers.get(), 8);
+        // No estimate, or no limit: CPU decides
+        assert_eq!(Sizing::resolve(0, 0, limits, 8, 0).workers.get(), 8);
+        let unlimited = CgroupLimits::default();
+        assert_eq!(
+            Sizing::resolve(0, 0, unlimited, 8, 128 * MIB).workers.get(),
+            8
+        );
+    }
+
+    #[test]
+    fn test_queue_multiplier() {
+        let limits = CgroupLimits::default();
+        let sizing = Sizing::resolve(4, 0, limits, 8, 0).with_queue_multiplier(25);
+        assert_eq!(sizing.queue_capacity.get(), 100);
+        assert_eq!(sizing.queue_multiplier.get(), 25);
+        assert_eq!(sizing.queue_source.as_str(), "workers_x_multiplier");
+
+        // 0 keeps the default; an explicit capacity is left alone
+        let sizing = Sizing::resolve(4, 0, limits, 8, 0).with_queue_multiplier(0);
+        assert_eq!(sizing.queue_capacity.get(), 4 * DEFAULT_QUEUE_MULTIPLIER);
+        let sizing = Sizing::resolve(4, 50, limits, 8, 0).with_queue_multiplier(25);
+        assert_eq!(sizing.queue_capacity.get(), 50);
+        assert_eq!(sizing.queue_source, QueueSource::Explicit);
+    }
+}
@@ -0,0 +1,192 @@
+//! `Set-Cookie` header construction for middleware responses.
+
+use std::time::{Duration, SystemTime};
+
+use http::HeaderValue;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+use super::format_http_date;
+
+/// Bytes outside RFC 6265 `cookie-octet`, plus `%` so values decode
+/// unambiguously. Non-ASCII is always encoded.
+const COOKIE_VALUE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b',')
+    .add(b';')
+    .add(b'\\')
+    .add(b'%');
+
+/// `SameSite` cookie attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// Cross-site too; browsers require `Secure`, so it is always added.
+    None,
+}
+
+impl SameSite {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// Attributes for [`Response::set_cookie`](super::Response::set_cookie).
+///
+/// The default is a session cookie with no attributes.
+#[derive(Debug, Clone, Default)]
+pub struct CookieOptions {
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<Duration>,
+    expires: Option<SystemTime>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl CookieOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// `Max-Age` in whole seconds; zero deletes the cookie.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// `Expires`, for clients that ignore `Max-Age`.
+    pub fn with_expires(mut self, expires: SystemTime) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn with_http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Build the `Set-Cookie` value. The value is percent-encoded; an
+    /// invalid name, or a path/domain that would inject attributes, gives `None`.
+    pub fn header_value(&self, name: &str, value: &str) -> Option<HeaderValue> {
+        if name.is_empty() || !name.bytes().all(is_token_byte) {
+            return None;
+        }
+        let attribute_ok = |s: &str| !s.contains(';') && !s.bytes().any(|b| b.is_ascii_control());
+
+        let mut cookie = format!("{}={}", name, utf8_percent_encode(value, COOKIE_VALUE));
+        if let Some(ref path) = self.path {
+            if !attribute_ok(path) {
+                return None;
+            }
+            cookie.push_str("; Path=");
+            cookie.push_str(path);
+        }
+        if let Some(ref domain) = self.domain {
+            if !attribute_ok(domain) {
+                return None;
+            }
+            cookie.push_str("; Domain=");
+            cookie.push_str(domain);
+        }
+        if let Some(max_age) = self.max_age {
+            cookie.push_str(&format!("; Max-Age={}", max_age.as_secs()));
+        }
+        if let Some(expires) = self.expires {
+            cookie.push_str("; Expires=");
+            cookie.push_str(&format_http_date(expires));
+        }
+        if self.secure || self.same_site == Some(SameSite::None) {
+            cookie.push_str("; Secure");
+        }
+        if self.http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        if let Some(same_site) = self.same_site {
+            cookie.push_str("; SameSite=");
+            cookie.push_str(same_site.as_str());
+        }
+        HeaderValue::try_from(cookie).ok()
+    }
+}
+
+/// RFC 7230 `tchar`.
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_all_attributes() {
+        let options = CookieOptions::new()
+            .with_path("/")
+            .with_domain("example.com")
+            .with_max_age(Duration::from_secs(3600))
+            .with_expires(UNIX_EPOCH + Duration::from_secs(1705322445))
+            .with_secure(true)
+            .with_http_only(true)
+            .with_same_site(SameSite::Lax);
+        assert_eq!(
+            options.header_value("sid", "abc").unwrap(),
+            "sid=abc; Path=/; Domain=example.com; Max-Age=3600; \
+             Expires=Mon, 15 Jan 2024 12:40:45 GMT; Secure; HttpOnly; SameSite=Lax"
+        );
+    }
+
+    #[test]
+    fn test_value_percent_encoded() {
+        let value = CookieOptions::new()
+            .header_value("pref", "a b;c,\"d\"%é")
+            .unwrap();
+        assert_eq!(value, "pref=a%20b%3Bc%2C%22d%22%25%C3%A9");
+    }
+
+    #[test]
+    fn test_same_site_none_implies_secure() {
+        let options = CookieOptions::new().with_same_site(SameSite::None);
+        assert_eq!(
+            options.header_value("x", "1").unwrap(),
+            "x=1; Secure; SameSite=None"
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid() {
+        let options = CookieOptions::new();
+        assert!(options.header_value("", "v").is_none());
+        assert!(options.header_value("a b", "v").is_none());
+        assert!(options.header_value("a=b", "v").is_none());
+        let injected = CookieOptions::new().with_path("/; Domain=evil.com");
+        assert!(injected.header_value("a", "v").is_none());
+    }
+}
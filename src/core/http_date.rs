@@ -0,0 +1,159 @@
+//! HTTP-date (RFC 7231, section 7.1.1.1) formatting and parsing, shared by
+//! static file caching headers and `Set-Cookie` expiry.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Format SystemTime as HTTP-date (RFC 7231).
+/// Example: "Sun, 06 Nov 1994 08:49:37 GMT"
+pub fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    // Calculate date/time components
+    let days = secs / 86400;
+    let day_secs = secs % 86400;
+
+    let hours = day_secs / 3600;
+    let minutes = (day_secs % 3600) / 60;
+    let seconds = day_secs % 60;
+
+    // Calculate year/month/day from days since epoch
+    let mut y = 1970i64;
+    let mut remaining_days = days as i64;
+
+    loop {
+        let year_days = if y % 4 == 0 && (y % 100 != 0 || y % 400 == 0) {
+            366
+        } else {
+            365
+        };
+        if remaining_days < year_days {
+            break;
+        }
+        remaining_days -= year_days;
+        y += 1;
+    }
+
+    let is_leap = y % 4 == 0 && (y % 100 != 0 || y % 400 == 0);
+    let month_days: [i64; 12] = if is_leap {
+        [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
+    } else {
+        [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
+    };
+
+    let mut m = 1;
+    for (i, &days_in_month) in month_days.iter().enumerate() {
+        if remaining_days < days_in_month {
+            m = i + 1;
+            break;
+        }
+        remaining_days -= days_in_month;
+    }
+    let d = remaining_days + 1;
+
+    // Calculate day of week (0 = Thursday for Unix epoch)
+    // (days + 4) % 7 gives: 0=Sun, 1=Mon, ...
+    let dow = ((days + 4) % 7) as usize;
+    let day_names = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    let month_names = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        day_names[dow],
+        d,
+        month_names[m - 1],
+        y,
+        hours,
+        minutes,
+        seconds
+    )
+}
+
+/// Parse HTTP-date (RFC 7231) to SystemTime.
+/// Supports format: "Sun, 06 Nov 1994 08:49:37 GMT"
+pub fn parse_http_date(s: &str) -> Option<SystemTime> {
+    // Format: "Day, DD Mon YYYY HH:MM:SS GMT"
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return None;
+    }
+
+    let day: u64 = parts[1].parse().ok()?;
+    let month = match parts[2] {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year: i64 = parts[3].parse().ok()?;
+
+    let time_parts: Vec<&str> = parts[4].split(':').collect();
+    if time_parts.len() != 3 {
+        return None;
+    }
+    let hours: u64 = time_parts[0].parse().ok()?;
+    let minutes: u64 = time_parts[1].parse().ok()?;
+    let seconds: u64 = time_parts[2].parse().ok()?;
+
+    // Calculate days since epoch
+    let mut total_days: i64 = 0;
+    for y in 1970..year {
+        total_days += if y % 4 == 0 && (y % 100 != 0 || y % 400 == 0) {
+            366
+        } else {
+            365
+        };
+    }
+
+    let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days: [i64; 12] = if is_leap {
+        [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
+    } else {
+        [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
+    };
+
+    for m in 0..(month - 1) {
+        total_days += month_days[m as usize];
+    }
+    total_days += day as i64 - 1;
+
+    let total_secs = total_days as u64 * 86400 + hours * 3600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(total_secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_http_date() {
+        // Unix epoch
+        let epoch = UNIX_EPOCH;
+        assert_eq!(format_http_date(epoch), "Thu, 01 Jan 1970 00:00:00 GMT");
+
+        // Known date: 2024-01-15 12:40:45 UTC (timestamp 1705322445)
+        let time = UNIX_EPOCH + Duration::from_secs(1705322445);
+        assert_eq!(format_http_date(time), "Mon, 15 Jan 2024 12:40:45 GMT");
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        let date = "Mon, 15 Jan 2024 12:40:45 GMT";
+        let parsed = parse_http_date(date).unwrap();
+        let expected = UNIX_EPOCH + Duration::from_secs(1705322445);
+        assert_eq!(parsed, expected);
+    }
+}
@@ -23,11 +23,15 @@
 //! ```
 
 mod context;
+mod cookie;
 mod error;
+mod http_date;
 mod request;
 mod response;
 
 pub use context::{generate_span_id, generate_trace_id, Context, ContextBuilder, HttpVersion};
+pub use cookie::{CookieOptions, SameSite};
 pub use error::{Error, Result};
+pub use http_date::{format_http_date, parse_http_date};
 pub use request::Request;
 pub use response::{Response, ResponseBuilder};
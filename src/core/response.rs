@@ -4,11 +4,14 @@ use bytes::Bytes;
 use http::header::{self, HeaderName};
 use http::{HeaderMap, HeaderValue, StatusCode};
 
+use super::cookie::CookieOptions;
+
 /// Common header name constants for fast lookup.
 mod header_names {
     use super::*;
     pub static CONTENT_TYPE: HeaderName = header::CONTENT_TYPE;
     pub static RETRY_AFTER: HeaderName = header::RETRY_AFTER;
+    pub static SET_COOKIE: HeaderName = header::SET_COOKIE;
 }
 
 /// Pre-allocated static header values for common content types.
@@ -186,6 +189,17 @@ impl Response {
         self
     }
 
+    /// Append a `Set-Cookie` header (invalid names are ignored, like
+    /// invalid headers in `with_header`).
+    #[inline]
+    pub fn set_cookie(mut self, name: &str, value: &str, options: &CookieOptions) -> Self {
+        if let Some(cookie) = options.header_value(name, value) {
+            self.headers
+                .append(header_names::SET_COOKIE.clone(), cookie);
+        }
+        self
+    }
+
     /// Set the body.
     #[inline]
     pub fn with_body(mut self, body: impl Into<Bytes>) -> Self {
@@ -322,6 +336,17 @@ impl ResponseBuilder {
         self
     }
 
+    /// Append a `Set-Cookie` header.
+    #[inline]
+    pub fn cookie(mut self, name: &str, value: &str, options: &CookieOptions) -> Self {
+        if let Some(cookie) = options.header_value(name, value) {
+            self.headers
+                .get_or_insert_with(HeaderMap::new)
+                .append(header_names::SET_COOKIE.clone(), cookie);
+        }
+        self
+    }
+
     /// Set the body.
     #[inline]
     pub fn body(mut self, body: impl Into<Bytes>) -> Self {
@@ -446,6 +471,22 @@ mod tests {
         assert_eq!(res.body().as_ref(), b"Modified");
     }
 
+    #[test]
+    fn test_response_set_cookie() {
+        let options = CookieOptions::new().with_path("/").with_http_only(true);
+        let res = Response::ok("")
+            .set_cookie("a", "1", &options)
+            .set_cookie("b", "2", &options)
+            .set_cookie("bad name", "3", &options);
+        let cookies: Vec<_> = res.headers().get_all("set-cookie").iter().collect();
+        assert_eq!(cookies, ["a=1; Path=/; HttpOnly", "b=2; Path=/; HttpOnly"]);
+
+        let res = Response::builder()
+            .cookie("c", "x y", &CookieOptions::new())
+            .build();
+        assert_eq!(res.header("set-cookie"), Some("c=x%20y"));
+    }
+
     #[test]
     fn test_response_content_types() {
         let html = Response::builder().html().body("<h1>Hi</h1>").build();
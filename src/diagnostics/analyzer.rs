@@ -1,6 +1,8 @@
-use serde::{Deserialize, Serialize};
 use super::os::limits::{LimitStatus, OsLimits};
+use super::runtime::traffic_stats::{TrafficStats, MIN_STATIC_RESPONSES, MIN_TLS_HANDSHAKES};
 use super::runtime::{tokio_metrics::TokioMetrics, worker_stats::*};
+use super::types::unix_now;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bottleneck {
@@ -12,7 +14,8 @@ pub struct Bottleneck {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub threshold: Option<u64>,
     pub impact: String,
-    pub detected_at: chrono::DateTime<chrono::Utc>,
+    /// Seconds since the Unix epoch.
+    pub detected_at: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,9 +47,10 @@ impl PerformanceAnalyzer {
         worker_stats: &PhpWorkerStats,
         memory_stats: &MemoryStats,
         lock_stats: &LockStats,
+        traffic_stats: &TrafficStats,
     ) -> Vec<Bottleneck> {
         let mut bottlenecks = Vec::new();
-        let now = chrono::Utc::now();
+        let now = unix_now();
 
         // Analyze OS limits
         bottlenecks.extend(Self::analyze_os_limits(os_limits, now));
@@ -63,17 +67,20 @@ impl PerformanceAnalyzer {
         // Analyze lock contention
         bottlenecks.extend(Self::analyze_locks(lock_stats, now));
 
+        // Analyze queue rejections, TLS and static caching
+        bottlenecks.extend(Self::analyze_traffic(traffic_stats, now));
+
         bottlenecks
     }
 
-    fn analyze_os_limits(
-        limits: &OsLimits,
-        now: chrono::DateTime<chrono::Utc>,
-    ) -> Vec<Bottleneck> {
+    fn analyze_os_limits(limits: &OsLimits, now: u64) -> Vec<Bottleneck> {
         let mut bottlenecks = Vec::new();
 
         // Check network limits
-        if matches!(limits.network.somaxconn.status, LimitStatus::Warning | LimitStatus::Critical) {
+        if matches!(
+            limits.network.somaxconn.status,
+            LimitStatus::Warning | LimitStatus::Critical
+        ) {
             bottlenecks.push(Bottleneck {
                 severity: match limits.network.somaxconn.status {
                     LimitStatus::Critical => Severity::Critical,
@@ -89,7 +96,10 @@ impl PerformanceAnalyzer {
         }
 
         // Check file descriptor limits
-        if matches!(limits.process.open_files.status, LimitStatus::Warning | LimitStatus::Critical) {
+        if matches!(
+            limits.process.open_files.status,
+            LimitStatus::Warning | LimitStatus::Critical
+        ) {
             bottlenecks.push(Bottleneck {
                 severity: match limits.process.open_files.status {
                     LimitStatus::Critical => Severity::Critical,
@@ -109,7 +119,10 @@ impl PerformanceAnalyzer {
 
         // Check container limits
         if let Some(container) = &limits.container {
-            if matches!(container.status, LimitStatus::Warning | LimitStatus::Critical) {
+            if matches!(
+                container.status,
+                LimitStatus::Warning | LimitStatus::Critical
+            ) {
                 bottlenecks.push(Bottleneck {
                     severity: match container.status {
                         LimitStatus::Critical => Severity::Critical,
@@ -131,10 +144,7 @@ impl PerformanceAnalyzer {
         bottlenecks
     }
 
-    fn analyze_tokio_runtime(
-        metrics: &TokioMetrics,
-        now: chrono::DateTime<chrono::Utc>,
-    ) -> Vec<Bottleneck> {
+    fn analyze_tokio_runtime(metrics: &TokioMetrics, now: u64) -> Vec<Bottleneck> {
         let mut bottlenecks = Vec::new();
 
         // Check for slow poll times
@@ -177,10 +187,7 @@ impl PerformanceAnalyzer {
         bottlenecks
     }
 
-    fn analyze_workers(
-        stats: &PhpWorkerStats,
-        now: chrono::DateTime<chrono::Utc>,
-    ) -> Vec<Bottleneck> {
+    fn analyze_workers(stats: &PhpWorkerStats, now: u64) -> Vec<Bottleneck> {
         let mut bottlenecks = Vec::new();
 
         // Check worker utilization and queue depth
@@ -230,10 +237,7 @@ impl PerformanceAnalyzer {
         bottlenecks
     }
 
-    fn analyze_memory(
-        stats: &MemoryStats,
-        now: chrono::DateTime<chrono::Utc>,
-    ) -> Vec<Bottleneck> {
+    fn analyze_memory(stats: &MemoryStats, now: u64) -> Vec<Bottleneck> {
         let mut bottlenecks = Vec::new();
 
         if matches!(stats.status, LimitStatus::Warning | LimitStatus::Critical) {
@@ -267,10 +271,7 @@ impl PerformanceAnalyzer {
         bottlenecks
     }
 
-    fn analyze_locks(
-        stats: &LockStats,
-        now: chrono::DateTime<chrono::Utc>,
-    ) -> Vec<Bottleneck> {
+    fn analyze_locks(stats: &LockStats, now: u64) -> Vec<Bottleneck> {
         let mut bottlenecks = Vec::new();
 
         if stats.worker_pool_contention_pct > 10.0 {
@@ -309,4 +310,83 @@ impl PerformanceAnalyzer {
 
         bottlenecks
     }
+
+    pub(crate) fn analyze_traffic(stats: &TrafficStats, now: u64) -> Vec<Bottleneck> {
+        let mut bottlenecks = Vec::new();
+
+        // Requests answered 503 because the PHP queue was full
+        if stats.dropped_requests > 0 {
+            bottlenecks.push(Bottleneck {
+                severity: match stats.status {
+                    LimitStatus::Critical => Severity::Critical,
+                    _ => Severity::Warning,
+                },
+                category: Category::Workers,
+                metric: "queue_rejections".to_string(),
+                current: Some(stats.dropped_requests),
+                threshold: Some(stats.queue_capacity as u64),
+                impact: format!(
+                    "{} requests ({:.2}%) rejected with 503 because the PHP queue was full",
+                    stats.dropped_requests, stats.dropped_pct
+                ),
+                detected_at: now,
+            });
+        }
+
+        if stats.tls_handshakes >= MIN_TLS_HANDSHAKES {
+            // Slow handshakes
+            if stats.tls_handshake_avg_ms > 100.0 {
+                bottlenecks.push(Bottleneck {
+                    severity: if stats.tls_handshake_avg_ms > 250.0 {
+                        Severity::Critical
+                    } else {
+                        Severity::Warning
+                    },
+                    category: Category::Network,
+                    metric: "tls_handshake_time".to_string(),
+                    current: Some(stats.tls_handshake_avg_ms as u64),
+                    threshold: Some(100),
+                    impact: format!(
+                        "TLS handshakes take {:.0}ms on average before the first request",
+                        stats.tls_handshake_avg_ms
+                    ),
+                    detected_at: now,
+                });
+            }
+
+            // Most requests pay for a new connection
+            if stats.tls_handshakes_per_request > 0.5 {
+                bottlenecks.push(Bottleneck {
+                    severity: Severity::Warning,
+                    category: Category::Network,
+                    metric: "tls_connection_reuse".to_string(),
+                    current: Some((stats.tls_handshakes_per_request * 100.0) as u64),
+                    threshold: Some(50),
+                    impact: format!(
+                        "{:.0}% of requests opened a new TLS connection; keep-alive connections are rarely reused",
+                        stats.tls_handshakes_per_request * 100.0
+                    ),
+                    detected_at: now,
+                });
+            }
+        }
+
+        // Static files served without Cache-Control max-age
+        if !stats.static_cache_enabled && stats.static_responses >= MIN_STATIC_RESPONSES {
+            bottlenecks.push(Bottleneck {
+                severity: Severity::Warning,
+                category: Category::Io,
+                metric: "static_cache_disabled".to_string(),
+                current: Some(stats.static_responses),
+                threshold: None,
+                impact: format!(
+                    "{} static responses sent without Cache-Control max-age; browsers re-download or revalidate every asset",
+                    stats.static_responses
+                ),
+                detected_at: now,
+            });
+        }
+
+        bottlenecks
+    }
 }
@@ -1,10 +1,12 @@
-use anyhow::Result;
-use std::time::Instant;
 use super::analyzer::PerformanceAnalyzer;
 use super::os;
 use super::recommender::RecommendationEngine;
-use super::runtime::{tokio_metrics, worker_stats::*};
-use super::types::{DiagnosticResponse, PlatformInfo, RuntimeMetrics};
+use super::runtime::{tokio_metrics, traffic_stats, worker_stats};
+use super::types::{unix_now, DiagnosticResponse, PlatformInfo, RuntimeMetrics};
+use std::time::Instant;
+
+pub use super::runtime::traffic_stats::TrafficSnapshot;
+pub use super::runtime::worker_stats::{LockTimes, WorkerActivityStats, WorkerSnapshot};
 
 pub struct DiagnosticCollector {
     platform: String,
@@ -23,68 +25,30 @@ impl DiagnosticCollector {
         Self { platform }
     }
 
-    /// Collect full diagnostics
-    /// This is the main entry point called by the /diagnostics endpoint
-    pub async fn collect(
+    /// Collect full diagnostics.
+    ///
+    /// Reads `/proc` (or runs `sysctl` on macOS), so call it from a blocking
+    /// context; the internal server's `/diagnostics` endpoint uses `spawn_blocking`.
+    pub fn collect(
         &self,
         runtime_handle: &tokio::runtime::Handle,
-        // These would come from your existing app state
-        worker_count: usize,
-        busy_workers: usize,
-        queue_depth: usize,
-        total_requests: u64,
-        execution_times_ms: Vec<f64>,
-        wait_times_ms: Vec<f64>,
-        php_memory_per_worker: Vec<u64>,
-        file_cache_size: u64,
-        // Lock contention metrics (you'd need to instrument these)
-        worker_pool_wait_ns: u64,
-        worker_pool_hold_ns: u64,
-        file_cache_wait_ns: u64,
-        file_cache_hold_ns: u64,
-        config_wait_ns: u64,
-        config_hold_ns: u64,
-    ) -> Result<DiagnosticResponse> {
+        workers: &WorkerSnapshot,
+        traffic: &TrafficSnapshot,
+    ) -> Result<DiagnosticResponse, String> {
         let start = Instant::now();
 
-        // Collect platform info
-        let platform_info = self.collect_platform_info().await?;
-
-        // Collect OS limits
+        let platform_info = self.collect_platform_info();
         let os_limits = self.collect_os_limits()?;
 
-        // Collect runtime metrics
-        let tokio_metrics = tokio_metrics::collect_tokio_metrics_from_handle(runtime_handle);
-
-        let worker_stats = worker_stats::collect_worker_stats(
-            worker_count,
-            busy_workers,
-            queue_depth,
-            total_requests,
-            &execution_times_ms,
-            &wait_times_ms,
-        );
-
-        let memory_stats = worker_stats::collect_memory_stats(
-            worker_count,
-            php_memory_per_worker,
-            file_cache_size,
-        );
-
-        let lock_stats = worker_stats::collect_lock_stats(
-            worker_pool_wait_ns,
-            worker_pool_hold_ns,
-            file_cache_wait_ns,
-            file_cache_hold_ns,
-            config_wait_ns,
-            config_hold_ns,
-        );
-
         let runtime_metrics = RuntimeMetrics {
-            tokio: tokio_metrics,
-            php_workers: worker_stats,
-            memory: memory_stats,
-            locks: lock_stats,
+            tokio: tokio_metrics::collect_tokio_metrics(runtime_handle),
+            php_workers: worker_stats::collect_worker_stats(workers),
+            memory: worker_stats::collect_memory_stats(
+                &workers.php_memory_per_worker,
+                workers.file_cache_size,
+            ),
+            locks: worker_stats::collect_lock_stats(&workers.locks),
+            traffic: traffic_stats::collect_traffic_stats(traffic, workers.total_requests),
         };
 
         // Analyze bottlenecks
@@ -94,39 +58,33 @@ impl DiagnosticCollector {
             &runtime_metrics.php_workers,
             &runtime_metrics.memory,
             &runtime_metrics.locks,
+            &runtime_metrics.traffic,
         );
 
         // Generate recommendations
         let recommendations = RecommendationEngine::generate(
             &bottlenecks,
             &os_limits,
-            &runtime_metrics.tokio,
             &runtime_metrics.php_workers,
             &self.platform,
         );
 
-        // Calculate health score
-        let health_score = DiagnosticResponse::calculate_health_score(
-            &os_limits,
-            &runtime_metrics,
-            &bottlenecks,
-        );
-
-        let collection_time_ms = start.elapsed().as_millis() as u64;
+        let health_score =
+            DiagnosticResponse::calculate_health_score(&os_limits, &runtime_metrics, &bottlenecks);
 
         Ok(DiagnosticResponse {
-            timestamp: chrono::Utc::now(),
+            timestamp: unix_now(),
             platform: platform_info,
             os_limits,
             runtime_metrics,
             bottlenecks,
             recommendations,
             health_score,
-            collection_time_ms,
+            collection_time_ms: start.elapsed().as_millis() as u64,
         })
     }
 
-    fn collect_os_limits(&self) -> Result<os::limits::OsLimits> {
+    fn collect_os_limits(&self) -> Result<os::limits::OsLimits, String> {
         #[cfg(target_os = "linux")]
         {
             os::linux::collect_os_limits()
@@ -139,22 +97,23 @@ impl DiagnosticCollector {
 
         #[cfg(not(any(target_os = "linux", target_os = "macos")))]
         {
-            anyhow::bail!("Unsupported platform for OS limits collection")
+            Err("Unsupported platform for OS limits collection".to_string())
         }
     }
 
-    async fn collect_platform_info(&self) -> Result<PlatformInfo> {
-        use sysinfo::{System, SystemExt};
-
-        let mut sys = System::new_all();
-        sys.refresh_all();
+    fn collect_platform_info(&self) -> PlatformInfo {
+        let (sysname, kernel) = uname();
 
-        let os = sys.name().unwrap_or_else(|| "unknown".to_string());
-        let kernel = sys.kernel_version().unwrap_or_else(|| "unknown".to_string());
-        let arch = std::env::consts::ARCH.to_string();
-
-        // Detect if running in container
-        let container = self.detect_container();
+        // Prefer the distribution name on Linux
+        let os = std::fs::read_to_string("/etc/os-release")
+            .ok()
+            .and_then(|content| {
+                content.lines().find_map(|line| {
+                    line.strip_prefix("PRETTY_NAME=")
+                        .map(|v| v.trim_matches('"').to_string())
+                })
+            })
+            .unwrap_or(sysname);
 
         // Detect cgroup version on Linux
         let cgroup_version = if cfg!(target_os = "linux") {
@@ -163,13 +122,13 @@ impl DiagnosticCollector {
             None
         };
 
-        Ok(PlatformInfo {
+        PlatformInfo {
             os,
             kernel,
-            arch,
-            container,
+            arch: std::env::consts::ARCH.to_string(),
+            container: self.detect_container(),
             cgroup_version,
-        })
+        }
     }
 
     fn detect_container(&self) -> String {
@@ -203,16 +162,13 @@ impl DiagnosticCollector {
     }
 
     fn detect_cgroup_version(&self) -> Option<String> {
-        #[cfg(target_os = "linux")]
-        {
-            if std::path::Path::new("/sys/fs/cgroup/cgroup.controllers").exists() {
-                return Some("v2".to_string());
-            } else if std::path::Path::new("/sys/fs/cgroup/memory").exists() {
-                return Some("v1".to_string());
-            }
+        if std::path::Path::new("/sys/fs/cgroup/cgroup.controllers").exists() {
+            Some("v2".to_string())
+        } else if std::path::Path::new("/sys/fs/cgroup/memory").exists() {
+            Some("v1".to_string())
+        } else {
+            None
         }
-
-        None
     }
 }
 
@@ -221,3 +177,20 @@ impl Default for DiagnosticCollector {
         Self::new()
     }
 }
+
+/// Kernel name and release from `uname(2)`.
+fn uname() -> (String, String) {
+    let field = |chars: &[libc::c_char]| {
+        // SAFETY: uname() NUL-terminates every field
+        unsafe { std::ffi::CStr::from_ptr(chars.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    // SAFETY: utsname is plain data and uname() fills it in
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return ("unknown".to_string(), "unknown".to_string());
+    }
+    (field(&uts.sysname), field(&uts.release))
+}
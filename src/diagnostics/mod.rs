@@ -5,39 +5,30 @@
 //!
 //! ## Usage
 //!
-//! Add to internal server routes:
+//! The internal server exposes this as `GET /diagnostics`, caching the result
+//! for a few seconds. Collection reads `/proc`, so run it off the async workers:
 //!
 //! ```rust,ignore
-//! use tokio_php::diagnostics::{DiagnosticCollector, DiagnosticResponse};
+//! use tokio_php::diagnostics::{DiagnosticCollector, TrafficSnapshot, WorkerSnapshot};
 //!
-//! async fn diagnostics_handler(
-//!     state: Arc<AppState>,
-//! ) -> Result<Json<DiagnosticResponse>, StatusCode> {
-//!     let collector = DiagnosticCollector::new();
+//! let handle = tokio::runtime::Handle::current();
+//! let snapshot = WorkerSnapshot {
+//!     worker_count: 8,
+//!     queue_depth: metrics.pending_requests.load(Ordering::Relaxed),
+//!     total_requests: metrics.total() as u64,
+//!     ..Default::default()
+//! };
 //!
-//!     // Gather current metrics from your app state
-//!     let metrics = state.metrics.snapshot();
+//! let traffic = TrafficSnapshot {
+//!     queue_capacity: 800,
+//!     dropped_requests: metrics.dropped_requests.load(Ordering::Relaxed) as u64,
+//!     ..Default::default()
+//! };
 //!
-//!     let response = collector.collect(
-//!         state.runtime_handle(),
-//!         metrics.worker_count,
-//!         metrics.busy_workers,
-//!         metrics.queue_depth,
-//!         metrics.total_requests,
-//!         metrics.execution_times_ms,
-//!         metrics.wait_times_ms,
-//!         metrics.php_memory_per_worker,
-//!         metrics.file_cache_size,
-//!         metrics.lock_stats.worker_pool_wait_ns,
-//!         metrics.lock_stats.worker_pool_hold_ns,
-//!         metrics.lock_stats.file_cache_wait_ns,
-//!         metrics.lock_stats.file_cache_hold_ns,
-//!         metrics.lock_stats.config_wait_ns,
-//!         metrics.lock_stats.config_hold_ns,
-//!     ).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-//!
-//!     Ok(Json(response))
-//! }
+//! let response = tokio::task::spawn_blocking(move || {
+//!     DiagnosticCollector::new().collect(&handle, &snapshot, &traffic)
+//! })
+//! .await??;
 //! ```
 
 pub mod analyzer;
@@ -47,15 +38,159 @@ pub mod recommender;
 pub mod runtime;
 pub mod types;
 
-pub use collector::DiagnosticCollector;
+pub use collector::{
+    DiagnosticCollector, LockTimes, TrafficSnapshot, WorkerActivityStats, WorkerSnapshot,
+};
 pub use types::{DiagnosticResponse, PlatformInfo, RuntimeMetrics};
 
 #[cfg(test)]
 mod tests {
+    use super::analyzer::{Bottleneck, Category, Severity};
+    use super::os::limits::*;
+    use super::recommender::RecommendationEngine;
+    use super::runtime::{tokio_metrics, traffic_stats, worker_stats};
     use super::*;
 
+    fn limits(open_files: u64) -> OsLimits {
+        OsLimits {
+            process: ProcessLimits {
+                open_files: ResourceLimit::new(1024, 4096, open_files),
+                max_processes: ResourceLimit::new(4096, 4096, 10),
+                stack_size_kb: 8192,
+            },
+            network: NetworkLimits {
+                somaxconn: NetworkLimit::new(8192, Recommendations::SOMAXCONN),
+                tcp_max_syn_backlog: 8192,
+                tcp_rmem: [4096, 131072, 6291456],
+                tcp_wmem: [4096, 16384, 4194304],
+                netdev_max_backlog: 5000,
+            },
+            io: IoLimits {
+                epoll_max_user_watches: None,
+                kqueue_max: None,
+                aio_max_nr: 65536,
+                file_max: 1 << 20,
+            },
+            container: None,
+        }
+    }
+
     #[test]
     fn test_health_score_calculation() {
-        // This would test the health score algorithm
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let metrics = |busy, queue_depth| RuntimeMetrics {
+            tokio: tokio_metrics::collect_tokio_metrics(runtime.handle()),
+            php_workers: worker_stats::collect_worker_stats(&WorkerSnapshot {
+                worker_count: 4,
+                busy_workers: busy,
+                queue_depth,
+                ..Default::default()
+            }),
+            memory: worker_stats::collect_memory_stats(&[], 0),
+            locks: worker_stats::collect_lock_stats(&LockTimes::default()),
+            traffic: traffic_stats::collect_traffic_stats(&TrafficSnapshot::default(), 0),
+        };
+
+        // Healthy: nothing to deduct
+        let healthy = metrics(1, 0);
+        assert_eq!(
+            DiagnosticResponse::calculate_health_score(&limits(10), &healthy, &[]),
+            100
+        );
+
+        // Saturated workers, fd usage at 90% and a critical bottleneck
+        let saturated = metrics(4, 20);
+        let bottleneck = Bottleneck {
+            severity: Severity::Critical,
+            category: Category::Workers,
+            metric: "worker_saturation".to_string(),
+            current: Some(4),
+            threshold: Some(4),
+            impact: String::new(),
+            detected_at: 0,
+        };
+        assert_eq!(
+            DiagnosticResponse::calculate_health_score(&limits(922), &saturated, &[bottleneck]),
+            100 - 20 - 10 - 12
+        );
+
+        // Never below zero
+        let many: Vec<_> = (0..10)
+            .map(|_| Bottleneck {
+                severity: Severity::Critical,
+                category: Category::Memory,
+                metric: "memory_usage".to_string(),
+                current: None,
+                threshold: None,
+                impact: String::new(),
+                detected_at: 0,
+            })
+            .collect();
+        assert_eq!(
+            DiagnosticResponse::calculate_health_score(&limits(10), &healthy, &many),
+            0
+        );
+    }
+
+    #[test]
+    fn test_traffic_recommendations() {
+        let traffic = TrafficSnapshot {
+            queue_capacity: 400,
+            dropped_requests: 50,
+            tls_handshakes: 900,
+            tls_handshake_time_us: 900 * 150_000,
+            static_responses: 500,
+            static_not_modified: 0,
+            static_cache_enabled: false,
+        };
+        let stats = traffic_stats::collect_traffic_stats(&traffic, 1000);
+        assert_eq!(stats.dropped_pct, 5.0);
+        assert_eq!(stats.tls_handshake_avg_ms, 150.0);
+
+        let bottlenecks = analyzer::PerformanceAnalyzer::analyze_traffic(&stats, 0);
+        let metrics: Vec<_> = bottlenecks.iter().map(|b| b.metric.as_str()).collect();
+        assert_eq!(
+            metrics,
+            [
+                "queue_rejections",
+                "tls_handshake_time",
+                "tls_connection_reuse",
+                "static_cache_disabled"
+            ]
+        );
+        assert!(matches!(bottlenecks[0].severity, Severity::Critical));
+
+        let workers = worker_stats::collect_worker_stats(&WorkerSnapshot::default());
+        let recommendations =
+            RecommendationEngine::generate(&bottlenecks, &limits(10), &workers, "linux");
+        let env = |action: &str| {
+            recommendations
+                .iter()
+                .find(|r| r.action == action)
+                .and_then(|r| r.commands.env.clone())
+                .unwrap()
+        };
+        assert_eq!(recommendations[0].action, "increase_queue_capacity");
+        assert_eq!(env("increase_queue_capacity"), "export QUEUE_CAPACITY=800");
+        assert_eq!(
+            env("increase_keepalive_reuse"),
+            "export HEADER_TIMEOUT_SECS=15"
+        );
+        assert_eq!(env("enable_static_cache"), "export STATIC_CACHE_TTL=1d");
+        assert!(env("use_ecdsa_certificate").contains("TLS_CERT="));
+
+        // Healthy traffic: nothing to report
+        let quiet = TrafficSnapshot {
+            queue_capacity: 400,
+            tls_handshakes: 10,
+            tls_handshake_time_us: 10 * 500_000,
+            static_responses: 500,
+            static_cache_enabled: true,
+            ..Default::default()
+        };
+        let stats = traffic_stats::collect_traffic_stats(&quiet, 1000);
+        assert!(analyzer::PerformanceAnalyzer::analyze_traffic(&stats, 0).is_empty());
     }
 }
@@ -1,10 +1,8 @@
 use super::limits::*;
-use anyhow::{Context, Result};
 use std::fs;
-use std::os::unix::io::AsRawFd;
 
 /// Collect Linux-specific OS limits
-pub fn collect_os_limits() -> Result<OsLimits> {
+pub fn collect_os_limits() -> Result<OsLimits, String> {
     Ok(OsLimits {
         process: collect_process_limits()?,
         network: collect_network_limits()?,
@@ -13,7 +11,7 @@ pub fn collect_os_limits() -> Result<OsLimits> {
     })
 }
 
-fn collect_process_limits() -> Result<ProcessLimits> {
+fn collect_process_limits() -> Result<ProcessLimits, String> {
     use libc::{getrlimit, rlimit, RLIMIT_NOFILE, RLIMIT_NPROC, RLIMIT_STACK};
 
     unsafe {
@@ -22,13 +20,13 @@ fn collect_process_limits() -> Result<ProcessLimits> {
         let mut stack: rlimit = std::mem::zeroed();
 
         if getrlimit(RLIMIT_NOFILE, &mut nofile) != 0 {
-            anyhow::bail!("Failed to get RLIMIT_NOFILE");
+            return Err("Failed to get RLIMIT_NOFILE".to_string());
         }
         if getrlimit(RLIMIT_NPROC, &mut nproc) != 0 {
-            anyhow::bail!("Failed to get RLIMIT_NPROC");
+            return Err("Failed to get RLIMIT_NPROC".to_string());
         }
         if getrlimit(RLIMIT_STACK, &mut stack) != 0 {
-            anyhow::bail!("Failed to get RLIMIT_STACK");
+            return Err("Failed to get RLIMIT_STACK".to_string());
         }
 
         // Count current open file descriptors
@@ -45,16 +43,16 @@ fn collect_process_limits() -> Result<ProcessLimits> {
     }
 }
 
-fn count_open_fds() -> Result<u64> {
+fn count_open_fds() -> Result<u64, String> {
     let fd_count = fs::read_dir("/proc/self/fd")
-        .context("Failed to read /proc/self/fd")?
+        .map_err(|e| format!("Failed to read /proc/self/fd: {e}"))?
         .count();
     Ok(fd_count as u64)
 }
 
-fn count_processes() -> Result<u64> {
+fn count_processes() -> Result<u64, String> {
     let status = fs::read_to_string("/proc/self/status")
-        .context("Failed to read /proc/self/status")?;
+        .map_err(|e| format!("Failed to read /proc/self/status: {e}"))?;
 
     for line in status.lines() {
         if line.starts_with("Threads:") {
@@ -66,7 +64,7 @@ fn count_processes() -> Result<u64> {
     Ok(1)
 }
 
-fn collect_network_limits() -> Result<NetworkLimits> {
+fn collect_network_limits() -> Result<NetworkLimits, String> {
     Ok(NetworkLimits {
         somaxconn: NetworkLimit::new(
             read_sysctl_u64("/proc/sys/net/core/somaxconn")?,
@@ -79,7 +77,7 @@ fn collect_network_limits() -> Result<NetworkLimits> {
     })
 }
 
-fn collect_io_limits() -> Result<IoLimits> {
+fn collect_io_limits() -> Result<IoLimits, String> {
     Ok(IoLimits {
         epoll_max_user_watches: read_sysctl_u64("/proc/sys/fs/epoll/max_user_watches").ok(),
         kqueue_max: None, // Linux doesn't use kqueue
@@ -88,29 +86,29 @@ fn collect_io_limits() -> Result<IoLimits> {
     })
 }
 
-fn collect_container_limits() -> Result<ContainerLimits> {
+fn collect_container_limits() -> Result<ContainerLimits, String> {
     // Detect cgroup v1 or v2
     let cgroup_version = detect_cgroup_version()?;
 
     match cgroup_version {
         1 => collect_cgroupv1_limits(),
         2 => collect_cgroupv2_limits(),
-        _ => anyhow::bail!("Unknown cgroup version"),
+        _ => Err("Unknown cgroup version".to_string()),
     }
 }
 
-fn detect_cgroup_version() -> Result<u8> {
+fn detect_cgroup_version() -> Result<u8, String> {
     // Check if cgroup v2 unified hierarchy exists
     if fs::metadata("/sys/fs/cgroup/cgroup.controllers").is_ok() {
         Ok(2)
     } else if fs::metadata("/sys/fs/cgroup/memory").is_ok() {
         Ok(1)
     } else {
-        anyhow::bail!("No cgroup support detected")
+        Err("No cgroup support detected".to_string())
     }
 }
 
-fn collect_cgroupv2_limits() -> Result<ContainerLimits> {
+fn collect_cgroupv2_limits() -> Result<ContainerLimits, String> {
     let memory_max = read_cgroup_file("/sys/fs/cgroup/memory.max")?
         .trim()
         .parse::<u64>()
@@ -161,7 +159,7 @@ fn collect_cgroupv2_limits() -> Result<ContainerLimits> {
     })
 }
 
-fn collect_cgroupv1_limits() -> Result<ContainerLimits> {
+fn collect_cgroupv1_limits() -> Result<ContainerLimits, String> {
     let memory_limit = read_cgroup_file("/sys/fs/cgroup/memory/memory.limit_in_bytes")?
         .trim()
         .parse::<u64>()
@@ -224,8 +222,8 @@ fn collect_cgroupv1_limits() -> Result<ContainerLimits> {
     })
 }
 
-fn parse_cpu_max(content: &str) -> Result<(f64, u64)> {
-    let parts: Vec<&str> = content.trim().split_whitespace().collect();
+fn parse_cpu_max(content: &str) -> Result<(f64, u64), String> {
+    let parts: Vec<&str> = content.split_whitespace().collect();
     if parts.len() != 2 {
         return Ok((0.0, 100000));
     }
@@ -247,18 +245,16 @@ fn parse_cpu_max(content: &str) -> Result<(f64, u64)> {
     Ok((quota_f, period))
 }
 
-fn read_sysctl_u64(path: &str) -> Result<u64> {
-    let content = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read {}", path))?;
+fn read_sysctl_u64(path: &str) -> Result<u64, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
     content
         .trim()
-        .parse()
-        .with_context(|| format!("Failed to parse {} as u64", path))
+        .parse::<u64>()
+        .map_err(|e| format!("Failed to parse {path} as u64: {e}"))
 }
 
-fn read_sysctl_triple(path: &str) -> Result<[u64; 3]> {
-    let content = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read {}", path))?;
+fn read_sysctl_triple(path: &str) -> Result<[u64; 3], String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
 
     let parts: Vec<u64> = content
         .split_whitespace()
@@ -266,12 +262,12 @@ fn read_sysctl_triple(path: &str) -> Result<[u64; 3]> {
         .collect();
 
     if parts.len() != 3 {
-        anyhow::bail!("Expected 3 values in {}", path);
+        return Err(format!("Expected 3 values in {path}"));
     }
 
     Ok([parts[0], parts[1], parts[2]])
 }
 
-fn read_cgroup_file(path: &str) -> Result<String> {
-    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))
+fn read_cgroup_file(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))
 }
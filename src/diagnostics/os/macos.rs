@@ -1,9 +1,8 @@
 use super::limits::*;
-use anyhow::{Context, Result};
 use std::process::Command;
 
 /// Collect macOS-specific OS limits
-pub fn collect_os_limits() -> Result<OsLimits> {
+pub fn collect_os_limits() -> Result<OsLimits, String> {
     Ok(OsLimits {
         process: collect_process_limits()?,
         network: collect_network_limits()?,
@@ -12,7 +11,7 @@ pub fn collect_os_limits() -> Result<OsLimits> {
     })
 }
 
-fn collect_process_limits() -> Result<ProcessLimits> {
+fn collect_process_limits() -> Result<ProcessLimits, String> {
     use libc::{getrlimit, rlimit, RLIMIT_NOFILE, RLIMIT_NPROC, RLIMIT_STACK};
 
     unsafe {
@@ -21,13 +20,13 @@ fn collect_process_limits() -> Result<ProcessLimits> {
         let mut stack: rlimit = std::mem::zeroed();
 
         if getrlimit(RLIMIT_NOFILE, &mut nofile) != 0 {
-            anyhow::bail!("Failed to get RLIMIT_NOFILE");
+            return Err("Failed to get RLIMIT_NOFILE".to_string());
         }
         if getrlimit(RLIMIT_NPROC, &mut nproc) != 0 {
-            anyhow::bail!("Failed to get RLIMIT_NPROC");
+            return Err("Failed to get RLIMIT_NPROC".to_string());
         }
         if getrlimit(RLIMIT_STACK, &mut stack) != 0 {
-            anyhow::bail!("Failed to get RLIMIT_STACK");
+            return Err("Failed to get RLIMIT_STACK".to_string());
         }
 
         // On macOS, we can use lsof to count open files for current process
@@ -42,12 +41,12 @@ fn collect_process_limits() -> Result<ProcessLimits> {
     }
 }
 
-fn count_open_fds() -> Result<u64> {
+fn count_open_fds() -> Result<u64, String> {
     let pid = std::process::id();
     let output = Command::new("lsof")
         .args(["-p", &pid.to_string()])
         .output()
-        .context("Failed to run lsof")?;
+        .map_err(|e| format!("Failed to run lsof: {e}"))?;
 
     if !output.status.success() {
         return Ok(0);
@@ -61,13 +60,13 @@ fn count_open_fds() -> Result<u64> {
     Ok(count as u64)
 }
 
-fn count_processes() -> Result<u64> {
+fn count_processes() -> Result<u64, String> {
     // On macOS, count threads using sysctl
     let pid = std::process::id();
     let output = Command::new("ps")
         .args(["-M", "-p", &pid.to_string()])
         .output()
-        .context("Failed to run ps")?;
+        .map_err(|e| format!("Failed to run ps: {e}"))?;
 
     if !output.status.success() {
         return Ok(1);
@@ -81,14 +80,13 @@ fn count_processes() -> Result<u64> {
     Ok(count.max(1) as u64)
 }
 
-fn collect_network_limits() -> Result<NetworkLimits> {
+fn collect_network_limits() -> Result<NetworkLimits, String> {
     Ok(NetworkLimits {
         somaxconn: NetworkLimit::new(
             sysctl_read_u64("kern.ipc.somaxconn")?,
             Recommendations::SOMAXCONN,
         ),
-        tcp_max_syn_backlog: sysctl_read_u64("net.inet.tcp.syncache.bucketlimit")
-            .unwrap_or(512),
+        tcp_max_syn_backlog: sysctl_read_u64("net.inet.tcp.syncache.bucketlimit").unwrap_or(512),
         tcp_rmem: [
             16384,
             sysctl_read_u64("net.inet.tcp.recvspace").unwrap_or(131072),
@@ -99,12 +97,11 @@ fn collect_network_limits() -> Result<NetworkLimits> {
             sysctl_read_u64("net.inet.tcp.sendspace").unwrap_or(131072),
             sysctl_read_u64("net.inet.tcp.autosndbufmax").unwrap_or(2097152),
         ],
-        netdev_max_backlog: sysctl_read_u64("net.inet.ip.intr_queue_maxlen")
-            .unwrap_or(256),
+        netdev_max_backlog: sysctl_read_u64("net.inet.ip.intr_queue_maxlen").unwrap_or(256),
     })
 }
 
-fn collect_io_limits() -> Result<IoLimits> {
+fn collect_io_limits() -> Result<IoLimits, String> {
     Ok(IoLimits {
         epoll_max_user_watches: None, // macOS doesn't use epoll
         kqueue_max: sysctl_read_u64("kern.maxfiles").ok(),
@@ -113,20 +110,20 @@ fn collect_io_limits() -> Result<IoLimits> {
     })
 }
 
-fn sysctl_read_u64(name: &str) -> Result<u64> {
+fn sysctl_read_u64(name: &str) -> Result<u64, String> {
     let output = Command::new("sysctl")
         .args(["-n", name])
         .output()
-        .with_context(|| format!("Failed to run sysctl {}", name))?;
+        .map_err(|e| format!("Failed to run sysctl {name}: {e}"))?;
 
     if !output.status.success() {
-        anyhow::bail!("sysctl {} failed", name);
+        return Err(format!("sysctl {name} failed"));
     }
 
     let value = String::from_utf8_lossy(&output.stdout)
         .trim()
-        .parse()
-        .with_context(|| format!("Failed to parse sysctl {} value", name))?;
+        .parse::<u64>()
+        .map_err(|e| format!("Failed to parse sysctl {name} value: {e}"))?;
 
     Ok(value)
 }
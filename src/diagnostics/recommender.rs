@@ -1,8 +1,7 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use super::analyzer::{Bottleneck, Category, Severity};
 use super::os::limits::OsLimits;
-use super::runtime::{tokio_metrics::TokioMetrics, worker_stats::*};
+use super::runtime::worker_stats::PhpWorkerStats;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Recommendation {
@@ -44,31 +43,21 @@ impl RecommendationEngine {
     pub fn generate(
         bottlenecks: &[Bottleneck],
         os_limits: &OsLimits,
-        tokio_metrics: &TokioMetrics,
         worker_stats: &PhpWorkerStats,
         platform: &str,
     ) -> Vec<Recommendation> {
         let mut recommendations = Vec::new();
 
         for bottleneck in bottlenecks {
-            if let Some(rec) = Self::bottleneck_to_recommendation(
-                bottleneck,
-                os_limits,
-                tokio_metrics,
-                worker_stats,
-                platform,
-            ) {
+            if let Some(rec) =
+                Self::bottleneck_to_recommendation(bottleneck, worker_stats, platform)
+            {
                 recommendations.push(rec);
             }
         }
 
         // Add proactive recommendations even without bottlenecks
-        recommendations.extend(Self::proactive_recommendations(
-            os_limits,
-            tokio_metrics,
-            worker_stats,
-            platform,
-        ));
+        recommendations.extend(Self::proactive_recommendations(os_limits, platform));
 
         // Sort by priority
         recommendations.sort_by(|a, b| {
@@ -92,8 +81,6 @@ impl RecommendationEngine {
 
     fn bottleneck_to_recommendation(
         bottleneck: &Bottleneck,
-        os_limits: &OsLimits,
-        tokio_metrics: &TokioMetrics,
         worker_stats: &PhpWorkerStats,
         platform: &str,
     ) -> Option<Recommendation> {
@@ -107,23 +94,27 @@ impl RecommendationEngine {
             (Category::Memory, "container_memory") => {
                 Some(Self::recommend_container_memory(bottleneck))
             }
-            (Category::Runtime, "max_poll_time") => {
-                Some(Self::recommend_poll_time(bottleneck))
-            }
-            (Category::Runtime, "task_queue_depth") => {
-                Some(Self::recommend_task_queue(bottleneck))
-            }
+            (Category::Runtime, "max_poll_time") => Some(Self::recommend_poll_time(bottleneck)),
+            (Category::Runtime, "task_queue_depth") => Some(Self::recommend_task_queue(bottleneck)),
             (Category::Workers, "worker_saturation") => {
                 Some(Self::recommend_worker_count(bottleneck, worker_stats))
             }
             (Category::Workers, "php_execution_time") => {
                 Some(Self::recommend_php_optimization(bottleneck))
             }
-            (Category::Memory, "php_worker_memory") => {
-                Some(Self::recommend_php_memory(bottleneck))
+            (Category::Memory, "php_worker_memory") => Some(Self::recommend_php_memory(bottleneck)),
+            (Category::Locks, _) => Some(Self::recommend_lock_optimization(bottleneck)),
+            (Category::Workers, "queue_rejections") => {
+                Some(Self::recommend_queue_capacity(bottleneck))
             }
-            (Category::Locks, _) => {
-                Some(Self::recommend_lock_optimization(bottleneck))
+            (Category::Network, "tls_handshake_time") => {
+                Some(Self::recommend_tls_handshake(bottleneck))
+            }
+            (Category::Network, "tls_connection_reuse") => {
+                Some(Self::recommend_keepalive(bottleneck))
+            }
+            (Category::Io, "static_cache_disabled") => {
+                Some(Self::recommend_static_cache(bottleneck))
             }
             _ => None,
         }
@@ -135,11 +126,17 @@ impl RecommendationEngine {
         let (immediate, persistent) = match platform {
             "linux" => (
                 format!("sysctl -w net.core.somaxconn={}", threshold),
-                format!("echo 'net.core.somaxconn = {}' >> /etc/sysctl.conf && sysctl -p", threshold),
+                format!(
+                    "echo 'net.core.somaxconn = {}' >> /etc/sysctl.conf && sysctl -p",
+                    threshold
+                ),
             ),
             "darwin" => (
                 format!("sudo sysctl -w kern.ipc.somaxconn={}", threshold),
-                format!("echo 'kern.ipc.somaxconn={}' | sudo tee -a /etc/sysctl.conf", threshold),
+                format!(
+                    "echo 'kern.ipc.somaxconn={}' | sudo tee -a /etc/sysctl.conf",
+                    threshold
+                ),
             ),
             _ => (String::new(), String::new()),
         };
@@ -173,8 +170,6 @@ impl RecommendationEngine {
     }
 
     fn recommend_open_files(bottleneck: &Bottleneck, platform: &str) -> Recommendation {
-        let recommended = 65536;
-
         let (immediate, persistent) = match platform {
             "linux" => (
                 "ulimit -n 65536".to_string(),
@@ -192,17 +187,25 @@ impl RecommendationEngine {
             category: "process".to_string(),
             issue: format!(
                 "File descriptor limit at {:.0}% capacity",
-                (bottleneck.current.unwrap_or(0) as f64 / bottleneck.threshold.unwrap_or(1) as f64) * 100.0
+                (bottleneck.current.unwrap_or(0) as f64 / bottleneck.threshold.unwrap_or(1) as f64)
+                    * 100.0
             ),
             action: "increase_nofile".to_string(),
             commands: Commands {
                 immediate: Some(immediate),
                 persistent: Some(persistent),
-                docker: Some("Add to docker-compose.yml: ulimits: nofile: {soft: 65536, hard: 65536}".to_string()),
+                docker: Some(
+                    "Add to docker-compose.yml: ulimits: nofile: {soft: 65536, hard: 65536}"
+                        .to_string(),
+                ),
                 env: None,
             },
-            rationale: Some("Async servers need high file descriptor limits for many concurrent connections".to_string()),
-            expected_impact: "Support up to 65536 concurrent connections without hitting limits".to_string(),
+            rationale: Some(
+                "Async servers need high file descriptor limits for many concurrent connections"
+                    .to_string(),
+            ),
+            expected_impact: "Support up to 65536 concurrent connections without hitting limits"
+                .to_string(),
             estimated_gain_pct: 20,
         }
     }
@@ -229,7 +232,9 @@ impl RecommendationEngine {
                 )),
                 env: None,
             },
-            rationale: Some("OOM killer will terminate process if memory limit is exceeded".to_string()),
+            rationale: Some(
+                "OOM killer will terminate process if memory limit is exceeded".to_string(),
+            ),
             expected_impact: "Prevent OOM kills and allow for traffic growth".to_string(),
             estimated_gain_pct: 30,
         }
@@ -247,8 +252,11 @@ impl RecommendationEngine {
                 docker: None,
                 env: Some("Enable task profiling: RUST_LOG=tokio=trace".to_string()),
             },
-            rationale: Some("Tasks taking >50ms block the executor and reduce throughput".to_string()),
-            expected_impact: "Move blocking operations to spawn_blocking or optimize PHP scripts".to_string(),
+            rationale: Some(
+                "Tasks taking >50ms block the executor and reduce throughput".to_string(),
+            ),
+            expected_impact: "Move blocking operations to spawn_blocking or optimize PHP scripts"
+                .to_string(),
             estimated_gain_pct: 25,
         }
     }
@@ -272,7 +280,7 @@ impl RecommendationEngine {
     }
 
     fn recommend_worker_count(
-        bottleneck: &Bottleneck,
+        _bottleneck: &Bottleneck,
         worker_stats: &PhpWorkerStats,
     ) -> Recommendation {
         let utilization_pct = if worker_stats.count > 0 {
@@ -326,8 +334,11 @@ impl RecommendationEngine {
                 docker: None,
                 env: Some("Enable OPcache: opcache.enable=1, opcache.jit=tracing".to_string()),
             },
-            rationale: Some("P99 execution time >1s indicates expensive PHP operations".to_string()),
-            expected_impact: "Profile and optimize slow endpoints, enable JIT compilation".to_string(),
+            rationale: Some(
+                "P99 execution time >1s indicates expensive PHP operations".to_string(),
+            ),
+            expected_impact: "Profile and optimize slow endpoints, enable JIT compilation"
+                .to_string(),
             estimated_gain_pct: 40,
         }
     }
@@ -342,10 +353,15 @@ impl RecommendationEngine {
                 immediate: None,
                 persistent: None,
                 docker: None,
-                env: Some("Reduce memory_limit in php.ini or enable garbage collection".to_string()),
+                env: Some(
+                    "Reduce memory_limit in php.ini or enable garbage collection".to_string(),
+                ),
             },
-            rationale: Some("Workers using >100MB may indicate memory leaks or inefficient code".to_string()),
-            expected_impact: "Profile memory usage, fix leaks, or reduce worker lifetime".to_string(),
+            rationale: Some(
+                "Workers using >100MB may indicate memory leaks or inefficient code".to_string(),
+            ),
+            expected_impact: "Profile memory usage, fix leaks, or reduce worker lifetime"
+                .to_string(),
             estimated_gain_pct: 15,
         }
     }
@@ -363,17 +379,119 @@ impl RecommendationEngine {
                 env: None,
             },
             rationale: Some("Lock contention >10% reduces concurrency benefits".to_string()),
-            expected_impact: "Use lock-free data structures or reduce critical section size".to_string(),
+            expected_impact: "Use lock-free data structures or reduce critical section size"
+                .to_string(),
             estimated_gain_pct: 10,
         }
     }
 
-    fn proactive_recommendations(
-        os_limits: &OsLimits,
-        tokio_metrics: &TokioMetrics,
-        worker_stats: &PhpWorkerStats,
-        platform: &str,
-    ) -> Vec<Recommendation> {
+    fn recommend_queue_capacity(bottleneck: &Bottleneck) -> Recommendation {
+        let capacity = bottleneck.threshold.unwrap_or(0);
+        let recommended = (capacity * 2).max(100);
+
+        Recommendation {
+            priority: match bottleneck.severity {
+                Severity::Critical => Priority::Critical,
+                _ => Priority::High,
+            },
+            category: "configuration".to_string(),
+            issue: format!(
+                "{} requests rejected with 503 by a full queue of {}",
+                bottleneck.current.unwrap_or(0),
+                capacity
+            ),
+            action: "increase_queue_capacity".to_string(),
+            commands: Commands {
+                immediate: None,
+                persistent: None,
+                docker: Some(format!("docker run -e QUEUE_CAPACITY={} ...", recommended)),
+                env: Some(format!("export QUEUE_CAPACITY={}", recommended)),
+            },
+            rationale: Some(
+                "A deeper queue absorbs short bursts instead of rejecting them; if it stays full, add PHP_WORKERS instead".to_string(),
+            ),
+            expected_impact: "Fewer 503 responses during traffic spikes, at the cost of queueing latency".to_string(),
+            estimated_gain_pct: 15,
+        }
+    }
+
+    fn recommend_tls_handshake(bottleneck: &Bottleneck) -> Recommendation {
+        Recommendation {
+            priority: match bottleneck.severity {
+                Severity::Critical => Priority::High,
+                _ => Priority::Medium,
+            },
+            category: "network".to_string(),
+            issue: format!(
+                "TLS handshakes average {}ms",
+                bottleneck.current.unwrap_or(0)
+            ),
+            action: "use_ecdsa_certificate".to_string(),
+            commands: Commands {
+                immediate: None,
+                persistent: Some(
+                    "openssl ecparam -genkey -name prime256v1 -noout -out tls-key.pem".to_string(),
+                ),
+                docker: None,
+                env: Some(
+                    "TLS_CERT=/path/to/ecdsa-cert.pem TLS_KEY=/path/to/tls-key.pem".to_string(),
+                ),
+            },
+            rationale: Some(
+                "ECDSA P-256 signatures cost a fraction of RSA-2048/4096 on the server side of the handshake".to_string(),
+            ),
+            expected_impact: "Lower time to first byte on new connections and less CPU per handshake".to_string(),
+            estimated_gain_pct: 10,
+        }
+    }
+
+    fn recommend_keepalive(bottleneck: &Bottleneck) -> Recommendation {
+        Recommendation {
+            priority: Priority::Medium,
+            category: "network".to_string(),
+            issue: format!(
+                "{}% of requests open a new TLS connection",
+                bottleneck.current.unwrap_or(0)
+            ),
+            action: "increase_keepalive_reuse".to_string(),
+            commands: Commands {
+                immediate: None,
+                persistent: None,
+                docker: Some("docker run -e HEADER_TIMEOUT_SECS=15 ...".to_string()),
+                env: Some("export HEADER_TIMEOUT_SECS=15".to_string()),
+            },
+            rationale: Some(
+                "HEADER_TIMEOUT_SECS is also how long an idle keep-alive connection waits for its next request; a short value forces clients to reconnect and handshake again".to_string(),
+            ),
+            expected_impact: "More requests per connection, fewer TLS handshakes".to_string(),
+            estimated_gain_pct: 10,
+        }
+    }
+
+    fn recommend_static_cache(bottleneck: &Bottleneck) -> Recommendation {
+        Recommendation {
+            priority: Priority::Medium,
+            category: "static".to_string(),
+            issue: format!(
+                "{} static responses served with STATIC_CACHE_TTL=off",
+                bottleneck.current.unwrap_or(0)
+            ),
+            action: "enable_static_cache".to_string(),
+            commands: Commands {
+                immediate: None,
+                persistent: None,
+                docker: Some("docker run -e STATIC_CACHE_TTL=1d ...".to_string()),
+                env: Some("export STATIC_CACHE_TTL=1d".to_string()),
+            },
+            rationale: Some(
+                "Without Cache-Control max-age browsers fetch or revalidate every asset on each page view; add STATIC_IMMUTABLE for fingerprinted asset paths".to_string(),
+            ),
+            expected_impact: "Fewer static requests reaching the server and faster repeat page loads".to_string(),
+            estimated_gain_pct: 10,
+        }
+    }
+
+    fn proactive_recommendations(os_limits: &OsLimits, platform: &str) -> Vec<Recommendation> {
         let mut recommendations = Vec::new();
 
         // Recommend TCP tuning for production
@@ -390,7 +508,10 @@ impl RecommendationEngine {
                         None
                     },
                     persistent: if platform == "linux" {
-                        Some("echo 'net.ipv4.tcp_max_syn_backlog = 8192' >> /etc/sysctl.conf".to_string())
+                        Some(
+                            "echo 'net.ipv4.tcp_max_syn_backlog = 8192' >> /etc/sysctl.conf"
+                                .to_string(),
+                        )
                     } else {
                         None
                     },
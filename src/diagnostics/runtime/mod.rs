@@ -1,2 +1,3 @@
 pub mod tokio_metrics;
+pub mod traffic_stats;
 pub mod worker_stats;
@@ -0,0 +1,75 @@
+use super::super::os::limits::LimitStatus;
+use serde::{Deserialize, Serialize};
+
+/// Handshakes needed before TLS timing and reuse are judged.
+pub const MIN_TLS_HANDSHAKES: u64 = 20;
+/// Static responses needed before a disabled `STATIC_CACHE_TTL` is reported.
+pub const MIN_STATIC_RESPONSES: u64 = 100;
+
+/// Request and connection counters supplied by the caller, cumulative since startup.
+#[derive(Debug, Clone, Default)]
+pub struct TrafficSnapshot {
+    pub queue_capacity: usize,
+    /// Requests rejected because the PHP queue was full.
+    pub dropped_requests: u64,
+    pub tls_handshakes: u64,
+    pub tls_handshake_time_us: u64,
+    pub static_responses: u64,
+    /// Static responses answered 304 Not Modified.
+    pub static_not_modified: u64,
+    pub static_cache_enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficStats {
+    pub queue_capacity: usize,
+    pub dropped_requests: u64,
+    pub dropped_pct: f64,
+    pub tls_handshakes: u64,
+    pub tls_handshake_avg_ms: f64,
+    /// New TLS connections per request; near 1 means keep-alive isn't reused.
+    pub tls_handshakes_per_request: f64,
+    pub static_responses: u64,
+    pub static_not_modified: u64,
+    pub static_cache_enabled: bool,
+    pub status: LimitStatus,
+}
+
+/// Collect queue, TLS and static file statistics
+pub fn collect_traffic_stats(snapshot: &TrafficSnapshot, total_requests: u64) -> TrafficStats {
+    let per_request = |count: u64| {
+        if total_requests > 0 {
+            count as f64 / total_requests as f64
+        } else {
+            0.0
+        }
+    };
+
+    let dropped_pct = per_request(snapshot.dropped_requests) * 100.0;
+    let tls_handshake_avg_ms = if snapshot.tls_handshakes > 0 {
+        snapshot.tls_handshake_time_us as f64 / snapshot.tls_handshakes as f64 / 1000.0
+    } else {
+        0.0
+    };
+
+    let status = if dropped_pct >= 1.0 {
+        LimitStatus::Critical
+    } else if snapshot.dropped_requests > 0 {
+        LimitStatus::Warning
+    } else {
+        LimitStatus::Ok
+    };
+
+    TrafficStats {
+        queue_capacity: snapshot.queue_capacity,
+        dropped_requests: snapshot.dropped_requests,
+        dropped_pct,
+        tls_handshakes: snapshot.tls_handshakes,
+        tls_handshake_avg_ms,
+        tls_handshakes_per_request: per_request(snapshot.tls_handshakes),
+        static_responses: snapshot.static_responses,
+        static_not_modified: snapshot.static_not_modified,
+        static_cache_enabled: snapshot.static_cache_enabled,
+        status,
+    }
+}
@@ -1,5 +1,46 @@
+use super::super::os::limits::LimitStatus;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+
+/// Live PHP worker pool figures supplied by the caller.
+///
+/// Samples that aren't collected can be left empty; the derived stats are
+/// then reported as zero.
+#[derive(Debug, Clone, Default)]
+pub struct WorkerSnapshot {
+    pub worker_count: usize,
+    pub busy_workers: usize,
+    pub queue_depth: usize,
+    pub total_requests: u64,
+    pub execution_times_ms: Vec<f64>,
+    pub wait_times_ms: Vec<f64>,
+    pub php_memory_per_worker: Vec<u64>,
+    pub file_cache_size: u64,
+    pub locks: LockTimes,
+    pub per_worker: Vec<WorkerActivityStats>,
+}
+
+/// Requests taken by one PHP worker and what it is doing now.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkerActivityStats {
+    /// Thread name, e.g. `ext-3`.
+    pub name: String,
+    pub requests: u64,
+    /// Milliseconds since the running request was picked up (None = idle).
+    pub busy_ms: Option<u64>,
+    /// Milliseconds since the last request finished (None = busy or never ran one).
+    pub idle_ms: Option<u64>,
+}
+
+/// Accumulated wait/hold times of instrumented locks, in nanoseconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LockTimes {
+    pub worker_pool_wait_ns: u64,
+    pub worker_pool_hold_ns: u64,
+    pub file_cache_wait_ns: u64,
+    pub file_cache_hold_ns: u64,
+    pub config_wait_ns: u64,
+    pub config_hold_ns: u64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PhpWorkerStats {
@@ -12,7 +53,9 @@ pub struct PhpWorkerStats {
     pub avg_wait_time_ms: f64,
     pub max_wait_time_ms: f64,
     pub total_requests: u64,
-    pub status: super::super::os::limits::LimitStatus,
+    pub status: LimitStatus,
+    #[serde(default)]
+    pub workers: Vec<WorkerActivityStats>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,7 +66,7 @@ pub struct MemoryStats {
     pub php_per_worker_max_bytes: u64,
     pub total_php_memory_bytes: u64,
     pub file_cache_bytes: u64,
-    pub status: super::super::os::limits::LimitStatus,
+    pub status: LimitStatus,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,36 +74,24 @@ pub struct LockStats {
     pub worker_pool_contention_pct: f64,
     pub file_cache_contention_pct: f64,
     pub config_lock_contention_pct: f64,
-    pub status: super::super::os::limits::LimitStatus,
+    pub status: LimitStatus,
 }
 
 /// Collect PHP worker pool statistics
-/// This would integrate with your existing worker pool implementation
-pub fn collect_worker_stats(
-    worker_count: usize,
-    busy_workers: usize,
-    queue_depth: usize,
-    total_requests: u64,
-    execution_times_ms: &[f64],
-    wait_times_ms: &[f64],
-) -> PhpWorkerStats {
+pub fn collect_worker_stats(snapshot: &WorkerSnapshot) -> PhpWorkerStats {
+    let worker_count = snapshot.worker_count;
+    let busy_workers = snapshot.busy_workers;
+    let queue_depth = snapshot.queue_depth;
     let idle = worker_count.saturating_sub(busy_workers);
 
-    let avg_execution_time_ms = if !execution_times_ms.is_empty() {
-        execution_times_ms.iter().sum::<f64>() / execution_times_ms.len() as f64
-    } else {
-        0.0
-    };
-
-    let p99_execution_time_ms = calculate_percentile(execution_times_ms, 0.99);
-
-    let avg_wait_time_ms = if !wait_times_ms.is_empty() {
-        wait_times_ms.iter().sum::<f64>() / wait_times_ms.len() as f64
-    } else {
-        0.0
-    };
-
-    let max_wait_time_ms = wait_times_ms.iter().cloned().fold(0.0f64, f64::max);
+    let avg_execution_time_ms = mean(&snapshot.execution_times_ms);
+    let p99_execution_time_ms = calculate_percentile(&snapshot.execution_times_ms, 0.99);
+    let avg_wait_time_ms = mean(&snapshot.wait_times_ms);
+    let max_wait_time_ms = snapshot
+        .wait_times_ms
+        .iter()
+        .copied()
+        .fold(0.0f64, f64::max);
 
     // Determine health status
     let utilization_pct = if worker_count > 0 {
@@ -70,11 +101,11 @@ pub fn collect_worker_stats(
     };
 
     let status = if utilization_pct > 90.0 && queue_depth > 10 {
-        super::super::os::limits::LimitStatus::Critical
+        LimitStatus::Critical
     } else if utilization_pct > 70.0 || queue_depth > 5 {
-        super::super::os::limits::LimitStatus::Warning
+        LimitStatus::Warning
     } else {
-        super::super::os::limits::LimitStatus::Ok
+        LimitStatus::Ok
     };
 
     PhpWorkerStats {
@@ -86,27 +117,15 @@ pub fn collect_worker_stats(
         p99_execution_time_ms,
         avg_wait_time_ms,
         max_wait_time_ms,
-        total_requests,
+        total_requests: snapshot.total_requests,
         status,
+        workers: snapshot.per_worker.clone(),
     }
 }
 
 /// Collect memory statistics
-pub fn collect_memory_stats(
-    php_worker_count: usize,
-    php_memory_per_worker: Vec<u64>,
-    file_cache_size: u64,
-) -> MemoryStats {
-    use sysinfo::{ProcessExt, System, SystemExt};
-
-    let mut sys = System::new_all();
-    sys.refresh_all();
-
-    let pid = sysinfo::get_current_pid().unwrap();
-    let process = sys.process(pid).unwrap();
-
-    let rust_allocated_bytes = process.memory() * 1024; // Convert from KB
-    let rust_resident_bytes = process.memory() * 1024;
+pub fn collect_memory_stats(php_memory_per_worker: &[u64], file_cache_size: u64) -> MemoryStats {
+    let resident_bytes = process_resident_bytes();
 
     let total_php_memory_bytes: u64 = php_memory_per_worker.iter().sum();
     let php_per_worker_avg_bytes = if !php_memory_per_worker.is_empty() {
@@ -115,11 +134,11 @@ pub fn collect_memory_stats(
         0
     };
 
-    let php_per_worker_max_bytes = php_memory_per_worker.iter().cloned().max().unwrap_or(0);
+    let php_per_worker_max_bytes = php_memory_per_worker.iter().copied().max().unwrap_or(0);
 
-    // Calculate total memory usage percentage (if we can detect system memory)
-    let total_memory = sys.total_memory() * 1024; // Convert from KB
-    let total_used = rust_allocated_bytes + total_php_memory_bytes + file_cache_size;
+    // Usage as a share of physical memory
+    let total_memory = physical_memory_bytes();
+    let total_used = resident_bytes + total_php_memory_bytes + file_cache_size;
     let usage_pct = if total_memory > 0 {
         (total_used as f64 / total_memory as f64) * 100.0
     } else {
@@ -127,16 +146,16 @@ pub fn collect_memory_stats(
     };
 
     let status = if usage_pct > 90.0 {
-        super::super::os::limits::LimitStatus::Critical
+        LimitStatus::Critical
     } else if usage_pct > 75.0 {
-        super::super::os::limits::LimitStatus::Warning
+        LimitStatus::Warning
     } else {
-        super::super::os::limits::LimitStatus::Ok
+        LimitStatus::Ok
     };
 
     MemoryStats {
-        rust_allocated_bytes,
-        rust_resident_bytes,
+        rust_allocated_bytes: resident_bytes,
+        rust_resident_bytes: resident_bytes,
         php_per_worker_avg_bytes,
         php_per_worker_max_bytes,
         total_php_memory_bytes,
@@ -146,29 +165,24 @@ pub fn collect_memory_stats(
 }
 
 /// Collect lock contention statistics
-/// This would integrate with instrumentation around your mutexes/RwLocks
-pub fn collect_lock_stats(
-    worker_pool_wait_ns: u64,
-    worker_pool_hold_ns: u64,
-    file_cache_wait_ns: u64,
-    file_cache_hold_ns: u64,
-    config_wait_ns: u64,
-    config_hold_ns: u64,
-) -> LockStats {
-    let worker_pool_contention_pct = calculate_contention_pct(worker_pool_wait_ns, worker_pool_hold_ns);
-    let file_cache_contention_pct = calculate_contention_pct(file_cache_wait_ns, file_cache_hold_ns);
-    let config_lock_contention_pct = calculate_contention_pct(config_wait_ns, config_hold_ns);
+pub fn collect_lock_stats(times: &LockTimes) -> LockStats {
+    let worker_pool_contention_pct =
+        calculate_contention_pct(times.worker_pool_wait_ns, times.worker_pool_hold_ns);
+    let file_cache_contention_pct =
+        calculate_contention_pct(times.file_cache_wait_ns, times.file_cache_hold_ns);
+    let config_lock_contention_pct =
+        calculate_contention_pct(times.config_wait_ns, times.config_hold_ns);
 
     let max_contention = worker_pool_contention_pct
         .max(file_cache_contention_pct)
         .max(config_lock_contention_pct);
 
     let status = if max_contention > 20.0 {
-        super::super::os::limits::LimitStatus::Critical
+        LimitStatus::Critical
     } else if max_contention > 10.0 {
-        super::super::os::limits::LimitStatus::Warning
+        LimitStatus::Warning
     } else {
-        super::super::os::limits::LimitStatus::Ok
+        LimitStatus::Ok
     };
 
     LockStats {
@@ -179,6 +193,54 @@ pub fn collect_lock_stats(
     }
 }
 
+/// Resident set size of this process.
+#[cfg(target_os = "linux")]
+fn process_resident_bytes() -> u64 {
+    // Second field of statm is resident pages
+    std::fs::read_to_string("/proc/self/statm")
+        .ok()
+        .and_then(|s| s.split_whitespace().nth(1)?.parse::<u64>().ok())
+        .map_or(0, |pages| pages * page_size())
+}
+
+/// Peak resident set size; the current RSS isn't exposed without Mach calls.
+#[cfg(not(target_os = "linux"))]
+fn process_resident_bytes() -> u64 {
+    // SAFETY: rusage is plain data and getrusage() fills it in
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return 0;
+    }
+    // ru_maxrss is in bytes on macOS
+    usage.ru_maxrss.max(0) as u64
+}
+
+fn physical_memory_bytes() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    let pages = unsafe { libc::sysconf(libc::_SC_PHYS_PAGES) };
+    if pages <= 0 {
+        return 0;
+    }
+    pages as u64 * page_size()
+}
+
+fn page_size() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 {
+        size as u64
+    } else {
+        4096
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
 fn calculate_contention_pct(wait_time_ns: u64, hold_time_ns: u64) -> f64 {
     let total_time = wait_time_ns + hold_time_ns;
     if total_time == 0 {
@@ -193,7 +255,7 @@ fn calculate_percentile(values: &[f64], percentile: f64) -> f64 {
     }
 
     let mut sorted = values.to_vec();
-    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    sorted.sort_by(f64::total_cmp);
 
     let index = ((sorted.len() as f64 - 1.0) * percentile) as usize;
     sorted[index.min(sorted.len() - 1)]
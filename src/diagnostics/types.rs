@@ -1,12 +1,14 @@
-use serde::{Deserialize, Serialize};
 use super::analyzer::Bottleneck;
 use super::os::limits::OsLimits;
 use super::recommender::Recommendation;
-use super::runtime::{tokio_metrics::TokioMetrics, worker_stats::*};
+use super::runtime::{tokio_metrics::TokioMetrics, traffic_stats::TrafficStats, worker_stats::*};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiagnosticResponse {
-    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Collection time, seconds since the Unix epoch.
+    pub timestamp: u64,
     pub platform: PlatformInfo,
     pub os_limits: OsLimits,
     pub runtime_metrics: RuntimeMetrics,
@@ -32,6 +34,7 @@ pub struct RuntimeMetrics {
     pub php_workers: PhpWorkerStats,
     pub memory: MemoryStats,
     pub locks: LockStats,
+    pub traffic: TrafficStats,
 }
 
 impl DiagnosticResponse {
@@ -41,11 +44,12 @@ impl DiagnosticResponse {
         runtime_metrics: &RuntimeMetrics,
         bottlenecks: &[Bottleneck],
     ) -> u8 {
+        use super::analyzer::Severity;
+
         let mut score = 100u8;
 
         // Deduct points for critical issues
         for bottleneck in bottlenecks {
-            use super::analyzer::Severity;
             match bottleneck.severity {
                 Severity::Critical => score = score.saturating_sub(20),
                 Severity::Warning => score = score.saturating_sub(10),
@@ -69,7 +73,8 @@ impl DiagnosticResponse {
 
         // Deduct points for worker saturation
         let worker_utilization = if runtime_metrics.php_workers.count > 0 {
-            (runtime_metrics.php_workers.busy as f64 / runtime_metrics.php_workers.count as f64) * 100.0
+            (runtime_metrics.php_workers.busy as f64 / runtime_metrics.php_workers.count as f64)
+                * 100.0
         } else {
             0.0
         };
@@ -78,7 +83,14 @@ impl DiagnosticResponse {
             score = score.saturating_sub(12);
         }
 
-        // Ensure score is in valid range
-        score.max(0).min(100)
+        score
     }
 }
+
+/// Seconds since the Unix epoch.
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,117 @@
+//! Per-worker request counts and busy/idle times, for `/diagnostics`.
+//!
+//! Each worker pool owns one counter slot per worker id. A replacement for a
+//! panicked worker keeps counting in its predecessor's slot.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use crate::diagnostics::WorkerActivityStats;
+
+/// Reference point for the `*_since_ms` timestamps.
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// Milliseconds after `epoch()` plus one, so 0 can mean "not set".
+fn now_ms() -> u64 {
+    epoch().elapsed().as_millis() as u64 + 1
+}
+
+/// Counters of one worker.
+#[derive(Default)]
+struct WorkerCounters {
+    requests: AtomicU64,
+    /// When the running request was picked up (0 = idle).
+    busy_since_ms: AtomicU64,
+    /// When the last request finished (0 = busy or never ran one).
+    idle_since_ms: AtomicU64,
+}
+
+/// Counter slots of one pool's workers.
+pub(crate) struct PoolActivity {
+    name_prefix: String,
+    workers: Box<[WorkerCounters]>,
+}
+
+impl PoolActivity {
+    /// Slots for a pool of `workers` workers named `{name_prefix}-{id}`.
+    pub(crate) fn new(name_prefix: &str, workers: usize) -> Self {
+        Self {
+            name_prefix: name_prefix.to_string(),
+            workers: (0..workers).map(|_| WorkerCounters::default()).collect(),
+        }
+    }
+
+    /// Worker `id` picked up a request.
+    pub(crate) fn record_pickup(&self, id: usize) {
+        let Some(worker) = self.workers.get(id) else {
+            return;
+        };
+        worker.requests.fetch_add(1, Ordering::Relaxed);
+        worker.busy_since_ms.store(now_ms(), Ordering::Relaxed);
+        worker.idle_since_ms.store(0, Ordering::Relaxed);
+    }
+
+    /// Worker `id` is done with its request.
+    pub(crate) fn record_finish(&self, id: usize) {
+        let Some(worker) = self.workers.get(id) else {
+            return;
+        };
+        worker.idle_since_ms.store(now_ms(), Ordering::Relaxed);
+        worker.busy_since_ms.store(0, Ordering::Relaxed);
+    }
+
+    /// Current activity of every worker, in id order.
+    pub(crate) fn snapshot(&self) -> Vec<WorkerActivityStats> {
+        let now = now_ms();
+        let since = |at: u64| (at > 0).then(|| now.saturating_sub(at));
+        self.workers
+            .iter()
+            .enumerate()
+            .map(|(id, worker)| {
+                let busy_ms = since(worker.busy_since_ms.load(Ordering::Relaxed));
+                WorkerActivityStats {
+                    name: format!("{}-{}", self.name_prefix, id),
+                    requests: worker.requests.load(Ordering::Relaxed),
+                    busy_ms,
+                    idle_ms: busy_ms
+                        .is_none()
+                        .then(|| since(worker.idle_since_ms.load(Ordering::Relaxed)))
+                        .flatten(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_activity() {
+        let pool = PoolActivity::new("activity-test", 2);
+        pool.record_pickup(1);
+        pool.record_finish(1);
+        pool.record_pickup(1);
+        pool.record_pickup(7); // unknown id is ignored
+
+        let workers = pool.snapshot();
+        assert_eq!(workers.len(), 2);
+        assert_eq!(workers[0].name, "activity-test-0");
+        assert_eq!(workers[0].requests, 0);
+        assert_eq!((workers[0].busy_ms, workers[0].idle_ms), (None, None));
+        assert_eq!(workers[1].requests, 2);
+        assert!(workers[1].busy_ms.unwrap() < 5000);
+        assert_eq!(workers[1].idle_ms, None);
+
+        // Idle time starts when the request finishes, not at pickup
+        pool.record_finish(1);
+        let workers = pool.snapshot();
+        assert_eq!(workers[1].busy_ms, None);
+        assert!(workers[1].idle_ms.unwrap() < 5000);
+    }
+}
@@ -5,18 +5,23 @@
 
 use std::cell::RefCell;
 use std::ffi::{c_char, c_int, c_void, CString};
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 use tokio::sync::{mpsc as tokio_mpsc, oneshot};
 
+use super::activity::PoolActivity;
+use super::queue::{QueueSender, Receivers};
+use super::{REQUEST_TIMEOUT_ERROR, WORKER_DROPPED_ERROR};
 use crate::bridge::{FinishChannel, FinishData, StreamingChannel};
+use crate::diagnostics::WorkerActivityStats;
 use crate::executor::sapi::{self, ResponseChunk};
 use crate::profiler::ProfileData;
 use crate::server::response::StreamChunk;
-use crate::types::{ScriptRequest, ScriptResponse};
+use crate::types::{Priority, ScriptRequest, ScriptResponse};
 
 // =============================================================================
 // Execute Result Types
@@ -61,6 +66,8 @@ extern "C" {
 /// PHP code to finalize output - just flush buffers
 pub static FINALIZE_CODE: &[u8] = b"1;\0";
 pub static FINALIZE_NAME: &[u8] = b"f\0";
+/// Source name reported for `ScriptRequest::inline_code` in PHP errors
+pub static INLINE_CODE_NAME: &[u8] = b"tokio_php_builtin\0";
 
 /// Name for memfd (Linux only)
 #[cfg(target_os = "linux")]
@@ -73,6 +80,21 @@ pub static MEMFD_NAME: &[u8] = b"php_out\0";
 thread_local! {
     /// Reusable buffer for reading PHP output (avoids allocation per request)
     pub static OUTPUT_BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+
+    /// Started-worker counter of the pool that spawned this thread.
+    static STARTED_WORKERS: RefCell<Option<Arc<AtomicUsize>>> = const { RefCell::new(None) };
+}
+
+/// Marks the current worker thread as ready to take requests.
+///
+/// Worker loops call this once their per-thread PHP state is set up;
+/// [`WorkerPool::all_started`] turns true when every worker has.
+pub fn mark_worker_started() {
+    STARTED_WORKERS.with(|started| {
+        if let Some(started) = started.borrow().as_ref() {
+            started.fetch_add(1, Ordering::Release);
+        }
+    });
 }
 
 // =============================================================================
@@ -110,19 +132,78 @@ pub struct LegacyWorkerRequest {
     pub explicit_sse: bool,
 }
 
+/// Receiving end of a worker pool's queue, one per worker thread.
+pub type WorkerQueue = super::queue::WorkerQueue<WorkerRequest>;
+
 /// Handle to a worker thread
 pub struct WorkerThread {
     pub handle: JoinHandle<()>,
 }
 
-/// Default queue capacity multiplier per worker
-const DEFAULT_QUEUE_MULTIPLIER: usize = 100;
+/// Starts worker threads and replaces any that panic.
+///
+/// A panicking worker drops its in-flight request, so the caller gets
+/// WORKER_DROPPED_ERROR instead of waiting, and a fresh thread (new PHP
+/// thread state) takes its id. Panics across FFI abort the process and
+/// cannot be recovered here.
+#[derive(Clone)]
+struct WorkerSpawner<F> {
+    name_prefix: Arc<str>,
+    queue: Receivers<WorkerRequest>,
+    started: Arc<AtomicUsize>,
+    handles: Arc<Mutex<Vec<WorkerThread>>>,
+    worker_fn: F,
+}
+
+impl<F> WorkerSpawner<F>
+where
+    F: Fn(usize, WorkerQueue) + Send + Clone + 'static,
+{
+    fn spawn(&self, id: usize) -> Result<(), String> {
+        let spawner = self.clone();
+        let handle = thread::Builder::new()
+            .name(format!("{}-{}", self.name_prefix, id))
+            .spawn(move || {
+                STARTED_WORKERS.with(|s| *s.borrow_mut() = Some(Arc::clone(&spawner.started)));
+                let rx = spawner.queue.get(id);
+                let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| {
+                    (spawner.worker_fn)(id, rx);
+                })) else {
+                    return;
+                };
+
+                let reason = panic
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("unknown panic");
+                tracing::error!(
+                    "Worker {}-{} panicked ({}), starting a replacement",
+                    spawner.name_prefix,
+                    id,
+                    reason
+                );
+                super::record_worker_respawn();
+                if let Err(e) = spawner.spawn(id) {
+                    tracing::error!("{}", e);
+                }
+            })
+            .map_err(|e| format!("Failed to spawn worker thread {}: {}", id, e))?;
+
+        self.handles
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(WorkerThread { handle });
+        Ok(())
+    }
+}
 
-/// Error returned when queue is full
-pub const QUEUE_FULL_ERROR: &str = "Queue full";
+/// Script run by `WorkerPool::opcache_status()` (scripts list omitted).
+const OPCACHE_STATUS_CODE: &str = "header('Content-Type: application/json');\
+    echo json_encode(function_exists('opcache_get_status') ? opcache_get_status(false) : false);";
 
-/// Error returned when request times out
-pub const REQUEST_TIMEOUT_ERROR: &str = "Request timeout";
+/// Upper bound for the OPcache status script, queue wait included.
+const OPCACHE_STATUS_TIMEOUT: Duration = Duration::from_secs(5);
 
 // =============================================================================
 // Heartbeat Context for Request Timeout Extension
@@ -139,6 +220,9 @@ pub struct HeartbeatContext {
     deadline_ms: AtomicU64,
     /// Maximum extension allowed per heartbeat call (= original REQUEST_TIMEOUT)
     max_extension_secs: u64,
+    /// Latest deadline heartbeats may set, as milliseconds from start
+    /// (u64::MAX = no cap)
+    max_deadline_ms: u64,
 }
 
 impl HeartbeatContext {
@@ -150,11 +234,21 @@ impl HeartbeatContext {
             start,
             deadline_ms: AtomicU64::new(deadline_ms),
             max_extension_secs: timeout_secs,
+            max_deadline_ms: u64::MAX,
         }
     }
 
+    /// Caps heartbeats at `max` past the initial timeout (None = no cap).
+    pub fn with_extension_cap(mut self, max: Option<Duration>) -> Self {
+        if let Some(max) = max {
+            self.max_deadline_ms = self.max_extension_secs * 1000 + max.as_millis() as u64;
+        }
+        self
+    }
+
     /// Extends the deadline by `secs` seconds from now.
-    /// Returns false if `secs` exceeds the max extension limit.
+    /// Returns false if `secs` exceeds the max extension limit, or if the
+    /// new deadline would pass the extension cap; the deadline then stays.
     pub fn heartbeat(&self, secs: u64) -> bool {
         if secs == 0 || secs > self.max_extension_secs {
             return false;
@@ -162,6 +256,11 @@ impl HeartbeatContext {
 
         let elapsed_ms = self.start.elapsed().as_millis() as u64;
         let new_deadline_ms = elapsed_ms + secs * 1000;
+        let extended = new_deadline_ms <= self.max_deadline_ms;
+        super::record_heartbeat(extended);
+        if !extended {
+            return false;
+        }
         self.deadline_ms.store(new_deadline_ms, Ordering::Release);
         true
     }
@@ -204,73 +303,86 @@ pub extern "C" fn tokio_php_heartbeat(ctx: *mut std::ffi::c_void, secs: u64) ->
 
 /// Generic worker pool for PHP execution
 pub struct WorkerPool {
-    request_tx: std_mpsc::SyncSender<WorkerRequest>,
-    workers: Vec<WorkerThread>,
+    request_tx: QueueSender<WorkerRequest>,
+    /// Worker threads, replacements for panicked ones included.
+    workers: Arc<Mutex<Vec<WorkerThread>>>,
     worker_count: AtomicUsize,
+    /// Workers that called [`mark_worker_started`].
+    started: Arc<AtomicUsize>,
+    /// Per-worker request counts and busy/idle times.
+    activity: Arc<PoolActivity>,
     queue_capacity: usize,
+    /// How far heartbeats may push a deadline past its timeout (None = no cap).
+    extension_cap: Option<Duration>,
 }
 
 impl WorkerPool {
-    /// Creates a new worker pool with the given number of workers.
-    /// The `worker_fn` is called for each worker thread.
-    /// Queue capacity defaults to workers * 100.
-    pub fn new<F>(num_workers: usize, name_prefix: &str, worker_fn: F) -> Result<Self, String>
-    where
-        F: Fn(usize, Arc<Mutex<std_mpsc::Receiver<WorkerRequest>>>) + Send + Clone + 'static,
-    {
-        Self::with_queue_capacity(
-            num_workers,
-            name_prefix,
-            num_workers * DEFAULT_QUEUE_MULTIPLIER,
-            worker_fn,
-        )
-    }
-
-    /// Creates a new worker pool with custom queue capacity.
-    pub fn with_queue_capacity<F>(
+    /// Creates a worker pool with the given number of workers, each running
+    /// `worker_fn`. `queue_capacity` of 0 means workers * 100;
+    /// `priority_capacity` slots are reserved for high-priority requests on
+    /// top of it (`PRIORITY_QUEUE_CAPACITY`, 0 = no lane).
+    pub fn with_lanes<F>(
         num_workers: usize,
         name_prefix: &str,
         queue_capacity: usize,
+        priority_capacity: usize,
         worker_fn: F,
     ) -> Result<Self, String>
     where
-        F: Fn(usize, Arc<Mutex<std_mpsc::Receiver<WorkerRequest>>>) + Send + Clone + 'static,
+        F: Fn(usize, WorkerQueue) + Send + Clone + 'static,
     {
-        let (request_tx, request_rx) = std_mpsc::sync_channel::<WorkerRequest>(queue_capacity);
-        let request_rx = Arc::new(Mutex::new(request_rx));
+        let queue_capacity = if queue_capacity > 0 {
+            queue_capacity
+        } else {
+            num_workers * crate::config::DEFAULT_QUEUE_MULTIPLIER
+        };
+        let activity = Arc::new(PoolActivity::new(name_prefix, num_workers));
+        let request_tx = QueueSender::new(
+            num_workers,
+            queue_capacity,
+            priority_capacity,
+            Arc::clone(&activity),
+        );
 
-        let mut workers = Vec::with_capacity(num_workers);
+        let started = Arc::new(AtomicUsize::new(0));
+        let workers = Arc::new(Mutex::new(Vec::with_capacity(num_workers)));
+        let spawner = WorkerSpawner {
+            name_prefix: Arc::from(name_prefix),
+            queue: request_tx.receivers(),
+            started: Arc::clone(&started),
+            handles: Arc::clone(&workers),
+            worker_fn,
+        };
 
         for id in 0..num_workers {
-            let rx = Arc::clone(&request_rx);
-            let worker_fn = worker_fn.clone();
-            let thread_name = format!("{}-{}", name_prefix, id);
-
-            let handle = thread::Builder::new()
-                .name(thread_name)
-                .spawn(move || {
-                    worker_fn(id, rx);
-                })
-                .map_err(|e| format!("Failed to spawn worker thread {}: {}", id, e))?;
-
-            workers.push(WorkerThread { handle });
+            spawner.spawn(id)?;
         }
 
         tracing::info!(
-            "WorkerPool '{}' created with {} workers, queue capacity {}",
+            "WorkerPool '{}' created with {} workers, queue capacity {} (+{} high priority)",
             name_prefix,
             num_workers,
-            queue_capacity
+            queue_capacity,
+            priority_capacity
         );
 
         Ok(Self {
             request_tx,
             workers,
             worker_count: AtomicUsize::new(num_workers),
+            started,
+            activity,
             queue_capacity,
+            extension_cap: None,
         })
     }
 
+    /// Caps how far `tokio_request_heartbeat()` can extend a request beyond
+    /// its initial timeout (`MAX_REQUEST_EXTENSION`, None = unlimited).
+    pub fn set_extension_cap(&mut self, max: Option<Duration>) {
+        self.extension_cap = max;
+    }
+
     /// Executes a request asynchronously via the worker pool.
     /// Returns QUEUE_FULL_ERROR if the queue is full.
     /// Returns REQUEST_TIMEOUT_ERROR if the request times out.
@@ -290,24 +402,27 @@ impl WorkerPool {
         let queued_at = Instant::now();
 
         // Create heartbeat context reusing queued_at
-        let heartbeat_ctx =
-            timeout.map(|t| Arc::new(HeartbeatContext::new(queued_at, t.as_secs())));
+        let heartbeat_ctx = timeout.map(|t| {
+            Arc::new(
+                HeartbeatContext::new(queued_at, t.as_secs())
+                    .with_extension_cap(self.extension_cap),
+            )
+        });
 
         // Create streaming channel (buffer size of 32 is enough for collecting)
         let (stream_tx, mut stream_rx) = tokio_mpsc::channel::<ResponseChunk>(32);
 
         // Use try_send to avoid blocking and detect queue full
-        self.request_tx
-            .try_send(WorkerRequest {
+        let priority = request.priority;
+        self.request_tx.try_send(
+            WorkerRequest {
                 request,
                 stream_tx,
                 queued_at,
                 heartbeat_ctx: heartbeat_ctx.clone(),
-            })
-            .map_err(|e| match e {
-                std_mpsc::TrySendError::Full(_) => QUEUE_FULL_ERROR.to_string(),
-                std_mpsc::TrySendError::Disconnected(_) => "Worker pool shut down".to_string(),
-            })?;
+            },
+            priority,
+        )?;
 
         // Collect streaming response into ScriptResponse
         let mut headers: Vec<(String, String)> = Vec::new();
@@ -345,7 +460,7 @@ impl WorkerPool {
                                         return Err(e);
                                     }
                                     None => {
-                                        return Err("Worker dropped connection".to_string());
+                                        return Err(WORKER_DROPPED_ERROR.to_string());
                                     }
                                 }
                             }
@@ -358,28 +473,32 @@ impl WorkerPool {
                 }
             }
         } else {
-            // No timeout - just collect all chunks
-            while let Some(chunk) = stream_rx.recv().await {
-                match chunk {
-                    ResponseChunk::Headers {
+            // No timeout - just collect all chunks; a channel closed before
+            // End means the worker died mid-request
+            loop {
+                match stream_rx.recv().await {
+                    Some(ResponseChunk::Headers {
                         status: s,
                         headers: h,
-                    } => {
+                    }) => {
                         status = s;
                         headers = h;
                     }
-                    ResponseChunk::Body(data) => {
+                    Some(ResponseChunk::Body(data)) => {
                         body.extend_from_slice(&data);
                     }
-                    ResponseChunk::Profile(p) => {
+                    Some(ResponseChunk::Profile(p)) => {
                         profile = Some(*p);
                     }
-                    ResponseChunk::End => {
+                    Some(ResponseChunk::End) => {
                         break;
                     }
-                    ResponseChunk::Error(e) => {
+                    Some(ResponseChunk::Error(e)) => {
                         return Err(e);
                     }
+                    None => {
+                        return Err(WORKER_DROPPED_ERROR.to_string());
+                    }
                 }
             }
         }
@@ -416,23 +535,27 @@ impl WorkerPool {
         let queued_at = Instant::now();
 
         // Create heartbeat context
-        let heartbeat_ctx =
-            timeout.map(|t| Arc::new(HeartbeatContext::new(queued_at, t.as_secs())));
+        let heartbeat_ctx = timeout.map(|t| {
+            Arc::new(
+                HeartbeatContext::new(queued_at, t.as_secs())
+                    .with_extension_cap(self.extension_cap),
+            )
+        });
 
-        // Create streaming channel with reasonable buffer
-        let (stream_tx, stream_rx) = tokio_mpsc::channel::<ResponseChunk>(32);
+        // Bounded by STREAM_BUFFER_SIZE: a slow client makes the worker wait
+        let (stream_tx, stream_rx) =
+            tokio_mpsc::channel::<ResponseChunk>(request.output_limits.stream_buffer_size.max(1));
 
-        self.request_tx
-            .try_send(WorkerRequest {
+        let priority = request.priority;
+        self.request_tx.try_send(
+            WorkerRequest {
                 request,
                 stream_tx,
                 queued_at,
                 heartbeat_ctx,
-            })
-            .map_err(|e| match e {
-                std_mpsc::TrySendError::Full(_) => QUEUE_FULL_ERROR.to_string(),
-                std_mpsc::TrySendError::Disconnected(_) => "Worker pool shut down".to_string(),
-            })?;
+            },
+            priority,
+        )?;
 
         Ok(stream_rx)
     }
@@ -506,7 +629,7 @@ impl WorkerPool {
                 // Profile before headers - shouldn't happen, treat as error
                 return Err("Received profile chunk before headers".to_string());
             }
-            None => return Err("Worker dropped connection".to_string()),
+            None => return Err(WORKER_DROPPED_ERROR.to_string()),
         };
 
         // Check if this is streaming mode:
@@ -589,6 +712,30 @@ impl WorkerPool {
         }
     }
 
+    /// Collects `opcache_get_status()` as JSON by evaluating a built-in
+    /// snippet on whichever worker is free (nothing is written to disk).
+    ///
+    /// OPcache shared memory belongs to the process, so every ZTS worker
+    /// reports the same state and one run is enough.
+    pub async fn opcache_status(&self) -> Result<String, String> {
+        let response = self
+            .execute(ScriptRequest {
+                inline_code: Some(OPCACHE_STATUS_CODE),
+                timeout: Some(OPCACHE_STATUS_TIMEOUT),
+                request_id: "opcache-status".to_string(),
+                // Monitoring must not starve behind a full queue
+                priority: Priority::High,
+                ..Default::default()
+            })
+            .await?;
+
+        match response.body.trim() {
+            "false" => Err("OPcache is not enabled".to_string()),
+            "" => Err("OPcache status script produced no output".to_string()),
+            body => Ok(body.to_string()),
+        }
+    }
+
     /// Returns the queue capacity
     pub fn queue_capacity(&self) -> usize {
         self.queue_capacity
@@ -599,10 +746,27 @@ impl WorkerPool {
         self.worker_count.load(Ordering::Relaxed)
     }
 
+    /// Returns request counts and busy/idle times of each worker
+    pub fn worker_activity(&self) -> Vec<WorkerActivityStats> {
+        self.activity.snapshot()
+    }
+
+    /// Returns true once every worker has finished its thread setup
+    pub fn all_started(&self) -> bool {
+        self.started.load(Ordering::Acquire) >= self.worker_count()
+    }
+
     /// Waits for all workers to finish
     pub fn join_all(&mut self) {
-        for worker in self.workers.drain(..) {
-            let _ = worker.handle.join();
+        // A worker may still push a replacement while we join
+        loop {
+            let next = self.workers.lock().unwrap_or_else(|e| e.into_inner()).pop();
+            match next {
+                Some(worker) => {
+                    let _ = worker.handle.join();
+                }
+                None => break,
+            }
         }
     }
 }
@@ -799,6 +963,10 @@ pub fn build_superglobals_code(request: &ScriptRequest) -> String {
 pub fn build_combined_code(request: &ScriptRequest) -> String {
     let mut code = String::with_capacity(4096);
     code.push_str(&build_superglobals_code(request));
+    if let Some(inline) = request.inline_code {
+        code.push_str(inline);
+        return code;
+    }
     code.push_str("require'");
     write_escaped(&mut code, &request.script_path);
     code.push_str("';");
@@ -1040,19 +1208,17 @@ pub fn execute_php_script_finish(
 
 /// Worker thread main loop - processes requests until channel closes.
 /// Uses streaming output via SAPI ub_write callback.
-pub fn worker_main_loop(id: usize, rx: Arc<Mutex<std_mpsc::Receiver<WorkerRequest>>>) {
+pub fn worker_main_loop(id: usize, mut rx: WorkerQueue) {
     // Initialize thread-local storage for ZTS
     unsafe {
         let _ = ts_resource_ex(0, ptr::null_mut());
     }
 
     tracing::debug!("Worker {}: Thread-local storage initialized", id);
+    mark_worker_started();
 
     loop {
-        let work = {
-            let guard = rx.lock().unwrap();
-            guard.recv()
-        };
+        let work = rx.recv();
 
         match work {
             Ok(WorkerRequest {
@@ -1065,7 +1231,7 @@ pub fn worker_main_loop(id: usize, rx: Arc<Mutex<std_mpsc::Receiver<WorkerReques
                 sapi::clear_captured_headers();
 
                 // Initialize streaming state (output will go through ub_write callback)
-                sapi::init_stream_state(stream_tx);
+                sapi::init_stream_state(stream_tx, request.output_limits.stream_write_timeout);
 
                 // Start PHP request
                 let startup_ok = unsafe { php_request_startup() } == 0;
@@ -1120,6 +1286,8 @@ pub fn worker_main_loop(id: usize, rx: Arc<Mutex<std_mpsc::Receiver<WorkerReques
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::executor::QUEUE_FULL_ERROR;
+    use std::sync::mpsc as std_mpsc;
 
     // -------------------------------------------------------------------------
     // HeartbeatContext tests
@@ -1183,6 +1351,22 @@ mod tests {
         assert!(ctx.heartbeat(30));
     }
 
+    #[test]
+    fn test_heartbeat_extension_cap() {
+        let start = Instant::now() - Duration::from_secs(20);
+        let ctx =
+            HeartbeatContext::new(start, 30).with_extension_cap(Some(Duration::from_secs(15)));
+
+        // 20s in, the deadline may reach 45s
+        assert!(ctx.heartbeat(10));
+        let before = ctx.remaining().unwrap();
+        assert!(!ctx.heartbeat(30));
+
+        // A refused heartbeat leaves the deadline alone
+        assert!(ctx.remaining().unwrap() <= before);
+        assert!(ctx.remaining().unwrap().as_secs() >= 9);
+    }
+
     #[test]
     fn test_heartbeat_expired() {
         let start = Instant::now() - Duration::from_secs(100);
@@ -1372,6 +1556,19 @@ mod tests {
         assert!(code.ends_with("require'/var/www/html/it\\'s.php';"));
     }
 
+    #[test]
+    fn test_build_combined_code_inline() {
+        let request = ScriptRequest {
+            inline_code: Some(OPCACHE_STATUS_CODE),
+            ..Default::default()
+        };
+
+        let code = build_combined_code(&request);
+
+        assert!(code.ends_with(OPCACHE_STATUS_CODE));
+        assert!(!code.contains("require"));
+    }
+
     // -------------------------------------------------------------------------
     // FFI callback test
     // -------------------------------------------------------------------------
@@ -1403,4 +1600,130 @@ mod tests {
         let result = tokio_php_heartbeat(ctx_ptr, 60);
         assert_eq!(result, 0);
     }
+
+    // -------------------------------------------------------------------------
+    // WorkerPool startup tests
+    // -------------------------------------------------------------------------
+
+    #[test]
+    fn test_worker_pool_all_started() {
+        let (release_tx, release_rx) = std_mpsc::channel::<()>();
+        let release_rx = Arc::new(Mutex::new(release_rx));
+
+        let pool = WorkerPool::with_lanes(2, "startup-test", 0, 0, move |_id, mut rx| {
+            // Hold thread setup until the test lets go
+            let _ = release_rx.lock().unwrap().recv();
+            mark_worker_started();
+            let _ = rx.recv();
+        })
+        .unwrap();
+        assert!(!pool.all_started());
+
+        release_tx.send(()).unwrap();
+        release_tx.send(()).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !pool.all_started() {
+            assert!(Instant::now() < deadline, "workers never started");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_worker_pool_replaces_panicked_worker() {
+        let pool = WorkerPool::with_lanes(1, "panic-test", 0, 0, |_id, mut rx| loop {
+            let Ok(work) = rx.recv() else {
+                return;
+            };
+            if work.request.script_path == "panic.php" {
+                panic!("worker crash");
+            }
+            let _ = work.stream_tx.blocking_send(ResponseChunk::Headers {
+                status: 200,
+                headers: Vec::new(),
+            });
+            let _ = work.stream_tx.blocking_send(ResponseChunk::End);
+        })
+        .unwrap();
+        let request = |path: &str| ScriptRequest {
+            script_path: path.to_string(),
+            ..Default::default()
+        };
+        let respawns = crate::executor::worker_respawns();
+
+        // The in-flight request fails instead of hanging
+        let err = pool.execute(request("panic.php")).await.unwrap_err();
+        assert_eq!(err, WORKER_DROPPED_ERROR);
+
+        // The replacement takes the next request
+        let response = pool.execute(request("index.php")).await.unwrap();
+        assert!(response.body.is_empty());
+        assert!(crate::executor::worker_respawns() > respawns);
+    }
+
+    #[test]
+    fn test_worker_pool_drains_high_priority_first() {
+        let (release_tx, release_rx) = std_mpsc::channel::<()>();
+        let release_rx = Arc::new(Mutex::new(release_rx));
+        let (done_tx, done_rx) = std_mpsc::channel::<String>();
+
+        let pool = WorkerPool::with_lanes(1, "lane-test", 1, 1, move |_id, mut rx| {
+            let _ = release_rx.lock().unwrap().recv();
+            while let Ok(work) = rx.recv() {
+                let _ = done_tx.send(work.request.script_path);
+            }
+        })
+        .unwrap();
+        let request = |path: &str, priority| ScriptRequest {
+            script_path: path.to_string(),
+            priority,
+            ..Default::default()
+        };
+
+        // The normal lane is full, the reserved lane still takes a request
+        let _a = pool
+            .submit_streaming(request("a.php", Priority::Normal))
+            .unwrap();
+        let err = pool
+            .submit_streaming(request("b.php", Priority::Normal))
+            .unwrap_err();
+        assert_eq!(err, QUEUE_FULL_ERROR);
+        let _h = pool
+            .submit_streaming(request("h.php", Priority::High))
+            .unwrap();
+
+        // A full reserved lane spills into the (full) normal lane
+        let err = pool
+            .submit_streaming(request("h2.php", Priority::High))
+            .unwrap_err();
+        assert_eq!(err, QUEUE_FULL_ERROR);
+
+        release_tx.send(()).unwrap();
+        let timeout = Duration::from_secs(5);
+        assert_eq!(done_rx.recv_timeout(timeout).unwrap(), "h.php");
+        assert_eq!(done_rx.recv_timeout(timeout).unwrap(), "a.php");
+    }
+
+    #[test]
+    fn test_pool_applies_sub_second_extension_cap() {
+        let (done_tx, done_rx) = std_mpsc::channel::<bool>();
+        let mut pool = WorkerPool::with_lanes(1, "cap-test", 0, 0, move |_id, mut rx| {
+            while let Ok(work) = rx.recv() {
+                let ctx = work.heartbeat_ctx.expect("request has a timeout");
+                // 600ms in, one more second would end past 1s + 500ms
+                thread::sleep(Duration::from_millis(600));
+                let _ = done_tx.send(ctx.heartbeat(1));
+            }
+        })
+        .unwrap();
+        pool.set_extension_cap(Some(Duration::from_millis(500)));
+
+        let _rx = pool
+            .submit_streaming(ScriptRequest {
+                timeout: Some(Duration::from_secs(1)),
+                ..Default::default()
+            })
+            .unwrap();
+        let extended = done_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(!extended, "heartbeat went past the 500ms cap");
+    }
 }
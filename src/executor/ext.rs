@@ -11,21 +11,23 @@
 //! - Script execution via tokio_sapi_execute_script()
 
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::ffi::{c_char, c_int, c_void, CString};
 use std::path::PathBuf;
 use std::ptr;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{mpsc, Arc, Mutex};
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 
 use super::common::{
-    php_request_shutdown, php_request_startup, tokio_php_heartbeat, ts_resource_ex, StdoutCapture,
-    WorkerPool, WorkerRequest, FINALIZE_CODE, FINALIZE_NAME,
+    mark_worker_started, php_request_shutdown, php_request_startup, tokio_php_heartbeat,
+    ts_resource_ex, StdoutCapture, WorkerPool, WorkerQueue, WorkerRequest, FINALIZE_CODE,
+    FINALIZE_NAME, INLINE_CODE_NAME,
 };
 use super::sapi;
-use super::{ExecutorError, ScriptExecutor};
+use super::{catch_init, ExecutorError, InitError, ScriptExecutor, DEFAULT_PRIORITY_CAPACITY};
 use crate::bridge;
 use crate::profiler::ProfileData;
 use crate::server::response::StreamChunk;
@@ -38,6 +40,7 @@ use crate::types::{ScriptRequest, ScriptResponse};
 #[link(name = "php")]
 extern "C" {
     fn zend_eval_string(str: *mut c_char, retval: *mut c_void, name: *mut c_char) -> c_int;
+    fn zend_set_timeout(seconds: i64, reset_signals: bool);
 }
 
 // tokio_sapi extension functions (from static library)
@@ -143,14 +146,56 @@ where
 // Stream Finish Callback (for tokio_finish_request)
 // =============================================================================
 
+thread_local! {
+    /// When the current request called `tokio_finish_request()`.
+    static FINISHED_AT: Cell<Option<Instant>> = const { Cell::new(None) };
+    /// MAX_BACKGROUND_TIME of the current request.
+    static MAX_BACKGROUND_TIME: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
 /// Callback invoked by bridge when PHP calls `tokio_finish_request()`.
 /// This marks the stream as finished, sending ResponseChunk::End immediately.
 ///
+/// With `MAX_BACKGROUND_TIME` set, PHP's execution timer is restarted with
+/// that limit, so the rest of the script and its shutdown functions are
+/// aborted with "Maximum execution time exceeded" once it runs out.
+///
 /// # Safety
 /// This is an FFI callback - called from C. The ctx parameter is unused
 /// since we use thread-local storage for stream state.
 extern "C" fn stream_finish_callback(_ctx: *mut c_void) {
-    sapi::mark_stream_finished();
+    if !sapi::mark_stream_finished() {
+        return;
+    }
+    FINISHED_AT.with(|at| at.set(Some(Instant::now())));
+    if let Some(max) = MAX_BACKGROUND_TIME.get() {
+        // SAFETY: called on the PHP thread while the request is executing
+        unsafe {
+            zend_set_timeout(max.as_secs() as i64, false);
+        }
+    }
+}
+
+/// Logs and counts a request whose background work ran into `MAX_BACKGROUND_TIME`.
+fn check_background_time(worker_id: usize, request: &ScriptRequest) {
+    let Some(finished_at) = FINISHED_AT.with(Cell::take) else {
+        return;
+    };
+    let Some(max) = request.output_limits.max_background_time else {
+        return;
+    };
+    let background = finished_at.elapsed();
+    if background >= max {
+        super::record_background_overrun();
+        tracing::warn!(
+            "ExtWorker {}: {} ran {:.1}s after tokio_finish_request(), aborted at MAX_BACKGROUND_TIME ({}s), request_id={}",
+            worker_id,
+            request.script_path,
+            background.as_secs_f64(),
+            max.as_secs(),
+            request.request_id
+        );
+    }
 }
 
 // =============================================================================
@@ -357,10 +402,7 @@ fn execute_script_with_ffi(
 
     // Execute script via FFI
     let script_start = Instant::now();
-    unsafe {
-        let path_c = CString::new(request.script_path.as_str()).map_err(|e| e.to_string())?;
-        tokio_sapi_execute_script(path_c.as_ptr());
-    }
+    execute_script_or_code(request).map_err(|e| e.to_string())?;
     if profiling {
         timing.script_exec_us = script_start.elapsed().as_micros() as u64;
     }
@@ -381,6 +423,29 @@ fn execute_script_with_ffi(
     Ok((capture, timing))
 }
 
+/// Run the request's script file, or its built-in code if it has one.
+fn execute_script_or_code(request: &ScriptRequest) -> Result<(), std::ffi::NulError> {
+    match request.inline_code {
+        Some(code) => {
+            let code_c = CString::new(code)?;
+            unsafe {
+                zend_eval_string(
+                    code_c.as_ptr() as *mut c_char,
+                    ptr::null_mut(),
+                    INLINE_CODE_NAME.as_ptr() as *mut c_char,
+                );
+            }
+        }
+        None => {
+            let path_c = CString::new(request.script_path.as_str())?;
+            unsafe {
+                tokio_sapi_execute_script(path_c.as_ptr());
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Finalize execution and build response
 #[allow(dead_code)]
 fn finalize_execution(
@@ -488,19 +553,17 @@ fn finalize_execution(
 // Worker Main Loop
 // =============================================================================
 
-fn ext_worker_main_loop(id: usize, rx: Arc<Mutex<mpsc::Receiver<WorkerRequest>>>) {
+fn ext_worker_main_loop(id: usize, mut rx: WorkerQueue) {
     // Initialize thread-local storage for ZTS
     unsafe {
         let _ = ts_resource_ex(0, ptr::null_mut());
     }
 
     tracing::debug!("ExtWorker {}: Thread-local storage initialized", id);
+    mark_worker_started();
 
     loop {
-        let work = {
-            let guard = rx.lock().unwrap();
-            guard.recv()
-        };
+        let work = rx.recv();
 
         match work {
             Ok(WorkerRequest {
@@ -544,7 +607,7 @@ fn ext_worker_main_loop(id: usize, rx: Arc<Mutex<mpsc::Receiver<WorkerRequest>>>
                 sapi::clear_captured_headers();
 
                 // Initialize streaming state (output goes through ub_write callback)
-                sapi::init_stream_state(stream_tx);
+                sapi::init_stream_state(stream_tx, request.output_limits.stream_write_timeout);
 
                 // Initialize bridge context BEFORE php_request_startup so that
                 // OPcache RINIT can read request_time via sapi_get_request_time()
@@ -590,6 +653,7 @@ fn ext_worker_main_loop(id: usize, rx: Arc<Mutex<mpsc::Receiver<WorkerRequest>>>
                     }
 
                     // Set up stream finish callback for tokio_finish_request()
+                    MAX_BACKGROUND_TIME.set(request.output_limits.max_background_time);
                     // SAFETY: null ctx is fine - we use thread-local storage for stream state
                     unsafe {
                         bridge::set_stream_finish_callback(ptr::null_mut(), stream_finish_callback);
@@ -622,6 +686,8 @@ fn ext_worker_main_loop(id: usize, rx: Arc<Mutex<mpsc::Receiver<WorkerRequest>>>
                     // Destroy bridge context
                     bridge::destroy_ctx();
 
+                    check_background_time(id, &request);
+
                     // Send profile data before finalize (which clears the state)
                     if profiling {
                         if let Some(tx) = sapi::get_stream_sender() {
@@ -825,10 +891,7 @@ fn execute_script_streaming(
 
     // Execute script via FFI
     let phase_start = Instant::now();
-    unsafe {
-        let path_c = CString::new(request.script_path.as_str()).unwrap_or_default();
-        tokio_sapi_execute_script(path_c.as_ptr());
-    }
+    let _ = execute_script_or_code(request);
     if profiling {
         timing.script_exec_us = phase_start.elapsed().as_micros() as u64;
     }
@@ -865,19 +928,30 @@ struct ExtPool {
 }
 
 impl ExtPool {
-    fn with_queue_capacity(num_workers: usize, queue_capacity: usize) -> Result<Self, String> {
+    fn new(
+        num_workers: usize,
+        queue_capacity: usize,
+        priority_capacity: usize,
+    ) -> Result<Self, InitError> {
         // Initialize SAPI (same as PhpExecutor)
         sapi::init()?;
 
-        let pool = if queue_capacity > 0 {
-            WorkerPool::with_queue_capacity(num_workers, "ext", queue_capacity, |id, rx| {
-                ext_worker_main_loop(id, rx);
-            })?
-        } else {
-            WorkerPool::new(num_workers, "ext", |id, rx| {
+        // Superglobals are written through the tokio_sapi extension
+        if !sapi::extension_loaded(c"tokio_sapi") {
+            sapi::shutdown();
+            return Err(InitError::MissingExtension("tokio_sapi"));
+        }
+
+        let pool = WorkerPool::with_lanes(
+            num_workers,
+            "ext",
+            queue_capacity,
+            priority_capacity,
+            |id, rx| {
                 ext_worker_main_loop(id, rx);
-            })?
-        };
+            },
+        )
+        .map_err(InitError::WorkerSpawn)?;
 
         for id in 0..num_workers {
             tracing::debug!("Spawned ExtWorker thread {}", id);
@@ -942,11 +1016,28 @@ impl ExtExecutor {
     pub fn with_queue_capacity(
         num_workers: usize,
         queue_capacity: usize,
-    ) -> Result<Self, ExecutorError> {
-        let pool = ExtPool::with_queue_capacity(num_workers, queue_capacity)?;
+    ) -> Result<Self, InitError> {
+        Self::with_lanes(num_workers, queue_capacity, DEFAULT_PRIORITY_CAPACITY)
+    }
+
+    /// Like `with_queue_capacity`, with `priority_capacity` queue slots
+    /// reserved for high-priority requests (0 = no lane).
+    pub fn with_lanes(
+        num_workers: usize,
+        queue_capacity: usize,
+        priority_capacity: usize,
+    ) -> Result<Self, InitError> {
+        let pool = catch_init(|| ExtPool::new(num_workers, queue_capacity, priority_capacity))?;
         Ok(Self { pool })
     }
 
+    /// Caps how far `tokio_request_heartbeat()` can extend a request beyond
+    /// its initial timeout (`MAX_REQUEST_EXTENSION`, None = unlimited).
+    pub fn with_max_request_extension(mut self, max: Option<Duration>) -> Self {
+        self.pool.pool.set_extension_cap(max);
+        self
+    }
+
     /// Returns the number of worker threads.
     pub fn worker_count(&self) -> usize {
         self.pool.worker_count()
@@ -982,6 +1073,26 @@ impl ScriptExecutor for ExtExecutor {
             .map_err(ExecutorError::from)
     }
 
+    async fn opcache_status(&self) -> Result<String, ExecutorError> {
+        self.pool
+            .pool
+            .opcache_status()
+            .await
+            .map_err(ExecutorError::from)
+    }
+
+    fn workers_started(&self) -> bool {
+        self.pool.pool.all_started()
+    }
+
+    fn worker_activity(&self) -> Vec<crate::diagnostics::WorkerActivityStats> {
+        self.pool.pool.worker_activity()
+    }
+
+    fn concurrency(&self) -> Option<usize> {
+        Some(self.worker_count())
+    }
+
     fn name(&self) -> &'static str {
         "ext"
     }
@@ -0,0 +1,702 @@
+//! FastCGI executor: forwards scripts to an external PHP-FPM pool.
+//!
+//! tokio_php stays the HTTP front door (TLS, HTTP/2, static files,
+//! middleware) while PHP runs in FPM. Each request is one FastCGI
+//! `RESPONDER` exchange on a kept-alive connection:
+//!
+//! ```text
+//! BEGIN_REQUEST ─▶ PARAMS… ─▶ PARAMS(empty) ─▶ STDIN… ─▶ STDIN(empty)
+//!                          ◀─ STDOUT… / STDERR… ◀─ END_REQUEST
+//! ```
+//!
+//! `SCRIPT_FILENAME` is the local script path, so FPM must see the document
+//! root at the same path (shared volume).
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::sync::Semaphore;
+
+use super::{
+    ExecutorError, ExecutorErrorKind, ScriptExecutor, QUEUE_FULL_ERROR, REQUEST_TIMEOUT_ERROR,
+};
+use crate::config::is_fastcgi_addr;
+use crate::types::{ScriptRequest, ScriptResponse};
+
+const FCGI_VERSION_1: u8 = 1;
+
+// Record types
+const FCGI_BEGIN_REQUEST: u8 = 1;
+const FCGI_END_REQUEST: u8 = 3;
+const FCGI_PARAMS: u8 = 4;
+const FCGI_STDIN: u8 = 5;
+const FCGI_STDOUT: u8 = 6;
+const FCGI_STDERR: u8 = 7;
+
+const FCGI_RESPONDER: u16 = 1;
+/// Ask the application not to close the connection after the request.
+const FCGI_KEEP_CONN: u8 = 1;
+
+// END_REQUEST protocol status
+const FCGI_REQUEST_COMPLETE: u8 = 0;
+const FCGI_OVERLOADED: u8 = 2;
+
+/// One request per connection at a time, so the id never changes.
+const REQUEST_ID: u16 = 1;
+/// Largest record body, rounded down to a multiple of 8 (no padding needed).
+const MAX_CONTENT_LEN: usize = 65_528;
+
+/// Backend connection (TCP or Unix socket).
+trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Stream for T {}
+
+type Connection = Box<dyn Stream>;
+
+/// FPM listen address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Upstream {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl Upstream {
+    /// `unix:/path/to.sock` or `host:port`.
+    fn parse(addr: &str) -> Result<Self, String> {
+        if !is_fastcgi_addr(addr) {
+            return Err(format!("expected host:port or unix:/path, got '{}'", addr));
+        }
+        Ok(match addr.strip_prefix("unix:") {
+            Some(path) => Self::Unix(PathBuf::from(path)),
+            None => Self::Tcp(addr.to_string()),
+        })
+    }
+
+    async fn connect(&self) -> io::Result<Connection> {
+        match self {
+            Self::Tcp(addr) => {
+                let stream = TcpStream::connect(addr).await?;
+                stream.set_nodelay(true)?;
+                Ok(Box::new(stream))
+            }
+            Self::Unix(path) => Ok(Box::new(UnixStream::connect(path).await?)),
+        }
+    }
+}
+
+/// Executor that proxies scripts to PHP-FPM over FastCGI.
+///
+/// At most `max_connections` requests are in FPM at once (match
+/// `pm.max_children`); up to `queue_capacity` more wait for a connection,
+/// beyond that requests fail with [`QUEUE_FULL_ERROR`] (503). Finished
+/// connections are kept open and reused.
+pub struct FastCgiExecutor {
+    upstream: Upstream,
+    /// Idle kept-alive connections.
+    idle: Mutex<Vec<Connection>>,
+    /// One permit per backend connection.
+    permits: Semaphore,
+    /// Requests in FPM or waiting for a connection.
+    in_flight: AtomicUsize,
+    max_connections: usize,
+    queue_capacity: usize,
+}
+
+impl FastCgiExecutor {
+    /// Create an executor for `addr` (`host:port` or `unix:/path`).
+    ///
+    /// No connection is opened until the first request.
+    pub fn new(addr: &str, max_connections: usize, queue_capacity: usize) -> Result<Self, String> {
+        let max_connections = max_connections.max(1);
+        Ok(Self {
+            upstream: Upstream::parse(addr)?,
+            idle: Mutex::new(Vec::with_capacity(max_connections)),
+            permits: Semaphore::new(max_connections),
+            in_flight: AtomicUsize::new(0),
+            max_connections,
+            queue_capacity,
+        })
+    }
+
+    /// Maximum number of concurrent backend connections.
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    async fn run(&self, records: &[u8], idempotent: bool) -> Result<ScriptResponse, String> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|_| "FastCGI executor is shut down".to_string())?;
+
+        let pooled = self.idle.lock().unwrap().pop();
+        let reused = pooled.is_some();
+        let mut conn = match pooled {
+            Some(conn) => conn,
+            None => self.connect().await?,
+        };
+
+        let mut result = exchange(&mut conn, records).await;
+        if reused && matches!(&result, Err(e) if e.retryable(idempotent)) {
+            // FPM closed the idle connection (pm.max_requests, reload): retry once
+            conn = self.connect().await?;
+            result = exchange(&mut conn, records).await;
+        }
+
+        let output = result.map_err(|e| format!("FastCGI upstream: {}", e.error))?;
+        match output.protocol_status {
+            FCGI_REQUEST_COMPLETE => {}
+            FCGI_OVERLOADED => return Err(QUEUE_FULL_ERROR.to_string()),
+            status => return Err(format!("FastCGI upstream rejected request ({})", status)),
+        }
+
+        if !output.stderr.is_empty() {
+            tracing::warn!(
+                "FastCGI stderr: {}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+
+        self.release(conn);
+        parse_response(&output.stdout)
+    }
+
+    async fn connect(&self) -> Result<Connection, String> {
+        self.upstream
+            .connect()
+            .await
+            .map_err(|e| format!("FastCGI connect to {:?}: {}", self.upstream, e))
+    }
+
+    /// Return a healthy connection to the pool.
+    fn release(&self, conn: Connection) {
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < self.max_connections {
+            idle.push(conn);
+        }
+    }
+}
+
+#[async_trait]
+impl ScriptExecutor for FastCgiExecutor {
+    async fn execute(&self, request: ScriptRequest) -> Result<ScriptResponse, ExecutorError> {
+        let limit = self.max_connections + self.queue_capacity;
+        let Some(_slot) = InFlight::acquire(&self.in_flight, limit) else {
+            return Err(QUEUE_FULL_ERROR.into());
+        };
+
+        let records = encode_request(&request);
+        let idempotent = request
+            .server_vars
+            .iter()
+            .find(|(name, _)| name == "REQUEST_METHOD")
+            .is_some_and(|(_, method)| is_idempotent(method));
+        let response = match request.timeout {
+            // Dropping the exchange drops its connection, so no stale output is reused
+            Some(timeout) => tokio::time::timeout(timeout, self.run(&records, idempotent))
+                .await
+                .map_err(|_| REQUEST_TIMEOUT_ERROR.to_string())?,
+            None => self.run(&records, idempotent).await,
+        };
+        // Script failures arrive as responses, so any other error is the upstream's
+        response.map_err(|e| match ExecutorError::from(e) {
+            err if err.kind == ExecutorErrorKind::ScriptError => {
+                ExecutorError::new(ExecutorErrorKind::WorkerCrashed, err.message)
+            }
+            err => err,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "fastcgi"
+    }
+
+    fn forwards_raw_body(&self) -> bool {
+        true
+    }
+
+    fn concurrency(&self) -> Option<usize> {
+        Some(self.max_connections)
+    }
+
+    fn shutdown(&self) {
+        self.permits.close();
+        self.idle.lock().unwrap().clear();
+    }
+}
+
+/// Slot in the in-flight counter, released on drop.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl<'a> InFlight<'a> {
+    fn acquire(counter: &'a AtomicUsize, limit: usize) -> Option<Self> {
+        if counter.fetch_add(1, Ordering::AcqRel) >= limit {
+            counter.fetch_sub(1, Ordering::AcqRel);
+            return None;
+        }
+        Some(Self(counter))
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Raw streams of one FastCGI exchange.
+struct Output {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    protocol_status: u8,
+}
+
+/// Failed exchange, and how far it got.
+#[derive(Debug)]
+struct ExchangeError {
+    error: io::Error,
+    /// The whole request was written, so FPM may have started the script.
+    sent: bool,
+    /// At least one response record arrived.
+    received: bool,
+}
+
+impl ExchangeError {
+    /// Whether the request can go to a fresh connection: FPM closed the
+    /// idle connection before taking the request (the write failed), or
+    /// before answering an idempotent one.
+    fn retryable(&self, idempotent: bool) -> bool {
+        is_stale(&self.error) && (!self.sent || (idempotent && !self.received))
+    }
+}
+
+/// Errors that mean a pooled connection was closed while idle.
+fn is_stale(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+    )
+}
+
+/// Idempotent methods (RFC 9110, section 9.2.2) are safe to send twice.
+fn is_idempotent(method: &str) -> bool {
+    matches!(
+        method,
+        "GET" | "HEAD" | "OPTIONS" | "TRACE" | "PUT" | "DELETE"
+    )
+}
+
+async fn exchange(conn: &mut Connection, records: &[u8]) -> Result<Output, ExchangeError> {
+    let failed = |error, sent, received| ExchangeError {
+        error,
+        sent,
+        received,
+    };
+    if let Err(e) = async {
+        conn.write_all(records).await?;
+        conn.flush().await
+    }
+    .await
+    {
+        return Err(failed(e, false, false));
+    }
+    read_output(conn)
+        .await
+        .map_err(|(e, received)| failed(e, true, received))
+}
+
+/// Read records up to END_REQUEST. On error, also reports whether any
+/// record had arrived.
+async fn read_output(conn: &mut Connection) -> Result<Output, (io::Error, bool)> {
+    let mut received = false;
+    let mut output = Output {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        protocol_status: FCGI_REQUEST_COMPLETE,
+    };
+    let mut header = [0u8; 8];
+    let mut content = Vec::new();
+    loop {
+        if let Err(e) = conn.read_exact(&mut header).await {
+            return Err((e, received));
+        }
+        received = true;
+        let request_id = u16::from_be_bytes([header[2], header[3]]);
+        let content_len = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let padding_len = header[6] as usize;
+
+        content.resize(content_len + padding_len, 0);
+        conn.read_exact(&mut content).await.map_err(|e| (e, true))?;
+        let body = &content[..content_len];
+
+        if request_id != REQUEST_ID {
+            continue; // Management records
+        }
+        match header[1] {
+            FCGI_STDOUT => output.stdout.extend_from_slice(body),
+            FCGI_STDERR => output.stderr.extend_from_slice(body),
+            FCGI_END_REQUEST => {
+                // appStatus (4 bytes), protocolStatus, reserved
+                output.protocol_status = body.get(4).copied().unwrap_or(FCGI_REQUEST_COMPLETE);
+                return Ok(output);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Encode the whole request (BEGIN_REQUEST, PARAMS, STDIN) in one buffer.
+fn encode_request(request: &ScriptRequest) -> Vec<u8> {
+    let mut params = Vec::with_capacity(1024);
+    for (name, value) in &request.server_vars {
+        if name != "SCRIPT_FILENAME" {
+            encode_pair(&mut params, name.as_bytes(), value.as_bytes());
+        }
+    }
+    encode_pair(
+        &mut params,
+        b"SCRIPT_FILENAME",
+        request.script_path.as_bytes(),
+    );
+
+    let stdin = request.raw_body.as_deref().unwrap_or_default();
+    let mut buf = Vec::with_capacity(params.len() + stdin.len() + 64);
+
+    let mut begin = [0u8; 8];
+    begin[..2].copy_from_slice(&FCGI_RESPONDER.to_be_bytes());
+    begin[2] = FCGI_KEEP_CONN;
+    push_record(&mut buf, FCGI_BEGIN_REQUEST, &begin);
+    push_stream(&mut buf, FCGI_PARAMS, &params);
+    push_stream(&mut buf, FCGI_STDIN, stdin);
+    buf
+}
+
+/// Name-value pair: lengths below 128 take one byte, others four.
+fn encode_pair(buf: &mut Vec<u8>, name: &[u8], value: &[u8]) {
+    for len in [name.len(), value.len()] {
+        if len < 0x80 {
+            buf.push(len as u8);
+        } else {
+            buf.extend_from_slice(&(len as u32 | 0x8000_0000).to_be_bytes());
+        }
+    }
+    buf.extend_from_slice(name);
+    buf.extend_from_slice(value);
+}
+
+/// Stream records split at [`MAX_CONTENT_LEN`], closed by an empty record.
+fn push_stream(buf: &mut Vec<u8>, kind: u8, data: &[u8]) {
+    for chunk in data.chunks(MAX_CONTENT_LEN) {
+        push_record(buf, kind, chunk);
+    }
+    push_record(buf, kind, &[]);
+}
+
+fn push_record(buf: &mut Vec<u8>, kind: u8, content: &[u8]) {
+    let [id_hi, id_lo] = REQUEST_ID.to_be_bytes();
+    let [len_hi, len_lo] = (content.len() as u16).to_be_bytes();
+    buf.extend_from_slice(&[FCGI_VERSION_1, kind, id_hi, id_lo, len_hi, len_lo, 0, 0]);
+    buf.extend_from_slice(content);
+}
+
+/// Split CGI output into headers (`Status` included) and body.
+fn parse_response(stdout: &[u8]) -> Result<ScriptResponse, String> {
+    let (head, body) = match find(stdout, b"\r\n\r\n") {
+        Some(pos) => (&stdout[..pos], &stdout[pos + 4..]),
+        None => match find(stdout, b"\n\n") {
+            Some(pos) => (&stdout[..pos], &stdout[pos + 2..]),
+            None => return Err("FastCGI response has no header terminator".to_string()),
+        },
+    };
+
+    let headers = String::from_utf8_lossy(head)
+        .lines()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+
+    Ok(ScriptResponse {
+        body: String::from_utf8_lossy(body).into_owned(),
+        headers,
+        profile: None,
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    /// Read one request's records off a fake FPM connection.
+    async fn read_request<S: AsyncRead + Unpin>(conn: &mut S) -> (Vec<(String, String)>, Vec<u8>) {
+        let mut params = Vec::new();
+        let mut stdin = Vec::new();
+        loop {
+            let mut header = [0u8; 8];
+            conn.read_exact(&mut header).await.unwrap();
+            let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+            let mut content = vec![0u8; len + header[6] as usize];
+            conn.read_exact(&mut content).await.unwrap();
+            content.truncate(len);
+            match header[1] {
+                FCGI_BEGIN_REQUEST => assert_eq!(content[2], FCGI_KEEP_CONN),
+                FCGI_PARAMS => params.extend_from_slice(&content),
+                FCGI_STDIN if len == 0 => break,
+                FCGI_STDIN => stdin.extend_from_slice(&content),
+                other => panic!("unexpected record type {}", other),
+            }
+        }
+        (decode_pairs(&params), stdin)
+    }
+
+    fn decode_pairs(mut buf: &[u8]) -> Vec<(String, String)> {
+        fn len(buf: &mut &[u8]) -> usize {
+            if buf[0] < 0x80 {
+                let n = buf[0] as usize;
+                *buf = &buf[1..];
+                n
+            } else {
+                let n = u32::from_be_bytes([buf[0] & 0x7f, buf[1], buf[2], buf[3]]) as usize;
+                *buf = &buf[4..];
+                n
+            }
+        }
+        let mut pairs = Vec::new();
+        while !buf.is_empty() {
+            let name_len = len(&mut buf);
+            let value_len = len(&mut buf);
+            let name = String::from_utf8(buf[..name_len].to_vec()).unwrap();
+            let value = String::from_utf8(buf[name_len..name_len + value_len].to_vec()).unwrap();
+            buf = &buf[name_len + value_len..];
+            pairs.push((name, value));
+        }
+        pairs
+    }
+
+    fn reply(stdout: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        push_stream(&mut buf, FCGI_STDOUT, stdout);
+        push_record(&mut buf, FCGI_END_REQUEST, &[0; 8]);
+        buf
+    }
+
+    fn request(body: Option<&[u8]>) -> ScriptRequest {
+        ScriptRequest {
+            script_path: "/var/www/html/index.php".to_string(),
+            server_vars: vec![
+                (Cow::Borrowed("REQUEST_METHOD"), Cow::Borrowed("POST")),
+                (Cow::Borrowed("SCRIPT_FILENAME"), Cow::Borrowed("/stale")),
+            ],
+            raw_body: body.map(<[u8]>::to_vec),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_upstream_parse() {
+        assert_eq!(
+            Upstream::parse("php-fpm:9000").unwrap(),
+            Upstream::Tcp("php-fpm:9000".to_string())
+        );
+        assert_eq!(
+            Upstream::parse("unix:/run/php/fpm.sock").unwrap(),
+            Upstream::Unix(PathBuf::from("/run/php/fpm.sock"))
+        );
+        assert!(Upstream::parse("php-fpm").is_err());
+        assert!(Upstream::parse(":9000").is_err());
+        assert!(Upstream::parse("unix:").is_err());
+    }
+
+    #[test]
+    fn test_encode_pair_lengths() {
+        let mut buf = Vec::new();
+        let long = "v".repeat(300);
+        encode_pair(&mut buf, b"A", long.as_bytes());
+        assert_eq!(&buf[..5], &[1, 0x80, 0, 1, 44]);
+        assert_eq!(decode_pairs(&buf), vec![("A".to_string(), long)]);
+    }
+
+    #[test]
+    fn test_large_stdin_split_into_records() {
+        let body = vec![b'x'; MAX_CONTENT_LEN + 10];
+        let mut buf = Vec::new();
+        push_stream(&mut buf, FCGI_STDIN, &body);
+        // Two data records and the terminator
+        assert_eq!(buf.len(), body.len() + 3 * 8);
+        assert_eq!(&buf[buf.len() - 8..], &[1, FCGI_STDIN, 0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let response = parse_response(
+            b"Status: 404 Not Found\r\nContent-type: text/html\r\nSet-Cookie: a=1\r\n\r\nmissing",
+        )
+        .unwrap();
+        assert_eq!(response.body, "missing");
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Status".to_string(), "404 Not Found".to_string()),
+                ("Content-type".to_string(), "text/html".to_string()),
+                ("Set-Cookie".to_string(), "a=1".to_string()),
+            ]
+        );
+
+        let bare = parse_response(b"X-A: 1\n\nbody\n\nmore").unwrap();
+        assert_eq!(bare.body, "body\n\nmore");
+        assert!(parse_response(b"no headers").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_reuses_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let fpm = tokio::spawn(async move {
+            // Single accept: the second request must come over the same connection
+            let (mut conn, _) = listener.accept().await.unwrap();
+            for _ in 0..2 {
+                let (params, stdin) = read_request(&mut conn).await;
+                assert!(params.contains(&(
+                    "SCRIPT_FILENAME".to_string(),
+                    "/var/www/html/index.php".to_string()
+                )));
+                assert!(!params.iter().any(|(_, v)| v == "/stale"));
+                assert!(params.contains(&("REQUEST_METHOD".to_string(), "POST".to_string())));
+
+                let out = format!("Status: 201 Created\r\nX-Len: {}\r\n\r\nok", stdin.len());
+                conn.write_all(&reply(out.as_bytes())).await.unwrap();
+            }
+        });
+
+        let executor = FastCgiExecutor::new(&addr, 2, 10).unwrap();
+        let body = vec![b'b'; 70_000];
+        for raw_body in [Some(&body[..]), None] {
+            let response = executor.execute(request(raw_body)).await.unwrap();
+            assert_eq!(response.body, "ok");
+            assert!(response
+                .headers
+                .contains(&("Status".to_string(), "201 Created".to_string())));
+            let len = raw_body.map_or(0, <[u8]>::len).to_string();
+            assert!(response.headers.contains(&("X-Len".to_string(), len)));
+        }
+        fpm.await.unwrap();
+    }
+
+    /// Answers once, then closes the kept-alive connection after reading
+    /// the next request; every later connection is answered.
+    async fn serve_then_close(listener: TcpListener) {
+        let (mut conn, _) = listener.accept().await.unwrap();
+        read_request(&mut conn).await;
+        conn.write_all(&reply(b"X-A: 1\r\n\r\nfirst"))
+            .await
+            .unwrap();
+        read_request(&mut conn).await;
+        drop(conn);
+        loop {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            read_request(&mut conn).await;
+            conn.write_all(&reply(b"X-A: 1\r\n\r\nretried"))
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale_connection_retry_only_when_safe() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let fpm = tokio::spawn(serve_then_close(listener));
+
+        let executor = FastCgiExecutor::new(&addr, 1, 0).unwrap();
+        let mut get = request(None);
+        get.server_vars[0].1 = Cow::Borrowed("GET");
+
+        // GET reached FPM but got no answer: sent again on a new connection
+        assert_eq!(executor.execute(get.clone()).await.unwrap().body, "first");
+        assert_eq!(executor.execute(get).await.unwrap().body, "retried");
+        fpm.abort();
+
+        // POST in the same situation may have run, so it fails instead
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let fpm = tokio::spawn(serve_then_close(listener));
+
+        let executor = FastCgiExecutor::new(&addr, 1, 0).unwrap();
+        assert_eq!(executor.execute(request(None)).await.unwrap().body, "first");
+        assert!(executor.execute(request(None)).await.is_err());
+        fpm.abort();
+    }
+
+    #[test]
+    fn test_exchange_error_retryable() {
+        let error = |sent, received| ExchangeError {
+            error: io::ErrorKind::UnexpectedEof.into(),
+            sent,
+            received,
+        };
+        assert!(error(false, false).retryable(false));
+        assert!(error(true, false).retryable(true));
+        assert!(!error(true, false).retryable(false));
+        assert!(!error(true, true).retryable(true));
+
+        let refused = ExchangeError {
+            error: io::ErrorKind::ConnectionRefused.into(),
+            sent: false,
+            received: false,
+        };
+        assert!(!refused.retryable(true));
+    }
+
+    #[tokio::test]
+    async fn test_execute_timeout_and_queue_full() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        // Accepts but never answers
+        let _fpm = tokio::spawn(async move {
+            let mut conns = Vec::new();
+            loop {
+                conns.push(listener.accept().await.unwrap());
+            }
+        });
+
+        let executor = FastCgiExecutor::new(&addr, 1, 0).unwrap();
+        let mut slow = request(None);
+        slow.timeout = Some(Duration::from_millis(50));
+        let err = executor.execute(slow).await.unwrap_err();
+        assert!(err.is_timeout());
+
+        // The only slot is taken: the next request is rejected, not queued
+        let _slot = InFlight::acquire(&executor.in_flight, 1).unwrap();
+        let err = executor.execute(request(None)).await.unwrap_err();
+        assert!(err.is_queue_full());
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_upstream_is_worker_crashed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let executor = FastCgiExecutor::new(&addr, 1, 0).unwrap();
+        let err = executor.execute(request(None)).await.unwrap_err();
+        assert_eq!(err.kind, ExecutorErrorKind::WorkerCrashed);
+    }
+}
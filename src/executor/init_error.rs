@@ -0,0 +1,197 @@
+//! PHP runtime startup failures.
+//!
+//! A failed `php_embed_init` or a panic while spawning workers used to reach
+//! `main` as a bare string. [`InitError`] names the common causes, says what
+//! to do about each, and maps them to distinct exit codes so orchestrators
+//! (and people reading `docker ps -a`) can tell them apart.
+
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+#[cfg(panic = "abort")]
+thread_local! {
+    /// Set while this thread is inside [`catch_init`]; panics elsewhere go
+    /// to the previously installed hook.
+    static IN_INIT: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Oldest PHP release the SAPI callbacks are written against (8.4.0).
+pub const MIN_PHP_VERSION_ID: u32 = 80400;
+
+/// Why the PHP runtime could not be started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitError {
+    /// `php_embed_init` returned an error (bad php.ini, broken extension).
+    EmbedStartup,
+    /// The linked libphp is older than [`MIN_PHP_VERSION_ID`].
+    IncompatiblePhp { version_id: u32 },
+    /// An extension the executor needs is not loaded.
+    MissingExtension(&'static str),
+    /// A worker thread could not be started.
+    WorkerSpawn(String),
+    /// Initialization panicked.
+    Panic(String),
+}
+
+impl InitError {
+    /// Process exit code for this failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::EmbedStartup => 10,
+            Self::IncompatiblePhp { .. } => 11,
+            Self::MissingExtension(_) => 12,
+            Self::WorkerSpawn(_) => 13,
+            Self::Panic(_) => 14,
+        }
+    }
+
+    /// What to check to fix it.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Self::EmbedStartup => {
+                "check the PHP startup messages above; a syntax error in php.ini or an \
+                 extension that fails to load are the usual causes"
+            }
+            Self::IncompatiblePhp { .. } => {
+                "rebuild against PHP 8.4 or newer (ZTS with php-embed), e.g. with PHP_VERSION=8.4"
+            }
+            Self::MissingExtension(_) => {
+                "add extension=tokio_sapi.so to php.ini, or use EXECUTOR=php which doesn't need it"
+            }
+            Self::WorkerSpawn(_) => {
+                "lower PHP_WORKERS or raise the container's thread/memory limits"
+            }
+            Self::Panic(_) => "this is a bug; please report it with the log output",
+        }
+    }
+
+    /// Fail unless `version_id` (as from `php_version_id()`) is supported.
+    pub fn check_php_version(version_id: u32) -> Result<(), Self> {
+        if version_id < MIN_PHP_VERSION_ID {
+            return Err(Self::IncompatiblePhp { version_id });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmbedStartup => write!(f, "PHP embed SAPI failed to start")?,
+            Self::IncompatiblePhp { version_id } => write!(
+                f,
+                "PHP {}.{}.{} is not supported (need {}.{} or newer)",
+                version_id / 10000,
+                version_id / 100 % 100,
+                version_id % 100,
+                MIN_PHP_VERSION_ID / 10000,
+                MIN_PHP_VERSION_ID / 100 % 100
+            )?,
+            Self::MissingExtension(name) => write!(f, "PHP extension '{name}' is not loaded")?,
+            Self::WorkerSpawn(e) => write!(f, "failed to start PHP worker: {e}")?,
+            Self::Panic(msg) => write!(f, "PHP initialization panicked: {msg}")?,
+        }
+        write!(f, "\n  hint: {}", self.hint())
+    }
+}
+
+impl std::error::Error for InitError {}
+
+/// Run startup code, turning a panic into [`InitError::Panic`].
+///
+/// Release builds abort on panic, so there is nothing to catch there: a
+/// panic on the calling thread is reported by the panic hook, which exits
+/// with its code instead. The hook is only in place for the duration of
+/// `init`, and panics on other threads still reach the previous hook.
+pub fn catch_init<T>(init: impl FnOnce() -> Result<T, InitError>) -> Result<T, InitError> {
+    #[cfg(panic = "abort")]
+    let previous_hook = {
+        let previous: std::sync::Arc<dyn Fn(&panic::PanicHookInfo<'_>) + Send + Sync> =
+            panic::take_hook().into();
+        let fallback = std::sync::Arc::clone(&previous);
+        panic::set_hook(Box::new(move |info| {
+            if !IN_INIT.with(|flag| flag.get()) {
+                return fallback(info);
+            }
+            let err = InitError::Panic(panic_message(info.payload()).to_string());
+            eprintln!("Failed to initialize PHP: {}", err);
+            std::process::exit(err.exit_code());
+        }));
+        IN_INIT.with(|flag| flag.set(true));
+        previous
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(init)).unwrap_or_else(|payload| {
+        Err(InitError::Panic(
+            panic_message(payload.as_ref()).to_string(),
+        ))
+    });
+
+    #[cfg(panic = "abort")]
+    {
+        IN_INIT.with(|flag| flag.set(false));
+        panic::set_hook(Box::new(move |info| previous_hook(info)));
+    }
+
+    result
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failing_init_reports_cause() {
+        let err = catch_init::<()>(|| Err(InitError::EmbedStartup)).unwrap_err();
+        assert_eq!(err.exit_code(), 10);
+        assert_eq!(
+            err.to_string(),
+            "PHP embed SAPI failed to start\n  hint: check the PHP startup messages above; a \
+             syntax error in php.ini or an extension that fails to load are the usual causes"
+        );
+    }
+
+    #[test]
+    fn test_panicking_init_is_caught() {
+        let err = catch_init::<()>(|| panic!("worker {} died", 3)).unwrap_err();
+        assert_eq!(err, InitError::Panic("worker 3 died".into()));
+        assert_eq!(err.exit_code(), 14);
+        assert!(err
+            .to_string()
+            .starts_with("PHP initialization panicked: worker 3 died\n  hint: "));
+
+        let err = catch_init::<()>(|| panic!("static message")).unwrap_err();
+        assert_eq!(err, InitError::Panic("static message".into()));
+    }
+
+    #[test]
+    fn test_php_version_check() {
+        assert!(InitError::check_php_version(80400).is_ok());
+        assert!(InitError::check_php_version(80501).is_ok());
+
+        let err = InitError::check_php_version(80312).unwrap_err();
+        assert_eq!(err.exit_code(), 11);
+        assert!(err
+            .to_string()
+            .starts_with("PHP 8.3.12 is not supported (need 8.4 or newer)"));
+    }
+
+    #[test]
+    fn test_missing_extension_message() {
+        let err = InitError::MissingExtension("tokio_sapi");
+        assert_eq!(err.exit_code(), 12);
+        assert!(err
+            .to_string()
+            .contains("'tokio_sapi' is not loaded\n  hint: add extension=tokio_sapi.so"));
+        assert_eq!(catch_init(|| Ok(7)), Ok(7));
+    }
+}
@@ -11,7 +11,8 @@
 //! | [`SapiExecutor`] | `tokio-sapi` | **Recommended (default).** Pure Rust SAPI, fastest performance |
 //! | [`ExtExecutor`] | `php` (without tokio-sapi) | Legacy executor with C extension FFI |
 //! | [`PhpExecutor`] | `php` (without tokio-sapi) | Legacy executor using `zend_eval_string` |
-//! | [`StubExecutor`] | - | Returns empty responses, useful for benchmarking |
+//! | [`FastCgiExecutor`] | - | Forwards scripts to an external PHP-FPM pool |
+//! | [`StubExecutor`] | - | Returns empty or canned responses, for benchmarks and tests |
 //!
 //! # Performance Comparison
 //!
@@ -38,11 +39,12 @@
 //!
 //! ```text
 //! ┌─────────────┐     ┌──────────────┐     ┌─────────────┐
-//! │   Request   │────▶│ mpsc channel │────▶│   Worker    │
-//! └─────────────┘     └──────────────┘     │   Thread    │
-//!                            │             │ (PHP TSRM)  │
-//!                            │             └─────────────┘
-//!                            │             ┌─────────────┐
+//! │   Request   │────▶│ least loaded │────▶│   Worker    │
+//! └─────────────┘     │    deque     │     │   Thread    │
+//!                     └──────────────┘     │ (PHP TSRM)  │
+//!                            │             └──────┬──────┘
+//!                            │                    │ steals when idle
+//!                            │             ┌──────▼──────┐
 //!                            └────────────▶│   Worker    │
 //!                                          │   Thread    │
 //!                                          └─────────────┘
@@ -50,11 +52,17 @@
 //!
 //! Each worker thread has its own PHP context via TSRM (Thread Safe Resource Manager).
 
+mod activity;
+mod fastcgi;
+mod init_error;
 mod stub;
 
 #[cfg(feature = "php")]
 mod common;
 
+#[cfg(feature = "php")]
+mod queue;
+
 #[cfg(feature = "php")]
 mod php;
 
@@ -64,8 +72,13 @@ pub mod sapi;
 #[cfg(feature = "php")]
 mod ext;
 
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
 use async_trait::async_trait;
 
+pub use fastcgi::FastCgiExecutor;
+pub use init_error::{catch_init, InitError, MIN_PHP_VERSION_ID};
 pub use stub::StubExecutor;
 
 #[cfg(feature = "php")]
@@ -74,51 +87,168 @@ pub use php::PhpExecutor;
 #[cfg(feature = "php")]
 pub use ext::ExtExecutor;
 
-#[cfg(feature = "php")]
-pub use common::QUEUE_FULL_ERROR;
-
-#[cfg(feature = "php")]
-pub use common::REQUEST_TIMEOUT_ERROR;
-
 #[cfg(feature = "php")]
 pub use common::ExecuteResult;
 
 #[cfg(feature = "php")]
 pub use sapi::ResponseChunk;
 
+use crate::diagnostics::WorkerActivityStats;
 use crate::server::response::StreamChunk;
 use crate::types::{ScriptRequest, ScriptResponse};
 
-/// Default buffer size for streaming channels.
+/// Default buffer size for streaming channels (`STREAM_BUFFER_SIZE`).
 pub const DEFAULT_STREAM_BUFFER_SIZE: usize = 100;
 
+/// Error returned when queue is full
+pub const QUEUE_FULL_ERROR: &str = "Queue full";
+
+/// Error returned when request times out
+pub const REQUEST_TIMEOUT_ERROR: &str = "Request timeout";
+
+/// Error returned when a worker goes away before finishing the response
+pub const WORKER_DROPPED_ERROR: &str = "Worker dropped connection";
+
+/// Error returned when the worker pool has no workers left
+pub const POOL_SHUT_DOWN_ERROR: &str = "Worker pool shut down";
+
+/// Worker threads replaced after a panic, across all pools.
+static WORKER_RESPAWNS: AtomicU64 = AtomicU64::new(0);
+
+/// Total PHP workers restarted after a panic (`tokio_php_worker_respawns_total`).
+pub fn worker_respawns() -> u64 {
+    WORKER_RESPAWNS.load(Ordering::Relaxed)
+}
+
+#[cfg_attr(not(feature = "php"), allow(dead_code))]
+pub(crate) fn record_worker_respawn() {
+    WORKER_RESPAWNS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Heartbeats that extended a deadline, and those refused by the cap.
+static HEARTBEAT_EXTENSIONS: AtomicU64 = AtomicU64::new(0);
+static HEARTBEAT_CAPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Heartbeats that extended a request deadline and heartbeats refused because
+/// they would pass `MAX_REQUEST_EXTENSION`
+/// (`tokio_php_heartbeat_extensions_total{result}`).
+pub fn heartbeat_extensions() -> (u64, u64) {
+    (
+        HEARTBEAT_EXTENSIONS.load(Ordering::Relaxed),
+        HEARTBEAT_CAPPED.load(Ordering::Relaxed),
+    )
+}
+
+#[cfg_attr(not(feature = "php"), allow(dead_code))]
+pub(crate) fn record_heartbeat(extended: bool) {
+    let counter = if extended {
+        &HEARTBEAT_EXTENSIONS
+    } else {
+        &HEARTBEAT_CAPPED
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Requests whose work after tokio_finish_request() hit MAX_BACKGROUND_TIME.
+static BACKGROUND_OVERRUNS: AtomicU64 = AtomicU64::new(0);
+
+/// Requests aborted for running too long after `tokio_finish_request()`
+/// (`tokio_php_background_overruns_total`).
+pub fn background_overruns() -> u64 {
+    BACKGROUND_OVERRUNS.load(Ordering::Relaxed)
+}
+
+#[cfg_attr(not(feature = "php"), allow(dead_code))]
+pub(crate) fn record_background_overrun() {
+    BACKGROUND_OVERRUNS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// How long a worker waits for room in a full stream channel, unless configured.
+pub const DEFAULT_STREAM_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Streaming responses given up at STREAM_WRITE_TIMEOUT.
+static STREAM_ABORTS: AtomicU64 = AtomicU64::new(0);
+
+/// Streaming responses aborted because the client stopped reading
+/// (`tokio_php_stream_aborts_total`).
+pub fn stream_aborts() -> u64 {
+    STREAM_ABORTS.load(Ordering::Relaxed)
+}
+
+#[cfg_attr(not(feature = "php"), allow(dead_code))]
+pub(crate) fn record_stream_abort() {
+    STREAM_ABORTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Reserved high-priority queue slots per worker pool, unless configured.
+pub const DEFAULT_PRIORITY_CAPACITY: usize = 8;
+
+/// What went wrong, for picking the status code and metrics label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutorErrorKind {
+    /// No room in the worker queue (503, retry shortly).
+    QueueFull,
+    /// The deadline passed before the script finished (504).
+    Timeout,
+    /// The worker died or was unreachable, including a FastCGI upstream (502).
+    WorkerCrashed,
+    /// The script or its setup failed (500).
+    ScriptError,
+}
+
+impl ExecutorErrorKind {
+    pub const ALL: [Self; 4] = [
+        Self::QueueFull,
+        Self::Timeout,
+        Self::WorkerCrashed,
+        Self::ScriptError,
+    ];
+
+    /// Label for logs and metrics.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::QueueFull => "queue_full",
+            Self::Timeout => "timeout",
+            Self::WorkerCrashed => "worker_crashed",
+            Self::ScriptError => "script_error",
+        }
+    }
+
+    /// Kind of a pool error message.
+    fn classify(message: &str) -> Self {
+        match message {
+            QUEUE_FULL_ERROR => Self::QueueFull,
+            REQUEST_TIMEOUT_ERROR => Self::Timeout,
+            WORKER_DROPPED_ERROR | POOL_SHUT_DOWN_ERROR => Self::WorkerCrashed,
+            _ => Self::ScriptError,
+        }
+    }
+}
+
 /// Error type for script execution.
 #[derive(Debug, Clone)]
 pub struct ExecutorError {
     pub message: String,
+    pub kind: ExecutorErrorKind,
 }
 
 impl ExecutorError {
-    /// Returns true if this error indicates the worker queue is full.
-    #[cfg(feature = "php")]
-    pub fn is_queue_full(&self) -> bool {
-        self.message == QUEUE_FULL_ERROR
+    /// Error with an explicit kind, for failures a message can't classify.
+    pub fn new(kind: ExecutorErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            kind,
+        }
     }
 
-    #[cfg(not(feature = "php"))]
+    /// Returns true if this error indicates the worker queue is full.
     pub fn is_queue_full(&self) -> bool {
-        false
+        self.kind == ExecutorErrorKind::QueueFull
     }
 
     /// Returns true if this error indicates a request timeout.
-    #[cfg(feature = "php")]
-    pub fn is_timeout(&self) -> bool {
-        self.message == REQUEST_TIMEOUT_ERROR
-    }
-
-    #[cfg(not(feature = "php"))]
     pub fn is_timeout(&self) -> bool {
-        false
+        self.kind == ExecutorErrorKind::Timeout
     }
 }
 
@@ -130,17 +260,17 @@ impl std::fmt::Display for ExecutorError {
 
 impl std::error::Error for ExecutorError {}
 
+/// Pool errors are strings; the shared constants give them their kind.
 impl From<String> for ExecutorError {
     fn from(message: String) -> Self {
-        Self { message }
+        let kind = ExecutorErrorKind::classify(&message);
+        Self { message, kind }
     }
 }
 
 impl From<&str> for ExecutorError {
     fn from(message: &str) -> Self {
-        Self {
-            message: message.to_string(),
-        }
+        Self::from(message.to_string())
     }
 }
 
@@ -167,6 +297,25 @@ pub trait ScriptExecutor: Send + Sync {
     /// * `Err(ExecutorError)` - If execution failed
     async fn execute(&self, request: ScriptRequest) -> Result<ScriptResponse, ExecutorError>;
 
+    /// Executes a script, giving up after `timeout`.
+    ///
+    /// Returns a [`REQUEST_TIMEOUT_ERROR`] error (see [`ExecutorError::is_timeout`])
+    /// when the deadline passes first; the server answers it with `504`. The
+    /// execution is dropped, which frees the caller but not a PHP worker that
+    /// is still running the script.
+    ///
+    /// The built-in executors already enforce `ScriptRequest::timeout` (with
+    /// heartbeat extensions), so this is for callers and executors that don't.
+    async fn execute_with_timeout(
+        &self,
+        request: ScriptRequest,
+        timeout: Duration,
+    ) -> Result<ScriptResponse, ExecutorError> {
+        tokio::time::timeout(timeout, self.execute(request))
+            .await
+            .unwrap_or_else(|_| Err(ExecutorError::from(REQUEST_TIMEOUT_ERROR)))
+    }
+
     /// Returns the name of this executor for logging purposes.
     fn name(&self) -> &'static str;
 
@@ -179,6 +328,47 @@ pub trait ScriptExecutor: Send + Sync {
         false
     }
 
+    /// Returns true if every script produces an empty 200, so the server can
+    /// answer PHP requests without calling `execute` (stub benchmarking mode).
+    fn empty_responses(&self) -> bool {
+        false
+    }
+
+    /// Returns true if the executor hands the raw request body to PHP as is
+    /// (FastCGI), so the server keeps it whole and parses no forms or
+    /// uploads itself.
+    fn forwards_raw_body(&self) -> bool {
+        false
+    }
+
+    /// Scripts this executor runs at once (PHP workers, FastCGI
+    /// connections), or None without a fixed limit.
+    fn concurrency(&self) -> Option<usize> {
+        None
+    }
+
+    /// Request counts and busy/idle times of each worker thread, for
+    /// `/diagnostics`. Empty for executors without worker threads.
+    fn worker_activity(&self) -> Vec<WorkerActivityStats> {
+        Vec::new()
+    }
+
+    /// Returns true once every worker thread is ready to take requests.
+    ///
+    /// Executors without worker threads are ready as soon as they exist.
+    fn workers_started(&self) -> bool {
+        true
+    }
+
+    /// Returns PHP's `opcache_get_status()` as a JSON string.
+    ///
+    /// Default implementation returns an error (no PHP runtime).
+    async fn opcache_status(&self) -> Result<String, ExecutorError> {
+        Err(ExecutorError::from(
+            "OPcache status not supported by this executor",
+        ))
+    }
+
     /// Executes a streaming script (SSE).
     ///
     /// Returns immediately with a receiver for streaming chunks.
@@ -214,3 +404,52 @@ pub trait ScriptExecutor: Send + Sync {
             .map(|r| ExecuteResult::Normal(Box::new(r)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct SlowExecutor(Duration);
+
+    #[async_trait]
+    impl ScriptExecutor for SlowExecutor {
+        async fn execute(&self, _request: ScriptRequest) -> Result<ScriptResponse, ExecutorError> {
+            tokio::time::sleep(self.0).await;
+            Ok(ScriptResponse::default())
+        }
+
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+    }
+
+    #[test]
+    fn test_error_kind_from_message() {
+        let kind = |m: &str| ExecutorError::from(m).kind;
+        assert_eq!(kind(QUEUE_FULL_ERROR), ExecutorErrorKind::QueueFull);
+        assert_eq!(kind(REQUEST_TIMEOUT_ERROR), ExecutorErrorKind::Timeout);
+        assert_eq!(kind(WORKER_DROPPED_ERROR), ExecutorErrorKind::WorkerCrashed);
+        assert_eq!(kind(POOL_SHUT_DOWN_ERROR), ExecutorErrorKind::WorkerCrashed);
+        assert_eq!(
+            kind("Failed to start PHP request"),
+            ExecutorErrorKind::ScriptError
+        );
+        assert!(ExecutorError::from(QUEUE_FULL_ERROR).is_queue_full());
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_timeout() {
+        let slow = SlowExecutor(Duration::from_secs(10));
+        let err = slow
+            .execute_with_timeout(ScriptRequest::default(), Duration::from_millis(20))
+            .await
+            .unwrap_err();
+        assert!(err.is_timeout());
+
+        let fast = SlowExecutor(Duration::ZERO);
+        assert!(fast
+            .execute_with_timeout(ScriptRequest::default(), Duration::from_secs(1))
+            .await
+            .is_ok());
+    }
+}
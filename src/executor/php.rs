@@ -3,11 +3,13 @@
 //! This executor provides PHP script execution with custom SAPI callbacks
 //! for proper header handling via the sapi module.
 
+use std::time::Duration;
+
 use async_trait::async_trait;
 
 use super::common::{self, WorkerPool};
 use super::sapi;
-use super::{ExecutorError, ScriptExecutor};
+use super::{catch_init, ExecutorError, InitError, ScriptExecutor, DEFAULT_PRIORITY_CAPACITY};
 use crate::types::{ScriptRequest, ScriptResponse};
 
 // =============================================================================
@@ -19,19 +21,24 @@ struct PhpPool {
 }
 
 impl PhpPool {
-    fn with_queue_capacity(num_workers: usize, queue_capacity: usize) -> Result<Self, String> {
+    fn new(
+        num_workers: usize,
+        queue_capacity: usize,
+        priority_capacity: usize,
+    ) -> Result<Self, InitError> {
         // Initialize custom SAPI
         sapi::init()?;
 
-        let pool = if queue_capacity > 0 {
-            WorkerPool::with_queue_capacity(num_workers, "php", queue_capacity, |id, rx| {
-                common::worker_main_loop(id, rx);
-            })?
-        } else {
-            WorkerPool::new(num_workers, "php", |id, rx| {
+        let pool = WorkerPool::with_lanes(
+            num_workers,
+            "php",
+            queue_capacity,
+            priority_capacity,
+            |id, rx| {
                 common::worker_main_loop(id, rx);
-            })?
-        };
+            },
+        )
+        .map_err(InitError::WorkerSpawn)?;
 
         for id in 0..num_workers {
             tracing::debug!("Spawned PHP worker thread {}", id);
@@ -77,11 +84,28 @@ impl PhpExecutor {
     pub fn with_queue_capacity(
         num_workers: usize,
         queue_capacity: usize,
-    ) -> Result<Self, ExecutorError> {
-        let pool = PhpPool::with_queue_capacity(num_workers, queue_capacity)?;
+    ) -> Result<Self, InitError> {
+        Self::with_lanes(num_workers, queue_capacity, DEFAULT_PRIORITY_CAPACITY)
+    }
+
+    /// Like `with_queue_capacity`, with `priority_capacity` queue slots
+    /// reserved for high-priority requests (0 = no lane).
+    pub fn with_lanes(
+        num_workers: usize,
+        queue_capacity: usize,
+        priority_capacity: usize,
+    ) -> Result<Self, InitError> {
+        let pool = catch_init(|| PhpPool::new(num_workers, queue_capacity, priority_capacity))?;
         Ok(Self { pool })
     }
 
+    /// Caps how far `tokio_request_heartbeat()` can extend a request beyond
+    /// its initial timeout (`MAX_REQUEST_EXTENSION`, None = unlimited).
+    pub fn with_max_request_extension(mut self, max: Option<Duration>) -> Self {
+        self.pool.pool.set_extension_cap(max);
+        self
+    }
+
     /// Returns the number of worker threads.
     pub fn worker_count(&self) -> usize {
         self.pool.worker_count()
@@ -97,6 +121,26 @@ impl ScriptExecutor for PhpExecutor {
             .map_err(ExecutorError::from)
     }
 
+    async fn opcache_status(&self) -> Result<String, ExecutorError> {
+        self.pool
+            .pool
+            .opcache_status()
+            .await
+            .map_err(ExecutorError::from)
+    }
+
+    fn workers_started(&self) -> bool {
+        self.pool.pool.all_started()
+    }
+
+    fn worker_activity(&self) -> Vec<crate::diagnostics::WorkerActivityStats> {
+        self.pool.pool.worker_activity()
+    }
+
+    fn concurrency(&self) -> Option<usize> {
+        Some(self.worker_count())
+    }
+
     fn name(&self) -> &'static str {
         "php"
     }
@@ -0,0 +1,550 @@
+//! Worker pool queue: one deque per worker, with work stealing.
+//!
+//! Enqueue picks the least loaded worker (queued plus running requests),
+//! so an idle worker gets a request directly. A worker whose own deque is
+//! empty takes the oldest request of another worker before it sleeps, so
+//! nothing waits behind a slow script while a worker is free. Every lock is
+//! held only for a deque operation; no worker blocks while holding one, and
+//! workers no longer take turns on a single shared receiver.
+//!
+//! Capacity is counted pool-wide per lane: the normal lane holds
+//! `capacity` requests and the high-priority lane `priority_capacity` more.
+//! Workers drain high-priority requests first.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::RecvError;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+use super::activity::PoolActivity;
+use super::QUEUE_FULL_ERROR;
+use crate::types::Priority;
+
+/// Requests queued at one worker, by lane.
+struct Lanes<T> {
+    high: VecDeque<T>,
+    normal: VecDeque<T>,
+    /// The owner is waiting on `Slot::wake`.
+    sleeping: bool,
+}
+
+/// One worker's deque and wakeup.
+struct Slot<T> {
+    lanes: Mutex<Lanes<T>>,
+    wake: Condvar,
+    /// Queued requests plus the one being run; enqueue picks the lowest.
+    load: AtomicUsize,
+    /// Mirrors `Lanes::sleeping` so enqueue can find a sleeper without locking.
+    sleeping: AtomicBool,
+    /// A worker holds this slot's [`WorkerQueue`]; enqueue skips dead slots.
+    alive: AtomicBool,
+}
+
+/// Capacity accounting of one lane, pool-wide.
+struct Lane {
+    len: AtomicUsize,
+    capacity: usize,
+}
+
+impl Lane {
+    fn new(capacity: usize) -> Self {
+        Self {
+            len: AtomicUsize::new(0),
+            capacity,
+        }
+    }
+
+    fn reserve(&self) -> bool {
+        self.len
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |len| {
+                (len < self.capacity).then_some(len + 1)
+            })
+            .is_ok()
+    }
+}
+
+struct Shared<T> {
+    slots: Box<[Slot<T>]>,
+    high: Lane,
+    normal: Lane,
+    /// Tie-break start for enqueue, so equally loaded workers take turns.
+    next: AtomicUsize,
+    closed: AtomicBool,
+    activity: Arc<PoolActivity>,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self, id: usize) -> MutexGuard<'_, Lanes<T>> {
+        self.slots[id]
+            .lanes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn queued(&self) -> usize {
+        self.high.len.load(Ordering::SeqCst) + self.normal.len.load(Ordering::SeqCst)
+    }
+
+    /// Takes the oldest request queued at `id`, high priority first.
+    fn pop(&self, lanes: &mut Lanes<T>) -> Option<T> {
+        if let Some(work) = lanes.high.pop_front() {
+            self.high.len.fetch_sub(1, Ordering::SeqCst);
+            return Some(work);
+        }
+        let work = lanes.normal.pop_front()?;
+        self.normal.len.fetch_sub(1, Ordering::SeqCst);
+        Some(work)
+    }
+
+    /// Takes the oldest request queued at another worker, high priority
+    /// first across the pool.
+    fn steal(&self, thief: usize) -> Option<T> {
+        let n = self.slots.len();
+        for high in [true, false] {
+            for victim in (1..n).map(|i| (thief + i) % n) {
+                let slot = &self.slots[victim];
+                if slot.load.load(Ordering::Relaxed) == 0 {
+                    continue;
+                }
+                let mut lanes = self.lock(victim);
+                let work = if high {
+                    lanes.high.pop_front()
+                } else {
+                    lanes.normal.pop_front()
+                };
+                let Some(work) = work else { continue };
+                drop(lanes);
+                let lane = if high { &self.high } else { &self.normal };
+                lane.len.fetch_sub(1, Ordering::SeqCst);
+                slot.load.fetch_sub(1, Ordering::Relaxed);
+                self.slots[thief].load.fetch_add(1, Ordering::Relaxed);
+                return Some(work);
+            }
+        }
+        None
+    }
+
+    /// Wakes a sleeping worker, if any, so it steals queued work.
+    fn wake_sleeper(&self) {
+        if let Some(idle) =
+            (0..self.slots.len()).find(|&id| self.slots[id].sleeping.load(Ordering::SeqCst))
+        {
+            self.wake(idle);
+        }
+    }
+
+    /// Wakes worker `id` if it is sleeping.
+    fn wake(&self, id: usize) {
+        let slot = &self.slots[id];
+        let mut lanes = self.lock(id);
+        if lanes.sleeping {
+            lanes.sleeping = false;
+            slot.sleeping.store(false, Ordering::SeqCst);
+            slot.wake.notify_one();
+        }
+    }
+}
+
+/// Receiving end of a worker pool's queue, one per worker thread.
+pub struct WorkerQueue<T> {
+    shared: Arc<Shared<T>>,
+    id: usize,
+    /// The last request returned by `recv` is still running.
+    busy: bool,
+}
+
+impl<T> WorkerQueue<T> {
+    /// Blocks until a request arrives; fails once the pool is dropped and
+    /// the queue is drained.
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        let shared = Arc::clone(&self.shared);
+        let slot = &shared.slots[self.id];
+        if std::mem::take(&mut self.busy) {
+            slot.load.fetch_sub(1, Ordering::Relaxed);
+            shared.activity.record_finish(self.id);
+        }
+
+        let work = loop {
+            let mut lanes = shared.lock(self.id);
+            if let Some(work) = shared.pop(&mut lanes) {
+                break work;
+            }
+            drop(lanes);
+
+            if let Some(work) = shared.steal(self.id) {
+                break work;
+            }
+
+            let mut lanes = shared.lock(self.id);
+            if !lanes.high.is_empty() || !lanes.normal.is_empty() {
+                continue;
+            }
+            // Enqueue checks for sleepers after counting its request, and
+            // we check the count after announcing sleep, so one of the two
+            // sees the other.
+            lanes.sleeping = true;
+            slot.sleeping.store(true, Ordering::SeqCst);
+            if shared.queued() > 0 {
+                // Queued elsewhere (or about to be): steal it
+                lanes.sleeping = false;
+                slot.sleeping.store(false, Ordering::SeqCst);
+                drop(lanes);
+                thread::yield_now();
+                continue;
+            }
+            if shared.closed.load(Ordering::SeqCst) {
+                lanes.sleeping = false;
+                slot.sleeping.store(false, Ordering::SeqCst);
+                return Err(RecvError);
+            }
+            while lanes.sleeping {
+                lanes = slot.wake.wait(lanes).unwrap_or_else(|e| e.into_inner());
+            }
+        };
+
+        self.busy = true;
+        shared.activity.record_pickup(self.id);
+        Ok(work)
+    }
+}
+
+impl<T> Drop for WorkerQueue<T> {
+    fn drop(&mut self) {
+        // A panicking worker's replacement starts with an idle slot
+        let slot = &self.shared.slots[self.id];
+        if self.busy {
+            slot.load.fetch_sub(1, Ordering::Relaxed);
+            self.shared.activity.record_finish(self.id);
+        }
+        // Until then, requests queued here go to the other workers
+        slot.alive.store(false, Ordering::SeqCst);
+        let lanes = self.shared.lock(self.id);
+        let stranded = !lanes.high.is_empty() || !lanes.normal.is_empty();
+        drop(lanes);
+        if stranded {
+            self.shared.wake_sleeper();
+        }
+    }
+}
+
+/// Sending end of a [`WorkerQueue`]; closes the queue when dropped.
+pub(crate) struct QueueSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> QueueSender<T> {
+    /// Creates a queue for `workers` workers.
+    pub(crate) fn new(
+        workers: usize,
+        capacity: usize,
+        priority_capacity: usize,
+        activity: Arc<PoolActivity>,
+    ) -> Self {
+        let slots = (0..workers)
+            .map(|_| Slot {
+                lanes: Mutex::new(Lanes {
+                    high: VecDeque::new(),
+                    normal: VecDeque::new(),
+                    sleeping: false,
+                }),
+                wake: Condvar::new(),
+                load: AtomicUsize::new(0),
+                sleeping: AtomicBool::new(false),
+                alive: AtomicBool::new(false),
+            })
+            .collect();
+        Self {
+            shared: Arc::new(Shared {
+                slots,
+                high: Lane::new(priority_capacity),
+                normal: Lane::new(capacity),
+                next: AtomicUsize::new(0),
+                closed: AtomicBool::new(false),
+                activity,
+            }),
+        }
+    }
+
+    /// Hands out receiving ends; unlike the sender, it keeps the queue open.
+    pub(crate) fn receivers(&self) -> Receivers<T> {
+        Receivers {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Queue without blocking. High-priority requests use the reserved lane
+    /// and fall back to the normal lane when it is full.
+    pub(crate) fn try_send(&self, work: T, priority: Priority) -> Result<(), String> {
+        let shared = &*self.shared;
+        let high = priority == Priority::High && shared.high.reserve();
+        if !high && !shared.normal.reserve() {
+            return Err(QUEUE_FULL_ERROR.to_string());
+        }
+
+        let target = self.least_loaded();
+        let slot = &shared.slots[target];
+        let mut lanes = shared.lock(target);
+        if high {
+            lanes.high.push_back(work);
+        } else {
+            lanes.normal.push_back(work);
+        }
+        let was_idle = slot.load.fetch_add(1, Ordering::Relaxed) == 0;
+        if lanes.sleeping {
+            lanes.sleeping = false;
+            slot.sleeping.store(false, Ordering::SeqCst);
+            slot.wake.notify_one();
+        }
+        drop(lanes);
+
+        // Queued behind a running request, or at a worker that just died:
+        // let a sleeping worker steal it
+        if !was_idle || !slot.alive.load(Ordering::SeqCst) {
+            shared.wake_sleeper();
+        }
+        Ok(())
+    }
+
+    /// Live worker with the fewest requests, or any worker while none is
+    /// alive (a replacement picks the request up).
+    fn least_loaded(&self) -> usize {
+        let slots = &self.shared.slots;
+        let start = self.shared.next.fetch_add(1, Ordering::Relaxed) % slots.len();
+        let mut best = (usize::MAX, start);
+        for id in (0..slots.len()).map(|i| (start + i) % slots.len()) {
+            if !slots[id].alive.load(Ordering::SeqCst) {
+                continue;
+            }
+            let load = slots[id].load.load(Ordering::Relaxed);
+            if load == 0 {
+                return id;
+            }
+            if load < best.0 {
+                best = (load, id);
+            }
+        }
+        best.1
+    }
+}
+
+/// Source of [`WorkerQueue`]s for (re)spawned workers.
+pub(crate) struct Receivers<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Clone for Receivers<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Receivers<T> {
+    /// Receiving end for worker `id`.
+    pub(crate) fn get(&self, id: usize) -> WorkerQueue<T> {
+        self.shared.slots[id].alive.store(true, Ordering::SeqCst);
+        WorkerQueue {
+            shared: Arc::clone(&self.shared),
+            id,
+            busy: false,
+        }
+    }
+}
+
+impl<T> Drop for QueueSender<T> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        for id in 0..self.shared.slots.len() {
+            self.shared.wake(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    fn queue(workers: usize, capacity: usize, priority_capacity: usize) -> QueueSender<u32> {
+        let activity = Arc::new(PoolActivity::new("queue-test", workers));
+        QueueSender::new(workers, capacity, priority_capacity, activity)
+    }
+
+    #[test]
+    fn test_queue_capacity_and_lanes() {
+        let tx = queue(1, 1, 1);
+        let mut rx = tx.receivers().get(0);
+
+        tx.try_send(1, Priority::Normal).unwrap();
+        assert_eq!(
+            tx.try_send(2, Priority::Normal).unwrap_err(),
+            QUEUE_FULL_ERROR
+        );
+        tx.try_send(3, Priority::High).unwrap();
+        assert_eq!(
+            tx.try_send(4, Priority::High).unwrap_err(),
+            QUEUE_FULL_ERROR
+        );
+
+        assert_eq!(rx.recv().unwrap(), 3);
+        assert_eq!(rx.recv().unwrap(), 1);
+
+        // Taken requests free their slot
+        tx.try_send(5, Priority::Normal).unwrap();
+        assert_eq!(rx.recv().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_queue_closes_after_drain() {
+        let tx = queue(2, 4, 0);
+        let mut rx = tx.receivers().get(1);
+        tx.try_send(1, Priority::Normal).unwrap();
+        drop(tx);
+
+        // Worker 0 never started, so the request went to worker 1
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn test_dead_worker_is_skipped_and_its_queue_stolen() {
+        let tx = queue(2, 8, 0);
+
+        // Worker 1 runs a request with another queued behind it
+        let mut rx1 = tx.receivers().get(1);
+        tx.try_send(1, Priority::Normal).unwrap();
+        tx.try_send(2, Priority::Normal).unwrap();
+        assert_eq!(rx1.recv().unwrap(), 1);
+
+        let (done_tx, done_rx) = mpsc::channel();
+        let mut rx0 = tx.receivers().get(0);
+        let worker = thread::spawn(move || {
+            while let Ok(work) = rx0.recv() {
+                done_tx.send(work).unwrap();
+            }
+        });
+
+        // Worker 1 dies: worker 0 takes its queued request and every new one
+        drop(rx1);
+        let timeout = Duration::from_secs(5);
+        assert_eq!(done_rx.recv_timeout(timeout).unwrap(), 2);
+        for work in 3..7 {
+            tx.try_send(work, Priority::Normal).unwrap();
+            assert_eq!(done_rx.recv_timeout(timeout).unwrap(), work);
+        }
+
+        drop(tx);
+        worker.join().unwrap();
+    }
+
+    #[test]
+    fn test_idle_worker_steals_from_busy_one() {
+        let tx = queue(2, 8, 0);
+        let (done_tx, done_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+
+        // Worker 0 blocks on its first request
+        let mut rx0 = tx.receivers().get(0);
+        let slow_done = done_tx.clone();
+        let slow = thread::spawn(move || {
+            let work = rx0.recv().unwrap();
+            let _ = release_rx.recv();
+            slow_done.send(work).unwrap();
+            while let Ok(work) = rx0.recv() {
+                slow_done.send(work).unwrap();
+            }
+        });
+        tx.try_send(0, Priority::Normal).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while tx.shared.queued() > 0 {
+            assert!(Instant::now() < deadline, "worker 0 never took a request");
+            thread::yield_now();
+        }
+
+        // Worker 1 starts later and picks up what queued at worker 0
+        tx.try_send(1, Priority::Normal).unwrap();
+        tx.try_send(2, Priority::Normal).unwrap();
+        let mut rx1 = tx.receivers().get(1);
+        let fast = thread::spawn(move || {
+            while let Ok(work) = rx1.recv() {
+                done_tx.send(work).unwrap();
+            }
+        });
+
+        let timeout = Duration::from_secs(5);
+        let mut fast_done = [
+            done_rx.recv_timeout(timeout).unwrap(),
+            done_rx.recv_timeout(timeout).unwrap(),
+        ];
+        fast_done.sort();
+        assert_eq!(fast_done, [1, 2]);
+
+        release_tx.send(()).unwrap();
+        assert_eq!(done_rx.recv_timeout(timeout).unwrap(), 0);
+        drop(tx);
+        slow.join().unwrap();
+        fast.join().unwrap();
+    }
+
+    /// Queue wait percentiles with many workers running short requests.
+    ///
+    /// `cargo test --release --features php bench_queue_wait -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_queue_wait() {
+        const WORKERS: usize = 64;
+        const REQUESTS: usize = 40_000;
+        const WORK: Duration = Duration::from_millis(1);
+        // Arrivals per millisecond, ~60% of what the workers can take
+        const BATCH: usize = WORKERS * 6 / 10;
+
+        let tx = QueueSender::<Instant>::new(
+            WORKERS,
+            REQUESTS,
+            0,
+            Arc::new(PoolActivity::new("queue-bench", WORKERS)),
+        );
+        let workers: Vec<_> = (0..WORKERS)
+            .map(|id| {
+                let mut rx = tx.receivers().get(id);
+                thread::spawn(move || {
+                    let mut waits = Vec::new();
+                    while let Ok(queued_at) = rx.recv() {
+                        waits.push(queued_at.elapsed());
+                        thread::sleep(WORK);
+                    }
+                    waits
+                })
+            })
+            .collect();
+
+        let start = Instant::now();
+        for i in 0..REQUESTS {
+            tx.try_send(Instant::now(), Priority::Normal).unwrap();
+            if i % BATCH == BATCH - 1 {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        drop(tx);
+        let mut waits: Vec<_> = workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect();
+        waits.sort();
+
+        let pct = |p: f64| waits[((waits.len() - 1) as f64 * p) as usize];
+        eprintln!(
+            "{} workers, {} requests in {:?}: wait p50 {:?}, p99 {:?}, p99.9 {:?}, max {:?}",
+            WORKERS,
+            REQUESTS,
+            start.elapsed(),
+            pct(0.5),
+            pct(0.99),
+            pct(0.999),
+            waits[waits.len() - 1]
+        );
+    }
+}
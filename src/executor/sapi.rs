@@ -23,13 +23,19 @@ use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::future::Future;
 use std::path::PathBuf;
 use std::ptr;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
 use tokio::sync::mpsc;
 
+use super::InitError;
+
 // =============================================================================
 // PHP FFI Bindings
 // =============================================================================
@@ -82,6 +88,7 @@ type RegisterVarsFn = unsafe extern "C" fn(*mut c_void);
 type LogMessageFn = unsafe extern "C" fn(*const c_char, c_int);
 type GetRequestTimeFn = unsafe extern "C" fn(*mut f64) -> c_int;
 type TerminateProcessFn = unsafe extern "C" fn();
+type IniDefaultsFn = unsafe extern "C" fn(*mut c_void);
 
 /// sapi_module_struct - main SAPI module structure
 /// Fields must match the order in PHP source (php_embed.c)
@@ -119,7 +126,7 @@ pub struct SapiModule {
     pub get_target_uid: *mut c_void,
     pub get_target_gid: *mut c_void,
     pub input_filter: *mut c_void,
-    pub ini_defaults: *mut c_void,
+    pub ini_defaults: Option<IniDefaultsFn>,
     pub phpinfo_as_text: c_int,
     pub ini_entries: *mut c_char,
     pub additional_functions: *mut c_void,
@@ -130,6 +137,8 @@ pub struct SapiModule {
 extern "C" {
     fn php_embed_init(argc: c_int, argv: *mut *mut c_char) -> c_int;
     fn php_embed_shutdown();
+    fn php_version_id() -> u32;
+    fn zend_get_module_started(module_name: *const c_char) -> c_int;
     static mut php_embed_module: SapiModule;
 
     // Global SAPI module (copied from php_embed_module during sapi_startup)
@@ -170,6 +179,10 @@ extern "C" {
     /// Mark headers as sent to client.
     /// Called after sending headers chunk.
     fn tokio_bridge_mark_headers_sent();
+
+    /// Mark the stream as aborted after STREAM_WRITE_TIMEOUT.
+    /// The next flush() then ends the script as on a closed connection.
+    fn tokio_bridge_mark_stream_aborted();
 }
 
 // tokio_sapi extension FFI - for SAPI flush handler
@@ -281,8 +294,77 @@ struct StreamState {
     status_code: u16,
     /// Whether headers have been sent to the client
     headers_sent: bool,
+    /// Give up on a client that makes no room for this long (None = wait forever)
+    write_timeout: Option<Duration>,
     /// Whether tokio_finish_request() was called
     finished: bool,
+    /// Whether the client stopped reading and the stream was given up
+    aborted: bool,
+}
+
+/// Send a chunk, waiting at most `STREAM_WRITE_TIMEOUT` for the client.
+///
+/// The channel is bounded, so a client that stops reading eventually blocks
+/// the worker here. When the timeout passes the stream is marked aborted:
+/// later output is discarded, and the script's next flush() ends it the way
+/// PHP handles a closed connection (see tokio_sapi_flush).
+fn send_chunk(stream_state: &mut StreamState, chunk: ResponseChunk) -> bool {
+    if stream_state.aborted {
+        return false;
+    }
+    let Some(timeout) = stream_state.write_timeout else {
+        return stream_state.tx.blocking_send(chunk).is_ok();
+    };
+
+    match send_before(&stream_state.tx, chunk, Instant::now() + timeout) {
+        Some(sent) => return sent,
+        None => stream_state.aborted = true,
+    }
+    super::record_stream_abort();
+    let request_id = TRACE_CTX.with(|ctx| ctx.borrow().request_id.clone());
+    tracing::warn!(
+        request_id = %request_id,
+        "Client did not read the stream for {:?}, aborting (STREAM_WRITE_TIMEOUT)",
+        timeout
+    );
+    unsafe {
+        tokio_bridge_mark_stream_aborted();
+    }
+    false
+}
+
+/// Wakes the worker thread parked in [`send_before`].
+struct ThreadWaker(std::thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Blocking send that gives up at `deadline`.
+///
+/// The thread parks until the receiver frees a slot, like `blocking_send`,
+/// so output resumes as soon as the client reads. Returns whether the chunk
+/// was sent (false = receiver gone), or None when the deadline passed.
+fn send_before<T>(tx: &mpsc::Sender<T>, chunk: T, deadline: Instant) -> Option<bool> {
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut send = std::pin::pin!(tx.send(chunk));
+    loop {
+        if let Poll::Ready(result) = send.as_mut().poll(&mut cx) {
+            return Some(result.is_ok());
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        std::thread::park_timeout(deadline - now);
+    }
 }
 
 /// SAPI ub_write callback - called for each output from PHP.
@@ -301,8 +383,8 @@ unsafe extern "C" fn stream_ub_write(str: *const c_char, len: usize) -> usize {
             None => return len, // No streaming context - ignore output
         };
 
-        // After tokio_finish_request(), output is discarded
-        if stream_state.finished {
+        // After tokio_finish_request() or an aborted stream, output is discarded
+        if stream_state.finished || stream_state.aborted {
             return len;
         }
 
@@ -312,12 +394,10 @@ unsafe extern "C" fn stream_ub_write(str: *const c_char, len: usize) -> usize {
             let headers = CAPTURED_HEADERS.with(|h| std::mem::take(&mut *h.borrow_mut()));
             // Filter headers for streaming (remove Content-Length if chunked mode)
             let headers = filter_headers_for_streaming(headers);
-            let status = stream_state.status_code;
+            let status = current_status(stream_state.status_code);
 
-            // Send headers chunk (blocking_send is ok - we're in a worker thread)
-            let _ = stream_state
-                .tx
-                .blocking_send(ResponseChunk::Headers { status, headers });
+            // Send headers chunk (blocking is ok - we're in a worker thread)
+            send_chunk(stream_state, ResponseChunk::Headers { status, headers });
             stream_state.headers_sent = true;
             // Mark headers as sent in bridge TLS
             tokio_bridge_mark_headers_sent();
@@ -326,9 +406,10 @@ unsafe extern "C" fn stream_ub_write(str: *const c_char, len: usize) -> usize {
         // Send body chunk
         if len > 0 {
             let data = std::slice::from_raw_parts(str.cast::<u8>(), len);
-            let _ = stream_state
-                .tx
-                .blocking_send(ResponseChunk::Body(Bytes::copy_from_slice(data)));
+            send_chunk(
+                stream_state,
+                ResponseChunk::Body(Bytes::copy_from_slice(data)),
+            );
         }
 
         len
@@ -340,13 +421,16 @@ unsafe extern "C" fn stream_ub_write(str: *const c_char, len: usize) -> usize {
 ///
 /// # Arguments
 /// * `tx` - Channel sender for response chunks
-pub fn init_stream_state(tx: mpsc::Sender<ResponseChunk>) {
+/// * `write_timeout` - STREAM_WRITE_TIMEOUT of the request
+pub fn init_stream_state(tx: mpsc::Sender<ResponseChunk>, write_timeout: Option<Duration>) {
     STREAM_STATE.with(|state| {
         *state.borrow_mut() = Some(StreamState {
             tx,
             status_code: 200,
             headers_sent: false,
+            write_timeout,
             finished: false,
+            aborted: false,
         });
     });
 }
@@ -382,10 +466,8 @@ pub fn finalize_stream() {
                 let headers = CAPTURED_HEADERS.with(|h| std::mem::take(&mut *h.borrow_mut()));
                 // Filter headers for streaming (remove Content-Length if chunked mode)
                 let headers = filter_headers_for_streaming(headers);
-                let status = stream_state.status_code;
-                let _ = stream_state
-                    .tx
-                    .blocking_send(ResponseChunk::Headers { status, headers });
+                let status = current_status(stream_state.status_code);
+                send_chunk(stream_state, ResponseChunk::Headers { status, headers });
                 stream_state.headers_sent = true;
                 // Mark headers as sent in bridge TLS
                 unsafe {
@@ -395,7 +477,7 @@ pub fn finalize_stream() {
 
             // Send End chunk (unless already finished via tokio_finish_request)
             if !stream_state.finished {
-                let _ = stream_state.tx.blocking_send(ResponseChunk::End);
+                send_chunk(stream_state, ResponseChunk::End);
             }
         }
 
@@ -422,10 +504,8 @@ pub fn mark_stream_finished() -> bool {
                 let headers = CAPTURED_HEADERS.with(|h| std::mem::take(&mut *h.borrow_mut()));
                 // Filter headers for streaming (remove Content-Length if chunked mode)
                 let headers = filter_headers_for_streaming(headers);
-                let status = stream_state.status_code;
-                let _ = stream_state
-                    .tx
-                    .blocking_send(ResponseChunk::Headers { status, headers });
+                let status = current_status(stream_state.status_code);
+                send_chunk(stream_state, ResponseChunk::Headers { status, headers });
                 stream_state.headers_sent = true;
                 // Mark headers as sent in bridge TLS
                 unsafe {
@@ -434,7 +514,7 @@ pub fn mark_stream_finished() -> bool {
             }
 
             // Send End chunk - client receives response now
-            let _ = stream_state.tx.blocking_send(ResponseChunk::End);
+            send_chunk(stream_state, ResponseChunk::End);
             return true;
         }
         false
@@ -445,10 +525,10 @@ pub fn mark_stream_finished() -> bool {
 /// Used when PHP execution fails.
 pub fn send_stream_error(error: String) {
     STREAM_STATE.with(|state| {
-        let state_ref = state.borrow();
-        if let Some(stream_state) = state_ref.as_ref() {
+        let mut state_ref = state.borrow_mut();
+        if let Some(stream_state) = state_ref.as_mut() {
             if !stream_state.finished {
-                let _ = stream_state.tx.blocking_send(ResponseChunk::Error(error));
+                send_chunk(stream_state, ResponseChunk::Error(error));
             }
         }
     });
@@ -522,6 +602,8 @@ unsafe extern "C" fn custom_header_handler(
             });
             // Also update streaming state status
             set_stream_status(code);
+            // Keep the bridge in step with tokio_http_response_code()
+            crate::bridge::set_response_code(code);
         }
     }
 
@@ -713,6 +795,13 @@ unsafe extern "C" fn custom_log_message(message: *const c_char, syslog_type: c_i
         )
     });
 
+    // Count PHP errors (fatal, warning, ...) for /metrics
+    let php_level = crate::logging::PhpErrorLevel::classify(msg, syslog_type);
+    if let Some(level) = php_level {
+        crate::logging::record_php_error(level);
+    }
+    let php_level = php_level.map_or("", |l| l.as_str());
+
     // Map syslog level to tracing level and log
     // Note: we use explicit match to avoid the overhead of creating spans
     match syslog_type {
@@ -723,6 +812,7 @@ unsafe extern "C" fn custom_log_message(message: *const c_char, syslog_type: c_i
                 request_id = %request_id,
                 trace_id = %trace_id,
                 span_id = %span_id,
+                php_level,
                 "{}",
                 msg
             );
@@ -734,6 +824,7 @@ unsafe extern "C" fn custom_log_message(message: *const c_char, syslog_type: c_i
                 request_id = %request_id,
                 trace_id = %trace_id,
                 span_id = %span_id,
+                php_level,
                 "{}",
                 msg
             );
@@ -745,6 +836,7 @@ unsafe extern "C" fn custom_log_message(message: *const c_char, syslog_type: c_i
                 request_id = %request_id,
                 trace_id = %trace_id,
                 span_id = %span_id,
+                php_level,
                 "{}",
                 msg
             );
@@ -756,6 +848,7 @@ unsafe extern "C" fn custom_log_message(message: *const c_char, syslog_type: c_i
                 request_id = %request_id,
                 trace_id = %trace_id,
                 span_id = %span_id,
+                php_level,
                 "{}",
                 msg
             );
@@ -916,9 +1009,7 @@ unsafe extern "C" fn custom_send_headers(sapi_headers: *mut SapiHeaders) -> c_in
         let headers = filter_headers_for_streaming(headers);
 
         // Send headers chunk immediately
-        let _ = stream_state
-            .tx
-            .blocking_send(ResponseChunk::Headers { status, headers });
+        send_chunk(stream_state, ResponseChunk::Headers { status, headers });
         stream_state.headers_sent = true;
         // Mark headers as sent in bridge TLS
         tokio_bridge_mark_headers_sent();
@@ -927,6 +1018,31 @@ unsafe extern "C" fn custom_send_headers(sapi_headers: *mut SapiHeaders) -> c_in
     })
 }
 
+/// SAPI ini_defaults callback - appends configured ini overrides.
+///
+/// Called from php_init_config() before php.ini is parsed. `ini_entries` is
+/// parsed after php.ini, so appending there lets config win over php.ini.
+/// The combined string is leaked: PHP reads it for the life of the process.
+unsafe extern "C" fn custom_ini_defaults(_configuration_hash: *mut c_void) {
+    let overrides = INI_OVERRIDES.lock().unwrap();
+    if overrides.is_empty() {
+        return;
+    }
+
+    let mut entries = if sapi_module.ini_entries.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(sapi_module.ini_entries)
+            .to_string_lossy()
+            .into_owned()
+    };
+    entries.push_str(&overrides);
+
+    if let Ok(entries) = CString::new(entries) {
+        sapi_module.ini_entries = entries.into_raw();
+    }
+}
+
 // =============================================================================
 // SAPI Configuration
 // =============================================================================
@@ -934,12 +1050,29 @@ unsafe extern "C" fn custom_send_headers(sapi_headers: *mut SapiHeaders) -> c_in
 static SAPI_NAME: &[u8] = b"cli-server\0";
 static SAPI_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
+/// Ini overrides ("name=value\n" lines) applied at startup, after php.ini.
+static INI_OVERRIDES: Mutex<String> = Mutex::new(String::new());
+
 // =============================================================================
 // Public API
 // =============================================================================
 
+/// Set ini directives to apply at PHP startup (call before `init()`).
+///
+/// These take precedence over php.ini, like `-d` on the command line.
+pub fn set_ini_overrides(entries: &[(String, String)]) {
+    let mut overrides = INI_OVERRIDES.lock().unwrap();
+    overrides.clear();
+    for (name, value) in entries {
+        overrides.push_str(name);
+        overrides.push('=');
+        overrides.push_str(value);
+        overrides.push('\n');
+    }
+}
+
 /// Initialize PHP with custom SAPI settings (call once at startup)
-pub fn init() -> Result<(), String> {
+pub fn init() -> Result<(), InitError> {
     if SAPI_INITIALIZED.swap(true, Ordering::SeqCst) {
         return Ok(());
     }
@@ -964,12 +1097,19 @@ pub fn init() -> Result<(), String> {
         php_embed_module.send_headers = Some(custom_send_headers); // Early header sending
         php_embed_module.flush = Some(tokio_sapi_flush); // SSE streaming support
         php_embed_module.ub_write = Some(stream_ub_write); // HTTP streaming output
+        php_embed_module.ini_defaults = Some(custom_ini_defaults); // Config ini overrides
 
         let program_name = CString::new("tokio_php").unwrap();
         let mut argv: [*mut c_char; 2] = [program_name.as_ptr() as *mut c_char, ptr::null_mut()];
 
         if php_embed_init(1, argv.as_mut_ptr()) != 0 {
-            return Err("Failed to initialize PHP embed".to_string());
+            SAPI_INITIALIZED.store(false, Ordering::SeqCst);
+            return Err(InitError::EmbedStartup);
+        }
+        if let Err(e) = InitError::check_php_version(php_version_id()) {
+            php_embed_shutdown();
+            SAPI_INITIALIZED.store(false, Ordering::SeqCst);
+            return Err(e);
         }
 
         // Also patch sapi_module directly (the global that PHP actually uses)
@@ -995,6 +1135,12 @@ pub fn init() -> Result<(), String> {
     Ok(())
 }
 
+/// Whether a PHP extension is loaded and started (call after `init`).
+pub fn extension_loaded(name: &CStr) -> bool {
+    // zend_get_module_started returns SUCCESS (0) when the module is running
+    unsafe { zend_get_module_started(name.as_ptr()) == 0 }
+}
+
 /// Shutdown PHP
 pub fn shutdown() {
     if !SAPI_INITIALIZED.swap(false, Ordering::SeqCst) {
@@ -1025,7 +1171,16 @@ pub fn get_captured_headers() -> Vec<(String, String)> {
 
 /// Get captured HTTP status code
 pub fn get_captured_status() -> u16 {
-    CAPTURED_STATUS.with(|s| *s.borrow())
+    current_status(CAPTURED_STATUS.with(|s| *s.borrow()))
+}
+
+/// Status from `tokio_http_response_code()` or `header()`, else `fallback`.
+///
+/// PHP's own `http_response_code()` doesn't reach the header handler, so a
+/// status set that way before the first output is missed; the bridge value
+/// is set by both `header()` and `tokio_http_response_code()`.
+fn current_status(fallback: u16) -> u16 {
+    crate::bridge::get_response_code().unwrap_or(fallback)
 }
 
 /// Set request data for SAPI callbacks.
@@ -1146,3 +1301,64 @@ pub fn register_temp_file(path: PathBuf) {
 pub fn temp_file_count() -> usize {
     TEMP_FILES.with(|files| files.borrow().len())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_before_wakes_on_room_and_times_out() {
+        let (tx, mut rx) = mpsc::channel(1);
+        tx.try_send(1).unwrap();
+
+        // Woken as soon as the reader frees the slot, long before the deadline
+        let reader = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            assert_eq!(rx.blocking_recv(), Some(1));
+            rx
+        });
+        let start = Instant::now();
+        assert_eq!(
+            send_before(&tx, 2, start + Duration::from_secs(10)),
+            Some(true)
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let rx = reader.join().unwrap();
+
+        // Nobody reads: gives up at the deadline
+        let start = Instant::now();
+        assert_eq!(send_before(&tx, 3, start + Duration::from_millis(50)), None);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        drop(rx);
+        assert_eq!(
+            send_before(&tx, 4, Instant::now() + Duration::from_secs(10)),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_send_chunk_aborts_at_write_timeout() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let mut state = StreamState {
+            tx,
+            status_code: 200,
+            headers_sent: false,
+            write_timeout: Some(Duration::from_millis(20)),
+            finished: false,
+            aborted: false,
+        };
+        let body = || ResponseChunk::Body(Bytes::from_static(b"x"));
+        let aborts = crate::executor::stream_aborts();
+
+        assert!(send_chunk(&mut state, body()));
+        assert!(!send_chunk(&mut state, body()));
+        assert!(state.aborted);
+        assert!(crate::executor::stream_aborts() > aborts);
+
+        // Later output is discarded even once there is room again
+        assert!(matches!(rx.try_recv(), Ok(ResponseChunk::Body(_))));
+        assert!(!send_chunk(&mut state, body()));
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -1,17 +1,82 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
+use http::StatusCode;
 
 use super::{ExecutorError, ScriptExecutor};
 use crate::types::{ScriptRequest, ScriptResponse};
 
-/// Stub executor that returns empty responses.
+/// Stub executor that returns empty or canned responses.
 ///
-/// Optimized for maximum throughput - returns pre-allocated empty response.
-pub struct StubExecutor;
+/// By default every script returns an empty 200 and the server answers
+/// without calling the executor at all, for raw throughput numbers. With
+/// [`with_response`](Self::with_response) or [`with_route`](Self::with_route)
+/// the canned response goes through the full response pipeline (headers,
+/// compression, error pages), so middleware and routing can be tested
+/// without PHP.
+#[derive(Clone, Debug, Default)]
+pub struct StubExecutor {
+    /// Response for paths without a route (None = empty body).
+    response: Option<ScriptResponse>,
+    /// Responses by request path (REQUEST_URI without the query string).
+    routes: HashMap<String, ScriptResponse>,
+    /// Resolve scripts on disk in canned mode (default: every path runs).
+    check_files: bool,
+}
 
 impl StubExecutor {
     #[inline]
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Answer every path without a route with this status, headers and body.
+    pub fn with_response(
+        mut self,
+        status: StatusCode,
+        headers: Vec<(String, String)>,
+        body: impl Into<String>,
+    ) -> Self {
+        self.response = Some(canned_response(status, headers, body.into()));
+        self
+    }
+
+    /// Answer requests for `path` (e.g. `/api/users`) with this response.
+    pub fn with_route(
+        mut self,
+        path: impl Into<String>,
+        status: StatusCode,
+        headers: Vec<(String, String)>,
+        body: impl Into<String>,
+    ) -> Self {
+        self.routes
+            .insert(path.into(), canned_response(status, headers, body.into()));
+        self
+    }
+
+    /// Route requests like the real executors: missing scripts are 404 and
+    /// static files are served. Ignored in empty-body mode.
+    pub fn with_file_check(mut self, enabled: bool) -> Self {
+        self.check_files = enabled;
+        self
+    }
+
+    /// No canned responses: every script is an empty 200.
+    #[inline]
+    fn is_empty_mode(&self) -> bool {
+        self.response.is_none() && self.routes.is_empty()
+    }
+
+    fn response_for(&self, request: &ScriptRequest) -> ScriptResponse {
+        let path = request
+            .server_vars
+            .iter()
+            .find(|(key, _)| key == "REQUEST_URI")
+            .map(|(_, uri)| uri.split('?').next().unwrap_or(""));
+        path.and_then(|path| self.routes.get(path))
+            .or(self.response.as_ref())
+            .cloned()
+            .unwrap_or_default()
     }
 
     /// Fast path for benchmarking - no request data needed.
@@ -21,17 +86,30 @@ impl StubExecutor {
     }
 }
 
-impl Default for StubExecutor {
-    fn default() -> Self {
-        Self
+/// The status travels as a `Status` header, the same way PHP sets it.
+fn canned_response(
+    status: StatusCode,
+    mut headers: Vec<(String, String)>,
+    body: String,
+) -> ScriptResponse {
+    if status != StatusCode::OK {
+        headers.insert(0, ("Status".to_string(), status.as_u16().to_string()));
+    }
+    ScriptResponse {
+        body,
+        headers,
+        profile: None,
     }
 }
 
 #[async_trait]
 impl ScriptExecutor for StubExecutor {
     #[inline]
-    async fn execute(&self, _request: ScriptRequest) -> Result<ScriptResponse, ExecutorError> {
-        Ok(ScriptResponse::default())
+    async fn execute(&self, request: ScriptRequest) -> Result<ScriptResponse, ExecutorError> {
+        if self.is_empty_mode() {
+            return Ok(ScriptResponse::default());
+        }
+        Ok(self.response_for(&request))
     }
 
     #[inline]
@@ -41,13 +119,26 @@ impl ScriptExecutor for StubExecutor {
 
     #[inline]
     fn skip_file_check(&self) -> bool {
-        true
+        self.is_empty_mode() || !self.check_files
+    }
+
+    #[inline]
+    fn empty_responses(&self) -> bool {
+        self.is_empty_mode()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::borrow::Cow;
+
+    fn request(uri: &'static str) -> ScriptRequest {
+        ScriptRequest {
+            server_vars: vec![(Cow::Borrowed("REQUEST_URI"), Cow::Borrowed(uri))],
+            ..Default::default()
+        }
+    }
 
     #[tokio::test]
     async fn test_stub_returns_empty_body() {
@@ -57,5 +148,37 @@ mod tests {
 
         assert!(response.body.is_empty());
         assert!(response.headers.is_empty());
+        assert!(executor.skip_file_check());
+        assert!(executor.empty_responses());
+    }
+
+    #[tokio::test]
+    async fn test_canned_response_and_routes() {
+        let executor = StubExecutor::new()
+            .with_response(
+                StatusCode::OK,
+                vec![("Content-Type".into(), "text/html".into())],
+                "<p>home</p>",
+            )
+            .with_route("/missing", StatusCode::NOT_FOUND, Vec::new(), "gone");
+        assert!(!executor.empty_responses());
+        assert!(executor.skip_file_check());
+
+        let home = executor.execute(request("/?page=1")).await.unwrap();
+        assert_eq!(home.body, "<p>home</p>");
+        assert_eq!(home.headers, [("Content-Type".into(), "text/html".into())]);
+
+        let missing = executor.execute(request("/missing?x=1")).await.unwrap();
+        assert_eq!(missing.body, "gone");
+        assert_eq!(missing.headers, [("Status".into(), "404".into())]);
+    }
+
+    #[test]
+    fn test_file_check_only_in_canned_mode() {
+        assert!(StubExecutor::new().with_file_check(true).skip_file_check());
+        let executor = StubExecutor::new()
+            .with_route("/", StatusCode::OK, Vec::new(), "ok")
+            .with_file_check(true);
+        assert!(!executor.skip_file_check());
     }
 }
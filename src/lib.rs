@@ -42,6 +42,7 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub mod bridge;
 pub mod config;
 pub mod core;
+pub mod diagnostics;
 pub mod executor;
 pub mod listener;
 pub mod logging;
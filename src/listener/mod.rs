@@ -54,6 +54,8 @@ pub struct TlsInfo {
     pub protocol: String,
     /// ALPN negotiated protocol (e.g., "h2", "http/1.1").
     pub alpn: Option<String>,
+    /// SNI hostname sent by the client, if any.
+    pub sni: Option<String>,
     /// TLS handshake duration.
     pub handshake_duration: Duration,
 }
@@ -155,6 +157,7 @@ mod tests {
         let info = TlsInfo {
             protocol: "TLSv1.3".to_string(),
             alpn: Some("h2".to_string()),
+            sni: None,
             handshake_duration: Duration::from_millis(50),
         };
 
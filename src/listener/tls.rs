@@ -160,6 +160,12 @@ impl TlsListener {
             .alpn_protocol()
             .map(|p| String::from_utf8_lossy(p).to_string())
     }
+
+    /// Get the SNI hostname sent by the client.
+    fn server_name(conn: &tokio_rustls::server::TlsStream<TcpStream>) -> Option<String> {
+        let (_, server_conn) = conn.get_ref();
+        server_conn.server_name().map(str::to_string)
+    }
 }
 
 impl Listener for TlsListener {
@@ -187,6 +193,7 @@ impl Listener for TlsListener {
             let tls_info = TlsInfo {
                 protocol: Self::protocol_version(&tls_stream),
                 alpn: Self::alpn_protocol(&tls_stream),
+                sni: Self::server_name(&tls_stream),
                 handshake_duration,
             };
 
@@ -217,6 +224,7 @@ mod tests {
         let info = TlsInfo {
             protocol: "TLSv1.3".to_string(),
             alpn: Some("h2".to_string()),
+            sni: None,
             handshake_duration: Duration::from_millis(50),
         };
 
@@ -0,0 +1,107 @@
+//! Log filter that can change while the server runs.
+//!
+//! `main` installs the `EnvFilter` through [`filter_layer`], which keeps the
+//! reload handle. SIGHUP re-applies the configured `LOG_LEVEL` / `RUST_LOG`
+//! when it has changed (e.g. in `CONFIG_FILE`), and `POST /loglevel` on the
+//! internal server sets a filter directly, so debug logging can be turned on
+//! for a few minutes without a restart.
+
+use std::sync::{Mutex, OnceLock};
+
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Handle to the installed filter layer.
+static HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Filter from the configuration as of the last load.
+static CONFIGURED: Mutex<Option<String>> = Mutex::new(None);
+
+/// Wrap the startup filter in a reloadable layer and keep its handle.
+///
+/// `configured` is the filter resolved from the configuration, which
+/// [`apply_configured_filter`] compares against on reload.
+pub fn filter_layer(filter: EnvFilter, configured: &str) -> reload::Layer<EnvFilter, Registry> {
+    let (layer, handle) = reload::Layer::new(filter);
+    HANDLE.set(handle).ok();
+    *CONFIGURED.lock().unwrap_or_else(|e| e.into_inner()) = Some(configured.to_string());
+    layer
+}
+
+/// The active filter, or None if no reloadable filter is installed.
+pub fn log_filter() -> Option<String> {
+    HANDLE.get()?.with_current(|f| f.to_string()).ok()
+}
+
+/// Filter from the configuration as of the last load.
+pub fn configured_filter() -> Option<String> {
+    CONFIGURED.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Replace the active filter (`RUST_LOG` syntax, e.g. `tokio_php=debug,hyper=info`).
+pub fn set_log_filter(filter: &str) -> Result<(), String> {
+    let handle = HANDLE
+        .get()
+        .ok_or_else(|| "log filter is not reloadable".to_string())?;
+    let new = EnvFilter::try_new(filter).map_err(|e| format!("invalid filter '{filter}': {e}"))?;
+    handle.reload(new).map_err(|e| e.to_string())
+}
+
+/// Apply the filter from a reloaded configuration if it differs from the
+/// previous one. An unchanged configuration keeps a filter set at runtime.
+pub fn apply_configured_filter(filter: &str) {
+    let mut configured = CONFIGURED.lock().unwrap_or_else(|e| e.into_inner());
+    if configured.as_deref() == Some(filter) {
+        return;
+    }
+    match set_log_filter(filter) {
+        Ok(()) => {
+            tracing::info!("Log filter changed to {}", filter);
+            *configured = Some(filter.to_string());
+        }
+        Err(e) => tracing::warn!("Config reload: log filter not applied: {}", e),
+    }
+}
+
+/// Installs the reloadable filter once for tests and serializes the tests
+/// that change it.
+#[cfg(test)]
+pub(crate) fn test_filter_guard() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    static LAYER: OnceLock<reload::Layer<EnvFilter, Registry>> = OnceLock::new();
+    let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    // The handle only reloads while its layer is alive
+    LAYER.get_or_init(|| filter_layer(EnvFilter::new("info"), "info"));
+    guard
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_log_filter_rejects_invalid() {
+        let _guard = test_filter_guard();
+        set_log_filter("tokio_php=warn").unwrap();
+
+        let err = set_log_filter("tokio_php=loud").unwrap_err();
+        assert!(err.contains("invalid filter"), "{}", err);
+        assert_eq!(log_filter().as_deref(), Some("tokio_php=warn"));
+    }
+
+    #[test]
+    fn test_apply_configured_filter_keeps_runtime_override() {
+        let _guard = test_filter_guard();
+        apply_configured_filter("info");
+        set_log_filter("tokio_php=debug").unwrap();
+
+        // Unchanged configuration: the runtime filter stays
+        apply_configured_filter("info");
+        assert_eq!(log_filter().as_deref(), Some("tokio_php=debug"));
+
+        // Changed configuration replaces it
+        apply_configured_filter("tokio_php=error");
+        assert_eq!(log_filter().as_deref(), Some("tokio_php=error"));
+        apply_configured_filter("info");
+        assert_eq!(log_filter().as_deref(), Some("info"));
+    }
+}
@@ -0,0 +1,677 @@
+//! Unified JSON logging with custom format.
+//!
+//! Log format:
+//! ```json
+//! {"ts":"2024-12-28T15:04:05.123Z","level":"info","type":"app","msg":"Server started","ctx":{},"data":{}}
+//! ```
+//!
+//! Access logs use async I/O via a background task to avoid blocking request handlers.
+//! They go to stdout by default, or to a rotating file (`ACCESS_LOG_FILE`) in
+//! JSON or Apache common/combined format (`ACCESS_LOG_FORMAT`).
+
+mod filter;
+mod redact;
+mod rotating_file;
+
+#[cfg(test)]
+pub(crate) use filter::test_filter_guard;
+pub use filter::{
+    apply_configured_filter, configured_filter, filter_layer, log_filter, set_log_filter,
+};
+pub use redact::{redactor, set_redact_keys, Redactor, DEFAULT_REDACT_KEYS, REDACTED};
+pub use rotating_file::RotatingFile;
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tokio::io::{stdout, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::{AccessLogConfig, AccessLogFormat};
+
+/// Channel sender for async access logging.
+static ACCESS_LOG_TX: OnceLock<mpsc::UnboundedSender<String>> = OnceLock::new();
+
+/// Access log line format (set once by `init_access_log_writer`).
+static ACCESS_LOG_FORMAT: OnceLock<AccessLogFormat> = OnceLock::new();
+
+/// Sink the access log writer was started with (set once by `init_access_log_writer`).
+static ACCESS_LOG_SINK: OnceLock<AccessLogConfig> = OnceLock::new();
+
+/// Sink settings of the running access log writer, or None if not started.
+pub fn access_log_sink() -> Option<&'static AccessLogConfig> {
+    ACCESS_LOG_SINK.get()
+}
+
+/// Initialize the async access log writer.
+/// Must be called from an async context. Subsequent calls are no-ops.
+pub fn init_access_log_writer(config: &AccessLogConfig) {
+    if ACCESS_LOG_TX.get().is_some() {
+        return;
+    }
+    let (tx, rx) = mpsc::unbounded_channel::<String>();
+    ACCESS_LOG_FORMAT.set(config.format).ok();
+
+    let file = config.file.as_ref().and_then(|path| {
+        RotatingFile::open(path, config.rotation, config.max_files)
+            .map_err(|e| {
+                tracing::error!(
+                    "Failed to open access log file {:?}: {}. Falling back to stdout",
+                    path,
+                    e
+                )
+            })
+            .ok()
+    });
+    match file {
+        // File I/O is blocking; keep it off the runtime thread
+        Some(file) => {
+            let spawned = std::thread::Builder::new()
+                .name("access-log".into())
+                .spawn(move || access_log_file_writer(rx, file));
+            if let Err(e) = spawned {
+                tracing::error!("Failed to start access log writer: {}", e);
+                return;
+            }
+        }
+        None => {
+            tokio::spawn(access_log_writer_task(rx));
+        }
+    }
+    ACCESS_LOG_SINK.set(config.clone()).ok();
+    ACCESS_LOG_TX.set(tx).ok();
+}
+
+/// Writer thread for the file sink. Batches queued entries before flushing.
+fn access_log_file_writer(mut rx: mpsc::UnboundedReceiver<String>, mut file: RotatingFile) {
+    while let Some(entry) = rx.blocking_recv() {
+        let _ = file.write_line(&entry);
+        while let Ok(entry) = rx.try_recv() {
+            let _ = file.write_line(&entry);
+        }
+        let _ = file.flush();
+    }
+}
+
+/// Background task that writes access log entries to stdout.
+async fn access_log_writer_task(mut rx: mpsc::UnboundedReceiver<String>) {
+    let mut stdout = stdout();
+    while let Some(entry) = rx.recv().await {
+        // Write and flush - errors are silently ignored (logging should not crash server)
+        let _ = stdout.write_all(entry.as_bytes()).await;
+        let _ = stdout.write_all(b"\n").await;
+        let _ = stdout.flush().await;
+    }
+}
+
+// =============================================================================
+// PHP error capture
+// =============================================================================
+
+/// PHP error severity, parsed from the log line PHP passes to the SAPI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhpErrorLevel {
+    Fatal,
+    Parse,
+    Error,
+    Warning,
+    Notice,
+    Deprecated,
+}
+
+impl PhpErrorLevel {
+    /// All levels, in metric output order.
+    pub const ALL: [Self; 6] = [
+        Self::Fatal,
+        Self::Parse,
+        Self::Error,
+        Self::Warning,
+        Self::Notice,
+        Self::Deprecated,
+    ];
+
+    /// Label value for `tokio_php_php_errors_total{level}`.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Fatal => "fatal",
+            Self::Parse => "parse",
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Notice => "notice",
+            Self::Deprecated => "deprecated",
+        }
+    }
+
+    /// Classify a PHP log line ("PHP Fatal error:  ...").
+    /// Falls back to the syslog level for plain `error_log()` calls;
+    /// returns None for info/debug lines.
+    pub fn classify(msg: &str, syslog_type: i32) -> Option<Self> {
+        const PREFIXES: [(&str, PhpErrorLevel); 7] = [
+            ("PHP Fatal error", PhpErrorLevel::Fatal),
+            ("PHP Parse error", PhpErrorLevel::Parse),
+            ("PHP Recoverable fatal error", PhpErrorLevel::Error),
+            ("PHP Warning", PhpErrorLevel::Warning),
+            ("PHP Notice", PhpErrorLevel::Notice),
+            ("PHP Deprecated", PhpErrorLevel::Deprecated),
+            ("PHP Strict Standards", PhpErrorLevel::Deprecated),
+        ];
+        if let Some((_, level)) = PREFIXES.iter().find(|(p, _)| msg.contains(p)) {
+            return Some(*level);
+        }
+        match syslog_type {
+            0..=3 => Some(Self::Error),
+            4 => Some(Self::Warning),
+            _ => None,
+        }
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// PHP error counters by level (written from PHP worker threads).
+static PHP_ERRORS: [AtomicU64; 6] = [const { AtomicU64::new(0) }; 6];
+
+/// Count a captured PHP error.
+#[inline]
+pub fn record_php_error(level: PhpErrorLevel) {
+    PHP_ERRORS[level.index()].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Total captured PHP errors at the given level.
+pub fn php_error_count(level: PhpErrorLevel) -> u64 {
+    PHP_ERRORS[level.index()].load(Ordering::Relaxed)
+}
+
+/// Log entry with unified structure.
+#[derive(Serialize)]
+pub struct LogEntry<'a> {
+    /// ISO 8601 timestamp with milliseconds, UTC
+    pub ts: &'a str,
+    /// Log level: debug, info, warn, error
+    pub level: &'a str,
+    /// Log type: app, access, error
+    #[serde(rename = "type")]
+    pub log_type: &'a str,
+    /// Short human-readable message
+    pub msg: &'a str,
+    /// Context: request_id, service, etc.
+    pub ctx: LogContext<'a>,
+    /// Type-specific data
+    pub data: HashMap<&'a str, serde_json::Value>,
+}
+
+/// Log context.
+#[derive(Serialize, Default)]
+pub struct LogContext<'a> {
+    /// Service name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<&'a str>,
+    /// Request ID for correlation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<&'a str>,
+    /// Worker ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker: Option<u32>,
+}
+
+/// Custom JSON formatter for tracing.
+pub struct JsonFormatter {
+    service_name: String,
+}
+
+impl JsonFormatter {
+    pub fn new(service_name: impl Into<String>) -> Self {
+        Self {
+            service_name: service_name.into(),
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormatter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let meta = event.metadata();
+        let level = match *meta.level() {
+            Level::TRACE => "debug",
+            Level::DEBUG => "debug",
+            Level::INFO => "info",
+            Level::WARN => "warn",
+            Level::ERROR => "error",
+        };
+
+        // Determine log type from target
+        let log_type = if meta.target() == "access" {
+            "access"
+        } else if meta.target() == "php" {
+            "php"
+        } else if *meta.level() == Level::ERROR {
+            "error"
+        } else {
+            "app"
+        };
+
+        // Collect fields
+        let mut visitor = FieldVisitor::new();
+        event.record(&mut visitor);
+
+        // Generate timestamp
+        let ts = crate::server::connection::chrono_lite_iso8601();
+
+        // Build message
+        let msg = if log_type == "access" {
+            // For access logs, build "METHOD /path STATUS"
+            let method = visitor
+                .fields
+                .get("method")
+                .and_then(|v| v.as_str())
+                .unwrap_or("?");
+            let path = visitor
+                .fields
+                .get("path")
+                .and_then(|v| v.as_str())
+                .unwrap_or("?");
+            let status = visitor
+                .fields
+                .get("status")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0);
+            format!("{} {} {}", method, path, status)
+        } else {
+            visitor.message.clone().unwrap_or_default()
+        };
+
+        // Build data (remove message from fields for app logs)
+        let mut data = visitor.fields;
+        if log_type != "access" {
+            data.remove("message");
+        }
+
+        // Build context; request correlation fields move from data to ctx
+        // (matching access log entries) so PHP logs can be joined by request.
+        let mut ctx = serde_json::Map::new();
+        ctx.insert("service".into(), serde_json::json!(&self.service_name));
+        for key in ["request_id", "trace_id", "span_id"] {
+            if let Some(value) = data.remove(key) {
+                if value.as_str().is_some_and(|v| !v.is_empty()) {
+                    ctx.insert(key.into(), value);
+                }
+            }
+        }
+        if log_type == "php" {
+            data.retain(|_, v| v.as_str() != Some(""));
+        }
+        redact_fields(&mut data);
+
+        // Build final JSON
+        let entry = serde_json::json!({
+            "ts": ts,
+            "level": level,
+            "type": log_type,
+            "msg": msg,
+            "ctx": ctx,
+            "data": data,
+        });
+
+        writeln!(
+            writer,
+            "{}",
+            serde_json::to_string(&entry).unwrap_or_default()
+        )
+    }
+}
+
+/// Redact sensitive fields, and sensitive parameters in `query`/`referer`.
+fn redact_fields(data: &mut HashMap<String, serde_json::Value>) {
+    let redactor = redactor();
+    for (key, value) in data.iter_mut() {
+        if redactor.is_sensitive(key) {
+            *value = serde_json::Value::String(REDACTED.to_string());
+            continue;
+        }
+        let redacted = match (key.as_str(), value.as_str()) {
+            ("query", Some(q)) => redactor.query(q).into_owned(),
+            ("referer", Some(r)) => redactor.url(r).into_owned(),
+            _ => continue,
+        };
+        *value = serde_json::Value::String(redacted);
+    }
+}
+
+/// Field visitor for collecting tracing fields.
+struct FieldVisitor {
+    message: Option<String>,
+    fields: HashMap<String, serde_json::Value>,
+}
+
+impl FieldVisitor {
+    fn new() -> Self {
+        Self {
+            message: None,
+            fields: HashMap::new(),
+        }
+    }
+}
+
+impl tracing::field::Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{:?}", value).trim_matches('"').to_string());
+        } else {
+            self.fields.insert(
+                field.name().to_string(),
+                serde_json::Value::String(format!("{:?}", value)),
+            );
+        }
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.fields.insert(
+                field.name().to_string(),
+                serde_json::Value::String(value.to_string()),
+            );
+        }
+    }
+
+    fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+        self.fields
+            .insert(field.name().to_string(), serde_json::json!(value));
+    }
+
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.fields
+            .insert(field.name().to_string(), serde_json::json!(value));
+    }
+
+    fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+        self.fields
+            .insert(field.name().to_string(), serde_json::json!(value));
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        self.fields
+            .insert(field.name().to_string(), serde_json::json!(value));
+    }
+}
+
+/// Log an access request directly (bypassing tracing for simpler output).
+#[allow(clippy::too_many_arguments)]
+pub fn log_access(
+    ts: &str,
+    request_id: &str,
+    ip: &str,
+    method: &str,
+    path: &str,
+    query: Option<&str>,
+    http: &str,
+    status: u16,
+    bytes: u64,
+    duration_ms: f64,
+    ua: Option<&str>,
+    referer: Option<&str>,
+    xff: Option<&str>,
+    tls: Option<&str>,
+    trace_id: Option<&str>,
+    span_id: Option<&str>,
+) {
+    // Redact before formatting so no sink sees the raw values
+    let redactor = redactor();
+    let query = query.map(|q| redactor.query(q));
+    let query = query.as_deref();
+    let referer = referer.map(|r| redactor.url(redactor.header("Referer", r)));
+    let referer = referer.as_deref();
+    let ua = ua.map(|u| redactor.header("User-Agent", u));
+    let xff = xff.map(|x| redactor.header("X-Forwarded-For", x));
+
+    let format = ACCESS_LOG_FORMAT.get().copied().unwrap_or_default();
+    if format != AccessLogFormat::Json {
+        let combined = (format == AccessLogFormat::Combined).then_some((referer, ua));
+        let line = format_clf(ip, ts, method, path, query, http, status, bytes, combined);
+        if let Some(tx) = ACCESS_LOG_TX.get() {
+            let _ = tx.send(line);
+        }
+        return;
+    }
+
+    let msg = format!("{} {} {}", method, path, status);
+
+    let mut data = serde_json::Map::new();
+    data.insert("method".into(), serde_json::json!(method));
+    data.insert("path".into(), serde_json::json!(path));
+    if let Some(q) = query {
+        data.insert("query".into(), serde_json::json!(q));
+    }
+    data.insert("http".into(), serde_json::json!(http));
+    data.insert("status".into(), serde_json::json!(status));
+    data.insert("bytes".into(), serde_json::json!(bytes));
+    data.insert("duration_ms".into(), serde_json::json!(duration_ms));
+    data.insert("ip".into(), serde_json::json!(ip));
+    if let Some(u) = ua {
+        data.insert("ua".into(), serde_json::json!(u));
+    }
+    if let Some(r) = referer {
+        data.insert("referer".into(), serde_json::json!(r));
+    }
+    if let Some(x) = xff {
+        data.insert("xff".into(), serde_json::json!(x));
+    }
+    if let Some(t) = tls {
+        data.insert("tls".into(), serde_json::json!(t));
+    }
+
+    // Build context with trace information
+    let mut ctx = serde_json::Map::new();
+    ctx.insert("service".into(), serde_json::json!("tokio_php"));
+    ctx.insert("request_id".into(), serde_json::json!(request_id));
+    if let Some(tid) = trace_id {
+        ctx.insert("trace_id".into(), serde_json::json!(tid));
+    }
+    if let Some(sid) = span_id {
+        ctx.insert("span_id".into(), serde_json::json!(sid));
+    }
+
+    let entry = serde_json::json!({
+        "ts": ts,
+        "level": "info",
+        "type": "access",
+        "msg": msg,
+        "ctx": ctx,
+        "data": data,
+    });
+
+    // Send to async writer (non-blocking)
+    if let Some(tx) = ACCESS_LOG_TX.get() {
+        let _ = tx.send(entry.to_string());
+    }
+}
+
+/// Format an Apache Common/Combined Log Format line.
+///
+/// `%h %l %u %t "%r" %>s %b` plus `"%{Referer}i" "%{User-agent}i"` for combined.
+#[allow(clippy::too_many_arguments)]
+fn format_clf(
+    ip: &str,
+    ts: &str,
+    method: &str,
+    path: &str,
+    query: Option<&str>,
+    http: &str,
+    status: u16,
+    bytes: u64,
+    combined: Option<(Option<&str>, Option<&str>)>,
+) -> String {
+    let mut line = format!("{} - - [{}] \"{} {}", ip, apache_time(ts), method, path);
+    if let Some(q) = query {
+        line.push('?');
+        line.push_str(q);
+    }
+    line.push(' ');
+    line.push_str(http);
+    line.push_str("\" ");
+    line.push_str(&status.to_string());
+    line.push(' ');
+    if bytes == 0 {
+        line.push('-');
+    } else {
+        line.push_str(&bytes.to_string());
+    }
+    if let Some((referer, ua)) = combined {
+        for value in [referer, ua] {
+            line.push_str(" \"");
+            push_clf_escaped(&mut line, value.unwrap_or("-"));
+            line.push('"');
+        }
+    }
+    line
+}
+
+/// Escape quotes and backslashes in quoted CLF fields.
+fn push_clf_escaped(out: &mut String, value: &str) {
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+/// Convert "2024-12-28T15:04:05.123Z" to Apache's "28/Dec/2024:15:04:05 +0000".
+fn apache_time(ts: &str) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let month = ts
+        .get(5..7)
+        .and_then(|m| m.parse::<usize>().ok())
+        .and_then(|m| MONTHS.get(m.wrapping_sub(1)));
+    match (ts.get(0..4), month, ts.get(8..10), ts.get(11..19)) {
+        (Some(year), Some(month), Some(day), Some(time)) => {
+            format!("{}/{}/{}:{} +0000", day, month, year, time)
+        }
+        _ => ts.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_php_error_level_from_message() {
+        assert_eq!(
+            PhpErrorLevel::classify("PHP Fatal error:  Uncaught Exception: boom", 3),
+            Some(PhpErrorLevel::Fatal)
+        );
+        assert_eq!(
+            PhpErrorLevel::classify("PHP Parse error:  syntax error", 3),
+            Some(PhpErrorLevel::Parse)
+        );
+        assert_eq!(
+            PhpErrorLevel::classify("PHP Warning:  Undefined variable $x", 4),
+            Some(PhpErrorLevel::Warning)
+        );
+        assert_eq!(
+            PhpErrorLevel::classify("PHP Deprecated:  Creation of dynamic property", 5),
+            Some(PhpErrorLevel::Deprecated)
+        );
+    }
+
+    #[test]
+    fn test_php_error_level_syslog_fallback() {
+        assert_eq!(
+            PhpErrorLevel::classify("custom error_log() line", 3),
+            Some(PhpErrorLevel::Error)
+        );
+        assert_eq!(
+            PhpErrorLevel::classify("custom error_log() line", 4),
+            Some(PhpErrorLevel::Warning)
+        );
+        assert_eq!(PhpErrorLevel::classify("just info", 6), None);
+    }
+
+    #[test]
+    fn test_php_error_counter() {
+        let before = php_error_count(PhpErrorLevel::Notice);
+        record_php_error(PhpErrorLevel::Notice);
+        assert_eq!(php_error_count(PhpErrorLevel::Notice), before + 1);
+    }
+
+    #[test]
+    fn test_apache_time() {
+        assert_eq!(
+            apache_time("2024-12-28T15:04:05.123Z"),
+            "28/Dec/2024:15:04:05 +0000"
+        );
+        assert_eq!(apache_time("garbage"), "garbage");
+    }
+
+    #[test]
+    fn test_format_common() {
+        let line = format_clf(
+            "10.0.0.1",
+            "2025-01-15T10:30:00.000Z",
+            "GET",
+            "/index.php",
+            Some("a=1"),
+            "HTTP/1.1",
+            200,
+            1234,
+            None,
+        );
+        assert_eq!(
+            line,
+            "10.0.0.1 - - [15/Jan/2025:10:30:00 +0000] \"GET /index.php?a=1 HTTP/1.1\" 200 1234"
+        );
+    }
+
+    #[test]
+    fn test_format_combined() {
+        let line = format_clf(
+            "10.0.0.1",
+            "2025-01-15T10:30:00.000Z",
+            "HEAD",
+            "/",
+            None,
+            "HTTP/2.0",
+            304,
+            0,
+            Some((None, Some("curl/8.0 \"x\""))),
+        );
+        assert_eq!(
+            line,
+            "10.0.0.1 - - [15/Jan/2025:10:30:00 +0000] \"HEAD / HTTP/2.0\" 304 - \"-\" \"curl/8.0 \\\"x\\\"\""
+        );
+    }
+
+    #[test]
+    fn test_redact_fields_with_default_keys() {
+        let mut data: HashMap<String, serde_json::Value> = HashMap::new();
+        data.insert("authorization".into(), serde_json::json!("Bearer abc"));
+        data.insert("x_api_key".into(), serde_json::json!("k-123"));
+        data.insert("path".into(), serde_json::json!("/api"));
+        data.insert("status".into(), serde_json::json!(200));
+        redact_fields(&mut data);
+
+        assert_eq!(data["authorization"], REDACTED);
+        assert_eq!(data["x_api_key"], REDACTED);
+        assert_eq!(data["path"], "/api");
+        assert_eq!(data["status"], 200);
+    }
+}
@@ -0,0 +1,174 @@
+//! Redaction of sensitive values before they reach the logs.
+//!
+//! A single key list covers header names, query parameters and form fields.
+//! Matching is case-insensitive and treats `-` and `_` alike, so `X-Api-Key`
+//! also matches a tracing field named `x_api_key`.
+
+use std::borrow::Cow;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// Replacement for redacted values.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Keys redacted unless `LOG_REDACT` replaces the list.
+pub const DEFAULT_REDACT_KEYS: [&str; 4] = ["Authorization", "Cookie", "Set-Cookie", "X-Api-Key"];
+
+/// Active redactor (swapped on SIGHUP).
+static REDACTOR: LazyLock<RwLock<Arc<Redactor>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(Redactor::default())));
+
+/// Replace the active redaction keys.
+pub fn set_redact_keys(keys: &[String]) {
+    *REDACTOR.write().unwrap() = Arc::new(Redactor::new(keys));
+}
+
+/// Snapshot of the active redactor.
+pub fn redactor() -> Arc<Redactor> {
+    Arc::clone(&REDACTOR.read().unwrap())
+}
+
+/// Normalized set of sensitive keys.
+#[derive(Debug)]
+pub struct Redactor {
+    keys: Vec<String>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new(&DEFAULT_REDACT_KEYS)
+    }
+}
+
+impl Redactor {
+    pub fn new<S: AsRef<str>>(keys: &[S]) -> Self {
+        Self {
+            keys: keys.iter().map(|k| normalize(k.as_ref())).collect(),
+        }
+    }
+
+    /// Whether a header name, parameter or log field name is sensitive.
+    pub fn is_sensitive(&self, name: &str) -> bool {
+        !self.keys.is_empty() && self.keys.contains(&normalize(name))
+    }
+
+    /// Whether a raw query/form key is sensitive once decoded the way PHP
+    /// sees it: `pass%77ord` and `password[]` both name `password`.
+    fn is_sensitive_param(&self, raw_key: &str) -> bool {
+        let key = if raw_key.contains(['%', '+']) {
+            let plus_decoded = raw_key.replace('+', " ");
+            Cow::Owned(
+                percent_encoding::percent_decode_str(&plus_decoded)
+                    .decode_utf8_lossy()
+                    .into_owned(),
+            )
+        } else {
+            Cow::Borrowed(raw_key)
+        };
+        let name = key.split_once('[').map_or(&*key, |(name, _)| name);
+        self.is_sensitive(name)
+    }
+
+    /// Header value, or `[REDACTED]` if the header is sensitive.
+    pub fn header<'a>(&self, name: &str, value: &'a str) -> &'a str {
+        if self.is_sensitive(name) {
+            REDACTED
+        } else {
+            value
+        }
+    }
+
+    /// Redact values of sensitive keys in a `k=v&k2=v2` string
+    /// (query string or urlencoded form body).
+    pub fn query<'a>(&self, query: &'a str) -> Cow<'a, str> {
+        if self.keys.is_empty() {
+            return Cow::Borrowed(query);
+        }
+        let sensitive = |pair: &str| {
+            pair.split_once('=')
+                .is_some_and(|(key, _)| self.is_sensitive_param(key))
+        };
+        if !query.split('&').any(sensitive) {
+            return Cow::Borrowed(query);
+        }
+        let pairs: Vec<Cow<'_, str>> = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, _)) if self.is_sensitive_param(key) => {
+                    Cow::Owned(format!("{}={}", key, REDACTED))
+                }
+                _ => Cow::Borrowed(pair),
+            })
+            .collect();
+        Cow::Owned(pairs.join("&"))
+    }
+
+    /// Redact the query part of a URL (e.g. Referer).
+    pub fn url<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        match url.split_once('?') {
+            Some((base, query)) => match self.query(query) {
+                Cow::Borrowed(_) => Cow::Borrowed(url),
+                Cow::Owned(q) => Cow::Owned(format!("{}?{}", base, q)),
+            },
+            None => Cow::Borrowed(url),
+        }
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim().to_ascii_lowercase().replace('_', "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_headers_case_insensitive() {
+        let r = Redactor::default();
+        assert!(r.is_sensitive("authorization"));
+        assert!(r.is_sensitive("COOKIE"));
+        assert!(r.is_sensitive("Set-Cookie"));
+        assert!(r.is_sensitive("x_api_key"));
+        assert!(!r.is_sensitive("User-Agent"));
+        assert_eq!(r.header("Authorization", "Bearer abc"), REDACTED);
+        assert_eq!(r.header("Accept", "*/*"), "*/*");
+    }
+
+    #[test]
+    fn test_query_redaction() {
+        let r = Redactor::new(&["token", "password"]);
+        assert_eq!(
+            r.query("page=2&token=s3cr3t&Password=x"),
+            "page=2&token=[REDACTED]&Password=[REDACTED]"
+        );
+        assert!(matches!(r.query("page=2&q=rust"), Cow::Borrowed(_)));
+        assert_eq!(r.query("token"), "token");
+    }
+
+    #[test]
+    fn test_query_keys_decoded_before_matching() {
+        let r = Redactor::new(&["password", "api key"]);
+        assert_eq!(r.query("pass%77ord=x&a=1"), "pass%77ord=[REDACTED]&a=1");
+        assert_eq!(r.query("PASS%57ORD=x"), "PASS%57ORD=[REDACTED]");
+        assert_eq!(r.query("password[]=x"), "password[]=[REDACTED]");
+        assert_eq!(r.query("api+key=x"), "api+key=[REDACTED]");
+        assert_eq!(r.query("passwords=x"), "passwords=x");
+    }
+
+    #[test]
+    fn test_url_redaction() {
+        let r = Redactor::new(&["token"]);
+        assert_eq!(
+            r.url("https://example.com/cb?token=abc&x=1"),
+            "https://example.com/cb?token=[REDACTED]&x=1"
+        );
+        assert_eq!(r.url("https://example.com/"), "https://example.com/");
+    }
+
+    #[test]
+    fn test_empty_list_disables_redaction() {
+        let r = Redactor::new::<&str>(&[]);
+        assert!(!r.is_sensitive("Authorization"));
+        assert_eq!(r.query("token=abc"), "token=abc");
+    }
+}
@@ -0,0 +1,348 @@
+//! Append-only log file with rotation.
+//!
+//! Rotation renames the active file to `<name>.<timestamp>` and reopens a
+//! fresh file at the original path. The file is also reopened when it is
+//! deleted or replaced externally (e.g. by logrotate without copytruncate).
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::LogRotation;
+use crate::server::connection::Iso8601Timestamp;
+
+/// How often to check whether the file was removed or replaced externally.
+const REOPEN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Log file writer with size/daily rotation and retention.
+pub struct RotatingFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    rotation: LogRotation,
+    max_files: usize,
+    /// Bytes written to the current file (including pre-existing content).
+    size: u64,
+    /// UTC day number the current file was opened on.
+    day: u64,
+    last_reopen_check: Instant,
+    #[cfg(unix)]
+    inode: u64,
+}
+
+impl RotatingFile {
+    /// Open (or create) the log file for appending.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        rotation: LogRotation,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        let file = open_append(&path)?;
+        let meta = file.metadata()?;
+        Ok(Self {
+            #[cfg(unix)]
+            inode: std::os::unix::fs::MetadataExt::ino(&meta),
+            size: meta.len(),
+            writer: BufWriter::new(file),
+            path,
+            rotation,
+            max_files,
+            day: now_secs() / 86400,
+            last_reopen_check: Instant::now(),
+        })
+    }
+
+    /// Append one line (a newline is added).
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        self.reopen_if_replaced()?;
+        if self.should_rotate(line.len() as u64 + 1) {
+            self.rotate()?;
+        }
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    /// Flush buffered lines to disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn should_rotate(&self, incoming: u64) -> bool {
+        match self.rotation {
+            LogRotation::Never => false,
+            LogRotation::Daily => now_secs() / 86400 != self.day,
+            // Never rotate an empty file, even if a single line exceeds the limit
+            LogRotation::Size(max) => self.size > 0 && self.size + incoming > max,
+        }
+    }
+
+    /// Rename the active file and reopen a fresh one at the original path.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+
+        let suffix = match self.rotation {
+            // Daily files are named after the day they cover
+            LogRotation::Daily => date_suffix(self.day * 86400),
+            _ => datetime_suffix(now_secs()),
+        };
+        let mut target = suffixed_path(&self.path, &suffix);
+        let mut n = 1;
+        while target.exists() {
+            target = suffixed_path(&self.path, &format!("{suffix}.{n}"));
+            n += 1;
+        }
+
+        // rename(2) is atomic: writers never see a partially moved file
+        match fs::rename(&self.path, &target) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        self.reopen()?;
+        self.prune();
+        Ok(())
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        let file = open_append(&self.path)?;
+        let meta = file.metadata()?;
+        #[cfg(unix)]
+        {
+            self.inode = std::os::unix::fs::MetadataExt::ino(&meta);
+        }
+        self.size = meta.len();
+        self.day = now_secs() / 86400;
+        self.writer = BufWriter::new(file);
+        Ok(())
+    }
+
+    /// Reopen if the path no longer points at our file (deleted or replaced).
+    fn reopen_if_replaced(&mut self) -> io::Result<()> {
+        if self.last_reopen_check.elapsed() < REOPEN_CHECK_INTERVAL {
+            return Ok(());
+        }
+        self.last_reopen_check = Instant::now();
+
+        let replaced = match fs::metadata(&self.path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+            Err(_) => false,
+            #[cfg(unix)]
+            Ok(meta) => std::os::unix::fs::MetadataExt::ino(&meta) != self.inode,
+            #[cfg(not(unix))]
+            Ok(_) => false,
+        };
+        if replaced {
+            let _ = self.writer.flush();
+            self.reopen()?;
+        }
+        Ok(())
+    }
+
+    /// Delete the oldest rotated files beyond `max_files`.
+    fn prune(&self) {
+        let (Some(dir), Some(name)) = (self.path.parent(), self.path.file_name()) else {
+            return;
+        };
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let name = name.to_string_lossy();
+
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let entries: Vec<(String, PathBuf)> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| (e.file_name().to_string_lossy().into_owned(), e.path()))
+            .collect();
+        // Only names this sink writes; other files in the directory are kept
+        let mut rotated: Vec<((&str, u64), &PathBuf)> = entries
+            .iter()
+            .filter_map(|(file_name, path)| {
+                let suffix = file_name.strip_prefix(name.as_ref())?.strip_prefix('.')?;
+                Some((parse_rotated_suffix(suffix)?, path))
+            })
+            .collect();
+        if rotated.len() <= self.max_files {
+            return;
+        }
+        // Oldest first: by timestamp, then by collision number
+        rotated.sort();
+        let excess = rotated.len() - self.max_files;
+        for (_, old) in &rotated[..excess] {
+            let _ = fs::remove_file(old);
+        }
+    }
+}
+
+/// Timestamp and collision number of a rotated-file suffix
+/// (`2025-01-15`, `2025-01-15T10-30-00`, optionally `.N`), or None for
+/// names this sink doesn't write.
+fn parse_rotated_suffix(suffix: &str) -> Option<(&str, u64)> {
+    let (stamp, seq) = match suffix.split_once('.') {
+        Some((stamp, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => {
+            (stamp, n.parse().ok()?)
+        }
+        Some(_) => return None,
+        None => (suffix, 0),
+    };
+    // Digits where the date-time has them, separators elsewhere
+    let shape = |pattern: &[u8]| {
+        stamp.len() == pattern.len()
+            && stamp.bytes().zip(pattern).all(|(b, &p)| match p {
+                b'd' => b.is_ascii_digit(),
+                _ => b == p,
+            })
+    };
+    (shape(b"dddd-dd-dd") || shape(b"dddd-dd-ddTdd-dd-dd")).then_some((stamp, seq))
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// "2025-01-15"
+fn date_suffix(secs: u64) -> String {
+    Iso8601Timestamp::from_duration(Duration::from_secs(secs)).as_str()[..10].to_string()
+}
+
+/// "2025-01-15T10-30-00" (colons are awkward in file names)
+fn datetime_suffix(secs: u64) -> String {
+    Iso8601Timestamp::from_duration(Duration::from_secs(secs)).as_str()[..19].replace(':', "-")
+}
+
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotated_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.file_name().unwrap() != "access.log")
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_write_without_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let mut f = RotatingFile::open(&path, LogRotation::Never, 3).unwrap();
+        f.write_line("one").unwrap();
+        f.write_line("two").unwrap();
+        f.flush().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+        assert!(rotated_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_size_rotation_and_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let mut f = RotatingFile::open(&path, LogRotation::Size(10), 2).unwrap();
+        for line in ["aaaaaaaa", "bbbbbbbb", "cccccccc", "dddddddd"] {
+            f.write_line(line).unwrap();
+        }
+        f.flush().unwrap();
+
+        // Active file holds only the latest line
+        assert_eq!(fs::read_to_string(&path).unwrap(), "dddddddd\n");
+        // Three rotations happened, only the two newest are kept
+        let rotated = rotated_files(dir.path());
+        assert_eq!(rotated.len(), 2);
+        assert_eq!(fs::read_to_string(&rotated[0]).unwrap(), "bbbbbbbb\n");
+        assert_eq!(fs::read_to_string(&rotated[1]).unwrap(), "cccccccc\n");
+    }
+
+    #[test]
+    fn test_reopen_after_external_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let mut f = RotatingFile::open(&path, LogRotation::Never, 3).unwrap();
+        f.write_line("before").unwrap();
+        f.flush().unwrap();
+
+        fs::remove_file(&path).unwrap();
+        // Force the periodic check to run on the next write
+        f.last_reopen_check = Instant::now() - REOPEN_CHECK_INTERVAL;
+        f.write_line("after").unwrap();
+        f.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "after\n");
+    }
+
+    #[test]
+    fn test_prune_keeps_unrelated_files_and_orders_collisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        for name in [
+            "access.log.bak",
+            "access.log.gz",
+            "access.log.2025-01-15T10-00-00.10",
+            "access.log.2025-01-15T10-00-00.2",
+            "access.log.2025-01-15T10-00-00",
+            "access.log.2025-01-16",
+        ] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        let f = RotatingFile::open(&path, LogRotation::Size(10), 2).unwrap();
+        f.prune();
+
+        let names: Vec<String> = rotated_files(dir.path())
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "access.log.2025-01-15T10-00-00.10",
+                "access.log.2025-01-16",
+                "access.log.bak",
+                "access.log.gz",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rotated_suffix() {
+        assert_eq!(parse_rotated_suffix("2025-01-15"), Some(("2025-01-15", 0)));
+        assert_eq!(
+            parse_rotated_suffix("2025-01-15T10-30-00.12"),
+            Some(("2025-01-15T10-30-00", 12))
+        );
+        assert_eq!(parse_rotated_suffix("bak"), None);
+        assert_eq!(parse_rotated_suffix("2025-01-15.gz"), None);
+        assert_eq!(parse_rotated_suffix("2025-01-15T10:30:00"), None);
+    }
+
+    #[test]
+    fn test_suffixes() {
+        assert_eq!(date_suffix(0), "1970-01-01");
+        assert_eq!(datetime_suffix(3661), "1970-01-01T01-01-01");
+        assert_eq!(
+            suffixed_path(Path::new("/var/log/access.log"), "2025-01-15"),
+            PathBuf::from("/var/log/access.log.2025-01-15")
+        );
+    }
+}
@@ -1,9 +1,10 @@
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use tokio_php::config::{Config, ExecutorType};
+use tokio_php::config::{Config, ExecutorType, RuntimeThreads};
 use tokio_php::logging;
-use tokio_php::server::{Server, ServerConfig};
+use tokio_php::server::response::ResponseSettings;
+use tokio_php::server::{InternalAccess, Server, ServerConfig};
 
 #[cfg(feature = "php")]
 use tokio_php::executor::PhpExecutor;
@@ -11,7 +12,7 @@ use tokio_php::executor::PhpExecutor;
 #[cfg(feature = "php")]
 use tokio_php::executor::ExtExecutor;
 
-use tokio_php::executor::StubExecutor;
+use tokio_php::executor::{FastCgiExecutor, StubExecutor};
 
 fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Load configuration from environment
@@ -20,12 +21,16 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         e
     })?;
 
-    // Initialize logging with custom JSON formatter
+    // Redaction applies to everything logged from here on
+    logging::set_redact_keys(&config.logging.redact_keys);
+
+    // Initialize logging with custom JSON formatter; the filter is reloadable
     tracing_subscriber::registry()
-        .with(
+        .with(logging::filter_layer(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| config.logging.filter.clone().into()),
-        )
+            &config.logging.filter,
+        ))
         .with(
             tracing_subscriber::fmt::layer()
                 .event_format(logging::JsonFormatter::new(
@@ -49,10 +54,23 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     info!("Starting tokio_php v{}", tokio_php::VERSION);
 
-    // Use single-threaded Tokio runtime - PHP workers handle blocking work
-    let runtime = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()?;
+    // Single-threaded by default - PHP workers handle blocking work
+    let runtime = match config.server.runtime_threads {
+        RuntimeThreads::CurrentThread => {
+            info!("Tokio runtime: current-thread");
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?
+        }
+        RuntimeThreads::MultiThread(threads) => {
+            info!("Tokio runtime: multi-thread, {} worker threads", threads);
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(threads)
+                .thread_name("tokio-rt")
+                .enable_all()
+                .build()?
+        }
+    };
 
     runtime.block_on(async_main(config))
 }
@@ -79,6 +97,9 @@ async fn async_main(config: Config) -> Result<(), Box<dyn std::error::Error + Se
             cert.to_string_lossy().into_owned(),
             key.to_string_lossy().into_owned(),
         );
+    } else if config.server.tls.auto {
+        warn!("TLS_MODE=auto: serving a generated self-signed certificate. Clients will not trust it; do not use in production");
+        server_config = server_config.with_tls_auto(true);
     }
 
     // Index file
@@ -88,7 +109,12 @@ async fn async_main(config: Config) -> Result<(), Box<dyn std::error::Error + Se
 
     // Internal server
     if let Some(internal_addr) = config.server.internal_addr {
-        server_config = server_config.with_internal_addr(internal_addr);
+        server_config = server_config
+            .with_internal_addr(internal_addr)
+            .with_internal_access(InternalAccess::new(
+                config.server.internal_token.clone(),
+                config.server.internal_allow.clone(),
+            ));
     }
 
     // Error pages
@@ -97,8 +123,10 @@ async fn async_main(config: Config) -> Result<(), Box<dyn std::error::Error + Se
         server_config = server_config.with_error_pages_dir(dir.to_string_lossy().into_owned());
     }
 
-    // Drain timeout
-    server_config = server_config.with_drain_timeout(config.server.drain_timeout);
+    // Drain timeout and per-connection GOAWAY window
+    server_config = server_config
+        .with_drain_timeout(config.server.drain_timeout)
+        .with_goaway_window(config.server.goaway_window);
 
     // Static cache TTL (unified type, no conversion needed)
     server_config = server_config.with_static_cache_ttl(config.server.static_cache_ttl);
@@ -106,23 +134,150 @@ async fn async_main(config: Config) -> Result<(), Box<dyn std::error::Error + Se
     // Request timeout (unified type, no conversion needed)
     server_config = server_config.with_request_timeout(config.server.request_timeout);
 
+    // Reject requests that would wait in the queue longer than this
+    server_config = server_config.with_max_queue_wait(config.server.max_queue_wait);
+
     // Connection timeouts
     server_config = server_config
         .with_header_timeout(config.server.header_timeout)
-        .with_idle_timeout(config.server.idle_timeout);
+        .with_idle_timeout(config.server.idle_timeout)
+        .with_keepalive_timeout(config.server.keepalive_timeout)
+        .with_tls_handshake_timeout(config.server.tls_handshake_timeout);
+
+    // HTTP/2 stream limit and flow control
+    server_config = server_config.with_http2(config.server.http2);
+
+    // Brotli, compressible types, default Content-Type, stream/background limits
+    server_config = server_config
+        .with_response_settings(ResponseSettings::from_config(&config))
+        .with_output_limits(config.output_limits());
+
+    // Slow request logging
+    server_config = server_config.with_slow_request_threshold(config.server.slow_request_threshold);
+
+    // Kill-switch (disabled path prefixes)
+    server_config = server_config.with_disabled_paths(
+        config.server.disabled_paths.clone(),
+        config.server.disabled_path_status,
+    );
+
+    // Timing-Allow-Origin for cross-origin Resource Timing
+    if let Some(ref tao) = config.server.timing_allow_origin {
+        server_config = server_config.with_timing_allow_origin(tao.clone());
+    }
+
+    // Directory listings and slash-less directory URIs
+    server_config = server_config
+        .with_autoindex(config.server.autoindex.clone())
+        .with_directory_request(config.server.directory_request);
+
+    // Canonical Link headers
+    server_config = server_config.with_canonical_links(config.server.canonical_links.clone());
+    server_config = server_config.with_route_labels(config.server.metrics_routes.clone());
+
+    // Prefixes served by HTTP upstreams instead of PHP
+    server_config = server_config.with_proxy_routes(
+        config.server.proxy_routes.clone(),
+        config.server.proxy_pool_size,
+    );
+
+    // Methods passed to PHP
+    server_config = server_config.with_allowed_methods(config.server.allowed_methods.clone());
+
+    // Requests that skip ahead of bulk traffic in the worker queue
+    server_config = server_config.with_priority_paths(config.server.priority_paths.clone());
+
+    // Per-request CSP nonce
+    if let Some(ref csp) = config.server.csp_nonce {
+        server_config = server_config.with_csp_nonce(csp.clone());
+    }
+
+    // Static file Content-Type overrides, fingerprinted assets and device variants
+    server_config = server_config
+        .with_mime_types(config.server.mime_types.clone())
+        .with_static_compress_cache_size(config.server.static_compress_cache_size)
+        .with_immutable_assets(config.server.immutable_assets.clone())
+        .with_ua_variants(config.server.ua_variants.clone());
+
+    // Server-wide, per-worker and per-IP connection caps
+    server_config = server_config
+        .with_max_connections(config.server.max_connections)
+        .with_max_connections_per_worker(config.server.max_connections_per_worker)
+        .with_max_connections_per_ip(config.server.max_connections_per_ip);
+
+    // Request variable limit, body buffer pre-sizing and php://input cap
+    server_config = server_config
+        .with_max_input_vars(config.server.max_input_vars)
+        .with_body_prealloc_max(config.server.body_prealloc_max)
+        .with_raw_body_max(config.server.raw_body_max)
+        .with_max_body_size(config.server.max_body_size);
+
+    // Upload content-type allowlist
+    server_config = server_config.with_upload_types(config.server.upload_types.clone());
+
+    // Proxies allowed to set X-Forwarded-Host
+    server_config = server_config.with_trusted_proxies(config.server.trusted_proxies.clone());
+
+    // B3 trace propagation to PHP
+    server_config = server_config.with_trace_b3_egress(config.server.trace_b3_egress);
+
+    // 421 for coalesced requests the certificate doesn't cover
+    server_config = server_config.with_tls_check_authority(config.server.tls_check_authority);
+
+    // Script error details in 500 bodies (development)
+    server_config = server_config.with_expose_errors(config.server.expose_errors);
+
+    // Server response header (custom value or none)
+    server_config = server_config.with_server_header(config.server.server_header.clone());
+
+    // /ready fails while the PHP queue stays saturated
+    server_config = server_config.with_readiness(
+        config.executor.queue_capacity(),
+        config.server.ready_queue_threshold,
+        config.server.ready_saturation_window,
+    );
+
+    // 503 for low-priority (or all) requests while memory is tight
+    server_config = server_config.with_memory_pressure(config.server.memory_pressure.clone());
+
+    // Worker/queue sizing gauges on /metrics
+    if config.executor.sizing_report {
+        server_config = server_config.with_sizing(config.executor.sizing().clone());
+    }
+
+    // Errors-only access logging
+    server_config = server_config.with_access_log_min_status(config.logging.access_log.min_status);
+
+    // Per-phase profile percentiles (debug-profile builds only)
+    server_config = server_config.with_profile_stats(config.middleware.is_profile_stats_enabled());
 
     // Get worker parameters
-    #[allow(unused_variables)]
     let worker_threads = config.executor.worker_count();
-    #[allow(unused_variables)]
     let queue_capacity = config.executor.queue_capacity();
+    // Reserved queue lane for PRIORITY_PATHS and middleware-marked requests
+    let priority_capacity = config.executor.priority_queue_capacity;
+    // Cap on heartbeat extensions past REQUEST_TIMEOUT
+    let max_request_extension = config.server.max_request_extension.as_duration();
     let profile_enabled = config.middleware.is_profile_enabled();
     let access_log_enabled = config.middleware.is_access_log_enabled();
     let rate_limit_config = config.middleware.rate_limit();
 
     // Initialize async access log writer (non-blocking stdout via channel)
     if access_log_enabled {
-        logging::init_access_log_writer();
+        logging::init_access_log_writer(&config.logging.access_log);
+    }
+
+    // PHP ini overrides must be in place before the SAPI starts
+    #[cfg(feature = "php")]
+    {
+        if !config.executor.php_ini.is_empty() {
+            info!("PHP ini overrides: {:?}", config.executor.php_ini);
+        }
+        // PHP appends the same charset to header('Content-Type: text/...');
+        // only set when DEFAULT_CONTENT_TYPE/DEFAULT_CHARSET are, else php.ini decides
+        let mut php_ini = config.executor.php_ini.clone();
+        php_ini.extend(config.server.default_content_type.php_ini());
+        tokio_php::executor::sapi::set_ini_overrides(&php_ini);
     }
 
     // Create executor based on type
@@ -136,6 +291,23 @@ async fn async_main(config: Config) -> Result<(), Box<dyn std::error::Error + Se
                 .with_rate_limiter(rate_limit_config);
             run_server(server).await
         }
+        ExecutorType::FastCgi => {
+            // Validated in config; required for EXECUTOR=fastcgi
+            let addr = config.executor.fastcgi_addr.as_deref().unwrap_or_default();
+            // PHP_WORKERS bounds concurrent FPM connections (match pm.max_children)
+            let executor = FastCgiExecutor::new(addr, worker_threads, queue_capacity)?;
+            info!(
+                "FastCGI executor ready ({}, {} connections)",
+                addr,
+                executor.max_connections()
+            );
+
+            let server = Server::new(server_config, executor)?
+                .with_profile_enabled(profile_enabled)
+                .with_access_log_enabled(access_log_enabled)
+                .with_rate_limiter(rate_limit_config);
+            run_server(server).await
+        }
         ExecutorType::Ext => {
             #[cfg(feature = "php")]
             {
@@ -144,11 +316,10 @@ async fn async_main(config: Config) -> Result<(), Box<dyn std::error::Error + Se
                     worker_threads
                 );
 
-                let executor = ExtExecutor::with_queue_capacity(worker_threads, queue_capacity)
-                    .map_err(|e| {
-                        eprintln!("Failed to initialize ExtExecutor: {}", e);
-                        e
-                    })?;
+                let executor =
+                    ExtExecutor::with_lanes(worker_threads, queue_capacity, priority_capacity)
+                        .unwrap_or_else(|e| exit_on_init_error("ExtExecutor", e))
+                        .with_max_request_extension(max_request_extension);
 
                 info!(
                     "ExtExecutor ready ({} workers, FFI mode)",
@@ -181,11 +352,10 @@ async fn async_main(config: Config) -> Result<(), Box<dyn std::error::Error + Se
                     worker_threads
                 );
 
-                let executor = PhpExecutor::with_queue_capacity(worker_threads, queue_capacity)
-                    .map_err(|e| {
-                        eprintln!("Failed to initialize PHP: {}", e);
-                        e
-                    })?;
+                let executor =
+                    PhpExecutor::with_lanes(worker_threads, queue_capacity, priority_capacity)
+                        .unwrap_or_else(|e| exit_on_init_error("PHP", e))
+                        .with_max_request_extension(max_request_extension);
 
                 info!("PHP executor ready ({} workers)", executor.worker_count());
 
@@ -210,6 +380,13 @@ async fn async_main(config: Config) -> Result<(), Box<dyn std::error::Error + Se
     }
 }
 
+/// Report a PHP startup failure and exit with its specific code.
+#[cfg(feature = "php")]
+fn exit_on_init_error(executor: &str, err: tokio_php::executor::InitError) -> ! {
+    eprintln!("Failed to initialize {}: {}", executor, err);
+    std::process::exit(err.exit_code())
+}
+
 /// Wait for shutdown signal (SIGINT or SIGTERM).
 async fn shutdown_signal() {
     let ctrl_c = async {
@@ -235,41 +412,84 @@ async fn shutdown_signal() {
     }
 }
 
+/// SIGHUP listener for config reload (never fires on non-unix).
+struct ReloadSignal {
+    #[cfg(unix)]
+    inner: Option<tokio::signal::unix::Signal>,
+}
+
+impl ReloadSignal {
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(ref mut sig) = self.inner {
+            sig.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+fn reload_signal() -> ReloadSignal {
+    ReloadSignal {
+        #[cfg(unix)]
+        inner: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .map_err(|e| warn!("Failed to listen for SIGHUP: {}", e))
+            .ok(),
+    }
+}
+
 async fn run_server<E: tokio_php::executor::ScriptExecutor + 'static>(
     server: Server<E>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let drain_timeout = server.drain_timeout();
 
-    // Handle shutdown gracefully with tokio::select
-    tokio::select! {
-        result = server.run() => {
-            if let Err(e) = result {
-                eprintln!("Server error: {}", e);
+    let run = server.run();
+    tokio::pin!(run);
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut reload = reload_signal();
+
+    // Handle shutdown gracefully with tokio::select; SIGHUP reloads config in place
+    loop {
+        tokio::select! {
+            result = &mut run => {
+                if let Err(e) = result {
+                    eprintln!("Server error: {}", e);
+                }
+                break;
             }
-        }
-        _ = shutdown_signal() => {
-            info!("Received shutdown signal, initiating graceful shutdown...");
-
-            // Trigger shutdown - stops accept loops and signals all connections
-            // Each connection will receive the shutdown signal and send HTTP/2 GOAWAY
-            server.trigger_shutdown();
-
-            let active = server.active_connections();
-            if active > 0 {
-                info!(
-                    "Waiting up to {}s for {} active connections to complete (HTTP/2 GOAWAY sent)",
-                    drain_timeout.as_secs(),
-                    active
-                );
-
-                // Wait for connections to drain with timeout
-                if server.wait_for_drain(drain_timeout).await {
-                    info!("All connections drained successfully");
+            _ = reload.recv() => {
+                info!("Received SIGHUP, reloading configuration...");
+                match Config::from_env() {
+                    Ok(config) => server.reload(&config),
+                    Err(e) => warn!("Config reload failed, keeping current settings: {}", e),
+                }
+            }
+            _ = &mut shutdown => {
+                info!("Received shutdown signal, initiating graceful shutdown...");
+
+                // Trigger shutdown - stops accept loops and signals all connections
+                // Each connection will receive the shutdown signal and send HTTP/2 GOAWAY
+                server.trigger_shutdown();
+
+                let active = server.active_connections();
+                if active > 0 {
+                    info!(
+                        "Waiting up to {}s for {} active connections to complete (HTTP/2 GOAWAY sent)",
+                        drain_timeout.as_secs(),
+                        active
+                    );
+
+                    // Wait for connections to drain with timeout
+                    if server.wait_for_drain(drain_timeout).await {
+                        info!("All connections drained successfully");
+                    } else {
+                        info!("Drain timeout reached, forcing shutdown");
+                    }
                 } else {
-                    info!("Drain timeout reached, forcing shutdown");
+                    info!("No active connections, shutting down immediately");
                 }
-            } else {
-                info!("No active connections, shutting down immediately");
+                break;
             }
         }
     }
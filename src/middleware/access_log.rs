@@ -69,19 +69,25 @@ impl Middleware for AccessLogMiddleware {
 
     fn on_request(&self, req: Request, ctx: &mut Context) -> MiddlewareResult {
         // Store request info in context for logging in on_response
+        // (sensitive values are redacted up front, see LOG_REDACT)
+        let redactor = crate::logging::redactor();
         ctx.set("log_method", req.method().to_string());
         ctx.set("log_path", req.path().to_string());
         if let Some(query) = req.query() {
-            ctx.set("log_query", query.to_string());
+            ctx.set("log_query", redactor.query(query).into_owned());
         }
         if let Some(ua) = req.user_agent() {
-            ctx.set("log_ua", ua.to_string());
+            ctx.set("log_ua", redactor.header("user-agent", ua).to_string());
         }
         if let Some(referer) = req.header("referer") {
-            ctx.set("log_referer", referer.to_string());
+            let referer = redactor.header("referer", referer);
+            ctx.set("log_referer", redactor.url(referer).into_owned());
         }
         if let Some(xff) = req.header("x-forwarded-for") {
-            ctx.set("log_xff", xff.to_string());
+            ctx.set(
+                "log_xff",
+                redactor.header("x-forwarded-for", xff).to_string(),
+            );
         }
 
         MiddlewareResult::Next(req)
@@ -56,8 +56,19 @@ impl MiddlewareChain {
     ///
     /// Returns `MiddlewareResult::Next(req)` if all middleware passed,
     /// or `MiddlewareResult::Stop(res)` if any middleware short-circuited.
-    pub fn process_request(&self, mut req: Request, ctx: &mut Context) -> MiddlewareResult {
-        for mw in &self.middlewares {
+    pub fn process_request(&self, req: Request, ctx: &mut Context) -> MiddlewareResult {
+        self.process_request_entered(req, ctx).0
+    }
+
+    /// Like [`process_request`](Self::process_request), also returning how
+    /// many middleware saw the request (on a short-circuit, the one that
+    /// stopped it and those before it).
+    pub fn process_request_entered(
+        &self,
+        mut req: Request,
+        ctx: &mut Context,
+    ) -> (MiddlewareResult, usize) {
+        for (index, mw) in self.middlewares.iter().enumerate() {
             match mw.on_request(req, ctx) {
                 MiddlewareResult::Next(r) => req = r,
                 MiddlewareResult::Stop(res) => {
@@ -66,16 +77,30 @@ impl MiddlewareChain {
                         status = %res.status(),
                         "middleware short-circuited request"
                     );
-                    return MiddlewareResult::Stop(res);
+                    return (MiddlewareResult::Stop(res), index + 1);
                 }
             }
         }
-        MiddlewareResult::Next(req)
+        (MiddlewareResult::Next(req), self.middlewares.len())
     }
 
     /// Process a response through all middleware in reverse order.
-    pub fn process_response(&self, mut res: Response, ctx: &Context) -> Response {
-        for mw in self.middlewares.iter().rev() {
+    pub fn process_response(&self, res: Response, ctx: &Context) -> Response {
+        self.process_response_entered(res, ctx, self.middlewares.len())
+    }
+
+    /// Process a response through the first `entered` middleware only, in
+    /// reverse order (see [`process_request_entered`](Self::process_request_entered)).
+    pub fn process_response_entered(
+        &self,
+        mut res: Response,
+        ctx: &Context,
+        entered: usize,
+    ) -> Response {
+        for mw in self.middlewares[..entered.min(self.middlewares.len())]
+            .iter()
+            .rev()
+        {
             res = mw.on_response(res, ctx);
         }
         res
@@ -283,6 +308,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_short_circuit_response_skips_unentered() {
+        let before = Arc::new(CountingMiddleware::new("before", -100));
+        let after = Arc::new(CountingMiddleware::new("after", 0));
+        let chain = MiddlewareChain::new()
+            .with_arc(after.clone())
+            .with(BlockingMiddleware)
+            .with_arc(before.clone());
+
+        let mut ctx = create_test_context();
+        let (result, entered) = chain.process_request_entered(create_test_request(), &mut ctx);
+        assert!(result.is_stop());
+        assert_eq!(entered, 2);
+
+        chain.process_response_entered(Response::ok("blocked"), &ctx, entered);
+        assert_eq!(before.response_count.load(Ordering::SeqCst), 1);
+        assert_eq!(after.request_count.load(Ordering::SeqCst), 0);
+        assert_eq!(after.response_count.load(Ordering::SeqCst), 0);
+    }
+
     #[test]
     fn test_process_response() {
         struct HeaderMiddleware {
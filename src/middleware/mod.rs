@@ -38,6 +38,7 @@ pub mod access_log;
 pub mod compression;
 pub mod error_pages;
 pub mod rate_limit;
+pub mod request_id;
 pub mod static_cache;
 
 pub use chain::MiddlewareChain;
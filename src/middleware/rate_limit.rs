@@ -1,13 +1,14 @@
 //! Rate limiting middleware.
 //!
-//! Per-IP rate limiting using a fixed window algorithm.
+//! Per-IP rate limiting using a fixed window (default) or sliding log
+//! algorithm (RATE_LIMIT_ALGORITHM).
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::net::IpAddr;
-use std::sync::RwLock;
+use std::sync::{Mutex, RwLock};
 use std::time::{Duration, Instant};
 
-use crate::config::MiddlewareConfig;
+use crate::config::{MiddlewareConfig, RateLimitAlgorithm};
 use crate::core::{Context, Request, Response};
 
 use super::{Middleware, MiddlewareResult};
@@ -19,23 +20,66 @@ struct IpCounter {
     window_start: Instant,
 }
 
+/// Per-IP timestamps of allowed requests, as milliseconds since `epoch`.
+///
+/// Each log holds at most `limit` entries (rejected requests aren't
+/// recorded), 4 bytes each. Offsets wrap after ~49 days; ages are computed
+/// with wrapping arithmetic, so only the window length has to fit.
+#[derive(Debug)]
+struct SlidingLog {
+    logs: HashMap<IpAddr, VecDeque<u32>>,
+    epoch: Instant,
+    /// Last full pass dropping IPs with no requests inside the window.
+    last_sweep: Instant,
+}
+
+/// Counting state for the configured algorithm.
+enum Counters {
+    FixedWindow(RwLock<HashMap<IpAddr, IpCounter>>),
+    SlidingLog(Mutex<SlidingLog>),
+}
+
 /// Rate limiter state.
 pub struct RateLimiter {
-    counters: RwLock<HashMap<IpAddr, IpCounter>>,
+    counters: Counters,
     limit: u64,
     window: Duration,
 }
 
 impl RateLimiter {
-    /// Create a new rate limiter.
+    /// Create a new fixed window rate limiter.
     pub fn new(limit: u64, window_secs: u64) -> Self {
+        Self::with_algorithm(limit, window_secs, RateLimitAlgorithm::FixedWindow)
+    }
+
+    /// Create a rate limiter using the given algorithm.
+    pub fn with_algorithm(limit: u64, window_secs: u64, algorithm: RateLimitAlgorithm) -> Self {
+        let counters = match algorithm {
+            RateLimitAlgorithm::FixedWindow => Counters::FixedWindow(RwLock::new(HashMap::new())),
+            RateLimitAlgorithm::SlidingLog => {
+                let now = Instant::now();
+                Counters::SlidingLog(Mutex::new(SlidingLog {
+                    logs: HashMap::new(),
+                    epoch: now,
+                    last_sweep: now,
+                }))
+            }
+        };
         Self {
-            counters: RwLock::new(HashMap::new()),
+            counters,
             limit,
             window: Duration::from_secs(window_secs),
         }
     }
 
+    /// Get the counting algorithm.
+    pub fn algorithm(&self) -> RateLimitAlgorithm {
+        match self.counters {
+            Counters::FixedWindow(_) => RateLimitAlgorithm::FixedWindow,
+            Counters::SlidingLog(_) => RateLimitAlgorithm::SlidingLog,
+        }
+    }
+
     /// Get the rate limit value.
     pub fn limit(&self) -> u64 {
         self.limit
@@ -49,11 +93,25 @@ impl RateLimiter {
     /// Check if a request from the given IP is allowed.
     /// Returns (allowed, remaining, reset_after_secs).
     pub fn check(&self, ip: IpAddr) -> (bool, u64, u64) {
-        let now = Instant::now();
+        self.check_at(ip, Instant::now())
+    }
 
+    fn check_at(&self, ip: IpAddr, now: Instant) -> (bool, u64, u64) {
+        match &self.counters {
+            Counters::FixedWindow(counters) => self.check_fixed_window(counters, ip, now),
+            Counters::SlidingLog(log) => self.check_sliding_log(&mut log.lock().unwrap(), ip, now),
+        }
+    }
+
+    fn check_fixed_window(
+        &self,
+        counters: &RwLock<HashMap<IpAddr, IpCounter>>,
+        ip: IpAddr,
+        now: Instant,
+    ) -> (bool, u64, u64) {
         // Fast path: read lock to check existing counter
         {
-            let counters = self.counters.read().unwrap();
+            let counters = counters.read().unwrap();
             if let Some(counter) = counters.get(&ip) {
                 let elapsed = now.duration_since(counter.window_start);
                 if elapsed < self.window && counter.count >= self.limit {
@@ -64,7 +122,7 @@ impl RateLimiter {
         }
 
         // Slow path: write lock to update counter
-        let mut counters = self.counters.write().unwrap();
+        let mut counters = counters.write().unwrap();
         let counter = counters.entry(ip).or_insert(IpCounter {
             count: 0,
             window_start: now,
@@ -88,11 +146,58 @@ impl RateLimiter {
             (false, 0, reset_after)
         }
     }
+
+    /// Count allowed requests in the trailing window; reset is when the
+    /// oldest of them leaves it.
+    fn check_sliding_log(
+        &self,
+        state: &mut SlidingLog,
+        ip: IpAddr,
+        now: Instant,
+    ) -> (bool, u64, u64) {
+        let window_ms = self.window.as_millis().min(u32::MAX as u128) as u32;
+        let now_ms = now.duration_since(state.epoch).as_millis() as u32;
+
+        // Idle IPs are only seen here: drop their logs once per window
+        if now.duration_since(state.last_sweep) >= self.window {
+            state.logs.retain(|_, log| {
+                evict_expired(log, now_ms, window_ms);
+                !log.is_empty()
+            });
+            state.last_sweep = now;
+        }
+
+        let log = state.logs.entry(ip).or_default();
+        evict_expired(log, now_ms, window_ms);
+
+        let allowed = (log.len() as u64) < self.limit;
+        if allowed {
+            log.push_back(now_ms);
+        }
+        let oldest_age = log.front().map_or(0, |&ts| now_ms.wrapping_sub(ts));
+        let reset_after = (u64::from(window_ms - oldest_age.min(window_ms)) / 1000).max(1);
+        let remaining = self.limit.saturating_sub(log.len() as u64);
+
+        if log.is_empty() {
+            state.logs.remove(&ip); // Zero limit or window: nothing to remember
+        }
+        (allowed, remaining, reset_after)
+    }
+}
+
+/// Pop timestamps that have left the window (oldest first).
+fn evict_expired(log: &mut VecDeque<u32>, now_ms: u32, window_ms: u32) {
+    while log
+        .front()
+        .is_some_and(|&ts| now_ms.wrapping_sub(ts) >= window_ms)
+    {
+        log.pop_front();
+    }
 }
 
 /// Rate limiting middleware.
 ///
-/// Limits requests per IP address using a fixed window algorithm.
+/// Limits requests per IP address (fixed window unless configured otherwise).
 /// Returns 429 Too Many Requests when limit is exceeded.
 pub struct RateLimitMiddleware {
     limiter: RateLimiter,
@@ -102,8 +207,13 @@ pub struct RateLimitMiddleware {
 impl RateLimitMiddleware {
     /// Create a new rate limit middleware.
     pub fn new(limit: u64, window_secs: u64) -> Self {
+        Self::with_algorithm(limit, window_secs, RateLimitAlgorithm::FixedWindow)
+    }
+
+    /// Create a rate limit middleware using the given algorithm.
+    pub fn with_algorithm(limit: u64, window_secs: u64, algorithm: RateLimitAlgorithm) -> Self {
         Self {
-            limiter: RateLimiter::new(limit, window_secs),
+            limiter: RateLimiter::with_algorithm(limit, window_secs, algorithm),
             limit,
         }
     }
@@ -113,7 +223,7 @@ impl RateLimitMiddleware {
     pub fn from_config(config: &MiddlewareConfig) -> Option<Self> {
         config
             .rate_limit()
-            .map(|rl| Self::new(rl.limit(), rl.window_secs()))
+            .map(|rl| Self::with_algorithm(rl.limit(), rl.window_secs(), rl.algorithm()))
     }
 }
 
@@ -238,6 +348,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sliding_log_counts_trailing_window() {
+        let limiter = RateLimiter::with_algorithm(3, 10, RateLimitAlgorithm::SlidingLog);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+
+        assert_eq!(limiter.check_at(ip, at(0)), (true, 2, 10));
+        assert_eq!(limiter.check_at(ip, at(4)), (true, 1, 6));
+        assert_eq!(limiter.check_at(ip, at(8)), (true, 0, 2));
+        // A fixed window would have reset here at t=10; t=0 only leaves at t=10
+        assert_eq!(limiter.check_at(ip, at(9)), (false, 0, 1));
+        assert_eq!(limiter.check_at(ip, at(10)), (true, 0, 4));
+        assert!(!limiter.check_at(ip, at(13)).0);
+        assert!(limiter.check_at(ip, at(14)).0);
+    }
+
+    #[test]
+    fn test_sliding_log_prunes_idle_ips() {
+        let limiter = RateLimiter::with_algorithm(5, 10, RateLimitAlgorithm::SlidingLog);
+        let t0 = Instant::now();
+        for i in 0..100 {
+            limiter.check_at(IpAddr::V4(Ipv4Addr::new(10, 0, 0, i)), t0);
+        }
+        let Counters::SlidingLog(ref state) = limiter.counters else {
+            unreachable!()
+        };
+        assert_eq!(state.lock().unwrap().logs.len(), 100);
+
+        // One window later the next check sweeps every expired log
+        let other = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1));
+        limiter.check_at(other, t0 + Duration::from_secs(10));
+        let state = state.lock().unwrap();
+        assert_eq!(state.logs.len(), 1);
+        assert_eq!(state.logs[&other].len(), 1);
+    }
+
     #[test]
     fn test_sets_rate_limit_headers() {
         let mw = RateLimitMiddleware::new(10, 60);
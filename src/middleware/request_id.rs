@@ -0,0 +1,157 @@
+//! Request ID middleware.
+//!
+//! Reuses the client's `X-Request-Id` or generates a UUID, so client-side and
+//! server-side logs can be correlated on one id.
+
+use http::header::{HeaderName, HeaderValue};
+
+use crate::core::{Context, Request, Response};
+
+use super::{Middleware, MiddlewareResult};
+
+static X_REQUEST_ID: std::sync::LazyLock<HeaderName> =
+    std::sync::LazyLock::new(|| HeaderName::from_static("x-request-id"));
+
+/// Longest accepted incoming request ID.
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Check an incoming request ID before it is logged or echoed.
+///
+/// Accepts 1 to [`MAX_REQUEST_ID_LEN`] visible ASCII characters; spaces and
+/// control characters could forge or split log lines.
+#[inline]
+pub fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Keep a valid incoming `X-Request-Id`, or replace a missing or rejected
+/// one with `generate()` in `headers`, so PHP never sees the raw value.
+/// Returns the id in effect.
+pub fn ensure_request_id(
+    headers: &mut http::HeaderMap,
+    generate: impl FnOnce() -> String,
+) -> String {
+    if let Some(id) = headers
+        .get(&*X_REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+    {
+        return id.to_string();
+    }
+    let id = generate();
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        headers.insert(X_REQUEST_ID.clone(), value);
+    }
+    id
+}
+
+/// Request ID middleware.
+///
+/// Stores the id in `Context::request_id` (and so in the access log line),
+/// writes it back to the request's `X-Request-Id` header so PHP sees it as
+/// `$_SERVER['HTTP_X_REQUEST_ID']`, and echoes it on the response.
+#[derive(Default)]
+pub struct RequestIdMiddleware;
+
+impl RequestIdMiddleware {
+    /// Create a new request ID middleware.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Middleware for RequestIdMiddleware {
+    fn name(&self) -> &'static str {
+        "request_id"
+    }
+
+    fn priority(&self) -> i32 {
+        -95 // Before access logging; echoed on short-circuit responses too
+    }
+
+    fn on_request(&self, mut req: Request, ctx: &mut Context) -> MiddlewareResult {
+        ctx.request_id = ensure_request_id(req.headers_mut(), || uuid::Uuid::new_v4().to_string());
+        MiddlewareResult::Next(req)
+    }
+
+    fn on_response(&self, res: Response, ctx: &Context) -> Response {
+        match HeaderValue::from_str(&ctx.request_id) {
+            Ok(value) => res.with_header_value(X_REQUEST_ID.clone(), value),
+            Err(_) => res,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn create_context() -> Context {
+        Context::new(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            "0af7651916cd43dd8448eb211c80319c".to_string(),
+            "b7ad6b7169203331".to_string(),
+        )
+    }
+
+    fn create_request(request_id: Option<&str>) -> Request {
+        let mut headers = http::HeaderMap::new();
+        if let Some(id) = request_id {
+            headers.insert("x-request-id", id.parse().unwrap());
+        }
+
+        Request::new(
+            http::Method::GET,
+            "/".parse().unwrap(),
+            headers,
+            bytes::Bytes::new(),
+        )
+    }
+
+    #[test]
+    fn test_is_valid_request_id() {
+        assert!(is_valid_request_id("abc-123"));
+        assert!(is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN)));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+        assert!(!is_valid_request_id("a b"));
+        assert!(!is_valid_request_id("a\tb"));
+        assert!(!is_valid_request_id("a\nb"));
+    }
+
+    #[test]
+    fn test_echoes_incoming_id() {
+        let mw = RequestIdMiddleware::new();
+        let mut ctx = create_context();
+
+        let req = mw
+            .on_request(create_request(Some("client-42")), &mut ctx)
+            .into_request()
+            .unwrap();
+        assert_eq!(ctx.request_id, "client-42");
+        assert_eq!(req.request_id(), Some("client-42"));
+
+        let res = mw.on_response(Response::ok("ok"), &ctx);
+        assert_eq!(res.header("x-request-id"), Some("client-42"));
+    }
+
+    #[test]
+    fn test_generates_missing_or_invalid_id() {
+        let mw = RequestIdMiddleware::new();
+
+        for incoming in [None, Some("has\ttab"), Some(&*"x".repeat(200))] {
+            let mut ctx = create_context();
+            let req = mw
+                .on_request(create_request(incoming), &mut ctx)
+                .into_request()
+                .unwrap();
+
+            assert!(uuid::Uuid::parse_str(&ctx.request_id).is_ok());
+            assert_eq!(req.request_id(), Some(ctx.request_id.as_str()));
+
+            let res = mw.on_response(Response::ok("ok"), &ctx);
+            assert_eq!(res.header("x-request-id"), Some(ctx.request_id.as_str()));
+        }
+    }
+}
@@ -1,14 +1,19 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 use std::time::Instant;
 
 #[cfg(feature = "debug-profile")]
 use std::io::Write;
 
+use bytes::Bytes;
+use serde::Serialize;
+
 // Note: Profiling is now controlled by the `debug-profile` compile-time feature.
 // When enabled, single-worker mode is enforced and detailed reports are written
 // to /tmp/tokio_profile_request_{request_id}.md
 
 /// A skipped action with the reason why it was skipped.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SkippedAction {
     /// Name of the action that was skipped
     pub action: String,
@@ -26,7 +31,8 @@ impl SkippedAction {
 }
 
 /// Route type for the request
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RouteType {
     /// PHP script execution
     #[default]
@@ -63,7 +69,7 @@ impl RouteType {
 }
 
 /// Profile data for a single request
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ProfileData {
     // Total time
     pub total_us: u64,
@@ -484,6 +490,110 @@ impl ProfileData {
         headers
     }
 
+    /// All fields as one JSON object, for tooling (`X-Profile: json`, `/profile/last`).
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Phase timings in folded-stack format (`request;php;script_exec 2480`),
+    /// in microseconds, for `flamegraph.pl` and compatible tools.
+    ///
+    /// Nesting follows the markdown report; time not covered by any phase
+    /// stays on the parent frame.
+    pub fn to_folded_stacks(&self) -> String {
+        let php_total = self.php_startup_us
+            + self.superglobals_us
+            + self.memfd_setup_us
+            + self.script_exec_us
+            + self.output_capture_us
+            + self.php_shutdown_us;
+
+        let root = Frame::node(
+            "request",
+            self.total_us,
+            vec![
+                Frame::node(
+                    "middleware",
+                    self.rate_limit_us + self.middleware_req_us,
+                    vec![Frame::leaf("rate_limit", self.rate_limit_us)],
+                ),
+                Frame::node(
+                    "parse",
+                    self.parse_request_us,
+                    vec![
+                        Frame::leaf("headers", self.headers_extract_us),
+                        Frame::leaf("query", self.query_parse_us),
+                        Frame::leaf("cookies", self.cookies_parse_us),
+                        Frame::leaf("body_read", self.body_read_us),
+                        Frame::leaf("body_parse", self.body_parse_us),
+                        Frame::leaf("server_vars", self.server_vars_us),
+                        Frame::leaf("path_resolve", self.path_resolve_us),
+                        Frame::leaf("file_check", self.file_check_us),
+                    ],
+                ),
+                Frame::node(
+                    "queue_wait",
+                    self.queue_wait_us,
+                    vec![Frame::leaf("channel_send", self.channel_send_us)],
+                ),
+                Frame::node(
+                    "php",
+                    php_total,
+                    vec![
+                        Frame::leaf("startup", self.php_startup_us),
+                        Frame::node(
+                            "superglobals",
+                            self.superglobals_us,
+                            vec![
+                                Frame::leaf("ffi_clear", self.ffi_clear_us),
+                                Frame::leaf("server", self.ffi_server_us),
+                                Frame::leaf("get", self.ffi_get_us),
+                                Frame::leaf("post", self.ffi_post_us),
+                                Frame::leaf("cookie", self.ffi_cookie_us),
+                                Frame::leaf("files", self.ffi_files_us),
+                                Frame::leaf("build_request", self.ffi_build_request_us),
+                                Frame::leaf("init_eval", self.ffi_init_eval_us),
+                                Frame::leaf("build_code", self.superglobals_build_us),
+                                Frame::leaf("eval", self.superglobals_eval_us),
+                            ],
+                        ),
+                        Frame::leaf("memfd_setup", self.memfd_setup_us),
+                        Frame::leaf("script_exec", self.script_exec_us),
+                        Frame::node(
+                            "output_capture",
+                            self.output_capture_us,
+                            vec![
+                                Frame::leaf("finalize_eval", self.finalize_eval_us),
+                                Frame::leaf("stdout_restore", self.stdout_restore_us),
+                                Frame::leaf("output_read", self.output_read_us),
+                                Frame::leaf("output_parse", self.output_parse_us),
+                            ],
+                        ),
+                        Frame::leaf("shutdown", self.php_shutdown_us),
+                    ],
+                ),
+                Frame::node(
+                    "response_build",
+                    self.response_build_us,
+                    vec![Frame::leaf("compression", self.compression_us)],
+                ),
+                Frame::node(
+                    "middleware_response",
+                    self.middleware_resp_us,
+                    vec![
+                        Frame::leaf("static_cache", self.mw_static_cache_us),
+                        Frame::leaf("error_pages", self.mw_error_pages_us),
+                        Frame::leaf("access_log", self.mw_access_log_us),
+                    ],
+                ),
+            ],
+        );
+
+        let mut out = String::with_capacity(1024);
+        root.fold("", &mut out);
+        out
+    }
+
     /// Format as human-readable string (summary only)
     pub fn to_summary(&self) -> String {
         let tls_info = if self.tls_handshake_us > 0 {
@@ -959,6 +1069,251 @@ impl ProfileData {
     }
 }
 
+/// Most recent profile, served by the internal server at `/profile/last`.
+#[derive(Default)]
+pub struct LastProfile {
+    /// (JSON body, folded stacks)
+    last: Mutex<Option<(Bytes, Bytes)>>,
+}
+
+impl LastProfile {
+    pub fn record(&self, request_id: &str, profile: &ProfileData) {
+        let json = serde_json::json!({ "request_id": request_id, "profile": profile });
+        let recorded = (
+            Bytes::from(json.to_string()),
+            Bytes::from(profile.to_folded_stacks()),
+        );
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) = Some(recorded);
+    }
+
+    /// JSON body of the last recorded profile, if any request was profiled.
+    pub fn get(&self) -> Option<Bytes> {
+        let last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        last.as_ref().map(|(json, _)| json.clone())
+    }
+
+    /// The last recorded profile as folded stacks.
+    pub fn folded(&self) -> Option<Bytes> {
+        let last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        last.as_ref().map(|(_, folded)| folded.clone())
+    }
+}
+
+/// Phase name and the field it reads.
+type StatPhase = (&'static str, fn(&ProfileData) -> u64);
+
+/// Phases aggregated by [`ProfileStats`].
+const STAT_PHASES: [StatPhase; 11] = [
+    ("total", |p| p.total_us),
+    ("parse", |p| p.parse_request_us),
+    ("body_read", |p| p.body_read_us),
+    ("queue_wait", |p| p.queue_wait_us),
+    ("php_startup", |p| p.php_startup_us),
+    ("superglobals", |p| p.superglobals_us),
+    ("script_exec", |p| p.script_exec_us),
+    ("output_capture", |p| p.output_capture_us),
+    ("php_shutdown", |p| p.php_shutdown_us),
+    ("response_build", |p| p.response_build_us),
+    ("compression", |p| p.compression_us),
+];
+
+/// Sub-buckets per power of two: recorded values are within 1/16 (~6%).
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+/// Largest power of two tracked (2^40 µs is ~12 days); beyond is clamped.
+const MAX_EXPONENT: u32 = 40;
+const HISTOGRAM_BUCKETS: usize = SUB_BUCKETS * (MAX_EXPONENT - SUB_BUCKET_BITS + 2) as usize;
+
+/// Log-linear histogram of microsecond values with atomic counters,
+/// similar to an HDR histogram with one significant hex digit.
+struct Histogram {
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            buckets: (0..HISTOGRAM_BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, us: u64) {
+        self.buckets[bucket_index(us)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(us, Ordering::Relaxed);
+        self.max.fetch_max(us, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        for bucket in self.buckets.iter() {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum.store(0, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+    }
+
+    /// Values at each quantile (upper bound of the bucket, capped at the max).
+    fn quantiles<const N: usize>(&self, quantiles: [f64; N]) -> [u64; N] {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        let max = self.max.load(Ordering::Relaxed);
+
+        quantiles.map(|q| {
+            if total == 0 {
+                return 0;
+            }
+            let rank = ((q * total as f64).ceil() as u64).clamp(1, total);
+            let mut seen = 0;
+            for (index, &count) in counts.iter().enumerate() {
+                seen += count;
+                if seen >= rank {
+                    return bucket_upper(index).min(max);
+                }
+            }
+            max
+        })
+    }
+}
+
+fn bucket_index(us: u64) -> usize {
+    if us < SUB_BUCKETS as u64 {
+        return us as usize;
+    }
+    let exponent = 63 - us.leading_zeros();
+    if exponent > MAX_EXPONENT {
+        return HISTOGRAM_BUCKETS - 1;
+    }
+    let group = (exponent - SUB_BUCKET_BITS + 1) as usize;
+    let sub = (us >> (exponent - SUB_BUCKET_BITS)) as usize & (SUB_BUCKETS - 1);
+    group * SUB_BUCKETS + sub
+}
+
+/// Largest value that lands in bucket `index`.
+fn bucket_upper(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    let group = (index / SUB_BUCKETS) as u32;
+    let sub = (index % SUB_BUCKETS) as u64;
+    let shift = group - 1;
+    ((SUB_BUCKETS as u64 + sub + 1) << shift) - 1
+}
+
+/// Per-phase percentiles across profiled requests (PROFILE_STATS), served by
+/// the internal server at `/profile/stats`.
+///
+/// Recording is lock-free; a reset racing with requests may keep a few of
+/// their samples.
+pub struct ProfileStats {
+    phases: Vec<Histogram>,
+    since: Mutex<Instant>,
+}
+
+impl Default for ProfileStats {
+    fn default() -> Self {
+        Self {
+            phases: STAT_PHASES.iter().map(|_| Histogram::new()).collect(),
+            since: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl ProfileStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one request. Phases that didn't run (0 µs) are not counted.
+    pub fn record(&self, profile: &ProfileData) {
+        for ((_, value), histogram) in STAT_PHASES.iter().zip(&self.phases) {
+            let us = value(profile);
+            if us > 0 {
+                histogram.record(us);
+            }
+        }
+    }
+
+    pub fn reset(&self) {
+        for histogram in &self.phases {
+            histogram.reset();
+        }
+        *self.since.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    /// Count, mean, p50/p90/p95/p99 and max per phase, in microseconds.
+    pub fn to_json(&self) -> serde_json::Value {
+        let since = self.since.lock().unwrap_or_else(|e| e.into_inner());
+        let mut phases = serde_json::Map::new();
+        for ((name, _), histogram) in STAT_PHASES.iter().zip(&self.phases) {
+            let count = histogram.count.load(Ordering::Relaxed);
+            let sum = histogram.sum.load(Ordering::Relaxed);
+            let [p50, p90, p95, p99] = histogram.quantiles([0.5, 0.9, 0.95, 0.99]);
+            phases.insert(
+                name.to_string(),
+                serde_json::json!({
+                    "count": count,
+                    "mean_us": sum.checked_div(count).unwrap_or(0),
+                    "p50_us": p50,
+                    "p90_us": p90,
+                    "p95_us": p95,
+                    "p99_us": p99,
+                    "max_us": histogram.max.load(Ordering::Relaxed),
+                }),
+            );
+        }
+        serde_json::json!({
+            "window_secs": since.elapsed().as_secs(),
+            "phases": phases,
+        })
+    }
+}
+
+/// Phase node for folded-stack output; `us` includes the children.
+struct Frame {
+    name: &'static str,
+    us: u64,
+    children: Vec<Frame>,
+}
+
+impl Frame {
+    fn leaf(name: &'static str, us: u64) -> Self {
+        Self::node(name, us, Vec::new())
+    }
+
+    fn node(name: &'static str, us: u64, children: Vec<Frame>) -> Self {
+        Self { name, us, children }
+    }
+
+    /// One line per frame with self time (total minus children); zero self
+    /// time is left out, as flamegraph.pl would draw nothing for it.
+    fn fold(&self, parent: &str, out: &mut String) {
+        let path = if parent.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{};{}", parent, self.name)
+        };
+        let children_us: u64 = self.children.iter().map(|c| c.us).sum();
+        let self_us = self.us.saturating_sub(children_us);
+        if self_us > 0 {
+            out.push_str(&format!("{} {}\n", path, self_us));
+        }
+        for child in &self.children {
+            child.fold(&path, out);
+        }
+    }
+}
+
 /// Timer helper for measuring phases
 pub struct Timer {
     start: Instant,
@@ -996,3 +1351,98 @@ impl Default for Timer {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_profile_json() {
+        let last = LastProfile::default();
+        assert!(last.get().is_none());
+
+        let profile = ProfileData {
+            total_us: 1200,
+            route_type: RouteType::IndexRedirect,
+            ..Default::default()
+        };
+        last.record("req-1", &profile);
+
+        let body: serde_json::Value = serde_json::from_slice(&last.get().unwrap()).unwrap();
+        assert_eq!(body["request_id"], "req-1");
+        assert_eq!(body["profile"]["total_us"], 1200);
+        assert_eq!(body["profile"]["route_type"], "index_redirect");
+
+        let json: serde_json::Value = serde_json::from_str(&profile.to_json()).unwrap();
+        assert_eq!(json, body["profile"]);
+        assert_eq!(&last.folded().unwrap()[..], b"request 1200\n");
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        for us in [0, 1, 15, 16, 17, 31, 32, 33, 1000, 123_456, 1 << 40] {
+            let index = bucket_index(us);
+            assert!(bucket_upper(index) >= us, "{us} above its bucket");
+            // Within 1/16 of the value
+            assert!(bucket_upper(index) - us <= us / 16, "{us} bucket too wide");
+            if index > 0 {
+                assert!(bucket_upper(index - 1) < us, "{us} in too high a bucket");
+            }
+        }
+        assert_eq!(bucket_index(u64::MAX), HISTOGRAM_BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_profile_stats_percentiles() {
+        let stats = ProfileStats::new();
+        for us in 1..=1000 {
+            stats.record(&ProfileData {
+                total_us: us,
+                script_exec_us: 500,
+                ..Default::default()
+            });
+        }
+
+        let json = stats.to_json();
+        let total = &json["phases"]["total"];
+        assert_eq!(total["count"], 1000);
+        assert_eq!(total["max_us"], 1000);
+        assert_eq!(total["mean_us"], 500);
+        let p50 = total["p50_us"].as_u64().unwrap();
+        let p99 = total["p99_us"].as_u64().unwrap();
+        assert!((500..=532).contains(&p50), "p50 = {p50}");
+        assert!((990..=1000).contains(&p99), "p99 = {p99}");
+        assert_eq!(json["phases"]["script_exec"]["p95_us"], 500);
+        // Phases that never ran have no samples
+        assert_eq!(json["phases"]["compression"]["count"], 0);
+
+        stats.reset();
+        assert_eq!(stats.to_json()["phases"]["total"]["count"], 0);
+    }
+
+    #[test]
+    fn test_folded_stacks_self_time() {
+        let profile = ProfileData {
+            total_us: 1000,
+            parse_request_us: 100,
+            headers_extract_us: 30,
+            php_startup_us: 50,
+            superglobals_us: 40,
+            ffi_server_us: 25,
+            script_exec_us: 600,
+            php_shutdown_us: 60,
+            ..Default::default()
+        };
+        assert_eq!(
+            profile.to_folded_stacks(),
+            "request 150\n\
+             request;parse 70\n\
+             request;parse;headers 30\n\
+             request;php;startup 50\n\
+             request;php;superglobals 15\n\
+             request;php;superglobals;server 25\n\
+             request;php;script_exec 600\n\
+             request;php;shutdown 60\n"
+        );
+    }
+}
@@ -3,6 +3,47 @@
 // Note: Global state has been moved to config::MiddlewareConfig.access_log.
 // The access_log_enabled flag is now passed via ConnectionContext.
 
+/// Warn about a request that exceeded SLOW_REQUEST_THRESHOLD_MS.
+///
+/// `queue_wait_us` is the time spent waiting for a PHP worker, known only
+/// when profiling data was collected.
+#[allow(clippy::too_many_arguments)]
+pub fn log_slow_request(
+    request_id: &str,
+    trace_id: &str,
+    span_id: &str,
+    method: &str,
+    path: &str,
+    status: u16,
+    duration_us: u64,
+    queue_wait_us: Option<u64>,
+) {
+    let duration_ms = duration_us as f64 / 1000.0;
+    match queue_wait_us {
+        Some(wait_us) => tracing::warn!(
+            request_id,
+            trace_id,
+            span_id,
+            method,
+            path,
+            status,
+            duration_ms,
+            queue_wait_ms = wait_us as f64 / 1000.0,
+            "Slow request"
+        ),
+        None => tracing::warn!(
+            request_id,
+            trace_id,
+            span_id,
+            method,
+            path,
+            status,
+            duration_ms,
+            "Slow request"
+        ),
+    }
+}
+
 /// Log an HTTP request using the unified log format.
 #[allow(clippy::too_many_arguments)]
 pub fn log_request(
@@ -0,0 +1,81 @@
+//! Self-signed certificate for `TLS_MODE=auto` (local development).
+//!
+//! Generates an ECDSA P-256 key and a certificate for `localhost`,
+//! `127.0.0.1` and `::1` in memory at startup, so HTTPS and HTTP/2 can be
+//! tried without running `openssl`. Clients don't trust it.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rcgen::{date_time_ymd, CertificateParams, DistinguishedName, DnType, KeyPair};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+
+/// Common name of the generated certificate (subject and issuer).
+const COMMON_NAME: &str = "tokio_php self-signed";
+/// Names the certificate is valid for.
+const SUBJECT_ALT_NAMES: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
+/// Validity starts a day early to tolerate clock skew.
+const BACKDATE_SECS: u64 = 86_400;
+const VALIDITY_SECS: u64 = 365 * 86_400;
+
+/// Generates self-signed certificates for the main HTTPS listener.
+#[derive(Debug, Default)]
+pub struct AutoCertGenerator;
+
+impl AutoCertGenerator {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// New key pair and certificate valid for a year.
+    pub fn generate(&self) -> Result<(CertificateDer<'static>, PrivateKeyDer<'static>), String> {
+        let key_pair =
+            KeyPair::generate().map_err(|e| format!("failed to generate key pair: {}", e))?;
+
+        let mut params = CertificateParams::new(SUBJECT_ALT_NAMES.map(String::from))
+            .map_err(|e| e.to_string())?;
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, COMMON_NAME);
+        params.distinguished_name = name;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs();
+        let epoch = date_time_ymd(1970, 1, 1);
+        params.not_before = epoch + Duration::from_secs(now.saturating_sub(BACKDATE_SECS));
+        params.not_after = epoch + Duration::from_secs(now + VALIDITY_SECS);
+
+        let cert = params
+            .self_signed(&key_pair)
+            .map_err(|e| format!("failed to sign certificate: {}", e))?;
+
+        Ok((
+            cert.der().clone(),
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::misdirected::CertHosts;
+    use tokio_rustls::rustls::ServerConfig as RustlsConfig;
+
+    #[test]
+    fn test_generated_cert() {
+        let (cert, key) = AutoCertGenerator::new().generate().unwrap();
+
+        let hosts = CertHosts::new(cert.clone()).unwrap();
+        assert!(hosts.covers("localhost"));
+        assert!(hosts.covers("127.0.0.1"));
+        assert!(hosts.covers("[::1]"));
+        assert!(!hosts.covers("example.com"));
+
+        // rustls accepts the pair as-is
+        RustlsConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert], key)
+            .unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+//! `Link: <url>; rel="canonical"` headers per path (`CANONICAL_LINKS`).
+//!
+//! Lets the canonical URL of static pages and simple routes be declared in
+//! one place instead of in each document's `<link rel="canonical">`.
+
+use std::collections::HashMap;
+
+use hyper::header::HeaderValue;
+
+/// Exact URI path -> prebuilt `Link` header value.
+#[derive(Clone, Debug, Default)]
+pub struct CanonicalLinks {
+    links: HashMap<Box<str>, HeaderValue>,
+}
+
+impl CanonicalLinks {
+    /// Parse entries like `/about=https://example.com/about`.
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> Result<Self, String> {
+        let mut links = HashMap::with_capacity(entries.len());
+        for entry in entries {
+            let entry = entry.as_ref();
+            let (path, url) = entry
+                .split_once('=')
+                .map(|(p, u)| (p.trim(), u.trim()))
+                .ok_or_else(|| format!("expected path=url, got '{entry}'"))?;
+            if !path.starts_with('/') {
+                return Err(format!("path must start with '/' in '{entry}'"));
+            }
+            if !(url.starts_with("https://") || url.starts_with("http://"))
+                || url.contains(['<', '>', ' '])
+            {
+                return Err(format!("invalid canonical URL in '{entry}'"));
+            }
+            let value = HeaderValue::from_str(&format!("<{url}>; rel=\"canonical\""))
+                .map_err(|_| format!("invalid canonical URL in '{entry}'"))?;
+            links.insert(path.into(), value);
+        }
+        Ok(Self { links })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// `Link` header value for a request path, if one is configured.
+    pub fn get(&self, uri_path: &str) -> Option<&HeaderValue> {
+        self.links.get(uri_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_path() {
+        let links = CanonicalLinks::parse(&[
+            "/about=https://example.com/about",
+            "/index.html = https://example.com/",
+        ])
+        .unwrap();
+        assert_eq!(
+            links.get("/about").unwrap(),
+            "<https://example.com/about>; rel=\"canonical\""
+        );
+        assert_eq!(
+            links.get("/index.html").unwrap(),
+            "<https://example.com/>; rel=\"canonical\""
+        );
+        // Exact match only
+        assert!(links.get("/about/").is_none());
+        assert!(links.get("/contact").is_none());
+    }
+
+    #[test]
+    fn test_query_in_url() {
+        let links = CanonicalLinks::parse(&["/p=https://example.com/p?id=1"]).unwrap();
+        assert_eq!(
+            links.get("/p").unwrap(),
+            "<https://example.com/p?id=1>; rel=\"canonical\""
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(CanonicalLinks::parse(&["/about"]).is_err());
+        assert!(CanonicalLinks::parse(&["about=https://example.com/"]).is_err());
+        assert!(CanonicalLinks::parse(&["/a=example.com/a"]).is_err());
+        assert!(CanonicalLinks::parse(&["/a=https://example.com/>; rel=x"]).is_err());
+        assert!(CanonicalLinks::default().is_empty());
+    }
+}
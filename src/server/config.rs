@@ -22,7 +22,52 @@ use std::sync::Arc;
 use std::time::Duration;
 
 // Re-export unified types from config module
-pub use crate::config::{OptionalDuration, RequestTimeout, StaticCacheTtl};
+pub use crate::config::{
+    Http2Settings, MemoryPressureSettings, OptionalDuration, RequestTimeout, Sizing,
+    StaticCacheTtl, TimingAllowOrigin,
+};
+
+use super::canonical::CanonicalLinks;
+use super::csp::CspNonce;
+use super::internal_access::InternalAccess;
+use super::methods::AllowedMethods;
+use super::request::UploadTypes;
+use super::response::{
+    ImmutableAssets, MimeTypes, ResponseSettings, UaVariants, DEFAULT_COMPRESSED_CACHE_SIZE,
+};
+use super::route_label::RouteLabels;
+use super::routing::{DirectoryRequest, ProxyRoutes};
+use super::trusted_proxy::TrustedProxies;
+use crate::middleware::rate_limit::RateLimiter;
+use crate::types::OutputLimits;
+
+/// Settings that can be swapped at runtime (SIGHUP) without restarting.
+///
+/// Connections hold a `watch::Receiver<Arc<ReloadableConfig>>` and take one
+/// snapshot per request, so in-flight requests finish with consistent values.
+#[derive(Clone)]
+pub struct ReloadableConfig {
+    /// Per-IP rate limiter (None if RATE_LIMIT=0).
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Access logging enabled (ACCESS_LOG=1).
+    pub access_log_enabled: bool,
+    /// Minimum response status to log (ACCESS_LOG_ERRORS_ONLY, 0 = all).
+    pub access_log_min_status: u16,
+    /// Cache-Control max-age for static files (STATIC_CACHE_TTL).
+    pub static_cache_ttl: StaticCacheTtl,
+    /// Brotli, compressible types and default Content-Type.
+    pub response: ResponseSettings,
+    /// Streaming buffer and background-time limits for PHP requests.
+    pub output_limits: OutputLimits,
+}
+
+impl ReloadableConfig {
+    /// Whether a response with this status gets an access log entry.
+    #[inline]
+    pub fn should_log_access(&self, status: u16) -> bool {
+        self.access_log_enabled && status >= self.access_log_min_status
+    }
+}
 
 /// TLS connection information for profiling
 #[derive(Clone, Default)]
@@ -30,6 +75,8 @@ pub struct TlsInfo {
     pub handshake_us: u64,
     pub protocol: String,
     pub alpn: String,
+    /// SNI hostname sent by the client (empty if none).
+    pub sni: String,
 }
 
 /// Server configuration.
@@ -56,6 +103,7 @@ pub struct TlsInfo {
 /// | `TLS_CERT` | _(empty)_ | TLS certificate path |
 /// | `TLS_KEY` | _(empty)_ | TLS private key path |
 /// | `DRAIN_TIMEOUT_SECS` | `30` | Graceful shutdown timeout |
+/// | `GOAWAY_WINDOW_SECS` | `30` | In-flight window after GOAWAY |
 #[derive(Clone, Debug)]
 pub struct ServerConfig {
     pub addr: SocketAddr,
@@ -66,24 +114,118 @@ pub struct ServerConfig {
     pub tls_cert: Option<String>,
     /// TLS private key file path (PEM format)
     pub tls_key: Option<String>,
+    /// Self-signed certificate generated at startup instead of files (default: off)
+    pub tls_auto: bool,
     /// Index file for single entry point mode (e.g., "index.php")
     pub index_file: Option<String>,
     /// Internal server address for /health and /metrics
     pub internal_addr: Option<SocketAddr>,
+    /// Token / network checks on the internal server (default: open)
+    pub internal_access: InternalAccess,
     /// Directory with custom error pages ({status_code}.html)
     pub error_pages_dir: Option<String>,
     /// Graceful shutdown drain timeout
     pub drain_timeout: Duration,
+    /// Per-connection window for in-flight streams after GOAWAY (default: 30s)
+    pub goaway_window: Duration,
     /// Static file cache TTL (default: 1d, "off" to disable)
     pub static_cache_ttl: StaticCacheTtl,
     /// Request timeout (default: 2m, "off" to disable)
     pub request_timeout: RequestTimeout,
+    /// Estimated queue wait that gets a request rejected with 503
+    /// (default: off, capped at the request timeout)
+    pub max_queue_wait: OptionalDuration,
     /// SSE timeout (default: 30m, "off" to disable)
     pub sse_timeout: RequestTimeout,
     /// Header read timeout (default: 5s, Slowloris protection)
     pub header_timeout: Duration,
     /// Idle connection timeout (default: 60s)
     pub idle_timeout: Duration,
+    /// Close connections with no request in flight for this long
+    /// (default: 75s, zero = off)
+    pub keepalive_timeout: Duration,
+    /// TLS handshake timeout (default: 10s)
+    pub tls_handshake_timeout: Duration,
+    /// Slow request log threshold (default: 5s, zero = off)
+    pub slow_request_threshold: Duration,
+    /// PHP queue capacity that readiness is measured against (default: 0 = check off)
+    pub queue_capacity: usize,
+    /// Queue fill percent that fails /ready once sustained (default: 90)
+    pub ready_queue_threshold: u8,
+    /// How long the queue must stay saturated (default: 10s)
+    pub ready_saturation_window: Duration,
+    /// HTTP/2 streams, windows and frame size (default: 250 streams, 1 MiB windows, 16 KiB frames)
+    pub http2: Http2Settings,
+    /// Worker/queue sizing decision exported on /metrics (default: None)
+    pub sizing: Option<Sizing>,
+    /// Path prefixes disabled at startup (kill-switch)
+    pub disabled_paths: Vec<String>,
+    /// Status returned for disabled paths (default: 503)
+    pub disabled_path_status: u16,
+    /// Memory pressure shedding thresholds and paths (default: off)
+    pub memory_pressure: MemoryPressureSettings,
+    /// Only access-log responses with status >= this (default: 0 = all)
+    pub access_log_min_status: u16,
+    /// Max query/form/cookie variables parsed per request (default: 1000)
+    pub max_input_vars: usize,
+    /// Max bytes reserved up front for a request body (default: 8 MiB)
+    pub body_prealloc_max: usize,
+    /// Largest body kept for php://input (default: 0 = unlimited)
+    pub raw_body_max: usize,
+    /// Largest request body accepted, else 413 (default: 0 = unlimited)
+    pub max_body_size: usize,
+    /// Multipart file types accepted, by declared and sniffed type (default: any)
+    pub upload_types: UploadTypes,
+    /// Set $_SERVER['HTTP_B3'] for downstream B3 propagation (default: off)
+    pub trace_b3_egress: bool,
+    /// 421 Misdirected Request when the certificate doesn't cover the host (default: off)
+    pub tls_check_authority: bool,
+    /// Script error details in 500 response bodies (default: off)
+    pub expose_errors: bool,
+    /// `Server` response header, None to omit it (default: tokio_php/<version>)
+    pub server_header: Option<String>,
+    /// Proxies whose forwarding headers are honored (default: none)
+    pub trusted_proxies: TrustedProxies,
+    /// Max concurrent connections across all workers (default: 0 = unlimited)
+    pub max_connections: usize,
+    /// Max concurrent connections per accept worker (default: 0 = unlimited)
+    pub max_connections_per_worker: usize,
+    /// Max concurrent connections per client IP (default: 0 = unlimited)
+    pub max_connections_per_ip: usize,
+    /// Timing-Allow-Origin for static/PHP responses (default: none)
+    pub timing_allow_origin: Option<TimingAllowOrigin>,
+    /// URI prefixes where directories without an index are listed (default: none)
+    pub autoindex: Vec<String>,
+    /// Handling of directory URIs without a trailing slash (default: 404)
+    pub directory_request: DirectoryRequest,
+    /// URI prefixes sent to the high-priority worker lane (default: none)
+    pub priority_paths: Vec<String>,
+    /// Per-request CSP nonce for PHP (default: off)
+    pub csp_nonce: Option<CspNonce>,
+    /// Static file Content-Type overrides (default: mjs, wasm, webmanifest)
+    pub mime_types: MimeTypes,
+    /// Memory for Brotli-compressed static files in bytes (default: 32 MiB, 0 = off)
+    pub static_compress_cache_size: usize,
+    /// Fingerprinted asset patterns cached for a year as immutable (default: none)
+    pub immutable_assets: ImmutableAssets,
+    /// Device classes served `name.<class>.ext` static variants (default: none)
+    pub ua_variants: UaVariants,
+    /// Paths answered with a `Link: rel="canonical"` header (default: none)
+    pub canonical_links: CanonicalLinks,
+    /// Route templates for path-labeled metrics (default: none, metric off)
+    pub route_labels: RouteLabels,
+    /// URI prefixes proxied to HTTP upstreams instead of PHP (default: none)
+    pub proxy_routes: ProxyRoutes,
+    /// Idle connections kept per proxy upstream (default: 8)
+    pub proxy_pool_size: usize,
+    /// Methods passed to PHP (default: GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS, QUERY)
+    pub allowed_methods: AllowedMethods,
+    /// Aggregate profiled requests for /profile/stats (default: false)
+    pub profile_stats: bool,
+    /// Brotli, compressible types and default Content-Type (default: quality 4, text/html)
+    pub response: ResponseSettings,
+    /// Streaming buffer and background-time limits (default: 100 chunks, 30s, unlimited)
+    pub output_limits: OutputLimits,
 }
 
 impl ServerConfig {
@@ -94,15 +236,61 @@ impl ServerConfig {
             num_workers: 0,
             tls_cert: None,
             tls_key: None,
+            tls_auto: false,
             index_file: None,
             internal_addr: None,
+            internal_access: InternalAccess::default(),
             error_pages_dir: None,
             drain_timeout: Duration::from_secs(30),
+            goaway_window: Duration::from_secs(30),
             static_cache_ttl: OptionalDuration::from_secs(86400), // 1 day
             request_timeout: OptionalDuration::from_secs(120),    // 2 minutes
             sse_timeout: OptionalDuration::from_secs(1800),       // 30 minutes
             header_timeout: Duration::from_secs(5),               // 5 seconds
             idle_timeout: Duration::from_secs(60),                // 60 seconds
+            keepalive_timeout: Duration::from_secs(75),           // 75 seconds
+            tls_handshake_timeout: Duration::from_secs(10),
+            slow_request_threshold: Duration::from_secs(5),
+            max_queue_wait: OptionalDuration::DISABLED,
+            queue_capacity: 0,
+            ready_queue_threshold: 90,
+            ready_saturation_window: Duration::from_secs(10),
+            http2: Http2Settings::default(),
+            sizing: None,
+            disabled_paths: Vec::new(),
+            disabled_path_status: 503,
+            memory_pressure: MemoryPressureSettings::default(),
+            access_log_min_status: 0,
+            max_input_vars: 1000,
+            body_prealloc_max: 8 * 1024 * 1024,
+            raw_body_max: 0,
+            max_body_size: 0,
+            upload_types: UploadTypes::default(),
+            trace_b3_egress: false,
+            tls_check_authority: false,
+            expose_errors: false,
+            server_header: Some(super::response::DEFAULT_SERVER_HEADER.to_string()),
+            trusted_proxies: TrustedProxies::default(),
+            max_connections: 0,
+            max_connections_per_worker: 0,
+            max_connections_per_ip: 0,
+            timing_allow_origin: None,
+            autoindex: Vec::new(),
+            priority_paths: Vec::new(),
+            directory_request: DirectoryRequest::NotFound,
+            csp_nonce: None,
+            mime_types: MimeTypes::default(),
+            static_compress_cache_size: DEFAULT_COMPRESSED_CACHE_SIZE,
+            immutable_assets: ImmutableAssets::default(),
+            ua_variants: UaVariants::default(),
+            canonical_links: CanonicalLinks::default(),
+            route_labels: RouteLabels::default(),
+            proxy_routes: ProxyRoutes::default(),
+            proxy_pool_size: 8,
+            allowed_methods: AllowedMethods::default(),
+            profile_stats: false,
+            response: ResponseSettings::default(),
+            output_limits: OutputLimits::default(),
         }
     }
 
@@ -122,6 +310,11 @@ impl ServerConfig {
         self
     }
 
+    pub fn with_tls_auto(mut self, enabled: bool) -> Self {
+        self.tls_auto = enabled;
+        self
+    }
+
     pub fn with_index_file(mut self, index_file: String) -> Self {
         self.index_file = Some(index_file);
         self
@@ -132,6 +325,11 @@ impl ServerConfig {
         self
     }
 
+    pub fn with_internal_access(mut self, access: InternalAccess) -> Self {
+        self.internal_access = access;
+        self
+    }
+
     pub fn with_error_pages_dir(mut self, dir: String) -> Self {
         self.error_pages_dir = Some(dir);
         self
@@ -142,6 +340,11 @@ impl ServerConfig {
         self
     }
 
+    pub fn with_goaway_window(mut self, window: Duration) -> Self {
+        self.goaway_window = window;
+        self
+    }
+
     pub fn with_static_cache_ttl(mut self, ttl: StaticCacheTtl) -> Self {
         self.static_cache_ttl = ttl;
         self
@@ -152,6 +355,22 @@ impl ServerConfig {
         self
     }
 
+    pub fn with_max_queue_wait(mut self, wait: OptionalDuration) -> Self {
+        self.max_queue_wait = wait;
+        self
+    }
+
+    /// Queue wait limit in effect: `max_queue_wait`, never above the
+    /// request timeout.
+    pub fn effective_max_queue_wait(&self) -> Option<Duration> {
+        let wait = self.max_queue_wait.as_duration()?;
+        Some(
+            self.request_timeout
+                .as_duration()
+                .map_or(wait, |timeout| wait.min(timeout)),
+        )
+    }
+
     pub fn with_sse_timeout(mut self, timeout: RequestTimeout) -> Self {
         self.sse_timeout = timeout;
         self
@@ -167,7 +386,251 @@ impl ServerConfig {
         self
     }
 
+    pub fn with_keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive_timeout = timeout;
+        self
+    }
+
+    pub fn with_tls_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.tls_handshake_timeout = timeout;
+        self
+    }
+
+    pub fn with_slow_request_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_request_threshold = threshold;
+        self
+    }
+
+    pub fn with_readiness(
+        mut self,
+        queue_capacity: usize,
+        threshold_percent: u8,
+        window: Duration,
+    ) -> Self {
+        self.queue_capacity = queue_capacity;
+        self.ready_queue_threshold = threshold_percent;
+        self.ready_saturation_window = window;
+        self
+    }
+
+    pub fn with_http2(mut self, settings: Http2Settings) -> Self {
+        self.http2 = settings;
+        self
+    }
+
+    pub fn with_sizing(mut self, sizing: Sizing) -> Self {
+        self.sizing = Some(sizing);
+        self
+    }
+
+    pub fn with_disabled_paths(mut self, paths: Vec<String>, status: u16) -> Self {
+        self.disabled_paths = paths;
+        self.disabled_path_status = status;
+        self
+    }
+
+    pub fn with_memory_pressure(mut self, settings: MemoryPressureSettings) -> Self {
+        self.memory_pressure = settings;
+        self
+    }
+
+    pub fn with_access_log_min_status(mut self, status: u16) -> Self {
+        self.access_log_min_status = status;
+        self
+    }
+
+    pub fn with_response_settings(mut self, settings: ResponseSettings) -> Self {
+        self.response = settings;
+        self
+    }
+
+    pub fn with_output_limits(mut self, limits: OutputLimits) -> Self {
+        self.output_limits = limits;
+        self
+    }
+
+    pub fn with_max_input_vars(mut self, max: usize) -> Self {
+        self.max_input_vars = max;
+        self
+    }
+
+    pub fn with_body_prealloc_max(mut self, max: usize) -> Self {
+        self.body_prealloc_max = max;
+        self
+    }
+
+    pub fn with_raw_body_max(mut self, max: usize) -> Self {
+        self.raw_body_max = max;
+        self
+    }
+
+    pub fn with_max_body_size(mut self, max: usize) -> Self {
+        self.max_body_size = max;
+        self
+    }
+
+    pub fn with_trace_b3_egress(mut self, enabled: bool) -> Self {
+        self.trace_b3_egress = enabled;
+        self
+    }
+
+    pub fn with_tls_check_authority(mut self, enabled: bool) -> Self {
+        self.tls_check_authority = enabled;
+        self
+    }
+
+    pub fn with_expose_errors(mut self, enabled: bool) -> Self {
+        self.expose_errors = enabled;
+        self
+    }
+
+    pub fn with_server_header(mut self, value: Option<String>) -> Self {
+        self.server_header = value;
+        self
+    }
+
+    pub fn with_trusted_proxies(mut self, proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = proxies;
+        self
+    }
+
+    pub fn with_upload_types(mut self, types: UploadTypes) -> Self {
+        self.upload_types = types;
+        self
+    }
+
+    pub fn with_max_connections(mut self, max: usize) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    pub fn with_max_connections_per_worker(mut self, max: usize) -> Self {
+        self.max_connections_per_worker = max;
+        self
+    }
+
+    pub fn with_max_connections_per_ip(mut self, max: usize) -> Self {
+        self.max_connections_per_ip = max;
+        self
+    }
+
+    pub fn with_autoindex(mut self, prefixes: Vec<String>) -> Self {
+        self.autoindex = prefixes;
+        self
+    }
+
+    pub fn with_priority_paths(mut self, prefixes: Vec<String>) -> Self {
+        self.priority_paths = prefixes;
+        self
+    }
+
+    pub fn with_directory_request(mut self, mode: DirectoryRequest) -> Self {
+        self.directory_request = mode;
+        self
+    }
+
+    pub fn with_csp_nonce(mut self, csp: CspNonce) -> Self {
+        self.csp_nonce = Some(csp);
+        self
+    }
+
+    pub fn with_mime_types(mut self, types: MimeTypes) -> Self {
+        self.mime_types = types;
+        self
+    }
+
+    pub fn with_static_compress_cache_size(mut self, bytes: usize) -> Self {
+        self.static_compress_cache_size = bytes;
+        self
+    }
+
+    pub fn with_immutable_assets(mut self, assets: ImmutableAssets) -> Self {
+        self.immutable_assets = assets;
+        self
+    }
+
+    pub fn with_ua_variants(mut self, variants: UaVariants) -> Self {
+        self.ua_variants = variants;
+        self
+    }
+
+    pub fn with_canonical_links(mut self, links: CanonicalLinks) -> Self {
+        self.canonical_links = links;
+        self
+    }
+
+    pub fn with_route_labels(mut self, routes: RouteLabels) -> Self {
+        self.route_labels = routes;
+        self
+    }
+
+    pub fn with_proxy_routes(mut self, routes: ProxyRoutes, pool_size: usize) -> Self {
+        self.proxy_routes = routes;
+        self.proxy_pool_size = pool_size;
+        self
+    }
+
+    pub fn with_allowed_methods(mut self, methods: AllowedMethods) -> Self {
+        self.allowed_methods = methods;
+        self
+    }
+
+    pub fn with_profile_stats(mut self, enabled: bool) -> Self {
+        self.profile_stats = enabled;
+        self
+    }
+
+    pub fn with_timing_allow_origin(mut self, tao: TimingAllowOrigin) -> Self {
+        self.timing_allow_origin = Some(tao);
+        self
+    }
+
     pub fn has_tls(&self) -> bool {
-        self.tls_cert.is_some() && self.tls_key.is_some()
+        self.tls_auto || (self.tls_cert.is_some() && self.tls_key.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live_config(access_log_enabled: bool, access_log_min_status: u16) -> ReloadableConfig {
+        ReloadableConfig {
+            rate_limiter: None,
+            access_log_enabled,
+            access_log_min_status,
+            static_cache_ttl: OptionalDuration::from_secs(86400),
+            response: ResponseSettings::default(),
+            output_limits: OutputLimits::default(),
+        }
+    }
+
+    #[test]
+    fn test_access_log_all_statuses_by_default() {
+        let live = live_config(true, 0);
+        assert!(live.should_log_access(200));
+        assert!(live.should_log_access(304));
+        assert!(live.should_log_access(404));
+    }
+
+    #[test]
+    fn test_access_log_errors_only() {
+        let live = live_config(true, 400);
+        assert!(!live.should_log_access(200));
+        assert!(!live.should_log_access(302));
+        assert!(!live.should_log_access(399));
+        assert!(live.should_log_access(400));
+        assert!(live.should_log_access(404));
+        assert!(live.should_log_access(503));
+
+        let live = live_config(true, 500);
+        assert!(!live.should_log_access(404));
+        assert!(live.should_log_access(500));
+    }
+
+    #[test]
+    fn test_access_log_disabled_ignores_threshold() {
+        assert!(!live_config(false, 0).should_log_access(200));
+        assert!(!live_config(false, 400).should_log_access(500));
     }
 }
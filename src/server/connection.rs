@@ -19,11 +19,13 @@ mod header_names {
 
     // Standard headers (from http crate)
     pub static CONTENT_TYPE: HeaderName = header::CONTENT_TYPE;
+    pub static CONTENT_SECURITY_POLICY: HeaderName = header::CONTENT_SECURITY_POLICY;
     pub static USER_AGENT: HeaderName = header::USER_AGENT;
+    pub static VARY: HeaderName = header::VARY;
+    pub static LINK: HeaderName = header::LINK;
     pub static REFERER: HeaderName = header::REFERER;
     pub static ACCEPT: HeaderName = header::ACCEPT;
     pub static ACCEPT_ENCODING: HeaderName = header::ACCEPT_ENCODING;
-    pub static ACCEPT_LANGUAGE: HeaderName = header::ACCEPT_LANGUAGE;
     pub static COOKIE: HeaderName = header::COOKIE;
     pub static HOST: HeaderName = header::HOST;
     pub static IF_NONE_MATCH: HeaderName = header::IF_NONE_MATCH;
@@ -37,6 +39,10 @@ static X_REQUEST_ID: std::sync::LazyLock<HeaderName> =
     std::sync::LazyLock::new(|| HeaderName::from_static("x-request-id"));
 static X_FORWARDED_FOR: std::sync::LazyLock<HeaderName> =
     std::sync::LazyLock::new(|| HeaderName::from_static("x-forwarded-for"));
+static X_FORWARDED_HOST: std::sync::LazyLock<HeaderName> =
+    std::sync::LazyLock::new(|| HeaderName::from_static("x-forwarded-host"));
+static TIMING_ALLOW_ORIGIN: std::sync::LazyLock<HeaderName> =
+    std::sync::LazyLock::new(|| HeaderName::from_static("timing-allow-origin"));
 static X_RATELIMIT_LIMIT: std::sync::LazyLock<HeaderName> =
     std::sync::LazyLock::new(|| HeaderName::from_static("x-ratelimit-limit"));
 static X_RATELIMIT_REMAINING: std::sync::LazyLock<HeaderName> =
@@ -45,6 +51,10 @@ static X_RATELIMIT_RESET: std::sync::LazyLock<HeaderName> =
     std::sync::LazyLock::new(|| HeaderName::from_static("x-ratelimit-reset"));
 static TRACEPARENT: std::sync::LazyLock<HeaderName> =
     std::sync::LazyLock::new(|| HeaderName::from_static("traceparent"));
+#[cfg(feature = "debug-profile")]
+static X_PROFILE: HeaderName = HeaderName::from_static("x-profile");
+#[cfg(feature = "debug-profile")]
+static X_PROFILE_JSON: HeaderName = HeaderName::from_static("x-profile-json");
 
 // Static header values (zero allocation)
 mod header_values {
@@ -255,6 +265,7 @@ mod server_var_keys {
     pub const SERVER_NAME: Cow<'static, str> = Cow::Borrowed("SERVER_NAME");
     pub const SERVER_PORT: Cow<'static, str> = Cow::Borrowed("SERVER_PORT");
     pub const SERVER_ADDR: Cow<'static, str> = Cow::Borrowed("SERVER_ADDR");
+    pub const REQUEST_SCHEME: Cow<'static, str> = Cow::Borrowed("REQUEST_SCHEME");
     pub const SERVER_SOFTWARE: Cow<'static, str> = Cow::Borrowed("SERVER_SOFTWARE");
     pub const SERVER_PROTOCOL: Cow<'static, str> = Cow::Borrowed("SERVER_PROTOCOL");
     pub const DOCUMENT_ROOT: Cow<'static, str> = Cow::Borrowed("DOCUMENT_ROOT");
@@ -271,21 +282,28 @@ mod server_var_keys {
 
     // HTTP headers
     pub const HTTP_HOST: Cow<'static, str> = Cow::Borrowed("HTTP_HOST");
+    pub const HTTP_X_FORWARDED_HOST: Cow<'static, str> = Cow::Borrowed("HTTP_X_FORWARDED_HOST");
     pub const HTTP_COOKIE: Cow<'static, str> = Cow::Borrowed("HTTP_COOKIE");
-    pub const HTTP_USER_AGENT: Cow<'static, str> = Cow::Borrowed("HTTP_USER_AGENT");
-    pub const HTTP_REFERER: Cow<'static, str> = Cow::Borrowed("HTTP_REFERER");
-    pub const HTTP_ACCEPT_LANGUAGE: Cow<'static, str> = Cow::Borrowed("HTTP_ACCEPT_LANGUAGE");
-    pub const HTTP_ACCEPT: Cow<'static, str> = Cow::Borrowed("HTTP_ACCEPT");
     pub const HTTP_TRACEPARENT: Cow<'static, str> = Cow::Borrowed("HTTP_TRACEPARENT");
+    pub const HTTP_B3: Cow<'static, str> = Cow::Borrowed("HTTP_B3");
+
+    // HTTP authentication
+    pub const AUTH_TYPE: Cow<'static, str> = Cow::Borrowed("AUTH_TYPE");
+    pub const PHP_AUTH_USER: Cow<'static, str> = Cow::Borrowed("PHP_AUTH_USER");
+    pub const PHP_AUTH_PW: Cow<'static, str> = Cow::Borrowed("PHP_AUTH_PW");
 
     // TLS info
     pub const HTTPS: Cow<'static, str> = Cow::Borrowed("HTTPS");
     pub const SSL_PROTOCOL: Cow<'static, str> = Cow::Borrowed("SSL_PROTOCOL");
+    pub const SSL_SERVER_NAME: Cow<'static, str> = Cow::Borrowed("SSL_SERVER_NAME");
 
     // Trace context
     pub const TRACE_ID: Cow<'static, str> = Cow::Borrowed("TRACE_ID");
     pub const SPAN_ID: Cow<'static, str> = Cow::Borrowed("SPAN_ID");
     pub const PARENT_SPAN_ID: Cow<'static, str> = Cow::Borrowed("PARENT_SPAN_ID");
+
+    // Security
+    pub const TOKIO_CSP_NONCE: Cow<'static, str> = Cow::Borrowed("TOKIO_CSP_NONCE");
 }
 
 // Static server variable values (zero allocation)
@@ -296,6 +314,9 @@ mod server_var_values {
     pub const SERVER_SOFTWARE: Cow<'static, str> = Cow::Borrowed("tokio_php/0.1.0");
     pub const GATEWAY_INTERFACE: Cow<'static, str> = Cow::Borrowed("CGI/1.1");
     pub const HTTPS_ON: Cow<'static, str> = Cow::Borrowed("on");
+    pub const SCHEME_HTTP: Cow<'static, str> = Cow::Borrowed("http");
+    pub const SCHEME_HTTPS: Cow<'static, str> = Cow::Borrowed("https");
+    pub const AUTH_BASIC: Cow<'static, str> = Cow::Borrowed("Basic");
     pub const PORT_80: Cow<'static, str> = Cow::Borrowed("80");
     pub const PORT_443: Cow<'static, str> = Cow::Borrowed("443");
     pub const LOCALHOST: Cow<'static, str> = Cow::Borrowed("localhost");
@@ -353,6 +374,60 @@ fn protocol_to_cow(version: &str) -> std::borrow::Cow<'static, str> {
 // IP address formatting (zero heap allocation)
 // ============================================================================
 
+/// Request headers the server never exposes as `HTTP_*` variables: hop-by-hop
+/// headers, `Proxy` (httpoxy), the CGI `CONTENT_*` pair, and headers whose
+/// `$_SERVER` value the server sets itself.
+const SKIPPED_HTTP_VARS: [&str; 16] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "proxy",
+    "content-type",
+    "content-length",
+    "host",
+    "x-forwarded-host",
+    "cookie",
+    "traceparent",
+    "b3",
+];
+
+/// Map request headers to `HTTP_<UPPER_SNAKE>` server variables.
+///
+/// Repeated headers are joined with `", "` into one entry. Names containing
+/// `_` are dropped, as nginx does, so `X_Foo` can't shadow `X-Foo`.
+fn http_header_vars(headers: &http::HeaderMap) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+    let mut vars = Vec::with_capacity(headers.keys_len());
+    for name in headers.keys() {
+        let name_str = name.as_str();
+        if name_str.contains('_') || SKIPPED_HTTP_VARS.contains(&name_str) {
+            continue;
+        }
+        let mut value = String::new();
+        for (i, v) in headers.get_all(name).iter().enumerate() {
+            if i > 0 {
+                value.push_str(", ");
+            }
+            value.push_str(&String::from_utf8_lossy(v.as_bytes()));
+        }
+        let mut key = String::with_capacity(5 + name_str.len());
+        key.push_str("HTTP_");
+        key.extend(name_str.chars().map(|c| {
+            if c == '-' {
+                '_'
+            } else {
+                c.to_ascii_uppercase()
+            }
+        }));
+        vars.push((Cow::Owned(key), Cow::Owned(value)));
+    }
+    vars
+}
+
 /// Format an IP address to a stack buffer, returning the string slice.
 /// Buffer must be at least 45 bytes for IPv6 (max: "xxxx:xxxx:xxxx:xxxx:xxxx:xxxx:xxxx:xxxx").
 #[inline]
@@ -366,7 +441,7 @@ fn format_ip_to_buf(ip: std::net::IpAddr, buf: &mut [u8; 48]) -> &str {
 }
 
 use bytes::Bytes;
-use http_body_util::{BodyExt, Full};
+use http_body_util::{BodyExt, Either, Full};
 use hyper::body::{Body, Incoming as IncomingBody};
 use hyper::service::service_fn;
 use hyper::{Method, Request, Response, StatusCode};
@@ -378,19 +453,33 @@ use tokio_rustls::TlsAcceptor;
 use tracing::{debug, error, warn};
 
 use super::access_log;
-use super::config::TlsInfo;
+use super::config::{ReloadableConfig, TlsInfo};
 use super::error_pages::{accepts_html, status_reason_phrase, ErrorPages};
-use super::request::{parse_cookies, parse_multipart, parse_query_string};
+use super::kill_switch::prefix_matches;
+use super::misdirected::is_misdirected;
+use super::request::{
+    parse_basic_auth, parse_cookies, parse_multipart, parse_query_string_limited, read_body,
+    reject_before_body, BodyError,
+};
 use super::response::{
-    accepts_brotli, empty_stub_response, from_script_response, full_to_flexible, is_sse_accept,
-    not_found_response, serve_static_file, streaming_response, streaming_to_flexible,
-    stub_response_with_profile, FlexibleResponse, BAD_REQUEST_BODY, EMPTY_BODY,
-    METHOD_NOT_ALLOWED_BODY,
+    accepts_brotli, empty_stub_response, forbidden_response, from_script_response,
+    full_to_flexible, head_response, is_sse_accept, not_found_response, script_error_response,
+    serve_directory_listing, serve_static_file, streaming_response, streaming_to_flexible,
+    stub_response_with_profile, trailing_slash_redirect, unavailable_response, FlexibleResponse,
+    BAD_REQUEST_BODY, EMPTY_BODY, METHOD_NOT_ALLOWED_BODY,
 };
 use super::routing::is_php_uri;
-use crate::executor::{ExecuteResult, ScriptExecutor, DEFAULT_STREAM_BUFFER_SIZE};
-use crate::middleware::rate_limit::RateLimiter;
-use crate::types::{ScriptRequest, UploadedFile};
+use crate::core::{Context as MiddlewareContext, HttpVersion};
+use crate::executor::{ExecuteResult, ExecutorErrorKind, ScriptExecutor};
+use crate::middleware::request_id::ensure_request_id;
+use crate::middleware::{MiddlewareChain, MiddlewareResult};
+use crate::types::{Priority, ScriptRequest, UploadedFile};
+
+/// Time a PHP request waited for a worker, attached to the response when
+/// profiling data is collected.
+#[derive(Clone, Copy)]
+#[cfg_attr(not(feature = "debug-profile"), allow(dead_code))]
+struct QueueWaitUs(u64);
 
 /// Check if an error is a common connection reset or timeout.
 #[inline]
@@ -405,7 +494,54 @@ fn is_connection_error(err_str: &str) -> bool {
         || err_str.contains("HeaderTimeout") // Slowloris protection timeout
 }
 
-use super::internal::RequestMetrics;
+/// Classify a connection error for the closed-connections metric.
+fn classify_connection_error(err_str: &str) -> CloseReason {
+    if err_str.contains("timed out")
+        || err_str.contains("deadline has elapsed")
+        || err_str.contains("HeaderTimeout")
+    {
+        CloseReason::Timeout
+    } else if is_connection_error(err_str) {
+        CloseReason::Reset
+    } else {
+        CloseReason::Protocol
+    }
+}
+
+/// Drive a connection until it completes, shutdown is signalled or `idle`
+/// resolves (keep-alive timeout).
+///
+/// On shutdown, `graceful_shutdown` is called once: HTTP/2 sends GOAWAY
+/// (refusing new streams, then a final GOAWAY with the last accepted stream),
+/// HTTP/1 stops keep-alive (the in-flight response gets `Connection: close`,
+/// idle connections close at once). In-flight requests then get `window` to finish
+/// before the connection is dropped. Returns `None` if the window elapsed.
+async fn serve_until_shutdown<F: std::future::Future>(
+    conn: F,
+    mut shutdown_rx: watch::Receiver<bool>,
+    idle: impl std::future::Future<Output = ()>,
+    window: Duration,
+    graceful_shutdown: impl FnOnce(std::pin::Pin<&mut F>),
+) -> Option<F::Output> {
+    tokio::pin!(conn);
+    let shutdown = async {
+        // Sender dropped: no shutdown will ever be signalled
+        if shutdown_rx.wait_for(|&stop| stop).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    tokio::select! {
+        result = conn.as_mut() => return Some(result),
+        () = shutdown => {}
+        () = idle => {}
+    }
+    graceful_shutdown(conn.as_mut());
+    tokio::time::timeout(window, conn).await.ok()
+}
+
+use super::internal::{CloseReason, ConnectionMetrics, RequestMetrics};
+use super::keepalive::{ConnectionActivity, TrackedBody};
+use super::proxy::ForwardedFor;
 use super::routing::{resolve_request, RouteResult};
 use crate::trace_context::TraceContext;
 
@@ -417,27 +553,101 @@ pub struct ConnectionContext<E: ScriptExecutor> {
     /// Created by leaking the document_root string at server startup.
     pub document_root_static: std::borrow::Cow<'static, str>,
     pub is_stub_mode: bool,
+    /// Executor answers every script with an empty 200 (stub fast path).
+    pub empty_responses: bool,
     /// Route configuration (INDEX_FILE handling)
     pub route_config: Arc<super::routing::RouteConfig>,
     pub active_connections: Arc<AtomicUsize>,
     pub request_metrics: Arc<RequestMetrics>,
+    /// Connection lifecycle counters (accepts, TLS handshakes, error closes).
+    pub connection_metrics: Arc<ConnectionMetrics>,
     pub error_pages: ErrorPages,
-    pub rate_limiter: Option<Arc<RateLimiter>>,
-    pub static_cache_ttl: super::config::StaticCacheTtl,
+    /// Hot-swappable settings (rate limiter, access log, static cache TTL).
+    /// Snapshotted once per request.
+    pub live_config: watch::Receiver<Arc<ReloadableConfig>>,
     pub request_timeout: super::config::RequestTimeout,
+    /// Estimated queue wait that answers 503 (MAX_QUEUE_WAIT, None = off).
+    pub max_queue_wait: Option<std::time::Duration>,
+    /// PHP worker count, for the queue wait estimate.
+    pub php_workers: usize,
     /// SSE timeout (SSE_TIMEOUT env var, default: 30m).
     pub sse_timeout: super::config::RequestTimeout,
     /// Header read timeout (HEADER_TIMEOUT_SECS, default: 5s).
     pub header_timeout: std::time::Duration,
     /// Idle connection timeout (IDLE_TIMEOUT_SECS, default: 60s).
     pub idle_timeout: std::time::Duration,
+    /// Close connections with no request in flight for this long
+    /// (KEEPALIVE_TIMEOUT_SECS, default: 75s, zero = off).
+    pub keepalive_timeout: Duration,
+    /// TLS handshake timeout (TLS_HANDSHAKE_TIMEOUT_SECS, default: 10s).
+    pub tls_handshake_timeout: std::time::Duration,
+    /// HTTP/2 tuning (HTTP2_MAX_CONCURRENT_STREAMS, HTTP2_*_WINDOW_SIZE, HTTP2_MAX_FRAME_SIZE).
+    pub http2: super::config::Http2Settings,
     /// Profiling enabled (compile-time with debug-profile feature).
     #[allow(dead_code)]
     pub profile_enabled: bool,
-    /// Access logging enabled (ACCESS_LOG=1).
-    pub access_log_enabled: bool,
     /// File cache (LRU, max 200 entries).
     pub file_cache: Arc<super::file_cache::FileCache>,
+    /// Disabled path prefixes (checked before routing/PHP).
+    pub kill_switch: Arc<super::kill_switch::KillSwitch>,
+    /// Max query/form/cookie variables per request (MAX_INPUT_VARS).
+    pub max_input_vars: usize,
+    /// Requests slower than this log a warning; zero = off (SLOW_REQUEST_THRESHOLD_MS).
+    pub slow_request_threshold: Duration,
+    /// Cap on body buffer pre-sizing from Content-Length (BODY_PREALLOC_MAX).
+    pub body_prealloc_max: usize,
+    /// Largest body passed as php://input (RAW_BODY_MAX, 0 = unlimited).
+    pub raw_body_max: usize,
+    /// Largest request body accepted, else 413 (MAX_BODY_SIZE, 0 = unlimited).
+    pub max_body_size: usize,
+    /// Permitted multipart file types (UPLOAD_ALLOWED_TYPES, UPLOAD_SNIFF).
+    pub upload_types: Arc<super::request::UploadTypes>,
+    /// Expose HTTP_B3 to PHP (TRACE_B3_EGRESS).
+    pub trace_b3_egress: bool,
+    /// Script error details in 500 response bodies (EXPOSE_ERRORS).
+    pub expose_errors: bool,
+    /// Precomputed `Server` header value (SERVER_HEADER, None = not sent).
+    pub server_header: Option<HeaderValue>,
+    /// Leaf certificate for 421 Misdirected Request checks (TLS_CHECK_AUTHORITY).
+    pub cert_hosts: Option<Arc<super::misdirected::CertHosts>>,
+    /// PHP requests get 503 unless running (starting or draining).
+    pub lifecycle: Arc<super::lifecycle::Lifecycle>,
+    /// Requests shed with 503 under memory pressure (MEMORY_PRESSURE_*).
+    pub memory_pressure: Arc<super::memory_pressure::MemoryPressure>,
+    /// Time in-flight streams get after GOAWAY on shutdown (GOAWAY_WINDOW_SECS).
+    pub goaway_window: Duration,
+    /// Peers allowed to set X-Forwarded-* headers (TRUSTED_PROXIES).
+    pub trusted_proxies: super::trusted_proxy::TrustedProxies,
+    /// Timing-Allow-Origin settings with the prebuilt header value.
+    pub timing_allow_origin: Option<(crate::config::TimingAllowOrigin, HeaderValue)>,
+    /// Per-request CSP nonce for PHP responses (CSP_NONCE / CSP_POLICY).
+    pub csp_nonce: Option<super::csp::CspNonce>,
+    /// Static file Content-Type overrides (MIME_TYPES).
+    pub mime_types: Arc<super::response::MimeTypes>,
+    /// Brotli-compressed static files (STATIC_COMPRESS_CACHE_SIZE).
+    pub compressed_cache: Arc<super::response::CompressedCache>,
+    /// Fingerprinted asset patterns cached as immutable (STATIC_IMMUTABLE).
+    pub immutable_assets: Arc<super::response::ImmutableAssets>,
+    /// Device classes with their own static file variants (STATIC_UA_VARIANTS).
+    pub ua_variants: Arc<super::response::UaVariants>,
+    /// Open connections per client IP (MAX_CONNECTIONS_PER_IP).
+    pub ip_limit: Arc<super::ip_limit::IpConnectionLimit>,
+    /// Canonical URLs sent as `Link` headers per path (CANONICAL_LINKS).
+    pub canonical_links: Arc<super::canonical::CanonicalLinks>,
+    /// Route templates for path-labeled metrics (METRICS_ROUTES).
+    pub route_labels: Arc<super::route_label::RouteLabels>,
+    /// Client for proxied prefixes (PROXY_ROUTES, None = no routes).
+    pub proxy: Option<Arc<super::proxy::ReverseProxy>>,
+    /// Methods passed to PHP; others answer 405 (ALLOWED_METHODS).
+    pub allowed_methods: Arc<super::methods::AllowedMethods>,
+    /// URI prefixes queued as high priority (PRIORITY_PATHS).
+    pub priority_paths: Arc<[String]>,
+    /// Last request profile for /profile/last (debug-profile builds).
+    pub last_profile: Arc<crate::profiler::LastProfile>,
+    /// Per-phase percentiles across profiled requests (PROFILE_STATS).
+    pub profile_stats: Option<Arc<crate::profiler::ProfileStats>>,
+    /// Middleware from `Server::with_middleware` (empty = skipped).
+    pub middleware: Arc<MiddlewareChain>,
 }
 
 impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
@@ -448,40 +658,84 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         remote_addr: SocketAddr,
         tls_acceptor: Option<TlsAcceptor>,
     ) {
+        // Sender dropped immediately: shutdown is never signalled
+        let (_, shutdown_rx) = watch::channel(false);
+        self.handle_connection_graceful(stream, remote_addr, tls_acceptor, shutdown_rx)
+            .await;
+    }
+
+    /// Handle an incoming TCP connection with graceful shutdown support.
+    ///
+    /// The graceful shutdown sequence:
+    /// 1. Accept loops stop when shutdown is triggered
+    /// 2. Each connection sends GOAWAY (HTTP/2) or stops keep-alive (HTTP/1)
+    /// 3. In-flight requests get `goaway_window` to finish, then the connection closes
+    /// 4. wait_for_drain() waits for active_connections to reach 0
+    pub async fn handle_connection_graceful(
+        self: Arc<Self>,
+        stream: TcpStream,
+        remote_addr: SocketAddr,
+        tls_acceptor: Option<TlsAcceptor>,
+        shutdown_rx: watch::Receiver<bool>,
+    ) {
+        self.connection_metrics.record_accept();
+
+        // Per-IP cap; trusted proxies carry many clients and are exempt
+        let ip = remote_addr.ip();
+        let _ip_guard = if self.ip_limit.is_enabled() && !self.trusted_proxies.contains(ip) {
+            match self.ip_limit.acquire(ip) {
+                Some(guard) => Some(guard),
+                None => {
+                    debug!("Connection limit reached for {}, closing", ip);
+                    self.connection_metrics.record_ip_limit_reject();
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+
         self.active_connections.fetch_add(1, Ordering::Relaxed);
 
         if let Some(acceptor) = tls_acceptor {
             self.clone()
-                .handle_tls_connection(stream, remote_addr, acceptor)
+                .handle_tls_connection(stream, remote_addr, acceptor, shutdown_rx)
                 .await;
         } else {
             self.clone()
-                .handle_plain_connection(stream, remote_addr)
+                .handle_plain_connection(stream, remote_addr, shutdown_rx)
                 .await;
         }
 
         self.active_connections.fetch_sub(1, Ordering::Relaxed);
     }
 
-    /// Handle an incoming TCP connection with graceful shutdown support.
-    /// When shutdown is triggered, in-flight requests complete naturally before connection closes.
-    pub async fn handle_connection_graceful(
-        self: Arc<Self>,
-        stream: TcpStream,
-        remote_addr: SocketAddr,
-        tls_acceptor: Option<TlsAcceptor>,
-        _shutdown_rx: watch::Receiver<bool>,
-    ) {
-        // The graceful shutdown is handled at the server level:
-        // 1. Accept loops stop when shutdown is triggered
-        // 2. Existing connections complete naturally
-        // 3. wait_for_drain() waits for active_connections to reach 0
-        //
-        // Note: HTTP/2 GOAWAY frames would require hyper's graceful_shutdown(),
-        // but auto::Builder's API design prevents storing the connection for later use.
-        // This is acceptable for most deployments - connections complete in-flight work.
-        self.handle_connection(stream, remote_addr, tls_acceptor)
-            .await;
+    /// Resolves once the connection has had nothing in flight for
+    /// `keepalive_timeout` (never when it is zero).
+    async fn keepalive_expired(&self, activity: &ConnectionActivity, remote_addr: SocketAddr) {
+        if self.keepalive_timeout.is_zero() {
+            return std::future::pending().await;
+        }
+        activity.idle(self.keepalive_timeout).await;
+        debug!("Keep-alive timeout, closing connection: {:?}", remote_addr);
+        self.connection_metrics.record_close(CloseReason::Idle);
+    }
+
+    /// HTTP/1 + HTTP/2 connection builder with this server's settings.
+    fn connection_builder(&self) -> auto::Builder<TokioExecutor> {
+        let mut builder = auto::Builder::new(TokioExecutor::new());
+        builder
+            .http1()
+            .timer(TokioTimer::new())
+            .header_read_timeout(Some(self.header_timeout))
+            .keep_alive(true);
+        builder
+            .http2()
+            .max_concurrent_streams(self.http2.max_concurrent_streams)
+            .initial_stream_window_size(self.http2.initial_stream_window_size)
+            .initial_connection_window_size(self.http2.initial_connection_window_size)
+            .max_frame_size(self.http2.max_frame_size);
+        builder
     }
 
     async fn handle_tls_connection(
@@ -489,22 +743,28 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         stream: TcpStream,
         remote_addr: SocketAddr,
         acceptor: TlsAcceptor,
+        shutdown_rx: watch::Receiver<bool>,
     ) {
+        // SERVER_ADDR: the address this connection actually arrived on
+        let local_addr = stream.local_addr().ok();
         let tls_start = Instant::now();
 
         // TLS handshake with timeout
         let tls_stream =
-            match tokio::time::timeout(Duration::from_secs(10), acceptor.accept(stream)).await {
+            match tokio::time::timeout(self.tls_handshake_timeout, acceptor.accept(stream)).await {
                 Ok(Ok(s)) => s,
                 Ok(Err(e)) => {
                     debug!("TLS handshake failed: {:?}", e);
+                    self.connection_metrics.record_tls_handshake(false);
                     return;
                 }
                 Err(_) => {
                     debug!("TLS handshake timeout: {:?}", remote_addr);
+                    self.connection_metrics.record_tls_handshake(false);
                     return;
                 }
             };
+        self.connection_metrics.record_tls_handshake(true);
 
         let handshake_us = tls_start.elapsed().as_micros() as u64;
 
@@ -520,45 +780,87 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                 .alpn_protocol()
                 .map(|p| String::from_utf8_lossy(p).to_string())
                 .unwrap_or_default(),
+            sni: server_conn.server_name().unwrap_or_default().to_string(),
         };
+        self.connection_metrics.record_tls_session(
+            &tls_info.protocol,
+            &tls_info.alpn,
+            handshake_us,
+        );
 
         let ctx = Arc::clone(&self);
+        let activity = ConnectionActivity::new();
+        let tracker = Arc::clone(&activity);
         let service = service_fn(move |req| {
             let ctx = Arc::clone(&ctx);
             let tls = tls_info.clone();
-            async move { ctx.handle_request(req, remote_addr, Some(tls)).await }
+            let guard = tracker.begin();
+            async move {
+                let response = ctx
+                    .handle_request(req, remote_addr, local_addr, Some(tls))
+                    .await?;
+                Ok::<_, Infallible>(response.map(|body| TrackedBody::new(body, guard)))
+            }
         });
 
         let io = TokioIo::new(tls_stream);
-        if let Err(err) = auto::Builder::new(TokioExecutor::new())
-            .http1()
-            .timer(TokioTimer::new())
-            .header_read_timeout(Some(self.header_timeout))
-            .keep_alive(true)
-            .http2()
-            .max_concurrent_streams(250)
-            .serve_connection(io, service)
-            .await
+        let builder = self.connection_builder();
+        let conn = builder.serve_connection(io, service);
+        match serve_until_shutdown(
+            conn,
+            shutdown_rx,
+            self.keepalive_expired(&activity, remote_addr),
+            self.goaway_window,
+            |c| c.graceful_shutdown(),
+        )
+        .await
         {
-            let err_str = format!("{:?}", err);
-            if !is_connection_error(&err_str) {
-                debug!("TLS connection error: {:?}", err);
+            Some(Ok(())) => {}
+            Some(Err(err)) => {
+                let err_str = format!("{:?}", err);
+                let reason = classify_connection_error(&err_str);
+                self.connection_metrics.record_close(reason);
+                if reason == CloseReason::Protocol {
+                    debug!("TLS connection error: {:?}", err);
+                }
+            }
+            None => {
+                debug!(
+                    "GOAWAY window elapsed, closing connection: {:?}",
+                    remote_addr
+                );
+                self.connection_metrics.record_close(CloseReason::Timeout);
             }
         }
     }
 
-    async fn handle_plain_connection(self: Arc<Self>, stream: TcpStream, remote_addr: SocketAddr) {
-        // Wait for first byte with timeout to detect idle connections (skip for stub mode)
+    async fn handle_plain_connection(
+        self: Arc<Self>,
+        stream: TcpStream,
+        remote_addr: SocketAddr,
+        shutdown_rx: watch::Receiver<bool>,
+    ) {
+        let local_addr = stream.local_addr().ok();
+
+        // Wait for first byte with timeout to detect idle connections (skip for stub mode).
+        // Later requests on a keep-alive connection fall under hyper's header
+        // read timer, which starts as soon as the previous response is written.
         if !self.is_stub_mode {
             let mut peek_buf = [0u8; 1];
             match tokio::time::timeout(self.idle_timeout, stream.peek(&mut peek_buf)).await {
-                Ok(Ok(0)) | Err(_) => {
-                    // Connection closed or timeout - client connected but sent nothing
-                    debug!("Connection idle timeout or closed: {:?}", remote_addr);
+                Ok(Ok(0)) => {
+                    // Client connected but closed without sending anything
+                    debug!("Connection closed before request: {:?}", remote_addr);
+                    return;
+                }
+                Err(_) => {
+                    debug!("Connection idle timeout: {:?}", remote_addr);
+                    self.connection_metrics.record_close(CloseReason::Idle);
                     return;
                 }
                 Ok(Err(e)) => {
                     debug!("Peek error: {:?}", e);
+                    self.connection_metrics.record_close(CloseReason::Reset);
                     return;
                 }
                 Ok(Ok(_)) => {
@@ -568,25 +870,46 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         }
 
         let ctx = Arc::clone(&self);
+        let activity = ConnectionActivity::new();
+        let tracker = Arc::clone(&activity);
         let service = service_fn(move |req| {
             let ctx = Arc::clone(&ctx);
-            async move { ctx.handle_request(req, remote_addr, None).await }
+            let guard = tracker.begin();
+            async move {
+                let response = ctx
+                    .handle_request(req, remote_addr, local_addr, None)
+                    .await?;
+                Ok::<_, Infallible>(response.map(|body| TrackedBody::new(body, guard)))
+            }
         });
 
         let io = TokioIo::new(stream);
-        if let Err(err) = auto::Builder::new(TokioExecutor::new())
-            .http1()
-            .timer(TokioTimer::new())
-            .header_read_timeout(Some(self.header_timeout))
-            .keep_alive(true)
-            .http2()
-            .max_concurrent_streams(250)
-            .serve_connection(io, service)
-            .await
+        let builder = self.connection_builder();
+        let conn = builder.serve_connection(io, service);
+        match serve_until_shutdown(
+            conn,
+            shutdown_rx,
+            self.keepalive_expired(&activity, remote_addr),
+            self.goaway_window,
+            |c| c.graceful_shutdown(),
+        )
+        .await
         {
-            let err_str = format!("{:?}", err);
-            if !is_connection_error(&err_str) {
-                debug!("Connection error: {:?}", err);
+            Some(Ok(())) => {}
+            Some(Err(err)) => {
+                let err_str = format!("{:?}", err);
+                let reason = classify_connection_error(&err_str);
+                self.connection_metrics.record_close(reason);
+                if reason == CloseReason::Protocol {
+                    debug!("Connection error: {:?}", err);
+                }
+            }
+            None => {
+                debug!(
+                    "GOAWAY window elapsed, closing connection: {:?}",
+                    remote_addr
+                );
+                self.connection_metrics.record_close(CloseReason::Timeout);
             }
         }
     }
@@ -595,7 +918,141 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         &self,
         req: Request<IncomingBody>,
         remote_addr: SocketAddr,
+        local_addr: Option<SocketAddr>,
+        tls_info: Option<TlsInfo>,
+    ) -> Result<FlexibleResponse, Infallible> {
+        // Extract or generate W3C Trace Context, once for the middleware and the handler
+        let trace_ctx = TraceContext::from_headers(req.headers());
+
+        let mut response = if self.middleware.is_empty() {
+            self.route_request(req, remote_addr, local_addr, tls_info, trace_ctx)
+                .await?
+        } else {
+            self.route_with_middleware(req, remote_addr, local_addr, tls_info, trace_ctx)
+                .await?
+        };
+
+        // One place for every response, stub fast path included; a Server
+        // header sent by the script wins
+        if let Some(ref value) = self.server_header {
+            response
+                .headers_mut()
+                .entry(header::SERVER)
+                .or_insert_with(|| value.clone());
+        }
+        Ok(response)
+    }
+
+    /// Run the registered middleware chain around `route_request`.
+    ///
+    /// Request middleware get the head with an empty body, since the body is
+    /// streamed to PHP later. Response middleware only see full responses,
+    /// and after a short-circuit only those that saw the request.
+    async fn route_with_middleware(
+        &self,
+        req: Request<IncomingBody>,
+        remote_addr: SocketAddr,
+        local_addr: Option<SocketAddr>,
+        tls_info: Option<TlsInfo>,
+        trace_ctx: TraceContext,
+    ) -> Result<FlexibleResponse, Infallible> {
+        let header_str = |name: &HeaderName| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("")
+        };
+        let mut ctx = MiddlewareContext::builder(remote_addr.ip())
+            .trace_id(trace_ctx.trace_id())
+            .span_id(trace_ctx.span_id())
+            .http_version(HttpVersion::from_http(req.version()))
+            .accepts_html(accepts_html(header_str(&header_names::ACCEPT)))
+            .accepts_brotli(accepts_brotli(header_str(&header_names::ACCEPT_ENCODING)))
+            .build();
+
+        let (mut parts, body) = req.into_parts();
+        let head = crate::core::Request::new(
+            parts.method.clone(),
+            parts.uri.clone(),
+            std::mem::take(&mut parts.headers),
+            Bytes::new(),
+        );
+
+        let (result, entered) = self.middleware.process_request_entered(head, &mut ctx);
+        let response = match result {
+            MiddlewareResult::Next(head) => {
+                parts.headers = http::Request::from(head).into_parts().0.headers;
+                if let Some(&priority) = ctx.get::<Priority>(Priority::CONTEXT_KEY) {
+                    parts.extensions.insert(priority);
+                }
+                let req = Request::from_parts(parts, body);
+                self.route_request(req, remote_addr, local_addr, tls_info, trace_ctx)
+                    .await?
+            }
+            MiddlewareResult::Stop(res) => {
+                full_to_flexible(http::Response::from(res).map(Full::new))
+            }
+        };
+
+        let (parts, body) = response.into_parts();
+        let mut response = match body {
+            Either::Left(full) => {
+                let bytes = match full.collect().await {
+                    Ok(collected) => collected.to_bytes(),
+                    Err(never) => match never {},
+                };
+                let len = bytes.len();
+                let res = crate::core::Response::from(Response::from_parts(parts, bytes));
+                let res = self.middleware.process_response_entered(res, &ctx, entered);
+                let mut res = http::Response::from(res);
+                // A middleware that rewrote the body (e.g. compression)
+                // leaves a stale length behind
+                if res.body().len() != len && res.headers().contains_key(header::CONTENT_LENGTH) {
+                    let new_len = HeaderValue::from(res.body().len());
+                    res.headers_mut().insert(header::CONTENT_LENGTH, new_len);
+                }
+                full_to_flexible(res.map(Full::new))
+            }
+            streamed => Response::from_parts(parts, streamed),
+        };
+
+        for (name, value) in ctx.response_headers() {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                response.headers_mut().insert(name, value);
+            }
+        }
+        Ok(response)
+    }
+
+    /// Queue lane for a PHP request: set by middleware through
+    /// [`Priority::CONTEXT_KEY`], else by PRIORITY_PATHS (whole segments of
+    /// the normalized path).
+    fn request_priority(&self, req: &Request<IncomingBody>) -> Priority {
+        if let Some(&priority) = req.extensions().get::<Priority>() {
+            return priority;
+        }
+        let path = super::routing::match_path(req.uri().path());
+        if self
+            .priority_paths
+            .iter()
+            .any(|prefix| prefix_matches(&path, prefix))
+        {
+            Priority::High
+        } else {
+            Priority::Normal
+        }
+    }
+
+    async fn route_request(
+        &self,
+        mut req: Request<IncomingBody>,
+        remote_addr: SocketAddr,
+        local_addr: Option<SocketAddr>,
         tls_info: Option<TlsInfo>,
+        trace_ctx: TraceContext,
     ) -> Result<FlexibleResponse, Infallible> {
         // Network I/O timing: capture entry time
         let handler_entry_time = Instant::now();
@@ -607,31 +1064,48 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
             .and_then(|v| v.to_str().ok());
         let is_sse = is_sse_accept(accept_header);
 
+        // Coalesced HTTP/2 request for a host this certificate doesn't cover
+        let misdirected = tls_info.is_some()
+            && self
+                .cert_hosts
+                .as_ref()
+                .is_some_and(|hosts| is_misdirected(&req, hosts));
+
+        // Shed and kill-switch prefixes match the decoded path, not the raw URI
+        let match_path = super::routing::match_path(req.uri().path());
+
+        // Low-priority paths under high memory pressure, everything under critical
+        let shed = !misdirected && self.memory_pressure.should_shed(&match_path);
+
+        let disabled_status = self.kill_switch.check(&match_path);
+
+        // PROXY_ROUTES prefix: forwarded upstream, never PHP or static (SSE included)
+        let proxy_route = self
+            .proxy
+            .as_ref()
+            .and_then(|_| self.route_config.proxy_routes.find(&match_path));
+
         // Handle SSE requests separately (streaming response path)
-        if is_sse {
-            return self.handle_sse_request(req, remote_addr, tls_info).await;
+        if is_sse && !misdirected && !shed && disabled_status.is_none() && proxy_route.is_none() {
+            return self
+                .handle_sse_request(req, remote_addr, tls_info, trace_ctx)
+                .await;
         }
 
         // Normal (non-streaming) request path
         let request_start = Instant::now();
 
-        // Extract or generate W3C Trace Context
-        let trace_ctx = TraceContext::from_headers(req.headers());
+        // Snapshot reloadable settings so this request sees one consistent config
+        let live = Arc::clone(&*self.live_config.borrow());
 
-        // Use trace_id as request_id for correlation, or fall back to X-Request-ID
-        // Zero-allocation when no X-Request-ID header (common case)
-        let request_id_from_header: Option<String> = req
-            .headers()
-            .get(&*X_REQUEST_ID)
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_owned());
-        let request_id: &str = request_id_from_header
-            .as_deref()
-            .unwrap_or_else(|| trace_ctx.short_id());
+        // Keep a valid X-Request-ID, otherwise replace it with the trace id on
+        // the request too, so PHP sees the same id as the logs
+        let request_id = ensure_request_id(req.headers_mut(), || trace_ctx.short_id().to_string());
+        let request_id: &str = &request_id;
 
         // Check rate limit (per-IP) with timing
         let rate_limit_start = Instant::now();
-        if let Some(ref limiter) = self.rate_limiter {
+        if let Some(ref limiter) = live.rate_limiter {
             let (allowed, _remaining, reset_after) = limiter.check(remote_addr.ip());
             if !allowed {
                 let mut response = Response::builder()
@@ -655,12 +1129,13 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         let rate_limit_us = rate_limit_start.elapsed().as_micros() as u64;
 
         // Increment request method metrics
-        self.request_metrics.increment_method(req.method());
+        self.request_metrics
+            .increment_method(req.method(), req.version(), tls_info.is_some());
 
         let is_head = *req.method() == Method::HEAD;
 
         // Capture data for access logging (before consuming request)
-        let access_log_enabled = self.access_log_enabled;
+        let access_log_enabled = live.access_log_enabled;
         let method_str = req.method().to_string();
         let uri_str = req.uri().path().to_string();
         let query_str = req.uri().query().map(|s| s.to_string());
@@ -697,14 +1172,76 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
             .map(accepts_html)
             .unwrap_or(false);
 
-        let mut response = match req.method().as_str() {
-            "GET" | "POST" | "HEAD" | "PUT" | "PATCH" | "DELETE" | "OPTIONS" | "QUERY" => {
+        // Refused before the body is read, so no 100 Continue goes out
+        let body_rejected = reject_before_body(req.headers(), self.max_body_size);
+        let proxy = match (&self.proxy, proxy_route) {
+            (Some(proxy), Some(route))
+                if !misdirected
+                    && !shed
+                    && disabled_status.is_none()
+                    && self.allowed_methods.contains(req.method()) =>
+            {
+                Some((proxy, route))
+            }
+            _ => None,
+        };
+
+        let mut response = match (req.method().as_str(), disabled_status, body_rejected, proxy) {
+            _ if misdirected => full_to_flexible(
+                Response::builder()
+                    .status(StatusCode::MISDIRECTED_REQUEST)
+                    .body(Full::new(EMPTY_BODY.clone()))
+                    .unwrap(),
+            ),
+            _ if shed => full_to_flexible(unavailable_response()),
+            // Kill-switch: empty body so the error page / reason phrase below applies
+            (_, Some(status), _, _) => full_to_flexible(
+                Response::builder()
+                    .status(status)
+                    .body(Full::new(EMPTY_BODY.clone()))
+                    .unwrap(),
+            ),
+            // OPTIONS for the whole server, or not passed to PHP: answer it here
+            ("OPTIONS", ..)
+                if req.uri().path() == "*" || !self.allowed_methods.contains(req.method()) =>
+            {
+                full_to_flexible(
+                    Response::builder()
+                        .status(StatusCode::NO_CONTENT)
+                        .header(
+                            hyper::header::ALLOW,
+                            self.allowed_methods.allow_header().clone(),
+                        )
+                        .body(Full::new(EMPTY_BODY.clone()))
+                        .unwrap(),
+                )
+            }
+            // MAX_BODY_SIZE / Expect, for proxied and PHP requests alike
+            (_, _, Some(status), _) => full_to_flexible(
+                Response::builder()
+                    .status(status)
+                    .body(Full::new(EMPTY_BODY.clone()))
+                    .unwrap(),
+            ),
+            (_, _, _, Some((proxy, route))) => {
+                let client = ForwardedFor {
+                    peer: remote_addr.ip(),
+                    https: tls_info.is_some(),
+                    trusted: self.trusted_proxies.contains(remote_addr.ip()),
+                };
+                proxy
+                    .forward(req, route, client, self.request_timeout.as_duration())
+                    .await
+            }
+            _ if self.allowed_methods.contains(req.method()) => {
                 let mut resp = self
                     .process_request(
                         req,
                         remote_addr,
+                        local_addr,
                         tls_info,
                         &trace_ctx,
+                        &live,
                         rate_limit_us,
                         handler_entry_time,
                     )
@@ -712,11 +1249,7 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
 
                 // HEAD: return headers only, no body
                 if is_head {
-                    let (parts, _) = resp.into_parts();
-                    resp = full_to_flexible(Response::from_parts(
-                        parts,
-                        Full::new(EMPTY_BODY.clone()),
-                    ));
+                    resp = head_response(resp);
                 }
                 resp
             }
@@ -727,74 +1260,45 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                         header_names::CONTENT_TYPE.clone(),
                         header_values::TEXT_PLAIN.clone(),
                     )
+                    .header(
+                        hyper::header::ALLOW,
+                        self.allowed_methods.allow_header().clone(),
+                    )
                     .body(Full::new(METHOD_NOT_ALLOWED_BODY.clone()))
                     .unwrap(),
             ),
         };
 
         // Apply custom error page or default reason phrase for 4xx/5xx responses
-        let status = response.status().as_u16();
-        if (400..600).contains(&status) {
-            let body_is_empty = response.body().size_hint().exact() == Some(0);
-            if body_is_empty {
-                // Try custom error page first (if client accepts HTML)
-                if client_accepts_html {
-                    if let Some(error_html) = self.error_pages.get(status) {
-                        let (mut parts, _) = response.into_parts();
-                        parts.headers.insert(
-                            header_names::CONTENT_TYPE.clone(),
-                            header_values::TEXT_HTML_UTF8.clone(),
-                        );
-                        parts.headers.insert(
-                            header_names::CONTENT_LENGTH.clone(),
-                            error_html.len().to_string().parse().unwrap(),
-                        );
-                        response = full_to_flexible(Response::from_parts(
-                            parts,
-                            Full::new(error_html.clone()),
-                        ));
-                    } else {
-                        // No custom page, use default reason phrase
-                        let reason = status_reason_phrase(status);
-                        let (mut parts, _) = response.into_parts();
-                        parts.headers.insert(
-                            header_names::CONTENT_TYPE.clone(),
-                            header_values::TEXT_PLAIN_UTF8.clone(),
-                        );
-                        parts.headers.insert(
-                            header_names::CONTENT_LENGTH.clone(),
-                            reason.len().to_string().parse().unwrap(),
-                        );
-                        response = full_to_flexible(Response::from_parts(
-                            parts,
-                            Full::new(Bytes::from(reason)),
-                        ));
-                    }
-                } else {
-                    // Non-HTML client, use default reason phrase
-                    let reason = status_reason_phrase(status);
-                    let (mut parts, _) = response.into_parts();
-                    parts.headers.insert(
-                        header_names::CONTENT_TYPE.clone(),
-                        header_values::TEXT_PLAIN_UTF8.clone(),
-                    );
-                    parts.headers.insert(
-                        header_names::CONTENT_LENGTH.clone(),
-                        reason.len().to_string().parse().unwrap(),
-                    );
-                    response = full_to_flexible(Response::from_parts(
-                        parts,
-                        Full::new(Bytes::from(reason)),
-                    ));
-                }
-            }
-        }
+        response = self.apply_error_page(response, client_accepts_html);
 
         // Record response time and status metrics
         let response_time_us = request_start.elapsed().as_micros() as u64;
         self.request_metrics.record_response_time(response_time_us);
         self.request_metrics
             .increment_status(response.status().as_u16());
+        if !self.route_labels.is_empty() {
+            self.request_metrics
+                .routes
+                .record(self.route_labels.label(&uri_str));
+        }
+
+        // Slow requests are logged regardless of access logging or profiling
+        if !self.slow_request_threshold.is_zero()
+            && response_time_us >= self.slow_request_threshold.as_micros() as u64
+        {
+            self.request_metrics.record_slow_request();
+            access_log::log_slow_request(
+                request_id,
+                trace_ctx.trace_id(),
+                trace_ctx.span_id(),
+                &method_str,
+                &uri_str,
+                response.status().as_u16(),
+                response_time_us,
+                response.extensions().get::<QueueWaitUs>().map(|q| q.0),
+            );
+        }
 
         // Add X-Request-ID header to response
         response
@@ -808,7 +1312,7 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         );
 
         // Access logging (optimized: stack-allocated timestamp, no heap alloc for IP)
-        if access_log_enabled {
+        if live.should_log_access(response.status().as_u16()) {
             let duration = request_start.elapsed();
             let body_size = response.body().size_hint().exact().unwrap_or(0);
             let ts = Iso8601Timestamp::now();
@@ -841,12 +1345,15 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
     }
 
     #[allow(unused_variables, unused_mut, unused_assignments)]
+    #[allow(clippy::too_many_arguments)]
     async fn process_request(
         &self,
         req: Request<IncomingBody>,
         remote_addr: SocketAddr,
+        local_addr: Option<SocketAddr>,
         tls_info: Option<TlsInfo>,
         trace_ctx: &TraceContext,
+        live: &ReloadableConfig,
         rate_limit_us: u64,
         handler_entry_time: Instant,
     ) -> FlexibleResponse {
@@ -877,12 +1384,19 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         let uri = req.uri().clone();
         let uri_path = uri.path();
         let query_string = uri.query().unwrap_or("");
+        let priority = self.request_priority(&req);
 
         // Profiling is controlled by compile-time feature, not runtime header
         #[cfg(feature = "debug-profile")]
         let profiling_enabled = true;
         #[cfg(not(feature = "debug-profile"))]
         let profiling_enabled = false;
+        // X-Profile: json also returns the profile as an X-Profile-Json header
+        #[cfg(feature = "debug-profile")]
+        let profile_json_requested = req
+            .headers()
+            .get(&X_PROFILE)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"json"));
 
         // Check if client accepts Brotli compression
         let use_brotli = req
@@ -905,8 +1419,8 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
             .and_then(|v| v.to_str().ok())
             .map(|s| s.to_string());
 
-        // Fast path for stub mode only
-        if self.is_stub_mode && is_php_uri(uri_path) {
+        // Fast path for the empty-body stub only
+        if self.empty_responses && is_php_uri(uri_path) {
             if profiling_enabled {
                 let total_us = parse_start.elapsed().as_micros() as u64;
                 let (tls_handshake_us, tls_protocol, tls_alpn) = match &tls_info {
@@ -919,9 +1433,10 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                     tls_handshake_us,
                     tls_protocol,
                     tls_alpn,
+                    &live.response.default_content_type,
                 ));
             }
-            return full_to_flexible(empty_stub_response());
+            return full_to_flexible(empty_stub_response(&live.response.default_content_type));
         }
 
         // Full processing path - extract headers before consuming body
@@ -940,10 +1455,25 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
             .unwrap_or("")
             .to_string();
 
+        // X-Forwarded-Host from a trusted proxy stands in for Host, so
+        // SERVER_NAME and HTTP_HOST carry the external host
+        let forwarded_host = self
+            .trusted_proxies
+            .forwarded_host(remote_addr.ip(), headers);
+        let x_forwarded_host = forwarded_host.and_then(|_| {
+            headers
+                .get(&*X_FORWARDED_HOST)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string())
+        });
+
         // For HTTP/2, the :authority pseudo-header is in uri.authority()
-        let host_header = headers
-            .get(&header_names::HOST)
-            .and_then(|v| v.to_str().ok())
+        let host_header = forwarded_host
+            .or_else(|| {
+                headers
+                    .get(&header_names::HOST)
+                    .and_then(|v| v.to_str().ok())
+            })
             .map(|s| s.to_string())
             .or_else(|| uri.authority().map(|a| a.to_string()))
             .unwrap_or_default();
@@ -954,23 +1484,12 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
             .unwrap_or("")
             .to_string();
 
-        let referer = headers
-            .get(&header_names::REFERER)
+        // Every other header as HTTP_* (User-Agent, Accept, Authorization, ...)
+        let header_vars = http_header_vars(headers);
+        let basic_auth = headers
+            .get(header::AUTHORIZATION)
             .and_then(|v| v.to_str().ok())
-            .unwrap_or("")
-            .to_string();
-
-        let accept_language = headers
-            .get(&header_names::ACCEPT_LANGUAGE)
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("")
-            .to_string();
-
-        let accept = headers
-            .get(&header_names::ACCEPT)
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("")
-            .to_string();
+            .and_then(parse_basic_auth);
 
         if profiling_enabled {
             headers_extract_us = headers_start.elapsed().as_micros() as u64;
@@ -980,7 +1499,7 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         let cookies_start = Instant::now();
         let has_cookies = !cookie_header_str.is_empty();
         let cookies = if has_cookies {
-            parse_cookies(&cookie_header_str)
+            parse_cookies(&cookie_header_str, self.max_input_vars)
         } else {
             Vec::new()
         };
@@ -993,7 +1512,7 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         let get_params = if query_string.is_empty() {
             Vec::new()
         } else {
-            parse_query_string(query_string)
+            parse_query_string_limited(query_string, self.max_input_vars)
         };
         if profiling_enabled {
             query_parse_us = query_start.elapsed().as_micros() as u64;
@@ -1005,11 +1524,20 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
             method_str,
             "POST" | "PUT" | "PATCH" | "DELETE" | "OPTIONS" | "QUERY"
         );
-        let (post_params, files, raw_body) = if has_body {
+        let (post_params, files, raw_body, body_len) = if has_body {
             let body_read_start = Instant::now();
-            let body_bytes = match req.collect().await {
-                Ok(collected) => collected.to_bytes(),
-                Err(_) => {
+            let body = read_body(req.into_body(), self.body_prealloc_max, self.max_body_size);
+            let body_bytes = match body.await {
+                Ok(bytes) => bytes,
+                Err(BodyError::TooLarge) => {
+                    return full_to_flexible(
+                        Response::builder()
+                            .status(StatusCode::PAYLOAD_TOO_LARGE)
+                            .body(Full::new(EMPTY_BODY.clone()))
+                            .unwrap(),
+                    );
+                }
+                Err(BodyError::Read(_)) => {
                     return full_to_flexible(
                         Response::builder()
                             .status(StatusCode::BAD_REQUEST)
@@ -1026,63 +1554,125 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                 body_read_us = body_read_start.elapsed().as_micros() as u64;
             }
 
-            // Store raw body for php://input (QUERY method especially needs this)
-            let raw_body_bytes = body_bytes.clone();
-
-            let body_parse_start = Instant::now();
-            let result = if content_type_str.starts_with("application/x-www-form-urlencoded") {
-                let body_str = String::from_utf8_lossy(&body_bytes);
-                (parse_query_string(&body_str), Vec::new())
-            } else if content_type_str.starts_with("multipart/form-data") {
-                match parse_multipart(&content_type_str, body_bytes).await {
-                    Ok((params, uploaded_files)) => (params, uploaded_files),
-                    Err(e) => {
-                        return full_to_flexible(
-                            Response::builder()
-                                .status(StatusCode::BAD_REQUEST)
-                                .header(
-                                    header_names::CONTENT_TYPE.clone(),
-                                    header_values::TEXT_PLAIN.clone(),
-                                )
-                                .body(Full::new(Bytes::from(format!(
-                                    "Failed to parse multipart form: {}",
-                                    e
-                                ))))
-                                .unwrap(),
-                        );
-                    }
-                }
+            // Like PHP, only POST form bodies fill $_POST / $_FILES; other
+            // methods get the raw body via php://input only. FastCGI
+            // sends the body on as stdin and FPM parses it itself.
+            let forwards_raw = self.executor.forwards_raw_body();
+            let is_post = method_str == "POST" && !forwards_raw;
+            let is_multipart = is_post && content_type_str.starts_with("multipart/form-data");
+
+            // Keep the raw body for php://input (JSON, QUERY, ...). Multipart
+            // parts already land in $_POST and upload temp files, and PHP
+            // leaves php://input empty for them, so don't hold a second copy.
+            let body_len = body_bytes.len();
+            let raw_body_bytes = if forwards_raw {
+                Some(body_bytes.clone())
+            } else if is_multipart {
+                None
+            } else if self.raw_body_max > 0 && body_len > self.raw_body_max {
+                warn!(
+                    trace_id = trace_ctx.trace_id(),
+                    span_id = trace_ctx.span_id(),
+                    "Request body of {} bytes exceeds RAW_BODY_MAX ({}), php://input is empty",
+                    body_len,
+                    self.raw_body_max
+                );
+                None
             } else {
-                // For JSON, XML, etc. - body available via raw_body
-                (Vec::new(), Vec::new())
+                Some(body_bytes.clone())
             };
+            let body_parse_start = Instant::now();
+            let result =
+                if is_post && content_type_str.starts_with("application/x-www-form-urlencoded") {
+                    let body_str = String::from_utf8_lossy(&body_bytes);
+                    (
+                        parse_query_string_limited(&body_str, self.max_input_vars),
+                        Vec::new(),
+                    )
+                } else if is_multipart {
+                    match parse_multipart(
+                        &content_type_str,
+                        body_bytes,
+                        self.max_input_vars,
+                        &self.upload_types,
+                    )
+                    .await
+                    {
+                        Ok((params, uploaded_files)) => (params, uploaded_files),
+                        Err(e) => {
+                            return full_to_flexible(
+                                Response::builder()
+                                    .status(StatusCode::BAD_REQUEST)
+                                    .header(
+                                        header_names::CONTENT_TYPE.clone(),
+                                        header_values::TEXT_PLAIN.clone(),
+                                    )
+                                    .body(Full::new(Bytes::from(format!(
+                                        "Failed to parse multipart form: {}",
+                                        e
+                                    ))))
+                                    .unwrap(),
+                            );
+                        }
+                    }
+                } else {
+                    // JSON, XML, non-POST forms, etc. - body available via raw_body
+                    (Vec::new(), Vec::new())
+                };
             if profiling_enabled {
                 body_parse_us = body_parse_start.elapsed().as_micros() as u64;
             }
-            (result.0, result.1, Some(raw_body_bytes))
+            (result.0, result.1, raw_body_bytes, Some(body_len))
         } else {
-            (Vec::new(), Vec::new(), None)
+            (Vec::new(), Vec::new(), None, None)
         };
 
         // Resolve route (routing + file existence check combined)
         let path_start = Instant::now();
-        let route_result = if self.is_stub_mode {
+        let mut route_result = if self.is_stub_mode {
             // Stub mode: route to PHP without file checks
             RouteResult::Execute(format!("{}/index.php", self.document_root))
         } else {
             resolve_request(uri_path, &self.route_config, &self.file_cache)
         };
 
+        // Device-specific static variant (index.html -> index.mobile.html)
+        let mut vary_user_agent = false;
+        if let RouteResult::Serve(ref mut path) = route_result {
+            if !self.ua_variants.is_empty() {
+                let selected = self.ua_variants.select(path, &user_agent, |p| {
+                    self.file_cache.is_file(p) && self.route_config.contains(p)
+                });
+                if let Some(selected) = selected {
+                    *path = selected.into_owned();
+                    vary_user_agent = true;
+                }
+            }
+        }
+
         // Handle routing result
         let file_path_string = match &route_result {
-            RouteResult::Execute(path) | RouteResult::Serve(path) => path.clone(),
+            RouteResult::Execute(path) | RouteResult::Serve(path) | RouteResult::Listing(path) => {
+                path.clone()
+            }
             RouteResult::NotFound => {
                 return full_to_flexible(not_found_response());
             }
+            RouteResult::AddTrailingSlash => {
+                return full_to_flexible(trailing_slash_redirect(&uri));
+            }
+            RouteResult::Forbidden => {
+                return full_to_flexible(forbidden_response());
+            }
         };
         let file_path = Path::new(&file_path_string);
         let is_php = matches!(route_result, RouteResult::Execute(_));
 
+        // Workers not up yet, or draining: don't queue PHP work (static files still served)
+        if is_php && !self.lifecycle.is_running() {
+            return full_to_flexible(unavailable_response());
+        }
+
         // For profiling compatibility
         let file_cache_hit = false; // Cache hit info is now internal to resolve_request
         if profiling_enabled {
@@ -1127,9 +1717,12 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                     )
                 }
             } else {
-                // No Host header
+                // No Host header: fall back to SNI (TLS), then localhost
                 (
-                    server_var_values::LOCALHOST,
+                    match tls_info {
+                        Some(ref tls) if !tls.sni.is_empty() => Cow::Owned(tls.sni.clone()),
+                        _ => server_var_values::LOCALHOST,
+                    },
                     if tls_info.is_some() {
                         server_var_values::PORT_443
                     } else {
@@ -1181,7 +1774,20 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         // Server info (mix of static and dynamic values)
         server_vars.push((server_var_keys::SERVER_NAME, server_name));
         server_vars.push((server_var_keys::SERVER_PORT, server_port));
-        server_vars.push((server_var_keys::SERVER_ADDR, server_var_values::ADDR_0000));
+        server_vars.push((
+            server_var_keys::SERVER_ADDR,
+            local_addr.map_or(server_var_values::ADDR_0000, |addr| {
+                Cow::Owned(addr.ip().to_string())
+            }),
+        ));
+        server_vars.push((
+            server_var_keys::REQUEST_SCHEME,
+            if tls_info.is_some() {
+                server_var_values::SCHEME_HTTPS
+            } else {
+                server_var_values::SCHEME_HTTP
+            },
+        ));
         server_vars.push((
             server_var_keys::SERVER_SOFTWARE,
             server_var_values::SERVER_SOFTWARE,
@@ -1216,23 +1822,20 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         if !host_header.is_empty() {
             server_vars.push((server_var_keys::HTTP_HOST, Cow::Owned(host_header)));
         }
-        if !cookie_header_str.is_empty() {
-            server_vars.push((server_var_keys::HTTP_COOKIE, Cow::Owned(cookie_header_str)));
-        }
-        if !user_agent.is_empty() {
-            server_vars.push((server_var_keys::HTTP_USER_AGENT, Cow::Owned(user_agent)));
-        }
-        if !referer.is_empty() {
-            server_vars.push((server_var_keys::HTTP_REFERER, Cow::Owned(referer)));
-        }
-        if !accept_language.is_empty() {
+        if let Some(forwarded) = x_forwarded_host {
             server_vars.push((
-                server_var_keys::HTTP_ACCEPT_LANGUAGE,
-                Cow::Owned(accept_language),
+                server_var_keys::HTTP_X_FORWARDED_HOST,
+                Cow::Owned(forwarded),
             ));
         }
-        if !accept.is_empty() {
-            server_vars.push((server_var_keys::HTTP_ACCEPT, Cow::Owned(accept)));
+        if !cookie_header_str.is_empty() {
+            server_vars.push((server_var_keys::HTTP_COOKIE, Cow::Owned(cookie_header_str)));
+        }
+        server_vars.extend(header_vars);
+        if let Some((user, password)) = basic_auth {
+            server_vars.push((server_var_keys::AUTH_TYPE, server_var_values::AUTH_BASIC));
+            server_vars.push((server_var_keys::PHP_AUTH_USER, Cow::Owned(user)));
+            server_vars.push((server_var_keys::PHP_AUTH_PW, Cow::Owned(password)));
         }
 
         // HTTPS/TLS info (static value "on")
@@ -1244,6 +1847,12 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                     Cow::Owned(tls.protocol.clone()),
                 ));
             }
+            if !tls.sni.is_empty() {
+                server_vars.push((
+                    server_var_keys::SSL_SERVER_NAME,
+                    Cow::Owned(tls.sni.clone()),
+                ));
+            }
         }
 
         // W3C Trace Context for distributed tracing
@@ -1266,10 +1875,22 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                 Cow::Owned(parent.to_owned()),
             ));
         }
+        // B3 single header for PHP to forward on downstream calls
+        if self.trace_b3_egress {
+            server_vars.push((server_var_keys::HTTP_B3, Cow::Owned(trace_ctx.b3())));
+        }
+
+        // CSP nonce shared by $_SERVER and the Content-Security-Policy header
+        let csp_nonce = match self.csp_nonce {
+            Some(_) if is_php => Some(super::csp::generate_nonce()),
+            _ => None,
+        };
+        if let Some(ref nonce) = csp_nonce {
+            server_vars.push((server_var_keys::TOKIO_CSP_NONCE, Cow::Owned(nonce.clone())));
+        }
 
-        // Set CONTENT_LENGTH for requests with body
-        if let Some(ref body) = raw_body {
-            let len: usize = body.len();
+        // Set CONTENT_LENGTH for requests with body (php://input may be empty)
+        if let Some(len) = body_len {
             server_vars.push((server_var_keys::CONTENT_LENGTH, Cow::Owned(len.to_string())));
         }
 
@@ -1277,7 +1898,7 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
             server_vars_us = server_vars_start.elapsed().as_micros() as u64;
         }
 
-        if is_php {
+        let mut response = if is_php {
             let temp_files: Vec<String> = files
                 .iter()
                 .flat_map(|(_, file_vec): &(String, Vec<UploadedFile>)| {
@@ -1294,25 +1915,60 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
 
             let script_request = ScriptRequest {
                 script_path: file_path.to_string_lossy().into_owned(),
+                inline_code: None,
                 get_params,
                 post_params,
                 cookies,
                 server_vars,
                 files,
-                raw_body: raw_body.map(|b: Bytes| b.to_vec()),
+                // Takes the buffer without copying once the parse is done with it
+                raw_body: raw_body.map(Vec::from),
                 profile: profiling_enabled,
                 timeout: self.request_timeout.as_duration(),
                 received_at: request_time_float,
                 request_id: trace_ctx.short_id().to_string(),
                 trace_id: trace_ctx.trace_id().to_string(),
                 span_id: trace_ctx.span_id().to_string(),
+                priority,
+                output_limits: live.output_limits,
             };
 
+            // Shed the request if the queue ahead of it outlasts MAX_QUEUE_WAIT
+            // (high-priority requests skip that queue)
+            let workers = self.executor.concurrency().unwrap_or(self.php_workers);
+            if let Some(limit) = self.max_queue_wait.filter(|_| priority == Priority::Normal) {
+                if self.request_metrics.estimated_queue_wait(workers) > limit {
+                    self.request_metrics.inc_dropped();
+                    return full_to_flexible(
+                        Response::builder()
+                            .status(StatusCode::SERVICE_UNAVAILABLE)
+                            .header(
+                                header_names::RETRY_AFTER.clone(),
+                                header_values::ONE.clone(),
+                            )
+                            .body(Full::new(EMPTY_BODY.clone()))
+                            .unwrap(),
+                    );
+                }
+            }
+
+            // A request that finds a free worker times execution alone
+            let got_worker = self
+                .request_metrics
+                .pending_requests
+                .load(Ordering::Relaxed)
+                < workers;
+
             // Track pending requests for metrics (guard ensures cleanup on cancel)
             let _pending_guard = RequestMetrics::pending_guard(&self.request_metrics);
 
             // Use execute_with_auto_sse for automatic SSE detection based on Content-Type header
+            let execute_start = Instant::now();
             let execute_result = self.executor.execute_with_auto_sse(script_request).await;
+            if got_worker && matches!(execute_result, Ok(ExecuteResult::Normal(_))) {
+                self.request_metrics
+                    .record_php_service_time(execute_start.elapsed().as_micros() as u64);
+            }
 
             let response = match execute_result {
                 Ok(ExecuteResult::Normal(resp)) => {
@@ -1356,7 +2012,7 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                             profile.file_check_us = file_check_us;
 
                             // Add skipped actions based on request
-                            if self.rate_limiter.is_none() {
+                            if live.rate_limiter.is_none() {
                                 profile.skip(
                                     "Rate limit check",
                                     "Rate limiting disabled (RATE_LIMIT=0)",
@@ -1387,9 +2043,32 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                     #[cfg(feature = "debug-profile")]
                     if let Some(ref profile) = resp.profile {
                         profile.write_report(trace_ctx.short_id());
+                        self.last_profile.record(trace_ctx.short_id(), profile);
+                        if let Some(ref stats) = self.profile_stats {
+                            stats.record(profile);
+                        }
                     }
+                    #[cfg(feature = "debug-profile")]
+                    let profile_json = resp
+                        .profile
+                        .as_ref()
+                        .filter(|_| profile_json_requested)
+                        .and_then(|p| HeaderValue::from_bytes(p.to_json().as_bytes()).ok());
 
-                    full_to_flexible(from_script_response(resp, profiling_enabled, use_brotli))
+                    #[cfg(feature = "debug-profile")]
+                    let queue_wait_us = resp.profile.as_ref().map(|p| p.queue_wait_us);
+                    #[allow(unused_mut)]
+                    let mut response =
+                        from_script_response(resp, profiling_enabled, use_brotli, &live.response);
+                    #[cfg(feature = "debug-profile")]
+                    if let Some(us) = queue_wait_us {
+                        response.extensions_mut().insert(QueueWaitUs(us));
+                    }
+                    #[cfg(feature = "debug-profile")]
+                    if let Some(json) = profile_json {
+                        response.headers_mut().insert(X_PROFILE_JSON.clone(), json);
+                    }
+                    full_to_flexible(response)
                 }
                 Ok(ExecuteResult::Streaming {
                     headers,
@@ -1405,53 +2084,58 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                     streaming_to_flexible(response)
                 }
                 Err(e) => {
-                    if e.is_timeout() {
-                        // Request timed out
-                        warn!("Request timeout: {}", uri_path);
-                        full_to_flexible(
-                            Response::builder()
-                                .status(StatusCode::GATEWAY_TIMEOUT)
-                                .header(
-                                    header_names::CONTENT_TYPE.clone(),
-                                    header_values::TEXT_PLAIN.clone(),
-                                )
-                                .body(Full::new(Bytes::from_static(b"504 Gateway Timeout")))
-                                .unwrap(),
-                        )
-                    } else if e.is_queue_full() {
-                        // Queue is full - server overloaded
-                        self.request_metrics.inc_dropped();
-                        full_to_flexible(
-                            Response::builder()
-                                .status(StatusCode::SERVICE_UNAVAILABLE)
-                                .header(
-                                    header_names::CONTENT_TYPE.clone(),
-                                    header_values::TEXT_PLAIN.clone(),
-                                )
-                                .header(
-                                    header_names::RETRY_AFTER.clone(),
-                                    header_values::ONE.clone(),
-                                )
-                                .body(Full::new(Bytes::from_static(
-                                    b"503 Service Unavailable - Server overloaded",
-                                )))
-                                .unwrap(),
-                        )
-                    } else {
-                        error!("Script execution error: {}", e);
-                        full_to_flexible(
-                            Response::builder()
-                                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                                .header(
-                                    header_names::CONTENT_TYPE.clone(),
-                                    header_values::TEXT_HTML_UTF8.clone(),
-                                )
-                                .body(Full::new(Bytes::from(format!(
-                                    "<h1>500 Internal Server Error</h1><pre>{}</pre>",
-                                    e
-                                ))))
-                                .unwrap(),
-                        )
+                    self.request_metrics.record_executor_error(e.kind);
+                    match e.kind {
+                        ExecutorErrorKind::Timeout => {
+                            warn!("Request timeout: {}", uri_path);
+                            full_to_flexible(
+                                Response::builder()
+                                    .status(StatusCode::GATEWAY_TIMEOUT)
+                                    .body(Full::new(EMPTY_BODY.clone()))
+                                    .unwrap(),
+                            )
+                        }
+                        ExecutorErrorKind::QueueFull => {
+                            // Server overloaded
+                            self.request_metrics.inc_dropped();
+                            full_to_flexible(
+                                Response::builder()
+                                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                                    .header(
+                                        header_names::RETRY_AFTER.clone(),
+                                        header_values::ONE.clone(),
+                                    )
+                                    .body(Full::new(EMPTY_BODY.clone()))
+                                    .unwrap(),
+                            )
+                        }
+                        ExecutorErrorKind::WorkerCrashed => {
+                            error!(
+                                trace_id = trace_ctx.trace_id(),
+                                span_id = trace_ctx.span_id(),
+                                "PHP worker failed: {}",
+                                e
+                            );
+                            full_to_flexible(
+                                Response::builder()
+                                    .status(StatusCode::BAD_GATEWAY)
+                                    .body(Full::new(EMPTY_BODY.clone()))
+                                    .unwrap(),
+                            )
+                        }
+                        ExecutorErrorKind::ScriptError => {
+                            error!(
+                                trace_id = trace_ctx.trace_id(),
+                                span_id = trace_ctx.span_id(),
+                                "Script execution error: {}",
+                                e
+                            );
+                            // Without EXPOSE_ERRORS the body stays empty, so clients
+                            // get the error page or reason phrase
+                            full_to_flexible(script_error_response(
+                                self.expose_errors.then(|| e.to_string()).as_deref(),
+                            ))
+                        }
                     }
                 }
             };
@@ -1462,18 +2146,105 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
             }
 
             response
+        } else if matches!(route_result, RouteResult::Listing(_)) {
+            serve_directory_listing(file_path, uri_path).await
         } else {
             // serve_static_file returns FlexibleResponse directly
             // (handles both small in-memory files and large streaming files)
-            serve_static_file(
+            let response = serve_static_file(
                 file_path,
+                &self.mime_types,
                 use_brotli,
-                &self.static_cache_ttl,
+                &live.response,
+                &self.compressed_cache,
+                &live.static_cache_ttl,
+                self.immutable_assets.matches_file(
+                    &self.document_root,
+                    &file_path_string,
+                    uri_path,
+                ),
                 if_none_match.as_deref(),
                 if_modified_since.as_deref(),
             )
-            .await
+            .await;
+            self.request_metrics
+                .record_static(response.status() == StatusCode::NOT_MODIFIED);
+            response
+        };
+
+        if vary_user_agent {
+            response.headers_mut().append(
+                header_names::VARY.clone(),
+                HeaderValue::from_static("User-Agent"),
+            );
+        }
+
+        // Content-Security-Policy with this request's nonce, unless PHP sent its own
+        if let (Some(csp), Some(nonce)) = (&self.csp_nonce, &csp_nonce) {
+            if !response
+                .headers()
+                .contains_key(&header_names::CONTENT_SECURITY_POLICY)
+            {
+                if let Some(value) = csp.policy_header(nonce) {
+                    response
+                        .headers_mut()
+                        .insert(header_names::CONTENT_SECURITY_POLICY.clone(), value);
+                }
+            }
         }
+
+        // Canonical URL for successful responses, unless PHP declared its own
+        if response.status().is_success() {
+            if let Some(link) = self.canonical_links.get(uri_path) {
+                let has_canonical = response
+                    .headers()
+                    .get_all(&header_names::LINK)
+                    .iter()
+                    .any(|v| v.to_str().is_ok_and(|v| v.contains("canonical")));
+                if !has_canonical {
+                    response
+                        .headers_mut()
+                        .append(header_names::LINK.clone(), link.clone());
+                }
+            }
+        }
+
+        // Expose Resource Timing details to the configured cross-origin frontends
+        if let Some((ref tao, ref value)) = self.timing_allow_origin {
+            if tao.applies_to(is_php) {
+                response
+                    .headers_mut()
+                    .insert(TIMING_ALLOW_ORIGIN.clone(), value.clone());
+            }
+        }
+
+        response
+    }
+
+    /// Fill an empty 4xx/5xx body with the custom error page (HTML clients
+    /// only) or the default reason phrase. Bodies set by PHP are kept.
+    fn apply_error_page(&self, response: FlexibleResponse, accepts_html: bool) -> FlexibleResponse {
+        let status = response.status().as_u16();
+        if !(400..600).contains(&status) || response.body().size_hint().exact() != Some(0) {
+            return response;
+        }
+
+        let (content_type, body) = match self.error_pages.get(status).filter(|_| accepts_html) {
+            Some(html) => (header_values::TEXT_HTML_UTF8.clone(), html.clone()),
+            None => (
+                header_values::TEXT_PLAIN_UTF8.clone(),
+                Bytes::from_static(status_reason_phrase(status).as_bytes()),
+            ),
+        };
+        let (mut parts, _) = response.into_parts();
+        parts
+            .headers
+            .insert(header_names::CONTENT_TYPE.clone(), content_type);
+        parts.headers.insert(
+            header_names::CONTENT_LENGTH.clone(),
+            HeaderValue::from(body.len()),
+        );
+        full_to_flexible(Response::from_parts(parts, Full::new(body)))
     }
 
     /// Handle an SSE (Server-Sent Events) streaming request.
@@ -1482,25 +2253,21 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
     /// It uses the streaming executor path and returns a streaming response.
     async fn handle_sse_request(
         &self,
-        req: Request<IncomingBody>,
+        mut req: Request<IncomingBody>,
         remote_addr: SocketAddr,
         tls_info: Option<TlsInfo>,
+        trace_ctx: TraceContext,
     ) -> Result<FlexibleResponse, Infallible> {
         let request_start = Instant::now();
-        let trace_ctx = TraceContext::from_headers(req.headers());
+        let live = Arc::clone(&*self.live_config.borrow());
 
-        // Get request ID
-        let request_id_from_header: Option<String> = req
-            .headers()
-            .get(&*X_REQUEST_ID)
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_owned());
-        let request_id: &str = request_id_from_header
-            .as_deref()
-            .unwrap_or_else(|| trace_ctx.short_id());
+        // Get request ID (replaced on the request if missing or invalid)
+        let request_id = ensure_request_id(req.headers_mut(), || trace_ctx.short_id().to_string());
+        let request_id: &str = &request_id;
 
         // Increment request method metrics
-        self.request_metrics.increment_method(req.method());
+        self.request_metrics
+            .increment_method(req.method(), req.version(), tls_info.is_some());
 
         let method = req.method().clone();
         let uri = req.uri().clone();
@@ -1517,7 +2284,7 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         // SSE only works for PHP scripts (RouteResult::Execute)
         let file_path_string = match route_result {
             RouteResult::Execute(path) => path,
-            RouteResult::Serve(_) => {
+            RouteResult::Serve(_) | RouteResult::Listing(_) => {
                 // Return error for non-PHP SSE requests
                 let response = Response::builder()
                     .status(StatusCode::BAD_REQUEST)
@@ -1532,9 +2299,18 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                 return Ok(full_to_flexible(response));
             }
             RouteResult::NotFound => {
-                return Ok(full_to_flexible(not_found_response()));
+                return Ok(self.apply_error_page(full_to_flexible(not_found_response()), false));
+            }
+            RouteResult::AddTrailingSlash => {
+                return Ok(full_to_flexible(trailing_slash_redirect(&uri)));
+            }
+            RouteResult::Forbidden => {
+                return Ok(full_to_flexible(forbidden_response()));
             }
         };
+        if !self.lifecycle.is_running() {
+            return Ok(self.apply_error_page(full_to_flexible(unavailable_response()), false));
+        }
         let file_path = Path::new(&file_path_string);
 
         // Build minimal server vars for SSE (optimized with static values)
@@ -1580,13 +2356,23 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                     Cow::Owned(tls.protocol.clone()),
                 ));
             }
+            if !tls.sni.is_empty() {
+                server_vars.push((
+                    server_var_keys::SSL_SERVER_NAME,
+                    Cow::Owned(tls.sni.clone()),
+                ));
+            }
+        }
+
+        if self.trace_b3_egress {
+            server_vars.push((server_var_keys::HTTP_B3, Cow::Owned(trace_ctx.b3())));
         }
 
         // Parse query string and cookies for SSE
         let get_params = if query_string.is_empty() {
             Vec::new()
         } else {
-            parse_query_string(query_string)
+            parse_query_string_limited(query_string, self.max_input_vars)
         };
 
         let cookie_header_str = req
@@ -1597,11 +2383,12 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
         let cookies = if cookie_header_str.is_empty() {
             Vec::new()
         } else {
-            parse_cookies(cookie_header_str)
+            parse_cookies(cookie_header_str, self.max_input_vars)
         };
 
         let script_request = ScriptRequest {
             script_path: file_path.to_string_lossy().into_owned(),
+            inline_code: None,
             get_params,
             post_params: Vec::new(),
             cookies,
@@ -1614,12 +2401,15 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
             request_id: request_id.to_string(),
             trace_id: trace_ctx.trace_id().to_string(),
             span_id: trace_ctx.span_id().to_string(),
+            // Long-lived streams stay out of the reserved lane
+            priority: Priority::Normal,
+            output_limits: live.output_limits,
         };
 
         // Execute streaming request
         match self
             .executor
-            .execute_streaming(script_request, DEFAULT_STREAM_BUFFER_SIZE)
+            .execute_streaming(script_request, live.output_limits.stream_buffer_size)
             .await
         {
             Ok(stream_rx) => {
@@ -1627,7 +2417,7 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                 self.request_metrics.sse_connection_started();
 
                 // Build SSE headers
-                let mut headers = vec![
+                let headers = vec![
                     ("Content-Type".to_string(), "text/event-stream".to_string()),
                     ("Cache-Control".to_string(), "no-cache".to_string()),
                     ("Connection".to_string(), "keep-alive".to_string()),
@@ -1639,9 +2429,6 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
                     ),
                 ];
 
-                // Add Server header
-                headers.push(("Server".to_string(), "tokio_php/0.1.0".to_string()));
-
                 let response = streaming_response(200, headers, stream_rx);
 
                 // Record metrics
@@ -1653,15 +2440,12 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
             }
             Err(e) => {
                 // Streaming not supported or error
+                error!("SSE error: {}", e);
                 let response = Response::builder()
                     .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .header(
-                        header_names::CONTENT_TYPE.clone(),
-                        header_values::TEXT_PLAIN.clone(),
-                    )
-                    .body(Full::new(Bytes::from(format!("SSE error: {}", e))))
+                    .body(Full::new(EMPTY_BODY.clone()))
                     .unwrap();
-                Ok(full_to_flexible(response))
+                Ok(self.apply_error_page(full_to_flexible(response), false))
             }
         }
     }
@@ -1671,6 +2455,34 @@ impl<E: ScriptExecutor + 'static> ConnectionContext<E> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_http_header_vars() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_static("curl/8"));
+        headers.insert("authorization", HeaderValue::from_static("Bearer t"));
+        headers.append("x-tag", HeaderValue::from_static("a"));
+        headers.append("x-tag", HeaderValue::from_static("b"));
+        headers.insert("x_tag", HeaderValue::from_static("spoof"));
+        headers.insert("connection", HeaderValue::from_static("close"));
+        headers.insert("proxy", HeaderValue::from_static("http://evil"));
+        headers.insert("content-length", HeaderValue::from_static("3"));
+        headers.insert("host", HeaderValue::from_static("example.com"));
+
+        let mut vars: Vec<(String, String)> = http_header_vars(&headers)
+            .into_iter()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        vars.sort();
+        assert_eq!(
+            vars,
+            [
+                ("HTTP_AUTHORIZATION".to_string(), "Bearer t".to_string()),
+                ("HTTP_USER_AGENT".to_string(), "curl/8".to_string()),
+                ("HTTP_X_TAG".to_string(), "a, b".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn test_iso8601_timestamp_format() {
         // Test a known timestamp: 2024-01-15T10:50:45.123Z
@@ -1753,6 +2565,24 @@ mod tests {
         assert_eq!(s, "2001:db8::1");
     }
 
+    #[test]
+    fn test_classify_connection_error() {
+        assert_eq!(
+            classify_connection_error(
+                "Os { code: 104, kind: ConnectionReset, message: \"Connection reset by peer\" }"
+            ),
+            CloseReason::Reset
+        );
+        assert_eq!(
+            classify_connection_error("hyper::Error(HeaderTimeout)"),
+            CloseReason::Timeout
+        );
+        assert_eq!(
+            classify_connection_error("hyper::Error(Parse(Method))"),
+            CloseReason::Protocol
+        );
+    }
+
     #[test]
     fn test_http_versions_from_hyper() {
         assert_eq!(
@@ -1772,4 +2602,195 @@ mod tests {
             "HTTP/3.0"
         );
     }
+
+    /// Serve one connection whose handler sleeps for `?{ms}`, returning the
+    /// address, the shutdown trigger and the server task. A non-zero
+    /// `keepalive` closes the connection once it has been idle that long.
+    async fn sleepy_server(
+        window: Duration,
+        keepalive: Duration,
+    ) -> (
+        std::net::SocketAddr,
+        watch::Sender<bool>,
+        tokio::task::JoinHandle<bool>,
+    ) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let activity = ConnectionActivity::new();
+            let tracker = Arc::clone(&activity);
+            let service = service_fn(move |req: Request<IncomingBody>| {
+                let guard = tracker.begin();
+                async move {
+                    let ms = req.uri().query().and_then(|q| q.parse().ok()).unwrap_or(0);
+                    tokio::time::sleep(Duration::from_millis(ms)).await;
+                    let body = Full::new(Bytes::from_static(b"ok"));
+                    Ok::<_, Infallible>(Response::new(TrackedBody::new(body, guard)))
+                }
+            });
+            let builder = auto::Builder::new(TokioExecutor::new());
+            let conn = builder.serve_connection(TokioIo::new(stream), service);
+            let idle = async {
+                if keepalive.is_zero() {
+                    std::future::pending::<()>().await;
+                }
+                activity.idle(keepalive).await;
+            };
+            // true if the connection finished within the window
+            serve_until_shutdown(conn, shutdown_rx, idle, window, |c| c.graceful_shutdown())
+                .await
+                .is_some()
+        });
+        (addr, shutdown_tx, server)
+    }
+
+    /// `sleepy_server` with a connected HTTP/2 client.
+    async fn h2_connection(
+        window: Duration,
+    ) -> (
+        h2::client::SendRequest<Bytes>,
+        watch::Sender<bool>,
+        tokio::task::JoinHandle<bool>,
+    ) {
+        let (addr, shutdown_tx, server) = sleepy_server(window, Duration::ZERO).await;
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        let (client, connection) = h2::client::handshake(tcp).await.unwrap();
+        tokio::spawn(async move {
+            let _ = connection.await;
+        });
+        (client, shutdown_tx, server)
+    }
+
+    fn h2_get(client: &mut h2::client::SendRequest<Bytes>, ms: u64) -> h2::client::ResponseFuture {
+        let req = Request::get(format!("http://localhost/?{ms}"))
+            .body(())
+            .unwrap();
+        client.send_request(req, true).unwrap().0
+    }
+
+    #[tokio::test]
+    async fn test_goaway_refuses_new_streams_and_finishes_in_flight() {
+        let (mut client, shutdown_tx, server) = h2_connection(Duration::from_secs(5)).await;
+
+        let in_flight = h2_get(&mut client, 300);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown_tx.send(true).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // GOAWAY received: no new streams on this connection
+        let err = client.clone().ready().await.expect_err("stream refused");
+        assert!(err.is_go_away());
+
+        // The stream opened before shutdown still completes
+        assert_eq!(in_flight.await.unwrap().status(), StatusCode::OK);
+
+        let start = Instant::now();
+        assert!(server.await.unwrap());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_goaway_window_closes_slow_streams() {
+        let (mut client, shutdown_tx, server) = h2_connection(Duration::from_millis(200)).await;
+
+        let slow = h2_get(&mut client, 5_000);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let start = Instant::now();
+        shutdown_tx.send(true).unwrap();
+
+        // Window elapsed: connection dropped before the stream finished
+        assert!(!server.await.unwrap());
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(slow.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_http1_long_poll_finishes_with_connection_close() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (addr, shutdown_tx, server) =
+            sleepy_server(Duration::from_secs(5), Duration::ZERO).await;
+        let mut tcp = TcpStream::connect(addr).await.unwrap();
+        tcp.write_all(b"GET /?300 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown_tx.send(true).unwrap();
+
+        // The long poll is answered, told not to reuse the connection, then closed
+        let mut response = String::new();
+        tcp.read_to_string(&mut response).await.unwrap();
+        let response = response.to_ascii_lowercase();
+        assert!(response.starts_with("http/1.1 200 ok"), "{response}");
+        assert!(response.contains("connection: close\r\n"), "{response}");
+        assert!(response.ends_with("\r\n\r\nok"));
+        assert!(server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_serve_until_shutdown_without_signal() {
+        let (_, shutdown_rx) = watch::channel(false);
+        let result = serve_until_shutdown(
+            async { 42 },
+            shutdown_rx,
+            std::future::pending(),
+            Duration::ZERO,
+            |_| unreachable!(),
+        )
+        .await;
+        assert_eq!(result, Some(42));
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_timeout_closes_idle_http1_connection() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let keepalive = Duration::from_millis(200);
+        let (addr, _shutdown_tx, server) = sleepy_server(Duration::from_secs(5), keepalive).await;
+        let mut tcp = TcpStream::connect(addr).await.unwrap();
+
+        // A request running past the keep-alive timeout is answered in full
+        tcp.write_all(b"GET /?500 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = [0u8; 1024];
+        let n = tcp.read(&mut buf).await.unwrap();
+        let response = String::from_utf8_lossy(&buf[..n]).to_ascii_lowercase();
+        assert!(response.starts_with("http/1.1 200 ok"), "{response}");
+        assert!(response.ends_with("\r\n\r\nok"), "{response}");
+        assert!(!response.contains("connection: close"), "{response}");
+
+        // Then the silent keep-alive connection is closed
+        let idle_since = Instant::now();
+        assert_eq!(tcp.read(&mut buf).await.unwrap(), 0);
+        let idle = idle_since.elapsed();
+        assert!(idle >= Duration::from_millis(150), "{idle:?}");
+        assert!(idle < Duration::from_secs(2), "{idle:?}");
+        assert!(server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_timeout_closes_idle_http2_connection() {
+        let keepalive = Duration::from_millis(200);
+        let (addr, _shutdown_tx, server) = sleepy_server(Duration::from_secs(5), keepalive).await;
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        let (mut client, connection) = h2::client::handshake(tcp).await.unwrap();
+        tokio::spawn(async move {
+            let _ = connection.await;
+        });
+
+        // An open stream outlives the timeout
+        let slow = h2_get(&mut client, 500);
+        assert_eq!(slow.await.unwrap().status(), StatusCode::OK);
+
+        // No streams left: GOAWAY once the timeout passes
+        let start = Instant::now();
+        assert!(server.await.unwrap());
+        assert!(start.elapsed() < Duration::from_secs(2));
+        let err = client.ready().await.expect_err("connection closed");
+        assert!(err.is_go_away() || err.is_io(), "{err:?}");
+    }
 }
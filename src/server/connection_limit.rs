@@ -0,0 +1,219 @@
+//! Per-worker and server-wide connection caps.
+//!
+//! With SO_REUSEPORT every worker runs its own accept loop, and the kernel
+//! hashes connections to listeners without regard to load. A worker that is
+//! at its cap stops calling `accept()`: new connections wait in that
+//! listener's backlog until one of its connections closes.
+//!
+//! The server-wide cap (`MAX_CONNECTIONS`) is one semaphore shared by all
+//! accept loops. Connections over it are closed right after accept, so the
+//! overload is visible to clients and in metrics instead of piling up in
+//! the kernel backlogs.
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{info, warn};
+
+/// Caps concurrent connections accepted by one worker.
+#[derive(Clone)]
+pub struct ConnectionLimit {
+    /// `None` when unlimited.
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl ConnectionLimit {
+    /// Limit to `max` connections; 0 = unlimited.
+    pub fn new(max: usize) -> Self {
+        Self {
+            semaphore: (max > 0).then(|| Arc::new(Semaphore::new(max))),
+        }
+    }
+
+    /// Accept the next connection once the worker is below its cap.
+    ///
+    /// The returned permit must live as long as the connection.
+    pub async fn accept(
+        &self,
+        listener: &TcpListener,
+    ) -> io::Result<(TcpStream, SocketAddr, Option<OwnedSemaphorePermit>)> {
+        let permit = match &self.semaphore {
+            Some(sem) => Some(
+                Arc::clone(sem)
+                    .acquire_owned()
+                    .await
+                    .expect("connection semaphore is never closed"),
+            ),
+            None => None,
+        };
+        let (stream, addr) = listener.accept().await?;
+        Ok((stream, addr, permit))
+    }
+}
+
+/// Returned when the server-wide connection cap is reached.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LimitReached;
+
+/// Caps concurrent connections across all workers.
+pub struct GlobalConnectionLimit {
+    max: usize,
+    /// `None` when unlimited.
+    semaphore: Option<Arc<Semaphore>>,
+    /// Set while connections are being rejected, so each episode logs once.
+    at_limit: AtomicBool,
+}
+
+impl GlobalConnectionLimit {
+    /// Limit to `max` connections; 0 = unlimited.
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            semaphore: (max > 0).then(|| Arc::new(Semaphore::new(max))),
+            at_limit: AtomicBool::new(false),
+        }
+    }
+
+    /// Claim a slot for a just-accepted connection.
+    ///
+    /// `Ok(None)` when there is no cap. The permit must live as long as the
+    /// connection.
+    pub fn try_acquire(&self) -> Result<Option<OwnedSemaphorePermit>, LimitReached> {
+        let Some(sem) = &self.semaphore else {
+            return Ok(None);
+        };
+        match Arc::clone(sem).try_acquire_owned() {
+            Ok(permit) => {
+                if self.at_limit.swap(false, Ordering::Relaxed) {
+                    info!("Below MAX_CONNECTIONS ({}) again", self.max);
+                }
+                Ok(Some(permit))
+            }
+            Err(_) => {
+                if !self.at_limit.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        "MAX_CONNECTIONS ({}) reached, closing new connections",
+                        self.max
+                    );
+                }
+                Err(LimitReached)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    /// Accept loop that hands each connection (and its permit) to the test.
+    async fn spawn_worker(
+        max: usize,
+    ) -> (
+        SocketAddr,
+        mpsc::UnboundedReceiver<(TcpStream, Option<OwnedSemaphorePermit>)>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let limit = ConnectionLimit::new(max);
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _, permit)) = limit.accept(&listener).await {
+                if tx.send((stream, permit)).is_err() {
+                    break;
+                }
+            }
+        });
+        (addr, rx)
+    }
+
+    async fn accepted_within(
+        rx: &mut mpsc::UnboundedReceiver<(TcpStream, Option<OwnedSemaphorePermit>)>,
+    ) -> Option<(TcpStream, Option<OwnedSemaphorePermit>)> {
+        tokio::time::timeout(Duration::from_millis(200), rx.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    #[tokio::test]
+    async fn test_worker_stops_accepting_at_cap() {
+        let (addr, mut rx) = spawn_worker(2).await;
+
+        // Connects succeed (kernel backlog), but only two are accepted
+        let _clients: Vec<TcpStream> =
+            futures_util::future::try_join_all((0..3).map(|_| TcpStream::connect(addr)))
+                .await
+                .unwrap();
+
+        let first = accepted_within(&mut rx).await.expect("first accepted");
+        let _second = accepted_within(&mut rx).await.expect("second accepted");
+        assert!(accepted_within(&mut rx).await.is_none());
+
+        // Closing one connection frees a slot for the queued one
+        drop(first);
+        assert!(accepted_within(&mut rx).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_zero_means_unlimited() {
+        let (addr, mut rx) = spawn_worker(0).await;
+
+        let mut clients = Vec::new();
+        for _ in 0..5 {
+            clients.push(TcpStream::connect(addr).await.unwrap());
+        }
+        for _ in 0..5 {
+            let (_, permit) = accepted_within(&mut rx).await.expect("accepted");
+            assert!(permit.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_global_limit_shared_across_listeners() {
+        let global = Arc::new(GlobalConnectionLimit::new(2));
+
+        // Two accept loops, like two SO_REUSEPORT workers
+        let mut addrs = Vec::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for _ in 0..2 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            addrs.push(listener.local_addr().unwrap());
+            let global = Arc::clone(&global);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let _ = tx.send(global.try_acquire().map(|permit| (stream, permit)));
+                }
+            });
+        }
+
+        let _a = TcpStream::connect(addrs[0]).await.unwrap();
+        let first = rx.recv().await.unwrap().expect("first admitted");
+        let _b = TcpStream::connect(addrs[1]).await.unwrap();
+        let _second = rx.recv().await.unwrap().expect("second admitted");
+
+        // Third connection on either listener is over the shared cap
+        let _c = TcpStream::connect(addrs[0]).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().unwrap_err(), LimitReached);
+
+        // Closing one connection frees a slot for the next
+        drop(first);
+        let _d = TcpStream::connect(addrs[1]).await.unwrap();
+        assert!(rx.recv().await.unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_global_zero_means_unlimited() {
+        let global = GlobalConnectionLimit::new(0);
+        for _ in 0..100 {
+            assert_eq!(global.try_acquire().unwrap().map(|_| ()), None);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Per-request Content-Security-Policy nonces.
+//!
+//! Each PHP request gets a fresh random nonce in `$_SERVER['TOKIO_CSP_NONCE']`.
+//! With `CSP_POLICY` set, the server also sends `Content-Security-Policy`
+//! with every `{{nonce}}` replaced by that value, so the header and the
+//! `<script nonce="...">` tags emitted by PHP always agree.
+
+use hyper::header::HeaderValue;
+
+/// Placeholder substituted in the `CSP_POLICY` template.
+pub const NONCE_PLACEHOLDER: &str = "{{nonce}}";
+
+/// CSP nonce settings.
+#[derive(Clone, Debug, Default)]
+pub struct CspNonce {
+    /// Header template; `None` = expose the nonce to PHP only.
+    policy: Option<String>,
+}
+
+impl CspNonce {
+    /// Validate the optional policy template.
+    pub fn new(policy: Option<String>) -> Result<Self, String> {
+        if let Some(ref template) = policy {
+            let sample = template.replace(NONCE_PLACEHOLDER, &generate_nonce());
+            HeaderValue::from_str(&sample)
+                .map_err(|_| "policy contains characters not allowed in a header".to_string())?;
+        }
+        Ok(Self { policy })
+    }
+
+    /// `Content-Security-Policy` value for a request's nonce, if configured.
+    pub fn policy_header(&self, nonce: &str) -> Option<HeaderValue> {
+        let template = self.policy.as_deref()?;
+        HeaderValue::from_str(&template.replace(NONCE_PLACEHOLDER, nonce)).ok()
+    }
+}
+
+/// Fresh nonce: 32 hex chars from a random (v4) UUID.
+#[inline]
+pub fn generate_nonce() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nonces_are_unique() {
+        let a = generate_nonce();
+        let b = generate_nonce();
+        assert_eq!(a.len(), 32);
+        assert!(a.bytes().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_policy_substitution() {
+        let csp = CspNonce::new(Some(
+            "script-src 'nonce-{{nonce}}'; style-src 'nonce-{{nonce}}'".into(),
+        ))
+        .unwrap();
+        assert_eq!(
+            csp.policy_header("abc123").unwrap(),
+            "script-src 'nonce-abc123'; style-src 'nonce-abc123'"
+        );
+
+        // Nonce for PHP only, no header
+        assert!(CspNonce::new(None).unwrap().policy_header("abc").is_none());
+    }
+
+    #[test]
+    fn test_invalid_policy() {
+        assert!(CspNonce::new(Some("script-src 'self'\n".into())).is_err());
+    }
+}
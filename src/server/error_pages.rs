@@ -2,6 +2,9 @@
 //!
 //! Loads HTML error pages from a directory at startup and serves them
 //! for 4xx/5xx responses when the client accepts text/html.
+//! `{{status}}` and `{{message}}` in a page are replaced at load time with
+//! the status code and its reason phrase, so one template can be copied for
+//! every status. Error details are never substituted; they stay in the logs.
 
 use bytes::Bytes;
 use std::collections::HashMap;
@@ -27,7 +30,8 @@ impl ErrorPages {
     /// Load error pages from a directory.
     ///
     /// Scans the directory for files matching `{status_code}.html` pattern
-    /// (e.g., `404.html`, `500.html`) and caches them in memory.
+    /// (e.g., `404.html`, `500.html`), renders their placeholders and caches
+    /// them in memory.
     pub fn from_directory(dir: &str) -> Self {
         let path = Path::new(dir);
 
@@ -79,7 +83,7 @@ impl ErrorPages {
                         file_path.display(),
                         content.len()
                     );
-                    pages.insert(status_code, Bytes::from(content));
+                    pages.insert(status_code, render_template(content, status_code));
                 }
                 Err(e) => {
                     warn!("Failed to read error page {}: {}", file_path.display(), e);
@@ -104,6 +108,21 @@ impl ErrorPages {
     }
 }
 
+/// Replace `{{status}}` and `{{message}}` in a page. Pages without
+/// placeholders (or not valid UTF-8) are kept byte for byte.
+fn render_template(content: Vec<u8>, status: u16) -> Bytes {
+    if !content.windows(2).any(|w| w == b"{{") {
+        return Bytes::from(content);
+    }
+    match String::from_utf8(content) {
+        Ok(html) => Bytes::from(
+            html.replace("{{status}}", &status.to_string())
+                .replace("{{message}}", status_reason_phrase(status)),
+        ),
+        Err(e) => Bytes::from(e.into_bytes()),
+    }
+}
+
 /// Get the default reason phrase for an HTTP status code.
 /// Returns human-readable text like "Not Found" for 404, "Bad Gateway" for 502.
 #[inline]
@@ -189,4 +208,54 @@ mod tests {
         assert!(!accepts_html("application/json"));
         assert!(!accepts_html("text/plain"));
     }
+
+    #[test]
+    fn test_render_template() {
+        let page = render_template(
+            b"<title>{{status}} {{message}}</title><h1>{{status}}</h1>".to_vec(),
+            503,
+        );
+        assert_eq!(
+            page,
+            "<title>503 Service Unavailable</title><h1>503</h1>".as_bytes()
+        );
+
+        // Unknown placeholders and plain pages are left alone
+        assert_eq!(
+            render_template(b"{{other}}".to_vec(), 500),
+            "{{other}}".as_bytes()
+        );
+        assert_eq!(
+            render_template(b"<h1>Oops</h1>".to_vec(), 500),
+            "<h1>Oops</h1>".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_from_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = "<h1>{{status}} {{message}}</h1>";
+        for name in ["404.html", "500.html", "502.html", "503.html", "504.html"] {
+            std::fs::write(dir.path().join(name), template).unwrap();
+        }
+        std::fs::write(dir.path().join("200.html"), template).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), template).unwrap();
+
+        let pages = ErrorPages::from_directory(dir.path().to_str().unwrap());
+        assert_eq!(pages.get(404).unwrap(), "<h1>404 Not Found</h1>".as_bytes());
+        assert_eq!(
+            pages.get(500).unwrap(),
+            "<h1>500 Internal Server Error</h1>".as_bytes()
+        );
+        assert_eq!(
+            pages.get(502).unwrap(),
+            "<h1>502 Bad Gateway</h1>".as_bytes()
+        );
+        assert_eq!(
+            pages.get(504).unwrap(),
+            "<h1>504 Gateway Timeout</h1>".as_bytes()
+        );
+        assert!(pages.get(200).is_none());
+        assert!(pages.get(410).is_none());
+    }
 }
@@ -2,13 +2,15 @@
 
 use std::convert::Infallible;
 use std::fs;
+use std::future::Future;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
-use http_body_util::Full;
+use futures_util::future::BoxFuture;
+use http_body_util::{BodyExt, Full, Limited};
 use hyper::body::Incoming as IncomingBody;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
@@ -16,6 +18,27 @@ use hyper::{Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
 use serde::Serialize;
 use tokio::net::TcpListener;
+use tokio::sync::{watch, Mutex};
+
+use super::config::{ReloadableConfig, Sizing};
+use super::internal_access::InternalAccess;
+use super::kill_switch::KillSwitch;
+use super::lifecycle::{Lifecycle, Phase};
+use super::memory_pressure::MemoryPressure;
+use super::readiness::Readiness;
+use super::request::parse_query_string;
+use super::route_label::RouteCounts;
+use crate::diagnostics::{
+    DiagnosticCollector, TrafficSnapshot, WorkerActivityStats, WorkerSnapshot,
+};
+use crate::executor::ExecutorErrorKind;
+use crate::profiler::{LastProfile, ProfileStats};
+
+/// Largest `POST /loglevel` body accepted.
+const LOGLEVEL_BODY_MAX: usize = 4096;
+
+/// Source of per-worker activity for /diagnostics (the executor's pool).
+pub type WorkerActivitySource = dyn Fn() -> Vec<WorkerActivityStats> + Send + Sync;
 
 // =============================================================================
 // Server Configuration Info (for /config endpoint)
@@ -46,6 +69,65 @@ pub struct ServerConfigInfo {
     pub tls_key: String,
     pub log_level: String,
     pub service_name: String,
+    /// Startup sizing decision, exported as gauges (SIZING_REPORT)
+    #[serde(skip)]
+    pub sizing: Option<Sizing>,
+    /// Queue saturation state behind /ready
+    #[serde(skip)]
+    pub readiness: Arc<Readiness>,
+    /// Lifecycle phase behind /health/startup and /ready
+    #[serde(skip)]
+    pub lifecycle: Arc<Lifecycle>,
+    /// Memory pressure level and shed count for /metrics
+    #[serde(skip)]
+    pub memory_pressure: Arc<MemoryPressure>,
+    /// Rate-limited report behind /diagnostics
+    #[serde(skip)]
+    pub diagnostics: Arc<Diagnostics>,
+    /// Per-worker activity reported in /diagnostics
+    #[serde(skip)]
+    pub worker_activity: Arc<WorkerActivitySource>,
+    /// Most recent request profile behind /profile/last (debug-profile builds)
+    #[serde(skip)]
+    pub last_profile: Arc<LastProfile>,
+    /// Per-phase percentiles behind /profile/stats (None unless PROFILE_STATS)
+    #[serde(skip)]
+    pub profile_stats: Option<Arc<ProfileStats>>,
+    /// Token and network checks for non-probe endpoints (never serialized)
+    #[serde(skip)]
+    pub access: InternalAccess,
+    /// Settings swapped by SIGHUP, re-read for every /config response
+    #[serde(skip)]
+    pub live_config: watch::Receiver<Arc<ReloadableConfig>>,
+}
+
+impl ServerConfigInfo {
+    /// Fill the SIGHUP-reloadable fields from `live`.
+    pub fn apply_live(&mut self, live: &ReloadableConfig) {
+        self.static_cache_ttl = super::format_optional_duration(&live.static_cache_ttl);
+        self.access_log = if live.access_log_enabled { "1" } else { "0" }.to_string();
+        self.rate_limit = live
+            .rate_limiter
+            .as_ref()
+            .map(|r| r.limit().to_string())
+            .unwrap_or_else(|| "0".to_string());
+        self.rate_window = live
+            .rate_limiter
+            .as_ref()
+            .map(|r| r.window_secs().to_string())
+            .unwrap_or_else(|| "60".to_string());
+        self.log_level = crate::logging::log_filter()
+            .or_else(crate::logging::configured_filter)
+            .unwrap_or_else(|| "info".to_string());
+    }
+
+    /// Current values, including settings changed by a reload since startup.
+    fn current(&self) -> Self {
+        let live = Arc::clone(&self.live_config.borrow());
+        let mut info = self.clone();
+        info.apply_live(&live);
+        info
+    }
 }
 
 // =============================================================================
@@ -133,6 +215,8 @@ pub struct RequestMetrics {
     pub options: AtomicUsize,
     pub patch: AtomicUsize,
     pub other: AtomicUsize,
+    // By HTTP version and TLS, indexed like PROTOCOL_LABELS
+    by_protocol: [AtomicUsize; 6],
     // By status code class
     pub status_2xx: AtomicUsize,
     pub status_3xx: AtomicUsize,
@@ -144,11 +228,24 @@ pub struct RequestMetrics {
     // Response time tracking (microseconds)
     pub total_response_time_us: AtomicU64,
     pub response_count: AtomicU64,
+    pub slow_requests: AtomicU64,
+    // Recent PHP execution time (µs) of requests that got a worker at once,
+    // moving average over about PHP_SERVICE_WINDOW requests
+    php_service_us: AtomicU64,
+    // Response time histogram, indexed like REQUEST_DURATION_BUCKETS_US
+    duration_buckets: [AtomicU64; 12],
     // SSE metrics
     pub sse_active: AtomicUsize,
     pub sse_total: AtomicU64,
     pub sse_chunks: AtomicU64,
     pub sse_bytes: AtomicU64,
+    // Static file responses (diagnostics)
+    pub static_responses: AtomicU64,
+    pub static_not_modified: AtomicU64,
+    // Executor failures, indexed like ExecutorErrorKind::ALL
+    executor_errors: [AtomicU64; 4],
+    // By normalized route (METRICS_ROUTES)
+    pub routes: RouteCounts,
 }
 
 impl Default for RequestMetrics {
@@ -169,6 +266,7 @@ impl RequestMetrics {
             options: AtomicUsize::new(0),
             patch: AtomicUsize::new(0),
             other: AtomicUsize::new(0),
+            by_protocol: Default::default(),
             status_2xx: AtomicUsize::new(0),
             status_3xx: AtomicUsize::new(0),
             status_4xx: AtomicUsize::new(0),
@@ -177,16 +275,30 @@ impl RequestMetrics {
             dropped_requests: AtomicUsize::new(0),
             total_response_time_us: AtomicU64::new(0),
             response_count: AtomicU64::new(0),
+            slow_requests: AtomicU64::new(0),
+            php_service_us: AtomicU64::new(0),
+            duration_buckets: Default::default(),
             sse_active: AtomicUsize::new(0),
             sse_total: AtomicU64::new(0),
             sse_chunks: AtomicU64::new(0),
             sse_bytes: AtomicU64::new(0),
+            static_responses: AtomicU64::new(0),
+            static_not_modified: AtomicU64::new(0),
+            executor_errors: Default::default(),
+            routes: RouteCounts::default(),
         }
     }
 
-    /// Increment counter for the given HTTP method.
+    /// Increment counters for the given HTTP method, version and transport.
     #[inline]
-    pub fn increment_method(&self, method: &hyper::Method) {
+    pub fn increment_method(&self, method: &hyper::Method, version: hyper::Version, tls: bool) {
+        let version = match version {
+            hyper::Version::HTTP_10 => 0,
+            hyper::Version::HTTP_2 => 2,
+            _ => 1,
+        };
+        self.by_protocol[version * 2 + tls as usize].fetch_add(1, Ordering::Relaxed);
+
         let counter = match *method {
             hyper::Method::GET => &self.get,
             hyper::Method::POST => &self.post,
@@ -200,6 +312,25 @@ impl RequestMetrics {
         counter.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Render request counts by protocol in Prometheus text format.
+    fn protocol_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::from(
+            "\n# HELP tokio_php_requests_by_protocol_total Total HTTP requests by HTTP version and TLS\n\
+             # TYPE tokio_php_requests_by_protocol_total counter\n",
+        );
+        for (labels, counter) in PROTOCOL_LABELS.iter().zip(&self.by_protocol) {
+            let _ = writeln!(
+                out,
+                "tokio_php_requests_by_protocol_total{{{}}} {}",
+                labels,
+                counter.load(Ordering::Relaxed)
+            );
+        }
+        out
+    }
+
     /// Increment counter for the given HTTP status code.
     #[inline]
     pub fn increment_status(&self, status: u16) {
@@ -224,6 +355,40 @@ impl RequestMetrics {
         self.pending_requests.fetch_sub(1, Ordering::Relaxed);
     }
 
+    /// Record a static file response and whether it was a 304.
+    #[inline]
+    pub fn record_static(&self, not_modified: bool) {
+        self.static_responses.fetch_add(1, Ordering::Relaxed);
+        if not_modified {
+            self.static_not_modified.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Count a failed PHP execution by kind.
+    #[inline]
+    pub fn record_executor_error(&self, kind: ExecutorErrorKind) {
+        self.executor_errors[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render executor failures by kind in Prometheus text format.
+    fn executor_errors_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::from(
+            "\n# HELP tokio_php_executor_errors_total Failed PHP executions by kind\n\
+             # TYPE tokio_php_executor_errors_total counter\n",
+        );
+        for (kind, counter) in ExecutorErrorKind::ALL.iter().zip(&self.executor_errors) {
+            let _ = writeln!(
+                out,
+                "tokio_php_executor_errors_total{{kind=\"{}\"}} {}",
+                kind.as_str(),
+                counter.load(Ordering::Relaxed)
+            );
+        }
+        out
+    }
+
     /// Increment dropped requests (called when queue is full).
     #[inline]
     pub fn inc_dropped(&self) {
@@ -255,6 +420,50 @@ impl RequestMetrics {
         self.total_response_time_us
             .fetch_add(duration_us, Ordering::Relaxed);
         self.response_count.fetch_add(1, Ordering::Relaxed);
+        let bucket = REQUEST_DURATION_BUCKETS_US
+            .iter()
+            .position(|&le| duration_us <= le)
+            .unwrap_or(REQUEST_DURATION_BUCKETS_US.len());
+        self.duration_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render the response time histogram in Prometheus text format.
+    ///
+    /// Only the total is broken down by duration: no method, status or path
+    /// labels, so the series count is fixed.
+    fn duration_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::from(
+            "\n# HELP tokio_php_request_duration_seconds Time from request received to response ready\n\
+             # TYPE tokio_php_request_duration_seconds histogram\n",
+        );
+        let mut cumulative = 0;
+        for (i, bucket) in self.duration_buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = REQUEST_DURATION_BUCKETS_US
+                .get(i)
+                .map_or_else(|| "+Inf".to_string(), |&us| (us as f64 / 1e6).to_string());
+            let _ = writeln!(
+                out,
+                "tokio_php_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                le, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "tokio_php_request_duration_seconds_sum {}\n\
+             tokio_php_request_duration_seconds_count {}",
+            self.total_response_time_us.load(Ordering::Relaxed) as f64 / 1e6,
+            cumulative
+        );
+        out
+    }
+
+    /// Count a request over SLOW_REQUEST_THRESHOLD_MS.
+    #[inline]
+    pub fn record_slow_request(&self) {
+        self.slow_requests.fetch_add(1, Ordering::Relaxed);
     }
 
     /// Get server uptime in seconds.
@@ -282,6 +491,32 @@ impl RequestMetrics {
         }
     }
 
+    /// Record how long PHP took for a request that didn't wait for a worker,
+    /// so the time is execution only.
+    pub fn record_php_service_time(&self, duration_us: u64) {
+        let _ =
+            self.php_service_us
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |avg| {
+                    Some(match avg {
+                        0 => duration_us.max(1),
+                        avg => (avg - avg / PHP_SERVICE_WINDOW + duration_us / PHP_SERVICE_WINDOW)
+                            .max(1),
+                    })
+                });
+    }
+
+    /// Estimated wait for a request joining the queue now.
+    ///
+    /// Requests beyond `workers` are waiting; each worker clears one per
+    /// recent PHP execution time, so the backlog drains at `workers` per
+    /// round. Static files and time spent queued don't count.
+    pub fn estimated_queue_wait(&self, workers: usize) -> Duration {
+        let pending = self.pending_requests.load(Ordering::Relaxed);
+        let waiting = pending.saturating_sub(workers);
+        let service_us = self.php_service_us.load(Ordering::Relaxed);
+        Duration::from_micros(waiting as u64 * service_us / workers.max(1) as u64)
+    }
+
     /// Increment active SSE connections (called when SSE stream starts).
     #[inline]
     pub fn sse_connection_started(&self) {
@@ -303,6 +538,387 @@ impl RequestMetrics {
     }
 }
 
+// =============================================================================
+// Connection Metrics
+// =============================================================================
+
+/// Reason a connection was closed with an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    /// Peer reset the connection or the pipe broke mid-write.
+    Reset,
+    /// Header read timeout or other I/O deadline.
+    Timeout,
+    /// Client connected but never sent a byte within IDLE_TIMEOUT_SECS, or
+    /// the connection had nothing in flight for KEEPALIVE_TIMEOUT_SECS.
+    Idle,
+    /// Malformed request or HTTP/2 protocol violation.
+    Protocol,
+}
+
+/// TLS versions counted separately; anything else is "other".
+const TLS_PROTOCOLS: [&str; 3] = ["TLSv1.2", "TLSv1.3", "other"];
+/// Protocols offered via ALPN, plus clients that sent none.
+const TLS_ALPNS: [&str; 3] = ["h2", "http/1.1", "none"];
+/// Requests averaged into the PHP execution time behind MAX_QUEUE_WAIT.
+const PHP_SERVICE_WINDOW: u64 = 16;
+
+/// Handshake duration histogram bucket bounds (microseconds).
+const TLS_HANDSHAKE_BUCKETS_US: [u64; 10] = [
+    1_000, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000, 2_500_000,
+];
+
+/// Response time histogram bucket bounds (microseconds), Prometheus' defaults.
+const REQUEST_DURATION_BUCKETS_US: [u64; 11] = [
+    5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000, 2_500_000, 5_000_000,
+    10_000_000,
+];
+
+/// Label sets for `RequestMetrics::by_protocol`, indexed `version * 2 + tls`.
+const PROTOCOL_LABELS: [&str; 6] = [
+    "version=\"HTTP/1.0\",tls=\"false\"",
+    "version=\"HTTP/1.0\",tls=\"true\"",
+    "version=\"HTTP/1.1\",tls=\"false\"",
+    "version=\"HTTP/1.1\",tls=\"true\"",
+    "version=\"HTTP/2.0\",tls=\"false\"",
+    "version=\"HTTP/2.0\",tls=\"true\"",
+];
+
+/// Connection lifecycle counters (accepts, TLS handshakes, error closes).
+///
+/// The active connection gauge lives in `Server::active_connections`;
+/// these are cumulative counters since startup.
+#[derive(Default)]
+pub struct ConnectionMetrics {
+    pub accepted: AtomicU64,
+    pub tls_handshakes_ok: AtomicU64,
+    pub tls_handshakes_failed: AtomicU64,
+    /// Completed handshakes, indexed `protocol * TLS_ALPNS.len() + alpn`.
+    tls_sessions: [AtomicU64; 9],
+    /// Per-bucket (non-cumulative) handshake counts; last slot is +Inf.
+    tls_handshake_buckets: [AtomicU64; 11],
+    tls_handshake_sum_us: AtomicU64,
+    pub closed_reset: AtomicU64,
+    pub closed_timeout: AtomicU64,
+    pub closed_idle: AtomicU64,
+    pub closed_protocol: AtomicU64,
+    pub rejected_ip_limit: AtomicU64,
+    pub rejected_max_connections: AtomicU64,
+}
+
+impl ConnectionMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an accepted TCP connection.
+    #[inline]
+    pub fn record_accept(&self) {
+        self.accepted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the outcome of a TLS handshake (timeouts count as failures).
+    #[inline]
+    pub fn record_tls_handshake(&self, ok: bool) {
+        let counter = if ok {
+            &self.tls_handshakes_ok
+        } else {
+            &self.tls_handshakes_failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a completed handshake's negotiated version, ALPN and duration.
+    ///
+    /// `protocol` is rustls' `Debug` form (`TLSv1_3`), `alpn` empty if none.
+    pub fn record_tls_session(&self, protocol: &str, alpn: &str, handshake_us: u64) {
+        let protocol = match protocol {
+            "TLSv1_2" => 0,
+            "TLSv1_3" => 1,
+            _ => 2,
+        };
+        let alpn = match alpn {
+            "h2" => 0,
+            "http/1.1" => 1,
+            _ => 2,
+        };
+        self.tls_sessions[protocol * TLS_ALPNS.len() + alpn].fetch_add(1, Ordering::Relaxed);
+
+        let bucket = TLS_HANDSHAKE_BUCKETS_US
+            .iter()
+            .position(|&le| handshake_us <= le)
+            .unwrap_or(TLS_HANDSHAKE_BUCKETS_US.len());
+        self.tls_handshake_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.tls_handshake_sum_us
+            .fetch_add(handshake_us, Ordering::Relaxed);
+    }
+
+    /// Completed handshakes and their total duration in microseconds.
+    pub fn tls_handshake_totals(&self) -> (u64, u64) {
+        let count = self
+            .tls_handshake_buckets
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .sum();
+        (count, self.tls_handshake_sum_us.load(Ordering::Relaxed))
+    }
+
+    /// Record a connection closed by an error.
+    #[inline]
+    pub fn record_close(&self, reason: CloseReason) {
+        let counter = match reason {
+            CloseReason::Reset => &self.closed_reset,
+            CloseReason::Timeout => &self.closed_timeout,
+            CloseReason::Idle => &self.closed_idle,
+            CloseReason::Protocol => &self.closed_protocol,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a connection closed because its IP hit MAX_CONNECTIONS_PER_IP.
+    #[inline]
+    pub fn record_ip_limit_reject(&self) {
+        self.rejected_ip_limit.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a connection closed because the server hit MAX_CONNECTIONS.
+    #[inline]
+    pub fn record_max_connections_reject(&self) {
+        self.rejected_max_connections
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render counters in Prometheus text format.
+    fn to_prometheus(&self) -> String {
+        let mut out = self.counters_prometheus();
+        out.push_str(&self.tls_prometheus());
+        out
+    }
+
+    fn counters_prometheus(&self) -> String {
+        format!(
+            "\n\
+             # HELP tokio_php_connections_accepted_total Total TCP connections accepted\n\
+             # TYPE tokio_php_connections_accepted_total counter\n\
+             tokio_php_connections_accepted_total {}\n\
+             \n\
+             # HELP tokio_php_tls_handshakes_total Total TLS handshakes by result\n\
+             # TYPE tokio_php_tls_handshakes_total counter\n\
+             tokio_php_tls_handshakes_total{{result=\"ok\"}} {}\n\
+             tokio_php_tls_handshakes_total{{result=\"failed\"}} {}\n\
+             \n\
+             # HELP tokio_php_connections_closed_total Connections closed by error type\n\
+             # TYPE tokio_php_connections_closed_total counter\n\
+             tokio_php_connections_closed_total{{reason=\"reset\"}} {}\n\
+             tokio_php_connections_closed_total{{reason=\"timeout\"}} {}\n\
+             tokio_php_connections_closed_total{{reason=\"idle\"}} {}\n\
+             tokio_php_connections_closed_total{{reason=\"protocol\"}} {}\n\
+             \n\
+             # HELP tokio_php_connections_rejected_total Connections closed right after accept\n\
+             # TYPE tokio_php_connections_rejected_total counter\n\
+             tokio_php_connections_rejected_total{{reason=\"ip_limit\"}} {}\n\
+             tokio_php_connections_rejected_total{{reason=\"max_connections\"}} {}\n",
+            self.accepted.load(Ordering::Relaxed),
+            self.tls_handshakes_ok.load(Ordering::Relaxed),
+            self.tls_handshakes_failed.load(Ordering::Relaxed),
+            self.closed_reset.load(Ordering::Relaxed),
+            self.closed_timeout.load(Ordering::Relaxed),
+            self.closed_idle.load(Ordering::Relaxed),
+            self.closed_protocol.load(Ordering::Relaxed),
+            self.rejected_ip_limit.load(Ordering::Relaxed),
+            self.rejected_max_connections.load(Ordering::Relaxed),
+        )
+    }
+
+    /// TLS version/ALPN counters and the handshake duration histogram.
+    fn tls_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::from(
+            "\n# HELP tokio_php_tls_sessions_total Completed TLS handshakes by protocol version and ALPN\n\
+             # TYPE tokio_php_tls_sessions_total counter\n",
+        );
+        for (i, protocol) in TLS_PROTOCOLS.iter().enumerate() {
+            for (j, alpn) in TLS_ALPNS.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "tokio_php_tls_sessions_total{{protocol=\"{}\",alpn=\"{}\"}} {}",
+                    protocol,
+                    alpn,
+                    self.tls_sessions[i * TLS_ALPNS.len() + j].load(Ordering::Relaxed)
+                );
+            }
+        }
+
+        out.push_str(
+            "\n# HELP tokio_php_tls_handshake_duration_seconds Duration of completed TLS handshakes\n\
+             # TYPE tokio_php_tls_handshake_duration_seconds histogram\n",
+        );
+        let mut cumulative = 0;
+        for (i, bucket) in self.tls_handshake_buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = TLS_HANDSHAKE_BUCKETS_US
+                .get(i)
+                .map_or_else(|| "+Inf".to_string(), |&us| (us as f64 / 1e6).to_string());
+            let _ = writeln!(
+                out,
+                "tokio_php_tls_handshake_duration_seconds_bucket{{le=\"{}\"}} {}",
+                le, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "tokio_php_tls_handshake_duration_seconds_sum {}\n\
+             tokio_php_tls_handshake_duration_seconds_count {}",
+            self.tls_handshake_sum_us.load(Ordering::Relaxed) as f64 / 1e6,
+            cumulative
+        );
+        out
+    }
+}
+
+// =============================================================================
+// OPcache Status (for /opcache endpoint)
+// =============================================================================
+
+/// How long an `/opcache` snapshot is reused before asking a worker again.
+const OPCACHE_STATUS_TTL: Duration = Duration::from_secs(2);
+
+type OpcacheCollector = dyn Fn() -> BoxFuture<'static, Result<String, String>> + Send + Sync;
+
+/// Briefly cached `opcache_get_status()` JSON.
+///
+/// Collecting occupies a PHP worker, so scrapes within the TTL share one
+/// snapshot and concurrent requests wait for the collection in flight.
+pub struct OpcacheStatus {
+    collect: Box<OpcacheCollector>,
+    cached: Mutex<Option<(Instant, Bytes)>>,
+}
+
+impl OpcacheStatus {
+    pub fn new<F, Fut>(collect: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, String>> + Send + 'static,
+    {
+        Self {
+            collect: Box::new(move || Box::pin(collect())),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Cached snapshot, or a fresh one once the TTL has passed.
+    /// Errors are not cached.
+    async fn get(&self) -> Result<Bytes, String> {
+        let mut cached = self.cached.lock().await;
+        if let Some((at, body)) = cached.as_ref() {
+            if at.elapsed() < OPCACHE_STATUS_TTL {
+                return Ok(body.clone());
+            }
+        }
+        let body = Bytes::from((self.collect)().await?);
+        *cached = Some((Instant::now(), body.clone()));
+        Ok(body)
+    }
+}
+
+// =============================================================================
+// Diagnostics (for /diagnostics endpoint)
+// =============================================================================
+
+/// How long a `/diagnostics` report is reused before collecting again.
+const DIAGNOSTICS_TTL: Duration = Duration::from_secs(5);
+
+/// Briefly cached `DiagnosticCollector` report.
+///
+/// Collection walks `/proc` and the cgroup files, so it runs on the blocking
+/// pool at most once per TTL and concurrent requests share the result.
+#[derive(Default)]
+pub struct Diagnostics {
+    cached: Mutex<Option<(Instant, Bytes)>>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cached report, or a fresh one from `snapshot` once the TTL has passed.
+    /// Errors are not cached.
+    async fn get(
+        &self,
+        snapshot: impl FnOnce() -> (WorkerSnapshot, TrafficSnapshot),
+    ) -> Result<Bytes, String> {
+        let mut cached = self.cached.lock().await;
+        if let Some((at, body)) = cached.as_ref() {
+            if at.elapsed() < DIAGNOSTICS_TTL {
+                return Ok(body.clone());
+            }
+        }
+
+        let (workers, traffic) = snapshot();
+        let handle = tokio::runtime::Handle::current();
+        let report = tokio::task::spawn_blocking(move || {
+            DiagnosticCollector::new().collect(&handle, &workers, &traffic)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        let body = Bytes::from(serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?);
+        *cached = Some((Instant::now(), body.clone()));
+        Ok(body)
+    }
+}
+
+/// JSON response built without the fallible `Response::builder()`.
+fn json_response(status: StatusCode, body: Bytes) -> Response<Full<Bytes>> {
+    let mut resp = Response::new(Full::new(body));
+    *resp.status_mut() = status;
+    resp.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    resp
+}
+
+/// Split pending PHP requests (executing + queued) into busy workers and
+/// queue depth: requests only wait once every worker has one.
+fn split_pending(pending: usize, workers: usize) -> (usize, usize) {
+    (pending.min(workers), pending.saturating_sub(workers))
+}
+
+/// Live worker pool and traffic figures for a diagnostics report.
+fn diagnostics_snapshot(
+    metrics: &RequestMetrics,
+    connection_metrics: &ConnectionMetrics,
+    config: &ServerConfigInfo,
+) -> (WorkerSnapshot, TrafficSnapshot) {
+    let worker_count = config.php_workers.parse().unwrap_or(0);
+    let (busy_workers, queue_depth) = split_pending(
+        metrics.pending_requests.load(Ordering::Relaxed),
+        worker_count,
+    );
+    let workers = WorkerSnapshot {
+        worker_count,
+        busy_workers,
+        queue_depth,
+        total_requests: metrics.total() as u64,
+        per_worker: (config.worker_activity)(),
+        ..Default::default()
+    };
+
+    let (tls_handshakes, tls_handshake_time_us) = connection_metrics.tls_handshake_totals();
+    let traffic = TrafficSnapshot {
+        queue_capacity: config.queue_capacity.parse().unwrap_or(0),
+        dropped_requests: metrics.dropped_requests.load(Ordering::Relaxed) as u64,
+        tls_handshakes,
+        tls_handshake_time_us,
+        static_responses: metrics.static_responses.load(Ordering::Relaxed),
+        static_not_modified: metrics.static_not_modified.load(Ordering::Relaxed),
+        static_cache_enabled: config.live_config.borrow().static_cache_ttl.is_enabled(),
+    };
+    (workers, traffic)
+}
+
 /// Guard that decrements pending_requests when dropped.
 /// Ensures proper cleanup even if async task is cancelled.
 pub struct PendingGuard(Arc<RequestMetrics>);
@@ -313,28 +929,37 @@ impl Drop for PendingGuard {
     }
 }
 
-/// Run the internal HTTP server for /health, /metrics, and /config endpoints.
+/// Run the internal HTTP server for /health, /metrics, /config, /opcache, /diagnostics and /profile/last endpoints.
 pub async fn run_internal_server(
     addr: SocketAddr,
     active_connections: Arc<AtomicUsize>,
     request_metrics: Arc<RequestMetrics>,
+    connection_metrics: Arc<ConnectionMetrics>,
+    kill_switch: Arc<KillSwitch>,
     config_info: Arc<ServerConfigInfo>,
+    opcache_status: Arc<OpcacheStatus>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let listener = TcpListener::bind(addr).await?;
 
     loop {
-        let (stream, _) = listener.accept().await?;
+        let (stream, peer) = listener.accept().await?;
         let _ = stream.set_nodelay(true);
         let connections = Arc::clone(&active_connections);
         let metrics = Arc::clone(&request_metrics);
+        let conn_metrics = Arc::clone(&connection_metrics);
+        let kill_switch = Arc::clone(&kill_switch);
         let config = Arc::clone(&config_info);
+        let opcache = Arc::clone(&opcache_status);
 
         tokio::spawn(async move {
             let service = service_fn(move |req| {
                 let conns = connections.load(Ordering::Relaxed);
                 let m = Arc::clone(&metrics);
+                let cm = Arc::clone(&conn_metrics);
+                let ks = Arc::clone(&kill_switch);
                 let c = Arc::clone(&config);
-                async move { handle_internal_request(req, conns, m, c).await }
+                let oc = Arc::clone(&opcache);
+                async move { handle_internal_request(req, peer, conns, m, cm, ks, c, oc).await }
             });
 
             let io = TokioIo::new(stream);
@@ -343,18 +968,96 @@ pub async fn run_internal_server(
     }
 }
 
-/// Handle internal server requests (/health, /metrics, /config).
+/// Handle internal server requests (/health, /ready, /metrics, /config, /kill-switch, /loglevel, /opcache, /diagnostics, /profile/last, /profile/stats).
+#[allow(clippy::too_many_arguments)]
 async fn handle_internal_request(
     req: Request<IncomingBody>,
+    peer: SocketAddr,
     active_connections: usize,
     metrics: Arc<RequestMetrics>,
+    connection_metrics: Arc<ConnectionMetrics>,
+    kill_switch: Arc<KillSwitch>,
     config: Arc<ServerConfigInfo>,
+    opcache: Arc<OpcacheStatus>,
 ) -> Result<Response<Full<Bytes>>, Infallible> {
     let path = req.uri().path();
 
+    if let Some(refused) = config.access.check(path, peer.ip(), req.headers()) {
+        return Ok(refused);
+    }
+
     let response = match path {
+        "/kill-switch" => handle_kill_switch(&req, &kill_switch),
+        "/loglevel" => handle_loglevel(req).await,
+        "/opcache" => match opcache.get().await {
+            Ok(body) => json_response(StatusCode::OK, body),
+            Err(e) => {
+                tracing::debug!("OPcache status unavailable: {}", e);
+                let body = serde_json::json!({ "error": e });
+                json_response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    Bytes::from(body.to_string()),
+                )
+            }
+        },
+        "/diagnostics" => match config
+            .diagnostics
+            .get(|| diagnostics_snapshot(&metrics, &connection_metrics, &config))
+            .await
+        {
+            Ok(body) => Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "application/json")
+                .body(Full::new(body))
+                .unwrap(),
+            Err(e) => {
+                tracing::warn!("Diagnostics collection failed: {}", e);
+                let body = serde_json::json!({ "error": e });
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .header("Content-Type", "application/json")
+                    .body(Full::new(Bytes::from(body.to_string())))
+                    .unwrap()
+            }
+        },
+        "/profile/last" => {
+            // ?format=folded: folded stacks for flamegraph.pl
+            let folded = parse_query_string(req.uri().query().unwrap_or(""))
+                .iter()
+                .any(|(k, v)| k == "format" && v == "folded");
+            let last = if folded {
+                config
+                    .last_profile
+                    .folded()
+                    .map(|body| (body, "text/plain; charset=utf-8"))
+            } else {
+                config
+                    .last_profile
+                    .get()
+                    .map(|body| (body, "application/json"))
+            };
+            match last {
+                Some((body, content_type)) => Response::builder()
+                    .status(StatusCode::OK)
+                    .header("Content-Type", content_type)
+                    .body(Full::new(body))
+                    .unwrap(),
+                None => {
+                    let body = serde_json::json!({
+                        "error": "no profiled request yet (profiling requires a debug-profile build)"
+                    });
+                    Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .header("Content-Type", "application/json")
+                        .body(Full::new(Bytes::from(body.to_string())))
+                        .unwrap()
+                }
+            }
+        }
+        "/profile/stats" => handle_profile_stats(&req, config.profile_stats.as_deref()),
         "/config" => {
-            let body = serde_json::to_string_pretty(&*config).unwrap_or_else(|_| "{}".to_string());
+            let body = serde_json::to_string_pretty(&config.current())
+                .unwrap_or_else(|_| "{}".to_string());
             Response::builder()
                 .status(StatusCode::OK)
                 .header("Content-Type", "application/json")
@@ -377,9 +1080,53 @@ async fn handle_internal_request(
                 .body(Full::new(Bytes::from(body)))
                 .unwrap()
         }
+        "/health/startup" => {
+            let (status, body) = if config.lifecycle.is_started() {
+                (StatusCode::OK, r#"{"status":"started"}"#)
+            } else {
+                (StatusCode::SERVICE_UNAVAILABLE, r#"{"status":"starting"}"#)
+            };
+            Response::builder()
+                .status(status)
+                .header("Content-Type", "application/json")
+                .body(Full::new(Bytes::from(body)))
+                .unwrap()
+        }
+        "/ready" => {
+            let pending = metrics.pending_requests.load(Ordering::Relaxed);
+            let phase = config.lifecycle.phase();
+            let (status, body) = if phase != Phase::Running {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!(
+                        r#"{{"status":"not_ready","reason":"{}","pending_requests":{}}}"#,
+                        phase.as_str(),
+                        pending
+                    ),
+                )
+            } else if config.readiness.is_ready(pending) {
+                (
+                    StatusCode::OK,
+                    format!(r#"{{"status":"ready","pending_requests":{}}}"#, pending),
+                )
+            } else {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!(
+                        r#"{{"status":"not_ready","reason":"queue_saturated","pending_requests":{}}}"#,
+                        pending
+                    ),
+                )
+            };
+            Response::builder()
+                .status(status)
+                .header("Content-Type", "application/json")
+                .body(Full::new(Bytes::from(body)))
+                .unwrap()
+        }
         "/metrics" => {
             let sys = SystemMetrics::read();
-            let body = format!(
+            let mut body = format!(
                 "# HELP tokio_php_uptime_seconds Server uptime in seconds\n\
                  # TYPE tokio_php_uptime_seconds gauge\n\
                  tokio_php_uptime_seconds {:.3}\n\
@@ -422,6 +1169,10 @@ async fn handle_internal_request(
                  tokio_php_responses_total{{status=\"4xx\"}} {}\n\
                  tokio_php_responses_total{{status=\"5xx\"}} {}\n\
                  \n\
+                 # HELP tokio_php_slow_requests_total Requests slower than SLOW_REQUEST_THRESHOLD_MS\n\
+                 # TYPE tokio_php_slow_requests_total counter\n\
+                 tokio_php_slow_requests_total {}\n\
+                 \n\
                  # HELP node_load1 1-minute load average\n\
                  # TYPE node_load1 gauge\n\
                  node_load1 {:.2}\n\
@@ -483,6 +1234,7 @@ async fn handle_internal_request(
                 metrics.status_3xx.load(Ordering::Relaxed),
                 metrics.status_4xx.load(Ordering::Relaxed),
                 metrics.status_5xx.load(Ordering::Relaxed),
+                metrics.slow_requests.load(Ordering::Relaxed),
                 sys.load_avg_1m,
                 sys.load_avg_5m,
                 sys.load_avg_15m,
@@ -495,6 +1247,44 @@ async fn handle_internal_request(
                 metrics.sse_chunks.load(Ordering::Relaxed),
                 metrics.sse_bytes.load(Ordering::Relaxed),
             );
+            body.push_str(&metrics.protocol_prometheus());
+            body.push_str(&metrics.duration_prometheus());
+            body.push_str(&metrics.routes.to_prometheus());
+            body.push_str(&metrics.executor_errors_prometheus());
+            body.push_str(&connection_metrics.to_prometheus());
+            body.push_str(&php_errors_prometheus());
+            body.push_str(&format!(
+                "\n# HELP tokio_php_worker_respawns_total PHP workers restarted after a panic\n\
+                 # TYPE tokio_php_worker_respawns_total counter\n\
+                 tokio_php_worker_respawns_total {}\n",
+                crate::executor::worker_respawns()
+            ));
+            let (extended, capped) = crate::executor::heartbeat_extensions();
+            body.push_str(&format!(
+                "\n# HELP tokio_php_heartbeat_extensions_total Request deadline extensions by tokio_request_heartbeat()\n\
+                 # TYPE tokio_php_heartbeat_extensions_total counter\n\
+                 tokio_php_heartbeat_extensions_total{{result=\"extended\"}} {}\n\
+                 tokio_php_heartbeat_extensions_total{{result=\"capped\"}} {}\n",
+                extended, capped
+            ));
+            body.push_str(&format!(
+                "\n# HELP tokio_php_background_overruns_total Requests aborted at MAX_BACKGROUND_TIME after tokio_finish_request()\n\
+                 # TYPE tokio_php_background_overruns_total counter\n\
+                 tokio_php_background_overruns_total {}\n",
+                crate::executor::background_overruns()
+            ));
+            body.push_str(&format!(
+                "\n# HELP tokio_php_stream_aborts_total Streaming responses aborted at STREAM_WRITE_TIMEOUT because the client stopped reading\n\
+                 # TYPE tokio_php_stream_aborts_total counter\n\
+                 tokio_php_stream_aborts_total {}\n",
+                crate::executor::stream_aborts()
+            ));
+            if let Some(ref sizing) = config.sizing {
+                body.push_str(&sizing_prometheus(sizing));
+            }
+            if config.memory_pressure.is_enabled() {
+                body.push_str(&config.memory_pressure.to_prometheus());
+            }
             Response::builder()
                 .status(StatusCode::OK)
                 .header("Content-Type", "text/plain; version=0.0.4")
@@ -510,3 +1300,457 @@ async fn handle_internal_request(
 
     Ok(response)
 }
+
+/// Render PHP error counters (captured via the SAPI log hook).
+fn php_errors_prometheus() -> String {
+    use crate::logging::{php_error_count, PhpErrorLevel};
+    use std::fmt::Write;
+
+    let mut out = String::from(
+        "\n# HELP tokio_php_php_errors_total PHP errors logged by level\n\
+         # TYPE tokio_php_php_errors_total counter\n",
+    );
+    for level in PhpErrorLevel::ALL {
+        let _ = writeln!(
+            out,
+            "tokio_php_php_errors_total{{level=\"{}\"}} {}",
+            level.as_str(),
+            php_error_count(level)
+        );
+    }
+    out
+}
+
+/// Render the startup sizing decision; unlimited cgroup values are 0.
+fn sizing_prometheus(sizing: &Sizing) -> String {
+    format!(
+        "\n# HELP tokio_php_cgroup_cpu_quota_cores CPU quota detected at startup (0 = unlimited)\n\
+         # TYPE tokio_php_cgroup_cpu_quota_cores gauge\n\
+         tokio_php_cgroup_cpu_quota_cores {}\n\
+         # HELP tokio_php_cgroup_memory_limit_bytes Memory limit detected at startup (0 = unlimited)\n\
+         # TYPE tokio_php_cgroup_memory_limit_bytes gauge\n\
+         tokio_php_cgroup_memory_limit_bytes {}\n\
+         # HELP tokio_php_workers PHP worker count and the heuristic that chose it\n\
+         # TYPE tokio_php_workers gauge\n\
+         tokio_php_workers{{source=\"{}\"}} {}\n\
+         # HELP tokio_php_queue_capacity Request queue capacity and the heuristic that chose it\n\
+         # TYPE tokio_php_queue_capacity gauge\n\
+         tokio_php_queue_capacity{{source=\"{}\"}} {}\n",
+        sizing.limits.cpu_quota.unwrap_or(0.0),
+        sizing.limits.memory_limit.unwrap_or(0),
+        sizing.worker_source.as_str(),
+        sizing.workers,
+        sizing.queue_source.as_str(),
+        sizing.queue_capacity,
+    )
+}
+
+/// Kill-switch endpoint.
+///
+/// - `GET /kill-switch` - list disabled prefixes
+/// - `POST /kill-switch?disable=/api/reports` - take a prefix offline
+/// - `POST /kill-switch?enable=/api/reports` - bring it back
+fn handle_kill_switch(
+    req: &Request<IncomingBody>,
+    kill_switch: &KillSwitch,
+) -> Response<Full<Bytes>> {
+    if req.method() == hyper::Method::POST {
+        let params = parse_query_string(req.uri().query().unwrap_or(""));
+        for (key, value) in &params {
+            match key.as_ref() {
+                "disable" if !value.is_empty() && kill_switch.disable(value) => {
+                    tracing::warn!("Kill-switch: disabled {}", value);
+                }
+                "enable" if !value.is_empty() && kill_switch.enable(value) => {
+                    tracing::info!("Kill-switch: re-enabled {}", value);
+                }
+                _ => {}
+            }
+        }
+    } else if req.method() != hyper::Method::GET {
+        return Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header("Allow", "GET, POST")
+            .body(Full::new(Bytes::new()))
+            .unwrap();
+    }
+
+    let body = serde_json::json!({
+        "status": kill_switch.status().as_u16(),
+        "disabled": kill_switch.disabled(),
+    });
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+/// Log filter endpoint.
+///
+/// - `GET /loglevel` - current filter
+/// - `POST /loglevel` with `{"filter":"tokio_php=debug"}` - replace it until
+///   the next restart, or a SIGHUP that changes `LOG_LEVEL` / `RUST_LOG`
+async fn handle_loglevel<B>(req: Request<B>) -> Response<Full<Bytes>>
+where
+    B: hyper::body::Body,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let json = |status: StatusCode, body: serde_json::Value| {
+        Response::builder()
+            .status(status)
+            .header("Content-Type", "application/json")
+            .body(Full::new(Bytes::from(body.to_string())))
+            .unwrap()
+    };
+
+    if req.method() == hyper::Method::POST {
+        let body = match Limited::new(req.into_body(), LOGLEVEL_BODY_MAX)
+            .collect()
+            .await
+        {
+            Ok(body) => body.to_bytes(),
+            Err(e) => {
+                return json(
+                    StatusCode::BAD_REQUEST,
+                    serde_json::json!({ "error": e.to_string() }),
+                )
+            }
+        };
+        let filter = serde_json::from_slice::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|v| v.get("filter")?.as_str().map(str::to_string));
+        let Some(filter) = filter else {
+            return json(
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({ "error": "expected {\"filter\": \"...\"}" }),
+            );
+        };
+        if let Err(e) = crate::logging::set_log_filter(&filter) {
+            return json(StatusCode::BAD_REQUEST, serde_json::json!({ "error": e }));
+        }
+        tracing::warn!("Log filter changed to {} via /loglevel", filter);
+    } else if req.method() != hyper::Method::GET {
+        return Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header("Allow", "GET, POST")
+            .body(Full::new(Bytes::new()))
+            .unwrap();
+    }
+
+    json(
+        StatusCode::OK,
+        serde_json::json!({ "filter": crate::logging::log_filter() }),
+    )
+}
+
+/// Profile percentiles endpoint.
+///
+/// - `GET /profile/stats` - per-phase count, mean, p50/p90/p95/p99, max
+/// - `DELETE /profile/stats` - start a new window
+fn handle_profile_stats(
+    req: &Request<IncomingBody>,
+    stats: Option<&ProfileStats>,
+) -> Response<Full<Bytes>> {
+    let Some(stats) = stats else {
+        let body = serde_json::json!({
+            "error": "profile stats disabled (set PROFILE_STATS=1 in a debug-profile build)"
+        });
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header("Content-Type", "application/json")
+            .body(Full::new(Bytes::from(body.to_string())))
+            .unwrap();
+    };
+
+    if req.method() == hyper::Method::DELETE {
+        stats.reset();
+        tracing::info!("Profile stats reset");
+    } else if req.method() != hyper::Method::GET {
+        return Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header("Allow", "GET, DELETE")
+            .body(Full::new(Bytes::new()))
+            .unwrap();
+    }
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(stats.to_json().to_string())))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_metrics_counters() {
+        let m = ConnectionMetrics::new();
+        m.record_accept();
+        m.record_accept();
+        m.record_tls_handshake(true);
+        m.record_tls_handshake(false);
+        m.record_tls_handshake(false);
+        m.record_close(CloseReason::Reset);
+        m.record_close(CloseReason::Idle);
+
+        assert_eq!(m.accepted.load(Ordering::Relaxed), 2);
+        assert_eq!(m.tls_handshakes_ok.load(Ordering::Relaxed), 1);
+        assert_eq!(m.tls_handshakes_failed.load(Ordering::Relaxed), 2);
+        assert_eq!(m.closed_reset.load(Ordering::Relaxed), 1);
+        assert_eq!(m.closed_idle.load(Ordering::Relaxed), 1);
+        assert_eq!(m.closed_timeout.load(Ordering::Relaxed), 0);
+        assert_eq!(m.closed_protocol.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_connection_metrics_prometheus() {
+        let m = ConnectionMetrics::new();
+        m.record_accept();
+        m.record_tls_handshake(false);
+        m.record_close(CloseReason::Protocol);
+        m.record_ip_limit_reject();
+
+        let text = m.to_prometheus();
+        assert!(text.contains("tokio_php_connections_accepted_total 1\n"));
+        assert!(text.contains("tokio_php_tls_handshakes_total{result=\"ok\"} 0\n"));
+        assert!(text.contains("tokio_php_tls_handshakes_total{result=\"failed\"} 1\n"));
+        assert!(text.contains("tokio_php_connections_closed_total{reason=\"protocol\"} 1\n"));
+        assert!(text.contains("tokio_php_connections_closed_total{reason=\"reset\"} 0\n"));
+        assert!(text.contains("tokio_php_connections_rejected_total{reason=\"ip_limit\"} 1\n"));
+        assert!(
+            text.contains("tokio_php_connections_rejected_total{reason=\"max_connections\"} 0\n")
+        );
+    }
+
+    #[test]
+    fn test_executor_errors_by_kind() {
+        let m = RequestMetrics::new();
+        m.record_executor_error(ExecutorErrorKind::Timeout);
+        m.record_executor_error(ExecutorErrorKind::WorkerCrashed);
+        m.record_executor_error(ExecutorErrorKind::WorkerCrashed);
+
+        let text = m.executor_errors_prometheus();
+        assert!(text.contains("tokio_php_executor_errors_total{kind=\"timeout\"} 1\n"));
+        assert!(text.contains("tokio_php_executor_errors_total{kind=\"worker_crashed\"} 2\n"));
+        assert!(text.contains("tokio_php_executor_errors_total{kind=\"queue_full\"} 0\n"));
+    }
+
+    #[test]
+    fn test_request_duration_histogram() {
+        let m = RequestMetrics::new();
+        m.record_response_time(3_000);
+        m.record_response_time(80_000);
+        m.record_response_time(60_000_000);
+
+        let text = m.duration_prometheus();
+        assert!(text.contains("# TYPE tokio_php_request_duration_seconds histogram\n"));
+        assert!(text.contains("tokio_php_request_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(text.contains("tokio_php_request_duration_seconds_bucket{le=\"0.1\"} 2\n"));
+        assert!(text.contains("tokio_php_request_duration_seconds_bucket{le=\"10\"} 2\n"));
+        assert!(text.contains("tokio_php_request_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("tokio_php_request_duration_seconds_sum 60.083\n"));
+        assert!(text.contains("tokio_php_request_duration_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn test_estimated_queue_wait() {
+        let m = RequestMetrics::new();
+        // Static files and slow queued responses don't count
+        m.record_response_time(5_000_000);
+        m.record_php_service_time(200_000);
+        for _ in 0..10 {
+            m.inc_pending();
+        }
+        // 2 workers busy, 8 waiting at 200ms each, 2 at a time
+        assert_eq!(m.estimated_queue_wait(2), Duration::from_millis(800));
+        assert_eq!(m.estimated_queue_wait(10), Duration::ZERO);
+
+        // Recent samples win: the average moves toward the new latency
+        for _ in 0..100 {
+            m.record_php_service_time(20_000);
+        }
+        assert!(m.estimated_queue_wait(2) < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_requests_by_protocol() {
+        let m = RequestMetrics::new();
+        m.increment_method(&hyper::Method::GET, hyper::Version::HTTP_11, false);
+        m.increment_method(&hyper::Method::GET, hyper::Version::HTTP_11, false);
+        m.increment_method(&hyper::Method::POST, hyper::Version::HTTP_2, true);
+
+        assert_eq!(m.get.load(Ordering::Relaxed), 2);
+        assert_eq!(m.post.load(Ordering::Relaxed), 1);
+        let text = m.protocol_prometheus();
+        assert!(text.contains(
+            "tokio_php_requests_by_protocol_total{version=\"HTTP/1.1\",tls=\"false\"} 2\n"
+        ));
+        assert!(text.contains(
+            "tokio_php_requests_by_protocol_total{version=\"HTTP/2.0\",tls=\"true\"} 1\n"
+        ));
+        assert!(text.contains(
+            "tokio_php_requests_by_protocol_total{version=\"HTTP/1.0\",tls=\"false\"} 0\n"
+        ));
+    }
+
+    #[test]
+    fn test_tls_session_metrics() {
+        let m = ConnectionMetrics::new();
+        m.record_tls_session("TLSv1_3", "h2", 3_000);
+        m.record_tls_session("TLSv1_3", "h2", 40_000);
+        m.record_tls_session("TLSv1_2", "", 4_000_000);
+
+        let text = m.to_prometheus();
+        assert!(text.contains("tokio_php_tls_sessions_total{protocol=\"TLSv1.3\",alpn=\"h2\"} 2\n"));
+        assert!(
+            text.contains("tokio_php_tls_sessions_total{protocol=\"TLSv1.2\",alpn=\"none\"} 1\n")
+        );
+        assert!(text.contains("tokio_php_tls_sessions_total{protocol=\"TLSv1.2\",alpn=\"h2\"} 0\n"));
+        assert!(text.contains("tokio_php_tls_handshake_duration_seconds_bucket{le=\"0.001\"} 0\n"));
+        assert!(text.contains("tokio_php_tls_handshake_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(text.contains("tokio_php_tls_handshake_duration_seconds_bucket{le=\"0.05\"} 2\n"));
+        assert!(text.contains("tokio_php_tls_handshake_duration_seconds_bucket{le=\"2.5\"} 2\n"));
+        assert!(text.contains("tokio_php_tls_handshake_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("tokio_php_tls_handshake_duration_seconds_sum 4.043\n"));
+        assert!(text.contains("tokio_php_tls_handshake_duration_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn test_sizing_prometheus() {
+        use crate::config::CgroupLimits;
+
+        let limits = CgroupLimits {
+            cpu_quota: Some(1.5),
+            memory_limit: Some(512 * 1024 * 1024),
+        };
+        let text = sizing_prometheus(&Sizing::resolve(0, 0, limits, 16, 0));
+        assert!(text.contains("tokio_php_cgroup_cpu_quota_cores 1.5\n"));
+        assert!(text.contains("tokio_php_cgroup_memory_limit_bytes 536870912\n"));
+        assert!(text.contains("tokio_php_workers{source=\"cgroup_cpu_quota\"} 2\n"));
+        assert!(text.contains("tokio_php_queue_capacity{source=\"workers_x_multiplier\"} 200\n"));
+
+        let text = sizing_prometheus(&Sizing::resolve(4, 0, CgroupLimits::default(), 16, 0));
+        assert!(text.contains("tokio_php_cgroup_cpu_quota_cores 0\n"));
+        assert!(text.contains("tokio_php_workers{source=\"PHP_WORKERS\"} 4\n"));
+    }
+
+    #[test]
+    fn test_loglevel_round_trip() {
+        use futures_util::FutureExt;
+
+        let _guard = crate::logging::test_filter_guard();
+        let call = |method: &str, body: &str| {
+            let req = Request::builder()
+                .method(method)
+                .uri("/loglevel")
+                .body(Full::new(Bytes::from(body.to_string())))
+                .unwrap();
+            let resp = handle_loglevel(req).now_or_never().unwrap();
+            let status = resp.status();
+            let body = resp.into_body().collect().now_or_never().unwrap().unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body.to_bytes()).unwrap();
+            (status, json)
+        };
+
+        let (status, json) = call("POST", r#"{"filter":"tokio_php=trace"}"#);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["filter"], "tokio_php=trace");
+        let (status, json) = call("GET", "");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["filter"], "tokio_php=trace");
+
+        // A bad filter is refused and the active one stays
+        let (status, json) = call("POST", r#"{"filter":"tokio_php=loud"}"#);
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(json["error"].as_str().unwrap().contains("invalid filter"));
+        let (status, _) = call("POST", "tokio_php=debug");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(call("GET", "").1["filter"], "tokio_php=trace");
+    }
+
+    #[tokio::test]
+    async fn test_opcache_status_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let status = OpcacheStatus::new(move || {
+            let n = counter.fetch_add(1, Ordering::Relaxed);
+            async move { Ok(format!("{{\"call\":{}}}", n)) }
+        });
+
+        assert_eq!(status.get().await.unwrap(), "{\"call\":0}");
+        assert_eq!(status.get().await.unwrap(), "{\"call\":0}");
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_cached() {
+        let diagnostics = Diagnostics::new();
+        let snapshot = || {
+            let workers = WorkerSnapshot {
+                worker_count: 4,
+                total_requests: 10,
+                ..Default::default()
+            };
+            (workers, TrafficSnapshot::default())
+        };
+
+        let first = diagnostics.get(snapshot).await.unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&first).unwrap();
+        assert_eq!(report["runtime_metrics"]["php_workers"]["count"], 4);
+        assert!(report["health_score"].as_u64().unwrap() <= 100);
+
+        // Within the TTL the snapshot isn't even built
+        let second = diagnostics
+            .get(|| unreachable!("collected twice within TTL"))
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_one_pending_request_is_not_saturation() {
+        let (busy_workers, queue_depth) = split_pending(1, 4);
+        assert_eq!((busy_workers, queue_depth), (1, 0));
+        assert_eq!(split_pending(6, 4), (4, 2));
+        assert_eq!(split_pending(0, 4), (0, 0));
+
+        let body = Diagnostics::new()
+            .get(|| {
+                let workers = WorkerSnapshot {
+                    worker_count: 4,
+                    busy_workers,
+                    queue_depth,
+                    ..Default::default()
+                };
+                (workers, TrafficSnapshot::default())
+            })
+            .await
+            .unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let workers = &report["runtime_metrics"]["php_workers"];
+        assert_eq!(workers["busy"], 1);
+        assert_eq!(workers["queue_depth"], 0);
+        assert!(!report["bottlenecks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|b| b["metric"] == "worker_saturation"));
+    }
+
+    #[tokio::test]
+    async fn test_opcache_status_errors_not_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let status = OpcacheStatus::new(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            async { Err("OPcache is not enabled".to_string()) }
+        });
+
+        assert!(status.get().await.is_err());
+        assert!(status.get().await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+}
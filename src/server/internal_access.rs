@@ -0,0 +1,168 @@
+//! Access control for the internal server (`INTERNAL_TOKEN`, `INTERNAL_ALLOW`).
+//!
+//! `/metrics`, `/config`, `/diagnostics` and friends describe the server in
+//! detail, and `/kill-switch` changes it. When `INTERNAL_ADDR` is reachable
+//! from outside, they can be limited to listed networks (403 otherwise) and
+//! to requests bearing `Authorization: Bearer <INTERNAL_TOKEN>` (401
+//! otherwise). Health and readiness probes stay open: kubelets and load
+//! balancers call them without credentials.
+
+use std::net::IpAddr;
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::header::{HeaderMap, AUTHORIZATION, WWW_AUTHENTICATE};
+use hyper::{Response, StatusCode};
+
+use super::trusted_proxy::TrustedProxies;
+
+/// Token and network checks for the internal server; open by default.
+#[derive(Clone, Debug, Default)]
+pub struct InternalAccess {
+    token: Option<Box<str>>,
+    /// Networks allowed to call protected endpoints (empty = any).
+    allow: TrustedProxies,
+}
+
+impl InternalAccess {
+    pub fn new(token: Option<String>, allow: TrustedProxies) -> Self {
+        Self {
+            token: token.filter(|t| !t.is_empty()).map(Into::into),
+            allow,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.token.is_none() && self.allow.is_empty()
+    }
+
+    /// Response refusing the request, or None when it may proceed.
+    pub fn check(
+        &self,
+        path: &str,
+        peer: IpAddr,
+        headers: &HeaderMap,
+    ) -> Option<Response<Full<Bytes>>> {
+        if self.is_open() || is_probe(path) {
+            return None;
+        }
+        if !self.allow.is_empty() && !self.allow.contains(peer) {
+            return Some(refusal(StatusCode::FORBIDDEN));
+        }
+        let token = self.token.as_deref()?;
+        let presented = headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(str::trim);
+        match presented {
+            Some(presented) if constant_time_eq(presented.as_bytes(), token.as_bytes()) => None,
+            _ => {
+                let mut resp = refusal(StatusCode::UNAUTHORIZED);
+                resp.headers_mut()
+                    .insert(WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+                Some(resp)
+            }
+        }
+    }
+}
+
+/// Health and readiness endpoints, always reachable.
+fn is_probe(path: &str) -> bool {
+    path == "/health" || path.starts_with("/health/") || path == "/ready"
+}
+
+fn refusal(status: StatusCode) -> Response<Full<Bytes>> {
+    let mut resp = Response::new(Full::new(Bytes::from_static(
+        status.canonical_reason().unwrap_or("").as_bytes(),
+    )));
+    *resp.status_mut() = status;
+    resp
+}
+
+/// Compares without stopping at the first differing byte, so response
+/// timing doesn't reveal how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+        headers
+    }
+
+    fn status(resp: Option<Response<Full<Bytes>>>) -> Option<u16> {
+        resp.map(|r| r.status().as_u16())
+    }
+
+    #[test]
+    fn test_open_by_default() {
+        let access = InternalAccess::default();
+        let peer = "203.0.113.9".parse().unwrap();
+        assert!(access.check("/metrics", peer, &HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_token() {
+        let access = InternalAccess::new(Some("s3cret".into()), TrustedProxies::default());
+        let peer = "203.0.113.9".parse().unwrap();
+
+        assert_eq!(
+            status(access.check("/metrics", peer, &bearer("s3cret"))),
+            None
+        );
+        assert_eq!(
+            status(access.check("/metrics", peer, &bearer("s3cres"))),
+            Some(401)
+        );
+        let resp = access.check("/config", peer, &HeaderMap::new()).unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(resp.headers()[WWW_AUTHENTICATE], "Bearer");
+
+        // Probes need no token
+        for path in ["/health", "/health/startup", "/ready"] {
+            assert_eq!(status(access.check(path, peer, &HeaderMap::new())), None);
+        }
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let allow = TrustedProxies::parse(&["127.0.0.1", "10.0.0.0/8"]).unwrap();
+        let access = InternalAccess::new(Some("s3cret".into()), allow);
+        let inside = "10.1.2.3".parse().unwrap();
+        let outside = "203.0.113.9".parse().unwrap();
+
+        assert_eq!(
+            status(access.check("/metrics", inside, &bearer("s3cret"))),
+            None
+        );
+        // The token is still required inside the allowed networks
+        assert_eq!(
+            status(access.check("/metrics", inside, &HeaderMap::new())),
+            Some(401)
+        );
+        assert_eq!(
+            status(access.check("/metrics", outside, &bearer("s3cret"))),
+            Some(403)
+        );
+        assert_eq!(
+            status(access.check("/health", outside, &HeaderMap::new())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+    }
+}
@@ -0,0 +1,153 @@
+//! Per-IP concurrent connection cap.
+//!
+//! Rate limiting counts requests, so a client that opens thousands of idle
+//! connections never trips it. This caps how many connections one address
+//! may hold open at once; connections over the cap are closed right after
+//! accept. Counts live in a sharded map so accepts from different clients
+//! rarely contend on the same lock.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+const SHARDS: usize = 16;
+
+/// Open connection counts per client IP.
+pub struct IpConnectionLimit {
+    /// 0 = unlimited.
+    max: usize,
+    shards: Box<[Mutex<HashMap<IpAddr, usize>>]>,
+    hasher: RandomState,
+}
+
+impl IpConnectionLimit {
+    /// Limit to `max` connections per IP; 0 = unlimited.
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max > 0
+    }
+
+    /// Register a connection from `ip`, or `None` if it already has `max` open.
+    ///
+    /// The slot is released when the guard is dropped.
+    pub fn acquire(self: &Arc<Self>, ip: IpAddr) -> Option<IpConnectionGuard> {
+        // IPv4-mapped IPv6 (dual-stack sockets) counts as the IPv4 address
+        let ip = ip.to_canonical();
+        let mut counts = self.shard(ip).lock().unwrap();
+        let count = counts.entry(ip).or_insert(0);
+        if *count >= self.max {
+            return None;
+        }
+        *count += 1;
+        Some(IpConnectionGuard {
+            limit: Arc::clone(self),
+            ip,
+        })
+    }
+
+    /// Open connections from `ip`.
+    pub fn count(&self, ip: IpAddr) -> usize {
+        let ip = ip.to_canonical();
+        let counts = self.shard(ip).lock().unwrap();
+        counts.get(&ip).copied().unwrap_or(0)
+    }
+
+    fn shard(&self, ip: IpAddr) -> &Mutex<HashMap<IpAddr, usize>> {
+        &self.shards[self.hasher.hash_one(ip) as usize % SHARDS]
+    }
+
+    fn release(&self, ip: IpAddr) {
+        // Poisoning only means another guard panicked mid-update; the counts
+        // are still consistent, so keep releasing
+        let mut counts = self
+            .shard(ip)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(count) = counts.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&ip);
+            }
+        }
+    }
+}
+
+/// Holds one connection slot for an IP (released on drop, panics included).
+pub struct IpConnectionGuard {
+    limit: Arc<IpConnectionLimit>,
+    ip: IpAddr,
+}
+
+impl Drop for IpConnectionGuard {
+    fn drop(&mut self) {
+        self.limit.release(self.ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_limit_per_ip() {
+        let limit = Arc::new(IpConnectionLimit::new(2));
+        let a = ip("203.0.113.1");
+
+        let first = limit.acquire(a).expect("first");
+        let _second = limit.acquire(a).expect("second");
+        assert!(limit.acquire(a).is_none());
+
+        // Other clients are unaffected
+        assert!(limit.acquire(ip("203.0.113.2")).is_some());
+
+        // Closing a connection frees a slot
+        drop(first);
+        assert_eq!(limit.count(a), 1);
+        assert!(limit.acquire(a).is_some());
+    }
+
+    #[test]
+    fn test_entries_removed_at_zero() {
+        let limit = Arc::new(IpConnectionLimit::new(1));
+        let guard = limit.acquire(ip("2001:db8::1")).unwrap();
+        drop(guard);
+        assert!(limit.shards.iter().all(|s| s.lock().unwrap().is_empty()));
+    }
+
+    #[test]
+    fn test_ipv4_mapped_shares_count() {
+        let limit = Arc::new(IpConnectionLimit::new(1));
+        let _guard = limit.acquire(ip("10.0.0.7")).unwrap();
+        assert!(limit.acquire(ip("::ffff:10.0.0.7")).is_none());
+    }
+
+    #[test]
+    fn test_released_on_panic() {
+        let limit = Arc::new(IpConnectionLimit::new(1));
+        let a = ip("198.51.100.4");
+
+        let task_limit = Arc::clone(&limit);
+        let result = std::thread::spawn(move || {
+            let _guard = task_limit.acquire(a).unwrap();
+            panic!("connection task failed");
+        })
+        .join();
+
+        assert!(result.is_err());
+        assert_eq!(limit.count(a), 0);
+        assert!(limit.acquire(a).is_some());
+    }
+}
@@ -0,0 +1,159 @@
+//! Keep-alive idle timeout for the whole life of a connection.
+//!
+//! `IDLE_TIMEOUT_SECS` only covers the wait for a connection's first byte.
+//! After that, an HTTP/1 connection that goes quiet between requests, or an
+//! HTTP/2 connection with no open streams, would hold its task forever.
+//! [`ConnectionActivity`] counts a connection's requests from the moment the
+//! service is called until the response body is dropped, so a slow script,
+//! a long poll or an SSE stream keeps it busy. [`ConnectionActivity::idle`]
+//! resolves once nothing has been in flight for the timeout.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use hyper::body::{Body, Frame, SizeHint};
+use tokio::time::Instant;
+
+/// In-flight requests and last activity of one connection.
+pub struct ConnectionActivity {
+    in_flight: AtomicUsize,
+    start: Instant,
+    /// Milliseconds after `start` when the last request finished.
+    last_done_ms: AtomicU64,
+}
+
+impl ConnectionActivity {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            in_flight: AtomicUsize::new(0),
+            start: Instant::now(),
+            last_done_ms: AtomicU64::new(0),
+        })
+    }
+
+    /// Marks a request as started; it ends when the guard is dropped.
+    pub fn begin(self: &Arc<Self>) -> ActivityGuard {
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        ActivityGuard(Arc::clone(self))
+    }
+
+    /// Resolves once no request has been in flight for `timeout`.
+    ///
+    /// Checks at most once per `timeout` while requests are running, so the
+    /// connection closes between `timeout` and twice that after going quiet.
+    pub async fn idle(&self, timeout: Duration) {
+        loop {
+            if self.in_flight.load(Ordering::Acquire) > 0 {
+                tokio::time::sleep(timeout).await;
+                continue;
+            }
+            let last_done =
+                self.start + Duration::from_millis(self.last_done_ms.load(Ordering::Acquire));
+            let deadline = last_done + timeout;
+            if Instant::now() >= deadline {
+                // A request may have started since the in-flight check
+                if self.in_flight.load(Ordering::Acquire) == 0 {
+                    return;
+                }
+                continue;
+            }
+            tokio::time::sleep_until(deadline).await;
+        }
+    }
+}
+
+/// Keeps a request counted as in flight while alive.
+pub struct ActivityGuard(Arc<ConnectionActivity>);
+
+impl Drop for ActivityGuard {
+    fn drop(&mut self) {
+        let activity = &self.0;
+        let now_ms = activity.start.elapsed().as_millis() as u64;
+        activity.last_done_ms.fetch_max(now_ms, Ordering::AcqRel);
+        activity.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Response body that holds its request's [`ActivityGuard`] until hyper
+/// drops it, after the last frame is written or the client goes away.
+pub struct TrackedBody<B> {
+    inner: B,
+    _guard: ActivityGuard,
+}
+
+impl<B> TrackedBody<B> {
+    pub fn new(inner: B, guard: ActivityGuard) -> Self {
+        Self {
+            inner,
+            _guard: guard,
+        }
+    }
+}
+
+impl<B: Body + Unpin> Body for TrackedBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn test_idle_after_timeout() {
+        let activity = ConnectionActivity::new();
+        let start = std::time::Instant::now();
+        activity.idle(TIMEOUT).await;
+        assert!(start.elapsed() >= TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_request_is_not_idle() {
+        let activity = ConnectionActivity::new();
+        let guard = activity.begin();
+
+        // A request running for several timeouts keeps the connection
+        let idle = tokio::time::timeout(TIMEOUT * 4, activity.idle(TIMEOUT)).await;
+        assert!(idle.is_err());
+
+        // The timeout counts from when it finished
+        drop(guard);
+        let done = std::time::Instant::now();
+        activity.idle(TIMEOUT).await;
+        assert!(done.elapsed() >= TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_tracked_body_holds_guard() {
+        let activity = ConnectionActivity::new();
+        let body = TrackedBody::new(Full::new(Bytes::from("ok")), activity.begin());
+        assert_eq!(activity.in_flight.load(Ordering::Acquire), 1);
+        assert_eq!(body.size_hint().exact(), Some(2));
+
+        let data = body.collect().await.unwrap().to_bytes();
+        assert_eq!(data, "ok");
+        assert_eq!(activity.in_flight.load(Ordering::Acquire), 0);
+    }
+}
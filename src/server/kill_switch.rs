@@ -0,0 +1,143 @@
+//! Runtime kill-switch for path prefixes.
+//!
+//! Lets operators take a misbehaving endpoint offline without a deploy.
+//! Disabled prefixes return a fixed status (default 503) before any routing
+//! or PHP execution. Prefixes come from `DISABLED_PATHS` at startup and can be
+//! toggled at runtime via the internal server's `/kill-switch` endpoint.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use hyper::StatusCode;
+
+/// Shared set of disabled path prefixes.
+pub struct KillSwitch {
+    prefixes: RwLock<Vec<String>>,
+    /// Fast path: false when no prefixes are disabled (no lock taken).
+    active: AtomicBool,
+    status: StatusCode,
+}
+
+impl KillSwitch {
+    /// Create a kill-switch with initial prefixes and the status to return.
+    pub fn new(prefixes: Vec<String>, status: StatusCode) -> Self {
+        let prefixes: Vec<String> = prefixes.into_iter().map(normalize_prefix).collect();
+        Self {
+            active: AtomicBool::new(!prefixes.is_empty()),
+            prefixes: RwLock::new(prefixes),
+            status,
+        }
+    }
+
+    /// Status returned for disabled paths.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Check a request path. Returns the configured status if it is disabled.
+    #[inline]
+    pub fn check(&self, path: &str) -> Option<StatusCode> {
+        if !self.active.load(Ordering::Relaxed) {
+            return None;
+        }
+        let prefixes = self.prefixes.read().unwrap();
+        prefixes
+            .iter()
+            .any(|prefix| prefix_matches(path, prefix))
+            .then_some(self.status)
+    }
+
+    /// Disable a path prefix. Returns false if it was already disabled.
+    pub fn disable(&self, prefix: &str) -> bool {
+        let prefix = normalize_prefix(prefix.to_string());
+        let mut prefixes = self.prefixes.write().unwrap();
+        if prefixes.contains(&prefix) {
+            return false;
+        }
+        prefixes.push(prefix);
+        self.active.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// Re-enable a path prefix. Returns false if it was not disabled.
+    pub fn enable(&self, prefix: &str) -> bool {
+        let prefix = normalize_prefix(prefix.to_string());
+        let mut prefixes = self.prefixes.write().unwrap();
+        let before = prefixes.len();
+        prefixes.retain(|p| *p != prefix);
+        self.active.store(!prefixes.is_empty(), Ordering::Relaxed);
+        prefixes.len() != before
+    }
+
+    /// Currently disabled prefixes.
+    pub fn disabled(&self) -> Vec<String> {
+        self.prefixes.read().unwrap().clone()
+    }
+}
+
+/// Ensure a leading slash so "api" and "/api" are the same prefix.
+fn normalize_prefix(prefix: String) -> String {
+    if prefix.starts_with('/') {
+        prefix
+    } else {
+        format!("/{}", prefix)
+    }
+}
+
+/// Segment-aware prefix match: "/api" matches "/api" and "/api/x", not "/apix".
+#[inline]
+pub(super) fn prefix_matches(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || prefix.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_kill_switch_allows_all() {
+        let ks = KillSwitch::new(Vec::new(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(ks.check("/"), None);
+        assert_eq!(ks.check("/api/users"), None);
+    }
+
+    #[test]
+    fn test_toggle_path_off_and_on() {
+        let ks = KillSwitch::new(Vec::new(), StatusCode::NOT_FOUND);
+
+        assert!(ks.disable("/api/reports"));
+        assert!(!ks.disable("/api/reports"));
+        assert_eq!(ks.check("/api/reports"), Some(StatusCode::NOT_FOUND));
+        assert_eq!(ks.check("/api/reports/42"), Some(StatusCode::NOT_FOUND));
+        assert_eq!(ks.check("/api/users"), None);
+        assert_eq!(ks.check("/index.php"), None);
+
+        assert!(ks.enable("/api/reports"));
+        assert!(!ks.enable("/api/reports"));
+        assert_eq!(ks.check("/api/reports"), None);
+    }
+
+    #[test]
+    fn test_prefix_is_segment_aware() {
+        let ks = KillSwitch::new(vec!["/api".into()], StatusCode::SERVICE_UNAVAILABLE);
+        assert!(ks.check("/api").is_some());
+        assert!(ks.check("/api/").is_some());
+        assert!(ks.check("/api/x").is_some());
+        assert!(ks.check("/apix").is_none());
+
+        let ks = KillSwitch::new(vec!["/static/".into()], StatusCode::SERVICE_UNAVAILABLE);
+        assert!(ks.check("/static/app.js").is_some());
+    }
+
+    #[test]
+    fn test_prefix_normalized() {
+        let ks = KillSwitch::new(vec!["admin".into()], StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(ks.disabled(), vec!["/admin".to_string()]);
+        assert!(ks.check("/admin/login.php").is_some());
+        assert!(ks.enable("admin"));
+        assert!(ks.check("/admin/login.php").is_none());
+    }
+}
@@ -0,0 +1,115 @@
+//! Server lifecycle phase, shared by the health probes and request handling.
+//!
+//! The listener binds before the PHP workers are up and keeps serving open
+//! connections while draining. In both windows PHP requests are answered
+//! with 503 and `Retry-After` instead of being queued on an executor that
+//! can't finish them; `/health/startup` and `/ready` report the same phase.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Where the server is in its lifetime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// PHP workers are still starting.
+    Starting,
+    /// Serving normally.
+    Running,
+    /// Shutdown triggered; open connections are draining.
+    Draining,
+}
+
+impl Phase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Starting => "starting",
+            Self::Running => "running",
+            Self::Draining => "draining",
+        }
+    }
+}
+
+/// Shared lifecycle phase. Only moves forward: starting, running, draining.
+#[derive(Debug)]
+pub struct Lifecycle {
+    phase: AtomicU8,
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self {
+            phase: AtomicU8::new(Phase::Starting as u8),
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        match self.phase.load(Ordering::Acquire) {
+            0 => Phase::Starting,
+            1 => Phase::Running,
+            _ => Phase::Draining,
+        }
+    }
+
+    /// Whether PHP requests can be executed.
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.phase() == Phase::Running
+    }
+
+    /// Whether startup has completed (stays true while draining).
+    pub fn is_started(&self) -> bool {
+        self.phase() != Phase::Starting
+    }
+
+    /// Startup finished. Ignored once draining has begun.
+    pub fn set_running(&self) {
+        let _ = self.phase.compare_exchange(
+            Phase::Starting as u8,
+            Phase::Running as u8,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+    }
+
+    /// Shutdown triggered.
+    pub fn set_draining(&self) {
+        self.phase.store(Phase::Draining as u8, Ordering::Release);
+    }
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_transitions() {
+        let lifecycle = Lifecycle::new();
+        assert_eq!(lifecycle.phase(), Phase::Starting);
+        assert!(!lifecycle.is_started());
+        assert!(!lifecycle.is_running());
+
+        lifecycle.set_running();
+        assert_eq!(lifecycle.phase(), Phase::Running);
+        assert!(lifecycle.is_started());
+        assert!(lifecycle.is_running());
+
+        lifecycle.set_draining();
+        assert_eq!(lifecycle.phase().as_str(), "draining");
+        assert!(lifecycle.is_started());
+        assert!(!lifecycle.is_running());
+    }
+
+    #[test]
+    fn test_draining_before_startup_completes() {
+        let lifecycle = Lifecycle::new();
+        lifecycle.set_draining();
+        // Workers finishing startup late must not reopen the server
+        lifecycle.set_running();
+        assert_eq!(lifecycle.phase(), Phase::Draining);
+    }
+}
@@ -0,0 +1,252 @@
+//! Request shedding under memory pressure (`MEMORY_PRESSURE_*`).
+//!
+//! A background task samples memory usage against the cgroup limit (or host
+//! memory when there is none). Above the high threshold, requests under
+//! `MEMORY_SHED_PATHS` get 503 so low-priority traffic stops adding to the
+//! load; above the critical threshold every new request does, until usage
+//! drops again on the next sample.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use tracing::{info, warn};
+
+use super::internal::SystemMetrics;
+use super::kill_switch::prefix_matches;
+use crate::config::CgroupLimits;
+
+/// Memory pressure level, as exported on /metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PressureLevel {
+    Normal = 0,
+    High = 1,
+    Critical = 2,
+}
+
+impl PressureLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+/// Current pressure level and the shedding policy that goes with it.
+#[derive(Debug)]
+pub struct MemoryPressure {
+    /// Usage percent for `High` (0 = off).
+    high_percent: u8,
+    /// Usage percent for `Critical` (0 = off).
+    critical_percent: u8,
+    /// Path prefixes shed under `High`.
+    shed_prefixes: Vec<String>,
+    level: AtomicU8,
+    /// Last sampled usage, in hundredths of a percent.
+    usage_centi: AtomicU64,
+    shed_total: AtomicU64,
+}
+
+impl MemoryPressure {
+    pub fn new(high_percent: u8, critical_percent: u8, shed_paths: Vec<String>) -> Self {
+        let shed_prefixes = shed_paths
+            .into_iter()
+            .map(|p| {
+                if p.starts_with('/') {
+                    p
+                } else {
+                    format!("/{p}")
+                }
+            })
+            .collect();
+        Self {
+            high_percent,
+            critical_percent,
+            shed_prefixes,
+            level: AtomicU8::new(PressureLevel::Normal as u8),
+            usage_centi: AtomicU64::new(0),
+            shed_total: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether any threshold is set (otherwise no sampling is needed).
+    pub fn is_enabled(&self) -> bool {
+        self.high_percent > 0 || self.critical_percent > 0
+    }
+
+    pub fn level(&self) -> PressureLevel {
+        match self.level.load(Ordering::Relaxed) {
+            0 => PressureLevel::Normal,
+            1 => PressureLevel::High,
+            _ => PressureLevel::Critical,
+        }
+    }
+
+    /// Last sampled memory usage in percent.
+    pub fn usage_percent(&self) -> f64 {
+        self.usage_centi.load(Ordering::Relaxed) as f64 / 100.0
+    }
+
+    /// Requests shed so far.
+    pub fn shed_total(&self) -> u64 {
+        self.shed_total.load(Ordering::Relaxed)
+    }
+
+    /// Record a usage sample and switch level, logging transitions.
+    pub fn update(&self, usage_percent: f64) -> PressureLevel {
+        let reached = |threshold: u8| threshold > 0 && usage_percent >= threshold as f64;
+        let level = if reached(self.critical_percent) {
+            PressureLevel::Critical
+        } else if reached(self.high_percent) {
+            PressureLevel::High
+        } else {
+            PressureLevel::Normal
+        };
+
+        self.usage_centi
+            .store((usage_percent * 100.0) as u64, Ordering::Relaxed);
+        let previous = self.level.swap(level as u8, Ordering::Relaxed);
+        if previous != level as u8 {
+            if level == PressureLevel::Normal {
+                info!(usage_percent, "Memory pressure back to normal");
+            } else {
+                warn!(
+                    usage_percent,
+                    level = level.as_str(),
+                    "Memory pressure, shedding requests"
+                );
+            }
+        }
+        level
+    }
+
+    /// Render level, usage and shed count in Prometheus text format.
+    pub fn to_prometheus(&self) -> String {
+        format!(
+            "\n# HELP tokio_php_memory_pressure_level Memory pressure (0 = normal, 1 = high, 2 = critical)\n\
+             # TYPE tokio_php_memory_pressure_level gauge\n\
+             tokio_php_memory_pressure_level {}\n\
+             \n\
+             # HELP tokio_php_memory_pressure_usage_percent Memory usage at the last sample\n\
+             # TYPE tokio_php_memory_pressure_usage_percent gauge\n\
+             tokio_php_memory_pressure_usage_percent {:.2}\n\
+             \n\
+             # HELP tokio_php_memory_shed_requests_total Requests answered 503 under memory pressure\n\
+             # TYPE tokio_php_memory_shed_requests_total counter\n\
+             tokio_php_memory_shed_requests_total {}\n",
+            self.level() as u8,
+            self.usage_percent(),
+            self.shed_total()
+        )
+    }
+
+    /// Whether a request for `path` should be shed now. Counts shed requests.
+    #[inline]
+    pub fn should_shed(&self, path: &str) -> bool {
+        let shed = match self.level() {
+            PressureLevel::Normal => false,
+            PressureLevel::High => self
+                .shed_prefixes
+                .iter()
+                .any(|prefix| prefix_matches(path, prefix)),
+            PressureLevel::Critical => true,
+        };
+        if shed {
+            self.shed_total.fetch_add(1, Ordering::Relaxed);
+        }
+        shed
+    }
+}
+
+/// Memory usage in percent of the cgroup limit, or of host memory.
+pub fn read_usage_percent() -> Option<f64> {
+    read_cgroup_usage(Path::new("/sys/fs/cgroup")).or_else(|| {
+        let sys = SystemMetrics::read();
+        (sys.memory_total_bytes > 0).then_some(sys.memory_usage_percent)
+    })
+}
+
+/// Usage against a v2 or v1 memory limit; `None` when unlimited.
+fn read_cgroup_usage(root: &Path) -> Option<f64> {
+    let limit = CgroupLimits::read_from(root)
+        .memory_limit
+        .filter(|&limit| limit > 0)?;
+    let usage = CgroupLimits::memory_usage(root)?;
+    Some(usage as f64 * 100.0 / limit as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_pressure_levels_and_shedding() {
+        let pressure = MemoryPressure::new(80, 95, vec!["/reports".into(), "static".into()]);
+        assert!(pressure.is_enabled());
+
+        assert_eq!(pressure.update(50.0), PressureLevel::Normal);
+        assert!(!pressure.should_shed("/reports/daily"));
+
+        // High: only the listed prefixes
+        assert_eq!(pressure.update(85.5), PressureLevel::High);
+        assert!(pressure.should_shed("/reports/daily"));
+        assert!(pressure.should_shed("/static/app.css"));
+        assert!(!pressure.should_shed("/reports-v2"));
+        assert!(!pressure.should_shed("/checkout"));
+
+        // Critical: everything
+        assert_eq!(pressure.update(97.0), PressureLevel::Critical);
+        assert!(pressure.should_shed("/checkout"));
+        assert_eq!(pressure.shed_total(), 3);
+        let text = pressure.to_prometheus();
+        assert!(text.contains("tokio_php_memory_pressure_level 2\n"));
+        assert!(text.contains("tokio_php_memory_pressure_usage_percent 97.00\n"));
+        assert!(text.contains("tokio_php_memory_shed_requests_total 3\n"));
+
+        assert_eq!(pressure.update(60.0), PressureLevel::Normal);
+        assert!(!pressure.should_shed("/checkout"));
+    }
+
+    #[test]
+    fn test_shedding_matches_the_decoded_path() {
+        use crate::server::routing::match_path;
+
+        let pressure = MemoryPressure::new(80, 95, vec!["/reports".into()]);
+        pressure.update(85.0);
+        for raw in ["/%72eports/x", "//reports/x", "/a/../reports/x"] {
+            assert!(!pressure.should_shed(raw), "{raw}");
+            assert!(pressure.should_shed(&match_path(raw)), "{raw}");
+        }
+    }
+
+    #[test]
+    fn test_disabled_thresholds() {
+        let pressure = MemoryPressure::new(0, 0, Vec::new());
+        assert!(!pressure.is_enabled());
+        assert_eq!(pressure.update(100.0), PressureLevel::Normal);
+
+        // Critical only
+        let pressure = MemoryPressure::new(0, 90, Vec::new());
+        assert_eq!(pressure.update(89.0), PressureLevel::Normal);
+        assert_eq!(pressure.update(90.0), PressureLevel::Critical);
+    }
+
+    #[test]
+    fn test_cgroup_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("memory.current"), "805306368\n").unwrap();
+        fs::write(dir.path().join("memory.max"), "1073741824\n").unwrap();
+        assert_eq!(read_cgroup_usage(dir.path()), Some(75.0));
+
+        fs::write(dir.path().join("memory.max"), "max\n").unwrap();
+        assert_eq!(read_cgroup_usage(dir.path()), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("memory")).unwrap();
+        fs::write(dir.path().join("memory/memory.usage_in_bytes"), "100\n").unwrap();
+        fs::write(dir.path().join("memory/memory.limit_in_bytes"), "400\n").unwrap();
+        assert_eq!(read_cgroup_usage(dir.path()), Some(25.0));
+    }
+}
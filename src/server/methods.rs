@@ -0,0 +1,140 @@
+//! Request methods passed to PHP (`ALLOWED_METHODS`).
+//!
+//! Anything outside the list answers `405` with an `Allow` header. OPTIONS
+//! is never a 405: unless it is listed explicitly the server answers it
+//! itself with `204` and the same `Allow` header (CORS middleware adds its
+//! headers to that response). TRACE and CONNECT cannot be enabled.
+
+use hyper::header::HeaderValue;
+use hyper::Method;
+
+/// Methods PHP can receive, in the order they are listed in `Allow`.
+const SUPPORTED: [&str; 8] = [
+    "GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS", "QUERY",
+];
+
+/// Method allowlist with the prebuilt `Allow` header value.
+#[derive(Clone, Debug)]
+pub struct AllowedMethods {
+    methods: Vec<Method>,
+    allow: HeaderValue,
+}
+
+/// Default list: every supported method except OPTIONS, which PHP only
+/// receives when ALLOWED_METHODS opts in.
+const DEFAULT: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "QUERY"];
+
+impl Default for AllowedMethods {
+    fn default() -> Self {
+        Self::parse(&DEFAULT).expect("default methods parse")
+    }
+}
+
+impl AllowedMethods {
+    /// Parse method names (case-insensitive). GET implies HEAD.
+    pub fn parse<S: AsRef<str>>(names: &[S]) -> Result<Self, String> {
+        if names.is_empty() {
+            return Err("at least one method is required".into());
+        }
+        let mut listed = [false; SUPPORTED.len()];
+        for name in names {
+            let name = name.as_ref().trim().to_ascii_uppercase();
+            match SUPPORTED.iter().position(|m| *m == name) {
+                Some(index) => listed[index] = true,
+                None if name == "TRACE" || name == "CONNECT" => {
+                    return Err(format!("{name} cannot be enabled"));
+                }
+                None => {
+                    return Err(format!(
+                        "unsupported method '{name}' (expected one of {})",
+                        SUPPORTED.join(", ")
+                    ));
+                }
+            }
+        }
+        // HEAD is GET without the body
+        if listed[0] {
+            listed[1] = true;
+        }
+
+        let methods: Vec<Method> = SUPPORTED
+            .iter()
+            .zip(listed)
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| Method::from_bytes(name.as_bytes()).expect("valid method"))
+            .collect();
+
+        // OPTIONS is always answered, so it is always allowed
+        let mut allow: Vec<&str> = methods.iter().map(Method::as_str).collect();
+        if !allow.contains(&"OPTIONS") {
+            allow.push("OPTIONS");
+        }
+        let allow = HeaderValue::from_str(&allow.join(", ")).expect("valid header value");
+
+        Ok(Self { methods, allow })
+    }
+
+    /// Whether requests with this method are passed to PHP.
+    #[inline]
+    pub fn contains(&self, method: &Method) -> bool {
+        self.methods.contains(method)
+    }
+
+    /// `Allow` header value for 405 and server-answered OPTIONS responses.
+    #[inline]
+    pub fn allow_header(&self) -> &HeaderValue {
+        &self.allow
+    }
+
+    /// Method names, for the startup log.
+    pub fn names(&self) -> Vec<&str> {
+        self.methods.iter().map(Method::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_allows_all_supported_but_options() {
+        let methods = AllowedMethods::default();
+        for name in DEFAULT {
+            assert!(methods.contains(&Method::from_bytes(name.as_bytes()).unwrap()));
+        }
+        assert!(!methods.contains(&Method::OPTIONS));
+        assert!(!methods.contains(&Method::TRACE));
+        assert_eq!(
+            methods.allow_header(),
+            "GET, HEAD, POST, PUT, PATCH, DELETE, QUERY, OPTIONS"
+        );
+
+        // Listed explicitly, OPTIONS reaches PHP
+        let all = AllowedMethods::parse(&SUPPORTED).unwrap();
+        assert!(all.contains(&Method::OPTIONS));
+        assert_eq!(
+            all.allow_header(),
+            "GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS, QUERY"
+        );
+    }
+
+    #[test]
+    fn test_subset() {
+        let methods = AllowedMethods::parse(&["post", " get "]).unwrap();
+        assert!(methods.contains(&Method::GET));
+        assert!(methods.contains(&Method::HEAD));
+        assert!(methods.contains(&Method::POST));
+        assert!(!methods.contains(&Method::PUT));
+        // OPTIONS is answered by the server, so it is still advertised
+        assert!(!methods.contains(&Method::OPTIONS));
+        assert_eq!(methods.allow_header(), "GET, HEAD, POST, OPTIONS");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(AllowedMethods::parse::<&str>(&[]).is_err());
+        assert!(AllowedMethods::parse(&["GET", "TRACE"]).is_err());
+        assert!(AllowedMethods::parse(&["connect"]).is_err());
+        assert!(AllowedMethods::parse(&["PROPFIND"]).is_err());
+    }
+}
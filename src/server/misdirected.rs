@@ -0,0 +1,174 @@
+//! 421 Misdirected Request for hosts the TLS certificate doesn't cover
+//! (`TLS_CHECK_AUTHORITY`).
+//!
+//! HTTP/2 clients may coalesce requests for several hosts onto one TLS
+//! connection when they resolve to the same address and the certificate
+//! seemed to fit. If it doesn't cover a request's host, answering 421 tells
+//! the client to retry on a fresh connection (RFC 9110, section 15.5.20).
+
+use hyper::header::HOST;
+use hyper::http::uri::Authority;
+use hyper::Request;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use webpki::EndEntityCert;
+
+/// Leaf certificate presented on TLS connections.
+#[derive(Clone, Debug)]
+pub struct CertHosts {
+    cert: CertificateDer<'static>,
+}
+
+impl CertHosts {
+    pub fn new(cert: CertificateDer<'static>) -> Result<Self, String> {
+        EndEntityCert::try_from(&cert).map_err(|e| format!("invalid certificate: {e}"))?;
+        Ok(Self { cert })
+    }
+
+    /// Whether the certificate is valid for `host` (DNS name or IP address).
+    pub fn covers(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let (Ok(cert), Ok(name)) = (
+            EndEntityCert::try_from(&self.cert),
+            ServerName::try_from(host),
+        ) else {
+            return false;
+        };
+        cert.verify_is_valid_for_subject_name(&name).is_ok()
+    }
+}
+
+/// Whether the request is addressed to a host the certificate doesn't cover.
+///
+/// Uses `:authority` for HTTP/2 and the `Host` header otherwise. Requests
+/// without either are left alone.
+pub fn is_misdirected<B>(req: &Request<B>, hosts: &CertHosts) -> bool {
+    let host = match req.uri().authority() {
+        Some(authority) => authority.host().to_owned(),
+        None => match req
+            .headers()
+            .get(HOST)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<Authority>().ok())
+        {
+            Some(authority) => authority.host().to_owned(),
+            None => return false,
+        },
+    };
+    !hosts.covers(&host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed, SANs: example.com, *.example.com, 127.0.0.1.
+    const CERT_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBsjCCAVegAwIBAgIUNVsyH/8/259fC9mhXaBO+OnIfEQwCgYIKoZIzj0EAwIw
+FjEUMBIGA1UEAwwLZXhhbXBsZS5jb20wIBcNMjYxMDE1MTIzMzAyWhgPMjEyNjA5
+MjExMjMzMDJaMBYxFDASBgNVBAMMC2V4YW1wbGUuY29tMFkwEwYHKoZIzj0CAQYI
+KoZIzj0DAQcDQgAEzr2B7sTOG64nM/0zUhPFtX49WdzZHMSsz1KFkgsfWri+aONt
+36yiZWnx3KC7fub3HvMlBIWSynq9GQRueIVyYaOBgDB+MB0GA1UdDgQWBBQilO0A
+Azodtc/fU7qntLkqK3r/HDAfBgNVHSMEGDAWgBQilO0AAzodtc/fU7qntLkqK3r/
+HDAPBgNVHRMBAf8EBTADAQH/MCsGA1UdEQQkMCKCC2V4YW1wbGUuY29tgg0qLmV4
+YW1wbGUuY29thwR/AAABMAoGCCqGSM49BAMCA0kAMEYCIQDxtoXQjSLDRf5Slgg/
+x+0yOJCwK9QOHZf2tmI/Oryt+QIhAJkEEhFwYLalo+4a82A7Re5wY3dkdYKBmbT8
+kKKQGIab
+-----END CERTIFICATE-----
+";
+
+    fn test_cert_hosts() -> CertHosts {
+        let cert = rustls_pemfile::certs(&mut CERT_PEM.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        CertHosts::new(cert).unwrap()
+    }
+
+    fn get(uri: &str, host: Option<&str>) -> Request<()> {
+        let mut builder = Request::get(uri);
+        if let Some(host) = host {
+            builder = builder.header(HOST, host);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_covered_hosts() {
+        let hosts = test_cert_hosts();
+        assert!(hosts.covers("example.com"));
+        assert!(hosts.covers("www.example.com"));
+        assert!(hosts.covers("127.0.0.1"));
+        assert!(!hosts.covers("a.b.example.com"));
+        assert!(!hosts.covers("example.org"));
+        assert!(!hosts.covers("[::1]"));
+    }
+
+    #[test]
+    fn test_request_host() {
+        let hosts = test_cert_hosts();
+        // HTTP/2 :authority
+        assert!(!is_misdirected(
+            &get("https://api.example.com/", None),
+            &hosts
+        ));
+        assert!(is_misdirected(
+            &get("https://other.test:8443/", None),
+            &hosts
+        ));
+        // HTTP/1.1 Host header, port ignored
+        assert!(!is_misdirected(&get("/", Some("example.com:8443")), &hosts));
+        assert!(is_misdirected(&get("/", Some("example.org")), &hosts));
+        // No host at all
+        assert!(!is_misdirected(&get("/", None), &hosts));
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_connection_mismatch() {
+        use bytes::Bytes;
+        use http_body_util::Full;
+        use hyper::service::service_fn;
+        use hyper::StatusCode;
+        use hyper_util::rt::{TokioExecutor, TokioIo};
+        use hyper_util::server::conn::auto;
+        use std::convert::Infallible;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let hosts = test_cert_hosts();
+            let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+                let status = if is_misdirected(&req, &hosts) {
+                    StatusCode::MISDIRECTED_REQUEST
+                } else {
+                    StatusCode::OK
+                };
+                let mut response = hyper::Response::new(Full::new(Bytes::new()));
+                *response.status_mut() = status;
+                async move { Ok::<_, Infallible>(response) }
+            });
+            let _ = auto::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+
+        let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (mut client, connection) = h2::client::handshake(tcp).await.unwrap();
+        tokio::spawn(async move {
+            let _ = connection.await;
+        });
+
+        // Both hosts share one connection; only the one the cert covers is served
+        let mut status = |uri: &str| {
+            let req = Request::get(uri).body(()).unwrap();
+            client.send_request(req, true).unwrap().0
+        };
+        let covered = status("https://www.example.com/");
+        let other = status("https://shop.example.org/");
+        assert_eq!(covered.await.unwrap().status(), StatusCode::OK);
+        assert_eq!(
+            other.await.unwrap().status(),
+            StatusCode::MISDIRECTED_REQUEST
+        );
+    }
+}
@@ -69,14 +69,30 @@
 //! ```
 
 pub mod access_log;
+mod auto_cert;
+pub mod canonical;
 pub mod config;
 pub mod connection;
+pub mod connection_limit;
+pub mod csp;
 pub mod error_pages;
 pub mod file_cache;
 mod internal;
+pub mod internal_access;
+pub mod ip_limit;
+mod keepalive;
+pub mod kill_switch;
+mod lifecycle;
+mod memory_pressure;
+pub mod methods;
+pub mod misdirected;
+mod proxy;
+mod readiness;
 pub mod request;
 pub mod response;
+pub mod route_label;
 mod routing;
+pub mod trusted_proxy;
 
 use std::io::BufReader;
 use std::net::SocketAddr;
@@ -88,21 +104,48 @@ use std::time::Duration;
 use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
 use tokio::net::TcpListener;
 use tokio::sync::watch;
-use tokio_rustls::rustls::pki_types::CertificateDer;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use tokio_rustls::rustls::ServerConfig as RustlsConfig;
 use tokio_rustls::TlsAcceptor;
 use tracing::{debug, error, info, warn};
 
-pub use config::ServerConfig;
+use auto_cert::AutoCertGenerator;
+pub use canonical::CanonicalLinks;
+pub use config::{ReloadableConfig, ServerConfig};
 use connection::ConnectionContext;
+use connection_limit::{ConnectionLimit, GlobalConnectionLimit, LimitReached};
+pub use csp::CspNonce;
 use error_pages::ErrorPages;
 use file_cache::FileCache;
-use internal::{run_internal_server, RequestMetrics, ServerConfigInfo};
+use internal::{
+    run_internal_server, ConnectionMetrics, Diagnostics, OpcacheStatus, RequestMetrics,
+    ServerConfigInfo,
+};
+pub use internal_access::InternalAccess;
+use ip_limit::IpConnectionLimit;
+use kill_switch::KillSwitch;
+use lifecycle::Lifecycle;
+use memory_pressure::MemoryPressure;
+pub use methods::AllowedMethods;
+use misdirected::CertHosts;
+use proxy::ReverseProxy;
+use readiness::Readiness;
+pub use route_label::RouteLabels;
 use routing::RouteConfig;
+pub use routing::{DirectoryRequest, ProxyRoutes};
+pub use trusted_proxy::TrustedProxies;
 
-use crate::config::RateLimitConfig;
+use crate::config::{AccessLogConfig, RateLimitConfig};
 use crate::executor::ScriptExecutor;
 use crate::middleware::rate_limit::RateLimiter;
+use crate::middleware::{Middleware, MiddlewareChain};
+use crate::profiler::{LastProfile, ProfileStats};
+
+/// How often `run()` checks whether the PHP workers have started.
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How often memory usage is sampled for request shedding.
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
 
 /// HTTP server with pluggable script executor.
 ///
@@ -131,18 +174,24 @@ pub struct Server<E: ScriptExecutor> {
     config: ServerConfig,
     executor: Arc<E>,
     tls_acceptor: Option<TlsAcceptor>,
+    /// Certificate names for 421 checks (TLS_CHECK_AUTHORITY)
+    cert_hosts: Option<Arc<CertHosts>>,
     /// Route configuration (INDEX_FILE handling)
     route_config: Arc<RouteConfig>,
     /// Active connections counter
     active_connections: Arc<AtomicUsize>,
     /// Request metrics by HTTP method
     request_metrics: Arc<RequestMetrics>,
+    /// Connection lifecycle counters (accepts, TLS handshakes, error closes)
+    connection_metrics: Arc<ConnectionMetrics>,
     /// Cached custom error pages
     error_pages: ErrorPages,
-    /// Per-IP rate limiter
-    rate_limiter: Option<Arc<RateLimiter>>,
+    /// Hot-swappable settings (rate limiter, access log, static cache TTL)
+    live_config: watch::Sender<Arc<ReloadableConfig>>,
     /// File cache (LRU, max 200 entries)
     file_cache: Arc<FileCache>,
+    /// Disabled path prefixes (DISABLED_PATHS, toggled via /kill-switch)
+    kill_switch: Arc<KillSwitch>,
     /// Cached document root as static str (zero allocation per request)
     document_root_static: std::borrow::Cow<'static, str>,
     /// Shutdown signal sender
@@ -151,10 +200,14 @@ pub struct Server<E: ScriptExecutor> {
     shutdown_rx: watch::Receiver<bool>,
     /// Shutdown initiated flag
     shutdown_initiated: Arc<AtomicBool>,
+    /// Starting / running / draining, shared with probes and connections
+    lifecycle: Arc<Lifecycle>,
+    /// Sampled memory pressure level (MEMORY_PRESSURE_*)
+    memory_pressure: Arc<MemoryPressure>,
     /// Profiling enabled (compile-time with debug-profile feature)
     profile_enabled: bool,
-    /// Access logging enabled (ACCESS_LOG=1)
-    access_log_enabled: bool,
+    /// Middleware registered with `with_middleware`, run around routing
+    middleware: MiddlewareChain,
 }
 
 impl<E: ScriptExecutor + 'static> Server<E> {
@@ -164,7 +217,13 @@ impl<E: ScriptExecutor + 'static> Server<E> {
         executor: E,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         // Create route configuration
-        let route_config = RouteConfig::new(&config.document_root, config.index_file.as_deref());
+        let route_config = RouteConfig::new(&config.document_root, config.index_file.as_deref())
+            .with_autoindex(&config.autoindex)
+            .with_directory_request(config.directory_request)
+            .with_proxy_routes(config.proxy_routes.clone());
+        if !config.autoindex.is_empty() {
+            info!("Directory listing enabled under: {:?}", config.autoindex);
+        }
 
         // Validate index file at startup if configured
         if let Some(ref index_file_path) = route_config.index_file_path {
@@ -201,16 +260,26 @@ impl<E: ScriptExecutor + 'static> Server<E> {
             }
         }
 
-        let tls_acceptor = if config.has_tls() {
+        let (tls_acceptor, cert_hosts) = if config.has_tls() {
             match Self::load_tls_config(&config) {
-                Ok(tls_config) => Some(TlsAcceptor::from(Arc::new(tls_config))),
+                Ok((tls_config, leaf)) => {
+                    let cert_hosts = if config.tls_check_authority {
+                        CertHosts::new(leaf)
+                            .map_err(|e| warn!("TLS_CHECK_AUTHORITY disabled: {}", e))
+                            .ok()
+                            .map(Arc::new)
+                    } else {
+                        None
+                    };
+                    (Some(TlsAcceptor::from(Arc::new(tls_config))), cert_hosts)
+                }
                 Err(e) => {
                     warn!("Failed to load TLS config: {}. Running without TLS.", e);
-                    None
+                    (None, None)
                 }
             }
         } else {
-            None
+            (None, None)
         };
 
         // Load custom error pages if configured
@@ -220,9 +289,38 @@ impl<E: ScriptExecutor + 'static> Server<E> {
             ErrorPages::new()
         };
 
+        let disabled_status = hyper::StatusCode::from_u16(config.disabled_path_status)
+            .unwrap_or(hyper::StatusCode::SERVICE_UNAVAILABLE);
+        if !config.disabled_paths.is_empty() {
+            warn!(
+                "Kill-switch: {:?} disabled (returns {})",
+                config.disabled_paths,
+                disabled_status.as_u16()
+            );
+        }
+        let kill_switch = Arc::new(KillSwitch::new(
+            config.disabled_paths.clone(),
+            disabled_status,
+        ));
+
+        let memory_pressure = Arc::new(MemoryPressure::new(
+            config.memory_pressure.high_percent,
+            config.memory_pressure.critical_percent,
+            config.memory_pressure.shed_paths.clone(),
+        ));
+
         // Create shutdown channel
         let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
+        let live_config = watch::Sender::new(Arc::new(ReloadableConfig {
+            rate_limiter: None,
+            access_log_enabled: false,
+            access_log_min_status: config.access_log_min_status,
+            static_cache_ttl: config.static_cache_ttl,
+            response: config.response.clone(),
+            output_limits: config.output_limits,
+        }));
+
         // Leak document_root to get 'static lifetime (lives for entire process)
         // This avoids string allocation on every request for $_SERVER['DOCUMENT_ROOT']
         let document_root_static: std::borrow::Cow<'static, str> = std::borrow::Cow::Borrowed(
@@ -233,18 +331,23 @@ impl<E: ScriptExecutor + 'static> Server<E> {
             config,
             executor: Arc::new(executor),
             tls_acceptor,
+            cert_hosts,
             route_config: Arc::new(route_config),
             active_connections: Arc::new(AtomicUsize::new(0)),
             request_metrics: Arc::new(RequestMetrics::new()),
+            connection_metrics: Arc::new(ConnectionMetrics::new()),
             error_pages,
-            rate_limiter: None,
+            live_config,
             file_cache: Arc::new(FileCache::new()),
+            kill_switch,
             document_root_static,
             shutdown_tx,
             shutdown_rx,
             shutdown_initiated: Arc::new(AtomicBool::new(false)),
+            lifecycle: Arc::new(Lifecycle::new()),
+            memory_pressure,
             profile_enabled: false,
-            access_log_enabled: false,
+            middleware: MiddlewareChain::new(),
         })
     }
 
@@ -260,9 +363,23 @@ impl<E: ScriptExecutor + 'static> Server<E> {
         self
     }
 
+    /// Add a middleware to every request (builder pattern).
+    ///
+    /// Middleware run in priority order, lowest first. Request middleware see
+    /// the method, URI and headers (the body is still streamed to PHP) and may
+    /// change headers or answer the request; response middleware run on
+    /// buffered responses, while streamed ones (SSE, files, proxy) only get
+    /// the headers set through `Context::set_response_header`.
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        info!("Middleware registered: {}", middleware.name());
+        self.middleware = std::mem::take(&mut self.middleware).with(middleware);
+        self
+    }
+
     /// Enable access logging for this server.
-    pub fn with_access_log_enabled(mut self, enabled: bool) -> Self {
-        self.access_log_enabled = enabled;
+    pub fn with_access_log_enabled(self, enabled: bool) -> Self {
+        self.live_config
+            .send_modify(|c| Arc::make_mut(c).access_log_enabled = enabled);
         if enabled {
             info!("Access logging enabled (ACCESS_LOG=1)");
         }
@@ -270,27 +387,176 @@ impl<E: ScriptExecutor + 'static> Server<E> {
     }
 
     /// Configure rate limiting for this server.
-    pub fn with_rate_limiter(mut self, config: Option<RateLimitConfig>) -> Self {
+    pub fn with_rate_limiter(self, config: Option<RateLimitConfig>) -> Self {
         if let Some(rl) = config {
-            let limiter = RateLimiter::new(rl.limit(), rl.window_secs());
+            let limiter = RateLimiter::with_algorithm(rl.limit(), rl.window_secs(), rl.algorithm());
             info!(
-                "Rate limiting enabled: {} requests per {} seconds per IP",
+                "Rate limiting enabled: {} requests per {} seconds per IP ({})",
                 limiter.limit(),
-                limiter.window_secs()
+                limiter.window_secs(),
+                rl.algorithm().as_str()
             );
-            self.rate_limiter = Some(Arc::new(limiter));
+            self.live_config
+                .send_modify(|c| Arc::make_mut(c).rate_limiter = Some(Arc::new(limiter)));
         }
         self
     }
 
+    /// Apply reloadable settings from a freshly loaded config (SIGHUP).
+    ///
+    /// Rate limiter, access log, log filter, Brotli settings, compressible
+    /// types, static cache TTL and the streaming and background-time limits
+    /// are swapped atomically; requests already in flight keep the snapshot
+    /// they started with.
+    /// Settings that need new listeners or workers are logged and ignored.
+    pub fn reload(&self, config: &crate::config::Config) {
+        for key in self.restart_required(config) {
+            warn!("Config reload: {} changed, requires restart (ignored)", key);
+        }
+
+        let current = self.live_config.borrow().clone();
+        let new_rate_limit = config.middleware.rate_limit();
+        let rate_limiter = match (&current.rate_limiter, new_rate_limit) {
+            // Keep existing counters when parameters are unchanged
+            (Some(old), Some(rl))
+                if old.limit() == rl.limit()
+                    && old.window_secs() == rl.window_secs()
+                    && old.algorithm() == rl.algorithm() =>
+            {
+                Some(Arc::clone(old))
+            }
+            (_, Some(rl)) => Some(Arc::new(RateLimiter::with_algorithm(
+                rl.limit(),
+                rl.window_secs(),
+                rl.algorithm(),
+            ))),
+            (_, None) => None,
+        };
+
+        let access_log_enabled = config.middleware.is_access_log_enabled();
+        if access_log_enabled {
+            crate::logging::init_access_log_writer(&config.logging.access_log);
+        }
+        crate::logging::set_redact_keys(&config.logging.redact_keys);
+        crate::logging::apply_configured_filter(&config.logging.filter);
+
+        let access_log_min_status = config.logging.access_log.min_status;
+        let static_cache_ttl = config.server.static_cache_ttl;
+
+        info!(
+            "Config reloaded: rate_limit={}, access_log={}, access_log_min_status={}, static_cache_ttl={}",
+            new_rate_limit
+                .map(|rl| format!(
+                    "{}/{}s ({})",
+                    rl.limit(),
+                    rl.window_secs(),
+                    rl.algorithm().as_str()
+                ))
+                .unwrap_or_else(|| "off".to_string()),
+            access_log_enabled,
+            access_log_min_status,
+            format_optional_duration(&static_cache_ttl)
+        );
+
+        self.live_config.send_replace(Arc::new(ReloadableConfig {
+            rate_limiter,
+            access_log_enabled,
+            access_log_min_status,
+            static_cache_ttl,
+            response: response::ResponseSettings {
+                // Restart-only: it must match PHP's default_charset ini
+                default_content_type: current.response.default_content_type.clone(),
+                ..response::ResponseSettings::from_config(config)
+            },
+            output_limits: config.output_limits(),
+        }));
+    }
+
+    /// Settings in `config` that differ from the running server but only
+    /// take effect after a restart.
+    fn restart_required(&self, config: &crate::config::Config) -> Vec<&'static str> {
+        // Same rule as startup: certificate files are used only as a pair
+        let tls = &config.server.tls;
+        let tls_files = match (&tls.cert_path, &tls.key_path) {
+            (Some(cert), Some(key)) => (Some(cert.to_string_lossy()), Some(key.to_string_lossy())),
+            _ => (None, None),
+        };
+        // The access log writer keeps the sink it was started with
+        let access_log = &config.logging.access_log;
+        let sink = crate::logging::access_log_sink();
+        let sink_changed = |changed: fn(&AccessLogConfig, &AccessLogConfig) -> bool| {
+            sink.is_some_and(|running| changed(running, access_log))
+        };
+        [
+            ("LISTEN_ADDR", config.server.listen_addr != self.config.addr),
+            (
+                "PHP_WORKERS",
+                config.executor.worker_count() != self.config.num_workers,
+            ),
+            (
+                "QUEUE_CAPACITY",
+                config.executor.queue_capacity() != self.config.queue_capacity,
+            ),
+            (
+                "EXECUTOR",
+                config.executor.executor_type.name() != self.executor.name(),
+            ),
+            (
+                "DOCUMENT_ROOT",
+                config.server.document_root.to_str() != Some(&*self.config.document_root),
+            ),
+            (
+                "INTERNAL_ADDR",
+                config.server.internal_addr != self.config.internal_addr,
+            ),
+            (
+                "TLS_CERT",
+                tls_files.0.as_deref() != self.config.tls_cert.as_deref(),
+            ),
+            (
+                "TLS_KEY",
+                tls_files.1.as_deref() != self.config.tls_key.as_deref(),
+            ),
+            ("TLS_MODE", tls.auto != self.config.tls_auto),
+            ("ACCESS_LOG_FILE", sink_changed(|a, b| a.file != b.file)),
+            (
+                "ACCESS_LOG_FORMAT",
+                sink_changed(|a, b| a.format != b.format),
+            ),
+            (
+                "ACCESS_LOG_ROTATE",
+                sink_changed(|a, b| a.rotation != b.rotation),
+            ),
+            (
+                "ACCESS_LOG_MAX_FILES",
+                sink_changed(|a, b| a.max_files != b.max_files),
+            ),
+            (
+                "DEFAULT_CONTENT_TYPE/DEFAULT_CHARSET",
+                response::ResponseSettings::from_config(config).default_content_type
+                    != self.config.response.default_content_type,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(key, changed)| changed.then_some(key))
+        .collect()
+    }
+
     /// Get current active connections count.
     pub fn active_connections(&self) -> usize {
         self.active_connections.load(Ordering::Relaxed)
     }
 
+    /// Load the TLS config, also returning the leaf certificate.
     fn load_tls_config(
         config: &ServerConfig,
-    ) -> Result<RustlsConfig, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<(RustlsConfig, CertificateDer<'static>), Box<dyn std::error::Error + Send + Sync>>
+    {
+        if config.tls_auto {
+            let (cert, key) = AutoCertGenerator::new().generate()?;
+            return Self::build_tls_config(vec![cert], key);
+        }
+
         let cert_path = config.tls_cert.as_ref().ok_or("TLS cert path not set")?;
         let key_path = config.tls_key.as_ref().ok_or("TLS key path not set")?;
 
@@ -311,6 +577,17 @@ impl<E: ScriptExecutor + 'static> Server<E> {
         let key = rustls_pemfile::private_key(&mut key_reader)?
             .ok_or("No private key found in key file")?;
 
+        Self::build_tls_config(certs, key)
+    }
+
+    /// Build the TLS config from a certificate chain and key.
+    fn build_tls_config(
+        certs: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Result<(RustlsConfig, CertificateDer<'static>), Box<dyn std::error::Error + Send + Sync>>
+    {
+        let leaf = certs[0].clone();
+
         // Build TLS config with ALPN for HTTP/2
         let mut tls_config = RustlsConfig::builder()
             .with_no_client_auth()
@@ -319,7 +596,7 @@ impl<E: ScriptExecutor + 'static> Server<E> {
         // Enable ALPN for HTTP/2 and HTTP/1.1
         tls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
 
-        Ok(tls_config)
+        Ok((tls_config, leaf))
     }
 
     /// Creates a socket with SO_REUSEPORT for multi-threaded accept.
@@ -366,6 +643,49 @@ impl<E: ScriptExecutor + 'static> Server<E> {
             num_workers
         );
 
+        // The executor has passed SAPI init by now; wait for its worker threads
+        let executor = Arc::clone(&self.executor);
+        let lifecycle = Arc::clone(&self.lifecycle);
+        tokio::spawn(async move {
+            while !executor.workers_started() {
+                tokio::time::sleep(STARTUP_POLL_INTERVAL).await;
+            }
+            lifecycle.set_running();
+            info!("Startup complete: all PHP workers started");
+        });
+
+        if self.memory_pressure.is_enabled() {
+            let memory_pressure = Arc::clone(&self.memory_pressure);
+            let mut shutdown_rx = self.shutdown_rx.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(MEMORY_SAMPLE_INTERVAL);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {
+                            if let Some(usage) = memory_pressure::read_usage_percent() {
+                                memory_pressure.update(usage);
+                            }
+                        }
+                        _ = shutdown_rx.changed() => break,
+                    }
+                }
+            });
+        }
+
+        // Written by workers for profiled requests, read by /profile/last
+        let last_profile = Arc::new(LastProfile::default());
+        let php_workers = self
+            .config
+            .sizing
+            .as_ref()
+            .map_or(num_workers, |s| s.workers.get());
+        let middleware = Arc::new(self.middleware.clone());
+        // Percentiles across profiled requests, read by /profile/stats
+        let profile_stats = self
+            .config
+            .profile_stats
+            .then(|| Arc::new(ProfileStats::new()));
+
         // Spawn accept loops on multiple threads
         let mut handles = Vec::with_capacity(num_workers + 1);
 
@@ -373,37 +693,41 @@ impl<E: ScriptExecutor + 'static> Server<E> {
         if let Some(internal_addr) = self.config.internal_addr {
             let active_connections = Arc::clone(&self.active_connections);
             let request_metrics = Arc::clone(&self.request_metrics);
+            let connection_metrics = Arc::clone(&self.connection_metrics);
+            let kill_switch = Arc::clone(&self.kill_switch);
             let mut shutdown_rx = self.shutdown_rx.clone();
 
+            // /opcache runs opcache_get_status() on a PHP worker
+            let executor = Arc::clone(&self.executor);
+            let opcache_status = Arc::new(OpcacheStatus::new(move || {
+                let executor = Arc::clone(&executor);
+                async move { executor.opcache_status().await.map_err(|e| e.to_string()) }
+            }));
+
             // Build config info for /config endpoint (env var names as keys)
             let executor_name = self.executor.name();
-            let config_info = Arc::new(ServerConfigInfo {
+            let mut config_info = ServerConfigInfo {
                 listen_addr: self.config.addr.to_string(),
                 document_root: self.config.document_root.to_string(),
                 php_workers: num_workers.to_string(),
-                queue_capacity: (num_workers * 100).to_string(),
+                queue_capacity: self
+                    .config
+                    .sizing
+                    .as_ref()
+                    .map_or(num_workers * crate::config::DEFAULT_QUEUE_MULTIPLIER, |s| {
+                        s.queue_capacity.get()
+                    })
+                    .to_string(),
                 index_file: self.config.index_file.clone().unwrap_or_default(),
                 internal_addr: internal_addr.to_string(),
                 error_pages_dir: self.config.error_pages_dir.clone().unwrap_or_default(),
                 drain_timeout_secs: self.config.drain_timeout.as_secs().to_string(),
-                static_cache_ttl: format_optional_duration(&self.config.static_cache_ttl),
+                static_cache_ttl: String::new(),
                 request_timeout: format_optional_duration(&self.config.request_timeout),
                 sse_timeout: format_optional_duration(&self.config.sse_timeout),
-                access_log: if self.access_log_enabled {
-                    "1".to_string()
-                } else {
-                    "0".to_string()
-                },
-                rate_limit: self
-                    .rate_limiter
-                    .as_ref()
-                    .map(|r| r.limit().to_string())
-                    .unwrap_or_else(|| "0".to_string()),
-                rate_window: self
-                    .rate_limiter
-                    .as_ref()
-                    .map(|r| r.window_secs().to_string())
-                    .unwrap_or_else(|| "60".to_string()),
+                access_log: String::new(),
+                rate_limit: String::new(),
+                rate_window: String::new(),
                 executor: executor_name.to_string(),
                 profile: if self.profile_enabled {
                     "1".to_string()
@@ -412,14 +736,33 @@ impl<E: ScriptExecutor + 'static> Server<E> {
                 },
                 tls_cert: self.config.tls_cert.clone().unwrap_or_default(),
                 tls_key: self.config.tls_key.clone().unwrap_or_default(),
-                log_level: std::env::var("LOG_LEVEL").unwrap_or_else(|_| "info".to_string()),
-                service_name: std::env::var("SERVICE_NAME")
-                    .unwrap_or_else(|_| "tokio_php".to_string()),
-            });
+                log_level: String::new(),
+                service_name: crate::config::env_var("SERVICE_NAME")
+                    .unwrap_or_else(|| "tokio_php".to_string()),
+                sizing: self.config.sizing.clone(),
+                readiness: Arc::new(Readiness::new(
+                    self.config.queue_capacity,
+                    self.config.ready_queue_threshold,
+                    self.config.ready_saturation_window,
+                )),
+                lifecycle: Arc::clone(&self.lifecycle),
+                memory_pressure: Arc::clone(&self.memory_pressure),
+                diagnostics: Arc::new(Diagnostics::new()),
+                worker_activity: {
+                    let executor = Arc::clone(&self.executor);
+                    Arc::new(move || executor.worker_activity())
+                },
+                last_profile: Arc::clone(&last_profile),
+                profile_stats: profile_stats.clone(),
+                access: self.config.internal_access.clone(),
+                live_config: self.live_config.subscribe(),
+            };
+            config_info.apply_live(&self.live_config.borrow());
+            let config_info = Arc::new(config_info);
 
             let handle = tokio::spawn(async move {
                 tokio::select! {
-                    result = run_internal_server(internal_addr, active_connections, request_metrics, config_info) => {
+                    result = run_internal_server(internal_addr, active_connections, request_metrics, connection_metrics, kill_switch, config_info, opcache_status) => {
                         if let Err(e) = result {
                             error!("Internal server error: {}", e);
                         }
@@ -433,11 +776,35 @@ impl<E: ScriptExecutor + 'static> Server<E> {
             info!("Internal server listening on http://{}", internal_addr);
         }
 
+        // Shared by all workers
+        let mime_types = Arc::new(self.config.mime_types.clone());
+        let compressed_cache = Arc::new(response::CompressedCache::new(
+            self.config.static_compress_cache_size,
+        ));
+        let immutable_assets = Arc::new(self.config.immutable_assets.clone());
+        let ua_variants = Arc::new(self.config.ua_variants.clone());
+        let ip_limit = Arc::new(IpConnectionLimit::new(self.config.max_connections_per_ip));
+        let global_limit = Arc::new(GlobalConnectionLimit::new(self.config.max_connections));
+        let canonical_links = Arc::new(self.config.canonical_links.clone());
+        let route_labels = Arc::new(self.config.route_labels.clone());
+        let allowed_methods = Arc::new(self.config.allowed_methods.clone());
+        let priority_paths: Arc<[String]> = self.config.priority_paths.clone().into();
+        let proxy = (!self.config.proxy_routes.is_empty())
+            .then(|| Arc::new(ReverseProxy::new(self.config.proxy_pool_size)));
+        let upload_types = Arc::new(self.config.upload_types.clone());
+        let server_header = self
+            .config
+            .server_header
+            .as_deref()
+            .and_then(|value| http::HeaderValue::from_str(value).ok());
+
         for worker_id in 0..num_workers {
             let addr = self.config.addr;
             let tls_acceptor = self.tls_acceptor.clone();
             let mut shutdown_rx = self.shutdown_rx.clone();
             let conn_shutdown_rx = self.shutdown_rx.clone();
+            let limit = ConnectionLimit::new(self.config.max_connections_per_worker);
+            let global_limit = Arc::clone(&global_limit);
 
             // Create connection context for this worker
             let ctx = Arc::new(ConnectionContext {
@@ -445,19 +812,57 @@ impl<E: ScriptExecutor + 'static> Server<E> {
                 document_root: Arc::clone(&self.config.document_root),
                 document_root_static: self.document_root_static.clone(),
                 is_stub_mode: self.executor.skip_file_check(),
+                empty_responses: self.executor.empty_responses(),
                 route_config: Arc::clone(&self.route_config),
                 active_connections: Arc::clone(&self.active_connections),
                 request_metrics: Arc::clone(&self.request_metrics),
+                connection_metrics: Arc::clone(&self.connection_metrics),
                 error_pages: self.error_pages.clone(),
-                rate_limiter: self.rate_limiter.clone(),
-                static_cache_ttl: self.config.static_cache_ttl,
+                live_config: self.live_config.subscribe(),
                 request_timeout: self.config.request_timeout,
+                max_queue_wait: self.config.effective_max_queue_wait(),
+                php_workers,
                 sse_timeout: self.config.sse_timeout,
                 header_timeout: self.config.header_timeout,
                 idle_timeout: self.config.idle_timeout,
+                keepalive_timeout: self.config.keepalive_timeout,
+                tls_handshake_timeout: self.config.tls_handshake_timeout,
+                slow_request_threshold: self.config.slow_request_threshold,
+                http2: self.config.http2,
                 profile_enabled: self.profile_enabled,
-                access_log_enabled: self.access_log_enabled,
                 file_cache: Arc::clone(&self.file_cache),
+                kill_switch: Arc::clone(&self.kill_switch),
+                max_input_vars: self.config.max_input_vars,
+                body_prealloc_max: self.config.body_prealloc_max,
+                raw_body_max: self.config.raw_body_max,
+                max_body_size: self.config.max_body_size,
+                upload_types: Arc::clone(&upload_types),
+                trace_b3_egress: self.config.trace_b3_egress,
+                expose_errors: self.config.expose_errors,
+                server_header: server_header.clone(),
+                cert_hosts: self.cert_hosts.clone(),
+                lifecycle: Arc::clone(&self.lifecycle),
+                memory_pressure: Arc::clone(&self.memory_pressure),
+                goaway_window: self.config.goaway_window,
+                trusted_proxies: self.config.trusted_proxies.clone(),
+                timing_allow_origin: self.config.timing_allow_origin.clone().and_then(|tao| {
+                    let value = http::HeaderValue::from_str(&tao.value).ok()?;
+                    Some((tao, value))
+                }),
+                csp_nonce: self.config.csp_nonce.clone(),
+                mime_types: Arc::clone(&mime_types),
+                compressed_cache: Arc::clone(&compressed_cache),
+                immutable_assets: Arc::clone(&immutable_assets),
+                ua_variants: Arc::clone(&ua_variants),
+                ip_limit: Arc::clone(&ip_limit),
+                canonical_links: Arc::clone(&canonical_links),
+                route_labels: Arc::clone(&route_labels),
+                proxy: proxy.clone(),
+                allowed_methods: Arc::clone(&allowed_methods),
+                priority_paths: Arc::clone(&priority_paths),
+                last_profile: Arc::clone(&last_profile),
+                profile_stats: profile_stats.clone(),
+                middleware: Arc::clone(&middleware),
             });
 
             let handle = tokio::spawn(async move {
@@ -482,8 +887,9 @@ impl<E: ScriptExecutor + 'static> Server<E> {
 
                 loop {
                     tokio::select! {
-                        result = listener.accept() => {
-                            let (stream, remote_addr) = match result {
+                        // At the per-worker cap this waits for a slot instead of accepting
+                        result = limit.accept(&listener) => {
+                            let (stream, remote_addr, permit) = match result {
                                 Ok(conn) => conn,
                                 Err(e) => {
                                     error!("Worker {}: Accept error: {}", worker_id, e);
@@ -491,6 +897,16 @@ impl<E: ScriptExecutor + 'static> Server<E> {
                                 }
                             };
 
+                            // Server-wide cap: close right away instead of queueing
+                            let global_permit = match global_limit.try_acquire() {
+                                Ok(permit) => permit,
+                                Err(LimitReached) => {
+                                    ctx.connection_metrics.record_accept();
+                                    ctx.connection_metrics.record_max_connections_reject();
+                                    continue;
+                                }
+                            };
+
                             let _ = stream.set_nodelay(true);
 
                             // Set TCP keepalive
@@ -508,6 +924,9 @@ impl<E: ScriptExecutor + 'static> Server<E> {
 
                             tokio::spawn(async move {
                                 ctx.handle_connection_graceful(stream, remote_addr, tls, conn_shutdown).await;
+                                // Free the worker's and the server-wide slot once the connection closes
+                                drop(permit);
+                                drop(global_permit);
                             });
                         }
                         _ = shutdown_rx.changed() => {
@@ -535,6 +954,10 @@ impl<E: ScriptExecutor + 'static> Server<E> {
         if self.shutdown_initiated.swap(true, Ordering::SeqCst) {
             return; // Already initiated
         }
+        self.lifecycle.set_draining();
+        // Long-running scripts can poll tokio_is_draining() and wrap up
+        #[cfg(feature = "php")]
+        crate::bridge::set_draining(true);
         let _ = self.shutdown_tx.send(true);
     }
 
@@ -587,3 +1010,538 @@ fn format_optional_duration(d: &config::OptionalDuration) -> String {
         format!("{}s", secs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Context, Request, Response};
+    use crate::executor::{ExecutorError, StubExecutor};
+    use crate::middleware::request_id::is_valid_request_id;
+    use crate::middleware::MiddlewareResult;
+    use crate::types::{ScriptRequest, ScriptResponse};
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Executor that answers every script with one `$_SERVER` value.
+    struct EchoServerVar(&'static str);
+
+    #[async_trait::async_trait]
+    impl ScriptExecutor for EchoServerVar {
+        async fn execute(&self, request: ScriptRequest) -> Result<ScriptResponse, ExecutorError> {
+            let body = request
+                .server_vars
+                .iter()
+                .find(|(k, _)| k == self.0)
+                .map(|(_, v)| v.to_string())
+                .unwrap_or_default();
+            Ok(ScriptResponse {
+                body,
+                ..Default::default()
+            })
+        }
+
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn skip_file_check(&self) -> bool {
+            true
+        }
+    }
+
+    /// Loopback address with a port that was free a moment ago.
+    fn free_addr() -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    /// Run `server` in the background until it accepts PHP requests.
+    async fn start<E: ScriptExecutor + 'static>(server: Server<E>) -> Arc<Server<E>> {
+        let server = Arc::new(server);
+        let running = Arc::clone(&server);
+        tokio::spawn(async move { running.run().await });
+        for _ in 0..200 {
+            if server.lifecycle.is_running()
+                && tokio::net::TcpStream::connect(server.config.addr)
+                    .await
+                    .is_ok()
+            {
+                return server;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("server did not start");
+    }
+
+    /// Send one HTTP/1.1 request and return the status, head and body.
+    async fn http_request(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> (u16, String, String) {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut request =
+            format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n");
+        for (name, value) in headers {
+            request.push_str(&format!("{name}: {value}\r\n"));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw).await.unwrap();
+        let raw = String::from_utf8_lossy(&raw).into_owned();
+        let (head, body) = raw.split_once("\r\n\r\n").unwrap_or((&raw, ""));
+        let status = head[9..12].parse().unwrap();
+        (status, head.to_string(), body.to_string())
+    }
+
+    /// Value of a response header in a raw HTTP/1.1 head.
+    fn header_value<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+        head.lines().skip(1).find_map(|line| {
+            let (n, v) = line.split_once(':')?;
+            n.eq_ignore_ascii_case(name).then(|| v.trim())
+        })
+    }
+
+    #[tokio::test]
+    async fn test_invalid_request_id_replaced_for_php_and_response() {
+        let addr = free_addr();
+        let config = ServerConfig::new(addr).with_workers(1);
+        let server = start(Server::new(config, EchoServerVar("HTTP_X_REQUEST_ID")).unwrap()).await;
+
+        let (status, head, body) =
+            http_request(addr, "GET", "/index.php", &[("X-Request-Id", "forged id")]).await;
+        assert_eq!(status, 200);
+        let echoed = header_value(&head, "x-request-id").unwrap();
+        assert_ne!(echoed, "forged id");
+        assert!(is_valid_request_id(echoed), "{}", echoed);
+        assert_eq!(body, echoed, "PHP sees the replacement id");
+
+        let (_, head, body) =
+            http_request(addr, "GET", "/index.php", &[("X-Request-Id", "client-42")]).await;
+        assert_eq!(header_value(&head, "x-request-id"), Some("client-42"));
+        assert_eq!(body, "client-42");
+
+        let (_, head, body) = http_request(addr, "GET", "/index.php", &[]).await;
+        let generated = header_value(&head, "x-request-id").unwrap();
+        assert!(is_valid_request_id(generated));
+        assert_eq!(body, generated);
+
+        server.trigger_shutdown();
+    }
+
+    /// Answers with the queue lane the request was given.
+    struct EchoPriority;
+
+    #[async_trait::async_trait]
+    impl ScriptExecutor for EchoPriority {
+        async fn execute(&self, request: ScriptRequest) -> Result<ScriptResponse, ExecutorError> {
+            Ok(ScriptResponse {
+                body: format!("{:?}", request.priority),
+                ..Default::default()
+            })
+        }
+
+        fn name(&self) -> &'static str {
+            "echo-priority"
+        }
+
+        fn skip_file_check(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_priority_paths_match_whole_segments() {
+        let addr = free_addr();
+        let config = ServerConfig::new(addr)
+            .with_workers(1)
+            .with_priority_paths(vec!["/api".to_string()]);
+        let server = start(Server::new(config, EchoPriority).unwrap()).await;
+
+        for (path, expected) in [
+            ("/api", "High"),
+            ("/api/index.php", "High"),
+            ("/%61pi/index.php", "High"),
+            ("/apix/index.php", "Normal"),
+            ("/index.php", "Normal"),
+        ] {
+            let (status, _, body) = http_request(addr, "GET", path, &[]).await;
+            assert_eq!(status, 200, "{}", path);
+            assert_eq!(body, expected, "{}", path);
+        }
+
+        server.trigger_shutdown();
+    }
+
+    /// Executor that forwards the raw body (like FastCGI) and answers with
+    /// what it was given.
+    struct RawBodyExecutor;
+
+    #[async_trait::async_trait]
+    impl ScriptExecutor for RawBodyExecutor {
+        async fn execute(&self, request: ScriptRequest) -> Result<ScriptResponse, ExecutorError> {
+            Ok(ScriptResponse {
+                body: format!(
+                    "post={} files={} raw={}",
+                    request.post_params.len(),
+                    request.files.len(),
+                    String::from_utf8_lossy(request.raw_body.as_deref().unwrap_or_default())
+                ),
+                ..Default::default()
+            })
+        }
+
+        fn name(&self) -> &'static str {
+            "raw-body"
+        }
+
+        fn skip_file_check(&self) -> bool {
+            true
+        }
+
+        fn forwards_raw_body(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raw_body_executor_gets_multipart_unparsed() {
+        let addr = free_addr();
+        let config = ServerConfig::new(addr).with_workers(1).with_raw_body_max(4);
+        let server = start(Server::new(config, RawBodyExecutor).unwrap()).await;
+
+        let body = "--b\r\nContent-Disposition: form-data; name=\"f\"; filename=\"a.txt\"\r\n\r\nhi\r\n--b--\r\n";
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "POST /upload.php HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Type: multipart/form-data; boundary=b\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw).await.unwrap();
+        let raw = String::from_utf8_lossy(&raw);
+        let (_, response) = raw.split_once("\r\n\r\n").unwrap();
+        assert_eq!(response, format!("post=0 files=0 raw={}", body));
+
+        server.trigger_shutdown();
+    }
+
+    /// Upstream that answers every request with its request line and
+    /// `X-Forwarded-For`, and counts them.
+    async fn echo_upstream() -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&hits);
+        tokio::spawn(async move {
+            loop {
+                let (mut conn, _) = listener.accept().await.unwrap();
+                let counter = Arc::clone(&counter);
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let mut head = String::new();
+                    while !head.contains("\r\n\r\n") {
+                        let n = conn.read(&mut buf).await.unwrap();
+                        if n == 0 {
+                            return;
+                        }
+                        head.push_str(&String::from_utf8_lossy(&buf[..n]));
+                    }
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let body = format!(
+                        "{} xff={}",
+                        head.lines().next().unwrap(),
+                        header_value(&head, "x-forwarded-for").unwrap_or("-")
+                    );
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    conn.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+        (addr, hits)
+    }
+
+    #[tokio::test]
+    async fn test_proxy_routes_forward_to_upstream() {
+        let (upstream, hits) = echo_upstream().await;
+        let addr = free_addr();
+        let routes = ProxyRoutes::parse(&[format!("/api=http://{upstream}")]).unwrap();
+        let config = ServerConfig::new(addr)
+            .with_workers(1)
+            .with_proxy_routes(routes, 4)
+            .with_allowed_methods(AllowedMethods::parse(&["GET", "POST"]).unwrap());
+        let server = start(Server::new(config, EchoServerVar("REQUEST_URI")).unwrap()).await;
+
+        let (status, _, body) = http_request(addr, "GET", "/api/users?page=2", &[]).await;
+        assert_eq!(status, 200);
+        assert!(body.contains("GET /api/users?page=2 HTTP/1.1"), "{}", body);
+        assert!(body.contains("xff=127.0.0.1"), "{}", body);
+
+        // Prefixes match the normalized path, so encoding can't route around them
+        for path in ["/%61pi/users", "//api/users", "/static/../api/users"] {
+            let (status, _, body) = http_request(addr, "GET", path, &[]).await;
+            assert_eq!(status, 200, "{}", path);
+            assert!(body.contains(&format!("GET {path} HTTP/1.1")), "{}", body);
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+
+        // Methods outside ALLOWED_METHODS never reach the upstream
+        let (status, head, _) = http_request(addr, "DELETE", "/api/users", &[]).await;
+        assert_eq!(status, 405);
+        assert_eq!(
+            header_value(&head, "allow"),
+            Some("GET, HEAD, POST, OPTIONS")
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+
+        server.trigger_shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_proxy_routes_apply_body_limits() {
+        let (upstream, hits) = echo_upstream().await;
+        let addr = free_addr();
+        let routes = ProxyRoutes::parse(&[format!("/api=http://{upstream}")]).unwrap();
+        let config = ServerConfig::new(addr)
+            .with_workers(1)
+            .with_proxy_routes(routes, 4)
+            .with_max_body_size(16);
+        let server = start(Server::new(config, EchoServerVar("REQUEST_URI")).unwrap()).await;
+
+        // Refused from the headers alone, before anything is forwarded
+        let (status, _, _) =
+            http_request(addr, "POST", "/api/upload", &[("Content-Length", "100")]).await;
+        assert_eq!(status, 413);
+        let (status, _, _) = http_request(addr, "POST", "/api/upload", &[("Expect", "x-y")]).await;
+        assert_eq!(status, 417);
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        server.trigger_shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_options_answered_unless_listed() {
+        let addr = free_addr();
+        let config = ServerConfig::new(addr).with_workers(1);
+        let server = start(Server::new(config, EchoServerVar("REQUEST_METHOD")).unwrap()).await;
+
+        let (status, head, body) = http_request(addr, "OPTIONS", "/index.php", &[]).await;
+        assert_eq!(status, 204);
+        assert_eq!(
+            header_value(&head, "allow"),
+            Some("GET, HEAD, POST, PUT, PATCH, DELETE, QUERY, OPTIONS")
+        );
+        assert!(body.is_empty());
+        server.trigger_shutdown();
+
+        // Opted in: PHP answers preflight requests itself
+        let addr = free_addr();
+        let methods = AllowedMethods::parse(&["GET", "POST", "OPTIONS"]).unwrap();
+        let config = ServerConfig::new(addr)
+            .with_workers(1)
+            .with_allowed_methods(methods);
+        let server = start(Server::new(config, EchoServerVar("REQUEST_METHOD")).unwrap()).await;
+
+        let (status, _, body) = http_request(addr, "OPTIONS", "/index.php", &[]).await;
+        assert_eq!(status, 200);
+        assert_eq!(body, "OPTIONS");
+
+        // The whole-server form never reaches PHP
+        let (status, _, _) = http_request(addr, "OPTIONS", "*", &[]).await;
+        assert_eq!(status, 204);
+        server.trigger_shutdown();
+    }
+
+    struct Recorder {
+        name: &'static str,
+        priority: i32,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Recorder {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+
+        fn on_request(&self, req: Request, _ctx: &mut Context) -> MiddlewareResult {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} request", self.name));
+            MiddlewareResult::Next(req)
+        }
+
+        fn on_response(&self, res: Response, _ctx: &Context) -> Response {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} response", self.name));
+            res
+        }
+    }
+
+    #[test]
+    fn test_reload_swaps_live_settings_and_flags_restart_only_ones() {
+        // reload() re-applies the configured log filter
+        let _guard = crate::logging::test_filter_guard();
+        let mut new_config = crate::config::Config::defaults();
+        new_config.server.listen_addr = "127.0.0.1:0".parse().unwrap();
+        new_config.executor.executor_type = crate::config::ExecutorType::Stub;
+
+        let config = ServerConfig::new(new_config.server.listen_addr)
+            .with_workers(new_config.executor.worker_count())
+            .with_readiness(7, 90, std::time::Duration::from_secs(10));
+        let server = Server::new(config, StubExecutor::new()).unwrap();
+        let live = server.live_config.subscribe();
+        assert_eq!(server.restart_required(&new_config), ["QUEUE_CAPACITY"]);
+
+        new_config.server.static_cache_ttl = crate::config::StaticCacheTtl::from_secs(42);
+        new_config.logging.access_log.min_status = 500;
+        new_config.server.brotli.quality = 9;
+        new_config.executor.stream_buffer_size = 7;
+        new_config.executor.executor_type = crate::config::ExecutorType::Php;
+        new_config.server.tls.cert_path = Some("/etc/tls/cert.pem".into());
+        new_config.server.tls.key_path = Some("/etc/tls/key.pem".into());
+
+        assert_eq!(
+            server.restart_required(&new_config),
+            ["QUEUE_CAPACITY", "EXECUTOR", "TLS_CERT", "TLS_KEY"]
+        );
+
+        server.reload(&new_config);
+        let snapshot = live.borrow().clone();
+        assert_eq!(snapshot.static_cache_ttl.as_secs(), 42);
+        assert_eq!(snapshot.access_log_min_status, 500);
+        assert_eq!(snapshot.response.brotli.quality, 9);
+        assert_eq!(snapshot.output_limits.stream_buffer_size, 7);
+    }
+
+    #[test]
+    fn test_with_middleware_runs_in_priority_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let config = ServerConfig::new("127.0.0.1:0".parse().unwrap());
+        let server = Server::new(config, StubExecutor::new())
+            .unwrap()
+            .with_middleware(Recorder {
+                name: "auth",
+                priority: 10,
+                log: Arc::clone(&log),
+            })
+            .with_middleware(Recorder {
+                name: "tracing",
+                priority: -10,
+                log: Arc::clone(&log),
+            });
+        assert_eq!(server.middleware.names(), ["tracing", "auth"]);
+
+        let req = Request::new(
+            hyper::Method::GET,
+            hyper::Uri::from_static("/"),
+            hyper::HeaderMap::new(),
+            bytes::Bytes::new(),
+        );
+        let mut ctx = Context::new([127, 0, 0, 1].into(), "0".repeat(32), "0".repeat(16));
+        server
+            .middleware
+            .process(req, &mut ctx, |_, _| Response::ok("ok"));
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "tracing request",
+                "auth request",
+                "auth response",
+                "tracing response"
+            ]
+        );
+    }
+
+    /// Records the trace id it sees and answers 403 when `X-Deny` is set.
+    struct Gate {
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Gate {
+        fn name(&self) -> &'static str {
+            "gate"
+        }
+
+        fn on_request(&self, req: Request, ctx: &mut Context) -> MiddlewareResult {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("gate request {}", ctx.trace_id));
+            if req.header("x-deny").is_some() {
+                return MiddlewareResult::Stop(Response::empty(http::StatusCode::FORBIDDEN));
+            }
+            MiddlewareResult::Next(req)
+        }
+
+        fn on_response(&self, res: Response, _ctx: &Context) -> Response {
+            self.log.lock().unwrap().push("gate response".to_string());
+            res
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_wired_into_connections() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let recorder = |name, priority| Recorder {
+            name,
+            priority,
+            log: Arc::clone(&log),
+        };
+        let addr = free_addr();
+        let config = ServerConfig::new(addr).with_workers(1);
+        let server = Server::new(config, EchoServerVar("REQUEST_URI"))
+            .unwrap()
+            .with_middleware(recorder("outer", -10))
+            .with_middleware(Gate {
+                log: Arc::clone(&log),
+            })
+            .with_middleware(recorder("inner", 10));
+        let server = start(server).await;
+
+        let (status, head, body) = http_request(addr, "GET", "/index.php", &[]).await;
+        assert_eq!(status, 200);
+        assert_eq!(body, "/index.php");
+        // Middleware and the response share one trace
+        let traceparent = header_value(&head, "traceparent").unwrap();
+        let trace_id = traceparent.split('-').nth(1).unwrap();
+        assert_eq!(
+            std::mem::take(&mut *log.lock().unwrap()),
+            [
+                "outer request".to_string(),
+                format!("gate request {trace_id}"),
+                "inner request".to_string(),
+                "inner response".to_string(),
+                "gate response".to_string(),
+                "outer response".to_string(),
+            ]
+        );
+
+        // Short-circuit: middleware after the gate see neither side
+        let (status, _, _) = http_request(addr, "GET", "/index.php", &[("X-Deny", "1")]).await;
+        assert_eq!(status, 403);
+        let log = std::mem::take(&mut *log.lock().unwrap());
+        assert_eq!(log.len(), 4, "{:?}", log);
+        assert_eq!(log[0], "outer request");
+        assert!(log[1].starts_with("gate request "));
+        assert_eq!(log[2..], ["gate response", "outer response"]);
+
+        server.trigger_shutdown();
+    }
+}
@@ -0,0 +1,271 @@
+//! Reverse proxy for `PROXY_ROUTES`.
+//!
+//! Requests under a proxied prefix skip PHP and static routing and go to an
+//! HTTP/1.1 upstream (Node, Python, ...) with bodies streamed both ways, so
+//! an application can move off PHP one route at a time.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Body, Incoming as IncomingBody};
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Request, Response, StatusCode, Version};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+
+use super::response::{
+    full_to_flexible, stream_channel, streaming_response, streaming_to_flexible, FlexibleResponse,
+    StreamChunk, EMPTY_BODY,
+};
+use super::routing::ProxyRoute;
+
+/// Upstream connect timeout; a down upstream answers 502 quickly.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Idle pooled connections are closed after this long.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+/// Response body chunks buffered between upstream and client.
+const BODY_BUFFER_CHUNKS: usize = 16;
+
+static X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+static X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// Connection-scoped headers that are never forwarded (RFC 9110, 7.6.1).
+static HOP_BY_HOP: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    HeaderName::from_static("proxy-connection"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+/// The client as seen by this server, for `X-Forwarded-*` headers.
+#[derive(Debug, Clone, Copy)]
+pub struct ForwardedFor {
+    /// Direct peer address.
+    pub peer: IpAddr,
+    /// Connection is TLS.
+    pub https: bool,
+    /// Peer is in TRUSTED_PROXIES: extend its X-Forwarded-* instead of replacing.
+    pub trusted: bool,
+}
+
+/// Pooled HTTP/1.1 client shared by all proxy routes.
+pub struct ReverseProxy {
+    client: Client<HttpConnector, IncomingBody>,
+}
+
+impl ReverseProxy {
+    /// Client keeping up to `pool_size` idle connections per upstream.
+    pub fn new(pool_size: usize) -> Self {
+        let mut connector = HttpConnector::new();
+        connector.set_nodelay(true);
+        connector.set_connect_timeout(Some(CONNECT_TIMEOUT));
+
+        let client = Client::builder(TokioExecutor::new())
+            .pool_max_idle_per_host(pool_size)
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+            .build(connector);
+        Self { client }
+    }
+
+    /// Forward a request and stream the upstream response back.
+    ///
+    /// Connection failures answer 502, and no response head within
+    /// `timeout` answers 504 (both with an empty body, so error pages apply).
+    pub async fn forward(
+        &self,
+        mut req: Request<IncomingBody>,
+        route: &ProxyRoute,
+        client: ForwardedFor,
+        timeout: Option<Duration>,
+    ) -> FlexibleResponse {
+        let path_and_query = req
+            .uri()
+            .path_and_query()
+            .map_or("/", |pq| pq.as_str())
+            .to_string();
+        let upstream_uri = match route.upstream_uri(&path_and_query) {
+            Ok(uri) => uri,
+            Err(e) => {
+                tracing::warn!("Proxy URI for {}: {}", path_and_query, e);
+                return status_response(StatusCode::BAD_GATEWAY);
+            }
+        };
+
+        // HTTP/2 requests carry the host in :authority only
+        if !req.headers().contains_key(header::HOST) {
+            if let Some(host) = req
+                .uri()
+                .authority()
+                .and_then(|a| HeaderValue::from_str(a.as_str()).ok())
+            {
+                req.headers_mut().insert(header::HOST, host);
+            }
+        }
+        prepare_request_headers(req.headers_mut(), client);
+        *req.uri_mut() = upstream_uri;
+        *req.version_mut() = Version::HTTP_11;
+
+        let pending = self.client.request(req);
+        let result = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, pending).await {
+                Ok(result) => result,
+                Err(_) => {
+                    tracing::warn!("Proxy to {} timed out", route.authority());
+                    return status_response(StatusCode::GATEWAY_TIMEOUT);
+                }
+            },
+            None => pending.await,
+        };
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::warn!("Proxy to {} failed: {}", route.authority(), e);
+                return status_response(StatusCode::BAD_GATEWAY);
+            }
+        };
+
+        let (mut parts, body) = response.into_parts();
+        remove_hop_by_hop(&mut parts.headers);
+
+        // HEAD, 204, 304: nothing to stream, keep upstream Content-Length
+        if body.is_end_stream() {
+            return full_to_flexible(Response::from_parts(parts, Full::new(EMPTY_BODY.clone())));
+        }
+
+        let (tx, rx) = stream_channel(BODY_BUFFER_CHUNKS);
+        tokio::spawn(async move {
+            let mut body = body;
+            while let Some(frame) = body.frame().await {
+                let data = match frame {
+                    Ok(frame) => match frame.into_data() {
+                        Ok(data) => data,
+                        Err(_) => continue, // Trailers
+                    },
+                    Err(e) => {
+                        tracing::debug!("Proxy response body: {}", e);
+                        break;
+                    }
+                };
+                // Empty chunks would go out as SSE keepalive comments
+                if !data.is_empty() && tx.send(StreamChunk::new(data)).await.is_err() {
+                    break; // Client went away
+                }
+            }
+        });
+
+        let mut response = streaming_response(parts.status.as_u16(), Vec::new(), rx);
+        *response.headers_mut() = parts.headers;
+        streaming_to_flexible(response)
+    }
+}
+
+/// Strip hop-by-hop headers and set `X-Forwarded-For` / `X-Forwarded-Proto`.
+fn prepare_request_headers(headers: &mut HeaderMap, client: ForwardedFor) {
+    remove_hop_by_hop(headers);
+
+    let peer = client.peer.to_string();
+    let forwarded_for = match headers.get(&X_FORWARDED_FOR).and_then(|v| v.to_str().ok()) {
+        Some(chain) if client.trusted => format!("{chain}, {peer}"),
+        _ => peer,
+    };
+    if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+        headers.insert(X_FORWARDED_FOR.clone(), value);
+    }
+
+    if !(client.trusted && headers.contains_key(&X_FORWARDED_PROTO)) {
+        let proto = if client.https { "https" } else { "http" };
+        headers.insert(X_FORWARDED_PROTO.clone(), HeaderValue::from_static(proto));
+    }
+}
+
+/// Remove hop-by-hop headers, including any named in `Connection`.
+fn remove_hop_by_hop(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    for name in HOP_BY_HOP.iter().chain(&listed) {
+        headers.remove(name);
+    }
+}
+
+fn status_response(status: StatusCode) -> FlexibleResponse {
+    full_to_flexible(
+        Response::builder()
+            .status(status)
+            .body(Full::new(Bytes::new()))
+            .unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn client(trusted: bool) -> ForwardedFor {
+        ForwardedFor {
+            peer: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7)),
+            https: true,
+            trusted,
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(k, v)| (HeaderName::from_static(k), HeaderValue::from_static(v)))
+            .collect()
+    }
+
+    #[test]
+    fn test_hop_by_hop_removed() {
+        let mut map = headers(&[
+            ("connection", "keep-alive, x-internal"),
+            ("keep-alive", "timeout=5"),
+            ("upgrade", "websocket"),
+            ("x-internal", "secret"),
+            ("accept", "*/*"),
+        ]);
+        remove_hop_by_hop(&mut map);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get("accept").unwrap(), "*/*");
+    }
+
+    #[test]
+    fn test_forwarded_headers_untrusted_peer() {
+        let mut map = headers(&[
+            ("x-forwarded-for", "1.2.3.4"),
+            ("x-forwarded-proto", "http"),
+        ]);
+        prepare_request_headers(&mut map, client(false));
+        assert_eq!(map.get("x-forwarded-for").unwrap(), "10.0.0.7");
+        assert_eq!(map.get("x-forwarded-proto").unwrap(), "https");
+    }
+
+    #[test]
+    fn test_forwarded_headers_trusted_peer() {
+        let mut map = headers(&[
+            ("x-forwarded-for", "1.2.3.4"),
+            ("x-forwarded-proto", "http"),
+        ]);
+        prepare_request_headers(&mut map, client(true));
+        assert_eq!(map.get("x-forwarded-for").unwrap(), "1.2.3.4, 10.0.0.7");
+        assert_eq!(map.get("x-forwarded-proto").unwrap(), "http");
+
+        let mut map = HeaderMap::new();
+        prepare_request_headers(&mut map, client(true));
+        assert_eq!(map.get("x-forwarded-for").unwrap(), "10.0.0.7");
+        assert_eq!(map.get("x-forwarded-proto").unwrap(), "https");
+    }
+}
@@ -0,0 +1,122 @@
+//! Readiness based on PHP queue saturation (`/ready`).
+//!
+//! `/health` only says the process is alive. `/ready` also fails while the
+//! PHP queue has stayed above `READY_QUEUE_THRESHOLD` percent of its
+//! capacity for `READY_SATURATION_WINDOW_SECS`, so a load balancer stops
+//! sending new traffic to an overloaded pod until it drains.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+/// Queue saturation tracker, sampled on every readiness probe.
+#[derive(Debug)]
+pub struct Readiness {
+    /// Pending requests above which the queue counts as saturated (0 = off).
+    limit: usize,
+    /// How long saturation must last before reporting not ready.
+    window: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// First probe that saw the queue above the limit.
+    saturated_since: Option<Instant>,
+    /// Last reported result, to log transitions only.
+    not_ready: bool,
+}
+
+impl Readiness {
+    /// `threshold_percent` of `queue_capacity`; 0 disables the check.
+    pub fn new(queue_capacity: usize, threshold_percent: u8, window: Duration) -> Self {
+        let limit = if threshold_percent == 0 {
+            0
+        } else {
+            (queue_capacity * threshold_percent as usize / 100).max(1)
+        };
+        Self {
+            limit,
+            window,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Ready unless `pending` has been above the limit for the whole window.
+    pub fn is_ready(&self, pending: usize) -> bool {
+        self.is_ready_at(pending, Instant::now())
+    }
+
+    fn is_ready_at(&self, pending: usize, now: Instant) -> bool {
+        if self.limit == 0 {
+            return true;
+        }
+        let mut state = self.state.lock().unwrap();
+        let ready = if pending > self.limit {
+            let start = *state.saturated_since.get_or_insert(now);
+            now.duration_since(start) < self.window
+        } else {
+            state.saturated_since = None;
+            true
+        };
+
+        if state.not_ready == ready {
+            state.not_ready = !ready;
+            if ready {
+                info!(
+                    pending,
+                    limit = self.limit,
+                    "PHP queue drained, ready again"
+                );
+            } else {
+                warn!(
+                    pending,
+                    limit = self.limit,
+                    window_secs = self.window.as_secs(),
+                    "PHP queue saturated, reporting not ready"
+                );
+            }
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sustained_saturation_not_ready() {
+        let readiness = Readiness::new(100, 80, Duration::from_secs(10));
+        let t0 = Instant::now();
+
+        assert!(readiness.is_ready_at(50, t0));
+        // Above 80 but not for the whole window yet
+        assert!(readiness.is_ready_at(95, t0));
+        assert!(readiness.is_ready_at(90, t0 + Duration::from_secs(5)));
+        assert!(!readiness.is_ready_at(90, t0 + Duration::from_secs(10)));
+        assert!(!readiness.is_ready_at(100, t0 + Duration::from_secs(20)));
+
+        // Drained: ready immediately, and the window starts over
+        assert!(readiness.is_ready_at(10, t0 + Duration::from_secs(21)));
+        assert!(readiness.is_ready_at(90, t0 + Duration::from_secs(22)));
+        assert!(!readiness.is_ready_at(90, t0 + Duration::from_secs(32)));
+    }
+
+    #[test]
+    fn test_transient_spike_stays_ready() {
+        let readiness = Readiness::new(100, 80, Duration::from_secs(10));
+        let t0 = Instant::now();
+
+        assert!(readiness.is_ready_at(99, t0));
+        assert!(readiness.is_ready_at(20, t0 + Duration::from_secs(8)));
+        assert!(readiness.is_ready_at(99, t0 + Duration::from_secs(12)));
+    }
+
+    #[test]
+    fn test_disabled() {
+        let readiness = Readiness::new(100, 0, Duration::ZERO);
+        assert!(readiness.is_ready_at(usize::MAX, Instant::now()));
+    }
+}
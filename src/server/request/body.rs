@@ -0,0 +1,226 @@
+//! Request body reading.
+//!
+//! Collecting a large body chunk by chunk reallocates as the buffer grows.
+//! When the body announces its length (`Content-Length`), the buffer is sized
+//! up front so it is allocated once.
+//!
+//! A body over `MAX_BODY_SIZE` is refused: up front from its `Content-Length`
+//! (see [`reject_before_body`]), or while reading when it is chunked.
+
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use hyper::body::Body;
+use hyper::header::{HeaderMap, CONTENT_LENGTH, EXPECT};
+use hyper::StatusCode;
+
+/// Why a request body could not be read.
+#[derive(Debug)]
+pub enum BodyError<E> {
+    /// The body grew past the size limit.
+    TooLarge,
+    /// The client connection failed mid-body.
+    Read(E),
+}
+
+/// Status to answer without reading the body, if any.
+///
+/// hyper sends `100 Continue` to a client that asked for it (`Expect:
+/// 100-continue`) only when the body is first read. Refusing here therefore
+/// spares the client the upload: 417 for any other expectation, 413 when the
+/// declared length is over `max_len` (0 = unlimited).
+pub fn reject_before_body(headers: &HeaderMap, max_len: usize) -> Option<StatusCode> {
+    if let Some(expect) = headers.get(EXPECT) {
+        let continue_expected = expect
+            .to_str()
+            .is_ok_and(|v| v.trim().eq_ignore_ascii_case("100-continue"));
+        if !continue_expected {
+            return Some(StatusCode::EXPECTATION_FAILED);
+        }
+    }
+    let declared = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    match declared {
+        Some(len) if max_len > 0 && len > max_len as u64 => Some(StatusCode::PAYLOAD_TOO_LARGE),
+        _ => None,
+    }
+}
+
+/// Read the whole body into one buffer.
+///
+/// The declared length is only a hint: it is capped at `max_prealloc`, so a
+/// client announcing a huge body can't make us reserve more than that. A
+/// shorter body leaves spare capacity, a longer one grows the buffer as
+/// usual. `max_prealloc = 0` disables pre-sizing. Reading stops with
+/// [`BodyError::TooLarge`] past `max_len` bytes (0 = unlimited).
+pub async fn read_body<B>(
+    body: B,
+    max_prealloc: usize,
+    max_len: usize,
+) -> Result<Bytes, BodyError<B::Error>>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    read_body_vec(body, max_prealloc, max_len)
+        .await
+        .map(Bytes::from)
+}
+
+async fn read_body_vec<B>(
+    mut body: B,
+    max_prealloc: usize,
+    max_len: usize,
+) -> Result<Vec<u8>, BodyError<B::Error>>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    let capacity = body
+        .size_hint()
+        .exact()
+        .map_or(0, |len| len.min(max_prealloc as u64) as usize);
+    let mut buf = Vec::with_capacity(capacity);
+
+    while let Some(frame) = body.frame().await {
+        // Trailers are not passed to PHP
+        if let Ok(data) = frame.map_err(BodyError::Read)?.into_data() {
+            if max_len > 0 && buf.len() + data.len() > max_len {
+                return Err(BodyError::TooLarge);
+            }
+            buf.extend_from_slice(&data);
+        }
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::{Frame, SizeHint};
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// Body yielding fixed chunks and announcing `declared` as its length.
+    struct Chunks {
+        chunks: VecDeque<Bytes>,
+        declared: Option<u64>,
+    }
+
+    impl Chunks {
+        fn new(chunks: usize, chunk_len: usize, declared: Option<u64>) -> Self {
+            Self {
+                chunks: (0..chunks)
+                    .map(|i| Bytes::from(vec![i as u8; chunk_len]))
+                    .collect(),
+                declared,
+            }
+        }
+    }
+
+    impl Body for Chunks {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+            Poll::Ready(self.chunks.pop_front().map(|c| Ok(Frame::data(c))))
+        }
+
+        fn size_hint(&self) -> SizeHint {
+            self.declared.map(SizeHint::with_exact).unwrap_or_default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_known_length_single_allocation() {
+        let buf = read_body_vec(Chunks::new(16, 4096, Some(65536)), 1 << 20, 0)
+            .await
+            .unwrap();
+        assert_eq!(buf.len(), 65536);
+        // Any reallocation would have grown capacity past the declared length
+        assert_eq!(buf.capacity(), 65536);
+        assert!(buf[..4096].iter().all(|&b| b == 0));
+        assert!(buf[61440..].iter().all(|&b| b == 15));
+    }
+
+    #[tokio::test]
+    async fn test_declared_length_capped() {
+        let buf = read_body_vec(Chunks::new(4, 1024, Some(4096)), 1024, 0)
+            .await
+            .unwrap();
+        assert_eq!(buf.len(), 4096);
+
+        // Claims 1 GiB, sends 10 bytes
+        let buf = read_body_vec(Chunks::new(1, 10, Some(1 << 30)), 4096, 0)
+            .await
+            .unwrap();
+        assert_eq!(buf.len(), 10);
+        assert!(buf.capacity() <= 4096);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_or_disabled_hint() {
+        let body = read_body(Chunks::new(3, 100, None), 1 << 20, 0)
+            .await
+            .unwrap();
+        assert_eq!(body.len(), 300);
+
+        let buf = read_body_vec(Chunks::new(0, 0, Some(4096)), 0, 0)
+            .await
+            .unwrap();
+        assert!(buf.is_empty());
+        assert_eq!(buf.capacity(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_max_len() {
+        let body = read_body(Chunks::new(4, 100, None), 0, 400).await.unwrap();
+        assert_eq!(body.len(), 400);
+
+        let err = read_body(Chunks::new(5, 100, None), 0, 400).await;
+        assert!(matches!(err, Err(BodyError::TooLarge)));
+    }
+
+    #[test]
+    fn test_reject_before_body() {
+        let headers = |pairs: &[(&str, &str)]| {
+            let mut map = HeaderMap::new();
+            for (name, value) in pairs {
+                map.insert(
+                    hyper::header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                    value.parse().unwrap(),
+                );
+            }
+            map
+        };
+
+        assert_eq!(reject_before_body(&headers(&[]), 0), None);
+        assert_eq!(
+            reject_before_body(
+                &headers(&[("expect", "100-Continue"), ("content-length", "10")]),
+                10
+            ),
+            None
+        );
+        assert_eq!(
+            reject_before_body(
+                &headers(&[("expect", "100-continue"), ("content-length", "11")]),
+                10
+            ),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+        // Without a limit any declared length passes
+        assert_eq!(
+            reject_before_body(&headers(&[("content-length", "99999999999")]), 0),
+            None
+        );
+        assert_eq!(
+            reject_before_body(&headers(&[("expect", "something-else")]), 0),
+            Some(StatusCode::EXPECTATION_FAILED)
+        );
+    }
+}
@@ -1,7 +1,11 @@
 //! HTTP request parsing and context.
 
+mod body;
 mod multipart;
 mod parser;
+mod upload_types;
 
+pub use body::{read_body, reject_before_body, BodyError};
 pub use multipart::parse_multipart;
-pub use parser::{parse_cookies, parse_query_string};
+pub use parser::{parse_basic_auth, parse_cookies, parse_query_string, parse_query_string_limited};
+pub use upload_types::UploadTypes;
@@ -1,6 +1,7 @@
 //! Multipart form data parsing.
 
 use std::borrow::Cow;
+use std::collections::HashMap;
 
 use bytes::Bytes;
 use futures_util::stream;
@@ -9,17 +10,25 @@ use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 
+use super::UploadTypes;
 use crate::types::{ParamList, UploadedFile};
 
 /// Maximum upload size (10 MB)
 const MAX_UPLOAD_SIZE: u64 = 10 * 1024 * 1024;
 
+/// PHP's UPLOAD_ERR_EXTENSION: the upload was stopped before it reached disk.
+const UPLOAD_ERR_EXTENSION: u8 = 8;
+
 /// Parse multipart form data.
 ///
-/// Returns a tuple of (form fields, uploaded files).
+/// Returns a tuple of (form fields, uploaded files). Text fields beyond
+/// `max_vars` are dropped (PHP's `max_input_vars`). Files whose type isn't
+/// in `allowed_types` are not written and get `UPLOAD_ERR_EXTENSION`.
 pub async fn parse_multipart(
     content_type: &str,
     body: Bytes,
+    max_vars: usize,
+    allowed_types: &UploadTypes,
 ) -> Result<(ParamList, Vec<(String, Vec<UploadedFile>)>), String> {
     let boundary = content_type
         .split(';')
@@ -37,6 +46,8 @@ pub async fn parse_multipart(
 
     let mut params = Vec::new();
     let mut files: Vec<(String, Vec<UploadedFile>)> = Vec::new();
+    // Field name -> index in `files` (keeps many distinct file fields linear)
+    let mut file_index: HashMap<String, usize> = HashMap::new();
 
     while let Some(field) = multipart.next_field().await.map_err(|e| e.to_string())? {
         let field_name = field.name().unwrap_or("").to_string();
@@ -68,6 +79,14 @@ pub async fn parse_multipart(
                     size,
                     error: 1,
                 }
+            } else if !allowed_types.allows(&field_content_type, &data) {
+                UploadedFile {
+                    name: original_name,
+                    mime_type: field_content_type,
+                    tmp_name: String::new(),
+                    size,
+                    error: UPLOAD_ERR_EXTENSION,
+                }
             } else {
                 let tmp_name = format!("/tmp/php{}", Uuid::new_v4().simple());
 
@@ -85,12 +104,13 @@ pub async fn parse_multipart(
             };
 
             // Find existing entry or create new one
-            if let Some(entry) = files.iter_mut().find(|(name, _)| name == &normalized_name) {
-                entry.1.push(uploaded_file);
+            if let Some(&idx) = file_index.get(&normalized_name) {
+                files[idx].1.push(uploaded_file);
             } else {
+                file_index.insert(normalized_name.clone(), files.len());
                 files.push((normalized_name, vec![uploaded_file]));
             }
-        } else {
+        } else if params.len() < max_vars {
             let value = field.text().await.map_err(|e| e.to_string())?;
             params.push((Cow::Owned(field_name), Cow::Owned(value)));
         }
@@ -98,3 +118,52 @@ pub async fn parse_multipart(
 
     Ok((params, files))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY: &str = "X-BOUNDARY";
+
+    fn file_part(name: &str, file_name: &str, mime: &str, data: &str) -> String {
+        format!(
+            "--{BOUNDARY}\r\n\
+             Content-Disposition: form-data; name=\"{name}\"; filename=\"{file_name}\"\r\n\
+             Content-Type: {mime}\r\n\r\n{data}\r\n"
+        )
+    }
+
+    async fn parse(parts: &[String], allowed: &UploadTypes) -> Vec<(String, Vec<UploadedFile>)> {
+        let body = format!("{}--{BOUNDARY}--\r\n", parts.concat());
+        let content_type = format!("multipart/form-data; boundary={BOUNDARY}");
+        let (_, files) = parse_multipart(&content_type, Bytes::from(body), 1000, allowed)
+            .await
+            .unwrap();
+        files
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_upload_flagged() {
+        let allowed = UploadTypes::parse(&["image/gif"], true).unwrap();
+        let files = parse(
+            &[
+                file_part("ok", "a.gif", "image/gif", "GIF89a"),
+                file_part("bad", "shell.gif", "image/gif", "<?php echo 1; ?>"),
+            ],
+            &allowed,
+        )
+        .await;
+
+        let ok = &files[0].1[0];
+        assert_eq!(ok.error, 0);
+        assert!(!ok.tmp_name.is_empty());
+        let _ = std::fs::remove_file(&ok.tmp_name);
+
+        // Spoofed file is reported to PHP but never written
+        let bad = &files[1].1[0];
+        assert_eq!(files[1].0, "bad");
+        assert_eq!(bad.error, UPLOAD_ERR_EXTENSION);
+        assert!(bad.tmp_name.is_empty());
+        assert_eq!(bad.size, 16);
+    }
+}
@@ -2,6 +2,9 @@
 
 use std::borrow::Cow;
 
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
 use crate::types::ParamList;
 
 /// Fast percent decode - returns Cow to avoid allocation when no decoding needed.
@@ -23,10 +26,21 @@ pub fn fast_percent_decode(s: &str) -> Cow<'static, str> {
 /// Returns `ParamList` (Vec of Cow pairs) - all values are dynamic (Owned).
 #[inline]
 pub fn parse_query_string(query: &str) -> ParamList {
-    let pair_count = query.matches('&').count() + 1;
-    let mut params = Vec::with_capacity(pair_count.min(16));
+    parse_query_string_limited(query, usize::MAX)
+}
+
+/// Parse a query string (or urlencoded body), keeping at most `max_vars` pairs.
+///
+/// Single pass, linear in the input length. Pairs beyond `max_vars` are
+/// dropped, like PHP's `max_input_vars`, so a huge request cannot make the
+/// superglobal build unbounded.
+pub fn parse_query_string_limited(query: &str, max_vars: usize) -> ParamList {
+    let mut params = Vec::with_capacity(estimate_pairs(query, b'&', max_vars));
 
     for pair in query.split('&') {
+        if params.len() >= max_vars {
+            break;
+        }
         if pair.is_empty() {
             continue;
         }
@@ -44,15 +58,17 @@ pub fn parse_query_string(query: &str) -> ParamList {
     params
 }
 
-/// Parse a Cookie header into name-value pairs.
+/// Parse a Cookie header into name-value pairs, keeping at most `max_vars`.
 ///
 /// Returns `ParamList` (Vec of Cow pairs) - all values are dynamic (Owned).
 #[inline]
-pub fn parse_cookies(cookie_header: &str) -> ParamList {
-    let cookie_count = cookie_header.matches(';').count() + 1;
-    let mut cookies = Vec::with_capacity(cookie_count.min(16));
+pub fn parse_cookies(cookie_header: &str, max_vars: usize) -> ParamList {
+    let mut cookies = Vec::with_capacity(estimate_pairs(cookie_header, b';', max_vars));
 
     for cookie in cookie_header.split(';') {
+        if cookies.len() >= max_vars {
+            break;
+        }
         let cookie = cookie.trim();
         if cookie.is_empty() {
             continue;
@@ -70,3 +86,99 @@ pub fn parse_cookies(cookie_header: &str) -> ParamList {
 
     cookies
 }
+
+/// Credentials from an `Authorization: Basic` header, for `PHP_AUTH_USER` /
+/// `PHP_AUTH_PW`. Other schemes and malformed values give `None`.
+pub fn parse_basic_auth(authorization: &str) -> Option<(String, String)> {
+    let (scheme, encoded) = authorization.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = STANDARD.decode(encoded.trim()).ok()?;
+    let decoded = String::from_utf8_lossy(&decoded);
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+/// Upper bound on pairs for preallocation (capped so a hostile input can't
+/// force a huge allocation up front).
+#[inline]
+fn estimate_pairs(input: &str, separator: u8, max_vars: usize) -> usize {
+    let pairs = input.bytes().filter(|&b| b == separator).count() + 1;
+    pairs.min(max_vars).min(1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query_with(n: usize) -> String {
+        (0..n)
+            .map(|i| format!("k{i}=v%20{i}"))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    #[test]
+    fn test_parse_basic_auth() {
+        // "aladdin:open:sesame" - the password may contain ':'
+        assert_eq!(
+            parse_basic_auth("Basic YWxhZGRpbjpvcGVuOnNlc2FtZQ=="),
+            Some(("aladdin".to_string(), "open:sesame".to_string()))
+        );
+        assert_eq!(
+            parse_basic_auth("basic dTo="),
+            Some(("u".to_string(), String::new()))
+        );
+        assert_eq!(parse_basic_auth("Bearer YWxhZGRpbjpvcGVu"), None);
+        assert_eq!(parse_basic_auth("Basic !!!"), None);
+        // No ':' separator
+        assert_eq!(parse_basic_auth("Basic dXNlcg=="), None);
+    }
+
+    #[test]
+    fn test_parse_query_string() {
+        let params = parse_query_string("a=1&b=hello%20world&&c&=x");
+        assert_eq!(params.len(), 3);
+        assert_eq!(params[0], (Cow::from("a"), Cow::from("1")));
+        assert_eq!(params[1], (Cow::from("b"), Cow::from("hello world")));
+        assert_eq!(params[2], (Cow::from("c"), Cow::from("")));
+    }
+
+    #[test]
+    fn test_query_string_respects_limit() {
+        let query = query_with(50);
+        assert_eq!(parse_query_string_limited(&query, 10).len(), 10);
+        assert_eq!(parse_query_string_limited(&query, 1000).len(), 50);
+        assert!(parse_query_string_limited(&query, 0).is_empty());
+    }
+
+    #[test]
+    fn test_cookies_respect_limit() {
+        let header = "a=1; b=2; c=3; d=4";
+        assert_eq!(parse_cookies(header, 2).len(), 2);
+        assert_eq!(parse_cookies(header, 100).len(), 4);
+    }
+
+    #[test]
+    fn test_large_param_count() {
+        let large = query_with(50_000);
+
+        let params = parse_query_string(&large);
+        assert_eq!(params.len(), 50_000);
+        assert_eq!(params[49_999].1, "v 49999");
+
+        // The max_vars cutoff keeps the first pairs only
+        let limited = parse_query_string_limited(&large, 1000);
+        assert_eq!(limited.len(), 1000);
+        assert_eq!(limited[999].0, "k999");
+    }
+
+    #[test]
+    fn test_estimate_pairs_caps_preallocation() {
+        let large = query_with(50_000);
+        assert_eq!(estimate_pairs(&large, b'&', usize::MAX), 1024);
+        assert_eq!(estimate_pairs(&large, b'&', 100), 100);
+        assert_eq!(estimate_pairs("a=1&b=2", b'&', usize::MAX), 2);
+    }
+}
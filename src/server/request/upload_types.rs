@@ -0,0 +1,150 @@
+//! Allowlist of upload content types (`UPLOAD_ALLOWED_TYPES`).
+//!
+//! The Content-Type of a multipart file part is whatever the client says it
+//! is. With sniffing on, the first bytes of the file are also checked against
+//! a few well-known signatures, so a PHP script declared as `image/png` is
+//! caught by its `<?php` prefix rather than trusted by its label.
+
+/// Permitted upload MIME types (empty = any type accepted).
+#[derive(Clone, Debug, Default)]
+pub struct UploadTypes {
+    /// Lowercase `type/subtype` or `type/*`.
+    patterns: Vec<String>,
+    /// Also check the file's magic bytes.
+    sniff: bool,
+}
+
+impl UploadTypes {
+    /// Parse patterns like `image/png` or `image/*`.
+    pub fn parse<S: AsRef<str>>(patterns: &[S], sniff: bool) -> Result<Self, String> {
+        let mut parsed = Vec::with_capacity(patterns.len());
+        for pattern in patterns {
+            let pattern = pattern.as_ref().trim().to_ascii_lowercase();
+            match pattern.split_once('/') {
+                Some((ty, subtype))
+                    if !ty.is_empty()
+                        && ty != "*"
+                        && !subtype.is_empty()
+                        && !subtype.contains('/') => {}
+                _ => return Err(format!("expected type/subtype or type/*, got '{pattern}'")),
+            }
+            parsed.push(pattern);
+        }
+        Ok(Self {
+            patterns: parsed,
+            sniff,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether an upload with this declared type and content is accepted.
+    ///
+    /// Both the declared type and, when sniffing, the detected type must be
+    /// allowlisted. Content that matches no known signature is judged by its
+    /// declared type alone.
+    pub fn allows(&self, declared: &str, data: &[u8]) -> bool {
+        if self.patterns.is_empty() {
+            return true;
+        }
+        if !self.matches(declared) {
+            return false;
+        }
+        !self.sniff || sniff(data).is_none_or(|detected| self.matches(detected))
+    }
+
+    fn matches(&self, mime: &str) -> bool {
+        // Drop parameters such as "; charset=utf-8"
+        let mime = mime.split(';').next().unwrap_or("").trim();
+        let Some((ty, _)) = mime.split_once('/') else {
+            return false;
+        };
+        self.patterns.iter().any(|pattern| {
+            pattern.eq_ignore_ascii_case(mime)
+                || pattern
+                    .strip_suffix("/*")
+                    .is_some_and(|p| p.eq_ignore_ascii_case(ty))
+        })
+    }
+}
+
+/// Content type from well-known leading bytes.
+fn sniff(data: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\x7fELF", "application/x-executable"),
+        (b"MZ", "application/x-msdownload"),
+        (b"#!", "text/x-shellscript"),
+    ];
+    // PHP may hide behind a valid-looking prefix (e.g. a GIF header), so an
+    // open tag near the start wins over any signature
+    let head = &data[..data.len().min(1024)];
+    if head.windows(5).any(|w| w.eq_ignore_ascii_case(b"<?php")) {
+        return Some("application/x-httpd-php");
+    }
+    if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    SIGNATURES
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map(|&(_, mime)| mime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn test_allowed_type_passes() {
+        let types = UploadTypes::parse(&["image/png", "application/pdf"], true).unwrap();
+        assert!(types.allows("image/png", PNG));
+        assert!(types.allows("application/pdf", b"%PDF-1.7\n"));
+        // Unknown signature: declared type decides
+        assert!(types.allows("application/pdf", b"plain bytes"));
+    }
+
+    #[test]
+    fn test_disallowed_type_rejected() {
+        let types = UploadTypes::parse(&["image/png"], true).unwrap();
+        assert!(!types.allows("application/x-php", b"<?php echo 1;"));
+        assert!(!types.allows("text/html", b"<html></html>"));
+        assert!(!types.allows("", PNG));
+    }
+
+    #[test]
+    fn test_spoofed_type_rejected() {
+        let types = UploadTypes::parse(&["image/*"], true).unwrap();
+        assert!(types.allows("image/gif", b"GIF89a\x01\0\x01\0"));
+        assert!(!types.allows("image/png", b"<?php system($_GET['c']); ?>"));
+        assert!(!types.allows("image/gif", b"GIF89a<?php echo 1; ?>"));
+        assert!(!types.allows("image/jpeg", b"MZ\x90\0"));
+
+        // Without sniffing only the label is checked
+        let types = UploadTypes::parse(&["image/*"], false).unwrap();
+        assert!(types.allows("image/png", b"<?php system($_GET['c']); ?>"));
+    }
+
+    #[test]
+    fn test_parse() {
+        let types = UploadTypes::default();
+        assert!(types.is_empty());
+        assert!(types.allows("application/x-php", b"<?php"));
+
+        let types = UploadTypes::parse(&["Image/PNG"], false).unwrap();
+        assert!(types.allows("image/png; name=a.png", b""));
+        assert!(UploadTypes::parse(&["png"], false).is_err());
+        assert!(UploadTypes::parse(&["*/*"], false).is_err());
+        assert!(UploadTypes::parse(&["image/"], false).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! Brotli-compressed static files kept in memory (`STATIC_COMPRESS_CACHE_SIZE`).
+//!
+//! Popular assets would otherwise be read and compressed again on every
+//! request. An entry is keyed by path and remembers the file's size and mtime
+//! and the encoder settings it was built with; a mismatch on lookup drops it,
+//! so edited files and `BROTLI_*` changes are picked up on the next request.
+//! Entries are evicted least recently used first to stay within the byte
+//! budget.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use bytes::Bytes;
+
+/// Budget used unless `STATIC_COMPRESS_CACHE_SIZE` says otherwise (32 MiB).
+pub const DEFAULT_COMPRESSED_CACHE_SIZE: usize = 32 * 1024 * 1024;
+
+struct Entry {
+    size: u64,
+    mtime: SystemTime,
+    quality: u32,
+    window: u32,
+    body: Bytes,
+    last_used: u64,
+}
+
+impl Entry {
+    /// Bytes charged against the budget.
+    fn cost(path: &Path, body: &Bytes) -> usize {
+        body.len() + path.as_os_str().len()
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<PathBuf, Entry>,
+    bytes: usize,
+    /// Use counter for LRU order.
+    tick: u64,
+}
+
+impl Inner {
+    fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.entries.remove(path) {
+            self.bytes -= Entry::cost(path, &entry.body);
+        }
+    }
+}
+
+/// Compressed static file bodies, bounded by total size.
+#[derive(Default)]
+pub struct CompressedCache {
+    inner: Mutex<Inner>,
+    /// Byte budget (0 = disabled).
+    max_bytes: usize,
+}
+
+impl CompressedCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            inner: Mutex::default(),
+            max_bytes,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes > 0
+    }
+
+    /// Cached body for this version of the file at this quality and window.
+    pub fn get(
+        &self,
+        path: &Path,
+        size: u64,
+        mtime: SystemTime,
+        quality: u32,
+        window: u32,
+    ) -> Option<Bytes> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.tick += 1;
+        let tick = inner.tick;
+        let entry = inner.entries.get_mut(path)?;
+        if entry.size == size
+            && entry.mtime == mtime
+            && entry.quality == quality
+            && entry.window == window
+        {
+            entry.last_used = tick;
+            return Some(entry.body.clone());
+        }
+        // Stale: the file or the encoder settings changed
+        inner.remove(path);
+        None
+    }
+
+    /// Store a compressed body, evicting least recently used entries to fit.
+    pub fn insert(
+        &self,
+        path: &Path,
+        size: u64,
+        mtime: SystemTime,
+        quality: u32,
+        window: u32,
+        body: Bytes,
+    ) {
+        let cost = Entry::cost(path, &body);
+        if cost > self.max_bytes {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.remove(path);
+        while inner.bytes + cost > self.max_bytes {
+            let Some(oldest) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(p, _)| p.clone())
+            else {
+                break;
+            };
+            inner.remove(&oldest);
+        }
+        inner.tick += 1;
+        let last_used = inner.tick;
+        inner.bytes += cost;
+        inner.entries.insert(
+            path.to_path_buf(),
+            Entry {
+                size,
+                mtime,
+                quality,
+                window,
+                body,
+                last_used,
+            },
+        );
+    }
+
+    /// Bytes currently held.
+    pub fn bytes(&self) -> usize {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const W: u32 = 20;
+
+    fn body(len: usize) -> Bytes {
+        Bytes::from(vec![b'x'; len])
+    }
+
+    #[test]
+    fn test_invalidated_by_mtime_size_and_settings() {
+        let cache = CompressedCache::new(1024);
+        let path = Path::new("/app.js");
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let q = 4;
+
+        cache.insert(path, 500, mtime, q, W, body(100));
+        assert_eq!(cache.get(path, 500, mtime, q, W).unwrap().len(), 100);
+
+        // Another quality is a different representation
+        assert!(cache.get(path, 500, mtime, q + 1, W).is_none());
+        assert!(cache.get(path, 500, mtime, q, W).is_none());
+
+        cache.insert(path, 500, mtime, q, W, body(100));
+        let edited = mtime + Duration::from_secs(1);
+        assert!(cache.get(path, 500, edited, q, W).is_none());
+        assert_eq!(cache.bytes(), 0);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = CompressedCache::new(350);
+        let mtime = SystemTime::UNIX_EPOCH;
+        let q = 4;
+        let (a, b, c) = (Path::new("/a"), Path::new("/b"), Path::new("/c"));
+
+        cache.insert(a, 1, mtime, q, W, body(100));
+        cache.insert(b, 1, mtime, q, W, body(100));
+        cache.insert(c, 1, mtime, q, W, body(100));
+        // Touch /a so /b is the oldest
+        assert!(cache.get(a, 1, mtime, q, W).is_some());
+        cache.insert(Path::new("/d"), 1, mtime, q, W, body(100));
+
+        assert!(cache.get(a, 1, mtime, q, W).is_some());
+        assert!(cache.get(b, 1, mtime, q, W).is_none());
+        assert!(cache.bytes() <= 350);
+
+        // Larger than the whole budget: not cached
+        cache.insert(Path::new("/big"), 1, mtime, q, W, body(400));
+        assert!(cache.get(Path::new("/big"), 1, mtime, q, W).is_none());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let cache = CompressedCache::default();
+        let mtime = SystemTime::UNIX_EPOCH;
+        cache.insert(Path::new("/a"), 1, mtime, 4, W, body(10));
+        assert!(cache.get(Path::new("/a"), 1, mtime, 4, W).is_none());
+    }
+}
@@ -1,6 +1,7 @@
 //! Brotli compression utilities.
 
-/// Minimum size to consider compression (smaller bodies don't benefit).
+/// Default minimum size to consider compression (BROTLI_MIN_SIZE,
+/// BROTLI_STATIC_MIN_SIZE); smaller bodies rarely benefit.
 pub const MIN_COMPRESSION_SIZE: usize = 256;
 
 /// Maximum size for compression (3 MB).
@@ -11,12 +12,6 @@ pub const MAX_COMPRESSION_SIZE: usize = 3 * 1024 * 1024; // 3 MB
 /// Non-compressible files larger than this are streamed from disk.
 pub const STREAM_THRESHOLD_NON_COMPRESSIBLE: usize = 1024 * 1024; // 1 MB
 
-/// Brotli compression quality (0-11, higher = better compression but slower)
-const BROTLI_QUALITY: u32 = 4;
-
-/// Brotli compression window size (10-24, affects memory usage)
-const BROTLI_WINDOW: u32 = 20;
-
 /// Check if the client accepts Brotli encoding
 #[inline]
 pub fn accepts_brotli(accept_encoding: &str) -> bool {
@@ -25,47 +20,110 @@ pub fn accepts_brotli(accept_encoding: &str) -> bool {
         .any(|enc| enc.trim().starts_with("br"))
 }
 
+/// Types compressed unless `COMPRESS_TYPES` removes them.
+const DEFAULT_COMPRESSIBLE_TYPES: [&str; 19] = [
+    // Text types
+    "text/html",
+    "text/css",
+    "text/plain",
+    "text/xml",
+    "text/javascript",
+    // Application types
+    "application/javascript",
+    "application/json",
+    "application/xml",
+    "application/xhtml+xml",
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/manifest+json",
+    "application/ld+json",
+    // SVG
+    "image/svg+xml",
+    // Fonts (uncompressed formats - WOFF/WOFF2 are already compressed)
+    "font/ttf",
+    "font/otf",
+    "application/x-font-ttf",
+    "application/x-font-opentype",
+    "application/vnd.ms-fontobject",
+];
+
+/// MIME types worth compressing.
+///
+/// Any `+json` or `+xml` structured-syntax suffix (RFC 6839) counts as
+/// compressible unless the type was removed explicitly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompressibleTypes {
+    /// Lowercase `type/subtype` entries.
+    types: Vec<Box<str>>,
+    /// Lowercase entries removed with `-type/subtype`.
+    removed: Vec<Box<str>>,
+}
+
+impl Default for CompressibleTypes {
+    fn default() -> Self {
+        Self {
+            types: DEFAULT_COMPRESSIBLE_TYPES
+                .iter()
+                .map(|&t| t.into())
+                .collect(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+impl CompressibleTypes {
+    /// Defaults plus `type/subtype` entries; `-type/subtype` removes one.
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> Result<Self, String> {
+        let mut types = Self::default();
+        for entry in entries {
+            let entry = entry.as_ref().trim();
+            let (remove, mime) = match entry.strip_prefix('-') {
+                Some(mime) => (true, mime.trim()),
+                None => (false, entry),
+            };
+            let mime = mime.to_ascii_lowercase();
+            match mime.split_once('/') {
+                Some((ty, sub)) if !ty.is_empty() && !sub.is_empty() && !mime.contains(';') => {}
+                _ => return Err(format!("expected type/subtype, got '{entry}'")),
+            }
+            types.types.retain(|t| **t != *mime);
+            types.removed.retain(|t| **t != *mime);
+            if remove {
+                types.removed.push(mime.into());
+            } else {
+                types.types.push(mime.into());
+            }
+        }
+        Ok(types)
+    }
+
+    /// Whether a Content-Type (parameters allowed) should be compressed.
+    pub fn matches(&self, content_type: &str) -> bool {
+        let ct = content_type.split(';').next().unwrap_or("").trim();
+        if self.removed.iter().any(|t| t.eq_ignore_ascii_case(ct)) {
+            return false;
+        }
+        let suffix = ct.rsplit_once('+').map(|(_, s)| s);
+        suffix.is_some_and(|s| s.eq_ignore_ascii_case("json") || s.eq_ignore_ascii_case("xml"))
+            || self.types.iter().any(|t| t.eq_ignore_ascii_case(ct))
+    }
+}
+
 /// Check if the MIME type should be compressed
 #[inline]
-pub fn should_compress_mime(content_type: &str) -> bool {
-    let ct = content_type.split(';').next().unwrap_or("").trim();
-    matches!(
-        ct,
-        // Text types
-        "text/html"
-            | "text/css"
-            | "text/plain"
-            | "text/xml"
-            | "text/javascript"
-            // Application types
-            | "application/javascript"
-            | "application/json"
-            | "application/xml"
-            | "application/xhtml+xml"
-            | "application/rss+xml"
-            | "application/atom+xml"
-            | "application/manifest+json"
-            | "application/ld+json"
-            // SVG
-            | "image/svg+xml"
-            // Fonts (uncompressed formats - WOFF/WOFF2 are already compressed)
-            | "font/ttf"
-            | "font/otf"
-            | "application/x-font-ttf"
-            | "application/x-font-opentype"
-            | "application/vnd.ms-fontobject"
-    )
+pub fn should_compress_mime(content_type: &str, types: &CompressibleTypes) -> bool {
+    types.matches(content_type)
 }
 
-/// Compress data using Brotli.
+/// Compress data using Brotli at `quality` (0-11) with a `window`-bit window.
 /// Returns None if compression would not reduce size.
 #[inline]
-pub fn compress_brotli(data: &[u8]) -> Option<Vec<u8>> {
+pub fn compress_brotli(data: &[u8], quality: u32, window: u32) -> Option<Vec<u8>> {
     let mut output = Vec::with_capacity(data.len() / 2);
     let mut input = std::io::Cursor::new(data);
     let params = brotli::enc::BrotliEncoderParams {
-        quality: BROTLI_QUALITY as i32,
-        lgwin: BROTLI_WINDOW as i32,
+        quality: quality as i32,
+        lgwin: window as i32,
         ..Default::default()
     };
 
@@ -74,3 +132,56 @@ pub fn compress_brotli(data: &[u8]) -> Option<Vec<u8>> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_compressible_types() {
+        let types = CompressibleTypes::default();
+        assert!(should_compress_mime("text/html; charset=utf-8", &types));
+        assert!(should_compress_mime("application/JSON", &types));
+        assert!(should_compress_mime("image/svg+xml", &types));
+        assert!(!should_compress_mime("image/png", &types));
+        assert!(!should_compress_mime("font/woff2", &types));
+        assert!(!should_compress_mime("", &types));
+    }
+
+    #[test]
+    fn test_structured_syntax_suffixes() {
+        let types = CompressibleTypes::default();
+        assert!(should_compress_mime("application/geo+json", &types));
+        assert!(should_compress_mime(
+            "application/vnd.api+json; charset=utf-8",
+            &types
+        ));
+        assert!(should_compress_mime("application/soap+xml", &types));
+        assert!(!should_compress_mime("application/vnd.foo+zip", &types));
+    }
+
+    #[test]
+    fn test_parse_compressible_types() {
+        let types = CompressibleTypes::parse(&["application/wasm", "-text/plain"]).unwrap();
+        assert!(should_compress_mime("application/wasm", &types));
+        assert!(!should_compress_mime("text/plain", &types));
+        assert!(should_compress_mime("text/css", &types));
+
+        // Removals also override the +json/+xml suffix rule
+        let types = CompressibleTypes::parse(&["-image/svg+xml", "-application/ld+json"]).unwrap();
+        assert!(!should_compress_mime("image/svg+xml", &types));
+        assert!(!should_compress_mime(
+            "application/LD+JSON; charset=utf-8",
+            &types
+        ));
+        assert!(should_compress_mime("application/geo+json", &types));
+
+        // A later entry adds a removed type back
+        let types = CompressibleTypes::parse(&["-image/svg+xml", "image/svg+xml"]).unwrap();
+        assert!(should_compress_mime("image/svg+xml", &types));
+
+        assert!(CompressibleTypes::parse(&["wasm"]).is_err());
+        assert!(CompressibleTypes::parse(&["text/"]).is_err());
+        assert!(CompressibleTypes::parse(&["text/html; q=1"]).is_err());
+    }
+}
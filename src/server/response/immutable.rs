@@ -0,0 +1,276 @@
+//! Fingerprinted asset detection for `Cache-Control: immutable`.
+//!
+//! Build tools put a content hash in asset names (`app.3f9a2c1d.js`,
+//! `index-BxkP3Y7a.css`), so such a URL never changes content and can be
+//! cached for a year. Patterns come from `STATIC_IMMUTABLE`:
+//!
+//! - `*` matches any run of characters
+//! - `[hash]` matches 8+ characters of `[A-Za-z0-9_-]` including a digit
+//! - everything else matches literally
+//!
+//! Patterns without `/` are matched against the file name, patterns with
+//! `/` against the whole URI path. Only a request that names the static file
+//! directly is matched: an INDEX_FILE fallback or directory index for the same
+//! URL must stay revalidated.
+
+/// Shortest run accepted as a content hash.
+const MIN_HASH_LEN: usize = 8;
+
+/// Longer paths are never treated as assets (bounds the matching work).
+const MAX_PATH_LEN: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Literal(String),
+    Star,
+    Hash,
+}
+
+#[derive(Clone, Debug)]
+struct Pattern {
+    tokens: Vec<Token>,
+    /// Match the full URI path instead of the file name.
+    full_path: bool,
+}
+
+/// Set of fingerprinted-asset patterns (empty = feature off).
+#[derive(Clone, Debug, Default)]
+pub struct ImmutableAssets {
+    patterns: Vec<Pattern>,
+}
+
+impl ImmutableAssets {
+    /// Parse patterns like `*.[hash].js` or `/assets/*`.
+    pub fn parse<S: AsRef<str>>(patterns: &[S]) -> Result<Self, String> {
+        let patterns = patterns
+            .iter()
+            .map(|p| parse_pattern(p.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// No patterns configured.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether the (decoded) URI path names a fingerprinted asset.
+    pub fn matches(&self, uri_path: &str) -> bool {
+        if uri_path.len() > MAX_PATH_LEN {
+            return false;
+        }
+        let file_name = uri_path.rsplit('/').next().unwrap_or(uri_path);
+        self.patterns.iter().any(|p| {
+            let subject = if p.full_path { uri_path } else { file_name };
+            match_tokens(&p.tokens, subject.as_bytes())
+        })
+    }
+
+    /// Whether `file_path`, resolved for the request `uri_path`, is a
+    /// fingerprinted asset. False unless the URI names that very file under
+    /// `document_root` (not an index or INDEX_FILE fallback).
+    pub fn matches_file(&self, document_root: &str, file_path: &str, uri_path: &str) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let Some(relative) = file_path.strip_prefix(document_root) else {
+            return false;
+        };
+        relative == crate::server::routing::match_path(uri_path) && self.matches(relative)
+    }
+}
+
+fn parse_pattern(raw: &str) -> Result<Pattern, String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err("empty pattern".into());
+    }
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut rest = raw;
+    while let Some(c) = rest.chars().next() {
+        let token = if c == '*' {
+            rest = &rest[1..];
+            Token::Star
+        } else if let Some(after) = rest.strip_prefix("[hash]") {
+            rest = after;
+            Token::Hash
+        } else {
+            literal.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(std::mem::take(&mut literal)));
+        }
+        // "**" is the same as "*"
+        if !(token == Token::Star && tokens.last() == Some(&Token::Star)) {
+            tokens.push(token);
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    if !tokens.contains(&Token::Hash) && !raw.contains('/') {
+        return Err(format!(
+            "pattern '{raw}' needs [hash] or a directory (e.g. /assets/*)"
+        ));
+    }
+    Ok(Pattern {
+        tokens,
+        full_path: raw.contains('/'),
+    })
+}
+
+fn is_hash_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'-'
+}
+
+/// Position in a pattern while matching.
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Before token `i` (`i == tokens.len()` accepts).
+    At(usize),
+    /// `n` bytes into the literal at token `i`.
+    Literal(usize, usize),
+    /// Inside the hash at token `i`: length so far (capped) and digit seen.
+    Hash(usize, usize, bool),
+}
+
+/// Matches by tracking every pattern position at once, so the work is
+/// linear in the subject length whatever the pattern.
+fn match_tokens(tokens: &[Token], s: &[u8]) -> bool {
+    let mut states = Vec::new();
+    add_state(tokens, &mut states, State::At(0));
+    let mut next = Vec::new();
+    for &b in s {
+        next.clear();
+        for &state in &states {
+            if let Some(state) = step(tokens, state, b) {
+                add_state(tokens, &mut next, state);
+            }
+        }
+        if next.is_empty() {
+            return false;
+        }
+        std::mem::swap(&mut states, &mut next);
+    }
+    states.contains(&State::At(tokens.len()))
+}
+
+/// Add `state` and the states reachable from it without input.
+fn add_state(tokens: &[Token], states: &mut Vec<State>, state: State) {
+    if states.contains(&state) {
+        return;
+    }
+    states.push(state);
+    match state {
+        // "*" may match nothing
+        State::At(i) if tokens.get(i) == Some(&Token::Star) => {
+            add_state(tokens, states, State::At(i + 1))
+        }
+        // A long enough hash may end here
+        State::Hash(i, MIN_HASH_LEN, true) => add_state(tokens, states, State::At(i + 1)),
+        _ => {}
+    }
+}
+
+/// State after consuming `b`, or None if `b` can't follow.
+fn step(tokens: &[Token], state: State, b: u8) -> Option<State> {
+    let literal_byte = |i: usize, n: usize| {
+        let Token::Literal(lit) = &tokens[i] else {
+            unreachable!("literal state on a non-literal token")
+        };
+        (lit.as_bytes()[n] == b).then(|| {
+            if n + 1 == lit.len() {
+                State::At(i + 1)
+            } else {
+                State::Literal(i, n + 1)
+            }
+        })
+    };
+    match state {
+        State::At(i) => match tokens.get(i)? {
+            Token::Literal(_) => literal_byte(i, 0),
+            Token::Star => Some(State::At(i)),
+            Token::Hash => is_hash_char(b).then_some(State::Hash(i, 1, b.is_ascii_digit())),
+        },
+        State::Literal(i, n) => literal_byte(i, n),
+        State::Hash(i, len, digit) => is_hash_char(b).then_some(State::Hash(
+            i,
+            (len + 1).min(MIN_HASH_LEN),
+            digit || b.is_ascii_digit(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_patterns() {
+        let assets = ImmutableAssets::parse(&["*.[hash].js", "*-[hash].css"]).unwrap();
+        assert!(assets.matches("/static/app.3f9a2c1d.js"));
+        assert!(assets.matches("/main.3f9a2c1d4e5f6a7b.js"));
+        assert!(assets.matches("/assets/index-BxkP3Y7a.css"));
+        assert!(assets.matches("/assets/vendor-react-a1B2c3D4.css"));
+
+        // No hash-like segment
+        assert!(!assets.matches("/app.js"));
+        assert!(!assets.matches("/bootstrap.min.js"));
+        assert!(!assets.matches("/lib.abcdefgh.js")); // no digit
+        assert!(!assets.matches("/app.3f9a2c.js")); // too short
+        assert!(!assets.matches("/app.3f9a2c1d.js.map"));
+    }
+
+    #[test]
+    fn test_directory_patterns() {
+        let assets = ImmutableAssets::parse(&["/assets/*", "/build/*.[hash].*"]).unwrap();
+        assert!(assets.matches("/assets/logo.svg"));
+        assert!(assets.matches("/assets/deep/font.woff2"));
+        assert!(assets.matches("/build/app.12345678.wasm"));
+        assert!(!assets.matches("/build/app.wasm"));
+        assert!(!assets.matches("/other/assets/logo.svg"));
+    }
+
+    #[test]
+    fn test_long_paths_match_in_linear_time() {
+        let assets =
+            ImmutableAssets::parse(&["*.[hash].*.[hash].js", "/a/*/*/*.[hash].css"]).unwrap();
+        let hostile = format!("/a/{}", "0a.".repeat(MAX_PATH_LEN / 3));
+        assert!(!assets.matches(&hostile));
+        assert!(!assets.matches(&format!("/a/{}", "0".repeat(8000))));
+
+        // Over the cap: never an asset, even if it would match
+        let long = format!("/{}.12345678.js", "a".repeat(MAX_PATH_LEN));
+        assert!(!assets.matches(&long));
+        assert!(assets.matches("/x.12345678.y.abcdefg1.js"));
+    }
+
+    #[test]
+    fn test_matches_file_only_for_direct_hits() {
+        let assets = ImmutableAssets::parse(&["*.[hash].js"]).unwrap();
+        let root = "/var/www/html";
+        assert!(assets.matches_file(root, "/var/www/html/app.3f9a2c1d.js", "/app.3f9a2c1d.js"));
+        assert!(assets.matches_file(root, "/var/www/html/app.3f9a2c1d.js", "//%61pp.3f9a2c1d.js"));
+
+        // Missing hashed URL that fell back to INDEX_FILE
+        assert!(!assets.matches_file(root, "/var/www/html/index.html", "/app.3f9a2c1d.js"));
+        // Directory index behind a hash-like directory name
+        assert!(!assets.matches_file(
+            root,
+            "/var/www/html/v.3f9a2c1d.js/index.html",
+            "/v.3f9a2c1d.js/"
+        ));
+        assert!(!ImmutableAssets::default().matches_file(root, "/var/www/html/a.js", "/a.js"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(ImmutableAssets::parse(&[""]).is_err());
+        // Would match every .js file
+        assert!(ImmutableAssets::parse(&["*.js"]).is_err());
+        assert!(ImmutableAssets::default().is_empty());
+    }
+}
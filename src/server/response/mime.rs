@@ -0,0 +1,127 @@
+//! Content-Type overrides for static files.
+//!
+//! `mime_guess` is consulted only for extensions not listed here. Operators
+//! extend or replace entries with `MIME_TYPES=ext=type,...`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use hyper::header::HeaderValue;
+
+/// Built-in overrides for types `mime_guess` gets wrong or too generic.
+const DEFAULT_MIME_TYPES: [(&str, &str); 3] = [
+    ("mjs", "text/javascript"),
+    ("wasm", "application/wasm"),
+    ("webmanifest", "application/manifest+json"),
+];
+
+/// Extension -> Content-Type map (extensions stored lowercase, without dot).
+#[derive(Clone, Debug)]
+pub struct MimeTypes {
+    map: HashMap<Box<str>, Box<str>>,
+}
+
+impl Default for MimeTypes {
+    fn default() -> Self {
+        Self {
+            map: DEFAULT_MIME_TYPES
+                .iter()
+                .map(|&(ext, mime)| (ext.into(), mime.into()))
+                .collect(),
+        }
+    }
+}
+
+impl MimeTypes {
+    /// Defaults plus `ext=type` entries (later entries win).
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> Result<Self, String> {
+        let mut types = Self::default();
+        for entry in entries {
+            let entry = entry.as_ref();
+            let (ext, mime) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected ext=type, got '{entry}'"))?;
+            let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+            let mime = mime.trim();
+            if ext.is_empty() || ext.contains('/') {
+                return Err(format!("invalid extension in '{entry}'"));
+            }
+            if !mime.contains('/') || HeaderValue::from_str(mime).is_err() {
+                return Err(format!("invalid content type in '{entry}'"));
+            }
+            types.map.insert(ext.into(), mime.into());
+        }
+        Ok(types)
+    }
+
+    /// Override for the file's extension (case-insensitive), if any.
+    pub fn lookup(&self, path: &Path) -> Option<&str> {
+        let ext = path.extension()?.to_str()?;
+        match self.map.get(ext) {
+            Some(mime) => Some(mime),
+            None => self
+                .map
+                .get(ext.to_ascii_lowercase().as_str())
+                .map(|m| m.as_ref()),
+        }
+    }
+
+    /// Content-Type for a file: override first, then `mime_guess`.
+    pub fn content_type(&self, path: &Path) -> String {
+        match self.lookup(path) {
+            Some(mime) => mime.to_string(),
+            None => mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let types = MimeTypes::default();
+        assert_eq!(
+            types.content_type(Path::new("/app/main.mjs")),
+            "text/javascript"
+        );
+        assert_eq!(
+            types.content_type(Path::new("/app/lib.WASM")),
+            "application/wasm"
+        );
+        assert_eq!(types.content_type(Path::new("/styles.css")), "text/css");
+        assert_eq!(
+            types.content_type(Path::new("/no-extension")),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn test_parse_overrides() {
+        let types =
+            MimeTypes::parse(&[".Map=application/json", "mjs=application/javascript"]).unwrap();
+        assert_eq!(
+            types.lookup(Path::new("/app.js.map")),
+            Some("application/json")
+        );
+        assert_eq!(
+            types.lookup(Path::new("/a.mjs")),
+            Some("application/javascript")
+        );
+        // Defaults are kept
+        assert_eq!(types.lookup(Path::new("/a.wasm")), Some("application/wasm"));
+        assert_eq!(types.lookup(Path::new("/a.css")), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(MimeTypes::parse(&["wasm"]).is_err());
+        assert!(MimeTypes::parse(&["=text/plain"]).is_err());
+        assert!(MimeTypes::parse(&["txt=plain"]).is_err());
+        assert!(MimeTypes::parse(&["txt=text/plain\n"]).is_ok()); // trimmed
+        assert!(MimeTypes::parse(&["txt=text/\u{7f}plain"]).is_err());
+    }
+}
@@ -1,20 +1,31 @@
 //! HTTP response building and utilities.
 
+pub mod compressed_cache;
 pub mod compression;
+pub mod immutable;
+pub mod mime;
 pub mod static_file;
 pub mod streaming;
+pub mod variant;
+
+use std::sync::Arc;
 
 use bytes::Bytes;
 use http_body_util::{Either, Full};
+use hyper::header::HeaderValue;
 use hyper::{Response, StatusCode};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
 
+use crate::config::{BrotliSettings, Config};
 use crate::types::ScriptResponse;
-use compression::{
-    compress_brotli, should_compress_mime, MAX_COMPRESSION_SIZE, MIN_COMPRESSION_SIZE,
-};
-
-pub use compression::{accepts_brotli, STREAM_THRESHOLD_NON_COMPRESSIBLE};
-pub use static_file::serve_static_file;
+use compression::{compress_brotli, should_compress_mime, MAX_COMPRESSION_SIZE};
+
+pub use compressed_cache::{CompressedCache, DEFAULT_COMPRESSED_CACHE_SIZE};
+pub use compression::MIN_COMPRESSION_SIZE;
+pub use compression::{accepts_brotli, CompressibleTypes, STREAM_THRESHOLD_NON_COMPRESSIBLE};
+pub use immutable::ImmutableAssets;
+pub use mime::MimeTypes;
+pub use static_file::{serve_directory_listing, serve_static_file};
 pub use streaming::{
     // File streaming exports
     file_streaming_response,
@@ -31,6 +42,7 @@ pub use streaming::{
     StreamingResponse,
     DEFAULT_STREAM_BUFFER_SIZE,
 };
+pub use variant::UaVariants;
 
 /// Inner Either type for streaming bodies (SSE/chunked or file).
 type StreamOrFileBody = Either<StreamingBody, FileBody>;
@@ -69,18 +81,99 @@ pub static EMPTY_BODY: Bytes = Bytes::from_static(b"");
 pub static METHOD_NOT_ALLOWED_BODY: Bytes = Bytes::from_static(b"Method Not Allowed");
 pub static BAD_REQUEST_BODY: Bytes = Bytes::from_static(b"Failed to read request body");
 
-const DEFAULT_CONTENT_TYPE: &str = "text/html; charset=utf-8";
+/// Settings used to build responses.
+///
+/// Part of the live config snapshot, so a SIGHUP applies them to new
+/// requests while in-flight ones keep what they started with.
+#[derive(Clone, Debug)]
+pub struct ResponseSettings {
+    /// Encoder settings (BROTLI_QUALITY, BROTLI_WINDOW, ...).
+    pub brotli: BrotliSettings,
+    /// Types worth compressing (COMPRESS_TYPES).
+    pub compress_types: Arc<CompressibleTypes>,
+    /// Content-Type for PHP responses that don't set one
+    /// (DEFAULT_CONTENT_TYPE, DEFAULT_CHARSET).
+    pub default_content_type: HeaderValue,
+}
+
+impl Default for ResponseSettings {
+    fn default() -> Self {
+        Self {
+            brotli: BrotliSettings::DEFAULT,
+            compress_types: Arc::new(CompressibleTypes::default()),
+            default_content_type: HeaderValue::from_static("text/html; charset=utf-8"),
+        }
+    }
+}
+
+impl ResponseSettings {
+    /// Build from BROTLI_*, COMPRESS_TYPES and DEFAULT_CONTENT_TYPE/DEFAULT_CHARSET.
+    pub fn from_config(config: &Config) -> Self {
+        let default_content_type =
+            HeaderValue::try_from(config.server.default_content_type.header_value())
+                .unwrap_or_else(|_| Self::default().default_content_type);
+        Self {
+            brotli: config.server.brotli,
+            compress_types: Arc::new(config.server.compress_types.clone()),
+            default_content_type,
+        }
+    }
+}
+
+/// `Server` header value unless SERVER_HEADER overrides it. The header is
+/// added once per response by the connection handler, not by the builders here.
+pub const DEFAULT_SERVER_HEADER: &str = concat!("tokio_php/", env!("CARGO_PKG_VERSION"));
+
+/// Fallback when a response can't be built (invalid header name or value).
+///
+/// Logs the cause and answers 500 with an empty body, so the error page
+/// logic applies and the connection task never panics.
+#[cold]
+fn build_error_response(err: http::Error) -> Response<Full<Bytes>> {
+    tracing::error!("Failed to build response: {}", err);
+    internal_error_response()
+}
+
+/// 500 response built without the fallible builder.
+fn internal_error_response() -> Response<Full<Bytes>> {
+    let mut resp = Response::new(Full::new(EMPTY_BODY.clone()));
+    *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    resp
+}
+
+/// Whether a response with this status may carry `Content-Length`
+/// (RFC 9110: never on 204, and on 304 it would describe the cached body).
+#[inline]
+fn has_content_length(status: StatusCode) -> bool {
+    status != StatusCode::NO_CONTENT && status != StatusCode::NOT_MODIFIED
+}
+
+/// Answer to a HEAD request: `response` without its body.
+///
+/// A buffered body's length stays in `Content-Length`, so the headers are
+/// those of the GET. Streamed bodies have no known length and stay without.
+pub fn head_response(response: FlexibleResponse) -> FlexibleResponse {
+    let (mut parts, body) = response.into_parts();
+    if let Some(len) = hyper::body::Body::size_hint(&body).exact() {
+        if has_content_length(parts.status) {
+            parts
+                .headers
+                .entry(hyper::header::CONTENT_LENGTH)
+                .or_insert_with(|| len.into());
+        }
+    }
+    full_to_flexible(Response::from_parts(parts, Full::new(EMPTY_BODY.clone())))
+}
 
 /// Build a pre-built empty response for stub mode.
 #[inline]
-pub fn empty_stub_response() -> Response<Full<Bytes>> {
+pub fn empty_stub_response(content_type: &HeaderValue) -> Response<Full<Bytes>> {
     Response::builder()
         .status(StatusCode::OK)
-        .header("Content-Type", DEFAULT_CONTENT_TYPE)
-        .header("Server", "tokio_php/0.1.0")
+        .header("Content-Type", content_type)
         .header("Content-Length", "0")
         .body(Full::new(EMPTY_BODY.clone()))
-        .unwrap()
+        .unwrap_or_else(build_error_response)
 }
 
 /// Build stub response with profiling headers.
@@ -91,11 +184,11 @@ pub fn stub_response_with_profile(
     tls_handshake_us: u64,
     tls_protocol: &str,
     tls_alpn: &str,
+    content_type: &HeaderValue,
 ) -> Response<Full<Bytes>> {
     let mut builder = Response::builder()
         .status(StatusCode::OK)
-        .header("Content-Type", DEFAULT_CONTENT_TYPE)
-        .header("Server", "tokio_php/0.1.0")
+        .header("Content-Type", content_type)
         .header("Content-Length", "0")
         // Profile headers
         .header("X-Profile-Total-Us", total_us.to_string())
@@ -113,7 +206,9 @@ pub fn stub_response_with_profile(
         builder = builder.header("X-Profile-TLS-ALPN", tls_alpn);
     }
 
-    builder.body(Full::new(EMPTY_BODY.clone())).unwrap()
+    builder
+        .body(Full::new(EMPTY_BODY.clone()))
+        .unwrap_or_else(build_error_response)
 }
 
 /// Create a Not Found response with empty body (for error page injection).
@@ -123,7 +218,75 @@ pub fn not_found_response() -> Response<Full<Bytes>> {
         .status(StatusCode::NOT_FOUND)
         .header("Content-Type", "text/html")
         .body(Full::new(EMPTY_BODY.clone()))
-        .unwrap()
+        .unwrap_or_else(build_error_response)
+}
+
+/// Create a Forbidden response with empty body (for error page injection).
+#[inline]
+pub fn forbidden_response() -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .header("Content-Type", "text/html")
+        .body(Full::new(EMPTY_BODY.clone()))
+        .unwrap_or_else(build_error_response)
+}
+
+/// 500 for a failed script. With `detail` (EXPOSE_ERRORS) the escaped error
+/// is shown; otherwise the body is empty and the error page logic applies.
+pub fn script_error_response(detail: Option<&str>) -> Response<Full<Bytes>> {
+    let Some(detail) = detail else {
+        return internal_error_response();
+    };
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(Full::new(Bytes::from(format!(
+            "<h1>500 Internal Server Error</h1><pre>{}</pre>",
+            static_file::html_escape(detail)
+        ))))
+        .unwrap_or_else(build_error_response)
+}
+
+/// Create a 503 with `Retry-After: 1` and empty body, for requests turned
+/// away while starting, draining or under memory pressure.
+#[inline]
+pub fn unavailable_response() -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header("Retry-After", "1")
+        .body(Full::new(EMPTY_BODY.clone()))
+        .unwrap_or_else(build_error_response)
+}
+
+/// Characters escaped when a decoded path goes back into a `Location` header.
+const PATH_ESCAPE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Create a 301 redirect to `uri` with "/" appended to its path (query kept).
+///
+/// The path is normalized first and always starts with a single "/", so a
+/// request like `//evil.com/../dir` can't become a protocol-relative redirect.
+pub fn trailing_slash_redirect(uri: &hyper::Uri) -> Response<Full<Bytes>> {
+    let normalized = super::routing::match_path(uri.path());
+    let path = utf8_percent_encode(normalized.trim_start_matches('/'), PATH_ESCAPE);
+    let location = match uri.query() {
+        Some(query) => format!("/{}/?{}", path, query),
+        None => format!("/{}/", path),
+    };
+    Response::builder()
+        .status(StatusCode::MOVED_PERMANENTLY)
+        .header("Location", location)
+        .body(Full::new(EMPTY_BODY.clone()))
+        .unwrap_or_else(build_error_response)
 }
 
 /// Create a response from a PHP script execution result.
@@ -132,6 +295,7 @@ pub fn from_script_response(
     mut script_response: ScriptResponse,
     profiling: bool,
     use_brotli: bool,
+    settings: &ResponseSettings,
 ) -> Response<Full<Bytes>> {
     use std::time::Instant;
 
@@ -139,26 +303,39 @@ pub fn from_script_response(
     if script_response.headers.is_empty() && !profiling && !use_brotli {
         return Response::builder()
             .status(StatusCode::OK)
-            .header("Content-Type", DEFAULT_CONTENT_TYPE)
-            .header("Server", "tokio_php/0.1.0")
+            .header("Content-Type", &settings.default_content_type)
+            .header("Content-Length", script_response.body.len())
             .body(Full::new(if script_response.body.is_empty() {
                 EMPTY_BODY.clone()
             } else {
                 Bytes::from(script_response.body)
             }))
-            .unwrap();
+            .unwrap_or_else(build_error_response);
     }
 
     let response_build_start = Instant::now();
 
     // Full header processing
     let mut status = StatusCode::OK;
-    let mut actual_content_type = DEFAULT_CONTENT_TYPE.to_string();
+    let mut actual_content_type = settings
+        .default_content_type
+        .to_str()
+        .unwrap_or_default()
+        .to_string();
     let mut custom_headers: Vec<(&str, String)> = Vec::with_capacity(script_response.headers.len());
 
     for (name, value) in &script_response.headers {
         let name_lower = name.to_lowercase();
 
+        // CR/LF, NUL etc. can't be sent; drop the header and fail the response
+        if hyper::header::HeaderValue::from_str(value).is_err() {
+            tracing::error!(
+                "Dropping {} header with invalid value from PHP response",
+                name
+            );
+            return internal_error_response();
+        }
+
         if name_lower.starts_with("http/") {
             if let Some(code_str) = value.split_whitespace().next() {
                 if let Ok(code) = code_str.parse::<u16>() {
@@ -183,6 +360,8 @@ pub fn from_script_response(
                 }
                 custom_headers.push(("Location", value.clone()));
             }
+            // Set below from the body actually sent, which may be compressed
+            "content-length" => {}
             "status" => {
                 if let Some(code_str) = value.split_whitespace().next() {
                     if let Ok(code) = code_str.parse::<u16>() {
@@ -201,17 +380,19 @@ pub fn from_script_response(
             }
         }
     }
+    drop_repeated_singletons(&mut custom_headers);
 
     // Determine body and compression
     let body_bytes = script_response.body;
     let original_size = body_bytes.len();
+    let brotli = &settings.brotli;
     let should_compress = use_brotli
-        && (MIN_COMPRESSION_SIZE..=MAX_COMPRESSION_SIZE).contains(&original_size)
-        && should_compress_mime(&actual_content_type);
+        && (brotli.min_size..=MAX_COMPRESSION_SIZE).contains(&original_size)
+        && should_compress_mime(&actual_content_type, &settings.compress_types);
 
     let compression_start = Instant::now();
     let (final_body, is_compressed) = if should_compress {
-        match compress_brotli(body_bytes.as_bytes()) {
+        match compress_brotli(body_bytes.as_bytes(), brotli.quality, brotli.window) {
             Some(compressed) => (Bytes::from(compressed), true),
             None => (Bytes::from(body_bytes), false),
         }
@@ -231,9 +412,7 @@ pub fn from_script_response(
         0.0
     };
 
-    let mut builder = Response::builder()
-        .status(status)
-        .header("Server", "tokio_php/0.1.0");
+    let mut builder = Response::builder().status(status);
 
     // Add Content-Encoding if compressed
     if is_compressed {
@@ -244,13 +423,18 @@ pub fn from_script_response(
     // Check if content-type was set
     let has_content_type = custom_headers.iter().any(|(n, _)| *n == "Content-Type");
     if !has_content_type {
-        builder = builder.header("Content-Type", DEFAULT_CONTENT_TYPE);
+        builder = builder.header("Content-Type", &settings.default_content_type);
     }
 
     for (name, value) in custom_headers {
         builder = builder.header(name, value);
     }
 
+    // Explicit length, so strict intermediaries never see a chunked reply
+    if has_content_length(status) {
+        builder = builder.header("Content-Length", final_body.len());
+    }
+
     // Update profile data if profiling is enabled
     if profiling {
         if let Some(ref mut profile) = script_response.profile {
@@ -267,7 +451,52 @@ pub fn from_script_response(
         }
     }
 
-    builder.body(Full::new(final_body)).unwrap()
+    builder
+        .body(Full::new(final_body))
+        .unwrap_or_else(build_error_response)
+}
+
+/// Fields that must not appear more than once in a response (RFC 9110).
+const SINGLETON_HEADERS: &[&str] = &[
+    "access-control-allow-origin",
+    "age",
+    "content-disposition",
+    "content-location",
+    "content-range",
+    "content-type",
+    "date",
+    "etag",
+    "expires",
+    "last-modified",
+    "location",
+    "retry-after",
+    "server",
+    "strict-transport-security",
+];
+
+/// Keep only the last value of singleton fields repeated by PHP
+/// (`header('...', false)`).
+///
+/// Every other repeated field stays one line per value, which HTTP treats as
+/// equal to a comma-joined list. Values are never joined here: `Set-Cookie`
+/// can't be (its `Expires` dates contain commas), so each cookie remains a
+/// line of its own, and a separate field under HTTP/2.
+pub(crate) fn drop_repeated_singletons<N: AsRef<str>>(headers: &mut Vec<(N, String)>) {
+    let keep: Vec<bool> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| {
+            let name = name.as_ref();
+            !SINGLETON_HEADERS
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(name))
+                || !headers[i + 1..]
+                    .iter()
+                    .any(|(later, _)| later.as_ref().eq_ignore_ascii_case(name))
+        })
+        .collect();
+    let mut keep = keep.into_iter();
+    headers.retain(|_| keep.next().unwrap_or(true));
 }
 
 /// Check if a header name is valid per HTTP spec.
@@ -279,3 +508,253 @@ fn is_valid_header_name(name: &str) -> bool {
                     b'0'..=b'9' | b'A'..=b'Z' | b'^' | b'_' | b'`' | b'a'..=b'z' | b'|' | b'~')
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::Body;
+
+    fn script_response(headers: &[(&str, &str)]) -> ScriptResponse {
+        ScriptResponse {
+            body: "<p>hello</p>".into(),
+            headers: headers
+                .iter()
+                .map(|&(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            profile: None,
+        }
+    }
+
+    #[test]
+    fn test_invalid_header_value_gives_500() {
+        for (name, value) in [
+            ("X-Custom", "a\r\nInjected: 1"),
+            ("Location", "/next\0"),
+            ("Content-Type", "text/html\n"),
+        ] {
+            let resp = from_script_response(
+                script_response(&[(name, value)]),
+                false,
+                false,
+                &ResponseSettings::default(),
+            );
+            assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR, "{name}");
+            assert!(!resp.headers().contains_key(name));
+            assert_eq!(resp.body().size_hint().exact(), Some(0));
+        }
+    }
+
+    #[test]
+    fn test_header_injection_rejected() {
+        for (name, value) in [
+            ("X-Name", "guest\r\nSet-Cookie: admin=1"),
+            ("Set-Cookie", "theme=dark\r\nSet-Cookie: admin=1"),
+            ("Location", "/home\r\n\r\n<script>alert(1)</script>"),
+            ("X-Name", "guest\nSet-Cookie: admin=1"),
+        ] {
+            let resp = from_script_response(
+                script_response(&[(name, value)]),
+                false,
+                false,
+                &ResponseSettings::default(),
+            );
+            assert_eq!(
+                resp.status(),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "{value:?}"
+            );
+            assert!(!resp.headers().contains_key("set-cookie"), "{value:?}");
+        }
+
+        // Tabs are allowed in field values
+        let resp = from_script_response(
+            script_response(&[("X-Name", "a\tb")]),
+            false,
+            false,
+            &ResponseSettings::default(),
+        );
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["x-name"], "a\tb");
+    }
+
+    #[tokio::test]
+    async fn test_streaming_drops_injected_headers() {
+        let (_tx, rx) = stream_channel(1);
+        let headers = [
+            ("X-Ok", "1"),
+            ("X-Name", "guest\r\nSet-Cookie: admin=1"),
+            ("Bad Name", "v"),
+            ("Set-Cookie", "a=1"),
+            ("Set-Cookie", "b=2"),
+        ]
+        .iter()
+        .map(|&(n, v)| (n.to_string(), v.to_string()))
+        .collect();
+
+        let resp = streaming_response(200, headers, rx);
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["x-ok"], "1");
+        assert!(!resp.headers().contains_key("x-name"));
+        assert_eq!(resp.headers().get_all("set-cookie").iter().count(), 2);
+
+        let (_tx, rx) = stream_channel(1);
+        let resp = streaming_response(1000, Vec::new(), rx);
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_set_cookie_lines_kept_apart() {
+        let resp = from_script_response(
+            script_response(&[
+                (
+                    "Set-Cookie",
+                    "a=1; expires=Thu, 01 Jan 2099 00:00:00 GMT; path=/",
+                ),
+                ("Set-Cookie", "b=2; HttpOnly"),
+                ("Set-Cookie", "c=3; Secure"),
+                ("X-Multi", "one"),
+                ("X-Multi", "two"),
+                ("Content-Type", "text/plain"),
+                ("content-type", "application/json"),
+            ]),
+            false,
+            false,
+            &ResponseSettings::default(),
+        );
+        assert_eq!(resp.status(), StatusCode::OK);
+        let cookies: Vec<_> = resp.headers().get_all("set-cookie").iter().collect();
+        assert_eq!(
+            cookies,
+            [
+                "a=1; expires=Thu, 01 Jan 2099 00:00:00 GMT; path=/",
+                "b=2; HttpOnly",
+                "c=3; Secure"
+            ]
+        );
+        assert_eq!(resp.headers().get_all("x-multi").iter().count(), 2);
+        // Singleton: the last value wins
+        let types: Vec<_> = resp.headers().get_all("content-type").iter().collect();
+        assert_eq!(types, ["application/json"]);
+    }
+
+    #[test]
+    fn test_valid_headers_pass_through() {
+        let resp = from_script_response(
+            script_response(&[
+                ("X-Custom", "caf\u{e9}"),
+                ("Content-Type", "text/plain"),
+                ("Status", "201 Created"),
+            ]),
+            false,
+            false,
+            &ResponseSettings::default(),
+        );
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(resp.headers()["content-type"], "text/plain");
+        assert_eq!(
+            resp.headers()["x-custom"].as_bytes(),
+            "caf\u{e9}".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_content_length() {
+        let resp = from_script_response(
+            script_response(&[]),
+            false,
+            false,
+            &ResponseSettings::default(),
+        );
+        assert_eq!(resp.headers()["content-length"], "12");
+
+        // PHP's own value is replaced by the length actually sent
+        let resp = from_script_response(
+            script_response(&[("Content-Length", "999")]),
+            false,
+            false,
+            &ResponseSettings::default(),
+        );
+        assert_eq!(resp.headers().get_all("content-length").iter().count(), 1);
+        assert_eq!(resp.headers()["content-length"], "12");
+
+        let resp = from_script_response(
+            script_response(&[("Status", "204")]),
+            false,
+            false,
+            &ResponseSettings::default(),
+        );
+        assert!(!resp.headers().contains_key("content-length"));
+    }
+
+    #[test]
+    fn test_content_length_after_compression() {
+        let mut response = script_response(&[("Content-Type", "text/html")]);
+        response.body = "<p>hello</p>".repeat(200);
+        let resp = from_script_response(response, false, true, &ResponseSettings::default());
+        assert_eq!(resp.headers()["content-encoding"], "br");
+        let len = resp.body().size_hint().exact().unwrap();
+        assert!(len < 2400);
+        assert_eq!(resp.headers()["content-length"], len.to_string().as_str());
+    }
+
+    #[test]
+    fn test_head_response_keeps_length() {
+        let get = full_to_flexible(from_script_response(
+            script_response(&[]),
+            false,
+            true,
+            &ResponseSettings::default(),
+        ));
+        let head = head_response(get);
+        assert_eq!(head.headers()["content-length"], "12");
+        assert_eq!(head.body().size_hint().exact(), Some(0));
+
+        // Length taken from the body when the GET response had no header
+        let get = full_to_flexible(Response::new(Full::new(Bytes::from_static(b"abc"))));
+        assert_eq!(head_response(get).headers()["content-length"], "3");
+    }
+
+    #[test]
+    fn test_trailing_slash_redirect_stays_on_host() {
+        let location = |uri: &'static str| {
+            let resp = trailing_slash_redirect(&hyper::Uri::from_static(uri));
+            assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY);
+            resp.headers()["Location"].to_str().unwrap().to_string()
+        };
+        assert_eq!(location("/docs"), "/docs/");
+        assert_eq!(location("/docs?page=2"), "/docs/?page=2");
+        assert_eq!(location("//evil.com/../site"), "/site/");
+        assert_eq!(location("//evil.com"), "/evil.com/");
+        assert_eq!(location("/%2Fevil.com"), "/evil.com/");
+        assert_eq!(location("/my%20docs"), "/my%20docs/");
+    }
+
+    #[test]
+    fn test_build_error_fallback() {
+        let resp = Response::builder()
+            .header("X-Bad", "a\nb")
+            .body(Full::new(EMPTY_BODY.clone()))
+            .unwrap_or_else(build_error_response);
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!resp.headers().contains_key("x-bad"));
+    }
+
+    #[tokio::test]
+    async fn test_script_error_response() {
+        use http_body_util::BodyExt;
+
+        // Default: nothing about the error reaches the client
+        let resp = script_error_response(None);
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(resp.body().size_hint().exact(), Some(0));
+
+        // EXPOSE_ERRORS: detail shown, escaped
+        let resp = script_error_response(Some("Parse error in <b>/var/www/x.php</b>"));
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            body,
+            "<h1>500 Internal Server Error</h1><pre>Parse error in &lt;b&gt;/var/www/x.php&lt;/b&gt;</pre>"
+        );
+    }
+}
@@ -1,153 +1,27 @@
 //! Static file serving with HTTP caching support and large file streaming.
+//!
+//! Also renders directory listings for AUTOINDEX prefixes.
 
+use std::borrow::Cow;
 use std::path::Path;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use bytes::Bytes;
 use http_body_util::{Either, Full};
 use hyper::{Response, StatusCode};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 
-use super::compression::{
-    compress_brotli, should_compress_mime, MAX_COMPRESSION_SIZE, MIN_COMPRESSION_SIZE,
-};
+use super::compressed_cache::CompressedCache;
+use super::compression::{compress_brotli, should_compress_mime, MAX_COMPRESSION_SIZE};
+use super::mime::MimeTypes;
 use super::streaming::{file_streaming_response, open_file_stream, should_stream_file, FileBody};
-use super::EMPTY_BODY;
+use super::{build_error_response, ResponseSettings, EMPTY_BODY};
+use crate::core::{format_http_date, parse_http_date};
 use crate::server::config::StaticCacheTtl;
 
 /// Response body type: either in-memory or file streaming.
 type StaticFileBody = Either<Full<Bytes>, Either<super::StreamingBody, FileBody>>;
 
-/// Format SystemTime as HTTP-date (RFC 7231).
-/// Example: "Sun, 06 Nov 1994 08:49:37 GMT"
-fn format_http_date(time: SystemTime) -> String {
-    let secs = time
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-
-    // Calculate date/time components
-    let days = secs / 86400;
-    let day_secs = secs % 86400;
-
-    let hours = day_secs / 3600;
-    let minutes = (day_secs % 3600) / 60;
-    let seconds = day_secs % 60;
-
-    // Calculate year/month/day from days since epoch
-    let mut y = 1970i64;
-    let mut remaining_days = days as i64;
-
-    loop {
-        let year_days = if y % 4 == 0 && (y % 100 != 0 || y % 400 == 0) {
-            366
-        } else {
-            365
-        };
-        if remaining_days < year_days {
-            break;
-        }
-        remaining_days -= year_days;
-        y += 1;
-    }
-
-    let is_leap = y % 4 == 0 && (y % 100 != 0 || y % 400 == 0);
-    let month_days: [i64; 12] = if is_leap {
-        [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
-    } else {
-        [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
-    };
-
-    let mut m = 1;
-    for (i, &days_in_month) in month_days.iter().enumerate() {
-        if remaining_days < days_in_month {
-            m = i + 1;
-            break;
-        }
-        remaining_days -= days_in_month;
-    }
-    let d = remaining_days + 1;
-
-    // Calculate day of week (0 = Thursday for Unix epoch)
-    // (days + 4) % 7 gives: 0=Sun, 1=Mon, ...
-    let dow = ((days + 4) % 7) as usize;
-    let day_names = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
-    let month_names = [
-        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
-    ];
-
-    format!(
-        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
-        day_names[dow],
-        d,
-        month_names[m - 1],
-        y,
-        hours,
-        minutes,
-        seconds
-    )
-}
-
-/// Parse HTTP-date (RFC 7231) to SystemTime.
-/// Supports format: "Sun, 06 Nov 1994 08:49:37 GMT"
-fn parse_http_date(s: &str) -> Option<SystemTime> {
-    // Format: "Day, DD Mon YYYY HH:MM:SS GMT"
-    let parts: Vec<&str> = s.split_whitespace().collect();
-    if parts.len() != 6 || parts[5] != "GMT" {
-        return None;
-    }
-
-    let day: u64 = parts[1].parse().ok()?;
-    let month = match parts[2] {
-        "Jan" => 1,
-        "Feb" => 2,
-        "Mar" => 3,
-        "Apr" => 4,
-        "May" => 5,
-        "Jun" => 6,
-        "Jul" => 7,
-        "Aug" => 8,
-        "Sep" => 9,
-        "Oct" => 10,
-        "Nov" => 11,
-        "Dec" => 12,
-        _ => return None,
-    };
-    let year: i64 = parts[3].parse().ok()?;
-
-    let time_parts: Vec<&str> = parts[4].split(':').collect();
-    if time_parts.len() != 3 {
-        return None;
-    }
-    let hours: u64 = time_parts[0].parse().ok()?;
-    let minutes: u64 = time_parts[1].parse().ok()?;
-    let seconds: u64 = time_parts[2].parse().ok()?;
-
-    // Calculate days since epoch
-    let mut total_days: i64 = 0;
-    for y in 1970..year {
-        total_days += if y % 4 == 0 && (y % 100 != 0 || y % 400 == 0) {
-            366
-        } else {
-            365
-        };
-    }
-
-    let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
-    let month_days: [i64; 12] = if is_leap {
-        [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
-    } else {
-        [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
-    };
-
-    for m in 0..(month - 1) {
-        total_days += month_days[m as usize];
-    }
-    total_days += day as i64 - 1;
-
-    let total_secs = total_days as u64 * 86400 + hours * 3600 + minutes * 60 + seconds;
-    Some(UNIX_EPOCH + Duration::from_secs(total_secs))
-}
-
 /// Generate ETag from file size and modification time.
 /// Format: "size-mtime_hex"
 fn generate_etag(size: u64, mtime: SystemTime) -> String {
@@ -204,22 +78,69 @@ fn is_cache_valid(
     false
 }
 
+/// Longest server-generated header value sent with a static response.
+///
+/// Well below common proxy/client limits (8 KB for all headers), so one odd
+/// value can't make the whole response unreadable.
+const MAX_HEADER_VALUE_LEN: usize = 1024;
+
+/// Make a computed header value safe for `Response::builder().header()`.
+///
+/// Drops bytes that are invalid in a header value (controls, DEL) and
+/// truncates overlong values, logging either case.
+fn bounded_header_value<'a>(name: &str, value: &'a str) -> Cow<'a, str> {
+    let is_valid = |c: char| c == '\t' || (c >= ' ' && c != '\u{7f}');
+    let mut value: Cow<'a, str> = if value.chars().all(is_valid) {
+        Cow::Borrowed(value)
+    } else {
+        tracing::warn!("Dropped invalid characters from {} header", name);
+        Cow::Owned(value.chars().filter(|&c| is_valid(c)).collect())
+    };
+
+    if value.len() > MAX_HEADER_VALUE_LEN {
+        tracing::warn!(
+            "Truncated {} header from {} to {} bytes",
+            name,
+            value.len(),
+            MAX_HEADER_VALUE_LEN
+        );
+        let mut end = MAX_HEADER_VALUE_LEN;
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        value = Cow::Owned(value[..end].trim_end().to_string());
+    }
+    value
+}
+
+/// Cache lifetime for fingerprinted assets (one year).
+const IMMUTABLE_CACHE_TTL: StaticCacheTtl = StaticCacheTtl::from_secs(31_536_000);
+
+/// Cache-Control value for a cacheable static response.
+fn cache_control_value(cache_ttl: &StaticCacheTtl, immutable: bool) -> String {
+    if immutable {
+        format!("public, max-age={}, immutable", cache_ttl.as_secs())
+    } else {
+        format!("public, max-age={}", cache_ttl.as_secs())
+    }
+}
+
 /// Helper to create 304 Not Modified response.
 fn not_modified_response(
     etag: &str,
     last_modified: &str,
     cache_ttl: &StaticCacheTtl,
+    cache_control: &str,
 ) -> Response<StaticFileBody> {
     let ttl_secs = cache_ttl.as_secs();
     let expires_time = SystemTime::now() + std::time::Duration::from_secs(ttl_secs);
 
     Response::builder()
         .status(StatusCode::NOT_MODIFIED)
-        .header("Cache-Control", format!("public, max-age={}", ttl_secs))
+        .header("Cache-Control", cache_control)
         .header("Expires", format_http_date(expires_time))
         .header("ETag", etag)
         .header("Last-Modified", last_modified)
-        .header("Server", "tokio_php/0.1.0")
         .body(Either::Left(Full::new(EMPTY_BODY.clone())))
         .unwrap()
 }
@@ -239,12 +160,22 @@ fn not_found_response() -> Response<StaticFileBody> {
 /// - Compressible files > 3MB → streaming (compression would be too slow)
 /// - Non-compressible files > 1MB → streaming (no benefit from in-memory)
 ///
-/// Smaller files are served from memory with optional Brotli compression.
+/// Smaller files are served from memory with optional Brotli compression;
+/// compressed bodies are reused from `compressed_cache` until the file changes.
 /// Supports conditional requests (If-None-Match, If-Modified-Since).
+/// Content-Type comes from `mime_types` overrides, then `mime_guess`.
+///
+/// `immutable` marks a fingerprinted asset (STATIC_IMMUTABLE): it is cached
+/// for a year with `immutable`, unless caching is off or the file is HTML.
+#[allow(clippy::too_many_arguments)]
 pub async fn serve_static_file(
     file_path: &Path,
+    mime_types: &MimeTypes,
     use_brotli: bool,
+    settings: &ResponseSettings,
+    compressed_cache: &CompressedCache,
     cache_ttl: &StaticCacheTtl,
+    immutable: bool,
     if_none_match: Option<&str>,
     if_modified_since: Option<&str>,
 ) -> Response<StaticFileBody> {
@@ -262,29 +193,38 @@ pub async fn serve_static_file(
     let etag = generate_etag(size, mtime);
     let last_modified = format_http_date(mtime);
 
-    // Check conditional request headers
-    if cache_ttl.is_enabled() && is_cache_valid(if_none_match, if_modified_since, &etag, mtime) {
-        return not_modified_response(&etag, &last_modified, cache_ttl);
-    }
+    let mime = mime_types.content_type(file_path);
+    let mime = bounded_header_value("Content-Type", &mime).into_owned();
 
-    let mime = mime_guess::from_path(file_path)
-        .first_or_octet_stream()
-        .to_string();
-
-    // Check if this MIME type is compressible
-    let is_compressible = should_compress_mime(&mime);
+    // HTML is never immutable: a cached page would pin stale asset URLs
+    let immutable = immutable && cache_ttl.is_enabled() && !mime.starts_with("text/html");
+    let cache_ttl = if immutable {
+        &IMMUTABLE_CACHE_TTL
+    } else {
+        cache_ttl
+    };
 
     // Build cache control header if caching enabled
     let cache_control = if cache_ttl.is_enabled() {
-        Some(format!("public, max-age={}", cache_ttl.as_secs()))
+        Some(cache_control_value(cache_ttl, immutable))
     } else {
         None
     };
 
-    // Streaming decision based on file size and compressibility:
-    // - Compressible files > 3MB → streaming (compression would be too slow)
-    // - Non-compressible files > 1MB → streaming (no benefit from in-memory)
-    if should_stream_file(size, is_compressible) {
+    // Check conditional request headers
+    if let Some(ref cache_control) = cache_control {
+        if is_cache_valid(if_none_match, if_modified_since, &etag, mtime) {
+            return not_modified_response(&etag, &last_modified, cache_ttl, cache_control);
+        }
+    }
+
+    // Check if this MIME type is compressible
+    let is_compressible = should_compress_mime(&mime, &settings.compress_types);
+
+    // Streaming decision based on file size and whether Brotli would apply:
+    // - Compressed responses > 3MB → streaming (compression would be too slow)
+    // - Uncompressed responses > 1MB → streaming (no benefit from in-memory)
+    if should_stream_file(size, use_brotli && is_compressible) {
         return match open_file_stream(file_path).await {
             Some(file) => {
                 let resp = file_streaming_response(
@@ -302,30 +242,47 @@ pub async fn serve_static_file(
         };
     }
 
-    // Small files: read into memory with optional compression
-    match tokio::fs::read(file_path).await {
-        Ok(contents) => {
-            // Compress if: client supports brotli, MIME is compressible,
-            // size is between 256 bytes and 3MB
-            let should_compress = use_brotli
-                && is_compressible
-                && contents.len() >= MIN_COMPRESSION_SIZE
-                && contents.len() <= MAX_COMPRESSION_SIZE;
-
-            let (final_body, is_compressed) = if should_compress {
-                if let Some(compressed) = compress_brotli(&contents) {
-                    (Bytes::from(compressed), true)
-                } else {
-                    (Bytes::from(contents), false)
+    // Compress if: client supports brotli, MIME is compressible,
+    // size is between BROTLI_STATIC_MIN_SIZE and 3MB
+    let brotli = &settings.brotli;
+    let should_compress = use_brotli
+        && is_compressible
+        && (brotli.static_min_size as u64..=MAX_COMPRESSION_SIZE as u64).contains(&size);
+    let (quality, window) = (brotli.static_quality, brotli.window);
+
+    // Small files: compressed copy from the cache, or read into memory
+    let cached = should_compress
+        .then(|| compressed_cache.get(file_path, size, mtime, quality, window))
+        .flatten();
+    let body = match cached {
+        Some(compressed) => Ok((compressed, true)),
+        None => tokio::fs::read(file_path).await.map(|contents| {
+            if !should_compress {
+                return (Bytes::from(contents), false);
+            }
+            match compress_brotli(&contents, quality, window) {
+                Some(compressed) => {
+                    let compressed = Bytes::from(compressed);
+                    compressed_cache.insert(
+                        file_path,
+                        size,
+                        mtime,
+                        quality,
+                        window,
+                        compressed.clone(),
+                    );
+                    (compressed, true)
                 }
-            } else {
-                (Bytes::from(contents), false)
-            };
+                None => (Bytes::from(contents), false),
+            }
+        }),
+    };
 
+    match body {
+        Ok((final_body, is_compressed)) => {
             let mut builder = Response::builder()
                 .status(StatusCode::OK)
-                .header("Content-Type", &mime)
-                .header("Server", "tokio_php/0.1.0");
+                .header("Content-Type", &mime);
 
             if is_compressed {
                 builder = builder
@@ -334,11 +291,11 @@ pub async fn serve_static_file(
             }
 
             // Add caching headers if enabled
-            if cache_ttl.is_enabled() {
+            if let Some(cache_control) = cache_control {
                 let ttl_secs = cache_ttl.as_secs();
 
                 builder = builder
-                    .header("Cache-Control", format!("public, max-age={}", ttl_secs))
+                    .header("Cache-Control", cache_control)
                     .header(
                         "Expires",
                         format_http_date(
@@ -358,27 +315,334 @@ pub async fn serve_static_file(
     }
 }
 
+/// Characters escaped in listing hrefs (RFC 3986 unreserved chars stay as-is).
+const HREF_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Escape text for HTML element content and attribute values.
+pub(crate) fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// One row of a directory listing.
+struct ListingEntry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    mtime: Option<SystemTime>,
+}
+
+/// Read directory entries: directories first, then by name; dotfiles hidden.
+async fn read_listing_entries(dir_path: &Path) -> std::io::Result<Vec<ListingEntry>> {
+    let mut entries = Vec::new();
+    let mut dir = tokio::fs::read_dir(dir_path).await?;
+    while let Some(entry) = dir.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        // Follows symlinks; dangling ones are skipped
+        let Ok(meta) = tokio::fs::metadata(entry.path()).await else {
+            continue;
+        };
+        entries.push(ListingEntry {
+            name,
+            is_dir: meta.is_dir(),
+            size: meta.len(),
+            mtime: meta.modified().ok(),
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+/// Render the listing page. Links are relative to `uri_path`, which ends with "/".
+fn render_listing(uri_path: &str, entries: &[ListingEntry]) -> String {
+    let title = html_escape(&format!(
+        "Index of {}",
+        percent_decode_str(uri_path).decode_utf8_lossy()
+    ));
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n\
+         <body>\n<h1>{title}</h1>\n<table>\n\
+         <tr><th>Name</th><th>Size</th><th>Last modified</th></tr>\n"
+    );
+    if uri_path != "/" {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td>-</td><td></td></tr>\n");
+    }
+    for entry in entries {
+        let slash = if entry.is_dir { "/" } else { "" };
+        let size = if entry.is_dir {
+            "-".to_string()
+        } else {
+            entry.size.to_string()
+        };
+        let mtime = entry.mtime.map(format_http_date).unwrap_or_default();
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}{slash}\">{}{slash}</a></td><td>{size}</td><td>{mtime}</td></tr>\n",
+            utf8_percent_encode(&entry.name, HREF_ESCAPE),
+            html_escape(&entry.name),
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Serve an HTML listing of `dir_path` (AUTOINDEX).
+///
+/// `dir_path` comes from route resolution and is already confined to the
+/// document root; `uri_path` is only used for the title and relative links.
+pub async fn serve_directory_listing(dir_path: &Path, uri_path: &str) -> Response<StaticFileBody> {
+    let entries = match read_listing_entries(dir_path).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::error!("Failed to list directory {:?}: {}", dir_path, e);
+            return not_found_response();
+        }
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .header("Cache-Control", "no-cache")
+        .body(Either::Left(Full::new(Bytes::from(render_listing(
+            uri_path, &entries,
+        )))))
+        .unwrap_or_else(|e| build_error_response(e).map(Either::Left))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use http_body_util::BodyExt;
 
     #[test]
-    fn test_format_http_date() {
-        // Unix epoch
-        let epoch = UNIX_EPOCH;
-        assert_eq!(format_http_date(epoch), "Thu, 01 Jan 1970 00:00:00 GMT");
-
-        // Known date: 2024-01-15 12:40:45 UTC (timestamp 1705322445)
-        let time = UNIX_EPOCH + std::time::Duration::from_secs(1705322445);
-        assert_eq!(format_http_date(time), "Mon, 15 Jan 2024 12:40:45 GMT");
+    fn test_bounded_header_value() {
+        assert!(matches!(
+            bounded_header_value("Content-Type", "text/css"),
+            Cow::Borrowed("text/css")
+        ));
+        assert_eq!(
+            bounded_header_value("Content-Type", "text/plain\r\nX-Injected: 1"),
+            "text/plainX-Injected: 1"
+        );
+
+        let long = format!("text/{}", "é".repeat(MAX_HEADER_VALUE_LEN));
+        let bounded = bounded_header_value("Content-Type", &long);
+        assert!(bounded.len() <= MAX_HEADER_VALUE_LEN);
+        assert!(bounded.starts_with("text/é"));
+    }
+
+    #[tokio::test]
+    async fn test_long_filename_and_content_type() {
+        let dir = tempfile::tempdir().unwrap();
+        // Near NAME_MAX, with an extension mapped to an absurd type
+        let path = dir.path().join(format!("{}.weird", "a".repeat(240)));
+        std::fs::write(&path, "body").unwrap();
+        let long_type = format!("application/{}", "x".repeat(8192));
+        let types = MimeTypes::parse(&[format!("weird={}", long_type)]).unwrap();
+
+        let resp = serve_static_file(
+            &path,
+            &types,
+            false,
+            &ResponseSettings::default(),
+            &CompressedCache::default(),
+            &StaticCacheTtl::from_secs(3600),
+            false,
+            None,
+            None,
+        )
+        .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let content_type = resp.headers()["content-type"].to_str().unwrap();
+        assert_eq!(content_type.len(), MAX_HEADER_VALUE_LEN);
+        assert!(content_type.starts_with("application/xxx"));
+        assert!(resp.headers().contains_key("etag"));
+    }
+
+    async fn cache_control_for(name: &str, immutable: bool, ttl: StaticCacheTtl) -> Option<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, "x").unwrap();
+        let resp = serve_static_file(
+            &path,
+            &MimeTypes::default(),
+            false,
+            &ResponseSettings::default(),
+            &CompressedCache::default(),
+            &ttl,
+            immutable,
+            None,
+            None,
+        )
+        .await;
+        resp.headers()
+            .get("cache-control")
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    async fn is_streamed(size: usize, use_brotli: bool) -> bool {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.js");
+        std::fs::write(&path, vec![b'x'; size]).unwrap();
+        let resp = serve_static_file(
+            &path,
+            &MimeTypes::default(),
+            use_brotli,
+            &ResponseSettings::default(),
+            &CompressedCache::default(),
+            &StaticCacheTtl::from_secs(0),
+            false,
+            None,
+            None,
+        )
+        .await;
+        let streamed = matches!(resp.body(), Either::Right(Either::Right(_)));
+        if streamed {
+            // hyper can't size a stream; the length comes from the metadata
+            assert_eq!(resp.headers()["content-length"], size.to_string().as_str());
+        }
+        streamed
+    }
+
+    #[tokio::test]
+    async fn test_stream_large_uncompressed_files() {
+        let two_mb = 2 * 1024 * 1024;
+        // Compressible, but the client doesn't accept Brotli
+        assert!(is_streamed(two_mb, false).await);
+        assert!(!is_streamed(two_mb, true).await);
+        assert!(!is_streamed(1024, false).await);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_cache_reuse_and_invalidation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.js");
+        let cache = CompressedCache::new(1024 * 1024);
+        let serve = || async {
+            let resp = serve_static_file(
+                &path,
+                &MimeTypes::default(),
+                true,
+                &ResponseSettings::default(),
+                &cache,
+                &StaticCacheTtl::from_secs(0),
+                false,
+                None,
+                None,
+            )
+            .await;
+            assert_eq!(resp.headers()["content-encoding"], "br");
+            resp.into_body().collect().await.unwrap().to_bytes()
+        };
+
+        std::fs::write(&path, "a".repeat(4096)).unwrap();
+        let first = serve().await;
+        assert_eq!(cache.bytes(), first.len() + path.as_os_str().len());
+        assert_eq!(serve().await, first);
+
+        // An edit of the same size is caught by the mtime
+        std::fs::write(&path, "b".repeat(4096)).unwrap();
+        let mtime = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime + std::time::Duration::from_secs(1))
+            .unwrap();
+        let second = serve().await;
+        assert_ne!(second, first);
+        assert_eq!(cache.bytes(), second.len() + path.as_os_str().len());
+    }
+
+    #[tokio::test]
+    async fn test_immutable_cache_control() {
+        let day = StaticCacheTtl::from_secs(86400);
+        assert_eq!(
+            cache_control_for("app.3f9a2c1d.js", true, day)
+                .await
+                .unwrap(),
+            "public, max-age=31536000, immutable"
+        );
+        assert_eq!(
+            cache_control_for("app.js", false, day).await.unwrap(),
+            "public, max-age=86400"
+        );
+        // HTML never gets immutable, even if a pattern matched
+        assert_eq!(
+            cache_control_for("page.3f9a2c1d.html", true, day)
+                .await
+                .unwrap(),
+            "public, max-age=86400"
+        );
+        // STATIC_CACHE_TTL=off wins
+        assert_eq!(
+            cache_control_for("app.3f9a2c1d.js", true, StaticCacheTtl::from_secs(0)).await,
+            None
+        );
     }
 
     #[test]
-    fn test_parse_http_date() {
-        let date = "Mon, 15 Jan 2024 12:40:45 GMT";
-        let parsed = parse_http_date(date).unwrap();
-        let expected = UNIX_EPOCH + std::time::Duration::from_secs(1705322445);
-        assert_eq!(parsed, expected);
+    fn test_render_listing_escapes_names() {
+        let mtime = UNIX_EPOCH + std::time::Duration::from_secs(1705322445);
+        let entries = vec![
+            ListingEntry {
+                name: "docs".into(),
+                is_dir: true,
+                size: 4096,
+                mtime: Some(mtime),
+            },
+            ListingEntry {
+                name: "<script>a b.txt".into(),
+                is_dir: false,
+                size: 42,
+                mtime: Some(mtime),
+            },
+        ];
+        let html = render_listing("/files/my%20dir/", &entries);
+
+        assert!(html.contains("<title>Index of /files/my dir/</title>"));
+        assert!(html.contains("<a href=\"../\">../</a>"));
+        assert!(html.contains("<a href=\"docs/\">docs/</a></td><td>-</td>"));
+        assert!(html.contains(
+            "<a href=\"%3Cscript%3Ea%20b.txt\">&lt;script&gt;a b.txt</a></td><td>42</td>\
+             <td>Mon, 15 Jan 2024 12:40:45 GMT</td>"
+        ));
+        assert!(!html.contains("<script>"));
+
+        // No parent link at the document root
+        assert!(!render_listing("/", &[]).contains("../"));
+    }
+
+    #[tokio::test]
+    async fn test_read_listing_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.txt"), "hello").unwrap();
+        std::fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+        std::fs::create_dir(dir.path().join("z-dir")).unwrap();
+        std::fs::write(dir.path().join("a.txt"), "").unwrap();
+
+        let entries = read_listing_entries(dir.path()).await.unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["z-dir", "a.txt", "b.txt"]);
+        assert_eq!(entries[2].size, 5);
     }
 
     #[test]
@@ -25,7 +25,8 @@
 use bytes::Bytes;
 use http_body_util::StreamBody;
 use hyper::body::Frame;
-use hyper::Response;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{Response, StatusCode};
 use std::convert::Infallible;
 use std::path::Path;
 use std::pin::Pin;
@@ -157,6 +158,10 @@ pub type StreamingResponse = Response<StreamingBody>;
 /// # Returns
 ///
 /// A streaming HTTP response that sends chunks as they arrive.
+///
+/// Headers come from PHP and the stream has already started, so one that
+/// can't be sent (a value with CR/LF or other control characters, an invalid
+/// name) is dropped with a warning rather than failing the response.
 pub fn streaming_response(
     status: u16,
     headers: Vec<(String, String)>,
@@ -165,13 +170,32 @@ pub fn streaming_response(
     let frame_stream = ChunkFrameStream::new(body_rx);
     let body = StreamBody::new(frame_stream);
 
-    let mut builder = Response::builder().status(status);
+    let mut response = Response::new(body);
+    *response.status_mut() = StatusCode::from_u16(status).unwrap_or_else(|_| {
+        tracing::warn!("Invalid status {} from streaming PHP response", status);
+        StatusCode::INTERNAL_SERVER_ERROR
+    });
+
+    let mut headers = headers;
+    super::drop_repeated_singletons(&mut headers);
 
+    let response_headers = response.headers_mut();
     for (name, value) in headers {
-        builder = builder.header(name, value);
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                response_headers.append(name, value);
+            }
+            _ => tracing::warn!(
+                "Dropping {} header with invalid name or value from streaming PHP response",
+                name.escape_debug()
+            ),
+        }
     }
 
-    builder.body(body).unwrap()
+    response
 }
 
 /// Create a streaming SSE response with default headers.
@@ -330,8 +354,7 @@ pub fn file_streaming_response(
         .header("Content-Length", size.to_string())
         .header("ETag", etag)
         .header("Last-Modified", last_modified)
-        .header("Accept-Ranges", "bytes")
-        .header("Server", "tokio_php/0.1.0");
+        .header("Accept-Ranges", "bytes");
 
     if let Some(cc) = cache_control {
         builder = builder.header("Cache-Control", cc);
@@ -340,13 +363,14 @@ pub fn file_streaming_response(
     builder.body(body).unwrap()
 }
 
-/// Check if a file should be streamed based on its size and compressibility.
+/// Check if a file should be streamed based on its size and whether it would
+/// be Brotli-compressed (compressible type and the client accepts `br`).
 ///
-/// - Compressible files > 3MB → streaming (compression would be too slow)
-/// - Non-compressible files > 1MB → streaming (no benefit from in-memory)
+/// - Compressed responses > 3MB → streaming (compression would be too slow)
+/// - Uncompressed responses > 1MB → streaming (no benefit from in-memory)
 #[inline]
-pub fn should_stream_file(size: u64, is_compressible: bool) -> bool {
-    if is_compressible {
+pub fn should_stream_file(size: u64, compress: bool) -> bool {
+    if compress {
         size > MAX_COMPRESSION_SIZE as u64
     } else {
         size > STREAM_THRESHOLD_NON_COMPRESSIBLE as u64
@@ -0,0 +1,208 @@
+//! Per-device static file variants (`STATIC_UA_VARIANTS`).
+//!
+//! Legacy sites sometimes ship a separate mobile page next to the desktop one.
+//! With `STATIC_UA_VARIANTS=mobile`, a phone asking for `/index.html` gets
+//! `index.mobile.html` when that file exists, and everyone else gets
+//! `index.html`. The User-Agent classification is deliberately coarse.
+
+use std::borrow::Cow;
+
+/// Coarse User-Agent class.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceClass {
+    Desktop,
+    Mobile,
+    Bot,
+}
+
+impl DeviceClass {
+    /// Classify a User-Agent (empty or unknown = desktop).
+    pub fn from_user_agent(user_agent: &str) -> Self {
+        let ua = user_agent.to_ascii_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|n| ua.contains(n));
+        if has(&["bot", "crawl", "spider", "slurp"]) {
+            Self::Bot
+        } else if has(&["mobi", "android", "iphone", "ipad", "ipod", "opera mini"]) {
+            Self::Mobile
+        } else {
+            Self::Desktop
+        }
+    }
+
+    /// Name used in config and in variant file names.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Desktop => "desktop",
+            Self::Mobile => "mobile",
+            Self::Bot => "bot",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "desktop" => Some(Self::Desktop),
+            "mobile" => Some(Self::Mobile),
+            "bot" => Some(Self::Bot),
+            _ => None,
+        }
+    }
+}
+
+/// Device classes that have their own file variants (empty = feature off).
+#[derive(Clone, Debug, Default)]
+pub struct UaVariants {
+    classes: Vec<DeviceClass>,
+}
+
+impl UaVariants {
+    /// Parse class names like `mobile` or `bot`.
+    pub fn parse<S: AsRef<str>>(classes: &[S]) -> Result<Self, String> {
+        let mut parsed = Vec::with_capacity(classes.len());
+        for class in classes {
+            let class = class.as_ref();
+            let class = DeviceClass::parse(class).ok_or_else(|| {
+                format!("unknown device class '{class}' (expected mobile, bot or desktop)")
+            })?;
+            if !parsed.contains(&class) {
+                parsed.push(class);
+            }
+        }
+        Ok(Self { classes: parsed })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    /// File to serve for `path` given the client's User-Agent.
+    ///
+    /// Returns `None` when no configured class has a variant of this file,
+    /// so the response doesn't depend on the User-Agent. Otherwise returns
+    /// the variant for the client's class, or `path` itself as fallback;
+    /// such responses need `Vary: User-Agent`.
+    pub fn select<'a>(
+        &self,
+        path: &'a str,
+        user_agent: &str,
+        exists: impl Fn(&str) -> bool,
+    ) -> Option<Cow<'a, str>> {
+        let client = DeviceClass::from_user_agent(user_agent);
+        let mut varies = false;
+        for &class in &self.classes {
+            let candidate = variant_path(path, class);
+            if exists(&candidate) {
+                if class == client {
+                    return Some(Cow::Owned(candidate));
+                }
+                varies = true;
+            }
+        }
+        varies.then_some(Cow::Borrowed(path))
+    }
+}
+
+/// `dir/index.html` -> `dir/index.mobile.html` (`dir/app` -> `dir/app.mobile`).
+fn variant_path(path: &str, class: DeviceClass) -> String {
+    let name_start = path.rfind('/').map_or(0, |i| i + 1);
+    let ext_start = path[name_start..]
+        .rfind('.')
+        .filter(|&i| i > 0)
+        .map_or(path.len(), |i| name_start + i);
+    format!(
+        "{}.{}{}",
+        &path[..ext_start],
+        class.as_str(),
+        &path[ext_start..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IPHONE: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) \
+                          AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148";
+    const FIREFOX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+    const GOOGLEBOT: &str =
+        "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)";
+
+    #[test]
+    fn test_classify_user_agent() {
+        assert_eq!(DeviceClass::from_user_agent(IPHONE), DeviceClass::Mobile);
+        assert_eq!(DeviceClass::from_user_agent(FIREFOX), DeviceClass::Desktop);
+        assert_eq!(DeviceClass::from_user_agent(GOOGLEBOT), DeviceClass::Bot);
+        assert_eq!(DeviceClass::from_user_agent(""), DeviceClass::Desktop);
+    }
+
+    #[test]
+    fn test_variant_path() {
+        assert_eq!(
+            variant_path("/www/index.html", DeviceClass::Mobile),
+            "/www/index.mobile.html"
+        );
+        assert_eq!(
+            variant_path("/www/app.min.js", DeviceClass::Bot),
+            "/www/app.min.bot.js"
+        );
+        assert_eq!(
+            variant_path("/www/v1.2/README", DeviceClass::Mobile),
+            "/www/v1.2/README.mobile"
+        );
+        assert_eq!(
+            variant_path("/www/.htaccess", DeviceClass::Mobile),
+            "/www/.htaccess.mobile"
+        );
+    }
+
+    #[test]
+    fn test_mobile_gets_variant() {
+        let variants = UaVariants::parse(&["mobile"]).unwrap();
+        let exists = |p: &str| p == "/www/index.mobile.html";
+
+        assert_eq!(
+            variants
+                .select("/www/index.html", IPHONE, exists)
+                .as_deref(),
+            Some("/www/index.mobile.html")
+        );
+        // Same URL, desktop client: original file, still User-Agent dependent
+        assert_eq!(
+            variants
+                .select("/www/index.html", FIREFOX, exists)
+                .as_deref(),
+            Some("/www/index.html")
+        );
+    }
+
+    #[test]
+    fn test_fallback_when_variant_absent() {
+        let variants = UaVariants::parse(&["mobile", "bot"]).unwrap();
+        assert_eq!(variants.select("/www/about.html", IPHONE, |_| false), None);
+        assert_eq!(
+            variants.select("/www/about.html", GOOGLEBOT, |_| false),
+            None
+        );
+
+        // Only a bot variant: phones get the original
+        let exists = |p: &str| p == "/www/about.bot.html";
+        assert_eq!(
+            variants
+                .select("/www/about.html", IPHONE, exists)
+                .as_deref(),
+            Some("/www/about.html")
+        );
+        assert_eq!(
+            variants
+                .select("/www/about.html", GOOGLEBOT, exists)
+                .as_deref(),
+            Some("/www/about.bot.html")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(UaVariants::default().is_empty());
+        assert!(!UaVariants::parse(&["Mobile", "mobile"]).unwrap().is_empty());
+        assert!(UaVariants::parse(&["tablet"]).is_err());
+    }
+}
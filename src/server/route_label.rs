@@ -0,0 +1,175 @@
+//! Bounded route labels for path-keyed metrics (`METRICS_ROUTES`).
+//!
+//! A raw URI path makes a poor metric label: every `/user/123` would be a
+//! series of its own. [`RouteLabels`] maps a path to the configured template
+//! it matches (`/user/{id}`), or to [`OTHER_ROUTE`], so there is one series
+//! per template plus one. Without templates nothing is recorded.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// Label for paths no template matches.
+pub const OTHER_ROUTE: &str = "other";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Literal(Box<str>),
+    /// `{name}`: any single segment.
+    Param,
+}
+
+#[derive(Clone, Debug)]
+struct Template {
+    label: Box<str>,
+    segments: Box<[Segment]>,
+}
+
+/// Route templates, tried in order.
+#[derive(Clone, Debug, Default)]
+pub struct RouteLabels {
+    templates: Vec<Template>,
+}
+
+impl RouteLabels {
+    /// Parse templates like `/user/{id}` or `/api/orders/{id}/items`.
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> Result<Self, String> {
+        let mut templates = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let entry = entry.as_ref().trim();
+            if !entry.starts_with('/') {
+                return Err(format!("route must start with '/', got '{entry}'"));
+            }
+            if entry.contains(['"', '\\']) {
+                return Err(format!("invalid character in route '{entry}'"));
+            }
+            let segments = segments(entry)
+                .map(|s| {
+                    if s.starts_with('{') && s.ends_with('}') {
+                        Segment::Param
+                    } else {
+                        Segment::Literal(s.into())
+                    }
+                })
+                .collect();
+            templates.push(Template {
+                label: entry.into(),
+                segments,
+            });
+        }
+        Ok(Self { templates })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// Label for a request path (without query string).
+    pub fn label(&self, path: &str) -> &str {
+        let parts: Vec<&str> = segments(path).collect();
+        self.templates
+            .iter()
+            .find(|t| {
+                t.segments.len() == parts.len()
+                    && t.segments.iter().zip(&parts).all(|(seg, part)| match seg {
+                        Segment::Literal(lit) => **lit == **part,
+                        Segment::Param => !part.is_empty(),
+                    })
+            })
+            .map_or(OTHER_ROUTE, |t| &t.label)
+    }
+}
+
+/// Path segments, ignoring one trailing slash (`/a/b/` = `/a/b`).
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    let path = path.strip_suffix('/').unwrap_or(path);
+    path.split('/').filter(|_| !path.is_empty())
+}
+
+/// Request counts per route label.
+#[derive(Debug, Default)]
+pub struct RouteCounts {
+    counts: RwLock<HashMap<Box<str>, AtomicU64>>,
+}
+
+impl RouteCounts {
+    pub fn record(&self, label: &str) {
+        {
+            let counts = self.counts.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(count) = counts.get(label) {
+                count.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        let mut counts = self.counts.write().unwrap_or_else(|e| e.into_inner());
+        counts
+            .entry(label.into())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render as `tokio_php_requests_by_route_total{route}` in Prometheus text
+    /// format; empty until a route is recorded.
+    pub fn to_prometheus(&self) -> String {
+        let counts = self.counts.read().unwrap_or_else(|e| e.into_inner());
+        if counts.is_empty() {
+            return String::new();
+        }
+        let mut routes: Vec<_> = counts.iter().collect();
+        routes.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut out = String::from(
+            "\n# HELP tokio_php_requests_by_route_total HTTP requests by normalized route (METRICS_ROUTES)\n\
+             # TYPE tokio_php_requests_by_route_total counter\n",
+        );
+        for (route, count) in routes {
+            let route = route.replace('\\', "\\\\").replace('"', "\\\"");
+            let _ = writeln!(
+                out,
+                "tokio_php_requests_by_route_total{{route=\"{}\"}} {}",
+                route,
+                count.load(Ordering::Relaxed)
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates() {
+        let routes = RouteLabels::parse(&["/user/{id}", "/api/orders/{id}/items", "/"]).unwrap();
+        assert_eq!(routes.label("/user/123"), "/user/{id}");
+        assert_eq!(routes.label("/user/alice/"), "/user/{id}");
+        assert_eq!(
+            routes.label("/api/orders/7/items"),
+            "/api/orders/{id}/items"
+        );
+        assert_eq!(routes.label("/"), "/");
+        assert_eq!(routes.label("/user"), OTHER_ROUTE);
+        assert_eq!(routes.label("/user/1/edit"), OTHER_ROUTE);
+        assert_eq!(routes.label("/user//"), OTHER_ROUTE);
+
+        assert!(RouteLabels::parse(&["user/{id}"]).is_err());
+    }
+
+    #[test]
+    fn test_route_counts() {
+        let counts = RouteCounts::default();
+        assert_eq!(counts.to_prometheus(), "");
+
+        let routes = RouteLabels::parse(&["/user/{id}"]).unwrap();
+        for path in ["/user/1", "/user/2", "/a", "/b/c"] {
+            counts.record(routes.label(path));
+        }
+        let text = counts.to_prometheus();
+        assert!(text.contains("tokio_php_requests_by_route_total{route=\"/user/{id}\"} 2\n"));
+        assert!(text.contains("tokio_php_requests_by_route_total{route=\"other\"} 2\n"));
+        assert_eq!(text.matches("_total{").count(), 2);
+    }
+}
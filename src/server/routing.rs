@@ -2,10 +2,28 @@
 //!
 //! Implements nginx-style try_files behavior for PHP applications.
 
+use std::borrow::Cow;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use hyper::Uri;
+
 use super::file_cache::{FileCache, FileType};
 
+/// Handling of a URI that names a directory but lacks the trailing slash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DirectoryRequest {
+    /// 404 Not Found (default)
+    #[default]
+    NotFound,
+    /// 301 to the same URI with "/" appended
+    Redirect,
+    /// Serve the directory index (or listing) as if "/" were present
+    Index,
+    /// 403 Forbidden
+    Forbidden,
+}
+
 /// Route configuration.
 #[derive(Debug, Clone)]
 pub struct RouteConfig {
@@ -17,6 +35,14 @@ pub struct RouteConfig {
     pub index_file_path: Option<Arc<str>>,
     /// Whether index file is PHP
     pub index_file_is_php: bool,
+    /// Document root with symlinks resolved (None if it doesn't exist)
+    pub canonical_root: Option<PathBuf>,
+    /// URI prefixes where directories without an index are listed (AUTOINDEX)
+    pub autoindex: Vec<String>,
+    /// What to do with "/dir" when "dir" is a directory (DIRECTORY_REQUEST)
+    pub directory_request: DirectoryRequest,
+    /// URI prefixes forwarded to HTTP upstreams (PROXY_ROUTES)
+    pub proxy_routes: ProxyRoutes,
 }
 
 impl RouteConfig {
@@ -37,12 +63,150 @@ impl RouteConfig {
         };
 
         Self {
+            canonical_root: std::fs::canonicalize(document_root.as_ref()).ok(),
             document_root,
             index_file,
             index_file_path,
             index_file_is_php,
+            autoindex: Vec::new(),
+            directory_request: DirectoryRequest::default(),
+            proxy_routes: ProxyRoutes::default(),
+        }
+    }
+
+    /// Forward matching URI prefixes to HTTP upstreams instead of PHP.
+    pub fn with_proxy_routes(mut self, routes: ProxyRoutes) -> Self {
+        self.proxy_routes = routes;
+        self
+    }
+
+    /// Set handling of directory URIs without a trailing slash.
+    pub fn with_directory_request(mut self, mode: DirectoryRequest) -> Self {
+        self.directory_request = mode;
+        self
+    }
+
+    /// Enable directory listings under the given URI prefixes ("/" = everywhere).
+    pub fn with_autoindex(mut self, prefixes: &[String]) -> Self {
+        self.autoindex = prefixes
+            .iter()
+            .map(|p| {
+                let p = p.trim_matches('/');
+                if p.is_empty() {
+                    "/".to_string()
+                } else {
+                    format!("/{}/", p)
+                }
+            })
+            .collect();
+        self
+    }
+
+    /// Whether `path` (after resolving symlinks) is inside the document root.
+    pub fn contains(&self, path: &str) -> bool {
+        match (&self.canonical_root, std::fs::canonicalize(path)) {
+            (Some(root), Ok(real)) => real.starts_with(root),
+            _ => false,
         }
     }
+
+    /// Whether a directory URI (ending with "/") may be listed.
+    #[inline]
+    fn autoindex_enabled(&self, dir_uri: &str) -> bool {
+        self.autoindex
+            .iter()
+            .any(|p| dir_uri.starts_with(p.as_str()))
+    }
+}
+
+/// URI prefix forwarded to an HTTP upstream.
+#[derive(Debug, Clone)]
+pub struct ProxyRoute {
+    /// Prefix without trailing slash ("" = every path)
+    prefix: Box<str>,
+    /// Upstream `host:port`
+    authority: Box<str>,
+    /// Upstream base path without trailing slash, prepended to the request path
+    base_path: Box<str>,
+}
+
+impl ProxyRoute {
+    /// Upstream URI for a request's path and query.
+    pub fn upstream_uri(&self, path_and_query: &str) -> Result<Uri, hyper::http::Error> {
+        Uri::builder()
+            .scheme("http")
+            .authority(&*self.authority)
+            .path_and_query(format!("{}{}", self.base_path, path_and_query))
+            .build()
+    }
+
+    /// Upstream `host:port` (for logging).
+    pub fn authority(&self) -> &str {
+        &self.authority
+    }
+
+    #[inline]
+    fn matches(&self, uri_path: &str) -> bool {
+        match uri_path.strip_prefix(&*self.prefix) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    }
+}
+
+/// URI prefixes proxied to HTTP upstreams, longest prefix first.
+///
+/// Checked before PHP and static routing, on the [`match_path`] form of the
+/// request path, and on segment boundaries: `/api` covers `/api` and
+/// `/api/users`, not `/apix`. Methods outside ALLOWED_METHODS are not proxied.
+#[derive(Debug, Clone, Default)]
+pub struct ProxyRoutes {
+    routes: Vec<ProxyRoute>,
+}
+
+impl ProxyRoutes {
+    /// Parse entries like `/api=http://127.0.0.1:3000`.
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> Result<Self, String> {
+        let mut routes = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let entry = entry.as_ref();
+            let (prefix, url) = entry
+                .split_once('=')
+                .map(|(p, u)| (p.trim(), u.trim()))
+                .ok_or_else(|| format!("expected prefix=url, got '{entry}'"))?;
+            if !prefix.starts_with('/') {
+                return Err(format!("prefix must start with '/' in '{entry}'"));
+            }
+            let uri: Uri = url
+                .parse()
+                .map_err(|_| format!("invalid upstream URL in '{entry}'"))?;
+            if uri.scheme_str() != Some("http") {
+                return Err(format!("upstream must be an http:// URL in '{entry}'"));
+            }
+            let authority = uri
+                .authority()
+                .ok_or_else(|| format!("upstream has no host in '{entry}'"))?;
+            if uri.query().is_some() {
+                return Err(format!("upstream URL can't have a query in '{entry}'"));
+            }
+            routes.push(ProxyRoute {
+                prefix: prefix.trim_end_matches('/').into(),
+                authority: authority.as_str().into(),
+                base_path: uri.path().trim_end_matches('/').into(),
+            });
+        }
+        routes.sort_by_key(|route| std::cmp::Reverse(route.prefix.len()));
+        Ok(Self { routes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Upstream for a request path, if a prefix covers it.
+    pub fn find(&self, uri_path: &str) -> Option<&ProxyRoute> {
+        self.routes.iter().find(|route| route.matches(uri_path))
+    }
 }
 
 /// Result of route resolution.
@@ -52,6 +216,12 @@ pub enum RouteResult {
     Execute(String),
     /// Serve static file at given path
     Serve(String),
+    /// Generate a listing of the directory at given path
+    Listing(String),
+    /// Redirect to the request URI with a trailing slash
+    AddTrailingSlash,
+    /// Return 403 Forbidden
+    Forbidden,
     /// Return 404 Not Found
     NotFound,
 }
@@ -65,11 +235,32 @@ pub enum RouteResult {
 /// 4. File exists -> serve/execute
 /// 5. INDEX_FILE set -> fallback to INDEX_FILE
 /// 6. -> 404
-#[inline]
+///
+/// Directories without an index are listed when AUTOINDEX covers them.
+///
+/// Any resolved path that lies outside the document root once symlinks are
+/// followed is reported as NotFound.
 pub fn resolve_request(uri_path: &str, config: &RouteConfig, cache: &FileCache) -> RouteResult {
-    // 1. Decode URI and sanitize
+    match resolve_uri(uri_path, config, cache) {
+        RouteResult::Execute(ref path)
+        | RouteResult::Serve(ref path)
+        | RouteResult::Listing(ref path)
+            if !config.contains(path) =>
+        {
+            tracing::debug!("Resolved path outside document root: {}", path);
+            RouteResult::NotFound
+        }
+        result => result,
+    }
+}
+
+#[inline]
+fn resolve_uri(uri_path: &str, config: &RouteConfig, cache: &FileCache) -> RouteResult {
+    // 1. Decode URI and normalize; ".." above the root -> 404
     let decoded = percent_encoding::percent_decode_str(uri_path).decode_utf8_lossy();
-    let safe_path = sanitize_path(&decoded);
+    let Some(safe_path) = normalize_path(&decoded) else {
+        return RouteResult::NotFound;
+    };
 
     // 2. Check direct access to INDEX_FILE -> 404
     if is_direct_index_access(&safe_path, config) {
@@ -83,12 +274,12 @@ pub fn resolve_request(uri_path: &str, config: &RouteConfig, cache: &FileCache)
 
     // 4. Root path "/"
     if safe_path == "/" || safe_path.is_empty() {
-        return resolve_root(config, cache);
+        return resolve_root(config, cache).or_listing("/", config, cache);
     }
 
     // 5. Trailing slash -> directory mode
     if safe_path.ends_with('/') {
-        return resolve_directory(&safe_path, config, cache);
+        return resolve_directory(&safe_path, config, cache).or_listing(&safe_path, config, cache);
     }
 
     // 6. Normal file path
@@ -165,10 +356,15 @@ fn resolve_file(path: &str, config: &RouteConfig, cache: &FileCache) -> RouteRes
                 RouteResult::Serve(full_path)
             }
         }
-        Some(FileType::Dir) => {
-            // Directory without trailing slash -> 404 (no redirect)
-            RouteResult::NotFound
-        }
+        Some(FileType::Dir) => match config.directory_request {
+            DirectoryRequest::NotFound => RouteResult::NotFound,
+            DirectoryRequest::Redirect => RouteResult::AddTrailingSlash,
+            DirectoryRequest::Index => {
+                let dir_uri = format!("{}/", path);
+                resolve_directory(&dir_uri, config, cache).or_listing(&dir_uri, config, cache)
+            }
+            DirectoryRequest::Forbidden => RouteResult::Forbidden,
+        },
         None => {
             // File doesn't exist -> fallback to INDEX_FILE
             if let Some(ref idx_path) = config.index_file_path {
@@ -184,10 +380,65 @@ fn resolve_file(path: &str, config: &RouteConfig, cache: &FileCache) -> RouteRes
     }
 }
 
-/// Sanitize path: remove ".." sequences for security.
-#[inline]
-fn sanitize_path(path: &str) -> String {
-    path.replace("..", "")
+impl RouteResult {
+    /// Replace a directory 404 with a listing if AUTOINDEX covers the URI.
+    fn or_listing(self, dir_uri: &str, config: &RouteConfig, cache: &FileCache) -> Self {
+        if self != RouteResult::NotFound || !config.autoindex_enabled(dir_uri) {
+            return self;
+        }
+        let dir_path = format!("{}{}", config.document_root, dir_uri.trim_end_matches('/'));
+        if cache.is_dir(&dir_path) {
+            RouteResult::Listing(dir_path)
+        } else {
+            RouteResult::NotFound
+        }
+    }
+}
+
+/// Decoded, normalized form of a URI path for prefix checks (kill-switch,
+/// proxy routes, priority paths), so `/api/%72eports` and `/api//reports`
+/// can't slip past a rule for `/api/reports`.
+///
+/// Paths that climb above the root are returned as-is; they never resolve.
+pub fn match_path(uri_path: &str) -> Cow<'_, str> {
+    if !uri_path.contains(['%', '.']) && !uri_path.contains("//") {
+        return Cow::Borrowed(uri_path);
+    }
+    let decoded = percent_encoding::percent_decode_str(uri_path).decode_utf8_lossy();
+    match normalize_path(&decoded) {
+        Some(normalized) => Cow::Owned(normalized),
+        None => Cow::Borrowed(uri_path),
+    }
+}
+
+/// Normalize a decoded URI path: collapse "//" and ".", resolve "..".
+///
+/// Returns None if ".." climbs above the root or the path contains NUL.
+/// The result always starts with "/" and keeps a trailing slash.
+fn normalize_path(path: &str) -> Option<String> {
+    if path.contains('\0') {
+        return None;
+    }
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len() + 1);
+    for segment in &segments {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if normalized.is_empty() || path.ends_with('/') || path.ends_with("/.") {
+        normalized.push('/');
+    }
+    Some(normalized)
 }
 
 /// Check if request is direct access to INDEX_FILE.
@@ -294,20 +545,317 @@ mod tests {
     }
 
     // ========================================
-    // sanitize_path tests
+    // normalize_path tests
+    // ========================================
+
+    #[test]
+    fn test_match_path() {
+        assert!(matches!(match_path("/api/reports"), Cow::Borrowed(_)));
+        assert_eq!(match_path("/api/%72eports"), "/api/reports");
+        assert_eq!(match_path("/api//reports/"), "/api/reports/");
+        assert_eq!(match_path("/x/../api/reports"), "/api/reports");
+        assert_eq!(match_path("/file.php"), "/file.php");
+        assert_eq!(match_path("/../etc"), "/../etc");
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("/etc/passwd").unwrap(), "/etc/passwd");
+        assert_eq!(
+            normalize_path("/admin/../config.php").unwrap(),
+            "/config.php"
+        );
+        assert_eq!(normalize_path("/a/./b//c/").unwrap(), "/a/b/c/");
+        assert_eq!(normalize_path("").unwrap(), "/");
+        assert_eq!(normalize_path("/docs/.").unwrap(), "/docs/");
+        // ".." inside a name is not a traversal
+        assert_eq!(normalize_path("/file..txt").unwrap(), "/file..txt");
+        assert_eq!(normalize_path("/....//").unwrap(), "/..../");
+
+        assert_eq!(normalize_path("/../etc/passwd"), None);
+        assert_eq!(normalize_path("/a/../../etc/passwd"), None);
+        assert_eq!(normalize_path("/a\0.php"), None);
+    }
+
+    // ========================================
+    // Autoindex tests
+    // ========================================
+
+    #[test]
+    fn test_autoindex_prefixes() {
+        let config = RouteConfig::new("/var/www/html", None)
+            .with_autoindex(&["/downloads".to_string(), "pub/".to_string()]);
+        assert!(config.autoindex_enabled("/downloads/"));
+        assert!(config.autoindex_enabled("/downloads/2024/"));
+        assert!(config.autoindex_enabled("/pub/"));
+        assert!(!config.autoindex_enabled("/downloads-old/"));
+        assert!(!config.autoindex_enabled("/"));
+
+        let everywhere = RouteConfig::new("/var/www/html", None).with_autoindex(&["/".to_string()]);
+        assert!(everywhere.autoindex_enabled("/"));
+        assert!(everywhere.autoindex_enabled("/any/dir/"));
+
+        // Off by default
+        assert!(!RouteConfig::new("/var/www/html", None).autoindex_enabled("/"));
+    }
+
+    #[test]
+    fn test_resolve_directory_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        std::fs::create_dir_all(dir.path().join("files/sub")).unwrap();
+        std::fs::create_dir(dir.path().join("site")).unwrap();
+        std::fs::write(dir.path().join("site/index.html"), "hi").unwrap();
+
+        let cache = FileCache::new();
+        let config = RouteConfig::new(root, None).with_autoindex(&["/files".to_string()]);
+
+        assert_eq!(
+            resolve_request("/files/", &config, &cache),
+            RouteResult::Listing(format!("{}/files", root))
+        );
+        assert_eq!(
+            resolve_request("/files/sub/", &config, &cache),
+            RouteResult::Listing(format!("{}/files/sub", root))
+        );
+        // Index still wins over the listing
+        assert_eq!(
+            resolve_request("/site/", &config, &cache),
+            RouteResult::Serve(format!("{}/site/index.html", root))
+        );
+        // Missing directory or outside the configured prefix -> 404 as before
+        assert_eq!(
+            resolve_request("/files/missing/", &config, &cache),
+            RouteResult::NotFound
+        );
+        let other = RouteConfig::new(root, None);
+        assert_eq!(
+            resolve_request("/files/", &other, &cache),
+            RouteResult::NotFound
+        );
+        // ".." cannot climb out of the document root
+        assert_eq!(
+            resolve_request("/files/../../", &config, &cache),
+            RouteResult::NotFound
+        );
+    }
+
+    // ========================================
+    // Traversal tests
+    // ========================================
+
+    /// Document root with `secret.txt` next to it (outside the root).
+    fn traversal_fixture() -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("html")).unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        std::fs::write(dir.path().join("html/app..min.js"), "").unwrap();
+        let root = dir.path().join("html").to_str().unwrap().to_string();
+        (dir, root)
+    }
+
+    #[test]
+    fn test_traversal_encoded_dots() {
+        let (_dir, root) = traversal_fixture();
+        let cache = FileCache::new();
+        let config = RouteConfig::new(&root, None);
+
+        for uri in [
+            "/%2e%2e%2fsecret.txt",
+            "/%2E%2E/secret.txt",
+            "/..%2fsecret.txt",
+            "/html/%2e%2e/%2e%2e/secret.txt",
+        ] {
+            assert_eq!(
+                resolve_request(uri, &config, &cache),
+                RouteResult::NotFound,
+                "{uri}"
+            );
+        }
+
+        // Even with a fallback, traversal must not reach INDEX_FILE logic
+        std::fs::write(format!("{}/index.php", root), "<?php").unwrap();
+        let spa = RouteConfig::new(&root, Some("index.php"));
+        assert_eq!(
+            resolve_request("/%2e%2e%2fsecret.txt", &spa, &cache),
+            RouteResult::NotFound
+        );
+    }
+
+    #[test]
+    fn test_traversal_dot_runs() {
+        let (_dir, root) = traversal_fixture();
+        let cache = FileCache::new();
+        let config = RouteConfig::new(&root, None);
+
+        // "...." is an ordinary (missing) name, not a parent reference
+        assert_eq!(
+            resolve_request("/....//secret.txt", &config, &cache),
+            RouteResult::NotFound
+        );
+        assert_eq!(
+            resolve_request("/....//....//secret.txt", &config, &cache),
+            RouteResult::NotFound
+        );
+        // Names containing ".." are no longer mangled
+        assert_eq!(
+            resolve_request("/app..min.js", &config, &cache),
+            RouteResult::Serve(format!("{}/app..min.js", root))
+        );
+    }
+
+    #[test]
+    fn test_traversal_absolute_path() {
+        let (dir, root) = traversal_fixture();
+        let cache = FileCache::new();
+        let config = RouteConfig::new(&root, None);
+        let secret = dir.path().join("secret.txt");
+        let secret = secret.to_str().unwrap();
+
+        // The absolute path is looked up under the document root, not at "/"
+        for uri in [
+            format!("/{}", secret),
+            format!("//{}", secret.trim_start_matches('/')),
+            percent_encoding::utf8_percent_encode(secret, percent_encoding::NON_ALPHANUMERIC)
+                .to_string(),
+        ] {
+            assert_eq!(
+                resolve_request(&uri, &config, &cache),
+                RouteResult::NotFound,
+                "{uri}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_outside_root_rejected() {
+        let (dir, root) = traversal_fixture();
+        let cache = FileCache::new();
+        let config = RouteConfig::new(&root, None);
+
+        std::os::unix::fs::symlink(dir.path().join("secret.txt"), format!("{}/leak.txt", root))
+            .unwrap();
+        std::os::unix::fs::symlink(dir.path(), format!("{}/up", root)).unwrap();
+        std::os::unix::fs::symlink(
+            format!("{}/app..min.js", root),
+            format!("{}/alias.js", root),
+        )
+        .unwrap();
+
+        assert_eq!(
+            resolve_request("/leak.txt", &config, &cache),
+            RouteResult::NotFound
+        );
+        assert_eq!(
+            resolve_request("/up/secret.txt", &config, &cache),
+            RouteResult::NotFound
+        );
+        // Symlinks within the root keep working
+        assert_eq!(
+            resolve_request("/alias.js", &config, &cache),
+            RouteResult::Serve(format!("{}/alias.js", root))
+        );
+    }
+
+    // ========================================
+    // Proxy routes tests
     // ========================================
 
     #[test]
-    fn test_sanitize_path() {
-        assert_eq!(sanitize_path("/etc/passwd"), "/etc/passwd");
-        assert_eq!(sanitize_path("/../etc/passwd"), "//etc/passwd");
-        assert_eq!(sanitize_path("/admin/../config.php"), "/admin//config.php");
+    fn test_proxy_routes_longest_prefix() {
+        let routes = ProxyRoutes::parse(&[
+            "/api=http://127.0.0.1:3000",
+            "/api/v2/=http://node:8080/base/",
+        ])
+        .unwrap();
+
+        let route = routes.find("/api/users").unwrap();
+        assert_eq!(route.authority(), "127.0.0.1:3000");
+        assert_eq!(
+            route.upstream_uri("/api/users?page=2").unwrap(),
+            "http://127.0.0.1:3000/api/users?page=2"
+        );
+
+        let route = routes.find("/api/v2/items").unwrap();
+        assert_eq!(
+            route.upstream_uri("/api/v2/items").unwrap(),
+            "http://node:8080/base/api/v2/items"
+        );
+
+        assert!(routes.find("/api").is_some());
+        assert!(routes.find("/apix").is_none());
+        assert!(routes.find("/index.php").is_none());
+
+        let all = ProxyRoutes::parse(&["/=http://backend"]).unwrap();
+        assert!(all.find("/").is_some());
+        assert!(all.find("/anything").is_some());
+    }
+
+    #[test]
+    fn test_proxy_routes_parse_errors() {
+        assert!(ProxyRoutes::parse(&["/api"]).is_err());
+        assert!(ProxyRoutes::parse(&["api=http://backend"]).is_err());
+        assert!(ProxyRoutes::parse(&["/api=https://backend"]).is_err());
+        assert!(ProxyRoutes::parse(&["/api=backend:3000"]).is_err());
+        assert!(ProxyRoutes::parse(&["/api=http://backend/?x=1"]).is_err());
+        assert!(ProxyRoutes::default().is_empty());
     }
 
     // ========================================
-    // Integration tests with mock filesystem
+    // Directory without trailing slash
     // ========================================
 
-    // Note: Full integration tests require actual filesystem.
-    // These tests verify logic with mocked cache responses.
+    #[test]
+    fn test_directory_request_modes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        std::fs::create_dir(dir.path().join("files")).unwrap();
+        std::fs::create_dir(dir.path().join("site")).unwrap();
+        std::fs::write(dir.path().join("site/index.php"), "<?php").unwrap();
+
+        let cache = FileCache::new();
+        let base = RouteConfig::new(root, None);
+
+        // Default: 404, nothing is read from the directory
+        assert_eq!(
+            resolve_request("/site", &base, &cache),
+            RouteResult::NotFound
+        );
+
+        let redirect = base
+            .clone()
+            .with_directory_request(DirectoryRequest::Redirect);
+        assert_eq!(
+            resolve_request("/site", &redirect, &cache),
+            RouteResult::AddTrailingSlash
+        );
+        // Regular files are unaffected
+        std::fs::write(dir.path().join("site/app.css"), "").unwrap();
+        assert_eq!(
+            resolve_request("/site/app.css", &redirect, &cache),
+            RouteResult::Serve(format!("{}/site/app.css", root))
+        );
+
+        let index = base.clone().with_directory_request(DirectoryRequest::Index);
+        assert_eq!(
+            resolve_request("/site", &index, &cache),
+            RouteResult::Execute(format!("{}/site/index.php", root))
+        );
+        assert_eq!(
+            resolve_request("/files", &index, &cache),
+            RouteResult::NotFound
+        );
+        let index_listing = index.with_autoindex(&["/files".to_string()]);
+        assert_eq!(
+            resolve_request("/files", &index_listing, &cache),
+            RouteResult::Listing(format!("{}/files", root))
+        );
+
+        let forbidden = base.with_directory_request(DirectoryRequest::Forbidden);
+        assert_eq!(
+            resolve_request("/files", &forbidden, &cache),
+            RouteResult::Forbidden
+        );
+    }
 }
@@ -0,0 +1,184 @@
+//! Trusted reverse proxies.
+//!
+//! Forwarding headers (`X-Forwarded-Host`, ...) are only honored when the
+//! direct peer is listed in `TRUSTED_PROXIES`. Anyone else could set them to
+//! arbitrary values, so for untrusted peers they are ignored entirely.
+
+use std::net::IpAddr;
+
+use hyper::HeaderMap;
+
+/// Set of trusted proxy networks (single IPs or CIDR ranges).
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    nets: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+    /// Parse entries like `10.0.0.1`, `10.0.0.0/8` or `fd00::/8`.
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> Result<Self, String> {
+        let nets = entries
+            .iter()
+            .map(|entry| parse_net(entry.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { nets })
+    }
+
+    /// No proxies trusted (the default).
+    pub fn is_empty(&self) -> bool {
+        self.nets.is_empty()
+    }
+
+    /// Whether the peer address belongs to a trusted proxy.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = canonical(ip);
+        self.nets
+            .iter()
+            .any(|&(net, prefix)| in_network(ip, net, prefix))
+    }
+
+    /// Original host from `X-Forwarded-Host`, if the peer is trusted.
+    ///
+    /// With several proxies the header is a list; the first entry is the
+    /// host the client asked for. Values that don't look like a host are
+    /// ignored.
+    pub fn forwarded_host<'a>(&self, peer: IpAddr, headers: &'a HeaderMap) -> Option<&'a str> {
+        if self.is_empty() || !self.contains(peer) {
+            return None;
+        }
+        let value = headers.get("x-forwarded-host")?.to_str().ok()?;
+        let host = value.split(',').next()?.trim();
+        is_valid_host(host).then_some(host)
+    }
+}
+
+fn parse_net(entry: &str) -> Result<(IpAddr, u8), String> {
+    let entry = entry.trim();
+    let (addr, prefix) = match entry.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (entry, None),
+    };
+    let ip: IpAddr = addr
+        .parse()
+        .map_err(|_| format!("invalid proxy address '{entry}'"))?;
+    let ip = canonical(ip);
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(p) => p
+            .parse::<u8>()
+            .ok()
+            .filter(|&p| p <= max)
+            .ok_or_else(|| format!("invalid prefix length in '{entry}'"))?,
+        None => max,
+    };
+    Ok((ip, prefix))
+}
+
+/// IPv4-mapped IPv6 (`::ffff:10.0.0.1`) compares as IPv4.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    }
+}
+
+fn in_network(ip: IpAddr, net: IpAddr, prefix: u8) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+/// `host`, `host:port` or `[v6]:port`, without paths, spaces or userinfo.
+fn is_valid_host(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= 255
+        && host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b':' | b'[' | b']'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(host: &str) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        map.insert("x-forwarded-host", host.parse().unwrap());
+        map
+    }
+
+    #[test]
+    fn test_cidr_matching() {
+        let proxies = TrustedProxies::parse(&["10.0.0.0/8", "192.168.1.5", "fd00::/8"]).unwrap();
+        assert!(proxies.contains("10.1.2.3".parse().unwrap()));
+        assert!(proxies.contains("192.168.1.5".parse().unwrap()));
+        assert!(!proxies.contains("192.168.1.6".parse().unwrap()));
+        assert!(proxies.contains("fd12::1".parse().unwrap()));
+        assert!(!proxies.contains("fe80::1".parse().unwrap()));
+        // IPv4-mapped peers on dual-stack sockets
+        assert!(proxies.contains("::ffff:10.0.0.7".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_catch_all_and_empty() {
+        let all = TrustedProxies::parse(&["0.0.0.0/0"]).unwrap();
+        assert!(all.contains("203.0.113.9".parse().unwrap()));
+
+        let none = TrustedProxies::default();
+        assert!(none.is_empty());
+        assert!(!none.contains("127.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(TrustedProxies::parse(&["not-an-ip"]).is_err());
+        assert!(TrustedProxies::parse(&["10.0.0.0/33"]).is_err());
+        assert!(TrustedProxies::parse(&["fd00::/129"]).is_err());
+        assert!(TrustedProxies::parse(&["10.0.0.0/x"]).is_err());
+    }
+
+    #[test]
+    fn test_forwarded_host_trusted() {
+        let proxies = TrustedProxies::parse(&["10.0.0.0/8"]).unwrap();
+        let peer = "10.0.0.2".parse().unwrap();
+        assert_eq!(
+            proxies.forwarded_host(peer, &headers("example.com")),
+            Some("example.com")
+        );
+        assert_eq!(
+            proxies.forwarded_host(peer, &headers("example.com:8443, internal.lan")),
+            Some("example.com:8443")
+        );
+        // Malformed values are ignored
+        assert_eq!(
+            proxies.forwarded_host(peer, &headers("evil.com/path")),
+            None
+        );
+        assert_eq!(proxies.forwarded_host(peer, &HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_forwarded_host_untrusted() {
+        let proxies = TrustedProxies::parse(&["10.0.0.0/8"]).unwrap();
+        let peer = "203.0.113.9".parse().unwrap();
+        assert_eq!(proxies.forwarded_host(peer, &headers("evil.com")), None);
+
+        // Nothing is trusted by default
+        let peer = "10.0.0.2".parse().unwrap();
+        assert_eq!(
+            TrustedProxies::default().forwarded_host(peer, &headers("evil.com")),
+            None
+        );
+    }
+}
@@ -6,6 +6,15 @@
 //! Format: `traceparent: {version}-{trace-id}-{parent-id}-{trace-flags}`
 //! Example: `traceparent: 00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01`
 //!
+//! B3 (Zipkin) headers are accepted as a fallback when no valid `traceparent`
+//! is present, in both the single-header and multi-header forms:
+//!
+//! - `b3: {trace-id}-{span-id}[-{sampled}[-{parent-id}]]`
+//! - `X-B3-TraceId`, `X-B3-SpanId`, `X-B3-Sampled`, `X-B3-Flags`
+//!
+//! B3 trace IDs may be 64-bit (16 hex chars); they are left-padded with zeros
+//! to 128 bits, as the W3C spec recommends.
+//!
 //! This implementation uses stack-allocated buffers for zero heap allocation.
 
 use std::fmt;
@@ -21,13 +30,14 @@ const HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
 /// Trace context containing trace ID, span ID, and flags.
 /// All fields are stack-allocated for zero heap allocation.
 ///
-/// Memory layout (138 bytes total):
+/// Memory layout (139 bytes total):
 /// - trace_id: 32 bytes
 /// - span_id: 16 bytes
 /// - parent_span_id: 17 bytes (1 byte flag + 16 bytes data)
 /// - flags: 1 byte
 /// - traceparent_buf: 55 bytes (cached traceparent header)
 /// - short_id_buf: 17 bytes (cached request ID)
+/// - b3_64bit: 1 byte
 #[derive(Clone, Copy)]
 pub struct TraceContext {
     /// 16-byte trace ID as 32 hex chars
@@ -43,6 +53,8 @@ pub struct TraceContext {
     traceparent_buf: [u8; 55],
     /// Cached short ID for request correlation: "{trace_id[0:12]}-{span_id[0:4]}"
     short_id_buf: [u8; 17],
+    /// Incoming B3 trace ID was 64-bit (echoed back as 16 hex chars)
+    b3_64bit: bool,
 }
 
 impl TraceContext {
@@ -56,6 +68,7 @@ impl TraceContext {
             flags: FLAG_SAMPLED,
             traceparent_buf: [0u8; 55],
             short_id_buf: [0u8; 17],
+            b3_64bit: false,
         };
 
         generate_trace_id(&mut ctx.trace_id);
@@ -118,6 +131,7 @@ impl TraceContext {
             flags,
             traceparent_buf: [0u8; 55],
             short_id_buf: [0u8; 17],
+            b3_64bit: false,
         };
 
         // Copy and lowercase trace_id
@@ -138,14 +152,151 @@ impl TraceContext {
         Some(ctx)
     }
 
+    /// Parse from a B3 single header.
+    ///
+    /// Format: `{trace-id}-{span-id}[-{sampled}[-{parent-id}]]`
+    /// Example: `80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90`
+    ///
+    /// The sampling-only form (`b3: 0`) carries no IDs and yields `None`.
+    pub fn parse_b3(header: &str) -> Option<Self> {
+        let mut parts = header.trim().split('-');
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let sampled = match parts.next() {
+            None => None,
+            Some("1") | Some("d") => Some(true),
+            Some("0") => Some(false),
+            Some(_) => return None,
+        };
+        // The incoming parent is the caller's parent, not ours
+        if let Some(parent) = parts.next() {
+            if parent.len() != 16 || !is_valid_hex_bytes(parent.as_bytes()) {
+                return None;
+            }
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+        Self::from_b3_parts(trace_id.as_bytes(), span_id.as_bytes(), sampled)
+    }
+
+    /// Parse from B3 multi headers (`X-B3-TraceId`, `X-B3-SpanId`,
+    /// `X-B3-Sampled`, `X-B3-Flags`).
+    pub fn parse_b3_multi(
+        trace_id: &str,
+        span_id: &str,
+        sampled: Option<&str>,
+        flags: Option<&str>,
+    ) -> Option<Self> {
+        // Debug flag implies an accept decision
+        let sampled = if flags.map(str::trim) == Some("1") {
+            Some(true)
+        } else {
+            match sampled.map(str::trim) {
+                None => None,
+                Some("1") | Some("true") => Some(true),
+                Some("0") | Some("false") => Some(false),
+                Some(_) => return None,
+            }
+        };
+        Self::from_b3_parts(
+            trace_id.trim().as_bytes(),
+            span_id.trim().as_bytes(),
+            sampled,
+        )
+    }
+
+    /// Build a context from B3 trace/span IDs. A missing sampling decision
+    /// defaults to sampled, as for a new trace.
+    fn from_b3_parts(trace_id: &[u8], span_id: &[u8], sampled: Option<bool>) -> Option<Self> {
+        if !matches!(trace_id.len(), 16 | 32)
+            || !is_valid_hex_bytes(trace_id)
+            || is_all_zeros_bytes(trace_id)
+        {
+            return None;
+        }
+        if span_id.len() != 16 || !is_valid_hex_bytes(span_id) || is_all_zeros_bytes(span_id) {
+            return None;
+        }
+
+        let mut ctx = Self {
+            trace_id: [b'0'; 32],
+            span_id: [0u8; 16],
+            parent_span_id: [0u8; 17],
+            flags: if sampled.unwrap_or(true) {
+                FLAG_SAMPLED
+            } else {
+                0
+            },
+            traceparent_buf: [0u8; 55],
+            short_id_buf: [0u8; 17],
+            b3_64bit: trace_id.len() == 16,
+        };
+
+        // Left-pad 64-bit trace IDs to 128 bits
+        let offset = 32 - trace_id.len();
+        for (i, &b) in trace_id.iter().enumerate() {
+            ctx.trace_id[offset + i] = b.to_ascii_lowercase();
+        }
+
+        generate_span_id(&mut ctx.span_id);
+
+        ctx.parent_span_id[0] = 1;
+        for (i, &b) in span_id.iter().enumerate() {
+            ctx.parent_span_id[i + 1] = b.to_ascii_lowercase();
+        }
+
+        ctx.build_cached_values();
+        Some(ctx)
+    }
+
     /// Extract trace context from request headers, or generate new one.
+    ///
+    /// W3C `traceparent` wins over B3; B3 headers are only used when there
+    /// is no valid `traceparent`.
     #[inline]
     pub fn from_headers(headers: &hyper::HeaderMap) -> Self {
-        headers
+        let w3c = headers
             .get("traceparent")
             .and_then(|v| v.to_str().ok())
-            .and_then(Self::parse)
-            .unwrap_or_default()
+            .and_then(Self::parse);
+
+        match w3c {
+            Some(ctx) => {
+                if headers.contains_key("b3") || headers.contains_key("x-b3-traceid") {
+                    tracing::debug!(
+                        trace_id = ctx.trace_id(),
+                        "B3 headers ignored, traceparent takes precedence"
+                    );
+                }
+                ctx
+            }
+            None => match Self::from_b3_headers(headers) {
+                Some(ctx) => {
+                    tracing::debug!(
+                        trace_id = ctx.trace_id(),
+                        "No valid traceparent, using B3 trace context"
+                    );
+                    ctx
+                }
+                None => Self::new(),
+            },
+        }
+    }
+
+    /// B3 context from the single `b3` header, falling back to `X-B3-*`.
+    fn from_b3_headers(headers: &hyper::HeaderMap) -> Option<Self> {
+        let get = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+        if let Some(ctx) = get("b3").and_then(Self::parse_b3) {
+            return Some(ctx);
+        }
+        Self::parse_b3_multi(
+            get("x-b3-traceid")?,
+            get("x-b3-spanid")?,
+            get("x-b3-sampled"),
+            get("x-b3-flags"),
+        )
     }
 
     /// Build cached traceparent and short_id values.
@@ -209,6 +360,20 @@ impl TraceContext {
         unsafe { std::str::from_utf8_unchecked(&self.short_id_buf) }
     }
 
+    /// B3 single header value for downstream calls.
+    /// Format: "{trace_id}-{span_id}-{0|1}"
+    ///
+    /// A 64-bit incoming B3 trace ID is kept at 16 hex chars.
+    pub fn b3(&self) -> String {
+        let trace_id = if self.b3_64bit {
+            &self.trace_id()[16..]
+        } else {
+            self.trace_id()
+        };
+        let sampled = if self.is_sampled() { '1' } else { '0' };
+        format!("{}-{}-{}", trace_id, self.span_id(), sampled)
+    }
+
     /// Check if trace is sampled.
     #[inline]
     pub fn is_sampled(&self) -> bool {
@@ -429,7 +594,126 @@ mod tests {
     #[test]
     fn test_size() {
         // Verify the struct is reasonably sized for stack allocation
-        // Actual size: 32 + 16 + 17 + 1 + 55 + 17 = 138 bytes
+        // Actual size: 32 + 16 + 17 + 1 + 55 + 17 + 1 = 139 bytes
         assert!(std::mem::size_of::<TraceContext>() <= 144);
     }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> hyper::HeaderMap {
+        let mut map = hyper::HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, value.parse().unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn test_parse_b3_single_128bit() {
+        let ctx = TraceContext::parse_b3(
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90",
+        )
+        .unwrap();
+
+        assert_eq!(ctx.trace_id(), "80f198ee56343ba864fe8b2a57d3eff7");
+        assert_eq!(ctx.parent_span_id(), Some("e457b5a2e4d86bd1"));
+        assert!(ctx.is_sampled());
+        assert!(ctx.b3().starts_with("80f198ee56343ba864fe8b2a57d3eff7-"));
+        assert!(ctx.b3().ends_with("-1"));
+    }
+
+    #[test]
+    fn test_parse_b3_single_64bit() {
+        let ctx = TraceContext::parse_b3("a3ce929d0e0e4736-00f067aa0ba902b7-0").unwrap();
+
+        // Padded to 128 bits for W3C, kept at 64 bits for B3
+        assert_eq!(ctx.trace_id(), "0000000000000000a3ce929d0e0e4736");
+        assert!(ctx
+            .traceparent()
+            .starts_with("00-0000000000000000a3ce929d0e0e4736-"));
+        assert!(!ctx.is_sampled());
+        let b3 = ctx.b3();
+        assert!(b3.starts_with("a3ce929d0e0e4736-"));
+        assert_eq!(b3.len(), 16 + 1 + 16 + 2);
+    }
+
+    #[test]
+    fn test_parse_b3_single_invalid() {
+        // Sampling-only
+        assert!(TraceContext::parse_b3("0").is_none());
+        // Unsupported trace ID length
+        assert!(TraceContext::parse_b3("a3ce929d0e0e47-00f067aa0ba902b7").is_none());
+        // All zeros
+        assert!(TraceContext::parse_b3("0000000000000000-00f067aa0ba902b7").is_none());
+        // Bad sampling state
+        assert!(TraceContext::parse_b3("a3ce929d0e0e4736-00f067aa0ba902b7-x").is_none());
+        // Debug state counts as sampled
+        assert!(
+            TraceContext::parse_b3("a3ce929d0e0e4736-00f067aa0ba902b7-d")
+                .unwrap()
+                .is_sampled()
+        );
+    }
+
+    #[test]
+    fn test_parse_b3_multi_64_and_128bit() {
+        let ctx =
+            TraceContext::parse_b3_multi("463ac35c9f6413ad", "a2fb4a1d1a96d312", Some("1"), None)
+                .unwrap();
+        assert_eq!(ctx.trace_id(), "0000000000000000463ac35c9f6413ad");
+        assert_eq!(ctx.parent_span_id(), Some("a2fb4a1d1a96d312"));
+        assert!(ctx.is_sampled());
+
+        let ctx = TraceContext::parse_b3_multi(
+            "463AC35C9F6413AD48485A3953BB6124",
+            "a2fb4a1d1a96d312",
+            Some("0"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(ctx.trace_id(), "463ac35c9f6413ad48485a3953bb6124");
+        assert!(!ctx.is_sampled());
+
+        // Debug flag overrides a deny decision
+        let ctx = TraceContext::parse_b3_multi(
+            "463ac35c9f6413ad",
+            "a2fb4a1d1a96d312",
+            Some("0"),
+            Some("1"),
+        )
+        .unwrap();
+        assert!(ctx.is_sampled());
+    }
+
+    #[test]
+    fn test_from_headers_b3_fallback() {
+        let ctx =
+            TraceContext::from_headers(&headers(&[("b3", "a3ce929d0e0e4736-00f067aa0ba902b7-1")]));
+        assert_eq!(ctx.trace_id(), "0000000000000000a3ce929d0e0e4736");
+
+        let ctx = TraceContext::from_headers(&headers(&[
+            ("x-b3-traceid", "463ac35c9f6413ad48485a3953bb6124"),
+            ("x-b3-spanid", "a2fb4a1d1a96d312"),
+        ]));
+        assert_eq!(ctx.trace_id(), "463ac35c9f6413ad48485a3953bb6124");
+        assert_eq!(ctx.parent_span_id(), Some("a2fb4a1d1a96d312"));
+    }
+
+    #[test]
+    fn test_from_headers_w3c_wins_over_b3() {
+        let ctx = TraceContext::from_headers(&headers(&[
+            (
+                "traceparent",
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            ),
+            ("b3", "a3ce929d0e0e4736-00f067aa0ba902b7-1"),
+        ]));
+        assert_eq!(ctx.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(ctx.parent_span_id(), Some("b7ad6b7169203331"));
+
+        // Invalid traceparent falls back to B3
+        let ctx = TraceContext::from_headers(&headers(&[
+            ("traceparent", "garbage"),
+            ("b3", "a3ce929d0e0e4736-00f067aa0ba902b7-1"),
+        ]));
+        assert_eq!(ctx.trace_id(), "0000000000000000a3ce929d0e0e4736");
+    }
 }
@@ -57,6 +57,45 @@ pub struct UploadedFile {
 // Script Request
 // =============================================================================
 
+/// Worker queue lane for a PHP request.
+///
+/// High-priority requests go to a small reserved lane that workers drain
+/// first, so they still get through when bulk traffic fills the queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Priority {
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    /// Middleware `Context` key: `ctx.set(Priority::CONTEXT_KEY, Priority::High)`.
+    pub const CONTEXT_KEY: &'static str = "priority";
+}
+
+/// Limits on a script's streamed output and on work after the response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputLimits {
+    /// Response chunks that may wait for a slow client (STREAM_BUFFER_SIZE).
+    pub stream_buffer_size: usize,
+    /// Give up on a client that makes no room in the buffer for this long
+    /// (STREAM_WRITE_TIMEOUT, None = wait forever).
+    pub stream_write_timeout: Option<Duration>,
+    /// Time a script may keep its worker after `tokio_finish_request()`
+    /// (MAX_BACKGROUND_TIME, None = unlimited).
+    pub max_background_time: Option<Duration>,
+}
+
+impl Default for OutputLimits {
+    fn default() -> Self {
+        Self {
+            stream_buffer_size: crate::executor::DEFAULT_STREAM_BUFFER_SIZE,
+            stream_write_timeout: Some(crate::executor::DEFAULT_STREAM_WRITE_TIMEOUT),
+            max_background_time: None,
+        }
+    }
+}
+
 /// Script execution request containing all HTTP request data.
 // Fields are only read by PHP executors (common.rs, ext.rs) which require the "php" feature.
 #[derive(Debug, Clone, Default)]
@@ -64,6 +103,9 @@ pub struct ScriptRequest {
     /// Path to the script file
     #[cfg_attr(not(feature = "php"), allow(dead_code))]
     pub script_path: String,
+    /// Built-in PHP code (no `<?php` tag) evaluated instead of `script_path`
+    #[cfg_attr(not(feature = "php"), allow(dead_code))]
+    pub inline_code: Option<&'static str>,
     /// GET parameters ($_GET)
     #[cfg_attr(not(feature = "php"), allow(dead_code))]
     pub get_params: ParamList,
@@ -100,6 +142,12 @@ pub struct ScriptRequest {
     /// W3C span ID (16 hex chars) for distributed tracing
     #[cfg_attr(not(feature = "php"), allow(dead_code))]
     pub span_id: String,
+    /// Worker queue lane
+    #[cfg_attr(not(feature = "php"), allow(dead_code))]
+    pub priority: Priority,
+    /// Streaming buffer and background-time limits
+    #[cfg_attr(not(feature = "php"), allow(dead_code))]
+    pub output_limits: OutputLimits,
 }
 
 // =============================================================================
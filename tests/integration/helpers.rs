@@ -40,6 +40,19 @@ impl TestServer {
             .expect("GET request failed")
     }
 
+    /// Make a GET request over HTTP/2 (h2c prior knowledge)
+    pub async fn get_http2(&self, path: &str) -> Response {
+        Client::builder()
+            .timeout(Duration::from_secs(30))
+            .http2_prior_knowledge()
+            .build()
+            .expect("Failed to create HTTP/2 client")
+            .get(format!("{}{}", self.base_url, path))
+            .send()
+            .await
+            .expect("HTTP/2 GET request failed")
+    }
+
     /// Make a GET request with custom headers
     pub async fn get_with_headers(&self, path: &str, headers: &[(&str, &str)]) -> Response {
         let mut req = self.client.get(format!("{}{}", self.base_url, path));
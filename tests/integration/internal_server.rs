@@ -197,3 +197,92 @@ async fn test_internal_server_isolation() {
     let resp = server.internal_get("/health").await;
     assert_status(&resp, StatusCode::OK);
 }
+
+/// Read a single unlabelled counter value from /metrics output
+fn metric_value(body: &str, name: &str) -> u64 {
+    body.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or_else(|| panic!("Metric '{}' not found", name))
+}
+
+/// Test connection lifecycle counters track accepts and protocol errors
+#[tokio::test]
+async fn test_metrics_connection_counters() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let server = TestServer::new();
+    let before = server.internal_get("/metrics").await.text().await.unwrap();
+    let accepted_before = metric_value(&before, "tokio_php_connections_accepted_total");
+    let protocol_before = metric_value(
+        &before,
+        "tokio_php_connections_closed_total{reason=\"protocol\"}",
+    );
+
+    // Garbage instead of an HTTP request (or a TLS ClientHello) is a protocol error
+    let addr = server
+        .base_url
+        .trim_start_matches("http://")
+        .trim_start_matches("https://")
+        .to_string();
+    let mut stream = tokio::net::TcpStream::connect(&addr).await.unwrap();
+    stream.write_all(b"\x00\x01garbage\r\n\r\n").await.unwrap();
+    let mut buf = Vec::new();
+    let _ = stream.read_to_end(&mut buf).await;
+    drop(stream);
+
+    // A regular request on a fresh connection
+    let _ = server.get("/").await;
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let after = server.internal_get("/metrics").await.text().await.unwrap();
+    assert!(metric_value(&after, "tokio_php_connections_accepted_total") >= accepted_before + 2);
+    assert!(
+        metric_value(
+            &after,
+            "tokio_php_connections_closed_total{reason=\"protocol\"}"
+        ) > protocol_before
+    );
+    assert!(after.contains("tokio_php_tls_handshakes_total{result=\"failed\"}"));
+}
+
+/// Test kill-switch disables a path prefix and re-enables it
+#[tokio::test]
+async fn test_kill_switch_toggle() {
+    let server = TestServer::new();
+    let url = format!("{}/kill-switch", server.internal_url);
+
+    let resp = server
+        .client
+        .post(format!("{}?disable=/kill-switch-test", url))
+        .send()
+        .await
+        .unwrap();
+    assert_status(&resp, StatusCode::OK);
+    let state: serde_json::Value = resp.json().await.unwrap();
+    let disabled_status = StatusCode::from_u16(state["status"].as_u64().unwrap() as u16).unwrap();
+    assert!(state["disabled"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|p| p == "/kill-switch-test"));
+
+    // Disabled prefix returns the configured status without running PHP
+    let resp = server.get("/kill-switch-test/index.php").await;
+    assert_status(&resp, disabled_status);
+
+    // Other paths keep working
+    let resp = server.get("/index.php").await;
+    assert_status(&resp, StatusCode::OK);
+
+    let resp = server
+        .client
+        .post(format!("{}?enable=/kill-switch-test", url))
+        .send()
+        .await
+        .unwrap();
+    assert_status(&resp, StatusCode::OK);
+
+    let resp = server.get("/kill-switch-test/index.php").await;
+    assert_ne!(resp.status(), disabled_status);
+}
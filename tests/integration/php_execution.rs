@@ -44,6 +44,132 @@ async fn test_server_superglobal() {
     );
 }
 
+/// Value of a `NAME = value` line from server_vars.php.
+fn server_var<'a>(body: &'a str, name: &str) -> &'a str {
+    body.lines()
+        .find_map(|l| l.strip_prefix(name)?.trim_start().strip_prefix("= "))
+        .unwrap_or_else(|| panic!("{} line missing", name))
+}
+
+/// Test the CSP header carries the same nonce PHP sees, fresh per request.
+/// The default server runs with CSP_POLICY="script-src 'nonce-{{nonce}}'".
+#[tokio::test]
+async fn test_csp_nonce_matches_server_var() {
+    let server = TestServer::new();
+    let mut nonces = Vec::new();
+    for _ in 0..2 {
+        let resp = server.get("/server_vars.php").await;
+        assert_status(&resp, StatusCode::OK);
+        let csp = resp
+            .headers()
+            .get("content-security-policy")
+            .expect("Content-Security-Policy header")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = resp.text().await.unwrap();
+        let nonce = server_var(&body, "TOKIO_CSP_NONCE").to_string();
+
+        assert_eq!(csp, format!("script-src 'nonce-{}'", nonce));
+        nonces.push(nonce);
+    }
+    assert_ne!(nonces[0], nonces[1]);
+
+    // Static files get no nonce
+    let resp = server.get("/styles.css").await;
+    assert!(resp.headers().get("content-security-policy").is_none());
+}
+
+/// Test X-Forwarded-Host from an untrusted peer is ignored.
+/// The default server trusts no proxies (TRUSTED_PROXIES empty).
+#[tokio::test]
+async fn test_x_forwarded_host_untrusted() {
+    let server = TestServer::new();
+    let resp = server
+        .get_with_headers("/server_vars.php", &[("X-Forwarded-Host", "evil.example")])
+        .await;
+
+    assert_status(&resp, StatusCode::OK);
+    let body = resp.text().await.unwrap();
+    assert_ne!(server_var(&body, "SERVER_NAME"), "evil.example");
+    assert_ne!(server_var(&body, "HTTP_HOST"), "evil.example");
+    assert_eq!(server_var(&body, "HTTP_X_FORWARDED_HOST"), "(not set)");
+}
+
+/// Test X-Forwarded-Host from a trusted proxy sets SERVER_NAME and HTTP_HOST.
+/// Requires the TLS variant (TRUSTED_PROXIES=0.0.0.0/0) and TEST_TLS_URL.
+#[tokio::test]
+async fn test_x_forwarded_host_trusted() {
+    let Ok(tls_url) = std::env::var("TEST_TLS_URL") else {
+        eprintln!("TEST_TLS_URL not set, skipping");
+        return;
+    };
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let resp = client
+        .get(format!("{}/server_vars.php", tls_url.trim_end_matches('/')))
+        .header("X-Forwarded-Host", "shop.example.com:8443, edge.internal")
+        .send()
+        .await
+        .unwrap();
+
+    assert_status(&resp, StatusCode::OK);
+    let body = resp.text().await.unwrap();
+    assert_eq!(server_var(&body, "SERVER_NAME"), "shop.example.com");
+    assert_eq!(server_var(&body, "SERVER_PORT"), "8443");
+    assert_eq!(server_var(&body, "HTTP_HOST"), "shop.example.com:8443");
+    assert_eq!(
+        server_var(&body, "HTTP_X_FORWARDED_HOST"),
+        "shop.example.com:8443, edge.internal"
+    );
+}
+
+/// Test SNI from the TLS handshake is exposed as SSL_SERVER_NAME.
+/// Requires the TLS variant (`docker compose up tokio_php_tls`) and TEST_TLS_URL.
+#[tokio::test]
+async fn test_tls_sni_server_var() {
+    let Ok(tls_url) = std::env::var("TEST_TLS_URL") else {
+        eprintln!("TEST_TLS_URL not set, skipping");
+        return;
+    };
+    let url = reqwest::Url::parse(&tls_url).unwrap();
+    let addr = format!("{}:{}", url.host_str().unwrap(), url.port().unwrap_or(443));
+    let addr = tokio::net::lookup_host(&addr)
+        .await
+        .unwrap()
+        .next()
+        .unwrap();
+
+    // Resolve a custom hostname to the server so the client sends it as SNI
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .resolve("sni.tokio-php.test", addr)
+        .build()
+        .unwrap();
+    let resp = client
+        .get(format!(
+            "https://sni.tokio-php.test:{}/server_vars.php",
+            addr.port()
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_status(&resp, StatusCode::OK);
+    let body = resp.text().await.unwrap();
+    let sni_line = body
+        .lines()
+        .find(|l| l.starts_with("SSL_SERVER_NAME"))
+        .expect("SSL_SERVER_NAME line");
+    assert!(
+        sni_line.ends_with("= sni.tokio-php.test"),
+        "unexpected: {}",
+        sni_line
+    );
+}
+
 /// Test $_COOKIE superglobal
 #[tokio::test]
 async fn test_cookie_superglobal() {
@@ -68,6 +194,32 @@ async fn test_php_set_cookie() {
     assert_has_header(&resp, "set-cookie");
 }
 
+/// Test that each setcookie() arrives as a separate Set-Cookie header
+#[tokio::test]
+async fn test_php_multiple_set_cookies() {
+    let server = TestServer::new();
+
+    for resp in [
+        server.get("/test_set_cookies.php").await,
+        server.get_http2("/test_set_cookies.php").await,
+    ] {
+        assert_status(&resp, StatusCode::OK);
+        let cookies: Vec<_> = resp
+            .headers()
+            .get_all("set-cookie")
+            .iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect();
+        assert_eq!(cookies.len(), 3, "{:?}: {:?}", resp.version(), cookies);
+        for (cookie, name) in cookies.iter().zip(["first=1", "second=2", "third=3"]) {
+            assert!(cookie.starts_with(name), "unexpected cookie: {}", cookie);
+        }
+    }
+
+    let resp = server.get_http2("/test_set_cookies.php").await;
+    assert_eq!(resp.version(), reqwest::Version::HTTP_2);
+}
+
 /// Test PHP output (bench.php returns "ok")
 #[tokio::test]
 async fn test_php_output() {
@@ -261,3 +413,37 @@ async fn test_request_time() {
     let is_valid = data["is_valid"].as_bool().unwrap_or(false);
     assert!(is_valid, "PHP validation failed: {}", body);
 }
+
+/// Test a JSON POST reaches PHP through php://input, not $_POST
+#[tokio::test]
+async fn test_json_body_in_php_input() {
+    let server = TestServer::new();
+    let payload = serde_json::json!({"name": "JsonTest", "tags": ["a", "b"], "count": 3});
+    let resp = server.post_json("/test_json_input.php", &payload).await;
+
+    assert_status(&resp, StatusCode::OK);
+    let data: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(data["input"], payload);
+    assert_eq!(data["post"], serde_json::json!([]));
+    assert_eq!(data["content_type"], "application/json");
+
+    let sent = serde_json::to_string(&payload).unwrap().len();
+    assert_eq!(data["raw_length"], sent);
+    assert_eq!(data["content_length"], sent.to_string());
+}
+
+/// Test PHP error ini settings from config are applied at startup.
+/// docker-compose sets PHP_LOG_ERRORS=1 and PHP_ERROR_REPORTING=E_ALL.
+#[tokio::test]
+async fn test_php_error_ini_from_config() {
+    let server = TestServer::new();
+    let resp = server.get("/error_ini.php").await;
+
+    assert_status(&resp, StatusCode::OK);
+    let ini: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(ini["log_errors"], "1");
+    assert_eq!(
+        ini["error_reporting"].as_str().unwrap(),
+        ini["E_ALL"].to_string()
+    );
+}
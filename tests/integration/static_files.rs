@@ -24,6 +24,126 @@ async fn test_static_file_cache_headers() {
     assert_has_header(&resp, "cache-control");
 }
 
+/// Test Timing-Allow-Origin lists the configured origins on static files only
+/// (docker-compose sets TIMING_ALLOW_ORIGIN_SCOPE=static)
+#[tokio::test]
+async fn test_timing_allow_origin() {
+    let server = TestServer::new();
+    let resp = server.get("/styles.css").await;
+
+    assert_status(&resp, StatusCode::OK);
+    assert_header(
+        &resp,
+        "timing-allow-origin",
+        "https://app.example.com, http://localhost:3000",
+    );
+
+    let resp = server.get("/hello.php").await;
+    assert_status(&resp, StatusCode::OK);
+    assert!(resp.headers().get("timing-allow-origin").is_none());
+}
+
+/// Test fingerprinted assets get an immutable one-year cache (STATIC_IMMUTABLE)
+#[tokio::test]
+async fn test_fingerprinted_asset_immutable() {
+    let server = TestServer::new();
+    let resp = server.get("/app.3f9a2c1d.js").await;
+
+    assert_status(&resp, StatusCode::OK);
+    assert_header(
+        &resp,
+        "cache-control",
+        "public, max-age=31536000, immutable",
+    );
+
+    // Non-fingerprinted assets keep the default TTL
+    let resp = server.get("/styles.css").await;
+    let cache_control = resp.headers()["cache-control"].to_str().unwrap();
+    assert!(!cache_control.contains("immutable"));
+}
+
+/// Test device variant selection (STATIC_UA_VARIANTS=mobile)
+#[tokio::test]
+async fn test_mobile_user_agent_variant() {
+    let server = TestServer::new();
+    let iphone = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) Mobile/15E148";
+
+    let resp = server
+        .get_with_headers("/device/page.html", &[("User-Agent", iphone)])
+        .await;
+    assert_status(&resp, StatusCode::OK);
+    assert_header(&resp, "vary", "User-Agent");
+    assert!(resp.text().await.unwrap().contains("mobile"));
+
+    let resp = server
+        .get_with_headers("/device/page.html", &[("User-Agent", "curl/8.5.0")])
+        .await;
+    assert_status(&resp, StatusCode::OK);
+    assert_header(&resp, "vary", "User-Agent");
+    assert!(resp.text().await.unwrap().contains("desktop"));
+
+    // No variant on disk: original file, response doesn't vary
+    let resp = server
+        .get_with_headers("/styles.css", &[("User-Agent", iphone)])
+        .await;
+    assert_status(&resp, StatusCode::OK);
+    assert!(resp.headers().get("vary").is_none());
+}
+
+/// Test Link rel=canonical for a configured path (CANONICAL_LINKS)
+#[tokio::test]
+async fn test_canonical_link_header() {
+    let server = TestServer::new();
+
+    let resp = server.get("/device/page.html").await;
+    assert_status(&resp, StatusCode::OK);
+    assert_header(
+        &resp,
+        "link",
+        "<https://example.com/page>; rel=\"canonical\"",
+    );
+
+    let resp = server.get("/styles.css").await;
+    assert!(resp.headers().get("link").is_none());
+}
+
+/// Test built-in Content-Type override for .mjs (mime_guess says application/javascript)
+#[tokio::test]
+async fn test_mjs_content_type_override() {
+    let server = TestServer::new();
+    let resp = server.get("/module.mjs").await;
+
+    assert_status(&resp, StatusCode::OK);
+    assert_header_starts_with(&resp, "content-type", "text/javascript");
+}
+
+/// Test directory listing under an AUTOINDEX prefix
+#[tokio::test]
+async fn test_directory_listing() {
+    let server = TestServer::new();
+    let resp = server.get("/listing/").await;
+
+    assert_status(&resp, StatusCode::OK);
+    assert_header_starts_with(&resp, "content-type", "text/html");
+    let body = resp.text().await.unwrap();
+    assert!(body.contains("Index of /listing/"));
+    assert!(body.contains("<a href=\"readme.txt\">readme.txt</a>"));
+}
+
+/// Test directory URI without trailing slash is redirected (DIRECTORY_REQUEST=redirect)
+#[tokio::test]
+async fn test_directory_without_slash_redirects() {
+    let server = TestServer::new();
+    // reqwest follows the 301
+    let resp = server.get("/listing?sort=name").await;
+
+    assert_status(&resp, StatusCode::OK);
+    assert_eq!(resp.url().path(), "/listing/");
+    assert_eq!(resp.url().query(), Some("sort=name"));
+    let body = resp.text().await.unwrap();
+    assert!(body.contains("Index of /listing/"));
+}
+
 /// Test ETag header on static files
 #[tokio::test]
 async fn test_static_file_etag() {
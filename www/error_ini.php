@@ -0,0 +1,9 @@
+<?php
+header('Content-Type: application/json');
+
+echo json_encode([
+    'display_errors' => ini_get('display_errors'),
+    'log_errors' => ini_get('log_errors'),
+    'error_reporting' => ini_get('error_reporting'),
+    'E_ALL' => E_ALL,
+]);
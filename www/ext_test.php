@@ -5,6 +5,9 @@ $functions = [
     'tokio_worker_id' => function_exists('tokio_worker_id'),
     'tokio_server_info' => function_exists('tokio_server_info'),
     'tokio_request_heartbeat' => function_exists('tokio_request_heartbeat'),
+    'tokio_is_draining' => function_exists('tokio_is_draining'),
+    'tokio_http_response_code' => function_exists('tokio_http_response_code'),
+    'tokio_sse_event' => function_exists('tokio_sse_event'),
 ];
 $serverVersion = $_SERVER['TOKIO_SERVER_BUILD_VERSION'] ?? null;
 $serverInfo = function_exists('tokio_server_info') ? tokio_server_info() : null;
@@ -16,10 +16,12 @@ $vars = [
     'HTTP_REFERER',
     'HTTP_ACCEPT_LANGUAGE',
     'HTTP_HOST',
+    'HTTP_X_FORWARDED_HOST',
     'HTTP_ACCEPT',
     'HTTP_COOKIE',
     'HTTPS',
     'SSL_PROTOCOL',
+    'SSL_SERVER_NAME',
     'REQUEST_METHOD',
     'REQUEST_URI',
     'QUERY_STRING',
@@ -29,6 +31,7 @@ $vars = [
     'SERVER_PROTOCOL',
     'CONTENT_TYPE',
     'GATEWAY_INTERFACE',
+    'TOKIO_CSP_NONCE',
 ];
 
 foreach ($vars as $var) {
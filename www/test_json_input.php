@@ -0,0 +1,19 @@
+<?php
+/**
+ * Test script for JSON request bodies
+ *
+ * Echoes what PHP received: the decoded php://input, $_POST (empty for
+ * non-form bodies) and the content headers from $_SERVER.
+ */
+
+header('Content-Type: application/json');
+
+$raw = file_get_contents('php://input');
+
+echo json_encode([
+    'input' => json_decode($raw, true),
+    'raw_length' => strlen($raw),
+    'post' => $_POST,
+    'content_type' => $_SERVER['CONTENT_TYPE'] ?? null,
+    'content_length' => $_SERVER['CONTENT_LENGTH'] ?? null,
+]);
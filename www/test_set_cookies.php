@@ -0,0 +1,15 @@
+<?php
+/**
+ * Test: several cookies in one response.
+ *
+ * Each setcookie() must arrive as its own Set-Cookie line (the Expires
+ * date contains a comma, so the values can't be merged).
+ *
+ * Run: curl -si http://localhost:8080/test_set_cookies.php
+ *      curl -si --http2-prior-knowledge http://localhost:8080/test_set_cookies.php
+ */
+setcookie('first', '1', time() + 3600, '/');
+setcookie('second', '2', ['path' => '/', 'httponly' => true]);
+setcookie('third', '3', ['path' => '/', 'samesite' => 'Lax']);
+
+echo "ok";